@@ -0,0 +1,114 @@
+//! Support for the Captive Portal API, as per RFC 8908, and for announcing it, as per RFC 8910.
+//!
+//! Clients supporting the API learn its URL from the DHCP option 114 (see `edge_dhcp::server::ServerOptions::captive_url`)
+//! or from the IPv6 Router Advertisement option 37 (see `ra_option`), and then query it for the state
+//! of their captivity, rather than relying on probing well-known URLs.
+//!
+//! Note that RFC 8908 requires the API URL to be an HTTPS one.
+
+use core::fmt::{self, Display, Write};
+
+/// The content type of the Captive Portal API responses
+pub const CONTENT_TYPE: &str = "application/captive+json";
+
+/// The type of the IPv6 Router Advertisement option carrying the URL of the Captive Portal API
+pub const RA_OPTION_TYPE: u8 = 37;
+
+/// The state of the captivity of a client, as reported by the Captive Portal API
+///
+/// Displays as the JSON object expected by the clients.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct CaptiveStatus<'a> {
+    /// Whether the client is (still) captive
+    pub captive: bool,
+    /// The URL of the web portal the user should interact with
+    pub user_portal_url: Option<&'a str>,
+    /// The URL of a web page with information about the venue
+    pub venue_info_url: Option<&'a str>,
+    /// Whether the client can extend its session via the portal
+    pub can_extend_session: Option<bool>,
+    /// The number of seconds remaining until the session of the client expires
+    pub seconds_remaining: Option<u64>,
+    /// The number of bytes remaining until the session of the client expires
+    pub bytes_remaining: Option<u64>,
+}
+
+impl<'a> CaptiveStatus<'a> {
+    /// Create a new status with the provided captivity state and portal URL, and with no other information
+    pub const fn new(captive: bool, user_portal_url: Option<&'a str>) -> Self {
+        Self {
+            captive,
+            user_portal_url,
+            venue_info_url: None,
+            can_extend_session: None,
+            seconds_remaining: None,
+            bytes_remaining: None,
+        }
+    }
+}
+
+impl Display for CaptiveStatus<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{\"captive\":{}", self.captive)?;
+
+        if let Some(url) = self.user_portal_url {
+            write!(f, ",\"user-portal-url\":\"{}\"", JsonStr(url))?;
+        }
+
+        if let Some(url) = self.venue_info_url {
+            write!(f, ",\"venue-info-url\":\"{}\"", JsonStr(url))?;
+        }
+
+        if let Some(can_extend_session) = self.can_extend_session {
+            write!(f, ",\"can-extend-session\":{can_extend_session}")?;
+        }
+
+        if let Some(seconds) = self.seconds_remaining {
+            write!(f, ",\"seconds-remaining\":{seconds}")?;
+        }
+
+        if let Some(bytes) = self.bytes_remaining {
+            write!(f, ",\"bytes-remaining\":{bytes}")?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+/// Encode the IPv6 Router Advertisement option announcing the provided Captive Portal API URL into `buf`
+///
+/// As per RFC 8910, the URL is padded with zeroes up to a multiple of 8 octets.
+///
+/// Returns `None` if the buffer is too small, or if the URL is too long to fit in the option.
+pub fn ra_option<'o>(url: &str, buf: &'o mut [u8]) -> Option<&'o [u8]> {
+    let len = (2 + url.len()).div_ceil(8) * 8;
+
+    if len / 8 > u8::MAX as usize || buf.len() < len {
+        return None;
+    }
+
+    buf[0] = RA_OPTION_TYPE;
+    buf[1] = (len / 8) as u8;
+    buf[2..2 + url.len()].copy_from_slice(url.as_bytes());
+    buf[2 + url.len()..len].fill(0);
+
+    Some(&buf[..len])
+}
+
+struct JsonStr<'a>(&'a str);
+
+impl Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+//! `edge-http` handlers for the HTTP side of a captive portal.
+
+use core::fmt::{self, Debug, Display, Write as _};
+
+use edge_http::io::server::{Connection, Handler, Matcher};
+use edge_http::io::Error;
+use edge_http::{Method, RequestHeaders};
+
+use edge_nal::io::{Read, Write};
+use edge_nal::{TcpSplit, WriteVectored};
+
+use crate::api::{CaptiveStatus, CONTENT_TYPE};
+
+/// The default path of the Captive Portal API
+pub const DEFAULT_API_PATH: &str = "/captive-portal/api";
+
+/// A handler implementing the Captive Portal API, as per RFC 8908
+///
+/// The handler answers GET requests for its path with the status returned by the `status` callback,
+/// which is expected to look up the captivity state of the client.
+///
+/// Since the handler is also a `Matcher` (matching the requests for its path), it can be used as the primary handler
+/// of an `edge_http::io::server::Fallback`, with the portal itself as the fallback handler.
+///
+/// `B` is the maximum size of the JSON status object.
+pub struct CaptivePortalApi<'a, F, const B: usize = 512> {
+    path: &'a str,
+    status: F,
+}
+
+impl<'a, F, const B: usize> CaptivePortalApi<'a, F, B>
+where
+    F: Fn() -> CaptiveStatus<'a>,
+{
+    /// Create a new handler serving the API on `DEFAULT_API_PATH`
+    pub const fn new(status: F) -> Self {
+        Self::new_with_path(DEFAULT_API_PATH, status)
+    }
+
+    /// Create a new handler serving the API on the provided path
+    pub const fn new_with_path(path: &'a str, status: F) -> Self {
+        Self { path, status }
+    }
+}
+
+impl<'a, F, const B: usize> Matcher for CaptivePortalApi<'a, F, B>
+where
+    F: Fn() -> CaptiveStatus<'a>,
+{
+    fn matches<const N: usize>(&self, headers: &RequestHeaders<'_, N>) -> bool {
+        headers.path == self.path
+    }
+}
+
+impl<'a, F, const B: usize> Handler for CaptivePortalApi<'a, F, B>
+where
+    F: Fn() -> CaptiveStatus<'a>,
+{
+    type Error<E>
+        = Error<E>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        _task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + WriteVectored + TcpSplit,
+    {
+        if connection.headers()?.method != Method::Get {
+            connection
+                .initiate_response(405, Some("Method Not Allowed"), &[("Allow", "GET")])
+                .await?;
+
+            return Ok(());
+        }
+
+        let mut buf = Buf([0; B], 0);
+
+        if write!(&mut buf, "{}", (self.status)()).is_err() {
+            connection
+                .initiate_response(500, Some("Internal Server Error"), &[])
+                .await?;
+
+            return Ok(());
+        }
+
+        let body = &buf.0[..buf.1];
+
+        let mut len = Buf([0; 20], 0);
+        write!(&mut len, "{}", body.len()).unwrap();
+
+        connection
+            .initiate_response(
+                200,
+                Some("OK"),
+                &[
+                    ("Content-Type", CONTENT_TYPE),
+                    ("Content-Length", len.as_str()),
+                    // As per RFC 8908, the status is specific to the client
+                    ("Cache-Control", "private"),
+                ],
+            )
+            .await?;
+
+        connection.write_all(body).await?;
+
+        Ok(())
+    }
+}
+
+/// A well-known URL path probed by an OS (or a browser) to detect captive portals,
+/// along with the response expected when there is no captive portal
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Probe {
+    /// The probed path
+    pub path: &'static str,
+    /// The status of the expected response
+    pub status: u16,
+    /// The content type of the expected response, if it has a body
+    pub content_type: Option<&'static str>,
+    /// The body of the expected response
+    pub body: &'static str,
+}
+
+/// The captive portal probes of the major operating systems and browsers
+pub const PROBES: &[Probe] = &[
+    // Android, ChromeOS
+    Probe {
+        path: "/generate_204",
+        status: 204,
+        content_type: None,
+        body: "",
+    },
+    Probe {
+        path: "/gen_204",
+        status: 204,
+        content_type: None,
+        body: "",
+    },
+    // iOS, macOS
+    Probe {
+        path: "/hotspot-detect.html",
+        status: 200,
+        content_type: Some("text/html"),
+        body: "<HTML><HEAD><TITLE>Success</TITLE></HEAD><BODY>Success</BODY></HTML>",
+    },
+    Probe {
+        path: "/library/test/success.html",
+        status: 200,
+        content_type: Some("text/html"),
+        body: "<HTML><HEAD><TITLE>Success</TITLE></HEAD><BODY>Success</BODY></HTML>",
+    },
+    // Windows
+    Probe {
+        path: "/ncsi.txt",
+        status: 200,
+        content_type: Some("text/plain"),
+        body: "Microsoft NCSI",
+    },
+    Probe {
+        path: "/connecttest.txt",
+        status: 200,
+        content_type: Some("text/plain"),
+        body: "Microsoft Connect Test",
+    },
+    // Firefox
+    Probe {
+        path: "/canonical.html",
+        status: 200,
+        content_type: Some("text/html"),
+        body: "<meta http-equiv=\"refresh\" content=\"0;url=https://support.mozilla.org/kb/captive-portal\"/>",
+    },
+    Probe {
+        path: "/success.txt",
+        status: 200,
+        content_type: Some("text/plain"),
+        body: "success\n",
+    },
+];
+
+impl Probe {
+    /// Return the probe for the provided request path (ignoring the query, if any)
+    pub fn find(path: &str) -> Option<&'static Probe> {
+        let path = path.split('?').next().unwrap_or(path);
+
+        PROBES.iter().find(|probe| probe.path == path)
+    }
+}
+
+/// A handler answering the captive portal probes of the major operating systems and browsers (see `PROBES`)
+///
+/// While the `captive` callback returns `true`, the probes are redirected to the portal URL,
+/// which makes the OS show its captive portal sign-in UI. Once it returns `false` (e.g. the user
+/// accepted the terms of the portal), the probes get the responses the OS expects when there is no captive portal.
+///
+/// Since the handler is also a `Matcher` (matching the probe paths only), it can be used as the primary handler
+/// of an `edge_http::io::server::Fallback`, with the portal itself as the fallback handler.
+pub struct ConnectivityCheck<'a, F> {
+    portal_url: &'a str,
+    captive: F,
+}
+
+impl<'a, F> ConnectivityCheck<'a, F>
+where
+    F: Fn() -> bool,
+{
+    /// Create a new handler redirecting the probes to the provided portal URL while `captive` returns `true`
+    pub const fn new(portal_url: &'a str, captive: F) -> Self {
+        Self {
+            portal_url,
+            captive,
+        }
+    }
+}
+
+impl<F> Matcher for ConnectivityCheck<'_, F>
+where
+    F: Fn() -> bool,
+{
+    fn matches<const N: usize>(&self, headers: &RequestHeaders<'_, N>) -> bool {
+        Probe::find(headers.path).is_some()
+    }
+}
+
+impl<F> Handler for ConnectivityCheck<'_, F>
+where
+    F: Fn() -> bool,
+{
+    type Error<E>
+        = Error<E>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        _task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + WriteVectored + TcpSplit,
+    {
+        let Some(probe) = Probe::find(connection.headers()?.path) else {
+            connection
+                .initiate_response(404, Some("Not Found"), &[])
+                .await?;
+
+            return Ok(());
+        };
+
+        // The OSes retry the probes every now and then, so these should never be cached
+        const NO_CACHE: (&str, &str) = ("Cache-Control", "no-cache, no-store, must-revalidate");
+
+        if (self.captive)() {
+            connection
+                .initiate_response(
+                    302,
+                    Some("Found"),
+                    &[
+                        ("Location", self.portal_url),
+                        NO_CACHE,
+                        ("Content-Length", "0"),
+                    ],
+                )
+                .await?;
+        } else {
+            let mut len = Buf([0; 20], 0);
+            write!(&mut len, "{}", probe.body.len()).unwrap();
+
+            let content_type = ("Content-Type", probe.content_type.unwrap_or("text/plain"));
+            let content_length = ("Content-Length", len.as_str());
+
+            let headers = if probe.content_type.is_some() {
+                &[NO_CACHE, content_type, content_length][..]
+            } else {
+                &[NO_CACHE, content_length][..]
+            };
+
+            connection
+                .initiate_response(probe.status, None, headers)
+                .await?;
+
+            connection.write_all(probe.body.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Buf<const B: usize>([u8; B], usize);
+
+impl<const B: usize> Buf<B> {
+    fn as_str(&self) -> &str {
+        // Only ever written to via `fmt::Write`, so always valid UTF-8
+        core::str::from_utf8(&self.0[..self.1]).unwrap()
+    }
+}
+
+impl<const B: usize> fmt::Write for Buf<B> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.1 + s.len();
+
+        if end > B {
+            return Err(fmt::Error);
+        }
+
+        self.0[self.1..end].copy_from_slice(s.as_bytes());
+        self.1 = end;
+
+        Ok(())
+    }
+}
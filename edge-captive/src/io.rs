@@ -1,9 +1,16 @@
-use core::fmt;
+use core::cell::RefCell;
+use core::fmt::{self, Write as _};
 use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use core::time::Duration;
 
+use domain::base::iana::Rcode;
+use domain::base::name::ParsedName;
+use domain::base::Message;
+
 use edge_nal::{UdpBind, UdpReceive, UdpSend};
 
+use embassy_sync::blocking_mutex::{self, raw::RawMutex};
+
 use log::*;
 
 use super::*;
@@ -53,6 +60,273 @@ where
 #[cfg(feature = "std")]
 impl<E> std::error::Error for DnsIoError<E> where E: std::error::Error {}
 
+/// A trait for observing the activity of the captive portal DNS runners.
+///
+/// Useful for showing in the device UI what the clients are trying to reach during onboarding.
+///
+/// All methods have a default no-op implementation, so implementors only need to
+/// override the notifications they are interested in.
+pub trait DnsObserver {
+    /// Called for the (first) question of each incoming query
+    fn query(&self, _name: &ParsedName<&[u8]>, _rtype: Rtype, _remote: SocketAddr) {}
+
+    /// Called after the query from `remote` was replied with the provided rcode,
+    /// or - if `rcode` is `None` - after the query was ignored (i.e. not replied at all)
+    fn reply(&self, _remote: SocketAddr, _rcode: Option<Rcode>) {}
+
+    /// Called after the query from `remote` was forwarded to the upstream DNS server
+    fn forward(&self, _remote: SocketAddr) {}
+
+    /// Called when an incoming packet from `remote` was dropped, because it was malformed
+    fn malformed(&self, _remote: SocketAddr) {}
+}
+
+impl<T> DnsObserver for &T
+where
+    T: DnsObserver,
+{
+    fn query(&self, name: &ParsedName<&[u8]>, rtype: Rtype, remote: SocketAddr) {
+        (*self).query(name, rtype, remote)
+    }
+
+    fn reply(&self, remote: SocketAddr, rcode: Option<Rcode>) {
+        (*self).reply(remote, rcode)
+    }
+
+    fn forward(&self, remote: SocketAddr) {
+        (*self).forward(remote)
+    }
+
+    fn malformed(&self, remote: SocketAddr) {
+        (*self).malformed(remote)
+    }
+}
+
+/// A `DnsObserver` that ignores all notifications.
+pub struct NoObserver;
+
+impl DnsObserver for NoObserver {}
+
+/// Statistics about the activity of the captive portal DNS, as collected by `StatsObserver`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DnsStats {
+    /// The number of A queries received
+    pub a_queries: u32,
+    /// The number of AAAA queries received
+    pub aaaa_queries: u32,
+    /// The number of queries of all other types received
+    pub other_queries: u32,
+    /// The number of queries replied with NOERROR (with or without answers)
+    pub answered: u32,
+    /// The number of queries replied with NXDOMAIN
+    pub nxdomain: u32,
+    /// The number of queries replied with REFUSED
+    pub refused: u32,
+    /// The number of queries replied with other rcodes (e.g. NOTIMP)
+    pub failed: u32,
+    /// The number of queries which were not replied at all
+    pub ignored: u32,
+    /// The number of queries forwarded to the upstream DNS server
+    pub forwarded: u32,
+    /// The number of incoming packets dropped, because they were malformed
+    pub malformed: u32,
+}
+
+/// A `DnsObserver` that counts the activity of the captive portal DNS,
+/// and keeps track of the `N` most recently queried distinct names.
+///
+/// Names longer than `L` bytes are truncated.
+pub struct StatsObserver<M, const N: usize = 8, const L: usize = 64>(
+    blocking_mutex::Mutex<M, RefCell<State<N, L>>>,
+)
+where
+    M: RawMutex;
+
+impl<M, const N: usize, const L: usize> StatsObserver<M, N, L>
+where
+    M: RawMutex,
+{
+    const NAME: QueriedName<L> = QueriedName::new();
+
+    /// Create a new `StatsObserver` instance with all counters set to zero and no names.
+    pub const fn new() -> Self {
+        Self(blocking_mutex::Mutex::new(RefCell::new(State {
+            stats: DnsStats {
+                a_queries: 0,
+                aaaa_queries: 0,
+                other_queries: 0,
+                answered: 0,
+                nxdomain: 0,
+                refused: 0,
+                failed: 0,
+                ignored: 0,
+                forwarded: 0,
+                malformed: 0,
+            },
+            names: [Self::NAME; N],
+            next: 0,
+        })))
+    }
+
+    /// Get a snapshot of the collected statistics.
+    pub fn stats(&self) -> DnsStats {
+        self.0.lock(|state| state.borrow().stats)
+    }
+
+    /// Call `f` with each of the recently queried names and the number of times it was queried,
+    /// starting with the most queried one.
+    pub fn names<F>(&self, mut f: F)
+    where
+        F: FnMut(&str, u32),
+    {
+        self.0.lock(|state| {
+            let mut names = state.borrow().names;
+            names.sort_unstable_by_key(|name| core::cmp::Reverse(name.count));
+
+            for name in names.iter().filter(|name| name.count > 0) {
+                f(name.as_str(), name.count);
+            }
+        })
+    }
+
+    /// Reset all counters to zero and forget all names.
+    pub fn reset(&self) {
+        self.0.lock(|state| {
+            let mut state = state.borrow_mut();
+
+            state.stats = DnsStats::default();
+            state.names = [Self::NAME; N];
+            state.next = 0;
+        });
+    }
+
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut DnsStats),
+    {
+        self.0.lock(|state| f(&mut state.borrow_mut().stats));
+    }
+}
+
+impl<M, const N: usize, const L: usize> Default for StatsObserver<M, N, L>
+where
+    M: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M, const N: usize, const L: usize> DnsObserver for StatsObserver<M, N, L>
+where
+    M: RawMutex,
+{
+    fn query(&self, name: &ParsedName<&[u8]>, rtype: Rtype, _remote: SocketAddr) {
+        let mut queried = QueriedName::<L>::new();
+        let _ = write!(&mut queried, "{name}");
+
+        self.0.lock(|state| {
+            let mut state = state.borrow_mut();
+            let state = &mut *state;
+
+            let counter = match rtype {
+                Rtype::A => &mut state.stats.a_queries,
+                Rtype::AAAA => &mut state.stats.aaaa_queries,
+                _ => &mut state.stats.other_queries,
+            };
+
+            *counter = counter.wrapping_add(1);
+
+            if N == 0 {
+                return;
+            }
+
+            if let Some(known) = state
+                .names
+                .iter_mut()
+                .find(|known| known.count > 0 && known.as_str() == queried.as_str())
+            {
+                known.count = known.count.saturating_add(1);
+            } else {
+                queried.count = 1;
+
+                state.names[state.next] = queried;
+                state.next = (state.next + 1) % N;
+            }
+        });
+    }
+
+    fn reply(&self, _remote: SocketAddr, rcode: Option<Rcode>) {
+        self.update(|stats| {
+            let counter = match rcode {
+                None => &mut stats.ignored,
+                Some(rcode) if rcode == Rcode::NOERROR => &mut stats.answered,
+                Some(rcode) if rcode == Rcode::NXDOMAIN => &mut stats.nxdomain,
+                Some(rcode) if rcode == Rcode::REFUSED => &mut stats.refused,
+                Some(_) => &mut stats.failed,
+            };
+
+            *counter = counter.wrapping_add(1);
+        });
+    }
+
+    fn forward(&self, _remote: SocketAddr) {
+        self.update(|stats| stats.forwarded = stats.forwarded.wrapping_add(1));
+    }
+
+    fn malformed(&self, _remote: SocketAddr) {
+        self.update(|stats| stats.malformed = stats.malformed.wrapping_add(1));
+    }
+}
+
+struct State<const N: usize, const L: usize> {
+    stats: DnsStats,
+    names: [QueriedName<L>; N],
+    next: usize,
+}
+
+#[derive(Copy, Clone)]
+struct QueriedName<const L: usize> {
+    name: [u8; L],
+    len: usize,
+    count: u32,
+}
+
+impl<const L: usize> QueriedName<L> {
+    const fn new() -> Self {
+        Self {
+            name: [0; L],
+            len: 0,
+            count: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only ever written to via `fmt::Write`, a whole character at a time
+        core::str::from_utf8(&self.name[..self.len]).unwrap_or_default()
+    }
+}
+
+impl<const L: usize> fmt::Write for QueriedName<L> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let mut utf8 = [0; 4];
+            let c = c.encode_utf8(&mut utf8).as_bytes();
+
+            if self.len + c.len() > L {
+                return Err(fmt::Error);
+            }
+
+            self.name[self.len..self.len + c.len()].copy_from_slice(c);
+            self.len += c.len();
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the captive portal DNS on the provided local address,
+/// answering all A questions with `ip` and all other questions with no records.
 pub async fn run<S>(
     stack: &S,
     local_addr: SocketAddr,
@@ -63,6 +337,111 @@ pub async fn run<S>(
 ) -> Result<(), DnsIoError<S::Error>>
 where
     S: UdpBind,
+{
+    run_with(
+        stack,
+        local_addr,
+        tx_buf,
+        rx_buf,
+        &ReplyPolicy::new(ip, ttl),
+    )
+    .await
+}
+
+/// Same as `run`, but replies as per the provided policy.
+pub async fn run_with<S>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    policy: &ReplyPolicy<'_>,
+) -> Result<(), DnsIoError<S::Error>>
+where
+    S: UdpBind,
+{
+    run_observed(stack, local_addr, tx_buf, rx_buf, policy, NoObserver).await
+}
+
+/// Same as `run_with`, but additionally reports the activity of the DNS server
+/// (queries received, replies sent and malformed packets dropped) to the provided observer.
+pub async fn run_observed<S, O>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    policy: &ReplyPolicy<'_>,
+    observer: O,
+) -> Result<(), DnsIoError<S::Error>>
+where
+    S: UdpBind,
+    O: DnsObserver,
+{
+    run_handler(stack, local_addr, tx_buf, rx_buf, policy.clone(), observer).await
+}
+
+/// Same as `run_with`, but the queries for the domains allowlisted in the provided forwarder
+/// are forwarded to its upstream DNS server rather than answered with the portal address.
+///
+/// The queries are forwarded and the responses are received over the same socket
+/// which serves the clients.
+pub async fn run_with_forwarder<S, const N: usize>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    policy: &ReplyPolicy<'_>,
+    forwarder: &mut Forwarder<'_, N>,
+) -> Result<(), DnsIoError<S::Error>>
+where
+    S: UdpBind,
+{
+    run_with_forwarder_observed(
+        stack, local_addr, tx_buf, rx_buf, policy, forwarder, NoObserver,
+    )
+    .await
+}
+
+/// Same as `run_with_forwarder`, but additionally reports the activity of the DNS server to the provided observer.
+pub async fn run_with_forwarder_observed<S, O, const N: usize>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    policy: &ReplyPolicy<'_>,
+    forwarder: &mut Forwarder<'_, N>,
+    observer: O,
+) -> Result<(), DnsIoError<S::Error>>
+where
+    S: UdpBind,
+    O: DnsObserver,
+{
+    run_handler(
+        stack,
+        local_addr,
+        tx_buf,
+        rx_buf,
+        ChainedHandler::new(forwarder, policy.clone()),
+        observer,
+    )
+    .await
+}
+
+/// Runs a DNS server on the provided local address, which processes all incoming packets
+/// with the provided handler (usually a chain of handlers, see `ChainedHandler`).
+///
+/// The activity of the server is reported to the provided observer.
+pub async fn run_handler<S, H, O>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    mut handler: H,
+    observer: O,
+) -> Result<(), DnsIoError<S::Error>>
+where
+    S: UdpBind,
+    H: DnsHandler,
+    O: DnsObserver,
 {
     let mut udp = stack.bind(local_addr).await.map_err(DnsIoError::IoError)?;
 
@@ -75,21 +454,69 @@ where
 
         debug!("Received {} bytes from {remote}", request.len());
 
-        let len = match crate::reply(request, &ip.octets(), ttl, tx_buf) {
-            Ok(len) => len,
-            Err(err) => match err {
-                DnsError::InvalidMessage => {
-                    warn!("Got invalid message from {remote}, skipping");
-                    continue;
+        // Only queries are observed, and not e.g. the responses of an upstream server
+        let query = match Message::from_octets(request) {
+            Ok(message) if !message.header().qr() => {
+                if let Some(question) = message.first_question() {
+                    observer.query(question.qname(), question.qtype(), remote);
                 }
-                other => Err(other)?,
+
+                true
+            }
+            _ => false,
+        };
+
+        let response = match handler.handle(
+            DnsRequest {
+                data: request,
+                remote,
             },
+            tx_buf,
+        ) {
+            Ok(response) => response,
+            Err(DnsError::InvalidMessage) => {
+                warn!("Got invalid message from {remote}, skipping");
+
+                if query {
+                    observer.malformed(remote);
+                }
+
+                continue;
+            }
+            Err(other) => Err(other)?,
         };
 
-        udp.send(remote, &tx_buf[..len])
-            .await
-            .map_err(DnsIoError::IoError)?;
+        match response {
+            DnsResponse::None => debug!("Message from {remote} not handled, skipping"),
+            DnsResponse::Ignore => {
+                debug!("Not replying to {remote}");
 
-        debug!("Sent {len} bytes to {remote}");
+                if query {
+                    observer.reply(remote, None);
+                }
+            }
+            DnsResponse::Reply(data) => {
+                udp.send(remote, data).await.map_err(DnsIoError::IoError)?;
+
+                debug!("Sent {} bytes to {remote}", data.len());
+
+                if query {
+                    let rcode = Message::from_octets(data)
+                        .map(|message| message.header().rcode())
+                        .ok();
+
+                    observer.reply(remote, rcode);
+                }
+            }
+            DnsResponse::Forward { data, remote: to } => {
+                udp.send(to, data).await.map_err(DnsIoError::IoError)?;
+
+                debug!("Forwarded {} bytes from {remote} to {to}", data.len());
+
+                if query {
+                    observer.forward(remote);
+                }
+            }
+        }
     }
 }
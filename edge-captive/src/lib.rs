@@ -2,11 +2,12 @@
 #![warn(clippy::large_futures)]
 
 use core::fmt::{self, Display};
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use core::time::Duration;
 
 use domain::base::wire::Composer;
 use domain::dep::octseq::{OctetsBuilder, Truncate};
-use log::debug;
+use log::{debug, warn};
 
 use domain::{
     base::{
@@ -18,9 +19,12 @@ use domain::{
         Record, Rtype,
     },
     dep::octseq::ShortBuf,
-    rdata::A,
+    rdata::{Aaaa, A},
 };
 
+pub mod api;
+#[cfg(feature = "http")]
+pub mod http;
 #[cfg(feature = "io")]
 pub mod io;
 
@@ -66,13 +70,274 @@ impl From<ParseError> for DnsError {
     }
 }
 
+/// An incoming DNS packet, as passed to `DnsHandler::handle`
+#[derive(Debug, Clone, Copy)]
+pub struct DnsRequest<'a> {
+    /// The data of the packet
+    pub data: &'a [u8],
+    /// The address the packet was received from
+    pub remote: SocketAddr,
+}
+
+/// What to do with an incoming DNS packet, as returned by `DnsHandler::handle`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DnsResponse<'a> {
+    /// The handler is not interested in the packet; it should be passed to the next handler in the chain, if any
+    None,
+    /// The packet was handled, but nothing should be sent in response
+    Ignore,
+    /// Send the data back to the sender of the packet
+    Reply(&'a [u8]),
+    /// Send the data to another address (e.g. forward a query to an upstream server, or relay its response)
+    Forward {
+        /// The data to send
+        data: &'a [u8],
+        /// The address to send the data to
+        remote: SocketAddr,
+    },
+}
+
+/// A handler for the DNS packets received on a (captive portal) DNS socket.
+///
+/// Handlers can be chained (see `ChainedHandler`), so that a single socket can serve
+/// e.g. both the forwarding of allowlisted queries (`Forwarder`) and the captive answering (`ReplyPolicy`).
+pub trait DnsHandler {
+    /// Handle an incoming packet, possibly composing the data to be sent in response into `response_buf`
+    fn handle<'a>(
+        &mut self,
+        request: DnsRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<DnsResponse<'a>, DnsError>;
+}
+
+impl<T> DnsHandler for &mut T
+where
+    T: DnsHandler,
+{
+    fn handle<'a>(
+        &mut self,
+        request: DnsRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<DnsResponse<'a>, DnsError> {
+        (**self).handle(request, response_buf)
+    }
+}
+
+/// A handler that does not handle anything.
+///
+/// Useful only when chaining multiple `DnsHandler` instances.
+pub struct NoHandler;
+
+impl NoHandler {
+    /// Chains a `NoHandler` with another handler.
+    pub fn chain<T>(self, handler: T) -> ChainedHandler<T, Self> {
+        ChainedHandler::new(handler, self)
+    }
+}
+
+impl DnsHandler for NoHandler {
+    fn handle<'a>(
+        &mut self,
+        _request: DnsRequest<'_>,
+        _response_buf: &'a mut [u8],
+    ) -> Result<DnsResponse<'a>, DnsError> {
+        Ok(DnsResponse::None)
+    }
+}
+
+/// A composite handler that chains two handlers together.
+pub struct ChainedHandler<T, U> {
+    first: T,
+    second: U,
+}
+
+impl<T, U> ChainedHandler<T, U> {
+    /// Create a new `ChainedHandler` instance from two handlers.
+    pub const fn new(first: T, second: U) -> Self {
+        Self { first, second }
+    }
+
+    /// Chains a `ChainedHandler` with another handler,
+    /// where our instance would be the first one to be called.
+    ///
+    /// Chaining works by calling each chained handler from the first to the last,
+    /// until a handler in the chain returns something other than `DnsResponse::None`.
+    pub fn chain<V>(self, handler: V) -> ChainedHandler<V, Self> {
+        ChainedHandler::new(handler, self)
+    }
+}
+
+impl<T, U> DnsHandler for ChainedHandler<T, U>
+where
+    T: DnsHandler,
+    U: DnsHandler,
+{
+    fn handle<'a>(
+        &mut self,
+        request: DnsRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<DnsResponse<'a>, DnsError> {
+        match self.first.handle(request, response_buf)? {
+            DnsResponse::None => self.second.handle(request, response_buf),
+            DnsResponse::Ignore => Ok(DnsResponse::Ignore),
+            DnsResponse::Reply(data) => {
+                let len = data.len();
+
+                Ok(DnsResponse::Reply(&response_buf[..len]))
+            }
+            DnsResponse::Forward { data, remote } => {
+                let len = data.len();
+
+                Ok(DnsResponse::Forward {
+                    data: &response_buf[..len],
+                    remote,
+                })
+            }
+        }
+    }
+}
+
+/// What to reply to a question of a given type
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum QtypeAction {
+    /// Answer the question with the portal address
+    ///
+    /// Only A and AAAA questions can be answered (the latter - only if an IPv6 portal address is configured);
+    /// other questions are treated as with `QtypeAction::NoData`.
+    Answer,
+    /// Reply with no records (NOERROR with an empty answer section)
+    NoData,
+    /// Reply with NXDOMAIN
+    NxDomain,
+    /// Reply with REFUSED
+    Refused,
+    /// Do not reply at all
+    Ignore,
+}
+
+/// Controls how the captive portal DNS replies to the questions of each type.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ReplyPolicy<'a> {
+    /// The IPv4 address of the portal; A questions are answered with it
+    pub ip: Ipv4Addr,
+    /// The IPv6 address of the portal, if any; AAAA questions are answered with it
+    ///
+    /// If not set, AAAA questions are replied with no records, so that dual-stack clients
+    /// fall back to IPv4 and reach the portal.
+    pub ipv6: Option<Ipv6Addr>,
+    /// The TTL of the answers
+    pub ttl: Duration,
+    /// Per-qtype overrides of `ttl`
+    pub ttls: &'a [(Rtype, Duration)],
+    /// Per-qtype overrides of the reply
+    pub actions: &'a [(Rtype, QtypeAction)],
+    /// The reply to questions which are not A or AAAA, and which are not in `actions`
+    pub default_action: QtypeAction,
+    /// The reply to questions of classes other than IN, regardless of their type
+    ///
+    /// `QtypeAction::Answer` is treated as `QtypeAction::NoData`.
+    pub other_class_action: QtypeAction,
+    /// The maximum length of a reply; 512 bytes by default, which is the maximum length
+    /// of a DNS message over UDP, unless a larger one is negotiated via EDNS(0)
+    pub max_len: usize,
+    /// Whether the answers which do not fit in `max_len` (or in the reply buffer) should be dropped
+    /// and the reply should be marked as truncated (TC), as per RFC 1035; `true` by default
+    ///
+    /// If `false`, composing such a reply fails with `DnsError::ShortBuf`.
+    pub truncate: bool,
+}
+
+impl ReplyPolicy<'_> {
+    /// Create a new policy answering the A questions with the provided address and all other questions with no records
+    pub const fn new(ip: Ipv4Addr, ttl: Duration) -> Self {
+        Self {
+            ip,
+            ipv6: None,
+            ttl,
+            ttls: &[],
+            actions: &[],
+            default_action: QtypeAction::NoData,
+            other_class_action: QtypeAction::NoData,
+            max_len: 512,
+            truncate: true,
+        }
+    }
+
+    /// Return the TTL of the answers to questions of the provided type
+    pub fn ttl(&self, qtype: Rtype) -> Duration {
+        self.ttls
+            .iter()
+            .find(|(rtype, _)| *rtype == qtype)
+            .map(|(_, ttl)| *ttl)
+            .unwrap_or(self.ttl)
+    }
+
+    /// Return the action for questions of the provided type and class
+    pub fn question_action(&self, qtype: Rtype, qclass: Class) -> QtypeAction {
+        if matches!(qclass, Class::IN) {
+            self.action(qtype)
+        } else {
+            self.other_class_action
+        }
+    }
+
+    /// Return the action for questions of the provided type
+    pub fn action(&self, qtype: Rtype) -> QtypeAction {
+        if let Some((_, action)) = self.actions.iter().find(|(rtype, _)| *rtype == qtype) {
+            return *action;
+        }
+
+        match qtype {
+            Rtype::A => QtypeAction::Answer,
+            Rtype::AAAA if self.ipv6.is_some() => QtypeAction::Answer,
+            _ => self.default_action,
+        }
+    }
+}
+
+/// Replies to all queries as per the policy; queries which are ignored as per the policy
+/// are reported as `DnsResponse::Ignore`.
+///
+/// Since all queries are handled, this handler should be the last one in a chain.
+impl DnsHandler for ReplyPolicy<'_> {
+    fn handle<'a>(
+        &mut self,
+        request: DnsRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<DnsResponse<'a>, DnsError> {
+        match reply_with(request.data, self, response_buf)? {
+            0 => Ok(DnsResponse::Ignore),
+            len => Ok(DnsResponse::Reply(&response_buf[..len])),
+        }
+    }
+}
+
+/// Process a DNS request and compose the reply into the provided buffer,
+/// answering all A questions with `ip` and all other questions with no records.
+///
+/// Returns the length of the reply.
 pub fn reply(
     request: &[u8],
     ip: &[u8; 4],
     ttl: Duration,
     buf: &mut [u8],
 ) -> Result<usize, DnsError> {
-    let buf = Buf(buf, 0);
+    let ip = Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]);
+
+    reply_with(request, &ReplyPolicy::new(ip, ttl), buf)
+}
+
+/// Same as `reply`, but replies as per the provided policy.
+///
+/// Returns the length of the reply, or 0 if the request should not be replied (see `QtypeAction::Ignore`).
+pub fn reply_with(
+    request: &[u8],
+    policy: &ReplyPolicy<'_>,
+    buf: &mut [u8],
+) -> Result<usize, DnsError> {
+    let max_len = core::cmp::min(buf.len(), policy.max_len);
+    let buf = Buf(&mut buf[..max_len], 0);
 
     let message = domain::base::Message::from_octets(request)?;
     debug!("Processing message with header: {:?}", message.header());
@@ -82,23 +347,70 @@ pub fn reply(
     let buf = if matches!(message.header().opcode(), Opcode::QUERY) {
         debug!("Message is of type Query, processing all questions");
 
-        let mut answerb = responseb.start_answer(&message, Rcode::NOERROR)?;
+        let mut rcode = Rcode::NOERROR;
 
         for question in message.question() {
             let question = question?;
 
-            if matches!(question.qtype(), Rtype::A) && matches!(question.qclass(), Class::IN) {
-                let record = Record::new(
-                    question.qname(),
-                    Class::IN,
-                    Ttl::from_duration_lossy(ttl),
-                    A::from_octets(ip[0], ip[1], ip[2], ip[3]),
-                );
-                debug!("Answering {:?} with {:?}", question, record);
-                answerb.push(record)?;
-            } else {
-                debug!("Question {:?} is not of type A, not answering", question);
+            match policy.question_action(question.qtype(), question.qclass()) {
+                QtypeAction::Ignore => {
+                    debug!("Ignoring message");
+                    return Ok(0);
+                }
+                QtypeAction::Refused => rcode = Rcode::REFUSED,
+                QtypeAction::NxDomain if rcode == Rcode::NOERROR => rcode = Rcode::NXDOMAIN,
+                _ => (),
+            }
+        }
+
+        let mut answerb = responseb.start_answer(&message, rcode)?;
+
+        for question in message.question() {
+            let question = question?;
+
+            let answer = matches!(question.qclass(), Class::IN)
+                && rcode == Rcode::NOERROR
+                && policy.action(question.qtype()) == QtypeAction::Answer;
+
+            let ttl = Ttl::from_duration_lossy(policy.ttl(question.qtype()));
+
+            let result = match (question.qtype(), policy.ipv6) {
+                (Rtype::A, _) if answer => {
+                    let ip = policy.ip.octets();
+
+                    let record = Record::new(
+                        question.qname(),
+                        Class::IN,
+                        ttl,
+                        A::from_octets(ip[0], ip[1], ip[2], ip[3]),
+                    );
+                    debug!("Answering {:?} with {:?}", question, record);
+                    answerb.push(record)
+                }
+                (Rtype::AAAA, Some(ipv6)) if answer => {
+                    let record = Record::new(
+                        question.qname(),
+                        Class::IN,
+                        ttl,
+                        Aaaa::new(ipv6.octets().into()),
+                    );
+                    debug!("Answering {:?} with {:?}", question, record);
+                    answerb.push(record)
+                }
+                _ => {
+                    debug!("Not answering question {:?}", question);
+                    Ok(())
+                }
+            };
+
+            if result.is_err() && policy.truncate {
+                debug!("Reply does not fit in {max_len} bytes, truncating");
+
+                answerb.header_mut().set_tc(true);
+                break;
             }
+
+            result?;
         }
 
         answerb.finish()
@@ -118,6 +430,219 @@ pub fn reply(
     Ok(buf.1)
 }
 
+/// Forwards the queries for a set of allowlisted domains (and their subdomains) to an upstream DNS server,
+/// and relays the responses of the upstream server back to the clients.
+///
+/// Useful for domains which should resolve to their real addresses even while the portal is active,
+/// like the cloud services or the OAuth providers the portal depends on.
+///
+/// The IDs of the forwarded queries are replaced with random ones, so that the queries of different clients
+/// cannot be confused with each other. Up to `N` queries can await a response; if more are forwarded,
+/// the oldest ones are forgotten and their responses are dropped.
+pub struct Forwarder<'a, const N: usize = 8> {
+    allowlist: &'a [&'a str],
+    upstream: SocketAddr,
+    rand: fn(&mut [u8]),
+    pending: [Option<Pending>; N],
+    next: usize,
+}
+
+impl<'a, const N: usize> Forwarder<'a, N> {
+    /// Create a new forwarder for the provided allowlisted domains and upstream DNS server
+    ///
+    /// `rand` is used for generating the IDs of the forwarded queries.
+    pub const fn new(allowlist: &'a [&'a str], upstream: SocketAddr, rand: fn(&mut [u8])) -> Self {
+        Self {
+            allowlist,
+            upstream,
+            rand,
+            pending: [None; N],
+            next: 0,
+        }
+    }
+
+    /// Return the address of the upstream DNS server
+    pub fn upstream(&self) -> SocketAddr {
+        self.upstream
+    }
+
+    /// Return `true` if the provided address is the address of the upstream DNS server
+    pub fn is_upstream(&self, addr: SocketAddr) -> bool {
+        addr.ip().to_canonical() == self.upstream.ip().to_canonical()
+            && addr.port() == self.upstream.port()
+    }
+
+    /// Return `true` if the provided request is a query for an allowlisted domain, or for a subdomain of it
+    pub fn is_allowlisted(&self, request: &[u8]) -> bool {
+        let mut name = [0; 255];
+
+        let Some(len) = qname(request, &mut name) else {
+            return false;
+        };
+
+        let name = &name[..len];
+
+        self.allowlist.iter().any(|domain| {
+            let domain = domain.trim_end_matches('.').as_bytes();
+
+            name.len() >= domain.len()
+                && name[name.len() - domain.len()..].eq_ignore_ascii_case(domain)
+                && (name.len() == domain.len() || name[name.len() - domain.len() - 1] == b'.')
+        })
+    }
+
+    /// Prepare the provided request from `client` for forwarding to the upstream server,
+    /// by replacing its ID in-place
+    pub fn forward(&mut self, request: &mut [u8], client: SocketAddr) -> Result<(), DnsError> {
+        if request.len() < 12 {
+            Err(DnsError::InvalidMessage)?;
+        }
+
+        let mut id = [0; 2];
+        (self.rand)(&mut id);
+
+        if let Some(pending) = &self.pending[self.next] {
+            debug!(
+                "Dropping the pending forwarded query from {}",
+                pending.client
+            );
+        }
+
+        self.pending[self.next] = Some(Pending {
+            id: u16::from_be_bytes(id),
+            client_id: u16::from_be_bytes([request[0], request[1]]),
+            client,
+        });
+
+        self.next = (self.next + 1) % N;
+
+        request[..2].copy_from_slice(&id);
+
+        Ok(())
+    }
+
+    /// Prepare the provided response of the upstream server for relaying back to the client,
+    /// by restoring the original ID of the query in-place
+    ///
+    /// Returns the address of the client, or `None` if the response does not match any forwarded query.
+    pub fn relay(&mut self, response: &mut [u8]) -> Option<SocketAddr> {
+        if response.len() < 12 {
+            return None;
+        }
+
+        let id = u16::from_be_bytes([response[0], response[1]]);
+
+        let pending = self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.map(|pending| pending.id) == Some(id))?
+            .take()?;
+
+        response[..2].copy_from_slice(&pending.client_id.to_be_bytes());
+
+        Some(pending.client)
+    }
+}
+
+/// Forwards the allowlisted queries to the upstream server and relays its responses back;
+/// all other packets are left to the next handler in the chain.
+impl<const N: usize> DnsHandler for Forwarder<'_, N> {
+    fn handle<'a>(
+        &mut self,
+        request: DnsRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<DnsResponse<'a>, DnsError> {
+        let upstream = self.is_upstream(request.remote);
+
+        if !upstream && !self.is_allowlisted(request.data) {
+            return Ok(DnsResponse::None);
+        }
+
+        let buf = response_buf
+            .get_mut(..request.data.len())
+            .ok_or(DnsError::ShortBuf)?;
+
+        buf.copy_from_slice(request.data);
+
+        if upstream {
+            if let Some(client) = self.relay(buf) {
+                debug!(
+                    "Relaying response from upstream {} to {client}",
+                    request.remote
+                );
+
+                Ok(DnsResponse::Forward {
+                    data: buf,
+                    remote: client,
+                })
+            } else {
+                warn!(
+                    "Got unexpected response from upstream {}, skipping",
+                    request.remote
+                );
+
+                Ok(DnsResponse::Ignore)
+            }
+        } else {
+            self.forward(buf, request.remote)?;
+
+            debug!(
+                "Forwarding query from {} to {}",
+                request.remote, self.upstream
+            );
+
+            Ok(DnsResponse::Forward {
+                data: buf,
+                remote: self.upstream,
+            })
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Pending {
+    id: u16,
+    client_id: u16,
+    client: SocketAddr,
+}
+
+/// Decode the name of the first question of the provided request in dotted form into `buf`
+///
+/// Returns the length of the name, or `None` if the request is malformed or has no questions.
+fn qname(request: &[u8], buf: &mut [u8; 255]) -> Option<usize> {
+    if request.len() < 12 || u16::from_be_bytes([request[4], request[5]]) == 0 {
+        return None;
+    }
+
+    let mut offset = 12;
+    let mut len = 0;
+
+    loop {
+        let label_len = *request.get(offset)? as usize;
+        offset += 1;
+
+        if label_len == 0 {
+            break Some(len);
+        }
+
+        // Compressed names are not expected in the question of a query
+        if label_len > 63 {
+            break None;
+        }
+
+        let label = request.get(offset..offset + label_len)?;
+        offset += label_len;
+
+        if len > 0 {
+            *buf.get_mut(len)? = b'.';
+            len += 1;
+        }
+
+        buf.get_mut(len..len + label_len)?.copy_from_slice(label);
+        len += label_len;
+    }
+}
+
 struct Buf<'a>(pub &'a mut [u8], pub usize);
 
 impl Composer for Buf<'_> {}
@@ -0,0 +1,46 @@
+//! Tests for the Captive Portal API (RFC 8908) and its announcement (RFC 8910)
+
+use edge_captive::api::{ra_option, CaptiveStatus, RA_OPTION_TYPE};
+
+#[test]
+fn status_json() {
+    let mut status = CaptiveStatus::new(true, Some("https://192.168.71.1/portal"));
+    status.seconds_remaining = Some(326);
+    status.can_extend_session = Some(true);
+
+    assert_eq!(
+        status.to_string(),
+        r#"{"captive":true,"user-portal-url":"https://192.168.71.1/portal","can-extend-session":true,"seconds-remaining":326}"#
+    );
+
+    assert_eq!(
+        CaptiveStatus::new(false, None).to_string(),
+        r#"{"captive":false}"#
+    );
+}
+
+#[test]
+fn status_json_escaping() {
+    let status = CaptiveStatus::new(true, Some("https://portal/\"\\"));
+
+    assert_eq!(
+        status.to_string(),
+        r#"{"captive":true,"user-portal-url":"https://portal/\"\\"}"#
+    );
+}
+
+#[test]
+fn ra_option_padding() {
+    let mut buf = [0xff; 64];
+
+    let option = ra_option("https://portal/api", &mut buf).unwrap();
+
+    // 2 bytes of type and length + 18 bytes of URL, padded to 24
+    assert_eq!(option.len(), 24);
+    assert_eq!(option[0], RA_OPTION_TYPE);
+    assert_eq!(option[1], 3);
+    assert_eq!(&option[2..20], b"https://portal/api");
+    assert!(option[20..].iter().all(|b| *b == 0));
+
+    assert!(ra_option("https://portal/api", &mut buf[..16]).is_none());
+}
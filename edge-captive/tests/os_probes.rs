@@ -0,0 +1,416 @@
+//! Conformance tests simulating the captive portal detection probes of the major operating systems
+//!
+//! Each OS resolves a well-known probe host name and then fetches a probe URL from it, expecting
+//! a fixed "success" response. The captive portal sheet is shown when the response does not match.
+//!
+//! These tests cover the DNS part of the sequence: every probe host must resolve to the portal IP,
+//! so that the HTTP probe lands on the portal (which never serves the expected success response).
+
+use core::time::Duration;
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use domain::base::iana::{Class, Rcode};
+use domain::base::{Message, Rtype};
+use domain::rdata::{Aaaa, A};
+
+use edge_captive::{
+    ChainedHandler, DnsHandler, DnsRequest, DnsResponse, Forwarder, QtypeAction, ReplyPolicy,
+};
+
+const PORTAL_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 71, 1);
+const PORTAL_IPV6: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+const TTL: Duration = Duration::from_secs(60);
+
+/// A captive portal probe, as documented (or observed) for a given OS
+struct Probe {
+    os: &'static str,
+    host: &'static str,
+    path: &'static str,
+    /// Whether the OS also resolves the host over IPv6 (AAAA) as part of the probe
+    aaaa: bool,
+}
+
+const PROBES: &[Probe] = &[
+    Probe {
+        os: "iOS / macOS",
+        host: "captive.apple.com",
+        path: "/hotspot-detect.html",
+        aaaa: true,
+    },
+    Probe {
+        os: "iOS (legacy)",
+        host: "www.apple.com",
+        path: "/library/test/success.html",
+        aaaa: false,
+    },
+    Probe {
+        os: "Android",
+        host: "connectivitycheck.gstatic.com",
+        path: "/generate_204",
+        aaaa: true,
+    },
+    Probe {
+        os: "Android (legacy)",
+        host: "clients3.google.com",
+        path: "/generate_204",
+        aaaa: false,
+    },
+    Probe {
+        os: "Windows 10+",
+        host: "www.msftconnecttest.com",
+        path: "/connecttest.txt",
+        aaaa: true,
+    },
+    Probe {
+        os: "Windows (legacy)",
+        host: "www.msftncsi.com",
+        path: "/ncsi.txt",
+        aaaa: false,
+    },
+    Probe {
+        os: "Windows (DNS check)",
+        host: "dns.msftncsi.com",
+        path: "",
+        aaaa: false,
+    },
+    Probe {
+        os: "Firefox",
+        host: "detectportal.firefox.com",
+        path: "/canonical.html",
+        aaaa: true,
+    },
+];
+
+#[test]
+fn probe_hosts_resolve_to_portal() {
+    for (index, probe) in PROBES.iter().enumerate() {
+        let request = query(index as u16, probe.host, Rtype::A, true);
+
+        let mut buf = [0; 512];
+        let len = edge_captive::reply(&request, &PORTAL_IP.octets(), TTL, &mut buf)
+            .unwrap_or_else(|e| panic!("{}: reply failed: {e}", probe.os));
+
+        let response = Message::from_octets(&buf[..len]).unwrap();
+        let header = response.header();
+
+        assert!(header.qr(), "{}: not a response", probe.os);
+        assert_eq!(header.id(), index as u16, "{}: ID mismatch", probe.os);
+        assert_eq!(header.rcode(), Rcode::NOERROR, "{}: bad rcode", probe.os);
+        assert!(header.rd(), "{}: RD flag not copied", probe.os);
+
+        let answers = response
+            .answer()
+            .unwrap()
+            .limit_to::<A>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(answers.len(), 1, "{}: expected a single A record", probe.os);
+
+        let answer = &answers[0];
+
+        assert!(
+            answer
+                .owner()
+                .to_string()
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(probe.host),
+            "{}: answer for the wrong name",
+            probe.os
+        );
+        assert_eq!(answer.class(), Class::IN);
+        assert_eq!(answer.ttl().as_secs(), TTL.as_secs() as u32);
+        assert_eq!(
+            answer.data().addr(),
+            PORTAL_IP,
+            "{}: probe http://{}{} would not reach the portal",
+            probe.os,
+            probe.host,
+            probe.path
+        );
+    }
+}
+
+#[test]
+fn probe_hosts_aaaa_not_answered() {
+    // IPv6 probes must not resolve, or dual-stack clients would probe the real
+    // internet over IPv6 (if available) and never see the portal
+    for (index, probe) in PROBES.iter().filter(|probe| probe.aaaa).enumerate() {
+        let request = query(index as u16, probe.host, Rtype::AAAA, true);
+
+        let mut buf = [0; 512];
+        let len = edge_captive::reply(&request, &PORTAL_IP.octets(), TTL, &mut buf).unwrap();
+
+        let response = Message::from_octets(&buf[..len]).unwrap();
+
+        assert_eq!(response.header().rcode(), Rcode::NOERROR, "{}", probe.os);
+        assert_eq!(response.header_counts().ancount(), 0, "{}", probe.os);
+    }
+}
+
+#[test]
+fn probe_hosts_aaaa_answered_with_ipv6_portal() {
+    let mut policy = ReplyPolicy::new(PORTAL_IP, TTL);
+    policy.ipv6 = Some(PORTAL_IPV6);
+
+    for (index, probe) in PROBES.iter().filter(|probe| probe.aaaa).enumerate() {
+        let request = query(index as u16, probe.host, Rtype::AAAA, true);
+
+        let mut buf = [0; 512];
+        let len = edge_captive::reply_with(&request, &policy, &mut buf).unwrap();
+
+        let response = Message::from_octets(&buf[..len]).unwrap();
+
+        assert_eq!(response.header().rcode(), Rcode::NOERROR, "{}", probe.os);
+
+        let answers = response
+            .answer()
+            .unwrap()
+            .limit_to::<Aaaa>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            answers.len(),
+            1,
+            "{}: expected a single AAAA record",
+            probe.os
+        );
+        assert_eq!(answers[0].data().addr(), PORTAL_IPV6, "{}", probe.os);
+    }
+}
+
+#[test]
+fn per_qtype_actions() {
+    let mut policy = ReplyPolicy::new(PORTAL_IP, TTL);
+    policy.actions = &[
+        (Rtype::AAAA, QtypeAction::NxDomain),
+        (Rtype::TXT, QtypeAction::Ignore),
+    ];
+
+    let mut buf = [0; 512];
+
+    let request = query(1, "captive.apple.com", Rtype::AAAA, true);
+    let len = edge_captive::reply_with(&request, &policy, &mut buf).unwrap();
+
+    let response = Message::from_octets(&buf[..len]).unwrap();
+
+    assert_eq!(response.header().rcode(), Rcode::NXDOMAIN);
+    assert_eq!(response.header_counts().ancount(), 0);
+
+    let request = query(2, "captive.apple.com", Rtype::TXT, true);
+
+    assert_eq!(
+        edge_captive::reply_with(&request, &policy, &mut buf).unwrap(),
+        0
+    );
+
+    let request = query(3, "captive.apple.com", Rtype::A, true);
+    let len = edge_captive::reply_with(&request, &policy, &mut buf).unwrap();
+
+    let response = Message::from_octets(&buf[..len]).unwrap();
+
+    assert_eq!(response.header().rcode(), Rcode::NOERROR);
+    assert_eq!(response.header_counts().ancount(), 1);
+}
+
+#[test]
+fn allowlisted_queries_forwarded() {
+    let upstream = "8.8.8.8:53".parse().unwrap();
+    let client = "192.168.71.2:49152".parse().unwrap();
+
+    let mut forwarder = Forwarder::<4>::new(&["gstatic.com"], upstream, |buf| buf.fill(0x5a));
+
+    let mut request = query(0x1234, "connectivitycheck.gstatic.com", Rtype::A, true);
+
+    assert!(forwarder.is_allowlisted(&request));
+    assert!(!forwarder.is_allowlisted(&query(1, "captive.apple.com", Rtype::A, true)));
+    assert!(!forwarder.is_allowlisted(&query(1, "notgstatic.com", Rtype::A, true)));
+
+    forwarder.forward(&mut request, client).unwrap();
+
+    assert_eq!(&request[..2], &[0x5a, 0x5a]);
+
+    // The upstream server echoes the ID of the forwarded query in its response
+    let mut response = request.clone();
+    response[2] |= 0x80;
+
+    assert!(forwarder.is_upstream(upstream));
+    assert_eq!(forwarder.relay(&mut response), Some(client));
+    assert_eq!(&response[..2], &[0x12, 0x34]);
+
+    // ... and a response is relayed only once
+    assert_eq!(forwarder.relay(&mut response), None);
+}
+
+#[test]
+fn chained_handlers() {
+    let upstream = "8.8.8.8:53".parse().unwrap();
+    let client = "192.168.71.2:49152".parse().unwrap();
+
+    let mut handler = ChainedHandler::new(
+        Forwarder::<4>::new(&["gstatic.com"], upstream, |buf| buf.fill(0x5a)),
+        ReplyPolicy::new(PORTAL_IP, TTL),
+    );
+
+    let mut buf = [0; 512];
+
+    let request = query(1, "connectivitycheck.gstatic.com", Rtype::A, true);
+
+    let response = handler
+        .handle(
+            DnsRequest {
+                data: &request,
+                remote: client,
+            },
+            &mut buf,
+        )
+        .unwrap();
+
+    let DnsResponse::Forward { data, remote } = response else {
+        panic!("Allowlisted query not forwarded: {response:?}");
+    };
+
+    assert_eq!(remote, upstream);
+    assert_eq!(&data[..2], &[0x5a, 0x5a]);
+
+    let request = query(2, "captive.apple.com", Rtype::A, true);
+
+    let response = handler
+        .handle(
+            DnsRequest {
+                data: &request,
+                remote: client,
+            },
+            &mut buf,
+        )
+        .unwrap();
+
+    let DnsResponse::Reply(data) = response else {
+        panic!("Query not answered: {response:?}");
+    };
+
+    let response = Message::from_octets(data).unwrap();
+
+    assert_eq!(response.header().id(), 2);
+    assert_eq!(response.header_counts().ancount(), 1);
+}
+
+#[test]
+fn other_classes_refused() {
+    let mut policy = ReplyPolicy::new(PORTAL_IP, TTL);
+    policy.other_class_action = QtypeAction::Refused;
+
+    let mut request = query(7, "version.bind", Rtype::TXT, true);
+
+    let len = request.len();
+    request[len - 2..].copy_from_slice(&Class::CH.to_int().to_be_bytes());
+
+    let mut buf = [0; 512];
+    let len = edge_captive::reply_with(&request, &policy, &mut buf).unwrap();
+
+    let response = Message::from_octets(&buf[..len]).unwrap();
+
+    assert_eq!(response.header().rcode(), Rcode::REFUSED);
+    assert_eq!(response.header_counts().ancount(), 0);
+}
+
+#[test]
+fn per_qtype_ttl() {
+    let mut policy = ReplyPolicy::new(PORTAL_IP, TTL);
+    policy.ttls = &[(Rtype::A, Duration::from_secs(1))];
+
+    let request = query(8, "captive.apple.com", Rtype::A, true);
+
+    let mut buf = [0; 512];
+    let len = edge_captive::reply_with(&request, &policy, &mut buf).unwrap();
+
+    let response = Message::from_octets(&buf[..len]).unwrap();
+
+    let answer = response
+        .answer()
+        .unwrap()
+        .limit_to::<A>()
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(answer.ttl().as_secs(), 1);
+}
+
+#[test]
+fn oversized_reply_truncated() {
+    let request = query(9, "captive.apple.com", Rtype::A, true);
+
+    // Room for the header and the question, but not for the answer
+    let mut policy = ReplyPolicy::new(PORTAL_IP, TTL);
+    policy.max_len = request.len() + 4;
+
+    let mut buf = [0; 512];
+    let len = edge_captive::reply_with(&request, &policy, &mut buf).unwrap();
+
+    let response = Message::from_octets(&buf[..len]).unwrap();
+
+    assert!(response.header().tc());
+    assert_eq!(response.header().rcode(), Rcode::NOERROR);
+    assert_eq!(response.header_counts().ancount(), 0);
+
+    policy.truncate = false;
+
+    assert!(edge_captive::reply_with(&request, &policy, &mut buf).is_err());
+}
+
+#[test]
+fn non_query_opcode_not_implemented() {
+    let mut request = query(0x4242, "captive.apple.com", Rtype::A, false);
+
+    // Opcode = STATUS
+    request[2] |= 2 << 3;
+
+    let mut buf = [0; 512];
+    let len = edge_captive::reply(&request, &PORTAL_IP.octets(), TTL, &mut buf).unwrap();
+
+    let response = Message::from_octets(&buf[..len]).unwrap();
+
+    assert_eq!(response.header().id(), 0x4242);
+    assert_eq!(response.header().rcode(), Rcode::NOTIMP);
+    assert_eq!(response.header_counts().ancount(), 0);
+}
+
+#[test]
+fn short_buffer() {
+    let request = query(1, "connectivitycheck.gstatic.com", Rtype::A, true);
+
+    let mut buf = [0; 16];
+
+    assert!(edge_captive::reply(&request, &PORTAL_IP.octets(), TTL, &mut buf).is_err());
+}
+
+#[test]
+fn garbage_request() {
+    let mut buf = [0; 512];
+
+    assert!(edge_captive::reply(&[0x12, 0x34, 0x01], &PORTAL_IP.octets(), TTL, &mut buf).is_err());
+}
+
+/// Build a DNS query the way the OS stub resolvers do: one question, class IN
+fn query(id: u16, name: &str, qtype: Rtype, rd: bool) -> Vec<u8> {
+    let mut request = Vec::new();
+
+    request.extend_from_slice(&id.to_be_bytes());
+    request.extend_from_slice(&[if rd { 0x01 } else { 0x00 }, 0x00]);
+    request.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.split('.') {
+        request.push(label.len() as u8);
+        request.extend_from_slice(label.as_bytes());
+    }
+
+    request.push(0);
+
+    request.extend_from_slice(&qtype.to_int().to_be_bytes());
+    request.extend_from_slice(&Class::IN.to_int().to_be_bytes());
+
+    request
+}
@@ -0,0 +1,974 @@
+use core::fmt::{self, Debug};
+use core::net::SocketAddr;
+
+use edge_nal::{UdpReceive, UdpSend};
+
+use embassy_futures::select::{select, Either};
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::signal::Signal;
+
+use embassy_time::{with_timeout, Duration, Instant};
+
+use log::{debug, warn};
+
+use rand_core::RngCore;
+
+use crate as coap;
+use crate::{Block, Code, ContentFormat, Message, MessageType, MessageWriter, OptionNumber};
+
+/// The length of the tokens of the requests sent by a `Client`
+pub const TOKEN_LEN: usize = 4;
+
+/// The default maximum number of observers of a `Server`
+pub const DEFAULT_MAX_OBSERVERS: usize = 4;
+
+/// The maximum length of an observe request which a `Server` can register
+pub const MAX_OBSERVE_REQUEST_LEN: usize = 128;
+
+/// The number of consecutive notifications an observer can leave unacknowledged before a `Server` drops it
+const MAX_UNACKNOWLEDGED_NOTIFICATIONS: u8 = 4;
+
+/// The time after which a notification is fresher than the previous one, regardless of the sequence numbers
+/// (RFC 7641, section 3.4)
+const NOTIFICATION_FRESHNESS: Duration = Duration::from_secs(128);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error<E> {
+    Io(E),
+    Format(coap::Error),
+    /// No response arrived in time
+    Timeout,
+    /// The peer rejected the request with a reset message
+    Reset,
+}
+
+pub type ErrorKind = Error<edge_nal::io::ErrorKind>;
+
+impl<E> Error<E>
+where
+    E: edge_nal::io::Error,
+{
+    pub fn erase(&self) -> Error<edge_nal::io::ErrorKind> {
+        match self {
+            Self::Io(e) => Error::Io(e.kind()),
+            Self::Format(e) => Error::Format(*e),
+            Self::Timeout => Error::Timeout,
+            Self::Reset => Error::Reset,
+        }
+    }
+}
+
+impl<E> From<coap::Error> for Error<E> {
+    fn from(value: coap::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "IO error: {err}"),
+            Self::Format(err) => write!(f, "Format error: {err}"),
+            Self::Timeout => write!(f, "Timeout"),
+            Self::Reset => write!(f, "Reset by peer"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E> where E: std::error::Error {}
+
+/// The configuration of a `Client`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// The initial timeout for the acknowledgement of a confirmable request, which is
+    /// randomized by up to 50% and doubles with every retransmission
+    pub ack_timeout: Duration,
+    /// The number of retransmissions of a confirmable request
+    pub max_retransmit: u8,
+    /// The time to wait for a non-confirmable response, or for a response which is not
+    /// piggybacked on the acknowledgement of the request
+    pub response_timeout: Duration,
+    /// The preferred size of the blocks of block-wise transfers, rounded down to a power of two between 16 and 1024
+    pub block_size: usize,
+}
+
+impl Config {
+    /// Create a new configuration, with the default transmission parameters of RFC 7252, section 4.8
+    /// (an acknowledgement timeout of 2 seconds and 4 retransmissions), a response timeout of
+    /// 93 seconds (`MAX_TRANSMIT_WAIT`) and blocks of 512 bytes
+    pub const fn new() -> Self {
+        Self {
+            ack_timeout: Duration::from_secs(2),
+            max_retransmit: 4,
+            response_timeout: Duration::from_secs(93),
+            block_size: 512,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A request sent by a `Client`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Request<'a> {
+    pub code: Code,
+    /// Whether the request is sent as a confirmable message, and retransmitted until acknowledged
+    pub confirmable: bool,
+    /// The path of the resource, i.e. `"sensors/temp"`
+    pub path: &'a str,
+    /// The query, i.e. `"ep=node1&lt=300"`
+    pub query: &'a str,
+    pub content_format: Option<ContentFormat>,
+    pub accept: Option<ContentFormat>,
+    /// Any other options; the `Observe`, `Block1` and `Block2` options are added by the client
+    pub options: &'a [(OptionNumber, &'a [u8])],
+    /// The payload, which is sent block by block if larger than `Config::block_size`
+    pub payload: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+    /// Create a confirmable request without options and payload
+    pub const fn new(code: Code, path: &'a str) -> Self {
+        Self {
+            code,
+            confirmable: true,
+            path,
+            query: "",
+            content_format: None,
+            accept: None,
+            options: &[],
+            payload: &[],
+        }
+    }
+
+    pub const fn get(path: &'a str) -> Self {
+        Self::new(Code::GET, path)
+    }
+
+    pub const fn post(path: &'a str, payload: &'a [u8]) -> Self {
+        Self {
+            payload,
+            ..Self::new(Code::POST, path)
+        }
+    }
+
+    pub const fn put(path: &'a str, payload: &'a [u8]) -> Self {
+        Self {
+            payload,
+            ..Self::new(Code::PUT, path)
+        }
+    }
+
+    pub const fn delete(path: &'a str) -> Self {
+        Self::new(Code::DELETE, path)
+    }
+}
+
+/// A CoAP client, sending requests to servers over a UDP socket
+///
+/// Confirmable requests are retransmitted until acknowledged, and both piggybacked and separate responses
+/// are matched to their requests by their tokens. Payloads larger than `Config::block_size` are transferred
+/// block by block, in both directions (RFC 7959).
+///
+/// The client does not have any buffers of its own: every request takes a buffer for the outgoing
+/// messages and a buffer for the incoming ones, which should fit a block and the options.
+pub struct Client<S, R> {
+    socket: S,
+    rng: R,
+    config: Config,
+    message_id: u16,
+}
+
+impl<S, R> Client<S, R>
+where
+    S: UdpReceive + UdpSend,
+    R: RngCore,
+{
+    /// Create a new client
+    ///
+    /// Parameters:
+    /// - `socket`: A UDP socket, bound to any local port
+    /// - `rng`: A random number generator, used for the message IDs and the tokens
+    /// - `config`: The configuration of the client
+    pub fn new(socket: S, mut rng: R, config: Config) -> Self {
+        let message_id = rng.next_u32() as u16;
+
+        Self {
+            socket,
+            rng,
+            config,
+            message_id,
+        }
+    }
+
+    /// Send a request to the server at `remote`, calling `f` with its response
+    ///
+    /// If the response is transferred block by block, the remaining blocks are requested in sequence,
+    /// and `f` is called with each of them, in order.
+    ///
+    /// Return the code of the (last) response.
+    pub async fn request<F>(
+        &mut self,
+        remote: SocketAddr,
+        request: &Request<'_>,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        mut f: F,
+    ) -> Result<Code, Error<S::Error>>
+    where
+        F: FnMut(&Message<'_>),
+    {
+        let mut block_size = Block::new(0, false, self.config.block_size).size();
+        let mut offset = 0;
+
+        // Upload the payload block by block if it does not fit in one block (RFC 7959, section 2.5)
+        let mut len = loop {
+            let payload = request.payload;
+
+            let (block1, end) = if payload.len() > block_size {
+                let end = payload.len().min(offset + block_size);
+
+                (
+                    Some(Block::new(
+                        (offset / block_size) as u32,
+                        end < payload.len(),
+                        block_size,
+                    )),
+                    end,
+                )
+            } else {
+                (None, payload.len())
+            };
+
+            let token = self.new_token();
+            let len = self.compose(
+                request,
+                &token,
+                None,
+                block1,
+                None,
+                &payload[offset..end],
+                tx_buf,
+            )?;
+
+            let len = self.exchange(remote, &tx_buf[..len], rx_buf).await?;
+
+            let Some(block1) = block1.filter(|block1| block1.more) else {
+                break len;
+            };
+
+            let response = Message::parse(&rx_buf[..len])?;
+
+            if response.code != Code::CONTINUE {
+                // The server rejected the upload
+                break len;
+            }
+
+            // The server might ask for smaller blocks
+            if let Some(ack) = response.block1() {
+                block_size = block_size.min(ack.size());
+            }
+
+            offset = block1.offset() + block1.size();
+        };
+
+        // Download the rest of the response block by block (RFC 7959, section 2.4)
+        loop {
+            let response = Message::parse(&rx_buf[..len])?;
+
+            f(&response);
+
+            let code = response.code;
+
+            let next = match response.block2() {
+                Some(block2) if block2.more && code.is_success() => {
+                    Block::new(block2.num + 1, false, block2.size())
+                }
+                _ => break Ok(code),
+            };
+
+            let token = self.new_token();
+            let tx_len = self.compose(request, &token, None, None, Some(next), &[], tx_buf)?;
+
+            len = self.exchange(remote, &tx_buf[..tx_len], rx_buf).await?;
+
+            let response = Message::parse(&rx_buf[..len])?;
+
+            if response.code.is_success()
+                && response.block2().map(|block2| block2.offset()) != Some(next.offset())
+            {
+                Err(coap::Error::InvalidMessage)?;
+            }
+        }
+    }
+
+    /// Register as an observer of the resource of `request` on the server at `remote` (RFC 7641),
+    /// calling `f` with its current representation and then with every notification about its changes
+    ///
+    /// The notifications arriving out of order are skipped. The observation lasts until `f` returns `false`
+    /// (in which case the observer is deregistered), or until the server ends it: by not accepting the registration,
+    /// or with a notification which is not a success. To bound it in time, wrap the returned future with a timeout.
+    ///
+    /// Only the first block of representations transferred block by block is delivered to `f`.
+    ///
+    /// Return the code of the last response or notification.
+    pub async fn observe<F>(
+        &mut self,
+        remote: SocketAddr,
+        request: &Request<'_>,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        mut f: F,
+    ) -> Result<Code, Error<S::Error>>
+    where
+        F: FnMut(&Message<'_>) -> bool,
+    {
+        let token = self.new_token();
+        let len = self.compose(
+            request,
+            &token,
+            Some(0),
+            None,
+            None,
+            request.payload,
+            tx_buf,
+        )?;
+
+        let len = self.exchange(remote, &tx_buf[..len], rx_buf).await?;
+
+        let response = Message::parse(&rx_buf[..len])?;
+        let mut code = response.code;
+
+        let Some(sequence) = response.observe() else {
+            // The server did not register us as an observer
+            f(&response);
+
+            return Ok(code);
+        };
+
+        let mut last = (sequence, Instant::now());
+
+        let mut observe = f(&response);
+
+        while observe {
+            let (len, from) = self.socket.receive(rx_buf).await.map_err(Error::Io)?;
+
+            if from != remote {
+                continue;
+            }
+
+            let Ok(notification) = Message::parse(&rx_buf[..len]) else {
+                debug!("Ignoring an invalid message from {from}");
+                continue;
+            };
+
+            let (ty, id) = (notification.ty, notification.id);
+
+            if notification.token != token {
+                if ty == MessageType::Confirmable {
+                    // Not ours (i.e. the notification of an observation we are no longer interested in)
+                    self.send_empty(remote, MessageType::Reset, id).await?;
+                }
+
+                continue;
+            }
+
+            if !matches!(ty, MessageType::Confirmable | MessageType::NonConfirmable) {
+                continue;
+            }
+
+            if ty == MessageType::Confirmable {
+                self.send_empty(remote, MessageType::Acknowledgement, id)
+                    .await?;
+            }
+
+            code = notification.code;
+
+            match notification.observe() {
+                Some(sequence) if code.is_success() => {
+                    if !is_fresh(last, sequence) {
+                        debug!("Ignoring a stale notification from {remote}");
+                        continue;
+                    }
+
+                    last = (sequence, Instant::now());
+
+                    observe = f(&notification);
+                }
+                _ => {
+                    // The server ended the observation
+                    f(&notification);
+
+                    return Ok(code);
+                }
+            }
+        }
+
+        // Deregister, with the token of the registration (RFC 7641, section 3.6)
+        let len = self.compose(request, &token, Some(1), None, None, &[], tx_buf)?;
+
+        self.exchange(remote, &tx_buf[..len], rx_buf).await?;
+
+        Ok(code)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn compose(
+        &mut self,
+        request: &Request<'_>,
+        token: &[u8],
+        observe: Option<u32>,
+        block1: Option<Block>,
+        block2: Option<Block>,
+        payload: &[u8],
+        buf: &mut [u8],
+    ) -> Result<usize, coap::Error> {
+        let ty = if request.confirmable {
+            MessageType::Confirmable
+        } else {
+            MessageType::NonConfirmable
+        };
+
+        let id = self.new_message_id();
+
+        let mut writer = MessageWriter::new(buf, ty, request.code, id, token)?;
+
+        writer.uri_path(request.path)?;
+        writer.uri_query(request.query)?;
+
+        if let Some(format) = request.content_format {
+            writer.content_format(format)?;
+        }
+
+        if let Some(format) = request.accept {
+            writer.uint_option(OptionNumber::ACCEPT, format.0 as _)?;
+        }
+
+        for (number, value) in request.options {
+            writer.option(*number, value)?;
+        }
+
+        if let Some(observe) = observe {
+            writer.uint_option(OptionNumber::OBSERVE, observe)?;
+        }
+
+        if let Some(block1) = block1 {
+            writer.uint_option(OptionNumber::BLOCK1, block1.encode())?;
+
+            if block1.num == 0 {
+                writer.uint_option(OptionNumber::SIZE1, request.payload.len() as _)?;
+            }
+        }
+
+        if let Some(block2) = block2 {
+            writer.uint_option(OptionNumber::BLOCK2, block2.encode())?;
+        }
+
+        writer.payload(payload)?;
+
+        Ok(writer.finish())
+    }
+
+    /// Send `request` and wait for its response, retransmitting it as long as it is not acknowledged
+    /// if it is confirmable; return the length of the response in `rx_buf`
+    async fn exchange(
+        &mut self,
+        remote: SocketAddr,
+        request: &[u8],
+        rx_buf: &mut [u8],
+    ) -> Result<usize, Error<S::Error>> {
+        let sent = Message::parse(request)?;
+
+        let mut acknowledged = sent.ty != MessageType::Confirmable;
+        let mut retransmissions = 0;
+
+        let mut timeout = if acknowledged {
+            self.config.response_timeout
+        } else {
+            let jitter = self.rng.next_u64() % (self.config.ack_timeout.as_micros() / 2 + 1);
+
+            self.config.ack_timeout + Duration::from_micros(jitter)
+        };
+
+        self.socket.send(remote, request).await.map_err(Error::Io)?;
+
+        let mut deadline = Instant::now() + timeout;
+
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .unwrap_or(Duration::from_ticks(0));
+
+            let Ok(result) = with_timeout(remaining, self.socket.receive(rx_buf)).await else {
+                if acknowledged || retransmissions >= self.config.max_retransmit {
+                    Err(Error::Timeout)?;
+                }
+
+                retransmissions += 1;
+                timeout *= 2;
+
+                debug!("Retransmitting request {} to {remote}", sent.id);
+
+                self.socket.send(remote, request).await.map_err(Error::Io)?;
+
+                deadline = Instant::now() + timeout;
+
+                continue;
+            };
+
+            let (len, from) = result.map_err(Error::Io)?;
+
+            if from != remote {
+                continue;
+            }
+
+            let Ok(reply) = Message::parse(&rx_buf[..len]) else {
+                debug!("Ignoring an invalid message from {from}");
+                continue;
+            };
+
+            let (ty, id, code) = (reply.ty, reply.id, reply.code);
+
+            match ty {
+                MessageType::Acknowledgement | MessageType::Reset if id != sent.id => {
+                    debug!("Ignoring a stale acknowledgement from {from}");
+                }
+                MessageType::Reset => Err(Error::Reset)?,
+                MessageType::Acknowledgement if code.is_empty() => {
+                    // The response will follow separately
+                    acknowledged = true;
+                    deadline = Instant::now() + self.config.response_timeout;
+                }
+                _ if reply.token != sent.token => {
+                    if ty == MessageType::Confirmable {
+                        self.send_empty(remote, MessageType::Reset, id).await?;
+                    }
+                }
+                MessageType::Confirmable => {
+                    self.send_empty(remote, MessageType::Acknowledgement, id)
+                        .await?;
+
+                    break Ok(len);
+                }
+                _ => break Ok(len),
+            }
+        }
+    }
+
+    async fn send_empty(
+        &mut self,
+        remote: SocketAddr,
+        ty: MessageType,
+        id: u16,
+    ) -> Result<(), Error<S::Error>> {
+        let mut buf = [0; 4];
+        let len = MessageWriter::new(&mut buf, ty, Code::EMPTY, id, &[])?.finish();
+
+        self.socket
+            .send(remote, &buf[..len])
+            .await
+            .map_err(Error::Io)
+    }
+
+    fn new_message_id(&mut self) -> u16 {
+        self.message_id = self.message_id.wrapping_add(1);
+        self.message_id
+    }
+
+    fn new_token(&mut self) -> [u8; TOKEN_LEN] {
+        let mut token = [0; TOKEN_LEN];
+        self.rng.fill_bytes(&mut token);
+
+        token
+    }
+}
+
+/// Return `true` if a notification with sequence number `sequence` is fresher than the last one (RFC 7641, section 3.4)
+fn is_fresh(last: (u32, Instant), sequence: u32) -> bool {
+    let (last_sequence, at) = last;
+
+    (last_sequence < sequence && sequence - last_sequence < 1 << 23)
+        || (last_sequence > sequence && last_sequence - sequence > 1 << 23)
+        || Instant::now() > at + NOTIFICATION_FRESHNESS
+}
+
+/// A trait (async callback) for handling incoming CoAP requests
+pub trait Handler {
+    type Error: Debug;
+
+    /// Handle an incoming CoAP request
+    ///
+    /// Parameters:
+    /// - `remote`: The address of the client
+    /// - `request`: The request
+    /// - `response`: A writer for the response, with its header and token composed already; its code is
+    ///   `4.04 Not Found` unless changed with `MessageWriter::set_code`
+    ///
+    /// The handler is also called with the registration request of each observer of a resource,
+    /// whenever the observers are notified (see `Server::run`).
+    async fn handle(
+        &self,
+        remote: SocketAddr,
+        request: &Message<'_>,
+        response: &mut MessageWriter<'_>,
+    ) -> Result<(), Self::Error>;
+}
+
+impl<H> Handler for &H
+where
+    H: Handler,
+{
+    type Error = H::Error;
+
+    async fn handle(
+        &self,
+        remote: SocketAddr,
+        request: &Message<'_>,
+        response: &mut MessageWriter<'_>,
+    ) -> Result<(), Self::Error> {
+        (**self).handle(remote, request, response).await
+    }
+}
+
+struct Observer {
+    remote: SocketAddr,
+    request: [u8; MAX_OBSERVE_REQUEST_LEN],
+    len: usize,
+    unacknowledged: Option<u16>,
+    unacknowledged_count: u8,
+}
+
+impl Observer {
+    fn request(&self) -> Option<Message<'_>> {
+        Message::parse(&self.request[..self.len]).ok()
+    }
+
+    fn is(&self, remote: SocketAddr, token: &[u8]) -> bool {
+        self.remote == remote
+            && self
+                .request()
+                .map(|request| request.token == token)
+                .unwrap_or(false)
+    }
+}
+
+/// A CoAP server, answering the requests with a `Handler`
+///
+/// The server keeps track of up to `O` observers of its resources (RFC 7641). The requests of clients
+/// registering as observers are stored, and answered again when the server is asked to notify the observers.
+///
+/// Block-wise transfers are left to the handler: `MessageWriter::block2_payload` returns the blocks of
+/// large representations, and the blocks of uploads arrive as separate requests, with a `Block1` option.
+pub struct Server<const O: usize = DEFAULT_MAX_OBSERVERS> {
+    observers: [Option<Observer>; O],
+    sequence: u32,
+    message_id: u16,
+    last_response: Option<(SocketAddr, u16, usize)>,
+}
+
+impl<const O: usize> Server<O> {
+    const NO_OBSERVER: Option<Observer> = None;
+
+    /// Create a new server
+    ///
+    /// Parameters:
+    /// - `rng`: A random number generator, used for the initial message ID of the notifications
+    pub fn new<R>(rng: &mut R) -> Self
+    where
+        R: RngCore,
+    {
+        Self {
+            observers: [Self::NO_OBSERVER; O],
+            sequence: 0,
+            message_id: rng.next_u32() as u16,
+            last_response: None,
+        }
+    }
+
+    /// Run the server on the provided socket, until an IO error occurs
+    ///
+    /// Parameters:
+    /// - `socket`: A UDP socket, usually bound to `coap::PORT`
+    /// - `tx_buf`, `rx_buf`: The buffers for the outgoing and the incoming messages
+    /// - `handler`: The handler of the requests
+    /// - `notify`: Signalling it makes the server notify all observers, by answering their registration
+    ///   requests again; signal it whenever an observable resource changes
+    pub async fn run<S, H, M>(
+        &mut self,
+        mut socket: S,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        handler: H,
+        notify: &Signal<M, ()>,
+    ) -> Result<(), Error<S::Error>>
+    where
+        S: UdpReceive + UdpSend,
+        H: Handler,
+        M: RawMutex,
+    {
+        loop {
+            let (len, remote) = match select(socket.receive(rx_buf), notify.wait()).await {
+                Either::First(result) => result.map_err(Error::Io)?,
+                Either::Second(()) => {
+                    self.notify(&mut socket, tx_buf, &handler).await?;
+                    continue;
+                }
+            };
+
+            self.respond(&mut socket, remote, &rx_buf[..len], tx_buf, &handler)
+                .await?;
+        }
+    }
+
+    async fn respond<S, H>(
+        &mut self,
+        socket: &mut S,
+        remote: SocketAddr,
+        data: &[u8],
+        tx_buf: &mut [u8],
+        handler: H,
+    ) -> Result<(), Error<S::Error>>
+    where
+        S: UdpReceive + UdpSend,
+        H: Handler,
+    {
+        let Ok(request) = Message::parse(data) else {
+            debug!("Ignoring an invalid message from {remote}");
+            return Ok(());
+        };
+
+        match request.ty {
+            MessageType::Acknowledgement | MessageType::Reset => {
+                self.acknowledged(remote, request.id, request.ty == MessageType::Reset);
+
+                return Ok(());
+            }
+            MessageType::Confirmable if !request.code.is_request() => {
+                // A ping (an empty confirmable message), or a message we do not expect
+                let len =
+                    MessageWriter::new(tx_buf, MessageType::Reset, Code::EMPTY, request.id, &[])?
+                        .finish();
+
+                self.last_response = None;
+
+                return socket.send(remote, &tx_buf[..len]).await.map_err(Error::Io);
+            }
+            MessageType::NonConfirmable if !request.code.is_request() => return Ok(()),
+            _ => (),
+        }
+
+        let confirmable = request.ty == MessageType::Confirmable;
+
+        if confirmable && self.last_response.map(|(r, id, _)| (r, id)) == Some((remote, request.id))
+        {
+            // A retransmission of the last request, whose acknowledgement got lost
+            let (_, _, len) = self.last_response.unwrap();
+
+            return socket.send(remote, &tx_buf[..len]).await.map_err(Error::Io);
+        }
+
+        let (ty, id) = if confirmable {
+            (MessageType::Acknowledgement, request.id)
+        } else {
+            (MessageType::NonConfirmable, self.new_message_id())
+        };
+
+        let mut response = MessageWriter::new(tx_buf, ty, Code::NOT_FOUND, id, request.token)?;
+
+        if let Err(e) = handler.handle(remote, &request, &mut response).await {
+            warn!("Handling a request from {remote} failed: {e:?}");
+
+            response =
+                MessageWriter::new(tx_buf, ty, Code::INTERNAL_SERVER_ERROR, id, request.token)?;
+        }
+
+        if request.code == Code::GET {
+            match request.observe() {
+                Some(0) if response.code().is_success() => {
+                    if self.register(remote, data) {
+                        response.uint_option(OptionNumber::OBSERVE, self.sequence)?;
+                    }
+                }
+                _ => self.deregister(remote, request.token),
+            }
+        }
+
+        let len = response.finish();
+
+        socket
+            .send(remote, &tx_buf[..len])
+            .await
+            .map_err(Error::Io)?;
+
+        self.last_response = confirmable.then_some((remote, request.id, len));
+
+        Ok(())
+    }
+
+    async fn notify<S, H>(
+        &mut self,
+        socket: &mut S,
+        tx_buf: &mut [u8],
+        handler: H,
+    ) -> Result<(), Error<S::Error>>
+    where
+        S: UdpReceive + UdpSend,
+        H: Handler,
+    {
+        self.sequence = (self.sequence + 1) & 0xff_ffff;
+        self.last_response = None;
+
+        for slot in &mut self.observers {
+            let Some(observer) = slot else {
+                continue;
+            };
+
+            if observer.unacknowledged.is_some() {
+                observer.unacknowledged_count += 1;
+
+                if observer.unacknowledged_count > MAX_UNACKNOWLEDGED_NOTIFICATIONS {
+                    debug!("Observer {} is gone, dropping it", observer.remote);
+
+                    *slot = None;
+                    continue;
+                }
+            }
+
+            let Some(request) = observer.request() else {
+                *slot = None;
+                continue;
+            };
+
+            // All notifications are confirmable, so that the observers which are gone can be detected
+            self.message_id = self.message_id.wrapping_add(1);
+            let id = self.message_id;
+
+            let mut response = MessageWriter::new(
+                tx_buf,
+                MessageType::Confirmable,
+                Code::NOT_FOUND,
+                id,
+                request.token,
+            )?;
+
+            let result = handler
+                .handle(observer.remote, &request, &mut response)
+                .await;
+
+            if let Err(e) = result {
+                warn!("Notifying observer {} failed: {e:?}", observer.remote);
+
+                response = MessageWriter::new(
+                    tx_buf,
+                    MessageType::Confirmable,
+                    Code::INTERNAL_SERVER_ERROR,
+                    id,
+                    request.token,
+                )?;
+            }
+
+            // A notification which is not a success ends the observation
+            let observing = response.code().is_success();
+
+            if observing {
+                response.uint_option(OptionNumber::OBSERVE, self.sequence)?;
+            }
+
+            let len = response.finish();
+            let remote = observer.remote;
+
+            socket
+                .send(remote, &tx_buf[..len])
+                .await
+                .map_err(Error::Io)?;
+
+            if observing {
+                observer.unacknowledged = Some(id);
+            } else {
+                *slot = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn register(&mut self, remote: SocketAddr, request: &[u8]) -> bool {
+        if request.len() > MAX_OBSERVE_REQUEST_LEN {
+            warn!("The observe request of {remote} is too long, not registering it");
+            return false;
+        }
+
+        let Ok(token) = Message::parse(request).map(|request| request.token) else {
+            return false;
+        };
+
+        let slot = match self
+            .observers
+            .iter()
+            .position(|slot| slot.as_ref().map(|o| o.is(remote, token)) == Some(true))
+        {
+            Some(index) => &mut self.observers[index],
+            None => match self.observers.iter_mut().find(|slot| slot.is_none()) {
+                Some(slot) => slot,
+                None => {
+                    warn!("No room for observer {remote}, not registering it");
+                    return false;
+                }
+            },
+        };
+
+        let mut observer = Observer {
+            remote,
+            request: [0; MAX_OBSERVE_REQUEST_LEN],
+            len: request.len(),
+            unacknowledged: None,
+            unacknowledged_count: 0,
+        };
+
+        observer.request[..request.len()].copy_from_slice(request);
+
+        *slot = Some(observer);
+
+        true
+    }
+
+    fn deregister(&mut self, remote: SocketAddr, token: &[u8]) {
+        for slot in &mut self.observers {
+            if slot.as_ref().map(|o| o.is(remote, token)) == Some(true) {
+                *slot = None;
+            }
+        }
+    }
+
+    fn acknowledged(&mut self, remote: SocketAddr, id: u16, reset: bool) {
+        for slot in &mut self.observers {
+            let Some(observer) = slot else {
+                continue;
+            };
+
+            if observer.remote == remote && observer.unacknowledged == Some(id) {
+                if reset {
+                    // The client is no longer interested (RFC 7641, section 3.6)
+                    *slot = None;
+                } else {
+                    observer.unacknowledged = None;
+                    observer.unacknowledged_count = 0;
+                }
+            }
+        }
+    }
+
+    fn new_message_id(&mut self) -> u16 {
+        self.message_id = self.message_id.wrapping_add(1);
+        self.message_id
+    }
+}
@@ -0,0 +1,883 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(async_fn_in_trait)]
+#![warn(clippy::large_futures)]
+
+use core::fmt;
+use core::str;
+
+#[cfg(feature = "io")]
+pub mod io;
+
+/// The UDP port of the CoAP servers
+pub const PORT: u16 = 5683;
+
+/// The version of the protocol, as per RFC 7252
+pub const VERSION: u8 = 1;
+
+/// The maximum length of a token
+pub const MAX_TOKEN_LEN: usize = 8;
+
+const HEADER_LEN: usize = 4;
+
+const PAYLOAD_MARKER: u8 = 0xff;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The buffer is too small for the message
+    BufferOverflow,
+    /// The message is truncated or malformed
+    InvalidMessage,
+    /// An option of the message is malformed
+    InvalidOption,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferOverflow => write!(f, "Buffer overflow"),
+            Self::InvalidMessage => write!(f, "Invalid message"),
+            Self::InvalidOption => write!(f, "Invalid option"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The type of a message (RFC 7252, section 4)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MessageType {
+    /// A message which requires an acknowledgement, and is retransmitted until it gets one
+    Confirmable,
+    /// A message which does not require an acknowledgement
+    NonConfirmable,
+    /// The acknowledgement of a confirmable message, possibly carrying the response to it
+    Acknowledgement,
+    /// The rejection of a message which could not be processed
+    Reset,
+}
+
+impl MessageType {
+    const fn from_bits(bits: u8) -> Self {
+        match bits & 0x03 {
+            0 => Self::Confirmable,
+            1 => Self::NonConfirmable,
+            2 => Self::Acknowledgement,
+            _ => Self::Reset,
+        }
+    }
+
+    const fn bits(&self) -> u8 {
+        match self {
+            Self::Confirmable => 0,
+            Self::NonConfirmable => 1,
+            Self::Acknowledgement => 2,
+            Self::Reset => 3,
+        }
+    }
+}
+
+/// The code of a message: the method of a request, or the status of a response,
+/// as a 3-bit class and a 5-bit detail (i.e. `2.05` is `Content`)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Code(pub u8);
+
+impl Code {
+    /// The code of the empty messages, i.e. the acknowledgements not carrying a response
+    pub const EMPTY: Self = Self::new(0, 0);
+
+    pub const GET: Self = Self::new(0, 1);
+    pub const POST: Self = Self::new(0, 2);
+    pub const PUT: Self = Self::new(0, 3);
+    pub const DELETE: Self = Self::new(0, 4);
+
+    pub const CREATED: Self = Self::new(2, 1);
+    pub const DELETED: Self = Self::new(2, 2);
+    pub const VALID: Self = Self::new(2, 3);
+    pub const CHANGED: Self = Self::new(2, 4);
+    pub const CONTENT: Self = Self::new(2, 5);
+    /// Asks the client to send the next block of a block-wise request (RFC 7959)
+    pub const CONTINUE: Self = Self::new(2, 31);
+
+    pub const BAD_REQUEST: Self = Self::new(4, 0);
+    pub const UNAUTHORIZED: Self = Self::new(4, 1);
+    pub const BAD_OPTION: Self = Self::new(4, 2);
+    pub const FORBIDDEN: Self = Self::new(4, 3);
+    pub const NOT_FOUND: Self = Self::new(4, 4);
+    pub const METHOD_NOT_ALLOWED: Self = Self::new(4, 5);
+    pub const NOT_ACCEPTABLE: Self = Self::new(4, 6);
+    pub const REQUEST_ENTITY_INCOMPLETE: Self = Self::new(4, 8);
+    pub const PRECONDITION_FAILED: Self = Self::new(4, 12);
+    pub const REQUEST_ENTITY_TOO_LARGE: Self = Self::new(4, 13);
+    pub const UNSUPPORTED_CONTENT_FORMAT: Self = Self::new(4, 15);
+
+    pub const INTERNAL_SERVER_ERROR: Self = Self::new(5, 0);
+    pub const NOT_IMPLEMENTED: Self = Self::new(5, 1);
+    pub const SERVICE_UNAVAILABLE: Self = Self::new(5, 3);
+
+    pub const fn new(class: u8, detail: u8) -> Self {
+        Self((class << 5) | (detail & 0x1f))
+    }
+
+    pub const fn class(&self) -> u8 {
+        self.0 >> 5
+    }
+
+    pub const fn detail(&self) -> u8 {
+        self.0 & 0x1f
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn is_request(&self) -> bool {
+        self.class() == 0 && !self.is_empty()
+    }
+
+    pub const fn is_response(&self) -> bool {
+        matches!(self.class(), 2..=5)
+    }
+
+    pub const fn is_success(&self) -> bool {
+        self.class() == 2
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.class(), self.detail())
+    }
+}
+
+/// The number of an option (RFC 7252, section 5.10, and the RFCs extending it)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct OptionNumber(pub u16);
+
+impl OptionNumber {
+    pub const IF_MATCH: Self = Self(1);
+    pub const URI_HOST: Self = Self(3);
+    pub const ETAG: Self = Self(4);
+    pub const IF_NONE_MATCH: Self = Self(5);
+    /// RFC 7641
+    pub const OBSERVE: Self = Self(6);
+    pub const URI_PORT: Self = Self(7);
+    pub const LOCATION_PATH: Self = Self(8);
+    pub const URI_PATH: Self = Self(11);
+    pub const CONTENT_FORMAT: Self = Self(12);
+    pub const MAX_AGE: Self = Self(14);
+    pub const URI_QUERY: Self = Self(15);
+    pub const ACCEPT: Self = Self(17);
+    pub const LOCATION_QUERY: Self = Self(20);
+    /// RFC 7959
+    pub const BLOCK2: Self = Self(23);
+    /// RFC 7959
+    pub const BLOCK1: Self = Self(27);
+    /// RFC 7959
+    pub const SIZE2: Self = Self(28);
+    pub const PROXY_URI: Self = Self(35);
+    pub const PROXY_SCHEME: Self = Self(39);
+    pub const SIZE1: Self = Self(60);
+
+    /// Return `true` if an endpoint which does not understand the option must reject the message
+    pub const fn is_critical(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+}
+
+/// The format of the payload of a message, as carried by the `Content-Format` and the `Accept` options
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ContentFormat(pub u16);
+
+impl ContentFormat {
+    pub const TEXT_PLAIN: Self = Self(0);
+    pub const LINK_FORMAT: Self = Self(40);
+    pub const XML: Self = Self(41);
+    pub const OCTET_STREAM: Self = Self(42);
+    pub const JSON: Self = Self(50);
+    pub const CBOR: Self = Self(60);
+    pub const SENML_JSON: Self = Self(110);
+    pub const SENML_CBOR: Self = Self(112);
+    pub const LWM2M_TLV: Self = Self(11542);
+    pub const LWM2M_JSON: Self = Self(11543);
+}
+
+/// The value of a `Block1` or a `Block2` option (RFC 7959, section 2.2)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Block {
+    /// The number of the block
+    pub num: u32,
+    /// Whether more blocks follow this one
+    pub more: bool,
+    /// The size of the block, as a power of two: `16 << szx` bytes
+    pub szx: u8,
+}
+
+impl Block {
+    /// The largest size of a block
+    pub const MAX_SIZE: usize = 1024;
+
+    /// Create a block, with `size` rounded down to a power of two between 16 and `Block::MAX_SIZE`
+    pub const fn new(num: u32, more: bool, size: usize) -> Self {
+        let size = if size < 16 {
+            16
+        } else if size > Self::MAX_SIZE {
+            Self::MAX_SIZE
+        } else {
+            size
+        };
+
+        Self {
+            num,
+            more,
+            szx: (usize::BITS - 1 - size.leading_zeros() - 4) as u8,
+        }
+    }
+
+    /// Decode the value of a `Block1` or a `Block2` option
+    pub const fn decode(value: u32) -> Result<Self, Error> {
+        let szx = (value & 0x07) as u8;
+
+        if szx == 7 || value >= 1 << 24 {
+            return Err(Error::InvalidOption);
+        }
+
+        Ok(Self {
+            num: value >> 4,
+            more: value & 0x08 != 0,
+            szx,
+        })
+    }
+
+    /// Encode the block as the value of a `Block1` or a `Block2` option
+    pub const fn encode(&self) -> u32 {
+        (self.num << 4) | ((self.more as u32) << 3) | self.szx as u32
+    }
+
+    /// The size of the block
+    pub const fn size(&self) -> usize {
+        16 << self.szx
+    }
+
+    /// The offset of the block in the whole payload
+    pub const fn offset(&self) -> usize {
+        self.num as usize * self.size()
+    }
+}
+
+/// A parsed CoAP message (RFC 7252, section 3), borrowing the data it is parsed from
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Message<'a> {
+    pub ty: MessageType,
+    pub code: Code,
+    pub id: u16,
+    pub token: &'a [u8],
+    options: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    /// Parse a message, validating its header and options
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_LEN || data[0] >> 6 != VERSION {
+            Err(Error::InvalidMessage)?;
+        }
+
+        let token_len = (data[0] & 0x0f) as usize;
+        if token_len > MAX_TOKEN_LEN || data.len() < HEADER_LEN + token_len {
+            Err(Error::InvalidMessage)?;
+        }
+
+        let code = Code(data[1]);
+
+        // The empty messages consist of the header only
+        if code.is_empty() && data.len() > HEADER_LEN {
+            Err(Error::InvalidMessage)?;
+        }
+
+        let options_start = HEADER_LEN + token_len;
+
+        let mut offset = options_start;
+        let mut number = 0_u16;
+
+        while offset < data.len() && data[offset] != PAYLOAD_MARKER {
+            let (delta, header_len, value_len) = read_option(&data[offset..])?;
+
+            number = number.checked_add(delta).ok_or(Error::InvalidOption)?;
+            offset += header_len + value_len;
+        }
+
+        let options = &data[options_start..offset];
+
+        let payload = if offset < data.len() {
+            // A payload marker followed by an empty payload is a format error
+            if offset + 1 == data.len() {
+                Err(Error::InvalidMessage)?;
+            }
+
+            &data[offset + 1..]
+        } else {
+            &[]
+        };
+
+        Ok(Self {
+            ty: MessageType::from_bits(data[0] >> 4),
+            code,
+            id: u16::from_be_bytes([data[2], data[3]]),
+            token: &data[HEADER_LEN..options_start],
+            options,
+            payload,
+        })
+    }
+
+    /// Return an iterator over the options of the message, in the order of their numbers
+    pub fn options(&self) -> Options<'a> {
+        Options {
+            data: self.options,
+            number: 0,
+        }
+    }
+
+    /// Return the value of the first option with the provided number
+    pub fn option(&self, number: OptionNumber) -> Option<&'a [u8]> {
+        self.options_of(number).next()
+    }
+
+    /// Return the values of all options with the provided number
+    pub fn options_of(&self, number: OptionNumber) -> impl Iterator<Item = &'a [u8]> {
+        self.options()
+            .filter(move |(n, _)| *n == number)
+            .map(|(_, value)| value)
+    }
+
+    /// Return the value of the first option with the provided number, decoded as an unsigned integer
+    pub fn uint_option(&self, number: OptionNumber) -> Option<u32> {
+        self.option(number).and_then(decode_uint)
+    }
+
+    /// Return the segments of the path of a request, as carried by its `Uri-Path` options
+    ///
+    /// Segments which are not valid UTF-8 are returned as `"\u{fffd}"`.
+    pub fn uri_path(&self) -> impl Iterator<Item = &'a str> {
+        self.options_of(OptionNumber::URI_PATH)
+            .map(|segment| str::from_utf8(segment).unwrap_or("\u{fffd}"))
+    }
+
+    /// Return the arguments of the query of a request, as carried by its `Uri-Query` options
+    ///
+    /// Arguments which are not valid UTF-8 are returned as `"\u{fffd}"`.
+    pub fn uri_query(&self) -> impl Iterator<Item = &'a str> {
+        self.options_of(OptionNumber::URI_QUERY)
+            .map(|arg| str::from_utf8(arg).unwrap_or("\u{fffd}"))
+    }
+
+    /// Return `true` if the path of a request is the provided one, i.e. `"sensors/temp"`
+    pub fn path_is(&self, path: &str) -> bool {
+        self.uri_path().eq(segments(path, '/'))
+    }
+
+    pub fn content_format(&self) -> Option<ContentFormat> {
+        self.uint_option(OptionNumber::CONTENT_FORMAT)
+            .and_then(|value| u16::try_from(value).ok())
+            .map(ContentFormat)
+    }
+
+    pub fn accept(&self) -> Option<ContentFormat> {
+        self.uint_option(OptionNumber::ACCEPT)
+            .and_then(|value| u16::try_from(value).ok())
+            .map(ContentFormat)
+    }
+
+    /// Return the value of the `Observe` option (RFC 7641): `0` registers an observer and `1` deregisters it
+    /// in requests, while in notifications it is their sequence number
+    pub fn observe(&self) -> Option<u32> {
+        self.uint_option(OptionNumber::OBSERVE)
+    }
+
+    /// Return the `Block1` option, carried by the requests uploading their payload block by block
+    pub fn block1(&self) -> Option<Block> {
+        self.uint_option(OptionNumber::BLOCK1)
+            .and_then(|value| Block::decode(value).ok())
+    }
+
+    /// Return the `Block2` option, carried by the responses returning their payload block by block
+    pub fn block2(&self) -> Option<Block> {
+        self.uint_option(OptionNumber::BLOCK2)
+            .and_then(|value| Block::decode(value).ok())
+    }
+}
+
+/// An iterator over the options of a message, returning their numbers and values
+#[derive(Clone, Debug)]
+pub struct Options<'a> {
+    data: &'a [u8],
+    number: u16,
+}
+
+impl<'a> Iterator for Options<'a> {
+    type Item = (OptionNumber, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        // The options are validated by `Message::parse` already
+        let (delta, header_len, value_len) = read_option(self.data).ok()?;
+
+        let value = &self.data[header_len..header_len + value_len];
+
+        self.data = &self.data[header_len + value_len..];
+        self.number += delta;
+
+        Some((OptionNumber(self.number), value))
+    }
+}
+
+/// A writer composing a CoAP message into a buffer
+///
+/// The options can be added in any order; they are sorted by their numbers, as the encoding requires.
+/// The payload can be set at any time too.
+pub struct MessageWriter<'a> {
+    buf: &'a mut [u8],
+    options_start: usize,
+    options_end: usize,
+    len: usize,
+}
+
+impl<'a> MessageWriter<'a> {
+    /// Create a writer, composing the header and the token of the message
+    pub fn new(
+        buf: &'a mut [u8],
+        ty: MessageType,
+        code: Code,
+        id: u16,
+        token: &[u8],
+    ) -> Result<Self, Error> {
+        if token.len() > MAX_TOKEN_LEN {
+            Err(Error::InvalidMessage)?;
+        }
+
+        let len = HEADER_LEN + token.len();
+        if buf.len() < len {
+            Err(Error::BufferOverflow)?;
+        }
+
+        buf[0] = (VERSION << 6) | (ty.bits() << 4) | token.len() as u8;
+        buf[1] = code.0;
+        buf[2..4].copy_from_slice(&id.to_be_bytes());
+        buf[HEADER_LEN..len].copy_from_slice(token);
+
+        Ok(Self {
+            buf,
+            options_start: len,
+            options_end: len,
+            len,
+        })
+    }
+
+    pub fn code(&self) -> Code {
+        Code(self.buf[1])
+    }
+
+    pub fn set_code(&mut self, code: Code) {
+        self.buf[1] = code.0;
+    }
+
+    /// Add an option
+    pub fn option(&mut self, number: OptionNumber, value: &[u8]) -> Result<(), Error> {
+        // Find the insertion point: after all options with a number not greater than `number`,
+        // so that repeated options keep the order in which they were added
+        let mut offset = self.options_start;
+        let mut prev = 0;
+        let mut next = None;
+
+        while offset < self.options_end {
+            let (delta, header_len, value_len) = read_option(&self.buf[offset..self.options_end])?;
+
+            if prev + delta > number.0 {
+                next = Some((prev + delta, header_len, value_len));
+                break;
+            }
+
+            prev += delta;
+            offset += header_len + value_len;
+        }
+
+        let mut header = [0; 5];
+        let header_len = write_option_header(&mut header, number.0 - prev, value.len())?;
+
+        // The delta of the next option shrinks, and so might its header
+        let mut next_header = [0; 5];
+        let (next_header_len, old_next_header_len) = match next {
+            Some((next_number, old_header_len, value_len)) => (
+                write_option_header(&mut next_header, next_number - number.0, value_len)?,
+                old_header_len,
+            ),
+            None => (0, 0),
+        };
+
+        let inserted = header_len + value.len() + next_header_len;
+
+        let len = self.len + inserted - old_next_header_len;
+        if len > self.buf.len() {
+            Err(Error::BufferOverflow)?;
+        }
+
+        self.buf
+            .copy_within(offset + old_next_header_len..self.len, offset + inserted);
+
+        for data in [
+            &header[..header_len],
+            value,
+            &next_header[..next_header_len],
+        ] {
+            self.buf[offset..offset + data.len()].copy_from_slice(data);
+            offset += data.len();
+        }
+
+        self.options_end += inserted - old_next_header_len;
+        self.len = len;
+
+        Ok(())
+    }
+
+    /// Add an option with an unsigned integer value, encoded in as few bytes as possible
+    pub fn uint_option(&mut self, number: OptionNumber, value: u32) -> Result<(), Error> {
+        let bytes = value.to_be_bytes();
+        let skip = (value.leading_zeros() / 8) as usize;
+
+        self.option(number, &bytes[skip..])
+    }
+
+    /// Add an option with a string value
+    pub fn str_option(&mut self, number: OptionNumber, value: &str) -> Result<(), Error> {
+        self.option(number, value.as_bytes())
+    }
+
+    /// Add the `Uri-Path` options of a request, one per segment of the provided path (i.e. `"sensors/temp"`)
+    pub fn uri_path(&mut self, path: &str) -> Result<(), Error> {
+        for segment in segments(path, '/') {
+            self.str_option(OptionNumber::URI_PATH, segment)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add the `Uri-Query` options of a request, one per argument of the provided query (i.e. `"ep=node1&lt=300"`)
+    pub fn uri_query(&mut self, query: &str) -> Result<(), Error> {
+        for arg in segments(query, '&') {
+            self.str_option(OptionNumber::URI_QUERY, arg)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn content_format(&mut self, format: ContentFormat) -> Result<(), Error> {
+        self.uint_option(OptionNumber::CONTENT_FORMAT, format.0 as _)
+    }
+
+    /// Set the payload, replacing the payload set previously (if any)
+    pub fn payload(&mut self, data: &[u8]) -> Result<(), Error> {
+        if data.is_empty() {
+            self.len = self.options_end;
+
+            return Ok(());
+        }
+
+        let len = self.options_end + 1 + data.len();
+        if len > self.buf.len() {
+            Err(Error::BufferOverflow)?;
+        }
+
+        self.buf[self.options_end] = PAYLOAD_MARKER;
+        self.buf[self.options_end + 1..len].copy_from_slice(data);
+        self.len = len;
+
+        Ok(())
+    }
+
+    /// Set the payload of a response to the block of `data` requested by the `Block2` option of the request
+    /// (RFC 7959, section 2.4), adding the `Block2` option (and the `Size2` option, with the first block).
+    ///
+    /// The blocks are no larger than `max_size`, so that the response fits in the buffer.
+    /// If `data` fits into one block and the request did not ask for a block, the whole `data` is set as payload.
+    /// If the request asked for a block past the end of `data`, the code of the response is set to `4.02 Bad Option`.
+    pub fn block2_payload(
+        &mut self,
+        requested: Option<Block>,
+        max_size: usize,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let max = Block::new(0, false, max_size);
+
+        let (size, offset) = match requested {
+            Some(requested) => (requested.size().min(max.size()), requested.offset()),
+            None if data.len() <= max.size() => return self.payload(data),
+            None => (max.size(), 0),
+        };
+
+        if offset >= data.len() && offset > 0 {
+            self.set_code(Code::BAD_OPTION);
+
+            return self.payload(&[]);
+        }
+
+        let end = data.len().min(offset + size);
+        let block = Block::new((offset / size) as u32, end < data.len(), size);
+
+        self.uint_option(OptionNumber::BLOCK2, block.encode())?;
+
+        if block.num == 0 {
+            self.uint_option(OptionNumber::SIZE2, data.len() as _)?;
+        }
+
+        self.payload(&data[offset..end])
+    }
+
+    /// Return the length of the composed message
+    pub fn finish(self) -> usize {
+        self.len
+    }
+}
+
+/// Decode the value of an unsigned integer option, which is at most 4 bytes long
+pub fn decode_uint(value: &[u8]) -> Option<u32> {
+    (value.len() <= 4).then(|| value.iter().fold(0, |acc, byte| (acc << 8) | *byte as u32))
+}
+
+fn segments(s: &str, separator: char) -> impl Iterator<Item = &str> {
+    s.split(separator).filter(|segment| !segment.is_empty())
+}
+
+/// Read the header of the option at the start of `data`, returning its delta,
+/// the length of its header and the length of its value
+fn read_option(data: &[u8]) -> Result<(u16, usize, usize), Error> {
+    let mut header_len = 1;
+
+    let delta = read_ext(data[0] >> 4, data, &mut header_len)?;
+    let value_len = read_ext(data[0] & 0x0f, data, &mut header_len)? as usize;
+
+    if data.len() < header_len + value_len {
+        Err(Error::InvalidOption)?;
+    }
+
+    Ok((delta, header_len, value_len))
+}
+
+fn read_ext(nibble: u8, data: &[u8], offset: &mut usize) -> Result<u16, Error> {
+    match nibble {
+        0..=12 => Ok(nibble as _),
+        13 => {
+            let byte = *data.get(*offset).ok_or(Error::InvalidOption)?;
+            *offset += 1;
+
+            Ok(byte as u16 + 13)
+        }
+        14 => {
+            let bytes = data.get(*offset..*offset + 2).ok_or(Error::InvalidOption)?;
+            *offset += 2;
+
+            u16::from_be_bytes([bytes[0], bytes[1]])
+                .checked_add(269)
+                .ok_or(Error::InvalidOption)
+        }
+        // 15 is reserved for the payload marker
+        _ => Err(Error::InvalidOption),
+    }
+}
+
+fn write_option_header(buf: &mut [u8; 5], delta: u16, value_len: usize) -> Result<usize, Error> {
+    let value_len = u16::try_from(value_len).map_err(|_| Error::BufferOverflow)?;
+
+    let mut len = 1;
+
+    let delta_nibble = write_ext(buf, delta, &mut len);
+    let len_nibble = write_ext(buf, value_len, &mut len);
+
+    buf[0] = (delta_nibble << 4) | len_nibble;
+
+    Ok(len)
+}
+
+fn write_ext(buf: &mut [u8; 5], value: u16, offset: &mut usize) -> u8 {
+    if value < 13 {
+        value as _
+    } else if value < 269 {
+        buf[*offset] = (value - 13) as _;
+        *offset += 1;
+
+        13
+    } else {
+        buf[*offset..*offset + 2].copy_from_slice(&(value - 269).to_be_bytes());
+        *offset += 2;
+
+        14
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Block, Code, ContentFormat, Error, Message, MessageType, MessageWriter};
+    use crate::{OptionNumber, MAX_TOKEN_LEN};
+
+    #[test]
+    fn compose_and_parse() {
+        let mut buf = [0; 128];
+
+        let mut writer = MessageWriter::new(
+            &mut buf,
+            MessageType::Confirmable,
+            Code::POST,
+            0x1234,
+            &[1, 2, 3, 4],
+        )
+        .unwrap();
+
+        // Out of order, as the users of the writer are allowed to add them
+        writer.content_format(ContentFormat::JSON).unwrap();
+        writer.uri_query("ep=node1&lt=300").unwrap();
+        writer.uri_path("/rd/").unwrap();
+        writer.uint_option(OptionNumber::OBSERVE, 0).unwrap();
+        writer.option(OptionNumber::SIZE1, &[0x01, 0x00]).unwrap();
+        writer.payload(b"{}").unwrap();
+
+        let len = writer.finish();
+
+        let message = Message::parse(&buf[..len]).unwrap();
+
+        assert_eq!(message.ty, MessageType::Confirmable);
+        assert_eq!(message.code, Code::POST);
+        assert_eq!(message.id, 0x1234);
+        assert_eq!(message.token, &[1, 2, 3, 4]);
+        assert!(message.path_is("rd"));
+        assert!(message.uri_query().eq(["ep=node1", "lt=300"]));
+        assert_eq!(message.content_format(), Some(ContentFormat::JSON));
+        assert_eq!(message.observe(), Some(0));
+        assert_eq!(message.uint_option(OptionNumber::SIZE1), Some(256));
+        assert_eq!(message.payload, b"{}");
+
+        assert!(message
+            .options()
+            .map(|(number, _)| number.0)
+            .eq([6, 11, 12, 15, 15, 60]));
+    }
+
+    #[test]
+    fn option_encoding() {
+        let mut buf = [0; 600];
+
+        let long = [0x55; 300];
+
+        let mut writer = MessageWriter::new(
+            &mut buf,
+            MessageType::Acknowledgement,
+            Code::CONTENT,
+            7,
+            &[],
+        )
+        .unwrap();
+
+        writer.option(OptionNumber(2000), &[]).unwrap();
+        writer.option(OptionNumber::PROXY_URI, &long).unwrap();
+        writer.option(OptionNumber::ETAG, &[9]).unwrap();
+
+        let len = writer.finish();
+
+        // ETag: 1 byte header, delta 4; Proxy-Uri: 4 byte header, delta 31 and length 300;
+        // the number 2000: 3 byte header, delta 1965, as it shrank twice
+        assert_eq!(len, 4 + 2 + 4 + 300 + 3);
+        assert_eq!(&buf[4..6], &[0x41, 9]);
+        assert_eq!(&buf[6..10], &[0xde, 31 - 13, 0, 31]);
+        assert_eq!(&buf[310..313], &[0xe0, 0x06, 0xa0]);
+
+        let message = Message::parse(&buf[..len]).unwrap();
+
+        assert_eq!(message.option(OptionNumber::ETAG), Some(&[9][..]));
+        assert_eq!(message.option(OptionNumber::PROXY_URI), Some(&long[..]));
+        assert_eq!(message.option(OptionNumber(2000)), Some(&[][..]));
+        assert!(message.payload.is_empty());
+    }
+
+    #[test]
+    fn invalid_messages() {
+        // Wrong version
+        assert_eq!(
+            Message::parse(&[0x80, 0x01, 0, 0]),
+            Err(Error::InvalidMessage)
+        );
+        // Token too long
+        let mut data = [0; 4 + MAX_TOKEN_LEN + 1];
+        data[0] = 0x40 | (MAX_TOKEN_LEN as u8 + 1);
+        data[1] = 0x01;
+        assert_eq!(Message::parse(&data), Err(Error::InvalidMessage));
+        // Payload marker without payload
+        assert_eq!(
+            Message::parse(&[0x40, 0x01, 0, 0, 0xff]),
+            Err(Error::InvalidMessage)
+        );
+        // Option value past the end of the message
+        assert_eq!(
+            Message::parse(&[0x40, 0x01, 0, 0, 0xb3, b'a']),
+            Err(Error::InvalidOption)
+        );
+        // Empty message with a token
+        assert_eq!(
+            Message::parse(&[0x41, 0x00, 0, 0, 1]),
+            Err(Error::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn block2_payload() {
+        let data = [0xaa; 100];
+
+        let mut buf = [0; 128];
+
+        let mut response = |requested| -> (Code, Option<Block>, Option<u32>, usize) {
+            let mut writer = MessageWriter::new(
+                &mut buf,
+                MessageType::Acknowledgement,
+                Code::CONTENT,
+                1,
+                &[],
+            )
+            .unwrap();
+
+            writer.block2_payload(requested, 64, &data).unwrap();
+            let len = writer.finish();
+
+            let message = Message::parse(&buf[..len]).unwrap();
+
+            (
+                message.code,
+                message.block2(),
+                message.uint_option(OptionNumber::SIZE2),
+                message.payload.len(),
+            )
+        };
+
+        assert_eq!(
+            response(None),
+            (Code::CONTENT, Some(Block::new(0, true, 64)), Some(100), 64)
+        );
+        // A larger block than `max_size` is requested
+        assert_eq!(
+            response(Some(Block::new(0, false, 256))),
+            (Code::CONTENT, Some(Block::new(0, true, 64)), Some(100), 64)
+        );
+        assert_eq!(
+            response(Some(Block::new(1, false, 64))),
+            (Code::CONTENT, Some(Block::new(1, false, 64)), None, 36)
+        );
+        assert_eq!(
+            response(Some(Block::new(6, false, 32))),
+            (Code::BAD_OPTION, None, None, 0)
+        );
+
+        let block = Block::new(5, true, 100);
+        assert_eq!((block.size(), block.offset()), (64, 320));
+        assert_eq!(Block::decode(block.encode()), Ok(block));
+        assert_eq!(Block::decode(0x07), Err(Error::InvalidOption));
+    }
+}
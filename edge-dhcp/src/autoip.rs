@@ -0,0 +1,199 @@
+//! IPv4 link-local address autoconfiguration, as per RFC 3927.
+//!
+//! Used as a fallback when no DHCP server is available: the host picks a random address
+//! from 169.254.1.0 - 169.254.254.255, checks via ARP probes that no other host uses it,
+//! claims it via ARP announcements and then defends it against conflicting hosts.
+//!
+//! Like the rest of the crate, this module is unaware of the transport and only produces
+//! and interprets ARP packets. See `io::autoip` for a runner on top of a raw socket.
+
+use rand_core::RngCore;
+
+use edge_raw::arp::ArpPacket;
+
+use super::Ipv4Addr;
+
+/// The maximum initial random delay before the first probe, in milliseconds
+pub const PROBE_WAIT_MS: u64 = 1000;
+/// The number of probes sent for a candidate address
+pub const PROBE_NUM: usize = 3;
+/// The minimum delay until repeated probe, in milliseconds
+pub const PROBE_MIN_MS: u64 = 1000;
+/// The maximum delay until repeated probe, in milliseconds
+pub const PROBE_MAX_MS: u64 = 2000;
+/// The delay before announcing, in milliseconds
+pub const ANNOUNCE_WAIT_MS: u64 = 2000;
+/// The number of announcements sent once an address is claimed
+pub const ANNOUNCE_NUM: usize = 2;
+/// The time between announcements, in milliseconds
+pub const ANNOUNCE_INTERVAL_MS: u64 = 2000;
+/// The number of conflicts after which the rate of probing is limited
+pub const MAX_CONFLICTS: usize = 10;
+/// The delay between successive probing attempts once `MAX_CONFLICTS` is reached, in milliseconds
+pub const RATE_LIMIT_INTERVAL_MS: u64 = 60000;
+/// The minimum interval between defensive announcements, in milliseconds;
+/// a second conflict within this interval makes the host give up the address
+pub const DEFEND_INTERVAL_MS: u64 = 10000;
+
+/// Return `true` if the provided address is an IPv4 link-local address (169.254.0.0/16)
+pub const fn is_link_local(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+
+    octets[0] == 169 && octets[1] == 254
+}
+
+/// Return `true` if the provided packet - received by the host with the provided MAC address while probing `ip` -
+/// indicates that another host is either using the address, or is probing for it too (RFC 5227)
+pub fn is_probe_conflict(mac: &[u8; 6], packet: &ArpPacket, ip: Ipv4Addr) -> bool {
+    packet.sender_mac != *mac
+        && (packet.sender_ip == ip || packet.is_probe() && packet.target_ip == ip)
+}
+
+/// A simple IPv4 link-local address autoconfiguration state machine helper.
+///
+/// RFC 3927 recommends that the random number generator is seeded with something host-specific
+/// (like the MAC address), so that the host tends to pick the same address after a restart,
+/// and so that different hosts pick different addresses.
+pub struct AutoIp<T> {
+    pub rng: T,
+    pub mac: [u8; 6],
+}
+
+impl<T> AutoIp<T>
+where
+    T: RngCore,
+{
+    pub const fn new(rng: T, mac: [u8; 6]) -> Self {
+        Self { rng, mac }
+    }
+
+    /// Pick a random candidate address from 169.254.1.0 - 169.254.254.255
+    pub fn candidate(&mut self) -> Ipv4Addr {
+        let n = self.rng.next_u32() % (254 * 256);
+
+        Ipv4Addr::new(169, 254, 1 + (n / 256) as u8, (n % 256) as u8)
+    }
+
+    /// Return a random delay in the `[min_ms, max_ms]` interval, in milliseconds
+    pub fn delay_ms(&mut self, min_ms: u64, max_ms: u64) -> u64 {
+        min_ms + self.rng.next_u64() % (max_ms - min_ms + 1)
+    }
+
+    /// Create an ARP probe for the provided candidate address
+    pub fn probe(&self, ip: Ipv4Addr) -> ArpPacket {
+        ArpPacket::probe(self.mac, ip)
+    }
+
+    /// Create an ARP announcement for the provided claimed address
+    pub fn announcement(&self, ip: Ipv4Addr) -> ArpPacket {
+        ArpPacket::announcement(self.mac, ip)
+    }
+
+    /// Return `true` if the provided packet - received while probing `ip` - indicates
+    /// that another host is either using the address, or is probing for it too
+    pub fn is_probe_conflict(&self, packet: &ArpPacket, ip: Ipv4Addr) -> bool {
+        is_probe_conflict(&self.mac, packet, ip)
+    }
+
+    /// Return `true` if the provided packet - received while using `ip` - indicates
+    /// that another host is using the address as well
+    pub fn is_conflict(&self, packet: &ArpPacket, ip: Ipv4Addr) -> bool {
+        packet.sender_mac != self.mac && packet.sender_ip == ip
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+    const OTHER_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+    const IP: Ipv4Addr = Ipv4Addr::new(169, 254, 17, 42);
+    const OTHER_IP: Ipv4Addr = Ipv4Addr::new(169, 254, 17, 43);
+
+    /// A "random" number generator returning the provided values in a cycle
+    struct CycleRng<'a>(&'a [u64], usize);
+
+    impl RngCore for CycleRng<'_> {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as _
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            let value = self.0[self.1 % self.0.len()];
+            self.1 += 1;
+
+            value
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            dest.fill(0);
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_candidate() {
+        let mut autoip = AutoIp::new(CycleRng(&[0, 254 * 256 - 1, 254 * 256, 0x1234], 0), MAC);
+
+        // The first and the last 256 addresses of 169.254.0.0/16 are reserved
+        assert_eq!(autoip.candidate(), Ipv4Addr::new(169, 254, 1, 0));
+        assert_eq!(autoip.candidate(), Ipv4Addr::new(169, 254, 254, 255));
+        assert_eq!(autoip.candidate(), Ipv4Addr::new(169, 254, 1, 0));
+        assert_eq!(autoip.candidate(), Ipv4Addr::new(169, 254, 0x13, 0x34));
+
+        assert!(is_link_local(autoip.candidate()));
+        assert!(!is_link_local(Ipv4Addr::new(192, 168, 0, 1)));
+
+        let mut autoip = AutoIp::new(CycleRng(&[0, 1000, 1001], 0), MAC);
+
+        assert_eq!(autoip.delay_ms(PROBE_MIN_MS, PROBE_MAX_MS), PROBE_MIN_MS);
+        assert_eq!(autoip.delay_ms(PROBE_MIN_MS, PROBE_MAX_MS), PROBE_MAX_MS);
+        assert_eq!(autoip.delay_ms(PROBE_MIN_MS, PROBE_MAX_MS), PROBE_MIN_MS);
+    }
+
+    #[test]
+    fn test_probe_conflict() {
+        let autoip = AutoIp::new(CycleRng(&[0], 0), MAC);
+
+        // Another host uses the address
+        assert!(autoip.is_probe_conflict(&ArpPacket::announcement(OTHER_MAC, IP), IP));
+        assert!(autoip.is_probe_conflict(
+            &ArpPacket::reply(OTHER_MAC, IP, MAC, Ipv4Addr::UNSPECIFIED),
+            IP
+        ));
+
+        // Another host probes for the address too
+        assert!(autoip.is_probe_conflict(&ArpPacket::probe(OTHER_MAC, IP), IP));
+
+        // Our own packets, looped back
+        assert!(!autoip.is_probe_conflict(&autoip.probe(IP), IP));
+        assert!(!autoip.is_probe_conflict(&autoip.announcement(IP), IP));
+
+        // Traffic about other addresses
+        assert!(!autoip.is_probe_conflict(&ArpPacket::probe(OTHER_MAC, OTHER_IP), IP));
+        assert!(!autoip.is_probe_conflict(&ArpPacket::announcement(OTHER_MAC, OTHER_IP), IP));
+
+        // A regular request for the address (i.e. with a sender address) is not a probe
+        assert!(!autoip.is_probe_conflict(&ArpPacket::request(OTHER_MAC, OTHER_IP, IP), IP));
+    }
+
+    #[test]
+    fn test_conflict() {
+        let autoip = AutoIp::new(CycleRng(&[0], 0), MAC);
+
+        assert!(autoip.is_conflict(&ArpPacket::announcement(OTHER_MAC, IP), IP));
+        assert!(autoip.is_conflict(&ArpPacket::request(OTHER_MAC, IP, OTHER_IP), IP));
+
+        // Once the address is claimed, probes of other hosts are answered by the ARP layer rather than defended against
+        assert!(!autoip.is_conflict(&ArpPacket::probe(OTHER_MAC, IP), IP));
+        assert!(!autoip.is_conflict(&autoip.announcement(IP), IP));
+        assert!(!autoip.is_conflict(&ArpPacket::request(OTHER_MAC, OTHER_IP, IP), IP));
+    }
+}
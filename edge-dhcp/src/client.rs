@@ -2,22 +2,124 @@ use rand_core::RngCore;
 
 use super::*;
 
+/// Additional options that the DHCP client sends to the server.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ClientOptions<'a> {
+    /// Option 12: the host name of the client, as displayed by many routers in their UI
+    pub hostname: Option<&'a str>,
+    /// Option 61: the client identifier; if not set, the server identifies the client by its MAC address
+    pub client_id: Option<&'a [u8]>,
+    /// Option 60: the vendor class identifier
+    pub vendor_class: Option<&'a [u8]>,
+    /// Option 55: the codes of the options that the client requests from the server;
+    /// if not set, the router, subnet and DNS options are requested
+    pub params: Option<&'a [u8]>,
+}
+
+impl<'a> ClientOptions<'a> {
+    /// Create a new `ClientOptions` instance, with no additional options
+    pub const fn new() -> Self {
+        Self {
+            hostname: None,
+            client_id: None,
+            vendor_class: None,
+            params: None,
+        }
+    }
+
+    fn options<'o>(
+        &self,
+        mt: MessageType,
+        requested_ip: Option<Ipv4Addr>,
+        buf: &'o mut [DhcpOption<'o>],
+    ) -> Options<'o>
+    where
+        'a: 'o,
+    {
+        let negotiation = matches!(mt, MessageType::Discover | MessageType::Request);
+
+        let options = [
+            Some(DhcpOption::MessageType(mt)),
+            requested_ip.map(DhcpOption::RequestedIpAddress),
+            negotiation.then(|| {
+                DhcpOption::ParameterRequestList(self.params.unwrap_or(Options::REQUEST_PARAMS))
+            }),
+            self.hostname
+                .filter(|_| negotiation)
+                .map(DhcpOption::HostName),
+            self.vendor_class
+                .filter(|_| negotiation)
+                .map(DhcpOption::VendorClassIdentifier),
+            self.client_id.map(DhcpOption::ClientIdentifier),
+        ];
+
+        let mut offset = 0;
+
+        for option in options.into_iter().flatten() {
+            buf[offset] = option;
+            offset += 1;
+        }
+
+        Options::new(&buf[..offset])
+    }
+}
+
+impl Default for ClientOptions<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A simple DHCP client.
 /// The client is unaware of the IP/UDP transport layer and operates purely in terms of packets
 /// represented as Rust slices.
 ///
 /// As such, the client can generate all BOOTP requests and parse BOOTP replies.
-pub struct Client<T> {
+///
+/// On multi-homed hosts, one client instance should be used per network interface,
+/// each one with the MAC address of its interface.
+pub struct Client<'a, T> {
     pub rng: T,
     pub mac: [u8; 6],
+    pub options: ClientOptions<'a>,
+    /// An application-defined index of the network interface the client operates on; 0 by default
+    ///
+    /// Not used by the protocol itself, but copied into the leases negotiated by the client,
+    /// so that on multi-homed hosts these can be told apart.
+    pub interface: u32,
 }
 
-impl<T> Client<T>
+impl<'a, T> Client<'a, T>
 where
     T: RngCore,
 {
     pub const fn new(rng: T, mac: [u8; 6]) -> Self {
-        Self { rng, mac }
+        Self::new_with_options(rng, mac, ClientOptions::new())
+    }
+
+    pub const fn new_with_options(rng: T, mac: [u8; 6], options: ClientOptions<'a>) -> Self {
+        Self {
+            rng,
+            mac,
+            options,
+            interface: 0,
+        }
+    }
+
+    /// Create a new client operating on the network interface with the provided application-defined index
+    pub const fn new_with_interface(
+        rng: T,
+        mac: [u8; 6],
+        options: ClientOptions<'a>,
+        interface: u32,
+    ) -> Self {
+        Self {
+            rng,
+            mac,
+            options,
+            interface,
+        }
     }
 
     pub fn discover<'o>(
@@ -25,8 +127,13 @@ where
         opt_buf: &'o mut [DhcpOption<'o>],
         secs: u16,
         ip: Option<Ipv4Addr>,
-    ) -> (Packet<'o>, u32) {
-        self.bootp_request(secs, None, true, Options::discover(ip, opt_buf))
+    ) -> (Packet<'o>, u32)
+    where
+        'a: 'o,
+    {
+        let options = self.options.options(MessageType::Discover, ip, opt_buf);
+
+        self.bootp_request(secs, None, true, options)
     }
 
     pub fn request<'o>(
@@ -35,8 +142,35 @@ where
         secs: u16,
         ip: Ipv4Addr,
         broadcast: bool,
-    ) -> (Packet<'o>, u32) {
-        self.bootp_request(secs, None, broadcast, Options::request(ip, opt_buf))
+    ) -> (Packet<'o>, u32)
+    where
+        'a: 'o,
+    {
+        let options = self
+            .options
+            .options(MessageType::Request, Some(ip), opt_buf);
+
+        self.bootp_request(secs, None, broadcast, options)
+    }
+
+    /// Create a DHCPREQUEST for extending the lease of `ip`
+    ///
+    /// `broadcast` is the BOOTP broadcast flag and should be `false`, as the client can receive unicast replies
+    /// with its IP configured. The request itself is unicast to the server which granted the lease in the RENEWING state,
+    /// and broadcast in the REBINDING state.
+    pub fn renew<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        secs: u16,
+        ip: Ipv4Addr,
+        broadcast: bool,
+    ) -> (Packet<'o>, u32)
+    where
+        'a: 'o,
+    {
+        let options = self.options.options(MessageType::Request, None, opt_buf);
+
+        self.bootp_request(secs, Some(ip), broadcast, options)
     }
 
     pub fn release<'o>(
@@ -44,9 +178,13 @@ where
         opt_buf: &'o mut [DhcpOption<'o>],
         secs: u16,
         ip: Ipv4Addr,
-    ) -> Packet<'o> {
-        self.bootp_request(secs, Some(ip), false, Options::release(opt_buf))
-            .0
+    ) -> Packet<'o>
+    where
+        'a: 'o,
+    {
+        let options = self.options.options(MessageType::Release, None, opt_buf);
+
+        self.bootp_request(secs, Some(ip), false, options).0
     }
 
     pub fn decline<'o>(
@@ -54,9 +192,13 @@ where
         opt_buf: &'o mut [DhcpOption<'o>],
         secs: u16,
         ip: Ipv4Addr,
-    ) -> Packet<'o> {
-        self.bootp_request(secs, Some(ip), false, Options::decline(opt_buf))
-            .0
+    ) -> Packet<'o>
+    where
+        'a: 'o,
+    {
+        let options = self.options.options(MessageType::Decline, None, opt_buf);
+
+        self.bootp_request(secs, Some(ip), false, options).0
     }
 
     pub fn is_offer(&self, reply: &Packet<'_>, xid: u32) -> bool {
@@ -0,0 +1,943 @@
+//! DHCPv6 messages and options, as per RFC 8415
+//!
+//! Only the subset necessary for a client doing stateful (IA_NA) and stateless
+//! (INFORMATION-REQUEST) configuration is modeled; all other options are preserved as `DhcpOption::Unrecognized`.
+
+use core::fmt;
+
+pub use core::net::Ipv6Addr;
+
+use num_enum::TryFromPrimitive;
+
+use edge_raw::bytes::{BytesIn, BytesOut};
+
+use crate::{EitherIterator, Error};
+
+pub mod client;
+
+/// The link-scoped multicast address of all DHCPv6 relay agents and servers
+pub const ALL_DHCP_RELAY_AGENTS_AND_SERVERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+
+///
+/// DHCPv6 Message Type.
+///
+/// # Standards
+///
+/// The semantics of the various DHCPv6 message types are described in Section 7.3 of RFC 8415.
+///
+#[derive(Copy, Clone, PartialEq, Eq, Debug, TryFromPrimitive)]
+#[repr(u8)]
+pub enum MessageType {
+    /// Client multicast to locate available servers.
+    Solicit = 1,
+
+    /// Server to client in response to SOLICIT, indicating that the server is available.
+    Advertise = 2,
+
+    /// Client to a specific server, requesting addresses and configuration parameters.
+    Request = 3,
+
+    /// Client to any server, checking whether its addresses are still appropriate for the link.
+    Confirm = 4,
+
+    /// Client to the server which assigned its addresses, extending their lifetimes.
+    Renew = 5,
+
+    /// Client to any server, extending the lifetimes of its addresses, after RENEW went unanswered.
+    Rebind = 6,
+
+    /// Server to client, carrying the assigned addresses and configuration parameters.
+    Reply = 7,
+
+    /// Client to server, relinquishing its addresses.
+    Release = 8,
+
+    /// Client to server, indicating that an assigned address is already in use on the link.
+    Decline = 9,
+
+    /// Server to client, informing the client that it should renew or re-request its configuration.
+    Reconfigure = 10,
+
+    /// Client to server, asking only for configuration parameters without any addresses.
+    InformationRequest = 11,
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Solicit => "SOLICIT",
+            Self::Advertise => "ADVERTISE",
+            Self::Request => "REQUEST",
+            Self::Confirm => "CONFIRM",
+            Self::Renew => "RENEW",
+            Self::Rebind => "REBIND",
+            Self::Reply => "REPLY",
+            Self::Release => "RELEASE",
+            Self::Decline => "DECLINE",
+            Self::Reconfigure => "RECONFIGURE",
+            Self::InformationRequest => "INFORMATION-REQUEST",
+        }
+        .fmt(f)
+    }
+}
+
+/// DHCPv6 Client/Server Message Structure
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Message<'a> {
+    pub message_type: MessageType,
+    /// The transaction ID; only the lower 24 bits are used
+    pub xid: u32,
+    pub options: Options<'a>,
+}
+
+impl<'a> Message<'a> {
+    pub const fn new(message_type: MessageType, xid: u32, options: Options<'a>) -> Self {
+        Self {
+            message_type,
+            xid: xid & 0x00ff_ffff,
+            options,
+        }
+    }
+
+    /// Parses the message from a byte slice
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        let message_type = TryFromPrimitive::try_from_primitive(bytes.byte()?)
+            .map_err(|_| Error::InvalidMessageType)?;
+
+        let xid = bytes.arr::<3>()?;
+
+        Ok(Self {
+            message_type,
+            xid: u32::from_be_bytes([0, xid[0], xid[1], xid[2]]),
+            options: Options(OptionsInner::decode(bytes.remaining())?),
+        })
+    }
+
+    /// Encodes the message into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        bytes
+            .byte(self.message_type as _)?
+            .push(&self.xid.to_be_bytes()[1..])?;
+
+        self.options.0.encode(&mut bytes)?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Options<'a>(OptionsInner<'a>);
+
+impl<'a> Options<'a> {
+    pub const fn new(options: &'a [DhcpOption<'a>]) -> Self {
+        Self(OptionsInner::DataSlice(options))
+    }
+
+    #[inline(always)]
+    pub const fn buf() -> [DhcpOption<'a>; 8] {
+        [DhcpOption::RapidCommit; 8]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = DhcpOption<'a>> + 'a {
+        self.0.iter()
+    }
+
+    /// Return the Client Identifier (DUID) option, if present
+    pub fn client_id(&self) -> Option<&'a [u8]> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::ClientId(duid) = option {
+                Some(duid)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Return the Server Identifier (DUID) option, if present
+    pub fn server_id(&self) -> Option<&'a [u8]> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::ServerId(duid) = option {
+                Some(duid)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Return the IA_NA option with the provided IAID, if present
+    pub fn ia_na(&self, iaid: u32) -> Option<IaNa<'a>> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::IaNa(ia) = option {
+                (ia.iaid == iaid).then_some(ia)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Return the first IA Address option, if present
+    pub fn ia_address(&self) -> Option<IaAddress<'a>> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::IaAddress(address) = option {
+                Some(address)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Return the Status Code option, if present
+    ///
+    /// As per RFC 8415, a missing Status Code option means success.
+    pub fn status(&self) -> Option<Status<'a>> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::StatusCode(status) = option {
+                Some(status)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Return the server preference, defaulting to 0 if the Preference option is missing
+    pub fn preference(&self) -> u8 {
+        self.iter()
+            .find_map(|option| {
+                if let DhcpOption::Preference(preference) = option {
+                    Some(preference)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(0)
+    }
+
+    /// Return `true` if the Rapid Commit option is present
+    pub fn rapid_commit(&self) -> bool {
+        self.iter()
+            .any(|option| matches!(option, DhcpOption::RapidCommit))
+    }
+}
+
+impl fmt::Debug for Options<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum OptionsInner<'a> {
+    ByteSlice(&'a [u8]),
+    DataSlice(&'a [DhcpOption<'a>]),
+}
+
+impl<'a> OptionsInner<'a> {
+    fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        while !bytes.is_empty() {
+            DhcpOption::decode(&mut bytes)?;
+        }
+
+        Ok(Self::ByteSlice(data))
+    }
+
+    fn encode(&self, buf: &mut BytesOut) -> Result<(), Error> {
+        for option in self.iter() {
+            option.encode(&mut |data| {
+                buf.push(data)?;
+
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = DhcpOption<'a>> + 'a {
+        struct ByteSliceDhcpOptions<'a>(BytesIn<'a>);
+
+        impl<'a> Iterator for ByteSliceDhcpOptions<'a> {
+            type Item = DhcpOption<'a>;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                if self.0.is_empty() {
+                    None
+                } else {
+                    // Cannot fail, as the options were validated when decoded
+                    Some(DhcpOption::decode(&mut self.0).unwrap())
+                }
+            }
+        }
+
+        match self {
+            Self::ByteSlice(data) => {
+                EitherIterator::First(ByteSliceDhcpOptions(BytesIn::new(data)))
+            }
+            Self::DataSlice(data) => EitherIterator::Second(data.iter().cloned()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DhcpOption<'a> {
+    /// 1: Client Identifier (the DUID of the client)
+    ClientId(&'a [u8]),
+    /// 2: Server Identifier (the DUID of the server)
+    ServerId(&'a [u8]),
+    /// 3: Identity Association for Non-temporary Addresses
+    IaNa(IaNa<'a>),
+    /// 5: IA Address
+    IaAddress(IaAddress<'a>),
+    /// 6: Option Request
+    OptionRequest(OptionCodes<'a>),
+    /// 7: Preference
+    Preference(u8),
+    /// 8: Elapsed Time, in hundredths of a second
+    ElapsedTime(u16),
+    /// 13: Status Code
+    StatusCode(Status<'a>),
+    /// 14: Rapid Commit
+    RapidCommit,
+    /// 23: DNS Recursive Name Server, as per RFC 3646
+    DnsServers(Ipv6Addrs<'a>),
+    /// 24: Domain Search List, in DNS wire format, as per RFC 3646
+    DomainList(&'a [u8]),
+    /// 32: Information Refresh Time, in seconds
+    InformationRefreshTime(u32),
+    // Other (unrecognized)
+    Unrecognized(u16, &'a [u8]),
+}
+
+impl DhcpOption<'_> {
+    pub const CODE_DNS: u16 = DNS_SERVERS;
+    pub const CODE_DOMAIN_LIST: u16 = DOMAIN_LIST;
+    pub const CODE_INFORMATION_REFRESH_TIME: u16 = INFORMATION_REFRESH_TIME;
+
+    fn decode<'o>(bytes: &mut BytesIn<'o>) -> Result<DhcpOption<'o>, Error> {
+        let code = u16::from_be_bytes(bytes.arr()?);
+        let len = u16::from_be_bytes(bytes.arr()?) as usize;
+
+        let mut bytes = BytesIn::new(bytes.slice(len)?);
+
+        let option = match code {
+            CLIENTID | SERVERID => {
+                if len == 0 {
+                    Err(Error::DataUnderflow)?;
+                }
+
+                if code == CLIENTID {
+                    DhcpOption::ClientId(bytes.remaining())
+                } else {
+                    DhcpOption::ServerId(bytes.remaining())
+                }
+            }
+            IA_NA => DhcpOption::IaNa(IaNa {
+                iaid: u32::from_be_bytes(bytes.arr()?),
+                t1: u32::from_be_bytes(bytes.arr()?),
+                t2: u32::from_be_bytes(bytes.arr()?),
+                options: Options(OptionsInner::decode(bytes.remaining())?),
+            }),
+            IAADDR => DhcpOption::IaAddress(IaAddress {
+                addr: Ipv6Addr::from(bytes.arr::<16>()?),
+                preferred_lifetime: u32::from_be_bytes(bytes.arr()?),
+                valid_lifetime: u32::from_be_bytes(bytes.arr()?),
+                options: Options(OptionsInner::decode(bytes.remaining())?),
+            }),
+            ORO => {
+                if len % 2 != 0 {
+                    Err(Error::InvalidPacket)?;
+                }
+
+                DhcpOption::OptionRequest(OptionCodes(OptionCodesInner::ByteSlice(
+                    bytes.remaining(),
+                )))
+            }
+            PREFERENCE => DhcpOption::Preference(bytes.remaining_byte()?),
+            ELAPSED_TIME => DhcpOption::ElapsedTime(u16::from_be_bytes(bytes.remaining_arr()?)),
+            STATUS_CODE => DhcpOption::StatusCode(Status {
+                code: u16::from_be_bytes(bytes.arr()?),
+                message: core::str::from_utf8(bytes.remaining()).map_err(Error::InvalidUtf8Str)?,
+            }),
+            RAPID_COMMIT => {
+                if len != 0 {
+                    Err(Error::InvalidPacket)?;
+                }
+
+                DhcpOption::RapidCommit
+            }
+            DNS_SERVERS => {
+                if len % 16 != 0 {
+                    Err(Error::InvalidPacket)?;
+                }
+
+                DhcpOption::DnsServers(Ipv6Addrs(Ipv6AddrsInner::ByteSlice(bytes.remaining())))
+            }
+            DOMAIN_LIST => DhcpOption::DomainList(bytes.remaining()),
+            INFORMATION_REFRESH_TIME => {
+                DhcpOption::InformationRefreshTime(u32::from_be_bytes(bytes.remaining_arr()?))
+            }
+            _ => DhcpOption::Unrecognized(code, bytes.remaining()),
+        };
+
+        Ok(option)
+    }
+
+    /// Produce the wire encoding of the option - header included - in pieces
+    ///
+    /// A `dyn` callback is used, as options nest (IA_NA carries IA Address options,
+    /// which carry Status Code options, and so on).
+    fn encode(&self, f: &mut dyn FnMut(&[u8]) -> Result<(), Error>) -> Result<(), Error> {
+        let mut len = 0;
+        self.data(&mut |data| {
+            len += data.len();
+
+            Ok(())
+        })?;
+
+        let len: u16 = len.try_into().map_err(|_| Error::BufferOverflow)?;
+
+        f(&self.code().to_be_bytes())?;
+        f(&len.to_be_bytes())?;
+
+        self.data(f)
+    }
+
+    pub const fn code(&self) -> u16 {
+        match self {
+            Self::ClientId(_) => CLIENTID,
+            Self::ServerId(_) => SERVERID,
+            Self::IaNa(_) => IA_NA,
+            Self::IaAddress(_) => IAADDR,
+            Self::OptionRequest(_) => ORO,
+            Self::Preference(_) => PREFERENCE,
+            Self::ElapsedTime(_) => ELAPSED_TIME,
+            Self::StatusCode(_) => STATUS_CODE,
+            Self::RapidCommit => RAPID_COMMIT,
+            Self::DnsServers(_) => DNS_SERVERS,
+            Self::DomainList(_) => DOMAIN_LIST,
+            Self::InformationRefreshTime(_) => INFORMATION_REFRESH_TIME,
+            Self::Unrecognized(code, _) => *code,
+        }
+    }
+
+    fn data(&self, f: &mut dyn FnMut(&[u8]) -> Result<(), Error>) -> Result<(), Error> {
+        match self {
+            Self::ClientId(duid) | Self::ServerId(duid) => f(duid),
+            Self::IaNa(ia) => {
+                f(&ia.iaid.to_be_bytes())?;
+                f(&ia.t1.to_be_bytes())?;
+                f(&ia.t2.to_be_bytes())?;
+
+                for option in ia.options.iter() {
+                    option.encode(f)?;
+                }
+
+                Ok(())
+            }
+            Self::IaAddress(address) => {
+                f(&address.addr.octets())?;
+                f(&address.preferred_lifetime.to_be_bytes())?;
+                f(&address.valid_lifetime.to_be_bytes())?;
+
+                for option in address.options.iter() {
+                    option.encode(f)?;
+                }
+
+                Ok(())
+            }
+            Self::OptionRequest(codes) => {
+                for code in codes.iter() {
+                    f(&code.to_be_bytes())?;
+                }
+
+                Ok(())
+            }
+            Self::Preference(preference) => f(&[*preference]),
+            Self::ElapsedTime(time) => f(&time.to_be_bytes()),
+            Self::StatusCode(status) => {
+                f(&status.code.to_be_bytes())?;
+                f(status.message.as_bytes())
+            }
+            Self::RapidCommit => Ok(()),
+            Self::DnsServers(addrs) => {
+                for addr in addrs.iter() {
+                    f(&addr.octets())?;
+                }
+
+                Ok(())
+            }
+            Self::DomainList(data) => f(data),
+            Self::InformationRefreshTime(secs) => f(&secs.to_be_bytes()),
+            Self::Unrecognized(_, data) => f(data),
+        }
+    }
+}
+
+/// An Identity Association for Non-temporary Addresses (IA_NA)
+///
+/// T1 and T2 are in seconds. The options are usually IA Address options and possibly a Status Code option.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct IaNa<'a> {
+    pub iaid: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub options: Options<'a>,
+}
+
+impl<'a> IaNa<'a> {
+    pub const fn new(iaid: u32, t1: u32, t2: u32, options: Options<'a>) -> Self {
+        Self {
+            iaid,
+            t1,
+            t2,
+            options,
+        }
+    }
+
+    /// Iterate over the addresses of the IA
+    pub fn addresses(&self) -> impl Iterator<Item = IaAddress<'a>> + 'a {
+        self.options.iter().filter_map(|option| {
+            if let DhcpOption::IaAddress(address) = option {
+                Some(address)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// An address of an IA, with its lifetimes in seconds
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct IaAddress<'a> {
+    pub addr: Ipv6Addr,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+    pub options: Options<'a>,
+}
+
+impl<'a> IaAddress<'a> {
+    pub const fn new(
+        addr: Ipv6Addr,
+        preferred_lifetime: u32,
+        valid_lifetime: u32,
+        options: Options<'a>,
+    ) -> Self {
+        Self {
+            addr,
+            preferred_lifetime,
+            valid_lifetime,
+            options,
+        }
+    }
+}
+
+/// The content of a Status Code option
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Status<'a> {
+    pub code: u16,
+    pub message: &'a str,
+}
+
+impl Status<'_> {
+    pub const SUCCESS: u16 = 0;
+    pub const UNSPEC_FAIL: u16 = 1;
+    pub const NO_ADDRS_AVAIL: u16 = 2;
+    pub const NO_BINDING: u16 = 3;
+    pub const NOT_ON_LINK: u16 = 4;
+    pub const USE_MULTICAST: u16 = 5;
+
+    /// Return `true` if the status code indicates success
+    pub const fn is_success(&self) -> bool {
+        self.code == Self::SUCCESS
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OptionCodes<'a>(OptionCodesInner<'a>);
+
+impl<'a> OptionCodes<'a> {
+    pub const fn new(codes: &'a [u16]) -> Self {
+        Self(OptionCodesInner::DataSlice(codes))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + 'a {
+        match self.0 {
+            OptionCodesInner::ByteSlice(data) => EitherIterator::First(
+                data.chunks_exact(2)
+                    .map(|code| u16::from_be_bytes([code[0], code[1]])),
+            ),
+            OptionCodesInner::DataSlice(codes) => EitherIterator::Second(codes.iter().cloned()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum OptionCodesInner<'a> {
+    ByteSlice(&'a [u8]),
+    DataSlice(&'a [u16]),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Ipv6Addrs<'a>(Ipv6AddrsInner<'a>);
+
+impl<'a> Ipv6Addrs<'a> {
+    pub const fn new(addrs: &'a [Ipv6Addr]) -> Self {
+        Self(Ipv6AddrsInner::DataSlice(addrs))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Ipv6Addr> + 'a {
+        match self.0 {
+            Ipv6AddrsInner::ByteSlice(data) => {
+                EitherIterator::First(data.chunks_exact(16).map(|octets| {
+                    let octets: [u8; 16] = octets.try_into().unwrap();
+
+                    octets.into()
+                }))
+            }
+            Ipv6AddrsInner::DataSlice(addrs) => EitherIterator::Second(addrs.iter().cloned()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Ipv6AddrsInner<'a> {
+    ByteSlice(&'a [u8]),
+    DataSlice(&'a [Ipv6Addr]),
+}
+
+/// Return the DUID-LL (DUID Based on Link-Layer Address, as per RFC 8415)
+/// of an Ethernet interface with the provided MAC address
+pub const fn duid_ll(mac: [u8; 6]) -> [u8; 10] {
+    [0, 3, 0, 1, mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]]
+}
+
+// DHCPv6 Options
+const CLIENTID: u16 = 1;
+const SERVERID: u16 = 2;
+const IA_NA: u16 = 3;
+const IAADDR: u16 = 5;
+const ORO: u16 = 6;
+const PREFERENCE: u16 = 7;
+const ELAPSED_TIME: u16 = 8;
+const STATUS_CODE: u16 = 13;
+const RAPID_COMMIT: u16 = 14;
+const DNS_SERVERS: u16 = 23;
+const DOMAIN_LIST: u16 = 24;
+const INFORMATION_REFRESH_TIME: u16 = 32;
+
+#[cfg(test)]
+mod test {
+    use rand_core::RngCore;
+
+    use super::client::{Client, Settings};
+    use super::*;
+
+    const CLIENT_DUID: [u8; 10] = duid_ll([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    const SERVER_DUID: [u8; 10] = duid_ll([0x02, 0x00, 0x00, 0x00, 0x00, 0xfe]);
+
+    const IAID: u32 = 7;
+
+    const IP: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x10);
+    const DNS: [Ipv6Addr; 2] = [
+        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53),
+        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 1, 0x53),
+    ];
+
+    /// An RNG returning consecutive numbers, so that the transaction IDs are predictable
+    struct Counter(u32);
+
+    impl RngCore for Counter {
+        fn next_u32(&mut self) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.next_u32() as _
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    /// Encode the provided message and decode it back, checking that the decoded message encodes the same way
+    fn roundtrip<'o>(message: &Message<'_>, buf: &'o mut [u8]) -> Message<'o> {
+        let len = message.encode(buf).unwrap().len();
+
+        let decoded = Message::decode(&buf[..len]).unwrap();
+
+        let mut reencoded = [0; 1500];
+        assert_eq!(decoded.encode(&mut reencoded).unwrap(), &buf[..len]);
+
+        decoded
+    }
+
+    /// Return the codes of the Option Request option of the message
+    fn requested<'o>(message: &Message<'o>) -> impl Iterator<Item = u16> + 'o {
+        message
+            .options
+            .iter()
+            .find_map(|option| {
+                if let DhcpOption::OptionRequest(codes) = option {
+                    Some(codes)
+                } else {
+                    None
+                }
+            })
+            .into_iter()
+            .flat_map(|codes| codes.iter())
+    }
+
+    /// Encode an ADVERTISE or REPLY of the server for the provided transaction,
+    /// assigning `IP` in the IA_NA of the client, unless `status` is set
+    fn server_message<'o>(
+        message_type: MessageType,
+        xid: u32,
+        status: Option<Status<'_>>,
+        buf: &'o mut [u8],
+    ) -> Message<'o> {
+        let ia_options = [
+            DhcpOption::IaAddress(IaAddress::new(IP, 1800, 3600, Options::new(&[]))),
+            // An address the server wants the client to stop using
+            DhcpOption::IaAddress(IaAddress::new(
+                Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x11),
+                0,
+                0,
+                Options::new(&[]),
+            )),
+        ];
+
+        let status_options = status.map(DhcpOption::StatusCode);
+        let ia = match status_options.as_ref() {
+            Some(option) => IaNa::new(IAID, 0, 0, Options::new(core::slice::from_ref(option))),
+            None => IaNa::new(IAID, 900, 1440, Options::new(&ia_options)),
+        };
+
+        let options = [
+            DhcpOption::ClientId(&CLIENT_DUID),
+            DhcpOption::ServerId(&SERVER_DUID),
+            DhcpOption::Preference(255),
+            DhcpOption::IaNa(ia),
+            DhcpOption::DnsServers(Ipv6Addrs::new(&DNS)),
+            DhcpOption::DomainList(b"\x07example\x00"),
+        ];
+
+        let message = Message::new(message_type, xid, Options::new(&options));
+        let len = message.encode(buf).unwrap().len();
+
+        Message::decode(&buf[..len]).unwrap()
+    }
+
+    #[test]
+    fn test_solicit() {
+        let mut client = Client::new(Counter(0), &CLIENT_DUID, IAID);
+
+        let mut opt_buf = Options::buf();
+        let (solicit, xid) = client.solicit(&mut opt_buf, 0, true);
+
+        let mut buf = [0; 1500];
+        let solicit = roundtrip(&solicit, &mut buf);
+
+        assert_eq!(xid, 1);
+        assert_eq!(solicit.message_type, MessageType::Solicit);
+        assert_eq!(solicit.options.client_id(), Some(&CLIENT_DUID[..]));
+        assert_eq!(solicit.options.server_id(), None);
+        assert!(solicit.options.rapid_commit());
+
+        let ia = solicit.options.ia_na(IAID).unwrap();
+        assert_eq!((ia.t1, ia.t2, ia.addresses().count()), (0, 0, 0));
+
+        assert!(requested(&solicit).eq([DhcpOption::CODE_DNS, DhcpOption::CODE_DOMAIN_LIST]));
+    }
+
+    #[test]
+    fn test_advertise_and_reply() {
+        for message_type in [MessageType::Advertise, MessageType::Reply] {
+            let mut buf = [0; 1500];
+            let message = server_message(message_type, 0x123456, None, &mut buf);
+
+            let mut buf = [0; 1500];
+            let message = roundtrip(&message, &mut buf);
+
+            let settings = Settings::new(&message, IAID);
+
+            assert!(settings.is_success());
+            assert_eq!(settings.server_id, Some(&SERVER_DUID[..]));
+            assert_eq!(settings.preference, 255);
+            assert_eq!(settings.ip, Some(IP));
+            assert_eq!(settings.preferred_lifetime_secs, Some(1800));
+            assert_eq!(settings.valid_lifetime_secs, Some(3600));
+            assert_eq!(settings.renewal_time_secs, Some(900));
+            assert_eq!(settings.rebinding_time_secs, Some(1440));
+            assert_eq!(settings.dns1, Some(DNS[0]));
+            assert_eq!(settings.dns2, Some(DNS[1]));
+            assert_eq!(settings.domain_list, Some(&b"\x07example\x00"[..]));
+
+            // Another IA of the client is not considered
+            assert_eq!(Settings::new(&message, IAID + 1).ip, None);
+        }
+    }
+
+    #[test]
+    fn test_request() {
+        let mut client = Client::new(Counter(0), &CLIENT_DUID, IAID);
+
+        let address = [DhcpOption::IaAddress(IaAddress::new(
+            IP,
+            0,
+            0,
+            Options::new(&[]),
+        ))];
+        let ia = IaNa::new(IAID, 0, 0, Options::new(&address));
+
+        let mut opt_buf = Options::buf();
+        let (request, _) = client.request(&mut opt_buf, 10, &SERVER_DUID, ia);
+
+        let mut buf = [0; 1500];
+        let request = roundtrip(&request, &mut buf);
+
+        assert_eq!(request.message_type, MessageType::Request);
+        assert_eq!(request.options.server_id(), Some(&SERVER_DUID[..]));
+        assert!(request
+            .options
+            .iter()
+            .any(|option| option == DhcpOption::ElapsedTime(10)));
+        assert!(!request.options.rapid_commit());
+
+        let ia = request.options.ia_na(IAID).unwrap();
+        assert!(ia.addresses().map(|address| address.addr).eq([IP]));
+    }
+
+    #[test]
+    fn test_client() {
+        let mut client = Client::new(Counter(0x00ff_fffe), &CLIENT_DUID, IAID);
+        let mut opt_buf = Options::buf();
+
+        // SOLICIT -> ADVERTISE
+        let (_, xid) = client.solicit(&mut opt_buf, 0, false);
+
+        // Only the lower 24 bits of the transaction ID are used
+        assert_eq!(xid, 0x00ff_ffff);
+
+        let mut buf = [0; 1500];
+        let advertise = server_message(MessageType::Advertise, xid, None, &mut buf);
+
+        assert!(client.is_advertise(&advertise, xid));
+        assert!(!client.is_reply(&advertise, xid));
+        assert!(!client.is_advertise(&advertise, xid - 1));
+
+        let advertised = Settings::new(&advertise, IAID);
+        let server_id = advertised.server_id.unwrap();
+        let ip = advertised.ip.unwrap();
+
+        // An ADVERTISE for another client is ignored
+        let other = Client::new(Counter(0), &SERVER_DUID, IAID);
+        assert!(!other.is_advertise(&advertise, xid));
+
+        // REQUEST -> REPLY
+        let address = [DhcpOption::IaAddress(IaAddress::new(
+            ip,
+            0,
+            0,
+            Options::new(&[]),
+        ))];
+        let ia = IaNa::new(IAID, 0, 0, Options::new(&address));
+
+        let mut opt_buf = Options::buf();
+        let (_, xid) = client.request(&mut opt_buf, 0, server_id, ia);
+
+        let mut buf = [0; 1500];
+        let reply = server_message(MessageType::Reply, xid, None, &mut buf);
+
+        assert!(client.is_reply(&reply, xid));
+
+        let bound = Settings::new(&reply, IAID);
+        assert!(bound.is_success());
+        assert_eq!(bound.ip, Some(ip));
+
+        // T1: RENEW with the server which assigned the address
+        let mut opt_buf = Options::buf();
+        let (renew, xid) = client.renew(&mut opt_buf, 0, server_id, ia);
+
+        assert_eq!(renew.message_type, MessageType::Renew);
+        assert_eq!(renew.options.server_id(), Some(server_id));
+        assert!(renew.options.ia_na(IAID).is_some());
+
+        // The server lost the binding
+        let mut buf = [0; 1500];
+        let no_binding = Status {
+            code: Status::NO_BINDING,
+            message: "No binding",
+        };
+        let reply = server_message(MessageType::Reply, xid, Some(no_binding), &mut buf);
+
+        assert!(client.is_reply(&reply, xid));
+
+        let settings = Settings::new(&reply, IAID);
+        assert!(!settings.is_success());
+        assert_eq!(settings.status, Some(Status::NO_BINDING));
+        assert_eq!(settings.ip, None);
+
+        // T2: REBIND with any server
+        let mut opt_buf = Options::buf();
+        let (rebind, _) = client.rebind(&mut opt_buf, 0, ia);
+
+        assert_eq!(rebind.message_type, MessageType::Rebind);
+        assert_eq!(rebind.options.server_id(), None);
+        assert!(rebind.options.ia_na(IAID).is_some());
+
+        // RELEASE does not request any options
+        let mut opt_buf = Options::buf();
+        let (release, _) = client.release(&mut opt_buf, 0, server_id, ia);
+
+        assert_eq!(release.message_type, MessageType::Release);
+        assert_eq!(release.options.server_id(), Some(server_id));
+        assert_eq!(requested(&release).count(), 0);
+    }
+
+    #[test]
+    fn test_information_request() {
+        let mut client = Client::new(Counter(0), &CLIENT_DUID, IAID);
+
+        let mut opt_buf = Options::buf();
+        let (request, _) = client.information_request(&mut opt_buf, 0);
+
+        let mut buf = [0; 1500];
+        let request = roundtrip(&request, &mut buf);
+
+        assert_eq!(request.message_type, MessageType::InformationRequest);
+        assert!(request.options.ia_na(IAID).is_none());
+        assert!(requested(&request).eq([
+            DhcpOption::CODE_DNS,
+            DhcpOption::CODE_DOMAIN_LIST,
+            DhcpOption::CODE_INFORMATION_REFRESH_TIME
+        ]));
+    }
+
+    #[test]
+    fn test_invalid() {
+        // Unknown message type
+        assert!(Message::decode(&[0, 0, 0, 1]).is_err());
+        // Truncated transaction ID
+        assert!(Message::decode(&[1, 0, 0]).is_err());
+        // Option longer than the message
+        assert!(Message::decode(&[1, 0, 0, 1, 0, 1, 0, 10, 0]).is_err());
+        // Odd-length Option Request
+        assert!(Message::decode(&[1, 0, 0, 1, 0, 6, 0, 1, 0]).is_err());
+        // Rapid Commit with data
+        assert!(Message::decode(&[1, 0, 0, 1, 0, 14, 0, 1, 0]).is_err());
+    }
+}
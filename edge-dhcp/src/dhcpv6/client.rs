@@ -0,0 +1,301 @@
+use rand_core::RngCore;
+
+use super::*;
+
+/// The options requested from the server: the DNS servers and the domain search list
+const REQUEST_OPTIONS: &[u16] = &[DhcpOption::CODE_DNS, DhcpOption::CODE_DOMAIN_LIST];
+
+/// Same as `REQUEST_OPTIONS`, but for INFORMATION-REQUEST, where - as per RFC 8415 -
+/// the Information Refresh Time option must be requested as well
+const INFORMATION_REQUEST_OPTIONS: &[u16] = &[
+    DhcpOption::CODE_DNS,
+    DhcpOption::CODE_DOMAIN_LIST,
+    DhcpOption::CODE_INFORMATION_REFRESH_TIME,
+];
+
+/// The configuration offered (ADVERTISE) or assigned (REPLY) by a DHCPv6 server.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Settings<'a> {
+    pub server_id: Option<&'a [u8]>,
+    pub preference: u8,
+    /// The status code of the message, or - if missing - of the IA_NA of the client
+    pub status: Option<u16>,
+    pub ip: Option<Ipv6Addr>,
+    pub preferred_lifetime_secs: Option<u32>,
+    pub valid_lifetime_secs: Option<u32>,
+    pub renewal_time_secs: Option<u32>,
+    pub rebinding_time_secs: Option<u32>,
+    pub dns1: Option<Ipv6Addr>,
+    pub dns2: Option<Ipv6Addr>,
+    pub domain_list: Option<&'a [u8]>,
+    pub information_refresh_time_secs: Option<u32>,
+}
+
+impl<'a> Settings<'a> {
+    /// Extract the settings from the provided message, considering only the IA_NA with the provided IAID
+    pub fn new(message: &Message<'a>, iaid: u32) -> Self {
+        let ia = message.options.ia_na(iaid);
+
+        // Addresses with a zero valid lifetime are the ones the server wants the client to stop using
+        let address = ia.and_then(|ia| ia.addresses().find(|address| address.valid_lifetime > 0));
+
+        Self {
+            server_id: message.options.server_id(),
+            preference: message.options.preference(),
+            status: message
+                .options
+                .status()
+                .or_else(|| ia.and_then(|ia| ia.options.status()))
+                .map(|status| status.code),
+            ip: address.map(|address| address.addr),
+            preferred_lifetime_secs: address.map(|address| address.preferred_lifetime),
+            valid_lifetime_secs: address.map(|address| address.valid_lifetime),
+            // As per RFC 8415, T1 and T2 set to 0 leave their choice to the client
+            renewal_time_secs: ia.map(|ia| ia.t1).filter(|t1| *t1 > 0),
+            rebinding_time_secs: ia.map(|ia| ia.t2).filter(|t2| *t2 > 0),
+            dns1: message.options.iter().find_map(|option| {
+                if let DhcpOption::DnsServers(ips) = option {
+                    ips.iter().next()
+                } else {
+                    None
+                }
+            }),
+            dns2: message.options.iter().find_map(|option| {
+                if let DhcpOption::DnsServers(ips) = option {
+                    ips.iter().nth(1)
+                } else {
+                    None
+                }
+            }),
+            domain_list: message.options.iter().find_map(|option| {
+                if let DhcpOption::DomainList(list) = option {
+                    Some(list)
+                } else {
+                    None
+                }
+            }),
+            information_refresh_time_secs: message.options.iter().find_map(|option| {
+                if let DhcpOption::InformationRefreshTime(secs) = option {
+                    Some(secs)
+                } else {
+                    None
+                }
+            }),
+        }
+    }
+
+    /// Return `true` if the status code of the message indicates success
+    pub fn is_success(&self) -> bool {
+        self.status.unwrap_or(Status::SUCCESS) == Status::SUCCESS
+    }
+}
+
+/// A simple DHCPv6 client.
+/// The client is unaware of the IP/UDP transport layer and operates purely in terms of messages
+/// represented as Rust slices.
+///
+/// As such, the client can generate all DHCPv6 client messages and recognize the server replies
+/// destined to it. It supports both stateful configuration (with a single IA_NA) and stateless
+/// configuration (INFORMATION-REQUEST).
+pub struct Client<'a, T> {
+    pub rng: T,
+    /// The DUID of the client (e.g. as returned by `duid_ll`)
+    pub duid: &'a [u8],
+    /// The IAID of the IA_NA of the client; should be stable across restarts of the client
+    pub iaid: u32,
+}
+
+impl<'a, T> Client<'a, T>
+where
+    T: RngCore,
+{
+    pub const fn new(rng: T, duid: &'a [u8], iaid: u32) -> Self {
+        Self { rng, duid, iaid }
+    }
+
+    /// Create a SOLICIT message
+    ///
+    /// `elapsed` is the time since the first transmission of the message, in hundredths of a second.
+    /// With `rapid_commit` set, the server is asked to assign the addresses right away with a REPLY,
+    /// skipping the ADVERTISE/REQUEST exchange.
+    pub fn solicit<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        elapsed: u16,
+        rapid_commit: bool,
+    ) -> (Message<'o>, u32)
+    where
+        'a: 'o,
+    {
+        let ia = IaNa::new(self.iaid, 0, 0, Options::new(&[]));
+
+        self.message(
+            MessageType::Solicit,
+            elapsed,
+            REQUEST_OPTIONS,
+            [
+                Some(DhcpOption::IaNa(ia)),
+                rapid_commit.then_some(DhcpOption::RapidCommit),
+            ],
+            opt_buf,
+        )
+    }
+
+    /// Create a REQUEST message, asking the server identified by `server_id` for the addresses of `ia`
+    pub fn request<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        elapsed: u16,
+        server_id: &'o [u8],
+        ia: IaNa<'o>,
+    ) -> (Message<'o>, u32)
+    where
+        'a: 'o,
+    {
+        self.message(
+            MessageType::Request,
+            elapsed,
+            REQUEST_OPTIONS,
+            [
+                Some(DhcpOption::ServerId(server_id)),
+                Some(DhcpOption::IaNa(ia)),
+            ],
+            opt_buf,
+        )
+    }
+
+    /// Create a RENEW message, extending the lifetimes of the addresses of `ia`
+    /// with the server identified by `server_id`, which assigned them
+    pub fn renew<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        elapsed: u16,
+        server_id: &'o [u8],
+        ia: IaNa<'o>,
+    ) -> (Message<'o>, u32)
+    where
+        'a: 'o,
+    {
+        self.message(
+            MessageType::Renew,
+            elapsed,
+            REQUEST_OPTIONS,
+            [
+                Some(DhcpOption::ServerId(server_id)),
+                Some(DhcpOption::IaNa(ia)),
+            ],
+            opt_buf,
+        )
+    }
+
+    /// Create a REBIND message, extending the lifetimes of the addresses of `ia` with any server
+    pub fn rebind<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        elapsed: u16,
+        ia: IaNa<'o>,
+    ) -> (Message<'o>, u32)
+    where
+        'a: 'o,
+    {
+        self.message(
+            MessageType::Rebind,
+            elapsed,
+            REQUEST_OPTIONS,
+            [Some(DhcpOption::IaNa(ia)), None],
+            opt_buf,
+        )
+    }
+
+    /// Create a RELEASE message, relinquishing the addresses of `ia` to the server identified by `server_id`
+    pub fn release<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        elapsed: u16,
+        server_id: &'o [u8],
+        ia: IaNa<'o>,
+    ) -> (Message<'o>, u32)
+    where
+        'a: 'o,
+    {
+        self.message(
+            MessageType::Release,
+            elapsed,
+            &[],
+            [
+                Some(DhcpOption::ServerId(server_id)),
+                Some(DhcpOption::IaNa(ia)),
+            ],
+            opt_buf,
+        )
+    }
+
+    /// Create an INFORMATION-REQUEST message, asking for configuration parameters only
+    /// (stateless DHCPv6, as used when the addresses are configured via SLAAC)
+    pub fn information_request<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        elapsed: u16,
+    ) -> (Message<'o>, u32)
+    where
+        'a: 'o,
+    {
+        self.message(
+            MessageType::InformationRequest,
+            elapsed,
+            INFORMATION_REQUEST_OPTIONS,
+            [None, None],
+            opt_buf,
+        )
+    }
+
+    pub fn is_advertise(&self, reply: &Message<'_>, xid: u32) -> bool {
+        self.is_reply_for_us(reply, xid, &[MessageType::Advertise])
+    }
+
+    pub fn is_reply(&self, reply: &Message<'_>, xid: u32) -> bool {
+        self.is_reply_for_us(reply, xid, &[MessageType::Reply])
+    }
+
+    /// Return `true` if the message is a reply of one of the expected types to our message with transaction ID `xid`
+    pub fn is_reply_for_us(
+        &self,
+        reply: &Message<'_>,
+        xid: u32,
+        expected_message_types: &[MessageType],
+    ) -> bool {
+        reply.xid == xid & 0x00ff_ffff
+            && expected_message_types.contains(&reply.message_type)
+            && reply.options.client_id() == Some(self.duid)
+    }
+
+    fn message<'o>(
+        &mut self,
+        mt: MessageType,
+        elapsed: u16,
+        requested: &'static [u16],
+        extra: [Option<DhcpOption<'o>>; 2],
+        opt_buf: &'o mut [DhcpOption<'o>],
+    ) -> (Message<'o>, u32)
+    where
+        'a: 'o,
+    {
+        let options = [
+            Some(DhcpOption::ClientId(self.duid)),
+            Some(DhcpOption::ElapsedTime(elapsed)),
+            (!requested.is_empty()).then(|| DhcpOption::OptionRequest(OptionCodes::new(requested))),
+        ];
+
+        let mut offset = 0;
+
+        for option in options.into_iter().chain(extra).flatten() {
+            opt_buf[offset] = option;
+            offset += 1;
+        }
+
+        let xid = self.rng.next_u32() & 0x00ff_ffff;
+
+        (Message::new(mt, xid, Options::new(&opt_buf[..offset])), xid)
+    }
+}
@@ -3,7 +3,11 @@ use core::net::{SocketAddr, SocketAddrV4};
 
 use crate as dhcp;
 
+pub mod autoip;
 pub mod client;
+pub mod dhcpv6;
+pub mod multihomed;
+pub mod relay;
 pub mod server;
 
 pub const DEFAULT_SERVER_PORT: u16 = 67;
@@ -35,6 +39,16 @@ impl<E> From<dhcp::Error> for Error<E> {
     }
 }
 
+impl<E> From<edge_raw::io::Error<E>> for Error<E> {
+    fn from(value: edge_raw::io::Error<E>) -> Self {
+        match value {
+            edge_raw::io::Error::Io(e) => Self::Io(e),
+            edge_raw::io::Error::UnsupportedProtocol => Self::Format(dhcp::Error::InvalidPacket),
+            edge_raw::io::Error::RawError(e) => Self::Format(e.into()),
+        }
+    }
+}
+
 impl<E> fmt::Display for Error<E>
 where
     E: fmt::Display,
@@ -0,0 +1,565 @@
+use core::net::Ipv4Addr;
+
+use edge_nal::{RawReceive, RawSend, UdpReceive, UdpSend};
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+
+use log::{info, warn};
+
+use rand_core::RngCore;
+
+pub use super::{Error, ErrorKind};
+
+use super::client::{Lease, LeaseEvent};
+
+use crate as dhcp;
+use crate::autoip::*;
+
+/// Events reported by `run` and `run_with_fallback` as a link-local address is claimed and lost.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AutoIpEvent {
+    /// The address was probed and claimed; it can now be assigned to the interface
+    Claimed(Ipv4Addr),
+    /// The address is used by another host which did not stop using it; the address should no longer be used
+    Lost(Ipv4Addr),
+    /// A DHCP lease was obtained; the link-local address should no longer be used
+    Released(Ipv4Addr),
+}
+
+/// Events reported by `run_with_fallback`
+#[derive(Debug)]
+pub enum FallbackEvent<'a> {
+    /// An event of the DHCP client
+    Dhcp(LeaseEvent<'a>),
+    /// An event of the link-local address autoconfiguration
+    LinkLocal(AutoIpEvent),
+}
+
+/// Runs the DHCP client, as `Lease::run` does, but falls back to a link-local address
+/// (as per RFC 3927) whenever no DHCP lease can be obtained within `timeout`.
+///
+/// While the link-local address is in use, the DHCP client keeps trying to obtain a lease,
+/// and once it does, the link-local address is released.
+///
+/// The `arp_socket` should be a raw socket bound to the ARP EtherType (`ArpPacket::ETHER_TYPE`)
+/// on the same interface as the DHCP client socket.
+///
+/// The lease and link-local address changes are reported to the application via the `f` callback.
+///
+/// The function returns only in case of an error.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_with_fallback<T, R, S, A, F>(
+    client: &mut dhcp::client::Client<'_, T>,
+    socket: &mut S,
+    buf: &mut [u8],
+    autoip: &mut AutoIp<R>,
+    arp_socket: &mut A,
+    timeout: Duration,
+    mut f: F,
+) -> Result<(), ErrorKind>
+where
+    T: RngCore,
+    R: RngCore,
+    S: UdpReceive + UdpSend,
+    A: RawReceive + RawSend,
+    F: FnMut(FallbackEvent<'_>),
+{
+    let mut fallback = false;
+
+    loop {
+        let mut lease = {
+            let result = if fallback {
+                info!("Falling back to a link-local address");
+
+                let mut claimed = None;
+
+                let result = select(
+                    Lease::new(client, socket, buf),
+                    run(autoip, arp_socket, |event| {
+                        claimed = match event {
+                            AutoIpEvent::Claimed(ip) => Some(ip),
+                            _ => None,
+                        };
+
+                        f(FallbackEvent::LinkLocal(event));
+                    }),
+                )
+                .await;
+
+                let result = match result {
+                    Either::First(result) => result,
+                    Either::Second(result) => {
+                        // `run` only ever returns with an error
+                        result.map_err(|e| e.erase())?;
+                        continue;
+                    }
+                };
+
+                if let Some(ip) = claimed {
+                    f(FallbackEvent::LinkLocal(AutoIpEvent::Released(ip)));
+                }
+
+                Some(result)
+            } else {
+                match select(Lease::new(client, socket, buf), Timer::after(timeout)).await {
+                    Either::First(result) => Some(result),
+                    Either::Second(_) => None,
+                }
+            };
+
+            let Some(result) = result else {
+                fallback = true;
+                continue;
+            };
+
+            let (lease, network_info) = result.map_err(|e| e.erase())?;
+
+            f(FallbackEvent::Dhcp(LeaseEvent::Bound(
+                &lease,
+                &network_info,
+            )));
+
+            lease
+        };
+
+        lease
+            .keep_with(client, socket, buf, |event| f(FallbackEvent::Dhcp(event)))
+            .await
+            .map_err(|e| e.erase())?;
+
+        fallback = false;
+    }
+}
+
+/// Runs the link-local address autoconfiguration using the supplied raw socket:
+/// - Claims an address (see `claim`)
+/// - Defends the address (see `defend`)
+/// - Starts over with claiming a new address, if the address is lost
+///
+/// The `socket` should be a raw socket bound to the ARP EtherType (`ArpPacket::ETHER_TYPE`).
+///
+/// The address changes are reported to the application via the `f` callback.
+///
+/// The function returns only in case of an error.
+pub async fn run<T, S, F>(
+    autoip: &mut AutoIp<T>,
+    socket: &mut S,
+    mut f: F,
+) -> Result<(), Error<S::Error>>
+where
+    T: RngCore,
+    S: RawReceive + RawSend,
+    F: FnMut(AutoIpEvent),
+{
+    loop {
+        let ip = claim(autoip, socket).await?;
+
+        f(AutoIpEvent::Claimed(ip));
+
+        defend(autoip, socket, ip).await?;
+
+        f(AutoIpEvent::Lost(ip));
+    }
+}
+
+/// Picks a link-local address which is not used by any other host on the link, and announces it,
+/// by utilizing the supplied raw socket.
+///
+/// As per RFC 3927, the probing is rate-limited after `MAX_CONFLICTS` conflicting addresses.
+pub async fn claim<T, S>(
+    autoip: &mut AutoIp<T>,
+    socket: &mut S,
+) -> Result<Ipv4Addr, Error<S::Error>>
+where
+    T: RngCore,
+    S: RawReceive + RawSend,
+{
+    let mut conflicts = 0;
+
+    'candidates: loop {
+        let ip = autoip.candidate();
+
+        if conflicts >= MAX_CONFLICTS {
+            Timer::after(Duration::from_millis(RATE_LIMIT_INTERVAL_MS)).await;
+        }
+
+        info!("Probing link-local IP {ip}");
+
+        let delay = autoip.delay_ms(0, PROBE_WAIT_MS);
+        if listen(autoip, socket, ip, Duration::from_millis(delay)).await? {
+            conflicts += 1;
+            continue;
+        }
+
+        for probe in 0..PROBE_NUM {
+            edge_raw::io::arp_probe(&mut *socket, autoip.mac, ip).await?;
+
+            let delay = if probe < PROBE_NUM - 1 {
+                autoip.delay_ms(PROBE_MIN_MS, PROBE_MAX_MS)
+            } else {
+                ANNOUNCE_WAIT_MS
+            };
+
+            if listen(autoip, socket, ip, Duration::from_millis(delay)).await? {
+                warn!("Link-local IP {ip} is already in use");
+
+                conflicts += 1;
+                continue 'candidates;
+            }
+        }
+
+        for announcement in 0..ANNOUNCE_NUM {
+            if announcement > 0 {
+                Timer::after(Duration::from_millis(ANNOUNCE_INTERVAL_MS)).await;
+            }
+
+            edge_raw::io::arp_announce(&mut *socket, autoip.mac, ip).await?;
+        }
+
+        info!("Link-local IP {ip} claimed");
+
+        break Ok(ip);
+    }
+}
+
+/// Defends the provided claimed link-local address by utilizing the supplied raw socket.
+///
+/// A conflicting host is answered with an announcement, unless there was already another conflict
+/// in the last `DEFEND_INTERVAL_MS`, in which case the address is given up, as per RFC 3927.
+///
+/// Returns when the address is lost.
+pub async fn defend<T, S>(
+    autoip: &mut AutoIp<T>,
+    socket: &mut S,
+    ip: Ipv4Addr,
+) -> Result<(), Error<S::Error>>
+where
+    T: RngCore,
+    S: RawReceive + RawSend,
+{
+    let mut last_defense: Option<Instant> = None;
+
+    loop {
+        let packet = edge_raw::io::arp_receive(&mut *socket).await?;
+
+        if !autoip.is_conflict(&packet, ip) {
+            continue;
+        }
+
+        let now = Instant::now();
+
+        if last_defense
+            .map(|last| now - last < Duration::from_millis(DEFEND_INTERVAL_MS))
+            .unwrap_or(false)
+        {
+            warn!("Link-local IP {ip} lost to another host");
+
+            break Ok(());
+        }
+
+        info!("Defending link-local IP {ip}");
+
+        edge_raw::io::arp_announce(&mut *socket, autoip.mac, ip).await?;
+
+        last_defense = Some(now);
+    }
+}
+
+/// Listens for ARP packets conflicting with the probing of the provided address for the provided duration
+///
+/// Returns `true` if a conflict was detected.
+async fn listen<T, S>(
+    autoip: &AutoIp<T>,
+    socket: &mut S,
+    ip: Ipv4Addr,
+    duration: Duration,
+) -> Result<bool, Error<S::Error>>
+where
+    T: RngCore,
+    S: RawReceive + RawSend,
+{
+    let deadline = Instant::now() + duration;
+
+    loop {
+        let Either::First(packet) =
+            select(edge_raw::io::arp_receive(&mut *socket), Timer::at(deadline)).await
+        else {
+            break Ok(false);
+        };
+
+        if autoip.is_probe_conflict(&packet?, ip) {
+            break Ok(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use core::net::{SocketAddr, SocketAddrV4};
+
+    use embassy_futures::block_on;
+
+    use edge_nal::io::ErrorType;
+    use edge_nal::MacAddr;
+    use edge_raw::arp::ArpPacket;
+
+    use rand_core::RngCore;
+
+    use crate::server::{Server, ServerOptions};
+    use crate::{Options, Packet};
+
+    use super::super::DEFAULT_SERVER_PORT;
+    use super::*;
+
+    const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+    const OTHER_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+    const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 1);
+
+    /// A "random" number generator returning 1, 2, 3 and so on, so that the delays are (almost) zero
+    struct CountingRng(u32);
+
+    impl RngCore for CountingRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.next_u32() as _
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            dest.iter_mut()
+                .for_each(|byte| *byte = self.next_u32() as _);
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+
+            Ok(())
+        }
+    }
+
+    /// An ARP socket which receives the provided packets right away, and records the sent packets;
+    /// once the packets are exhausted, receiving blocks forever
+    struct MockArp<'a> {
+        incoming: &'a [ArpPacket],
+        sent: heapless::Vec<ArpPacket, 16>,
+    }
+
+    impl<'a> MockArp<'a> {
+        fn new(incoming: &'a [ArpPacket]) -> Self {
+            Self {
+                incoming,
+                sent: heapless::Vec::new(),
+            }
+        }
+    }
+
+    impl ErrorType for MockArp<'_> {
+        type Error = Infallible;
+    }
+
+    impl RawReceive for MockArp<'_> {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+            let Some((packet, rest)) = self.incoming.split_first() else {
+                core::future::pending().await
+            };
+
+            self.incoming = rest;
+
+            let len = packet.encode(buffer).unwrap().len();
+
+            Ok((len, packet.sender_mac))
+        }
+    }
+
+    impl RawSend for MockArp<'_> {
+        async fn send(&mut self, _addr: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+            self.sent.push(ArpPacket::decode(data).unwrap()).unwrap();
+
+            Ok(())
+        }
+    }
+
+    /// A DHCP client socket connected to a DHCP server, which only answers once it is `online`
+    struct MockDhcp<'a> {
+        server: Server<fn() -> u64, 4>,
+        options: ServerOptions<'a>,
+        online: &'a Cell<bool>,
+        reply: [u8; 576],
+        reply_len: usize,
+    }
+
+    impl<'a> MockDhcp<'a> {
+        fn new(online: &'a Cell<bool>) -> Self {
+            Self {
+                server: Server::new(|| Instant::now().as_secs(), SERVER_IP),
+                options: ServerOptions::new(SERVER_IP, None),
+                online,
+                reply: [0; 576],
+                reply_len: 0,
+            }
+        }
+    }
+
+    impl ErrorType for MockDhcp<'_> {
+        type Error = Infallible;
+    }
+
+    impl UdpSend for MockDhcp<'_> {
+        async fn send(&mut self, _remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+            if self.online.get() {
+                let request = Packet::decode(data).unwrap();
+                let mut opt_buf = Options::buf();
+
+                if let Some(reply) =
+                    self.server
+                        .handle_request(&mut opt_buf, &self.options, &request)
+                {
+                    self.reply_len = reply.encode(&mut self.reply).unwrap().len();
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    impl UdpReceive for MockDhcp<'_> {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+            if self.reply_len == 0 {
+                core::future::pending().await
+            }
+
+            let len = core::mem::take(&mut self.reply_len);
+            buffer[..len].copy_from_slice(&self.reply[..len]);
+
+            Ok((
+                len,
+                SocketAddr::V4(SocketAddrV4::new(SERVER_IP, DEFAULT_SERVER_PORT)),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_claim() {
+        // The first candidate is taken by another host probing for it at the same time
+        let first = AutoIp::new(CountingRng(0), MAC).candidate();
+
+        let incoming = [
+            // Our own probe, looped back, and an unrelated probe are not conflicts
+            ArpPacket::probe(MAC, first),
+            ArpPacket::probe(OTHER_MAC, Ipv4Addr::new(169, 254, 99, 99)),
+            ArpPacket::probe(OTHER_MAC, first),
+        ];
+
+        let mut autoip = AutoIp::new(CountingRng(0), MAC);
+        let mut socket = MockArp::new(&incoming);
+
+        let ip = block_on(claim(&mut autoip, &mut socket)).unwrap();
+
+        assert_ne!(ip, first);
+        assert!(is_link_local(ip));
+
+        // No probes for the first candidate, as the conflict was detected before the first probe
+        assert_eq!(
+            socket.sent,
+            [
+                ArpPacket::probe(MAC, ip),
+                ArpPacket::probe(MAC, ip),
+                ArpPacket::probe(MAC, ip),
+                ArpPacket::announcement(MAC, ip),
+                ArpPacket::announcement(MAC, ip),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_defend() {
+        let ip = Ipv4Addr::new(169, 254, 17, 42);
+
+        // A single conflict is defended against
+        let incoming = [
+            ArpPacket::announcement(MAC, ip),
+            ArpPacket::probe(OTHER_MAC, ip),
+            ArpPacket::announcement(OTHER_MAC, ip),
+        ];
+
+        let mut autoip = AutoIp::new(CountingRng(0), MAC);
+        let mut socket = MockArp::new(&incoming);
+
+        let result = block_on(select(
+            defend(&mut autoip, &mut socket, ip),
+            Timer::after(Duration::from_millis(100)),
+        ));
+
+        assert!(matches!(result, Either::Second(_)));
+        assert_eq!(socket.sent, [ArpPacket::announcement(MAC, ip)]);
+
+        // A second conflict within `DEFEND_INTERVAL_MS` makes the host give up the address
+        let incoming = [
+            ArpPacket::announcement(OTHER_MAC, ip),
+            ArpPacket::request(OTHER_MAC, ip, Ipv4Addr::new(169, 254, 1, 1)),
+        ];
+
+        let mut socket = MockArp::new(&incoming);
+
+        block_on(defend(&mut autoip, &mut socket, ip)).unwrap();
+
+        assert_eq!(socket.sent, [ArpPacket::announcement(MAC, ip)]);
+    }
+
+    #[test]
+    fn test_run_with_fallback() {
+        let online = Cell::new(false);
+        let bound = Cell::new(None);
+
+        let mut client = dhcp::client::Client::new(CountingRng(0), MAC);
+        let mut socket = MockDhcp::new(&online);
+        let mut buf = [0; 1500];
+
+        let mut autoip = AutoIp::new(CountingRng(0), MAC);
+        let mut arp_socket = MockArp::new(&[]);
+
+        let mut events = heapless::Vec::<AutoIpEvent, 4>::new();
+
+        let result = block_on(select(
+            run_with_fallback(
+                &mut client,
+                &mut socket,
+                &mut buf,
+                &mut autoip,
+                &mut arp_socket,
+                Duration::from_millis(100),
+                |event| match event {
+                    FallbackEvent::LinkLocal(event) => {
+                        // The DHCP server becomes available once the link-local address is claimed
+                        online.set(true);
+                        events.push(event).unwrap();
+                    }
+                    FallbackEvent::Dhcp(LeaseEvent::Bound(lease, _)) => bound.set(Some(lease.ip)),
+                    FallbackEvent::Dhcp(_) => (),
+                },
+            ),
+            async {
+                while bound.get().is_none() {
+                    Timer::after(Duration::from_millis(10)).await;
+                }
+            },
+        ));
+
+        assert!(matches!(result, Either::Second(_)));
+
+        let ip = arp_socket.sent[0].target_ip;
+
+        assert!(is_link_local(ip));
+        assert_eq!(
+            events,
+            [AutoIpEvent::Claimed(ip), AutoIpEvent::Released(ip)]
+        );
+        assert_eq!(bound.get(), Some(Ipv4Addr::new(192, 168, 0, 50)));
+    }
+}
@@ -1,7 +1,8 @@
 use core::fmt::Debug;
-use core::net::Ipv4Addr;
+use core::net::{Ipv4Addr, SocketAddrV4};
 
-use edge_nal::{UdpReceive, UdpSend};
+use edge_nal::{RawBind, RawReceive, RawSend, UdpReceive, UdpSend};
+use edge_raw::io::RawSocket2Udp;
 use embassy_futures::select::{select, Either};
 use embassy_time::{Duration, Instant, Timer};
 
@@ -25,6 +26,91 @@ pub struct NetworkInfo<'a> {
     pub captive_url: Option<&'a str>,
 }
 
+/// Events reported by `Lease::run` and `Lease::keep_with` as the lease goes through
+/// the states of the RFC 2131 client state machine.
+#[derive(Debug)]
+pub enum LeaseEvent<'a> {
+    /// A new lease was negotiated with a DHCP server (BOUND)
+    Bound(&'a Lease, &'a NetworkInfo<'a>),
+    /// T1 passed; extending the lease with the server which granted it (RENEWING)
+    Renewing,
+    /// T2 passed; extending the lease with any server (REBINDING)
+    Rebinding,
+    /// The lease was extended (BOUND)
+    Renewed(&'a Lease),
+    /// The server refused to extend the lease; the IP should no longer be used (INIT)
+    Nak,
+    /// The lease expired without being extended; the IP should no longer be used (INIT)
+    Expired,
+}
+
+/// The subset of a DHCP lease which is persisted by a `ClientLeaseStore`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct PersistedLease {
+    /// The leased IP
+    pub ip: Ipv4Addr,
+    /// The IP of the server which granted the lease
+    pub server_ip: Ipv4Addr,
+    /// The duration of the lease, as of the moment it was (last) acquired
+    ///
+    /// Stores having access to a wall clock (e.g. an RTC) might use it to skip restoring
+    /// leases which are known to have expired.
+    pub duration_secs: u32,
+}
+
+impl From<&Lease> for PersistedLease {
+    fn from(lease: &Lease) -> Self {
+        Self {
+            ip: lease.ip,
+            server_ip: lease.server_ip,
+            duration_secs: lease.duration.as_secs() as _,
+        }
+    }
+}
+
+/// A trait for persisting the last DHCP lease (e.g. in flash), so that after a reboot
+/// the client can try to re-acquire the same IP (INIT-REBOOT) rather than going through a full DISCOVER cycle.
+pub trait ClientLeaseStore {
+    /// Load the persisted lease, if any
+    fn load(&mut self) -> Option<PersistedLease>;
+
+    /// Persist the lease; called when a lease is acquired or renewed
+    fn store(&mut self, lease: &PersistedLease);
+
+    /// Remove the persisted lease; called when the lease is lost
+    fn clear(&mut self);
+}
+
+impl<T> ClientLeaseStore for &mut T
+where
+    T: ClientLeaseStore,
+{
+    fn load(&mut self) -> Option<PersistedLease> {
+        (**self).load()
+    }
+
+    fn store(&mut self, lease: &PersistedLease) {
+        (**self).store(lease)
+    }
+
+    fn clear(&mut self) {
+        (**self).clear()
+    }
+}
+
+/// A `ClientLeaseStore` which does not persist anything
+pub struct NoClientLeaseStore;
+
+impl ClientLeaseStore for NoClientLeaseStore {
+    fn load(&mut self) -> Option<PersistedLease> {
+        None
+    }
+
+    fn store(&mut self, _lease: &PersistedLease) {}
+
+    fn clear(&mut self) {}
+}
+
 /// Represents a DHCP IP lease.
 ///
 /// This structure has a set of asynchronous methods that can utilize a supplied DHCP client instance and UDP socket to
@@ -32,19 +118,195 @@ pub struct NetworkInfo<'a> {
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct Lease {
+    /// The index of the network interface the lease belongs to (see `Client::interface`)
+    pub interface: u32,
     pub ip: Ipv4Addr,
     pub server_ip: Ipv4Addr,
     pub duration: Duration,
+    /// The renewal time (T1), relative to `acquired`
+    pub renewal: Duration,
+    /// The rebinding time (T2), relative to `acquired`
+    pub rebinding: Duration,
     pub acquired: Instant,
 }
 
 impl Lease {
+    /// Runs the complete lease lifecycle, as per RFC 2131, by utilizing the supplied DHCP client instance and UDP socket:
+    /// - Negotiates a lease with a DHCP server (see `Lease::new`)
+    /// - Keeps the lease up to date (see `Lease::keep_with`)
+    /// - Starts over with negotiating a new lease, if the lease is lost
+    ///
+    /// The lease state changes are reported to the application via the `f` callback.
+    ///
+    /// The function returns only in case of an error.
+    pub async fn run<T, S, F>(
+        client: &mut dhcp::client::Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+        f: F,
+    ) -> Result<(), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+        F: FnMut(LeaseEvent<'_>),
+    {
+        Self::run_with_store(client, socket, buf, NoClientLeaseStore, f).await
+    }
+
+    /// Same as `run`, but additionally persists the lease in the supplied `ClientLeaseStore`.
+    ///
+    /// On start, if the store contains a lease, the client first tries to re-acquire its IP
+    /// (see `Lease::reboot`) and falls back to a full DISCOVER cycle only if that fails.
+    pub async fn run_with_store<T, S, L, F>(
+        client: &mut dhcp::client::Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+        mut store: L,
+        mut f: F,
+    ) -> Result<(), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+        L: ClientLeaseStore,
+        F: FnMut(LeaseEvent<'_>),
+    {
+        let mut persisted = store.load();
+
+        loop {
+            let mut lease = {
+                let rebooted = if let Some(persisted) = persisted.take() {
+                    // Nasty but necessary to avoid Rust's borrow checker not dealing
+                    // with the non-lexical lifetimes involved here
+                    let buf = unsafe { Self::unsafe_reborrow(buf) };
+
+                    Self::reboot(client, socket, buf, &persisted).await?
+                } else {
+                    None
+                };
+
+                let (lease, network_info) = if let Some(rebooted) = rebooted {
+                    rebooted
+                } else {
+                    Self::new(client, socket, buf).await?
+                };
+
+                store.store(&(&lease).into());
+
+                f(LeaseEvent::Bound(&lease, &network_info));
+
+                lease
+            };
+
+            lease
+                .keep_with(client, socket, buf, |event| {
+                    match &event {
+                        LeaseEvent::Renewed(lease) => store.store(&(*lease).into()),
+                        LeaseEvent::Nak | LeaseEvent::Expired => store.clear(),
+                        _ => (),
+                    }
+
+                    f(event)
+                })
+                .await?;
+        }
+    }
+
+    /// Same as `run`, but operates over a raw socket created by the supplied `RawBind` stack,
+    /// rather than over a regular UDP socket.
+    ///
+    /// The stack is expected to be bound to a specific network interface (as is e.g. `edge_nal_std::Interface`).
+    /// Since the IP and UDP headers are encoded by the client itself (see `raw_socket`), the DHCP packets are sent
+    /// from `0.0.0.0` and broadcast on that interface only. This works before any IP address is configured on the
+    /// interface, and regardless of the routing setup of the host (e.g. a host with multiple NICs).
+    ///
+    /// Note that creating raw sockets usually requires admin privileges.
+    pub async fn run_raw<T, R, F>(
+        client: &mut dhcp::client::Client<'_, T>,
+        stack: &R,
+        buf: &mut [u8],
+        f: F,
+    ) -> Result<(), Error<edge_raw::io::Error<R::Error>>>
+    where
+        T: RngCore,
+        R: RawBind,
+        F: FnMut(LeaseEvent<'_>),
+    {
+        Self::run_raw_with_store(client, stack, buf, NoClientLeaseStore, f).await
+    }
+
+    /// Same as `run_raw`, but additionally persists the lease in the supplied `ClientLeaseStore`.
+    pub async fn run_raw_with_store<T, R, L, F>(
+        client: &mut dhcp::client::Client<'_, T>,
+        stack: &R,
+        buf: &mut [u8],
+        store: L,
+        f: F,
+    ) -> Result<(), Error<edge_raw::io::Error<R::Error>>>
+    where
+        T: RngCore,
+        R: RawBind,
+        L: ClientLeaseStore,
+        F: FnMut(LeaseEvent<'_>),
+    {
+        let socket = stack
+            .bind()
+            .await
+            .map_err(|e| Error::Io(edge_raw::io::Error::Io(e)))?;
+
+        let mut socket = raw_socket(socket);
+
+        Self::run_with_store(client, &mut socket, buf, store, f).await
+    }
+
+    /// Tries to re-acquire the IP of a previously persisted lease (the INIT-REBOOT state, as per RFC 2131),
+    /// by utilizing the supplied DHCP client instance and UDP socket.
+    ///
+    /// Returns `None` if the server refused the request, or if no server replied.
+    pub async fn reboot<'a, T, S>(
+        client: &mut dhcp::client::Client<'_, T>,
+        socket: &mut S,
+        buf: &'a mut [u8],
+        persisted: &PersistedLease,
+    ) -> Result<Option<(Self, NetworkInfo<'a>)>, Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        info!("Trying to re-acquire IP {}...", persisted.ip);
+
+        let now = Instant::now();
+
+        let reply = Self::request(
+            client,
+            socket,
+            buf,
+            persisted.server_ip,
+            persisted.ip,
+            false,
+            true,
+            Duration::from_secs(3),
+            2,
+        )
+        .await?;
+
+        if let Reply::Ack(settings) = reply {
+            Ok(Some(Self::bound(
+                settings,
+                client.interface,
+                persisted.server_ip,
+                now,
+            )))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Creates a new DHCP lease by discovering a DHCP server and requesting an IP from it.
     /// This is done by utilizing the supplied DHCP client instance and UDP socket.
     ///
     /// Note that the supplied UDP socket should be capable of sending and receiving broadcast UDP packets.
     pub async fn new<'a, T, S>(
-        client: &mut dhcp::client::Client<T>,
+        client: &mut dhcp::client::Client<'_, T>,
         socket: &mut S,
         buf: &'a mut [u8],
     ) -> Result<(Self, NetworkInfo<'a>), Error<S::Error>>
@@ -64,61 +326,73 @@ impl Lease {
                 // with the non-lexical lifetimes involved here
                 let buf = unsafe { Self::unsafe_reborrow(buf) };
 
-                if let Some(settings) = Self::request(
+                if let Reply::Ack(settings) = Self::request(
                     client,
                     socket,
                     buf,
                     server_ip,
                     ip,
+                    false,
                     true,
                     Duration::from_secs(3),
                     3,
                 )
                 .await?
                 {
-                    break Ok((
-                        Self {
-                            ip: settings.ip,
-                            server_ip: settings.server_ip.unwrap(),
-                            duration: Duration::from_secs(
-                                settings.lease_time_secs.unwrap_or(7200) as _
-                            ),
-                            acquired: now,
-                        },
-                        NetworkInfo {
-                            gateway: settings.gateway,
-                            subnet: settings.subnet,
-                            dns1: settings.dns1,
-                            dns2: settings.dns2,
-                            captive_url: settings.captive_url,
-                        },
-                    ));
+                    break Ok(Self::bound(settings, client.interface, server_ip, now));
                 }
             }
         }
     }
 
     /// Keeps the DHCP lease up to date by renewing it when necessary using the supplied DHCP client instance and UDP socket.
+    ///
+    /// Returns when the lease is lost (i.e. NAKed by the server or expired).
     pub async fn keep<T, S>(
         &mut self,
-        client: &mut dhcp::client::Client<T>,
+        client: &mut dhcp::client::Client<'_, T>,
         socket: &mut S,
         buf: &mut [u8],
     ) -> Result<(), Error<S::Error>>
     where
         T: RngCore,
         S: UdpReceive + UdpSend,
+    {
+        self.keep_with(client, socket, buf, |_| ()).await
+    }
+
+    /// Keeps the DHCP lease up to date using the supplied DHCP client instance and UDP socket,
+    /// as per the RFC 2131 client state machine:
+    /// - At T1 the lease is renewed with the server which granted it (RENEWING)
+    /// - At T2 the lease is extended with any server by broadcasting (REBINDING)
+    /// - If no reply is received, the request is retransmitted after half of the remaining time
+    ///   until T2 (or the lease expiry), but no sooner than in 60 seconds
+    ///
+    /// The lease state changes are reported to the application via the `f` callback.
+    ///
+    /// Returns when the lease is lost (i.e. NAKed by the server or expired).
+    pub async fn keep_with<T, S, F>(
+        &mut self,
+        client: &mut dhcp::client::Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+        mut f: F,
+    ) -> Result<(), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+        F: FnMut(LeaseEvent<'_>),
     {
         loop {
-            let now = Instant::now();
+            let elapsed = Instant::now() - self.acquired;
 
-            if now - self.acquired >= self.duration / 3 {
-                if !self.renew(client, socket, buf).await? {
-                    // Lease was not renewed; let the user know
-                    break;
-                }
-            } else {
-                Timer::after(Duration::from_secs(60)).await;
+            if elapsed < self.renewal {
+                Timer::after(self.renewal - elapsed).await;
+                continue;
+            }
+
+            if !self.extend(client, socket, buf, &mut f).await? {
+                break;
             }
         }
 
@@ -126,9 +400,14 @@ impl Lease {
     }
 
     /// Renews the DHCP lease by utilizing the supplied DHCP client instance and UDP socket.
+    ///
+    /// The lease is renewed with the server which granted it until T2, and with any server afterwards,
+    /// retransmitting the request as described in `Lease::keep_with`.
+    ///
+    /// Returns `false` when the lease is lost (i.e. NAKed by the server or expired).
     pub async fn renew<T, S>(
         &mut self,
-        client: &mut dhcp::client::Client<T>,
+        client: &mut dhcp::client::Client<'_, T>,
         socket: &mut S,
         buf: &mut [u8],
     ) -> Result<bool, Error<S::Error>>
@@ -138,34 +417,98 @@ impl Lease {
     {
         info!("Renewing DHCP lease...");
 
-        let now = Instant::now();
-        let settings = Self::request(
-            client,
-            socket,
-            buf,
-            self.server_ip,
-            self.ip,
-            false,
-            Duration::from_secs(3),
-            3,
-        )
-        .await?;
+        self.extend(client, socket, buf, &mut |_| ()).await
+    }
 
-        if let Some(settings) = settings.as_ref() {
-            self.duration = settings
-                .lease_time_secs
-                .map(|lt| Duration::from_secs(lt as _))
-                .unwrap_or(self.duration);
-            self.acquired = now;
-        }
+    /// Extends the lease (RENEWING, then REBINDING) until it is acknowledged, NAKed, or until it expires
+    ///
+    /// Returns `true` if the lease was extended.
+    async fn extend<T, S, F>(
+        &mut self,
+        client: &mut dhcp::client::Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+        f: &mut F,
+    ) -> Result<bool, Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+        F: FnMut(LeaseEvent<'_>),
+    {
+        let mut extending = None;
 
-        Ok(settings.is_some())
+        loop {
+            let elapsed = Instant::now() - self.acquired;
+
+            if elapsed >= self.duration {
+                info!("DHCP lease for IP {} expired", self.ip);
+
+                f(LeaseEvent::Expired);
+
+                return Ok(false);
+            }
+
+            let rebinding = elapsed >= self.rebinding;
+
+            if extending != Some(rebinding) {
+                extending = Some(rebinding);
+
+                f(if rebinding {
+                    LeaseEvent::Rebinding
+                } else {
+                    LeaseEvent::Renewing
+                });
+            }
+
+            let now = Instant::now();
+
+            let reply = Self::request(
+                client,
+                socket,
+                buf,
+                self.server_ip,
+                self.ip,
+                true,
+                rebinding,
+                Duration::from_secs(3),
+                1,
+            )
+            .await?;
+
+            match reply {
+                Reply::Ack(settings) => {
+                    self.update(&settings, now);
+
+                    f(LeaseEvent::Renewed(self));
+
+                    return Ok(true);
+                }
+                Reply::Nak => {
+                    f(LeaseEvent::Nak);
+
+                    return Ok(false);
+                }
+                Reply::None => {
+                    let deadline = if rebinding {
+                        self.duration
+                    } else {
+                        self.rebinding
+                    };
+
+                    let elapsed = Instant::now() - self.acquired;
+
+                    if let Some(remaining) = deadline.checked_sub(elapsed) {
+                        Timer::after(Self::retransmit_after(remaining)).await;
+                    }
+                }
+            }
+        }
     }
 
     /// Releases the DHCP lease by utilizing the supplied DHCP client instance and UDP socket.
     pub async fn release<T, S>(
         self,
-        client: &mut dhcp::client::Client<T>,
+        client: &mut dhcp::client::Client<'_, T>,
         socket: &mut S,
         buf: &mut [u8],
     ) -> Result<(), Error<S::Error>>
@@ -188,7 +531,7 @@ impl Lease {
     }
 
     async fn discover<'a, T, S>(
-        client: &mut dhcp::client::Client<T>,
+        client: &mut dhcp::client::Client<'_, T>,
         socket: &mut S,
         buf: &'a mut [u8],
         timeout: Duration,
@@ -243,32 +586,36 @@ impl Lease {
 
     #[allow(clippy::too_many_arguments)]
     async fn request<'a, T, S>(
-        client: &mut dhcp::client::Client<T>,
+        client: &mut dhcp::client::Client<'_, T>,
         socket: &mut S,
         buf: &'a mut [u8],
         server_ip: Ipv4Addr,
         ip: Ipv4Addr,
+        renew: bool,
         broadcast: bool,
         timeout: Duration,
         retries: usize,
-    ) -> Result<Option<Settings<'a>>, Error<S::Error>>
+    ) -> Result<Reply<'a>, Error<S::Error>>
     where
         T: RngCore,
         S: UdpReceive + UdpSend,
     {
+        let start = Instant::now();
+
         for _ in 0..retries {
             info!("Requesting IP {ip} from DHCP server {server_ip}");
 
-            let start = Instant::now();
-
             let mut opt_buf = Options::buf();
 
-            let (request, xid) = client.request(
-                &mut opt_buf,
-                (Instant::now() - start).as_secs() as _,
-                ip,
-                broadcast,
-            );
+            let secs = (Instant::now() - start).as_secs() as _;
+
+            // `broadcast` is about the destination of the request only: when renewing (or rebinding),
+            // the client already has its IP configured, so it asks for unicast replies (RFC 2131, 4.1)
+            let (request, xid) = if renew {
+                client.renew(&mut opt_buf, secs, ip, false)
+            } else {
+                client.request(&mut opt_buf, secs, ip, true)
+            };
 
             socket
                 .send(
@@ -302,18 +649,83 @@ impl Lease {
 
                     info!("IP {} leased successfully", ip);
 
-                    return Ok(Some(settings));
+                    return Ok(Reply::Ack(settings));
                 } else if client.is_nak(&reply, xid) {
                     info!("IP {} not acknowledged", ip);
 
-                    return Ok(None);
+                    return Ok(Reply::Nak);
                 }
             }
         }
 
         warn!("IP request was not replied");
 
-        Ok(None)
+        Ok(Reply::None)
+    }
+
+    fn bound(
+        settings: Settings<'_>,
+        interface: u32,
+        server_ip: Ipv4Addr,
+        now: Instant,
+    ) -> (Self, NetworkInfo<'_>) {
+        let mut lease = Self {
+            interface,
+            ip: settings.ip,
+            server_ip: settings.server_ip.unwrap_or(server_ip),
+            duration: Duration::from_secs(7200),
+            renewal: Duration::from_secs(3600),
+            rebinding: Duration::from_secs(6300),
+            acquired: now,
+        };
+
+        lease.update(&settings, now);
+
+        (
+            lease,
+            NetworkInfo {
+                gateway: settings.gateway,
+                subnet: settings.subnet,
+                dns1: settings.dns1,
+                dns2: settings.dns2,
+                captive_url: settings.captive_url,
+            },
+        )
+    }
+
+    fn update(&mut self, settings: &Settings, now: Instant) {
+        if let Some(lease_time_secs) = settings.lease_time_secs {
+            self.duration = Duration::from_secs(lease_time_secs as _);
+        }
+
+        // As per RFC 2131, T1 defaults to 0.5 * duration and T2 defaults to 0.875 * duration
+        self.renewal = settings
+            .renewal_time_secs
+            .map(|secs| Duration::from_secs(secs as _))
+            .unwrap_or(self.duration / 2);
+        self.rebinding = settings
+            .rebinding_time_secs
+            .map(|secs| Duration::from_secs(secs as _))
+            .unwrap_or(self.duration * 7 / 8);
+
+        if self.rebinding > self.duration {
+            self.rebinding = self.duration;
+        }
+
+        if self.renewal > self.rebinding {
+            self.renewal = self.rebinding;
+        }
+
+        self.acquired = now;
+    }
+
+    /// The delay before retransmitting an unanswered RENEWING or REBINDING request, as per RFC 2131, 4.4.5:
+    /// half of the time remaining until T2 (or the lease expiry), but no less than 60 seconds
+    fn retransmit_after(remaining: Duration) -> Duration {
+        core::cmp::min(
+            core::cmp::max(remaining / 2, Duration::from_secs(60)),
+            remaining,
+        )
     }
 
     // Useful when Rust's borrow-checker still cannot handle some NLLs
@@ -323,3 +735,301 @@ impl Lease {
         unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr(), len) }
     }
 }
+
+enum Reply<'a> {
+    Ack(Settings<'a>),
+    Nak,
+    None,
+}
+
+/// Wraps the supplied raw socket into a UDP socket suitable for the DHCP client.
+///
+/// The returned socket only accepts packets sent from the DHCP server port to the DHCP client port,
+/// and sends all packets from `0.0.0.0` to the broadcast MAC address.
+pub fn raw_socket<S>(socket: S) -> RawSocket2Udp<S>
+where
+    S: RawReceive + RawSend,
+{
+    RawSocket2Udp::new(
+        socket,
+        Some(SocketAddrV4::new(
+            Ipv4Addr::UNSPECIFIED,
+            DEFAULT_CLIENT_PORT,
+        )),
+        Some(SocketAddrV4::new(
+            Ipv4Addr::UNSPECIFIED,
+            DEFAULT_SERVER_PORT,
+        )),
+        [0xff; 6],
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+    use core::net::SocketAddr;
+
+    use embassy_futures::block_on;
+
+    use edge_nal::io::ErrorType;
+
+    use crate::{DhcpOption, MessageType};
+
+    use super::*;
+
+    const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+    const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 1);
+    const IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 50);
+
+    const UNICAST: SocketAddr = SocketAddr::V4(SocketAddrV4::new(SERVER_IP, DEFAULT_SERVER_PORT));
+    const BROADCAST: SocketAddr =
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, DEFAULT_SERVER_PORT));
+
+    /// A "random" number generator returning 1, 2, 3 and so on
+    struct CountingRng(u32);
+
+    impl RngCore for CountingRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.next_u32() as _
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            dest.iter_mut()
+                .for_each(|byte| *byte = self.next_u32() as _);
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+
+            Ok(())
+        }
+    }
+
+    /// How the scripted server answers a request
+    #[derive(Copy, Clone)]
+    enum Answer {
+        /// ACK, granting a lease of the provided duration in seconds
+        Ack(u32),
+        Nak,
+        /// No reply at all
+        Drop,
+    }
+
+    /// A request sent by the client: its destination, `ciaddr` and Requested IP Address option
+    type Sent = (SocketAddr, Ipv4Addr, Option<Ipv4Addr>);
+
+    /// A client socket connected to a DHCP server which answers the requests as per the provided script;
+    /// once the script is exhausted, the server no longer replies
+    struct MockServer<'a> {
+        script: &'a [Answer],
+        sent: heapless::Vec<Sent, 8>,
+        reply: [u8; 576],
+        reply_len: usize,
+    }
+
+    impl<'a> MockServer<'a> {
+        fn new(script: &'a [Answer]) -> Self {
+            Self {
+                script,
+                sent: heapless::Vec::new(),
+                reply: [0; 576],
+                reply_len: 0,
+            }
+        }
+    }
+
+    impl ErrorType for MockServer<'_> {
+        type Error = Infallible;
+    }
+
+    impl UdpSend for MockServer<'_> {
+        async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+            let request = Packet::decode(data).unwrap();
+
+            let requested = request.options.iter().find_map(|option| {
+                if let DhcpOption::RequestedIpAddress(ip) = option {
+                    Some(ip)
+                } else {
+                    None
+                }
+            });
+
+            self.sent.push((remote, request.ciaddr, requested)).unwrap();
+
+            let (answer, rest) = self.script.split_first().unwrap_or((&Answer::Drop, &[]));
+            self.script = rest;
+
+            let (mt, lease_secs) = match *answer {
+                Answer::Ack(lease_secs) => (MessageType::Ack, lease_secs),
+                Answer::Nak => (MessageType::Nak, 0),
+                Answer::Drop => return Ok(()),
+            };
+
+            let mut opt_buf = Options::buf();
+            let options = request.options.reply(
+                mt,
+                SERVER_IP,
+                lease_secs,
+                &[],
+                None,
+                &[],
+                None,
+                &mut opt_buf,
+            );
+
+            let ip = requested.unwrap_or(request.ciaddr);
+            let reply = request.new_reply((mt == MessageType::Ack).then_some(ip), options);
+
+            self.reply_len = reply.encode(&mut self.reply).unwrap().len();
+
+            Ok(())
+        }
+    }
+
+    impl UdpReceive for MockServer<'_> {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+            if self.reply_len == 0 {
+                core::future::pending().await
+            }
+
+            let len = core::mem::take(&mut self.reply_len);
+            buffer[..len].copy_from_slice(&self.reply[..len]);
+
+            Ok((len, UNICAST))
+        }
+    }
+
+    /// A lease which is due for renewal right away
+    fn lease(rebinding_secs: u64, duration_secs: u64) -> Lease {
+        Lease {
+            interface: 0,
+            ip: IP,
+            server_ip: SERVER_IP,
+            duration: Duration::from_secs(duration_secs),
+            renewal: Duration::from_secs(0),
+            rebinding: Duration::from_secs(rebinding_secs),
+            acquired: Instant::now(),
+        }
+    }
+
+    fn event_name(event: &LeaseEvent<'_>) -> &'static str {
+        match event {
+            LeaseEvent::Bound(..) => "Bound",
+            LeaseEvent::Renewing => "Renewing",
+            LeaseEvent::Rebinding => "Rebinding",
+            LeaseEvent::Renewed(_) => "Renewed",
+            LeaseEvent::Nak => "Nak",
+            LeaseEvent::Expired => "Expired",
+        }
+    }
+
+    #[test]
+    fn test_renew() {
+        let mut client = dhcp::client::Client::new(CountingRng(0), MAC);
+        let mut socket = MockServer::new(&[Answer::Ack(2), Answer::Nak]);
+        let mut buf = [0; 576];
+
+        let mut lease = lease(10, 20);
+        let mut events = heapless::Vec::<_, 8>::new();
+
+        block_on(
+            lease.keep_with(&mut client, &mut socket, &mut buf, |event| {
+                events.push(event_name(&event)).unwrap();
+            }),
+        )
+        .unwrap();
+
+        // Renewed with a 2 seconds lease, then - one second later at its T1 - NAKed
+        assert_eq!(events, ["Renewing", "Renewed", "Renewing", "Nak"]);
+
+        assert_eq!(lease.duration, Duration::from_secs(2));
+        assert_eq!(lease.renewal, Duration::from_secs(1));
+        assert_eq!(lease.rebinding, Duration::from_millis(1750));
+
+        // While RENEWING, the requests go to the server which granted the lease, and carry the IP in `ciaddr`
+        assert_eq!(socket.sent, [(UNICAST, IP, None), (UNICAST, IP, None)]);
+    }
+
+    #[test]
+    fn test_rebind_and_expire() {
+        let mut client = dhcp::client::Client::new(CountingRng(0), MAC);
+        let mut socket = MockServer::new(&[]);
+        let mut buf = [0; 576];
+
+        let mut lease = lease(2, 4);
+        let mut events = heapless::Vec::<_, 8>::new();
+
+        // No server replies: the RENEWING request times out after T2, and the REBINDING one after the expiry
+        block_on(
+            lease.keep_with(&mut client, &mut socket, &mut buf, |event| {
+                events.push(event_name(&event)).unwrap();
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(events, ["Renewing", "Rebinding", "Expired"]);
+
+        // While REBINDING, the requests are broadcast to any server
+        assert_eq!(socket.sent, [(UNICAST, IP, None), (BROADCAST, IP, None)]);
+    }
+
+    #[test]
+    fn test_retransmit() {
+        let retransmit = |secs| Lease::retransmit_after(Duration::from_secs(secs));
+
+        assert_eq!(retransmit(3600), Duration::from_secs(1800));
+        assert_eq!(retransmit(121), Duration::from_millis(60500));
+        assert_eq!(retransmit(100), Duration::from_secs(60));
+        assert_eq!(retransmit(30), Duration::from_secs(30));
+        assert_eq!(retransmit(0), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_reboot() {
+        let persisted = PersistedLease {
+            ip: IP,
+            server_ip: SERVER_IP,
+            duration_secs: 3600,
+        };
+
+        let mut client = dhcp::client::Client::new(CountingRng(0), MAC);
+        let mut buf = [0; 576];
+
+        let mut socket = MockServer::new(&[Answer::Ack(600)]);
+
+        let (lease, _) = block_on(Lease::reboot(
+            &mut client,
+            &mut socket,
+            &mut buf,
+            &persisted,
+        ))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(lease.ip, IP);
+        assert_eq!(lease.server_ip, SERVER_IP);
+        assert_eq!(lease.duration, Duration::from_secs(600));
+        assert_eq!(lease.renewal, Duration::from_secs(300));
+
+        // INIT-REBOOT broadcasts the request, with the IP in the Requested IP Address option rather than in `ciaddr`
+        assert_eq!(socket.sent, [(BROADCAST, Ipv4Addr::UNSPECIFIED, Some(IP))]);
+
+        // The IP is no longer valid on this network
+        let mut socket = MockServer::new(&[Answer::Nak]);
+
+        assert!(block_on(Lease::reboot(
+            &mut client,
+            &mut socket,
+            &mut buf,
+            &persisted
+        ))
+        .unwrap()
+        .is_none());
+    }
+}
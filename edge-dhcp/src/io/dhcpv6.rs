@@ -0,0 +1,660 @@
+use core::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
+
+use edge_nal::{UdpReceive, UdpSend};
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+
+use log::{info, warn};
+
+use rand_core::RngCore;
+
+pub use super::Error;
+
+use crate::dhcpv6::client::{Client, Settings};
+use crate::dhcpv6::{
+    DhcpOption, IaAddress, IaNa, Message, MessageType, Options, Status,
+    ALL_DHCP_RELAY_AGENTS_AND_SERVERS,
+};
+
+pub const DEFAULT_SERVER_PORT: u16 = 547;
+pub const DEFAULT_CLIENT_PORT: u16 = 546;
+
+/// The maximum length of a DUID, as per RFC 8415
+pub const MAX_DUID_LEN: usize = 130;
+
+/// Represents the additional network-related information that might be returned by the DHCPv6 server.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NetworkInfo<'a> {
+    pub dns1: Option<Ipv6Addr>,
+    pub dns2: Option<Ipv6Addr>,
+    /// The domain search list, in DNS wire format
+    pub domain_list: Option<&'a [u8]>,
+}
+
+impl<'a> From<&Settings<'a>> for NetworkInfo<'a> {
+    fn from(settings: &Settings<'a>) -> Self {
+        Self {
+            dns1: settings.dns1,
+            dns2: settings.dns2,
+            domain_list: settings.domain_list,
+        }
+    }
+}
+
+/// Events reported by `Lease::run` and `Lease::keep_with` as the lease goes through
+/// the states of the RFC 8415 client state machine.
+#[derive(Debug)]
+pub enum LeaseEvent<'a> {
+    /// A new lease was negotiated with a DHCPv6 server
+    Bound(&'a Lease, &'a NetworkInfo<'a>),
+    /// T1 passed; extending the lease with the server which granted it (RENEW)
+    Renewing,
+    /// T2 passed; extending the lease with any server (REBIND)
+    Rebinding,
+    /// The lease was extended
+    Renewed(&'a Lease),
+    /// The server has no binding for the address any longer; the IP should no longer be used
+    NoBinding,
+    /// The lease expired without being extended; the IP should no longer be used
+    Expired,
+}
+
+/// Represents a DHCPv6 lease of a single non-temporary address (IA_NA).
+///
+/// This structure has a set of asynchronous methods that can utilize a supplied DHCPv6 client instance and UDP socket to
+/// transparently implement all aspects of negotiating an IP with the DHCPv6 server and then keeping the lease of that IP up to date.
+///
+/// The UDP socket is expected to be bound to the DHCPv6 client port (546) on the interface with index `interface`,
+/// as all messages are sent to the link-scoped multicast address of the DHCPv6 servers on that interface.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Lease {
+    pub ip: Ipv6Addr,
+    pub server_id: heapless::Vec<u8, MAX_DUID_LEN>,
+    pub interface: u32,
+    /// The valid lifetime of the address, relative to `acquired`
+    pub duration: Duration,
+    /// The preferred lifetime of the address, relative to `acquired`
+    pub preferred: Duration,
+    /// The renewal time (T1), relative to `acquired`
+    pub renewal: Duration,
+    /// The rebinding time (T2), relative to `acquired`
+    pub rebinding: Duration,
+    pub acquired: Instant,
+}
+
+impl Lease {
+    /// Runs the complete lease lifecycle, as per RFC 8415, by utilizing the supplied DHCPv6 client instance and UDP socket:
+    /// - Negotiates a lease with a DHCPv6 server (see `Lease::new`)
+    /// - Keeps the lease up to date (see `Lease::keep_with`)
+    /// - Starts over with negotiating a new lease, if the lease is lost
+    ///
+    /// The lease state changes are reported to the application via the `f` callback.
+    ///
+    /// The function returns only in case of an error.
+    pub async fn run<T, S, F>(
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+        interface: u32,
+        mut f: F,
+    ) -> Result<(), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+        F: FnMut(LeaseEvent<'_>),
+    {
+        loop {
+            let mut lease = {
+                let (lease, network_info) = Self::new(client, socket, buf, interface).await?;
+
+                f(LeaseEvent::Bound(&lease, &network_info));
+
+                lease
+            };
+
+            lease.keep_with(client, socket, buf, &mut f).await?;
+        }
+    }
+
+    /// Creates a new DHCPv6 lease by soliciting a DHCPv6 server and requesting an IP from it.
+    /// This is done by utilizing the supplied DHCPv6 client instance and UDP socket.
+    ///
+    /// The first server which advertises an address is used, unless it is able to assign the address right away
+    /// (Rapid Commit, as per RFC 8415), in which case the REQUEST/REPLY exchange is skipped.
+    pub async fn new<'a, T, S>(
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &'a mut [u8],
+        interface: u32,
+    ) -> Result<(Self, NetworkInfo<'a>), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        loop {
+            let (server_id, ip) = {
+                // Nasty but necessary to avoid Rust's borrow checker not dealing
+                // with the non-lexical lifetimes involved here
+                let buf = unsafe { Self::unsafe_reborrow(buf) };
+
+                let now = Instant::now();
+
+                let settings = Self::solicit(client, socket, buf, interface).await?;
+
+                let Some(server_id) = settings.server_id.and_then(Self::own) else {
+                    continue;
+                };
+
+                let Some(ip) = settings.ip else {
+                    continue;
+                };
+
+                if settings.valid_lifetime_secs.is_some() && settings.is_success() {
+                    if let Some(rapid) = Self::bound(&settings, server_id.clone(), interface, now) {
+                        info!("IP {ip} leased successfully (rapid commit)");
+
+                        break Ok(rapid);
+                    }
+                }
+
+                (server_id, ip)
+            };
+
+            let now = Instant::now();
+
+            let address = [DhcpOption::IaAddress(IaAddress::new(
+                ip,
+                0,
+                0,
+                Options::new(&[]),
+            ))];
+            let ia = IaNa::new(client.iaid, 0, 0, Options::new(&address));
+
+            // Nasty but necessary to avoid Rust's borrow checker not dealing
+            // with the non-lexical lifetimes involved here
+            let buf = unsafe { Self::unsafe_reborrow(buf) };
+
+            info!("Requesting IP {ip} from DHCPv6 server");
+
+            let reply = Self::transact(
+                client,
+                socket,
+                buf,
+                interface,
+                MessageType::Request,
+                &server_id,
+                ia,
+                10,
+            )
+            .await?;
+
+            if let Some(settings) = reply {
+                if let Some(bound) = Self::bound(&settings, server_id, interface, now) {
+                    info!("IP {ip} leased successfully");
+
+                    break Ok(bound);
+                }
+
+                warn!("IP {ip} not assigned, status: {:?}", settings.status);
+            }
+        }
+    }
+
+    /// Keeps the DHCPv6 lease up to date by renewing it when necessary using the supplied DHCPv6 client instance and UDP socket.
+    ///
+    /// Returns when the lease is lost (i.e. no longer bound on the server or expired).
+    pub async fn keep<T, S>(
+        &mut self,
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+    ) -> Result<(), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        self.keep_with(client, socket, buf, |_| ()).await
+    }
+
+    /// Keeps the DHCPv6 lease up to date using the supplied DHCPv6 client instance and UDP socket,
+    /// as per the RFC 8415 client state machine:
+    /// - At T1 the lease is renewed with the server which granted it (RENEW)
+    /// - At T2 the lease is extended with any server (REBIND)
+    /// - If no reply is received, the message is retransmitted after half of the remaining time
+    ///   until T2 (or the lease expiry), but no sooner than in 60 seconds
+    ///
+    /// The lease state changes are reported to the application via the `f` callback.
+    ///
+    /// Returns when the lease is lost (i.e. no longer bound on the server or expired).
+    pub async fn keep_with<T, S, F>(
+        &mut self,
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+        mut f: F,
+    ) -> Result<(), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+        F: FnMut(LeaseEvent<'_>),
+    {
+        let mut extending = None;
+
+        loop {
+            let elapsed = Instant::now() - self.acquired;
+
+            if elapsed >= self.duration {
+                info!("DHCPv6 lease for IP {} expired", self.ip);
+
+                f(LeaseEvent::Expired);
+
+                break;
+            }
+
+            if elapsed < self.renewal {
+                Timer::after(self.renewal - elapsed).await;
+                continue;
+            }
+
+            let rebinding = elapsed >= self.rebinding;
+
+            if extending != Some(rebinding) {
+                extending = Some(rebinding);
+
+                f(if rebinding {
+                    LeaseEvent::Rebinding
+                } else {
+                    LeaseEvent::Renewing
+                });
+            }
+
+            let now = Instant::now();
+
+            let address = [DhcpOption::IaAddress(IaAddress::new(
+                self.ip,
+                0,
+                0,
+                Options::new(&[]),
+            ))];
+            let ia = IaNa::new(client.iaid, 0, 0, Options::new(&address));
+
+            let reply = Self::transact(
+                client,
+                socket,
+                buf,
+                self.interface,
+                if rebinding {
+                    MessageType::Rebind
+                } else {
+                    MessageType::Renew
+                },
+                &self.server_id,
+                ia,
+                1,
+            )
+            .await?;
+
+            match reply {
+                Some(settings) if settings.ip == Some(self.ip) && settings.is_success() => {
+                    let server_id = settings
+                        .server_id
+                        .and_then(Self::own)
+                        .unwrap_or_else(|| self.server_id.clone());
+
+                    self.server_id = server_id;
+                    self.update(&settings, now);
+                    extending = None;
+
+                    f(LeaseEvent::Renewed(self));
+                }
+                Some(settings)
+                    if settings.status == Some(Status::NO_BINDING) || settings.ip.is_none() =>
+                {
+                    info!("IP {} no longer bound", self.ip);
+
+                    f(LeaseEvent::NoBinding);
+
+                    break;
+                }
+                _ => {
+                    let deadline = if rebinding {
+                        self.duration
+                    } else {
+                        self.rebinding
+                    };
+
+                    let elapsed = Instant::now() - self.acquired;
+
+                    if let Some(remaining) = deadline.checked_sub(elapsed) {
+                        let retransmit = core::cmp::max(remaining / 2, Duration::from_secs(60));
+
+                        Timer::after(core::cmp::min(retransmit, remaining)).await;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Releases the DHCPv6 lease by utilizing the supplied DHCPv6 client instance and UDP socket.
+    pub async fn release<T, S>(
+        self,
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &mut [u8],
+    ) -> Result<(), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        let address = [DhcpOption::IaAddress(IaAddress::new(
+            self.ip,
+            0,
+            0,
+            Options::new(&[]),
+        ))];
+        let ia = IaNa::new(client.iaid, 0, 0, Options::new(&address));
+
+        let mut opt_buf = Options::buf();
+        let (request, _) = client.release(&mut opt_buf, 0, &self.server_id, ia);
+
+        socket
+            .send(Self::servers(self.interface), request.encode(buf)?)
+            .await
+            .map_err(Error::Io)?;
+
+        Ok(())
+    }
+
+    /// Obtains the configuration parameters (DNS servers and so on) without leasing any address (stateless DHCPv6),
+    /// by utilizing the supplied DHCPv6 client instance and UDP socket.
+    ///
+    /// Useful on networks where the addresses are configured via SLAAC.
+    ///
+    /// Returns the network information, and the duration after which it should be refreshed.
+    pub async fn information<'a, T, S>(
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &'a mut [u8],
+        interface: u32,
+    ) -> Result<(NetworkInfo<'a>, Duration), Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        // As per RFC 8415, IRT_DEFAULT and IRT_MINIMUM
+        const DEFAULT_REFRESH_SECS: u32 = 86400;
+        const MIN_REFRESH_SECS: u32 = 600;
+
+        let start = Instant::now();
+        let mut timeout = Duration::from_secs(1);
+
+        loop {
+            let mut opt_buf = Options::buf();
+
+            let (request, xid) = client.information_request(&mut opt_buf, Self::elapsed(start));
+
+            socket
+                .send(Self::servers(interface), request.encode(buf)?)
+                .await
+                .map_err(Error::Io)?;
+
+            // Nasty but necessary to avoid Rust's borrow checker not dealing
+            // with the non-lexical lifetimes involved here
+            let buf = unsafe { Self::unsafe_reborrow(buf) };
+
+            if let Some(reply) =
+                Self::receive(client, socket, buf, xid, &[MessageType::Reply], timeout).await?
+            {
+                let settings = Settings::new(&reply, client.iaid);
+
+                let refresh = settings
+                    .information_refresh_time_secs
+                    .unwrap_or(DEFAULT_REFRESH_SECS)
+                    .max(MIN_REFRESH_SECS);
+
+                return Ok(((&settings).into(), Duration::from_secs(refresh as _)));
+            }
+
+            info!("No DHCPv6 information received, retrying...");
+
+            // As per RFC 8415, INF_MAX_RT
+            timeout = core::cmp::min(timeout * 2, Duration::from_secs(3600));
+        }
+    }
+
+    async fn solicit<'a, T, S>(
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &'a mut [u8],
+        interface: u32,
+    ) -> Result<Settings<'a>, Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        info!("Soliciting DHCPv6 servers...");
+
+        let start = Instant::now();
+        let mut timeout = Duration::from_secs(1);
+
+        loop {
+            let mut opt_buf = Options::buf();
+
+            let (request, xid) = client.solicit(&mut opt_buf, Self::elapsed(start), true);
+
+            socket
+                .send(Self::servers(interface), request.encode(buf)?)
+                .await
+                .map_err(Error::Io)?;
+
+            // Nasty but necessary to avoid Rust's borrow checker not dealing
+            // with the non-lexical lifetimes involved here
+            let buf = unsafe { Self::unsafe_reborrow(buf) };
+
+            if let Some(reply) = Self::receive(
+                client,
+                socket,
+                buf,
+                xid,
+                &[MessageType::Advertise, MessageType::Reply],
+                timeout,
+            )
+            .await?
+            {
+                let settings = Settings::new(&reply, client.iaid);
+
+                if settings.ip.is_some() && settings.is_success() {
+                    if let Some(ip) = settings.ip {
+                        info!("IP {ip} advertised by a DHCPv6 server");
+                    }
+
+                    return Ok(settings);
+                }
+            }
+
+            info!("No DHCPv6 advertisements received, retrying...");
+
+            // As per RFC 8415, SOL_MAX_RT
+            timeout = core::cmp::min(timeout * 2, Duration::from_secs(3600));
+        }
+    }
+
+    /// Sends a REQUEST, RENEW or REBIND message with the provided IA and waits for the REPLY,
+    /// retransmitting the message up to `retries` times
+    #[allow(clippy::too_many_arguments)]
+    async fn transact<'a, T, S>(
+        client: &mut Client<'_, T>,
+        socket: &mut S,
+        buf: &'a mut [u8],
+        interface: u32,
+        mt: MessageType,
+        server_id: &[u8],
+        ia: IaNa<'_>,
+        retries: usize,
+    ) -> Result<Option<Settings<'a>>, Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        let start = Instant::now();
+        let mut timeout = Duration::from_secs(1);
+
+        for _ in 0..retries {
+            let mut opt_buf = Options::buf();
+
+            let elapsed = Self::elapsed(start);
+
+            let (request, xid) = match mt {
+                MessageType::Request => client.request(&mut opt_buf, elapsed, server_id, ia),
+                MessageType::Renew => client.renew(&mut opt_buf, elapsed, server_id, ia),
+                _ => client.rebind(&mut opt_buf, elapsed, ia),
+            };
+
+            socket
+                .send(Self::servers(interface), request.encode(buf)?)
+                .await
+                .map_err(Error::Io)?;
+
+            // Nasty but necessary to avoid Rust's borrow checker not dealing
+            // with the non-lexical lifetimes involved here
+            let buf = unsafe { Self::unsafe_reborrow(buf) };
+
+            if let Some(reply) =
+                Self::receive(client, socket, buf, xid, &[MessageType::Reply], timeout).await?
+            {
+                return Ok(Some(Settings::new(&reply, client.iaid)));
+            }
+
+            timeout *= 2;
+        }
+
+        warn!("DHCPv6 {mt} was not replied");
+
+        Ok(None)
+    }
+
+    /// Waits up to `timeout` for a reply of one of the expected types to our message with transaction ID `xid`,
+    /// skipping all other messages
+    async fn receive<'a, T, S>(
+        client: &Client<'_, T>,
+        socket: &mut S,
+        buf: &'a mut [u8],
+        xid: u32,
+        expected_message_types: &[MessageType],
+        timeout: Duration,
+    ) -> Result<Option<Message<'a>>, Error<S::Error>>
+    where
+        T: RngCore,
+        S: UdpReceive + UdpSend,
+    {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let Either::First(result) = select(socket.receive(buf), Timer::at(deadline)).await
+            else {
+                break Ok(None);
+            };
+
+            let (len, _remote) = result.map_err(Error::Io)?;
+
+            // Nasty but necessary to avoid Rust's borrow checker not dealing
+            // with the non-lexical lifetimes involved here
+            let buf = unsafe { Self::unsafe_reborrow(buf) };
+
+            // Malformed messages and messages destined to other clients are silently discarded
+            if let Ok(reply) = Message::decode(&buf[..len]) {
+                if client.is_reply_for_us(&reply, xid, expected_message_types) {
+                    break Ok(Some(reply));
+                }
+            }
+        }
+    }
+
+    fn bound<'a>(
+        settings: &Settings<'a>,
+        server_id: heapless::Vec<u8, MAX_DUID_LEN>,
+        interface: u32,
+        now: Instant,
+    ) -> Option<(Self, NetworkInfo<'a>)> {
+        if !settings.is_success() {
+            return None;
+        }
+
+        let mut lease = Self {
+            ip: settings.ip?,
+            server_id,
+            interface,
+            duration: Duration::from_secs(0),
+            preferred: Duration::from_secs(0),
+            renewal: Duration::from_secs(0),
+            rebinding: Duration::from_secs(0),
+            acquired: now,
+        };
+
+        lease.update(settings, now);
+
+        Some((lease, settings.into()))
+    }
+
+    fn update(&mut self, settings: &Settings, now: Instant) {
+        if let Some(valid_lifetime_secs) = settings.valid_lifetime_secs {
+            self.duration = Duration::from_secs(valid_lifetime_secs as _);
+        }
+
+        self.preferred = settings
+            .preferred_lifetime_secs
+            .map(|secs| Duration::from_secs(secs as _))
+            .unwrap_or(self.duration);
+
+        // As per RFC 8415, T1 defaults to 0.5 * preferred lifetime and T2 defaults to 0.8 * preferred lifetime
+        self.renewal = settings
+            .renewal_time_secs
+            .map(|secs| Duration::from_secs(secs as _))
+            .unwrap_or(self.preferred / 2);
+        self.rebinding = settings
+            .rebinding_time_secs
+            .map(|secs| Duration::from_secs(secs as _))
+            .unwrap_or(self.preferred * 4 / 5);
+
+        if self.rebinding > self.duration {
+            self.rebinding = self.duration;
+        }
+
+        if self.renewal > self.rebinding {
+            self.renewal = self.rebinding;
+        }
+
+        self.acquired = now;
+    }
+
+    /// Copy the DUID of the server, so that it outlives the message it came with
+    fn own(server_id: &[u8]) -> Option<heapless::Vec<u8, MAX_DUID_LEN>> {
+        heapless::Vec::from_slice(server_id).ok()
+    }
+
+    /// Return the address of all DHCPv6 servers on the provided interface
+    fn servers(interface: u32) -> SocketAddr {
+        SocketAddr::V6(SocketAddrV6::new(
+            ALL_DHCP_RELAY_AGENTS_AND_SERVERS,
+            DEFAULT_SERVER_PORT,
+            0,
+            interface,
+        ))
+    }
+
+    /// Return the time elapsed since `start`, in hundredths of a second, as per the DHCPv6 Elapsed Time option
+    fn elapsed(start: Instant) -> u16 {
+        core::cmp::min((Instant::now() - start).as_millis() / 10, u16::MAX as _) as _
+    }
+
+    // Useful when Rust's borrow-checker still cannot handle some NLLs
+    // https://rust-lang.github.io/rfcs/2094-nll.html
+    unsafe fn unsafe_reborrow<'a>(buf: &mut [u8]) -> &'a mut [u8] {
+        let len = buf.len();
+        unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr(), len) }
+    }
+}
@@ -0,0 +1,399 @@
+use core::cell::Cell;
+use core::net::SocketAddr;
+
+use edge_nal::io::ErrorType;
+use edge_nal::{UdpReceive, UdpSend};
+
+use embassy_futures::select::{select, Either};
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+
+use log::{debug, warn};
+
+/// The default maximum size of a DHCP reply which can be queued for a client
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 576;
+
+/// A UDP socket shared by up to `N` DHCP clients on a multi-homed host.
+///
+/// Each client (see `dhcp::client::Client`) operates on its own network interface, with its own MAC address
+/// and transaction IDs, and talks to the DHCP servers via its own `SharedSocketHandle`.
+/// This way, a single UDP socket bound to the DHCP client port can serve all interfaces, which is useful
+/// as most stacks would not allow binding more than one socket to that port.
+///
+/// All clients receive from the shared socket in turn: replies which are addressed to another client
+/// (as per their `chaddr` field) are queued for that client, and replies which are not addressed to any client are dropped.
+/// Up to one reply of size up to `B` can be queued per client, which is enough as the DHCP clients
+/// only ever wait for a reply to their last request.
+///
+/// Note that the requests are sent via the shared socket as well, so it is up to the networking stack
+/// to route the broadcast requests of each client to its interface. If that is not possible,
+/// consider running each client over a raw socket bound to its interface instead (see `Lease::run_raw`).
+pub struct SharedSocket<M, R, S, const N: usize, const B: usize = DEFAULT_MAX_PACKET_SIZE>
+where
+    M: RawMutex,
+{
+    recv: Mutex<M, R>,
+    send: Mutex<M, S>,
+    slots: [Slot<M, B>; N],
+}
+
+impl<M, R, S, const N: usize, const B: usize> SharedSocket<M, R, S, N, B>
+where
+    M: RawMutex,
+    R: UdpReceive,
+    S: UdpSend<Error = R::Error>,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const SLOT: Slot<M, B> = Slot::new();
+
+    /// Create a new shared socket from the receiving and sending halves of a UDP socket
+    /// bound to the DHCP client port and capable of sending and receiving broadcast packets
+    pub const fn new(recv: R, send: S) -> Self {
+        Self {
+            recv: Mutex::new(recv),
+            send: Mutex::new(send),
+            slots: [Self::SLOT; N],
+        }
+    }
+
+    /// Create a handle for the client with the provided MAC address
+    ///
+    /// Returns `None` if all `N` handles are in use, or if a handle for that MAC address already exists.
+    pub fn handle(&self, mac: [u8; 6]) -> Option<SharedSocketHandle<'_, M, R, S, N, B>> {
+        if self.owner(&mac).is_some() {
+            return None;
+        }
+
+        let index = self.slots.iter().position(|slot| {
+            slot.mac.lock(|slot_mac| {
+                if slot_mac.get().is_none() {
+                    slot_mac.set(Some(mac));
+                    true
+                } else {
+                    false
+                }
+            })
+        })?;
+
+        self.slots[index].packet.reset();
+
+        Some(SharedSocketHandle {
+            shared: self,
+            index,
+        })
+    }
+
+    fn owner(&self, mac: &[u8; 6]) -> Option<usize> {
+        self.slots.iter().position(|slot| {
+            slot.mac
+                .lock(|slot_mac| slot_mac.get().as_ref() == Some(mac))
+        })
+    }
+}
+
+/// The handle of a single client to a `SharedSocket`; usable as a regular UDP socket
+/// with `Lease::run`, `Lease::new` and the other client functions.
+///
+/// Dropping the handle frees its slot in the shared socket.
+pub struct SharedSocketHandle<'a, M, R, S, const N: usize, const B: usize>
+where
+    M: RawMutex,
+{
+    shared: &'a SharedSocket<M, R, S, N, B>,
+    index: usize,
+}
+
+impl<M, R, S, const N: usize, const B: usize> SharedSocketHandle<'_, M, R, S, N, B>
+where
+    M: RawMutex,
+{
+    /// Return the MAC address of the client which owns this handle
+    pub fn mac(&self) -> [u8; 6] {
+        self.slot().mac.lock(Cell::get).unwrap()
+    }
+
+    fn slot(&self) -> &Slot<M, B> {
+        &self.shared.slots[self.index]
+    }
+}
+
+impl<M, R, S, const N: usize, const B: usize> ErrorType for SharedSocketHandle<'_, M, R, S, N, B>
+where
+    M: RawMutex,
+    R: ErrorType,
+{
+    type Error = R::Error;
+}
+
+impl<M, R, S, const N: usize, const B: usize> UdpReceive for SharedSocketHandle<'_, M, R, S, N, B>
+where
+    M: RawMutex,
+    R: UdpReceive,
+    S: UdpSend<Error = R::Error>,
+{
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let mac = self.mac();
+
+        loop {
+            // A reply for us might be queued by another client while we are waiting for our turn to receive
+            let mut recv = match select(self.slot().packet.wait(), self.shared.recv.lock()).await {
+                Either::First((remote, packet)) => break Ok(copy(&packet, buffer, remote)),
+                Either::Second(recv) => recv,
+            };
+
+            if let Some((remote, packet)) = self.slot().packet.try_take() {
+                break Ok(copy(&packet, buffer, remote));
+            }
+
+            let (size, remote) = recv.receive(buffer).await?;
+            let len = core::cmp::min(size, buffer.len());
+
+            let Some(chaddr) = chaddr(&buffer[..len]) else {
+                debug!("Dropping a malformed packet from {remote}");
+                continue;
+            };
+
+            if chaddr == mac {
+                break Ok((size, remote));
+            }
+
+            match self.shared.owner(&chaddr) {
+                Some(index) if size <= B => {
+                    let mut packet = heapless::Vec::new();
+                    packet.extend_from_slice(&buffer[..len]).unwrap();
+
+                    self.shared.slots[index].packet.signal((remote, packet));
+                }
+                Some(_) => {
+                    warn!("Dropping a reply of {size} bytes which is too large to be queued")
+                }
+                None => debug!("Dropping a reply for unknown client {chaddr:02x?}"),
+            }
+        }
+    }
+}
+
+impl<M, R, S, const N: usize, const B: usize> UdpSend for SharedSocketHandle<'_, M, R, S, N, B>
+where
+    M: RawMutex,
+    R: UdpReceive,
+    S: UdpSend<Error = R::Error>,
+{
+    async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+        self.shared.send.lock().await.send(remote, data).await
+    }
+}
+
+impl<M, R, S, const N: usize, const B: usize> Drop for SharedSocketHandle<'_, M, R, S, N, B>
+where
+    M: RawMutex,
+{
+    fn drop(&mut self) {
+        self.slot().packet.reset();
+        self.slot().mac.lock(|mac| mac.set(None));
+    }
+}
+
+struct Slot<M, const B: usize>
+where
+    M: RawMutex,
+{
+    mac: BlockingMutex<M, Cell<Option<[u8; 6]>>>,
+    packet: Signal<M, (SocketAddr, heapless::Vec<u8, B>)>,
+}
+
+impl<M, const B: usize> Slot<M, B>
+where
+    M: RawMutex,
+{
+    const fn new() -> Self {
+        Self {
+            mac: BlockingMutex::new(Cell::new(None)),
+            packet: Signal::new(),
+        }
+    }
+}
+
+/// Return the client hardware address of a BOOTP reply, if the packet looks like one
+fn chaddr(packet: &[u8]) -> Option<[u8; 6]> {
+    const BOOTREPLY: u8 = 2;
+
+    if packet.len() < 34 || packet[0] != BOOTREPLY {
+        return None;
+    }
+
+    packet[28..34].try_into().ok()
+}
+
+fn copy(packet: &[u8], buffer: &mut [u8], remote: SocketAddr) -> (usize, SocketAddr) {
+    let len = core::cmp::min(packet.len(), buffer.len());
+
+    buffer[..len].copy_from_slice(&packet[..len]);
+
+    (packet.len(), remote)
+}
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+    use core::net::{Ipv4Addr, SocketAddrV4};
+
+    use embassy_futures::block_on;
+
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use super::*;
+
+    const MAC_A: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x0a];
+    const MAC_B: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x0b];
+    const MAC_C: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x0c];
+
+    const SERVER: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 67));
+
+    type TestSocket<'a> = SharedSocket<NoopRawMutex, MockSocket<'a>, MockSocket<'a>, 2, 64>;
+
+    /// A UDP socket which receives the provided packets right away;
+    /// once the packets are exhausted, receiving blocks forever
+    struct MockSocket<'a> {
+        incoming: &'a [&'a [u8]],
+    }
+
+    impl<'a> MockSocket<'a> {
+        const fn new(incoming: &'a [&'a [u8]]) -> Self {
+            Self { incoming }
+        }
+    }
+
+    impl ErrorType for MockSocket<'_> {
+        type Error = Infallible;
+    }
+
+    impl UdpReceive for MockSocket<'_> {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+            let Some((packet, rest)) = self.incoming.split_first() else {
+                core::future::pending().await
+            };
+
+            self.incoming = rest;
+
+            let len = core::cmp::min(packet.len(), buffer.len());
+            buffer[..len].copy_from_slice(&packet[..len]);
+
+            Ok((packet.len(), SERVER))
+        }
+    }
+
+    impl UdpSend for MockSocket<'_> {
+        async fn send(&mut self, _remote: SocketAddr, _data: &[u8]) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// A minimal BOOTP reply of `N` bytes for the client with the provided MAC address and transaction ID
+    fn reply<const N: usize>(mac: [u8; 6], xid: u8) -> [u8; N] {
+        let mut packet = [0; N];
+
+        packet[0] = 2;
+        packet[7] = xid;
+        packet[28..34].copy_from_slice(&mac);
+
+        packet
+    }
+
+    #[test]
+    fn test_routing() {
+        let for_b = reply::<48>(MAC_B, 1);
+        let for_a = reply::<48>(MAC_A, 2);
+        let incoming: [&[u8]; 2] = [&for_b, &for_a];
+
+        let shared = TestSocket::new(MockSocket::new(&incoming), MockSocket::new(&[]));
+
+        let mut a = shared.handle(MAC_A).unwrap();
+        let mut b = shared.handle(MAC_B).unwrap();
+
+        let mut buf = [0; 128];
+
+        // `a` receives the reply for `b` first, and queues it
+        let (len, remote) = block_on(a.receive(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &for_a);
+        assert_eq!(remote, SERVER);
+
+        let (len, remote) = block_on(b.receive(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &for_b);
+        assert_eq!(remote, SERVER);
+    }
+
+    #[test]
+    fn test_oversized() {
+        let for_b = reply::<100>(MAC_B, 1);
+        let for_a = reply::<48>(MAC_A, 2);
+        let incoming: [&[u8]; 2] = [&for_b, &for_a];
+
+        let shared = TestSocket::new(MockSocket::new(&incoming), MockSocket::new(&[]));
+
+        let mut a = shared.handle(MAC_A).unwrap();
+        let b = shared.handle(MAC_B).unwrap();
+
+        let mut buf = [0; 128];
+
+        let (len, _) = block_on(a.receive(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &for_a);
+
+        // The reply for `b` does not fit in its slot and is dropped
+        assert!(!b.slot().packet.signaled());
+    }
+
+    #[test]
+    fn test_handles() {
+        let shared = TestSocket::new(MockSocket::new(&[]), MockSocket::new(&[]));
+
+        let a = shared.handle(MAC_A).unwrap();
+        assert_eq!(a.mac(), MAC_A);
+
+        // Only one handle per MAC address
+        assert!(shared.handle(MAC_A).is_none());
+
+        let b = shared.handle(MAC_B).unwrap();
+        assert_eq!(b.mac(), MAC_B);
+
+        // All slots are in use
+        assert!(shared.handle(MAC_C).is_none());
+
+        drop(a);
+
+        let c = shared.handle(MAC_C).unwrap();
+        assert_eq!(c.mac(), MAC_C);
+
+        drop(b);
+
+        let a = shared.handle(MAC_A).unwrap();
+        assert_eq!(a.mac(), MAC_A);
+    }
+
+    #[test]
+    fn test_drop() {
+        let for_b = reply::<48>(MAC_B, 1);
+        let for_a = reply::<48>(MAC_A, 2);
+        let incoming: [&[u8]; 2] = [&for_b, &for_a];
+
+        let shared = TestSocket::new(MockSocket::new(&incoming), MockSocket::new(&[]));
+
+        let mut a = shared.handle(MAC_A).unwrap();
+        let b = shared.handle(MAC_B).unwrap();
+
+        let mut buf = [0; 128];
+
+        block_on(a.receive(&mut buf)).unwrap();
+        assert!(b.slot().packet.signaled());
+
+        drop(b);
+
+        // The slot of `b` is free, and its queued reply is gone
+        let c = shared.handle(MAC_C).unwrap();
+        assert!(!c.slot().packet.signaled());
+        assert_eq!(shared.owner(&MAC_B), None);
+    }
+}
@@ -0,0 +1,76 @@
+use core::net::Ipv4Addr;
+
+use edge_nal::{UdpReceive, UdpSend};
+use log::{info, warn};
+
+use self::dhcp::{Options, Packet};
+
+pub use super::*;
+
+/// Runs the provided DHCP relay agent asynchronously using the supplied UDP socket.
+///
+/// The socket should be bound to the DHCP server port (67) on the clients' segment, and needs to be capable of
+/// sending and receiving broadcast UDP packets. It receives both the requests broadcast by the clients,
+/// which are forwarded to the server, and the replies of the server (addressed to the `giaddr` of the relay agent),
+/// which are forwarded to the clients.
+///
+/// Replies to clients which do not have an IP address yet are broadcast.
+///
+/// The buffer holds both the received packet and the forwarded one, so it should be twice the size of the largest
+/// expected packet (1500 bytes are plenty).
+pub async fn run<T>(
+    relay: &dhcp::relay::Relay<'_>,
+    socket: &mut T,
+    buf: &mut [u8],
+) -> Result<(), Error<T::Error>>
+where
+    T: UdpReceive + UdpSend,
+{
+    info!(
+        "Running DHCP relay agent {} for server {}",
+        relay.giaddr, relay.server
+    );
+
+    loop {
+        let (len, _remote) = socket.receive(buf).await.map_err(Error::Io)?;
+
+        let (packet, out_buf) = buf.split_at_mut(len);
+        let packet = &*packet;
+
+        let packet = match Packet::decode(packet) {
+            Ok(packet) => packet,
+            Err(err) => {
+                warn!("Decoding packet returned error: {:?}", err);
+                continue;
+            }
+        };
+
+        let mut opt_buf = Options::buf();
+
+        let forwarded = if packet.reply {
+            relay.forward_reply(&packet, &mut opt_buf).map(|reply| {
+                let ip = if reply.ciaddr.is_unspecified() || reply.broadcast {
+                    Ipv4Addr::BROADCAST
+                } else {
+                    reply.ciaddr
+                };
+
+                (reply, SocketAddrV4::new(ip, DEFAULT_CLIENT_PORT))
+            })
+        } else {
+            relay.forward_request(&packet, &mut opt_buf).map(|request| {
+                (
+                    request,
+                    SocketAddrV4::new(relay.server, DEFAULT_SERVER_PORT),
+                )
+            })
+        };
+
+        if let Some((packet, remote)) = forwarded {
+            socket
+                .send(SocketAddr::V4(remote), packet.encode(out_buf)?)
+                .await
+                .map_err(Error::Io)?;
+        }
+    }
+}
@@ -1,12 +1,152 @@
+use core::convert::Infallible;
 use core::net::Ipv4Addr;
 
-use edge_nal::{UdpReceive, UdpSend};
+use edge_nal::{MacAddr, RawReceive, RawSend, UdpReceive, UdpSend};
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+
 use log::{info, warn};
 
-use self::dhcp::{Options, Packet};
+use self::dhcp::{DhcpOption, MessageType, Options, Packet};
 
 pub use super::*;
 
+/// The maximum number of addresses probed before giving up and offering the last one anyway
+const MAX_PROBES: usize = 4;
+
+/// The number of addresses found to be free which `ArpProbe` remembers
+const PROBE_CACHE_SIZE: usize = 8;
+
+/// A probe detecting whether an IP address is already in use on the network,
+/// e.g. by a device with a statically configured address.
+///
+/// Typical implementations send an ARP request (on targets with raw sockets) or an ICMP echo request for the address,
+/// and wait briefly for a response.
+pub trait AddressProbe {
+    type Error: Debug;
+
+    /// Return `true` if a host responded on the provided IP address
+    async fn probe(&mut self, ip: Ipv4Addr) -> Result<bool, Self::Error>;
+}
+
+impl<T> AddressProbe for &mut T
+where
+    T: AddressProbe,
+{
+    type Error = T::Error;
+
+    async fn probe(&mut self, ip: Ipv4Addr) -> Result<bool, Self::Error> {
+        (**self).probe(ip).await
+    }
+}
+
+/// An `AddressProbe` implementation which never detects a conflict
+pub struct NoProbe;
+
+impl AddressProbe for NoProbe {
+    type Error = Infallible;
+
+    async fn probe(&mut self, _ip: Ipv4Addr) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+/// An `AddressProbe` implementation sending ARP probes (as per RFC 5227) over a raw socket
+/// bound to the ARP EtherType (`edge_raw::arp::ArpPacket::ETHER_TYPE`) on the interface of the server.
+///
+/// An address is considered in use if any host claims it - by either replying to the probe
+/// or by sending any other ARP packet for the address - or if another host probes for it as well,
+/// within the configured timeout.
+///
+/// Addresses found to be free are not probed again for `CACHE_DURATION`, so that the retransmitted
+/// DISCOVERs of a client are answered without delay.
+pub struct ArpProbe<S> {
+    socket: S,
+    mac: MacAddr,
+    timeout: Duration,
+    free: heapless::Vec<(Ipv4Addr, Instant), PROBE_CACHE_SIZE>,
+}
+
+impl<S> ArpProbe<S>
+where
+    S: RawReceive + RawSend,
+{
+    /// The default time to wait for a reply to a probe
+    ///
+    /// Kept shorter than the `PROBE_WAIT` of RFC 5227, as the DHCP clients are waiting for the offer in the meantime.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// For how long an address found to be free is not probed again
+    pub const CACHE_DURATION: Duration = Duration::from_secs(60);
+
+    /// Create a new probe sending via the provided raw socket, on behalf of the interface with the provided MAC address
+    pub const fn new(socket: S, mac: MacAddr) -> Self {
+        Self::new_with_timeout(socket, mac, Self::DEFAULT_TIMEOUT)
+    }
+
+    /// Create a new probe with a custom timeout
+    pub const fn new_with_timeout(socket: S, mac: MacAddr, timeout: Duration) -> Self {
+        Self {
+            socket,
+            mac,
+            timeout,
+            free: heapless::Vec::new(),
+        }
+    }
+
+    fn is_known_free(&mut self, ip: Ipv4Addr) -> bool {
+        let now = Instant::now();
+
+        self.free
+            .retain(|(_, probed)| now.saturating_duration_since(*probed) < Self::CACHE_DURATION);
+
+        self.free.iter().any(|(addr, _)| *addr == ip)
+    }
+
+    fn set_free(&mut self, ip: Ipv4Addr) {
+        if self.free.is_full() {
+            self.free.remove(0);
+        }
+
+        let _ = self.free.push((ip, Instant::now()));
+    }
+}
+
+impl<S> AddressProbe for ArpProbe<S>
+where
+    S: RawReceive + RawSend,
+{
+    type Error = Error<S::Error>;
+
+    async fn probe(&mut self, ip: Ipv4Addr) -> Result<bool, Self::Error> {
+        if self.is_known_free(ip) {
+            return Ok(false);
+        }
+
+        edge_raw::io::arp_probe(&mut self.socket, self.mac, ip).await?;
+
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            let Either::First(packet) = select(
+                edge_raw::io::arp_receive(&mut self.socket),
+                Timer::at(deadline),
+            )
+            .await
+            else {
+                self.set_free(ip);
+
+                break Ok(false);
+            };
+
+            if dhcp::autoip::is_probe_conflict(&self.mac, &packet?, ip) {
+                break Ok(true);
+            }
+        }
+    }
+}
+
 /// Runs the provided DHCP server asynchronously using the supplied UDP socket and server options.
 ///
 /// All incoming BOOTP requests are processed by updating the DHCP server's internal simple database of leases,
@@ -23,24 +163,110 @@ pub use super::*;
 ///
 /// This is currently only possible with STD's BSD raw sockets' implementation. Unfortunately, `smoltcp` and thus `embassy-net`
 /// do not have an equivalent (yet).
-pub async fn run<T, F, const N: usize>(
-    server: &mut dhcp::server::Server<F, N>,
+///
+/// Requests forwarded by a DHCP relay agent are answered via the relay agent (i.e. to the `giaddr` address of the request),
+/// with the relay agent information (option 82) of the request echoed back.
+///
+/// The buffer holds both the received request and the reply, so it should be twice the size of the largest expected packet
+/// (1500 bytes are plenty).
+pub async fn run<T, F, const N: usize, S, H>(
+    server: &mut dhcp::server::Server<F, N, S, H>,
+    server_options: &dhcp::server::ServerOptions<'_>,
+    socket: &mut T,
+    buf: &mut [u8],
+) -> Result<(), Error<T::Error>>
+where
+    T: UdpReceive + UdpSend,
+    F: FnMut() -> u64,
+    S: dhcp::server::LeaseStore,
+    H: dhcp::server::HostnameRegistry,
+{
+    run_with_probe(server, server_options, socket, buf, NoProbe).await
+}
+
+/// Same as `run`, but additionally probes each address with the supplied `AddressProbe` before offering it.
+///
+/// Addresses found to be in use are marked as such (for the lease duration) and are skipped, so that
+/// the clients are not handed out addresses squatted by statically configured devices.
+/// Probing errors are logged and the address is treated as free.
+///
+/// The address already leased to the requesting client is offered without probing, as the client itself might answer the probe.
+pub async fn run_with_probe<T, F, const N: usize, S, H, P>(
+    server: &mut dhcp::server::Server<F, N, S, H>,
     server_options: &dhcp::server::ServerOptions<'_>,
     socket: &mut T,
     buf: &mut [u8],
+    probe: P,
 ) -> Result<(), Error<T::Error>>
 where
     T: UdpReceive + UdpSend,
     F: FnMut() -> u64,
+    S: dhcp::server::LeaseStore,
+    H: dhcp::server::HostnameRegistry,
+    P: AddressProbe,
 {
-    info!(
-        "Running DHCP server for addresses {}-{} with configuration {server_options:?}",
-        server.range_start, server.range_end
-    );
+    let pools = [dhcp::server::Pool::new(
+        server.range_start,
+        server.range_end,
+        server_options.clone(),
+    )];
+
+    run_pools_with_probe(server, &pools, None, socket, buf, probe).await
+}
+
+/// Same as `run`, but serves several address pools, each one with its own options.
+///
+/// Only one pool can be served on the segment of the socket (the one whose server IP is `interface_ip`, or the first one,
+/// if `interface_ip` is not provided). The other pools serve the requests forwarded by relay agents on other segments.
+/// See `Server::handle_pool_request` for details.
+pub async fn run_pools<T, F, const N: usize, S, H>(
+    server: &mut dhcp::server::Server<F, N, S, H>,
+    pools: &[dhcp::server::Pool<'_>],
+    interface_ip: Option<Ipv4Addr>,
+    socket: &mut T,
+    buf: &mut [u8],
+) -> Result<(), Error<T::Error>>
+where
+    T: UdpReceive + UdpSend,
+    F: FnMut() -> u64,
+    S: dhcp::server::LeaseStore,
+    H: dhcp::server::HostnameRegistry,
+{
+    run_pools_with_probe(server, pools, interface_ip, socket, buf, NoProbe).await
+}
+
+/// Same as `run_pools`, but additionally probes each address with the supplied `AddressProbe` before offering it.
+///
+/// See `run_with_probe` for details.
+pub async fn run_pools_with_probe<T, F, const N: usize, S, H, P>(
+    server: &mut dhcp::server::Server<F, N, S, H>,
+    pools: &[dhcp::server::Pool<'_>],
+    interface_ip: Option<Ipv4Addr>,
+    socket: &mut T,
+    buf: &mut [u8],
+    mut probe: P,
+) -> Result<(), Error<T::Error>>
+where
+    T: UdpReceive + UdpSend,
+    F: FnMut() -> u64,
+    S: dhcp::server::LeaseStore,
+    H: dhcp::server::HostnameRegistry,
+    P: AddressProbe,
+{
+    for pool in pools {
+        info!(
+            "Running DHCP server for addresses {}-{} with configuration {:?}",
+            pool.range_start, pool.range_end, pool.options
+        );
+    }
 
     loop {
         let (len, remote) = socket.receive(buf).await.map_err(Error::Io)?;
-        let packet = &buf[..len];
+
+        // The reply might refer to data of the request (e.g. the relay agent information),
+        // so it is encoded in the unused part of the buffer
+        let (packet, reply_buf) = buf.split_at_mut(len);
+        let packet = &*packet;
 
         let request = match Packet::decode(packet) {
             Ok(request) => request,
@@ -50,23 +276,122 @@ where
             }
         };
 
-        let mut opt_buf = Options::buf();
+        let Some(pool) = dhcp::server::Pool::select(pools, request.giaddr, interface_ip) else {
+            warn!("Ignoring request, no address pool for it: {request:?}");
+            continue;
+        };
 
-        if let Some(reply) = server.handle_request(&mut opt_buf, server_options, &request) {
-            let remote = if let SocketAddr::V4(socket) = remote {
-                if request.broadcast || *socket.ip() == Ipv4Addr::UNSPECIFIED {
-                    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, socket.port()))
-                } else {
-                    remote
-                }
-            } else {
-                remote
+        let discover = request
+            .options
+            .iter()
+            .any(|option| matches!(option, DhcpOption::MessageType(MessageType::Discover)));
+
+        let mut probes = if discover { MAX_PROBES } else { 0 };
+
+        loop {
+            let mut opt_buf = Options::buf();
+
+            let Some(reply) =
+                server.handle_pool_request(&mut opt_buf, pools, interface_ip, &request)
+            else {
+                break;
             };
 
+            let ip = reply.yiaddr;
+
+            // The address leased to the client itself is in use by the client, so it is not probed
+            let leased = server
+                .leases
+                .get(ip)
+                .is_some_and(|lease| lease.is_held_by(&request.chaddr));
+
+            if probes > 0 && !leased {
+                probes -= 1;
+
+                match probe.probe(ip).await {
+                    Ok(false) => (),
+                    Ok(true) => {
+                        warn!("IP {ip} is in use by another host, marking it as unavailable");
+
+                        server.mark_in_use(ip, pool.options.lease_duration_secs);
+
+                        continue;
+                    }
+                    Err(err) => warn!("Probing IP {ip} returned error: {err:?}"),
+                }
+            }
+
             socket
-                .send(remote, reply.encode(buf)?)
+                .send(reply_remote(&request, remote), reply.encode(reply_buf)?)
                 .await
                 .map_err(Error::Io)?;
+
+            break;
         }
     }
 }
+
+/// Return the address the reply to the provided request, received from `remote`, should be sent to
+///
+/// Requests forwarded by a relay agent are answered to the server port of the relay agent,
+/// while the replies to clients without an address yet (or asking for it) are broadcast.
+fn reply_remote(request: &Packet, remote: SocketAddr) -> SocketAddr {
+    if !request.giaddr.is_unspecified() {
+        SocketAddr::V4(SocketAddrV4::new(request.giaddr, DEFAULT_SERVER_PORT))
+    } else if let SocketAddr::V4(socket) = remote {
+        if request.broadcast || *socket.ip() == Ipv4Addr::UNSPECIFIED {
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, socket.port()))
+        } else {
+            remote
+        }
+    } else {
+        remote
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reply_remote() {
+        let options = [DhcpOption::MessageType(MessageType::Discover)];
+
+        let mut request = Packet::new_request(
+            [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+            0x1234,
+            0,
+            None,
+            false,
+            Options::new(&options),
+        );
+
+        let client = SocketAddr::V4(SocketAddrV4::new(
+            Ipv4Addr::UNSPECIFIED,
+            DEFAULT_CLIENT_PORT,
+        ));
+        let broadcast = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, DEFAULT_CLIENT_PORT));
+
+        assert_eq!(reply_remote(&request, client), broadcast);
+
+        let bound = SocketAddr::V4(SocketAddrV4::new(
+            Ipv4Addr::new(192, 168, 0, 50),
+            DEFAULT_CLIENT_PORT,
+        ));
+
+        assert_eq!(reply_remote(&request, bound), bound);
+
+        request.broadcast = true;
+        assert_eq!(reply_remote(&request, bound), broadcast);
+
+        // Relayed requests are answered to the relay agent, on the server port
+        let relay = SocketAddr::V4(SocketAddrV4::new(
+            Ipv4Addr::new(10, 0, 1, 1),
+            DEFAULT_SERVER_PORT,
+        ));
+
+        request.giaddr = Ipv4Addr::new(10, 0, 1, 1);
+        assert_eq!(reply_remote(&request, relay), relay);
+        assert_eq!(reply_remote(&request, client), relay);
+    }
+}
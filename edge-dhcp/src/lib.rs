@@ -1,4 +1,5 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+#![allow(async_fn_in_trait)]
 #![warn(clippy::large_futures)]
 
 /// This code is a `no_std` and no-alloc modification of https://github.com/krolaw/dhcp4r
@@ -11,7 +12,10 @@ use num_enum::TryFromPrimitive;
 
 use edge_raw::bytes::{self, BytesIn, BytesOut};
 
+pub mod autoip;
 pub mod client;
+pub mod dhcpv6;
+pub mod relay;
 pub mod server;
 
 #[cfg(feature = "io")]
@@ -38,6 +42,18 @@ impl From<bytes::Error> for Error {
     }
 }
 
+impl From<edge_raw::Error> for Error {
+    fn from(value: edge_raw::Error) -> Self {
+        match value {
+            edge_raw::Error::BufferOverflow => Self::BufferOverflow,
+            edge_raw::Error::DataUnderflow => Self::DataUnderflow,
+            edge_raw::Error::InvalidFormat | edge_raw::Error::InvalidChecksum => {
+                Self::InvalidPacket
+            }
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let str = match self {
@@ -172,12 +188,13 @@ impl<'a> Packet<'a> {
 
     pub fn new_reply<'b>(&self, ip: Option<Ipv4Addr>, options: Options<'b>) -> Packet<'b> {
         let mut ciaddr = Ipv4Addr::UNSPECIFIED;
-        if ip.is_some() {
-            for opt in self.options.iter() {
-                if matches!(opt, DhcpOption::MessageType(MessageType::Request)) {
-                    ciaddr = self.ciaddr;
-                    break;
-                }
+        for opt in self.options.iter() {
+            // Replies to DHCPINFORM carry no address (`ip` is `None`) but still echo the client's one
+            if (matches!(opt, DhcpOption::MessageType(MessageType::Request)) && ip.is_some())
+                || matches!(opt, DhcpOption::MessageType(MessageType::Inform))
+            {
+                ciaddr = self.ciaddr;
+                break;
             }
         }
 
@@ -292,11 +309,19 @@ pub struct Settings<'a> {
     pub ip: Ipv4Addr,
     pub server_ip: Option<Ipv4Addr>,
     pub lease_time_secs: Option<u32>,
+    pub renewal_time_secs: Option<u32>,
+    pub rebinding_time_secs: Option<u32>,
     pub gateway: Option<Ipv4Addr>,
     pub subnet: Option<Ipv4Addr>,
     pub dns1: Option<Ipv4Addr>,
     pub dns2: Option<Ipv4Addr>,
     pub captive_url: Option<&'a str>,
+    pub domain_name: Option<&'a str>,
+    pub ntp1: Option<Ipv4Addr>,
+    pub ntp2: Option<Ipv4Addr>,
+    pub mtu: Option<u16>,
+    pub broadcast: Option<Ipv4Addr>,
+    pub static_routes: Option<StaticRoutes<'a>>,
 }
 
 impl<'a> Settings<'a> {
@@ -317,6 +342,20 @@ impl<'a> Settings<'a> {
                     None
                 }
             }),
+            renewal_time_secs: packet.options.iter().find_map(|option| {
+                if let DhcpOption::RenewalTime(renewal_time_secs) = option {
+                    Some(renewal_time_secs)
+                } else {
+                    None
+                }
+            }),
+            rebinding_time_secs: packet.options.iter().find_map(|option| {
+                if let DhcpOption::RebindingTime(rebinding_time_secs) = option {
+                    Some(rebinding_time_secs)
+                } else {
+                    None
+                }
+            }),
             gateway: packet.options.iter().find_map(|option| {
                 if let DhcpOption::Router(ips) = option {
                     ips.iter().next()
@@ -352,6 +391,48 @@ impl<'a> Settings<'a> {
                     None
                 }
             }),
+            domain_name: packet.options.iter().find_map(|option| {
+                if let DhcpOption::DomainName(name) = option {
+                    Some(name)
+                } else {
+                    None
+                }
+            }),
+            ntp1: packet.options.iter().find_map(|option| {
+                if let DhcpOption::NtpServers(ips) = option {
+                    ips.iter().next()
+                } else {
+                    None
+                }
+            }),
+            ntp2: packet.options.iter().find_map(|option| {
+                if let DhcpOption::NtpServers(ips) = option {
+                    ips.iter().nth(1)
+                } else {
+                    None
+                }
+            }),
+            mtu: packet.options.iter().find_map(|option| {
+                if let DhcpOption::InterfaceMtu(mtu) = option {
+                    Some(mtu)
+                } else {
+                    None
+                }
+            }),
+            broadcast: packet.options.iter().find_map(|option| {
+                if let DhcpOption::BroadcastAddress(ip) = option {
+                    Some(ip)
+                } else {
+                    None
+                }
+            }),
+            static_routes: packet.options.iter().find_map(|option| {
+                if let DhcpOption::ClasslessStaticRoutes(routes) = option {
+                    Some(routes)
+                } else {
+                    None
+                }
+            }),
         }
     }
 }
@@ -371,8 +452,8 @@ impl<'a> Options<'a> {
     }
 
     #[inline(always)]
-    pub const fn buf() -> [DhcpOption<'a>; 8] {
-        [DhcpOption::Message(""); 8]
+    pub const fn buf() -> [DhcpOption<'a>; 16] {
+        [DhcpOption::Message(""); 16]
     }
 
     pub fn discover(requested_ip: Option<Ipv4Addr>, buf: &'a mut [DhcpOption<'a>]) -> Self {
@@ -396,6 +477,15 @@ impl<'a> Options<'a> {
         Self::new(&buf[..3])
     }
 
+    /// Options for a DHCPREQUEST sent in the RENEWING or REBINDING state,
+    /// where - as per RFC 2131 - the leased IP is in `ciaddr` rather than in the options
+    pub fn renew(buf: &'a mut [DhcpOption<'a>]) -> Self {
+        buf[0] = DhcpOption::MessageType(MessageType::Request);
+        buf[1] = DhcpOption::ParameterRequestList(Self::REQUEST_PARAMS);
+
+        Self::new(&buf[..2])
+    }
+
     pub fn release(buf: &'a mut [DhcpOption<'a>]) -> Self {
         buf[0] = DhcpOption::MessageType(MessageType::Release);
 
@@ -420,46 +510,58 @@ impl<'a> Options<'a> {
         captive_url: Option<&'b str>,
         buf: &'b mut [DhcpOption<'b>],
     ) -> Options<'b> {
-        let requested = self.iter().find_map(|option| {
-            if let DhcpOption::ParameterRequestList(requested) = option {
-                Some(requested)
-            } else {
-                None
-            }
-        });
+        let requested = self.requested_params();
 
         Options::internal_reply(
             requested,
             mt,
             server_ip,
-            lease_duration_secs,
+            Some(lease_duration_secs),
             gateways,
             subnet,
             dns,
             captive_url,
+            None,
+            &[],
+            None,
             buf,
         )
     }
 
     #[allow(clippy::too_many_arguments)]
-    fn internal_reply(
+    pub(crate) fn internal_reply(
         requested: Option<&[u8]>,
         mt: MessageType,
         server_ip: Ipv4Addr,
-        lease_duration_secs: u32,
+        lease_duration_secs: Option<u32>,
         gateways: &'a [Ipv4Addr],
         subnet: Option<Ipv4Addr>,
         dns: &'a [Ipv4Addr],
         captive_url: Option<&'a str>,
+        hostname: Option<&'a str>,
+        extra: &[DhcpOption<'a>],
+        relay_agent_info: Option<RelayAgentInfo<'a>>,
         buf: &'a mut [DhcpOption<'a>],
     ) -> Self {
         buf[0] = DhcpOption::MessageType(mt);
         buf[1] = DhcpOption::ServerIdentifier(server_ip);
-        buf[2] = DhcpOption::IpAddressLeaseTime(lease_duration_secs);
 
-        let mut offset = 3;
+        // The relay agent information must be echoed back (as the last option), so reserve a slot for it
+        let capacity = buf.len() - relay_agent_info.is_some() as usize;
+
+        let mut offset = 2;
+
+        if let Some(lease_duration_secs) = lease_duration_secs {
+            buf[offset] = DhcpOption::IpAddressLeaseTime(lease_duration_secs);
+            offset += 1;
+        }
 
         if !matches!(mt, MessageType::Nak) {
+            if let Some(hostname) = hostname {
+                buf[offset] = DhcpOption::HostName(hostname);
+                offset += 1;
+            }
+
             if let Some(requested) = requested {
                 for code in requested {
                     if !buf[0..offset].iter().any(|option| option.code() == *code) {
@@ -470,7 +572,7 @@ impl<'a> Options<'a> {
                                 .then_some(DhcpOption::DomainNameServer(Ipv4Addrs::new(dns))),
                             DhcpOption::CODE_SUBNET => subnet.map(DhcpOption::SubnetMask),
                             DhcpOption::CODE_CAPTIVE_URL => captive_url.map(DhcpOption::CaptiveUrl),
-                            _ => None,
+                            _ => extra.iter().find(|option| option.code() == *code).copied(),
                         };
 
                         if let Some(option) = option {
@@ -479,13 +581,18 @@ impl<'a> Options<'a> {
                         }
                     }
 
-                    if offset == buf.len() {
+                    if offset == capacity {
                         break;
                     }
                 }
             }
         }
 
+        if let Some(relay_agent_info) = relay_agent_info {
+            buf[offset] = DhcpOption::RelayAgentInformation(relay_agent_info);
+            offset += 1;
+        }
+
         Self::new(&buf[..offset])
     }
 
@@ -493,6 +600,50 @@ impl<'a> Options<'a> {
         self.0.iter()
     }
 
+    pub(crate) fn requested_params(&self) -> Option<&'a [u8]> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::ParameterRequestList(requested) = option {
+                Some(requested)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn relay_agent_info(&self) -> Option<RelayAgentInfo<'a>> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::RelayAgentInformation(info) = option {
+                Some(info)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn client_fqdn(&self) -> Option<ClientFqdn<'a>> {
+        self.iter().find_map(|option| {
+            if let DhcpOption::ClientFqdn(fqdn) = option {
+                Some(fqdn)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Return the host name of the client, i.e. the first label of its Client FQDN option (81) or - if missing -
+    /// of its Host Name option (12)
+    pub fn client_hostname(&self) -> Option<&'a str> {
+        self.client_fqdn().and_then(|fqdn| fqdn.host()).or_else(|| {
+            self.iter().find_map(|option| {
+                if let DhcpOption::HostName(name) = option {
+                    name.split('.').next().filter(|host| !host.is_empty())
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
     pub(crate) fn requested_ip(&self) -> Option<Ipv4Addr> {
         self.iter().find_map(|option| {
             if let DhcpOption::RequestedIpAddress(ip) = option {
@@ -541,10 +692,17 @@ impl<'a> OptionsInner<'a> {
 
             fn next(&mut self) -> Option<Self::Item> {
                 if self.0.is_empty() {
-                    None
-                } else {
-                    DhcpOption::decode(&mut self.0).unwrap()
+                    return None;
+                }
+
+                // The options are validated when the packet is decoded, yet iterating must not panic regardless,
+                // so the iteration simply stops at the end option or at the first malformed option
+                let option = DhcpOption::decode(&mut self.0).ok().flatten();
+                if option.is_none() {
+                    self.0 = BytesIn::new(&[]);
                 }
+
+                option
             }
         }
 
@@ -581,10 +739,36 @@ pub enum DhcpOption<'a> {
     Message(&'a str),
     /// 57: Maximum DHCP Message Size
     MaximumMessageSize(u16),
+    /// 58: Renewal (T1) Time Value
+    RenewalTime(u32),
+    /// 59: Rebinding (T2) Time Value
+    RebindingTime(u32),
+    /// 60: Vendor class identifier
+    VendorClassIdentifier(&'a [u8]),
     /// 61: Client-identifier
     ClientIdentifier(&'a [u8]),
     /// 114: Captive-portal URL
     CaptiveUrl(&'a str),
+    /// 15: Domain Name
+    DomainName(&'a str),
+    /// 26: Interface MTU Option
+    InterfaceMtu(u16),
+    /// 42: Network Time Protocol Servers Option
+    NtpServers(Ipv4Addrs<'a>),
+    /// 43: Vendor Specific Information
+    VendorSpecificInformation(&'a [u8]),
+    /// 66: TFTP server name
+    TftpServerName(&'a str),
+    /// 67: Bootfile name
+    BootfileName(&'a str),
+    /// 28: Broadcast Address Option
+    BroadcastAddress(Ipv4Addr),
+    /// 81: Client FQDN
+    ClientFqdn(ClientFqdn<'a>),
+    /// 82: Relay Agent Information Option
+    RelayAgentInformation(RelayAgentInfo<'a>),
+    /// 121: Classless Static Route Option
+    ClasslessStaticRoutes(StaticRoutes<'a>),
     // Other (unrecognized)
     Unrecognized(u8, &'a [u8]),
 }
@@ -594,9 +778,27 @@ impl DhcpOption<'_> {
     pub const CODE_DNS: u8 = DhcpOption::DomainNameServer(Ipv4Addrs::new(&[])).code();
     pub const CODE_SUBNET: u8 = DhcpOption::SubnetMask(Ipv4Addr::new(0, 0, 0, 0)).code();
     pub const CODE_CAPTIVE_URL: u8 = DhcpOption::CaptiveUrl("").code();
+    pub const CODE_DOMAIN_NAME: u8 = DhcpOption::DomainName("").code();
+    pub const CODE_MTU: u8 = DhcpOption::InterfaceMtu(0).code();
+    pub const CODE_NTP: u8 = DhcpOption::NtpServers(Ipv4Addrs::new(&[])).code();
+    pub const CODE_VENDOR_SPECIFIC: u8 = DhcpOption::VendorSpecificInformation(&[]).code();
+    pub const CODE_TFTP_SERVER: u8 = DhcpOption::TftpServerName("").code();
+    pub const CODE_BOOTFILE: u8 = DhcpOption::BootfileName("").code();
+    pub const CODE_BROADCAST: u8 = DhcpOption::BroadcastAddress(Ipv4Addr::new(0, 0, 0, 0)).code();
+    pub const CODE_STATIC_ROUTES: u8 =
+        DhcpOption::ClasslessStaticRoutes(StaticRoutes::new(&[])).code();
+    pub const CODE_RELAY_AGENT_INFO: u8 =
+        DhcpOption::RelayAgentInformation(RelayAgentInfo::new(&[])).code();
+    pub const CODE_CLIENT_FQDN: u8 = DhcpOption::ClientFqdn(ClientFqdn::new(0, &[])).code();
 
     fn decode<'o>(bytes: &mut BytesIn<'o>) -> Result<Option<DhcpOption<'o>>, Error> {
-        let code = bytes.byte()?;
+        let mut code = bytes.byte()?;
+
+        // The pad option has no length and may be used for aligning the subsequent options
+        while code == Packet::PAD {
+            code = bytes.byte()?;
+        }
+
         if code == Packet::END {
             Ok(None)
         } else {
@@ -621,12 +823,10 @@ impl DhcpOption<'_> {
                 MAXIMUM_DHCP_MESSAGE_SIZE => {
                     DhcpOption::MaximumMessageSize(u16::from_be_bytes(bytes.remaining_arr()?))
                 }
-                ROUTER => {
-                    DhcpOption::Router(Ipv4Addrs(Ipv4AddrsInner::ByteSlice(bytes.remaining())))
+                ROUTER => DhcpOption::Router(Ipv4Addrs::decode(bytes.remaining())?),
+                DOMAIN_NAME_SERVER => {
+                    DhcpOption::DomainNameServer(Ipv4Addrs::decode(bytes.remaining())?)
                 }
-                DOMAIN_NAME_SERVER => DhcpOption::DomainNameServer(Ipv4Addrs(
-                    Ipv4AddrsInner::ByteSlice(bytes.remaining()),
-                )),
                 IP_ADDRESS_LEASE_TIME => {
                     DhcpOption::IpAddressLeaseTime(u32::from_be_bytes(bytes.remaining_arr()?))
                 }
@@ -634,6 +834,11 @@ impl DhcpOption<'_> {
                 MESSAGE => DhcpOption::Message(
                     core::str::from_utf8(bytes.remaining()).map_err(Error::InvalidUtf8Str)?,
                 ),
+                RENEWAL_TIME => DhcpOption::RenewalTime(u32::from_be_bytes(bytes.remaining_arr()?)),
+                REBINDING_TIME => {
+                    DhcpOption::RebindingTime(u32::from_be_bytes(bytes.remaining_arr()?))
+                }
+                VENDOR_CLASS_IDENTIFIER => DhcpOption::VendorClassIdentifier(bytes.remaining()),
                 CLIENT_IDENTIFIER => {
                     if len < 2 {
                         return Err(Error::DataUnderflow);
@@ -641,9 +846,35 @@ impl DhcpOption<'_> {
 
                     DhcpOption::ClientIdentifier(bytes.remaining())
                 }
-                CAPTIVE_URL => DhcpOption::HostName(
+                CAPTIVE_URL => DhcpOption::CaptiveUrl(
+                    core::str::from_utf8(bytes.remaining()).map_err(Error::InvalidUtf8Str)?,
+                ),
+                DOMAIN_NAME => DhcpOption::DomainName(
+                    core::str::from_utf8(bytes.remaining()).map_err(Error::InvalidUtf8Str)?,
+                ),
+                INTERFACE_MTU => {
+                    DhcpOption::InterfaceMtu(u16::from_be_bytes(bytes.remaining_arr()?))
+                }
+                NTP_SERVERS => DhcpOption::NtpServers(Ipv4Addrs::decode(bytes.remaining())?),
+                VENDOR_SPECIFIC_INFORMATION => {
+                    DhcpOption::VendorSpecificInformation(bytes.remaining())
+                }
+                TFTP_SERVER_NAME => DhcpOption::TftpServerName(
+                    core::str::from_utf8(bytes.remaining()).map_err(Error::InvalidUtf8Str)?,
+                ),
+                BOOTFILE_NAME => DhcpOption::BootfileName(
                     core::str::from_utf8(bytes.remaining()).map_err(Error::InvalidUtf8Str)?,
                 ),
+                BROADCAST_ADDRESS => {
+                    DhcpOption::BroadcastAddress(Ipv4Addr::from(bytes.remaining_arr()?))
+                }
+                CLIENT_FQDN => DhcpOption::ClientFqdn(ClientFqdn::decode(bytes.remaining())?),
+                RELAY_AGENT_INFORMATION => {
+                    DhcpOption::RelayAgentInformation(RelayAgentInfo::new(bytes.remaining()))
+                }
+                CLASSLESS_STATIC_ROUTES => {
+                    DhcpOption::ClasslessStaticRoutes(StaticRoutes::new(bytes.remaining()))
+                }
                 _ => DhcpOption::Unrecognized(code, bytes.remaining()),
             };
 
@@ -654,8 +885,18 @@ impl DhcpOption<'_> {
     fn encode(&self, out: &mut BytesOut) -> Result<(), Error> {
         out.byte(self.code())?;
 
+        // Options with multiple values (e.g. a list of IP addresses) are produced
+        // in several pieces, yet need to be encoded with a single length prefix
+        let mut len = 0;
+        self.data(|data| {
+            len += data.len();
+
+            Ok(())
+        })?;
+
+        out.byte(len as _)?;
+
         self.data(|data| {
-            out.byte(data.len() as _)?;
             out.push(data)?;
 
             Ok(())
@@ -675,8 +916,21 @@ impl DhcpOption<'_> {
             Self::SubnetMask(_) => SUBNET_MASK,
             Self::MaximumMessageSize(_) => MAXIMUM_DHCP_MESSAGE_SIZE,
             Self::Message(_) => MESSAGE,
+            Self::RenewalTime(_) => RENEWAL_TIME,
+            Self::RebindingTime(_) => REBINDING_TIME,
+            Self::VendorClassIdentifier(_) => VENDOR_CLASS_IDENTIFIER,
             Self::ClientIdentifier(_) => CLIENT_IDENTIFIER,
             Self::CaptiveUrl(_) => CAPTIVE_URL,
+            Self::DomainName(_) => DOMAIN_NAME,
+            Self::InterfaceMtu(_) => INTERFACE_MTU,
+            Self::NtpServers(_) => NTP_SERVERS,
+            Self::VendorSpecificInformation(_) => VENDOR_SPECIFIC_INFORMATION,
+            Self::TftpServerName(_) => TFTP_SERVER_NAME,
+            Self::BootfileName(_) => BOOTFILE_NAME,
+            Self::BroadcastAddress(_) => BROADCAST_ADDRESS,
+            Self::ClientFqdn(_) => CLIENT_FQDN,
+            Self::RelayAgentInformation(_) => RELAY_AGENT_INFORMATION,
+            Self::ClasslessStaticRoutes(_) => CLASSLESS_STATIC_ROUTES,
             Self::Unrecognized(code, _) => *code,
         }
     }
@@ -688,7 +942,7 @@ impl DhcpOption<'_> {
             Self::ParameterRequestList(prl) => f(prl),
             Self::RequestedIpAddress(addr) => f(&addr.octets()),
             Self::HostName(name) => f(name.as_bytes()),
-            Self::Router(addrs) | Self::DomainNameServer(addrs) => {
+            Self::Router(addrs) | Self::DomainNameServer(addrs) | Self::NtpServers(addrs) => {
                 for addr in addrs.iter() {
                     f(&addr.octets())?;
                 }
@@ -699,8 +953,21 @@ impl DhcpOption<'_> {
             Self::SubnetMask(mask) => f(&mask.octets()),
             Self::Message(msg) => f(msg.as_bytes()),
             Self::MaximumMessageSize(size) => f(&size.to_be_bytes()),
-            Self::ClientIdentifier(id) => f(id),
+            Self::RenewalTime(secs) | Self::RebindingTime(secs) => f(&secs.to_be_bytes()),
+            Self::VendorClassIdentifier(id) | Self::ClientIdentifier(id) => f(id),
             Self::CaptiveUrl(name) => f(name.as_bytes()),
+            Self::DomainName(name) | Self::TftpServerName(name) | Self::BootfileName(name) => {
+                f(name.as_bytes())
+            }
+            Self::InterfaceMtu(mtu) => f(&mtu.to_be_bytes()),
+            Self::VendorSpecificInformation(data) => f(data),
+            Self::BroadcastAddress(addr) => f(&addr.octets()),
+            Self::ClientFqdn(fqdn) => {
+                f(&[fqdn.flags, fqdn.rcode1, fqdn.rcode2])?;
+                f(fqdn.name)
+            }
+            Self::RelayAgentInformation(info) => f(info.0),
+            Self::ClasslessStaticRoutes(routes) => f(routes.0),
             Self::Unrecognized(_, data) => f(data),
         }
     }
@@ -717,6 +984,197 @@ impl<'a> Ipv4Addrs<'a> {
     pub fn iter(&self) -> impl Iterator<Item = Ipv4Addr> + 'a {
         self.0.iter()
     }
+
+    fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() % 4 != 0 {
+            Err(Error::InvalidPacket)?;
+        }
+
+        Ok(Self(Ipv4AddrsInner::ByteSlice(data)))
+    }
+}
+
+/// The routes of a Classless Static Route Option (121), as per RFC 3442
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StaticRoutes<'a>(&'a [u8]);
+
+impl<'a> StaticRoutes<'a> {
+    /// Create the routes from their RFC 3442 wire encoding
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    /// Iterate over the routes as `(destination, prefix length, router)` triples
+    ///
+    /// The iteration stops at the first malformed route.
+    pub fn iter(&self) -> impl Iterator<Item = (Ipv4Addr, u8, Ipv4Addr)> + 'a {
+        let mut data = self.0;
+
+        core::iter::from_fn(move || {
+            let (&prefix_len, rest) = data.split_first()?;
+            if prefix_len > 32 {
+                return None;
+            }
+
+            let dest_len = (prefix_len as usize).div_ceil(8);
+            if rest.len() < dest_len + 4 {
+                return None;
+            }
+
+            let mut dest = [0; 4];
+            dest[..dest_len].copy_from_slice(&rest[..dest_len]);
+
+            let router: [u8; 4] = rest[dest_len..dest_len + 4].try_into().unwrap();
+
+            data = &rest[dest_len + 4..];
+
+            Some((dest.into(), prefix_len, router.into()))
+        })
+    }
+}
+
+/// The Client FQDN Option (81), as per RFC 4702
+///
+/// Clients use it to announce their fully qualified domain name - or just their host name - and to negotiate
+/// with the server which of them is responsible for updating the DNS records of the client.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ClientFqdn<'a> {
+    /// The flags; see the `FLAG_*` constants
+    pub flags: u8,
+    /// Deprecated; set to 0 by clients and to 255 by servers
+    pub rcode1: u8,
+    /// Deprecated; set to 0 by clients and to 255 by servers
+    pub rcode2: u8,
+    /// The (possibly partial) domain name: in DNS wire format when `FLAG_E` is set,
+    /// or in the deprecated ASCII format otherwise
+    pub name: &'a [u8],
+}
+
+impl<'a> ClientFqdn<'a> {
+    /// The server should perform the A record update
+    pub const FLAG_S: u8 = 0x01;
+    /// The server has overridden the client's preference for the S flag
+    pub const FLAG_O: u8 = 0x02;
+    /// The name is in DNS wire format
+    pub const FLAG_E: u8 = 0x04;
+    /// The server should not perform any DNS updates
+    pub const FLAG_N: u8 = 0x08;
+
+    /// Create the option as sent by a client, with a name in the provided format (see `FLAG_E`)
+    pub const fn new(flags: u8, name: &'a [u8]) -> Self {
+        Self {
+            flags,
+            rcode1: 0,
+            rcode2: 0,
+            name,
+        }
+    }
+
+    /// Iterate over the labels of the name
+    ///
+    /// The iteration stops at the first malformed label.
+    pub fn labels(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let mut data = self.name;
+
+        if self.flags & Self::FLAG_E != 0 {
+            EitherIterator::First(core::iter::from_fn(move || {
+                let (&len, rest) = data.split_first()?;
+
+                if len == 0 || rest.len() < len as usize {
+                    return None;
+                }
+
+                let (label, rest) = rest.split_at(len as usize);
+
+                data = rest;
+
+                Some(label)
+            }))
+        } else {
+            EitherIterator::Second(
+                data.split(|byte| *byte == b'.')
+                    .take_while(|label| !label.is_empty()),
+            )
+        }
+    }
+
+    /// Return the host name of the client (i.e. the first label of the name), if it is valid UTF-8
+    pub fn host(&self) -> Option<&'a str> {
+        self.labels()
+            .next()
+            .and_then(|label| core::str::from_utf8(label).ok())
+    }
+
+    fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        Ok(Self {
+            flags: bytes.byte()?,
+            rcode1: bytes.byte()?,
+            rcode2: bytes.byte()?,
+            name: bytes.remaining(),
+        })
+    }
+}
+
+/// The sub-options of a Relay Agent Information Option (82), as per RFC 3046
+///
+/// Relay agents attach it to the requests they forward, and servers echo it back verbatim in their replies.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RelayAgentInfo<'a>(&'a [u8]);
+
+impl<'a> RelayAgentInfo<'a> {
+    /// Sub-option 1: Agent Circuit ID
+    pub const CIRCUIT_ID: u8 = 1;
+    /// Sub-option 2: Agent Remote ID
+    pub const REMOTE_ID: u8 = 2;
+
+    /// Create the information from its RFC 3046 wire encoding
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    /// Return the RFC 3046 wire encoding of the information
+    pub const fn data(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Iterate over the sub-options as `(code, data)` pairs
+    ///
+    /// The iteration stops at the first malformed sub-option.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &'a [u8])> + 'a {
+        let mut data = self.0;
+
+        core::iter::from_fn(move || {
+            let (&code, rest) = data.split_first()?;
+            let (&len, rest) = rest.split_first()?;
+
+            if rest.len() < len as usize {
+                return None;
+            }
+
+            let (sub_option, rest) = rest.split_at(len as usize);
+
+            data = rest;
+
+            Some((code, sub_option))
+        })
+    }
+
+    /// Return the Agent Circuit ID sub-option, if present
+    pub fn circuit_id(&self) -> Option<&'a [u8]> {
+        self.sub_option(Self::CIRCUIT_ID)
+    }
+
+    /// Return the Agent Remote ID sub-option, if present
+    pub fn remote_id(&self) -> Option<&'a [u8]> {
+        self.sub_option(Self::REMOTE_ID)
+    }
+
+    fn sub_option(&self, code: u8) -> Option<&'a [u8]> {
+        self.iter()
+            .find_map(|(sub_code, data)| (sub_code == code).then_some(data))
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -728,13 +1186,11 @@ enum Ipv4AddrsInner<'a> {
 impl<'a> Ipv4AddrsInner<'a> {
     fn iter(&self) -> impl Iterator<Item = Ipv4Addr> + 'a {
         match self {
-            Self::ByteSlice(data) => {
-                EitherIterator::First((0..data.len()).step_by(4).map(|offset| {
-                    let octets: [u8; 4] = data[offset..offset + 4].try_into().unwrap();
+            Self::ByteSlice(data) => EitherIterator::First(data.chunks_exact(4).map(|octets| {
+                let octets: [u8; 4] = octets.try_into().unwrap();
 
-                    octets.into()
-                }))
-            }
+                octets.into()
+            })),
             Self::DataSlice(data) => EitherIterator::Second(data.iter().cloned()),
         }
     }
@@ -765,6 +1221,11 @@ const SUBNET_MASK: u8 = 1;
 const ROUTER: u8 = 3;
 const DOMAIN_NAME_SERVER: u8 = 6;
 const HOST_NAME: u8 = 12;
+const DOMAIN_NAME: u8 = 15;
+const INTERFACE_MTU: u8 = 26;
+const BROADCAST_ADDRESS: u8 = 28;
+const NTP_SERVERS: u8 = 42;
+const VENDOR_SPECIFIC_INFORMATION: u8 = 43;
 
 // DHCP Extensions
 const REQUESTED_IP_ADDRESS: u8 = 50;
@@ -774,5 +1235,224 @@ const SERVER_IDENTIFIER: u8 = 54;
 const PARAMETER_REQUEST_LIST: u8 = 55;
 const MESSAGE: u8 = 56;
 const MAXIMUM_DHCP_MESSAGE_SIZE: u8 = 57;
+const RENEWAL_TIME: u8 = 58;
+const REBINDING_TIME: u8 = 59;
+const VENDOR_CLASS_IDENTIFIER: u8 = 60;
 const CLIENT_IDENTIFIER: u8 = 61;
+const TFTP_SERVER_NAME: u8 = 66;
+const BOOTFILE_NAME: u8 = 67;
+const CLIENT_FQDN: u8 = 81;
+const RELAY_AGENT_INFORMATION: u8 = 82;
 const CAPTIVE_URL: u8 = 114;
+const CLASSLESS_STATIC_ROUTES: u8 = 121;
+
+#[cfg(test)]
+mod test {
+    use crate::{DhcpOption, Ipv4Addr, Ipv4Addrs, MessageType, Options, Packet, Settings};
+
+    const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+    /// A trivial xorshift generator, so that the fuzzing is reproducible
+    struct XorShift(u32);
+
+    impl XorShift {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+
+            self.0
+        }
+    }
+
+    fn encode_reply(buf: &mut [u8]) -> usize {
+        let gateways = [Ipv4Addr::new(192, 168, 0, 1)];
+        let dns = [Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(8, 8, 4, 4)];
+
+        let options = [
+            DhcpOption::MessageType(MessageType::Ack),
+            DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 0, 1)),
+            DhcpOption::IpAddressLeaseTime(3600),
+            DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)),
+            DhcpOption::Router(Ipv4Addrs::new(&gateways)),
+            DhcpOption::DomainNameServer(Ipv4Addrs::new(&dns)),
+            DhcpOption::HostName("edge"),
+            DhcpOption::CaptiveUrl("http://192.168.0.1/"),
+        ];
+
+        let mut packet = Packet::new_request(MAC, 0x1234, 0, None, false, Options::new(&options));
+        packet.reply = true;
+        packet.yiaddr = Ipv4Addr::new(192, 168, 0, 10);
+
+        packet.encode(buf).unwrap().len()
+    }
+
+    /// Exercise everything that might be done with a decoded packet
+    fn exercise(data: &[u8]) {
+        let Ok(packet) = Packet::decode(data) else {
+            return;
+        };
+
+        for option in packet.options.iter() {
+            match option {
+                DhcpOption::Router(addrs)
+                | DhcpOption::DomainNameServer(addrs)
+                | DhcpOption::NtpServers(addrs) => for _ in addrs.iter() {},
+                DhcpOption::ClasslessStaticRoutes(routes) => for _ in routes.iter() {},
+                DhcpOption::RelayAgentInformation(info) => for _ in info.iter() {},
+                _ => (),
+            }
+        }
+
+        let _ = Settings::new(&packet);
+        let _ = packet.options.requested_params();
+
+        let mut buf = [0; 1500];
+        let _ = packet.encode(&mut buf);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut buf = [0; 1500];
+        let len = encode_reply(&mut buf);
+
+        let packet = Packet::decode(&buf[..len]).unwrap();
+        assert!(packet.is_for_us(&MAC, 0x1234));
+
+        let settings = Settings::new(&packet);
+        assert_eq!(settings.ip, Ipv4Addr::new(192, 168, 0, 10));
+        assert_eq!(settings.gateway, Some(Ipv4Addr::new(192, 168, 0, 1)));
+        assert_eq!(settings.dns1, Some(Ipv4Addr::new(8, 8, 8, 8)));
+        assert_eq!(settings.dns2, Some(Ipv4Addr::new(8, 8, 4, 4)));
+        assert_eq!(settings.captive_url, Some("http://192.168.0.1/"));
+    }
+
+    #[test]
+    fn test_pad_options() {
+        let mut buf = [0; 1500];
+        let len = encode_reply(&mut buf);
+
+        // Re-encode the options with a pad option in front of each of them
+        let options_start = 240;
+        let mut padded = [0; 1500];
+        padded[..options_start].copy_from_slice(&buf[..options_start]);
+
+        let mut offset = options_start;
+        let mut src = options_start;
+        while buf[src] != 255 {
+            let option_len = 2 + buf[src + 1] as usize;
+
+            padded[offset] = 0;
+            padded[offset + 1..offset + 1 + option_len]
+                .copy_from_slice(&buf[src..src + option_len]);
+
+            offset += 1 + option_len;
+            src += option_len;
+        }
+
+        padded[offset] = 255;
+
+        let original = Packet::decode(&buf[..len]).unwrap();
+        let packet = Packet::decode(&padded[..offset + 1]).unwrap();
+
+        assert!(packet.options.iter().eq(original.options.iter()));
+    }
+
+    #[test]
+    fn test_malformed_address_list() {
+        let mut buf = [0; 1500];
+        let len = encode_reply(&mut buf);
+
+        // Append a router option which is not a multiple of 4 bytes long
+        let end = buf[..len].iter().rposition(|b| *b == 255).unwrap();
+        buf[end..end + 8].copy_from_slice(&[3, 5, 10, 0, 0, 1, 10, 255]);
+
+        assert!(Packet::decode(&buf[..end + 8]).is_err());
+    }
+
+    #[test]
+    fn test_fuzz_truncated() {
+        let mut buf = [0; 1500];
+        let len = encode_reply(&mut buf);
+
+        for len in 0..=len {
+            exercise(&buf[..len]);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_mutated() {
+        let mut buf = [0; 1500];
+        let len = encode_reply(&mut buf);
+
+        let mut rng = XorShift(0x2545_f491);
+
+        for _ in 0..20000 {
+            let mut data = [0; 1500];
+            data[..len].copy_from_slice(&buf[..len]);
+
+            // Mutate a few bytes, mostly in the options area
+            for _ in 0..1 + rng.next() % 8 {
+                let offset = if rng.next() % 4 == 0 {
+                    rng.next() as usize % len
+                } else {
+                    240 + rng.next() as usize % (len - 240)
+                };
+
+                data[offset] = rng.next() as u8;
+            }
+
+            exercise(&data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_random_options() {
+        let mut buf = [0; 1500];
+        encode_reply(&mut buf);
+
+        let mut rng = XorShift(0x9e37_79b9);
+
+        for _ in 0..20000 {
+            let len = 240 + rng.next() as usize % 128;
+
+            for byte in &mut buf[240..len] {
+                // Bias towards small values, which are more likely to be valid option codes and lengths
+                *byte = match rng.next() % 4 {
+                    0 => rng.next() as u8 % 8,
+                    1 => [0, 1, 3, 6, 12, 42, 53, 54, 82, 121, 255][rng.next() as usize % 11],
+                    _ => rng.next() as u8,
+                };
+            }
+
+            exercise(&buf[..len]);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_dhcpv6() {
+        let mut rng = XorShift(0x1b87_3593);
+
+        let mut buf = [0; 256];
+
+        for _ in 0..20000 {
+            let len = 4 + rng.next() as usize % (buf.len() - 4);
+
+            buf[0] = 1 + (rng.next() % 11) as u8;
+            for byte in &mut buf[1..len] {
+                *byte = match rng.next() % 3 {
+                    0 => 0,
+                    1 => rng.next() as u8 % 32,
+                    _ => rng.next() as u8,
+                };
+            }
+
+            if let Ok(message) = crate::dhcpv6::Message::decode(&buf[..len]) {
+                let _ = crate::dhcpv6::client::Settings::new(&message, 0);
+
+                let mut out = [0; 512];
+                let _ = message.encode(&mut out);
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use log::{debug, warn};
+
+use super::*;
+
+/// A simple DHCP relay agent, as per RFC 1542 and RFC 3046.
+///
+/// The relay agent forwards the requests broadcast by the clients on its segment to a DHCP server
+/// on another segment, and the replies of the server back to the clients.
+///
+/// Like the server, the relay agent is unaware of the IP/UDP transport layer and operates purely in terms of packets.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Relay<'a> {
+    /// The IP address of the relay agent on the clients' segment;
+    /// the server selects the address pool by it, and sends its replies to it
+    pub giaddr: Ipv4Addr,
+    /// The IP address of the DHCP server the requests are forwarded to
+    pub server: Ipv4Addr,
+    /// The relay agent information (option 82) attached to the forwarded requests, if any
+    pub relay_agent_info: Option<RelayAgentInfo<'a>>,
+    /// Requests which had already passed through that many relay agents are dropped
+    pub max_hops: u8,
+}
+
+impl<'a> Relay<'a> {
+    /// Create a new relay agent
+    ///
+    /// # Arguments
+    /// - `giaddr`: The IP address of the relay agent on the clients' segment.
+    /// - `server`: The IP address of the DHCP server.
+    pub const fn new(giaddr: Ipv4Addr, server: Ipv4Addr) -> Self {
+        Self {
+            giaddr,
+            server,
+            relay_agent_info: None,
+            max_hops: 16,
+        }
+    }
+
+    /// Prepare a request of a client for forwarding to the server
+    ///
+    /// Returns `None` if the packet should not be forwarded.
+    pub fn forward_request<'o>(
+        &self,
+        request: &Packet<'o>,
+        opt_buf: &'o mut [DhcpOption<'o>],
+    ) -> Option<Packet<'o>>
+    where
+        'a: 'o,
+    {
+        if request.reply {
+            return None;
+        }
+
+        if request.hops >= self.max_hops {
+            warn!("Dropping request, hop limit reached: {request:?}");
+            return None;
+        }
+
+        // Requests already relayed by another agent keep their original `giaddr` and option 82
+        let relayed = !request.giaddr.is_unspecified();
+
+        let options = match self.relay_agent_info {
+            Some(relay_agent_info) if !relayed && request.options.relay_agent_info().is_none() => {
+                let mut offset = 0;
+
+                for option in request.options.iter() {
+                    if offset == opt_buf.len() - 1 {
+                        warn!(
+                            "Too many options, forwarding request without relay agent information"
+                        );
+                        return Some(self.relayed(request, request.options.clone(), relayed));
+                    }
+
+                    opt_buf[offset] = option;
+                    offset += 1;
+                }
+
+                opt_buf[offset] = DhcpOption::RelayAgentInformation(relay_agent_info);
+                offset += 1;
+
+                Options::new(&opt_buf[..offset])
+            }
+            _ => request.options.clone(),
+        };
+
+        Some(self.relayed(request, options, relayed))
+    }
+
+    /// Prepare a reply of the server for forwarding to the client
+    ///
+    /// The relay agent information (option 82) echoed back by the server is removed.
+    ///
+    /// Returns `None` if the packet should not be forwarded.
+    pub fn forward_reply<'o>(
+        &self,
+        reply: &Packet<'o>,
+        opt_buf: &'o mut [DhcpOption<'o>],
+    ) -> Option<Packet<'o>> {
+        if !reply.reply {
+            return None;
+        }
+
+        if reply.giaddr != self.giaddr {
+            debug!("Ignoring reply, not relayed by this agent: {reply:?}");
+            return None;
+        }
+
+        let mut offset = 0;
+
+        for option in reply
+            .options
+            .iter()
+            .filter(|option| option.code() != DhcpOption::CODE_RELAY_AGENT_INFO)
+        {
+            if offset == opt_buf.len() {
+                warn!("Dropping reply, too many options: {reply:?}");
+                return None;
+            }
+
+            opt_buf[offset] = option;
+            offset += 1;
+        }
+
+        Some(Packet {
+            options: Options::new(&opt_buf[..offset]),
+            ..reply.clone()
+        })
+    }
+
+    fn relayed<'o>(&self, request: &Packet<'o>, options: Options<'o>, relayed: bool) -> Packet<'o> {
+        Packet {
+            hops: request.hops + 1,
+            giaddr: if relayed { request.giaddr } else { self.giaddr },
+            options,
+            ..request.clone()
+        }
+    }
+}
@@ -6,8 +6,155 @@ use super::*;
 
 #[derive(Clone, Debug)]
 pub struct Lease {
-    mac: [u8; 16],
-    expires: u64,
+    pub mac: [u8; 16],
+    pub expires: u64,
+    /// The lease was revoked by `Server::revoke`; its renewal is NAKed
+    pub revoked: bool,
+    /// The lease tracks a declined IP address (i.e. an address found to be in use by an unknown host)
+    /// rather than a real client, so its MAC address is meaningless
+    pub declined: bool,
+}
+
+impl Lease {
+    /// Return `true` if the lease is held by the client with the provided MAC address
+    pub fn is_held_by(&self, mac: &[u8; 16]) -> bool {
+        !self.declined && self.mac == *mac
+    }
+}
+
+/// A storage for the leases handed out by the DHCP server.
+///
+/// By default, the server keeps its leases in a fixed-capacity in-memory map, but
+/// users can plug their own implementation - e.g. one persisting the leases to flash,
+/// so that they survive a reboot of the gateway.
+pub trait LeaseStore {
+    /// Get the lease for the provided IP address, if any
+    fn get(&self, ip: Ipv4Addr) -> Option<Lease>;
+
+    /// Get the IP address leased to the client with the provided MAC address, if any
+    ///
+    /// Declined addresses are not leased to any client, so they are never returned.
+    fn get_by_mac(&self, mac: &[u8; 16]) -> Option<Ipv4Addr>;
+
+    /// Insert or replace the lease for the provided IP address
+    ///
+    /// Returns `false` if the store is full.
+    fn insert(&mut self, ip: Ipv4Addr, lease: Lease) -> bool;
+
+    /// Remove the lease for the provided IP address
+    ///
+    /// Returns `true` if there was such a lease.
+    fn remove(&mut self, ip: Ipv4Addr) -> bool;
+
+    /// Iterate over all leases in the store
+    fn iter(&self) -> impl Iterator<Item = (Ipv4Addr, Lease)>;
+
+    /// Remove the lease of the client with the provided MAC address
+    ///
+    /// Returns `true` if there was such a lease.
+    fn remove_by_mac(&mut self, mac: &[u8; 16]) -> bool {
+        if let Some(ip) = self.get_by_mac(mac) {
+            self.remove(ip)
+        } else {
+            false
+        }
+    }
+
+    /// Find a lease which had expired as of `now`
+    fn find_expired(&self, now: u64) -> Option<Ipv4Addr> {
+        self.iter()
+            .find_map(|(ip, lease)| (now > lease.expires).then_some(ip))
+    }
+}
+
+impl<T> LeaseStore for &mut T
+where
+    T: LeaseStore,
+{
+    fn get(&self, ip: Ipv4Addr) -> Option<Lease> {
+        (**self).get(ip)
+    }
+
+    fn get_by_mac(&self, mac: &[u8; 16]) -> Option<Ipv4Addr> {
+        (**self).get_by_mac(mac)
+    }
+
+    fn insert(&mut self, ip: Ipv4Addr, lease: Lease) -> bool {
+        (**self).insert(ip, lease)
+    }
+
+    fn remove(&mut self, ip: Ipv4Addr) -> bool {
+        (**self).remove(ip)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Ipv4Addr, Lease)> {
+        (**self).iter()
+    }
+
+    fn remove_by_mac(&mut self, mac: &[u8; 16]) -> bool {
+        (**self).remove_by_mac(mac)
+    }
+
+    fn find_expired(&self, now: u64) -> Option<Ipv4Addr> {
+        (**self).find_expired(now)
+    }
+}
+
+impl<const N: usize> LeaseStore for heapless::LinearMap<Ipv4Addr, Lease, N> {
+    fn get(&self, ip: Ipv4Addr) -> Option<Lease> {
+        heapless::LinearMap::get(self, &ip).cloned()
+    }
+
+    fn get_by_mac(&self, mac: &[u8; 16]) -> Option<Ipv4Addr> {
+        heapless::LinearMap::iter(self).find_map(|(ip, lease)| lease.is_held_by(mac).then_some(*ip))
+    }
+
+    fn insert(&mut self, ip: Ipv4Addr, lease: Lease) -> bool {
+        heapless::LinearMap::insert(self, ip, lease).is_ok()
+    }
+
+    fn remove(&mut self, ip: Ipv4Addr) -> bool {
+        heapless::LinearMap::remove(self, &ip).is_some()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Ipv4Addr, Lease)> {
+        heapless::LinearMap::iter(self).map(|(ip, lease)| (*ip, lease.clone()))
+    }
+}
+
+/// A hook notified as clients acquire and lose their leases, along with the host names they announce
+/// (via the Client FQDN option 81 or the Host Name option 12) or that are configured in their reservations.
+///
+/// Useful for registering the clients in a local DNS or mDNS responder, so that they become resolvable by name.
+pub trait HostnameRegistry {
+    /// The address was leased (or its lease was renewed) to a client with the provided host name
+    fn register(&mut self, ip: Ipv4Addr, hostname: &str);
+
+    /// The address is no longer leased, because its lease was released, revoked, expired or dropped
+    fn unregister(&mut self, ip: Ipv4Addr);
+}
+
+impl<T> HostnameRegistry for &mut T
+where
+    T: HostnameRegistry,
+{
+    fn register(&mut self, ip: Ipv4Addr, hostname: &str) {
+        (**self).register(ip, hostname)
+    }
+
+    fn unregister(&mut self, ip: Ipv4Addr) {
+        (**self).unregister(ip)
+    }
+}
+
+/// A `HostnameRegistry` implementation which ignores the host names of the clients
+#[derive(Clone, Debug, Default)]
+pub struct NoHostnames;
+
+impl HostnameRegistry for NoHostnames {
+    fn register(&mut self, _ip: Ipv4Addr, _hostname: &str) {}
+
+    fn unregister(&mut self, _ip: Ipv4Addr) {}
 }
 
 #[derive(Clone, Debug)]
@@ -16,6 +163,102 @@ pub enum Action<'a> {
     Request(Ipv4Addr, &'a [u8; 16]),
     Release(Ipv4Addr, &'a [u8; 16]),
     Decline(Ipv4Addr, &'a [u8; 16]),
+    Inform(Ipv4Addr, &'a [u8; 16]),
+}
+
+/// An address pool served by a `Server` with `Server::handle_pool_request`
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Pool<'a> {
+    pub range_start: Ipv4Addr,
+    pub range_end: Ipv4Addr,
+    /// The options sent to the clients of the pool; `options.ip` is the address of the server on the pool's segment
+    pub options: ServerOptions<'a>,
+}
+
+impl<'a> Pool<'a> {
+    /// Create a new address pool
+    ///
+    /// # Arguments
+    /// - `range_start`: The first IP address of the pool.
+    /// - `range_end`: The last IP address of the pool.
+    /// - `options`: The options sent to the clients of the pool.
+    pub const fn new(
+        range_start: Ipv4Addr,
+        range_end: Ipv4Addr,
+        options: ServerOptions<'a>,
+    ) -> Self {
+        Self {
+            range_start,
+            range_end,
+            options,
+        }
+    }
+
+    /// Return `true` if the provided address is in the subnet of the pool
+    ///
+    /// When the pool has no subnet mask configured, only addresses within its range are considered in the subnet.
+    pub fn in_subnet(&self, addr: Ipv4Addr) -> bool {
+        let addr: u32 = addr.into();
+
+        if let Some(subnet) = self.options.subnet {
+            let mask: u32 = subnet.into();
+
+            addr & mask == u32::from(self.range_start) & mask
+        } else {
+            addr >= self.range_start.into() && addr <= self.range_end.into()
+        }
+    }
+
+    /// Select the pool serving a request, as described in `Server::handle_pool_request`
+    pub fn select<'p>(
+        pools: &'p [Pool<'a>],
+        giaddr: Ipv4Addr,
+        interface_ip: Option<Ipv4Addr>,
+    ) -> Option<&'p Pool<'a>> {
+        if !giaddr.is_unspecified() {
+            pools.iter().find(|pool| pool.in_subnet(giaddr))
+        } else if let Some(interface_ip) = interface_ip {
+            pools.iter().find(|pool| pool.options.ip == interface_ip)
+        } else {
+            pools.first()
+        }
+    }
+}
+
+/// A static reservation of an IP address for the client with a given MAC address
+///
+/// Reserved clients always receive their reserved address (even if it is outside of the server's pool),
+/// while the reserved address is never handed out to other clients.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Reservation<'a> {
+    pub mac: [u8; 16],
+    pub ip: Ipv4Addr,
+    /// The host name to assign to the client (option 12), if any
+    pub hostname: Option<&'a str>,
+    /// DNS servers to send to the client instead of the ones in `ServerOptions::dns`
+    pub dns: Option<&'a [Ipv4Addr]>,
+}
+
+impl Reservation<'_> {
+    /// Create a new reservation of `ip` for the client with the provided (Ethernet) MAC address
+    pub const fn new(mac: [u8; 6], ip: Ipv4Addr) -> Self {
+        let mut chaddr = [0; 16];
+
+        let mut index = 0;
+        while index < mac.len() {
+            chaddr[index] = mac[index];
+            index += 1;
+        }
+
+        Self {
+            mac: chaddr,
+            ip,
+            hostname: None,
+            dns: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -27,6 +270,19 @@ pub struct ServerOptions<'a> {
     pub dns: &'a [Ipv4Addr],
     pub captive_url: Option<&'a str>,
     pub lease_duration_secs: u32,
+    pub reservations: &'a [Reservation<'a>],
+    /// NTP servers (option 42)
+    pub ntp: &'a [Ipv4Addr],
+    /// Interface MTU (option 26)
+    pub mtu: Option<u16>,
+    /// Domain name (option 15)
+    pub domain_name: Option<&'a str>,
+    /// TFTP server name (option 66)
+    pub tftp_server: Option<&'a str>,
+    /// Bootfile name (option 67)
+    pub bootfile: Option<&'a str>,
+    /// Vendor specific information (option 43), already encoded
+    pub vendor_specific: Option<&'a [u8]>,
 }
 
 impl<'a> ServerOptions<'a> {
@@ -45,9 +301,30 @@ impl<'a> ServerOptions<'a> {
             dns: &[],
             captive_url: None,
             lease_duration_secs: 7200,
+            reservations: &[],
+            ntp: &[],
+            mtu: None,
+            domain_name: None,
+            tftp_server: None,
+            bootfile: None,
+            vendor_specific: None,
         }
     }
 
+    /// Return the static reservation for the client with the provided MAC address, if any
+    pub fn reservation(&self, mac: &[u8; 16]) -> Option<&Reservation<'a>> {
+        self.reservations
+            .iter()
+            .find(|reservation| reservation.mac == *mac)
+    }
+
+    /// Return `true` if the provided IP address is reserved for a client other than the one with the provided MAC address
+    pub fn is_reserved_for_other(&self, mac: &[u8; 16], ip: Ipv4Addr) -> bool {
+        self.reservations
+            .iter()
+            .any(|reservation| reservation.ip == ip && reservation.mac != *mac)
+    }
+
     pub fn process<'o>(&self, request: &'o Packet<'o>) -> Option<Action<'o>> {
         if request.reply {
             return None;
@@ -99,10 +376,14 @@ impl<'a> ServerOptions<'a> {
                 Some(Action::Request(requested_ip, &request.chaddr))
             }
             MessageType::Release if server_identifier == Some(self.ip) => {
-                Some(Action::Release(request.yiaddr, &request.chaddr))
+                Some(Action::Release(request.ciaddr, &request.chaddr))
             }
-            MessageType::Decline if server_identifier == Some(self.ip) => {
-                Some(Action::Decline(request.yiaddr, &request.chaddr))
+            MessageType::Decline if server_identifier == Some(self.ip) => Some(Action::Decline(
+                request.options.requested_ip()?,
+                &request.chaddr,
+            )),
+            MessageType::Inform if !request.ciaddr.is_unspecified() => {
+                Some(Action::Inform(request.ciaddr, &request.chaddr))
             }
             _ => None,
         }
@@ -110,7 +391,7 @@ impl<'a> ServerOptions<'a> {
 
     pub fn offer(
         &self,
-        request: &Packet,
+        request: &Packet<'a>,
         yiaddr: Ipv4Addr,
         opt_buf: &'a mut [DhcpOption<'a>],
     ) -> Packet<'a> {
@@ -119,7 +400,7 @@ impl<'a> ServerOptions<'a> {
 
     pub fn ack_nak(
         &self,
-        request: &Packet,
+        request: &Packet<'a>,
         ip: Option<Ipv4Addr>,
         opt_buf: &'a mut [DhcpOption<'a>],
     ) -> Packet<'a> {
@@ -135,23 +416,79 @@ impl<'a> ServerOptions<'a> {
         )
     }
 
+    /// Acknowledge a DHCPINFORM request
+    ///
+    /// The client already has an externally configured IP address, so the reply carries
+    /// only the configuration parameters - and neither an address, nor a lease time.
+    pub fn inform_ack(
+        &self,
+        request: &Packet<'a>,
+        opt_buf: &'a mut [DhcpOption<'a>],
+    ) -> Packet<'a> {
+        self.reply_with(request, MessageType::Ack, None, None, opt_buf)
+    }
+
     fn reply(
         &self,
-        request: &Packet,
+        request: &Packet<'a>,
+        message_type: MessageType,
+        ip: Option<Ipv4Addr>,
+        buf: &'a mut [DhcpOption<'a>],
+    ) -> Packet<'a> {
+        self.reply_with(
+            request,
+            message_type,
+            ip,
+            Some(self.lease_duration_secs),
+            buf,
+        )
+    }
+
+    fn reply_with(
+        &self,
+        request: &Packet<'a>,
         message_type: MessageType,
         ip: Option<Ipv4Addr>,
+        lease_duration_secs: Option<u32>,
         buf: &'a mut [DhcpOption<'a>],
     ) -> Packet<'a> {
+        let reservation = self.reservation(&request.chaddr);
+
+        // Sent only when requested by the client
+        let extra = [
+            (!self.ntp.is_empty()).then_some(DhcpOption::NtpServers(Ipv4Addrs::new(self.ntp))),
+            self.mtu.map(DhcpOption::InterfaceMtu),
+            self.domain_name.map(DhcpOption::DomainName),
+            self.tftp_server.map(DhcpOption::TftpServerName),
+            self.bootfile.map(DhcpOption::BootfileName),
+            self.vendor_specific
+                .map(DhcpOption::VendorSpecificInformation),
+        ];
+
+        let mut extra_buf = [DhcpOption::Message(""); 6];
+        let mut extra_len = 0;
+
+        for option in extra.into_iter().flatten() {
+            extra_buf[extra_len] = option;
+            extra_len += 1;
+        }
+
         let reply = request.new_reply(
             ip,
-            request.options.reply(
+            Options::internal_reply(
+                request.options.requested_params(),
                 message_type,
                 self.ip,
-                self.lease_duration_secs as _,
+                lease_duration_secs,
                 self.gateways,
                 self.subnet,
-                self.dns,
+                reservation
+                    .and_then(|reservation| reservation.dns)
+                    .unwrap_or(self.dns),
                 self.captive_url,
+                reservation.and_then(|reservation| reservation.hostname),
+                &extra_buf[..extra_len],
+                request.options.relay_agent_info(),
                 buf,
             ),
         );
@@ -165,12 +502,16 @@ impl<'a> ServerOptions<'a> {
 /// A simple DHCP server.
 /// The server is unaware of the IP/UDP transport layer and operates purely in terms of packets
 /// represented as Rust slices.
+///
+/// The leases are kept in a `LeaseStore`, which - by default - is an in-memory map of up to `N` leases.
+/// The host names of the clients are reported to a `HostnameRegistry`, which - by default - ignores them.
 #[derive(Clone, Debug)]
-pub struct Server<F, const N: usize> {
+pub struct Server<F, const N: usize, S = heapless::LinearMap<Ipv4Addr, Lease, N>, H = NoHostnames> {
     pub now: F,
     pub range_start: Ipv4Addr,
     pub range_end: Ipv4Addr,
-    pub leases: heapless::LinearMap<Ipv4Addr, Lease, N>,
+    pub leases: S,
+    pub hostnames: H,
 }
 
 impl<F, const N: usize> Server<F, N>
@@ -190,30 +531,162 @@ where
             range_start: Ipv4Addr::new(octets[0], octets[1], octets[2], 50),
             range_end: Ipv4Addr::new(octets[0], octets[1], octets[2], 200),
             leases: heapless::LinearMap::new(),
+            hostnames: NoHostnames,
+        }
+    }
+}
+
+impl<F, const N: usize, S> Server<F, N, S>
+where
+    F: FnMut() -> u64,
+    S: LeaseStore,
+{
+    /// Create a new DHCP server which keeps its leases in the provided lease store.
+    ///
+    /// # Arguments
+    /// - `now`: A closure that returns the current time in seconds since some epoch.
+    ///   When the store persists leases across reboots, the epoch should persist as well.
+    /// - `ip`: The IP address of the server.
+    /// - `leases`: The lease store.
+    pub const fn new_with_store(now: F, ip: Ipv4Addr, leases: S) -> Self {
+        let octets = ip.octets();
+
+        Self {
+            now,
+            range_start: Ipv4Addr::new(octets[0], octets[1], octets[2], 50),
+            range_end: Ipv4Addr::new(octets[0], octets[1], octets[2], 200),
+            leases,
+            hostnames: NoHostnames,
+        }
+    }
+}
+
+impl<F, const N: usize, S, H> Server<F, N, S, H>
+where
+    F: FnMut() -> u64,
+    S: LeaseStore,
+    H: HostnameRegistry,
+{
+    /// Report the host names of the clients to the provided registry
+    ///
+    /// The leases already in the lease store are not reported.
+    pub fn with_hostnames<R>(self, hostnames: R) -> Server<F, N, S, R>
+    where
+        R: HostnameRegistry,
+    {
+        Server {
+            now: self.now,
+            range_start: self.range_start,
+            range_end: self.range_end,
+            leases: self.leases,
+            hostnames,
+        }
+    }
+
+    /// Reconfigure the server at run-time, without dropping its leases' database.
+    ///
+    /// Existing leases which are still within the new address range are preserved,
+    /// while the ones outside of the new range or not matching the new reservations are removed,
+    /// so that their renewal requests are NAKed and the clients are forced to acquire a new address.
+    /// Preserved leases are shortened so that they do not exceed the new lease duration.
+    ///
+    /// The new options (gateways, DNS servers, subnet, etc.) take effect once they are provided to `handle_request`
+    /// instead of the old ones, i.e. `server_options` should be passed to all subsequent `handle_request` calls.
+    /// The clients pick them up when renewing their (shortened) leases.
+    ///
+    /// # Arguments
+    /// - `range_start`: The first IP address of the new address pool.
+    /// - `range_end`: The last IP address of the new address pool.
+    /// - `server_options`: The new server options.
+    ///
+    /// Returns the number of removed leases.
+    pub fn reconfigure(
+        &mut self,
+        range_start: Ipv4Addr,
+        range_end: Ipv4Addr,
+        server_options: &ServerOptions,
+    ) -> usize {
+        self.range_start = range_start;
+        self.range_end = range_end;
+
+        let mut removed = 0;
+
+        loop {
+            let stale = self.leases.iter().find_map(|(addr, lease)| {
+                // Same as `is_available`, but for the client holding the lease
+                let valid = if lease.declined {
+                    self.in_range(addr)
+                } else if let Some(reservation) = server_options.reservation(&lease.mac) {
+                    reservation.ip == addr
+                } else {
+                    self.in_range(addr) && !server_options.is_reserved_for_other(&lease.mac, addr)
+                };
+
+                (!valid).then_some(addr)
+            });
+
+            let Some(addr) = stale else {
+                break;
+            };
+
+            debug!("Removing lease for IP {addr}, not valid with the new configuration");
+
+            self.drop_lease(addr);
+            removed += 1;
         }
+
+        let max_expires = (self.now)() + server_options.lease_duration_secs as u64;
+
+        loop {
+            let longer = self
+                .leases
+                .iter()
+                .find(|(_, lease)| lease.expires > max_expires);
+
+            let Some((addr, mut lease)) = longer else {
+                break;
+            };
+
+            lease.expires = max_expires;
+            self.leases.insert(addr, lease);
+        }
+
+        removed
     }
 
     pub fn handle_request<'o>(
         &mut self,
         opt_buf: &'o mut [DhcpOption<'o>],
         server_options: &'o ServerOptions,
-        request: &Packet,
+        request: &'o Packet<'o>,
     ) -> Option<Packet<'o>> {
+        if !request.giaddr.is_unspecified() && !self.serves_relay(server_options, request.giaddr) {
+            debug!(
+                "Ignoring request relayed by {}, not in the subnet of this server's pool",
+                request.giaddr
+            );
+            return None;
+        }
+
         server_options
             .process(request)
             .and_then(|action| match action {
                 Action::Discover(requested_ip, mac) => {
-                    let ip = requested_ip
-                        .and_then(|ip| self.is_available(mac, ip).then_some(ip))
-                        .or_else(|| self.current_lease(mac))
-                        .or_else(|| self.available());
+                    let ip = if let Some(reservation) = server_options.reservation(mac) {
+                        Some(reservation.ip)
+                    } else {
+                        requested_ip
+                            .and_then(|ip| self.is_available(server_options, mac, ip).then_some(ip))
+                            .or_else(|| self.current_lease(mac).filter(|ip| self.in_range(*ip)))
+                            .or_else(|| self.available(server_options))
+                    };
 
                     ip.map(|ip| server_options.offer(request, ip, opt_buf))
                 }
                 Action::Request(ip, mac) => {
                     let now = (self.now)();
 
-                    let ip = (self.is_available(mac, ip)
+                    let ip = (self.is_available(server_options, mac, ip)
                         && self.add_lease(
                             ip,
                             request.chaddr,
@@ -221,48 +694,193 @@ where
                         ))
                     .then_some(ip);
 
+                    if let Some(ip) = ip {
+                        let hostname = server_options
+                            .reservation(mac)
+                            .and_then(|reservation| reservation.hostname)
+                            .or_else(|| request.options.client_hostname());
+
+                        if let Some(hostname) = hostname {
+                            self.hostnames.register(ip, hostname);
+                        }
+                    }
+
                     Some(server_options.ack_nak(request, ip, opt_buf))
                 }
-                Action::Release(_ip, mac) | Action::Decline(_ip, mac) => {
-                    self.remove_lease(mac);
+                Action::Release(ip, mac) => {
+                    if self.is_leased_to(mac, ip) {
+                        debug!("Releasing lease for IP {ip}");
+
+                        self.drop_lease(ip);
+                    }
+
+                    None
+                }
+                Action::Decline(ip, mac) => {
+                    if self.is_leased_to(mac, ip) || self.leases.get(ip).is_none() {
+                        // The client found the address to be already in use, so it
+                        // should not be handed out to anyone for a while
+                        warn!("IP {ip} declined, marking it as unavailable");
+
+                        self.mark_in_use(ip, server_options.lease_duration_secs);
+                    }
 
                     None
                 }
+                Action::Inform(ip, _) => {
+                    debug!("Answering DHCPINFORM from IP {ip}");
+
+                    Some(server_options.inform_ack(request, opt_buf))
+                }
             })
     }
 
-    fn is_available(&mut self, mac: &[u8; 16], addr: Ipv4Addr) -> bool {
-        let pos: u32 = addr.into();
+    /// Same as `handle_request`, but serves several address pools (e.g. the local segment and the segments
+    /// behind DHCP relay agents), each one with its own options (gateway, DNS, lease duration, etc.).
+    ///
+    /// All pools share the lease store of the server, so their address ranges must not overlap.
+    /// The server's own address range is not used.
+    ///
+    /// The pool is selected as follows:
+    /// - For requests forwarded by a relay agent, the pool in the subnet of the relay agent's address (`giaddr`)
+    /// - Otherwise, the pool whose server IP is the address of the interface the request was received on (`interface_ip`),
+    ///   or the first pool, if `interface_ip` is not provided
+    ///
+    /// Requests for which no pool is found are ignored.
+    pub fn handle_pool_request<'o>(
+        &mut self,
+        opt_buf: &'o mut [DhcpOption<'o>],
+        pools: &'o [Pool<'o>],
+        interface_ip: Option<Ipv4Addr>,
+        request: &'o Packet<'o>,
+    ) -> Option<Packet<'o>> {
+        let Some(pool) = Pool::select(pools, request.giaddr, interface_ip) else {
+            debug!("Ignoring request, no address pool for it: {request:?}");
+            return None;
+        };
 
-        let start: u32 = self.range_start.into();
-        let end: u32 = self.range_end.into();
+        let range_start = core::mem::replace(&mut self.range_start, pool.range_start);
+        let range_end = core::mem::replace(&mut self.range_end, pool.range_end);
+
+        let reply = self.handle_request(opt_buf, &pool.options, request);
+
+        self.range_start = range_start;
+        self.range_end = range_end;
+
+        reply
+    }
+
+    /// Revoke the lease for the provided IP address, e.g. to kick a misbehaving client off the network.
+    ///
+    /// The lease is kept until it expires, yet its renewal is NAKed and the address is not offered to the client again,
+    /// so the client is forced to go through a full DISCOVER cycle and to acquire a new address.
+    ///
+    /// Returns `true` if there was such a lease.
+    pub fn revoke(&mut self, ip: Ipv4Addr) -> bool {
+        let Some(mut lease) = self.leases.get(ip).filter(|lease| !lease.declined) else {
+            return false;
+        };
+
+        debug!("Revoking lease for IP {ip}");
+
+        lease.revoked = true;
+
+        self.hostnames.unregister(ip);
+
+        self.leases.insert(ip, lease)
+    }
+
+    /// Same as `revoke`, but revokes the lease of the client with the provided MAC address
+    pub fn revoke_by_mac(&mut self, mac: &[u8; 16]) -> bool {
+        self.leases
+            .get_by_mac(mac)
+            .map(|ip| self.revoke(ip))
+            .unwrap_or(false)
+    }
+
+    /// Mark the provided IP address as used by an unknown host (i.e. one with a statically configured address),
+    /// so that it is not handed out to clients for the provided duration.
+    ///
+    /// Any lease for that address is dropped.
+    pub fn mark_in_use(&mut self, ip: Ipv4Addr, duration_secs: u32) {
+        let expires = (self.now)() + duration_secs as u64;
+
+        self.drop_lease(ip);
+        self.leases.insert(
+            ip,
+            Lease {
+                mac: [0; 16],
+                expires,
+                revoked: false,
+                declined: true,
+            },
+        );
+    }
+
+    /// Return `true` if the server's address pool is in the subnet of the provided relay agent,
+    /// i.e. if the server should answer the requests relayed by it
+    ///
+    /// When no subnet mask is configured, requests from all relay agents are answered.
+    fn serves_relay(&self, server_options: &ServerOptions, giaddr: Ipv4Addr) -> bool {
+        let Some(subnet) = server_options.subnet else {
+            return true;
+        };
 
-        pos >= start
-            && pos <= end
-            && match self.leases.get(&addr) {
-                Some(lease) => lease.mac == *mac || (self.now)() > lease.expires,
+        let mask: u32 = subnet.into();
+
+        u32::from(giaddr) & mask == u32::from(self.range_start) & mask
+    }
+
+    fn is_available(
+        &mut self,
+        server_options: &ServerOptions,
+        mac: &[u8; 16],
+        addr: Ipv4Addr,
+    ) -> bool {
+        if let Some(reservation) = server_options.reservation(mac) {
+            return reservation.ip == addr;
+        }
+
+        if server_options.is_reserved_for_other(mac, addr) {
+            return false;
+        }
+
+        self.in_range(addr)
+            && match self.leases.get(addr) {
+                Some(lease) => {
+                    (lease.is_held_by(mac) && !lease.revoked) || (self.now)() > lease.expires
+                }
                 None => true,
             }
     }
 
-    fn available(&mut self) -> Option<Ipv4Addr> {
+    fn available(&mut self, server_options: &ServerOptions) -> Option<Ipv4Addr> {
         let start: u32 = self.range_start.into();
         let end: u32 = self.range_end.into();
 
+        let reserved = |addr: Ipv4Addr| {
+            server_options
+                .reservations
+                .iter()
+                .any(|reservation| reservation.ip == addr)
+        };
+
         for pos in start..end + 1 {
             let addr = pos.into();
 
-            if !self.leases.contains_key(&addr) {
+            if self.leases.get(addr).is_none() && !reserved(addr) {
                 return Some(addr);
             }
         }
 
-        if let Some(addr) = self
-            .leases
-            .iter()
-            .find_map(|(addr, lease)| ((self.now)() > lease.expires).then_some(*addr))
-        {
-            self.leases.remove(&addr);
+        let now = (self.now)();
+
+        let expired = self.leases.iter().find_map(|(addr, lease)| {
+            (now > lease.expires && self.in_range(addr) && !reserved(addr)).then_some(addr)
+        });
+
+        if let Some(addr) = expired {
+            self.drop_lease(addr);
 
             Some(addr)
         } else {
@@ -270,27 +888,81 @@ where
         }
     }
 
+    fn in_range(&self, addr: Ipv4Addr) -> bool {
+        let pos: u32 = addr.into();
+
+        let start: u32 = self.range_start.into();
+        let end: u32 = self.range_end.into();
+
+        pos >= start && pos <= end
+    }
+
     fn current_lease(&self, mac: &[u8; 16]) -> Option<Ipv4Addr> {
         self.leases
-            .iter()
-            .find_map(|(addr, lease)| (lease.mac == *mac).then_some(*addr))
+            .get_by_mac(mac)
+            .filter(|addr| !self.leases.get(*addr).is_some_and(|lease| lease.revoked))
+    }
+
+    /// Remove all leases which had expired, thus returning their addresses to the pool.
+    ///
+    /// Expired leases are also reclaimed on demand, when the pool or the lease store is exhausted,
+    /// so calling this method periodically is only necessary to keep the lease store tidy
+    /// (i.e. when persisting it).
+    ///
+    /// Returns the number of removed leases.
+    pub fn expire(&mut self) -> usize {
+        let now = (self.now)();
+
+        let mut removed = 0;
+
+        while let Some(addr) = self.leases.find_expired(now) {
+            debug!("Lease for IP {addr} expired");
+
+            self.drop_lease(addr);
+            removed += 1;
+        }
+
+        removed
+    }
+
+    fn is_leased_to(&self, mac: &[u8; 16], addr: Ipv4Addr) -> bool {
+        self.leases
+            .get(addr)
+            .map(|lease| lease.is_held_by(mac))
+            .unwrap_or(false)
     }
 
     fn add_lease(&mut self, addr: Ipv4Addr, mac: [u8; 16], expires: u64) -> bool {
         self.remove_lease(&mac);
 
-        self.leases.insert(addr, Lease { mac, expires }).is_ok()
+        let lease = Lease {
+            mac,
+            expires,
+            revoked: false,
+            declined: false,
+        };
+
+        self.leases.insert(addr, lease.clone())
+            || (self.expire() > 0 && self.leases.insert(addr, lease))
     }
 
     fn remove_lease(&mut self, mac: &[u8; 16]) -> bool {
-        if let Some(addr) = self.current_lease(mac) {
-            self.leases.remove(&addr);
-
-            true
+        if let Some(addr) = self.leases.get_by_mac(mac) {
+            self.drop_lease(addr)
         } else {
             false
         }
     }
+
+    fn drop_lease(&mut self, addr: Ipv4Addr) -> bool {
+        let removed = self.leases.remove(addr);
+
+        if removed {
+            self.hostnames.unregister(addr);
+        }
+
+        removed
+    }
 }
 
 #[cfg(feature = "io")]
@@ -303,3 +975,550 @@ impl<const N: usize> Server<fn() -> u64, N> {
         Self::new(|| embassy_time::Instant::now().as_secs(), ip)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+
+    use super::*;
+
+    const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 1);
+
+    const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+    const OTHER_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+    fn chaddr(mac: [u8; 6]) -> [u8; 16] {
+        let mut chaddr = [0; 16];
+        chaddr[..6].copy_from_slice(&mac);
+
+        chaddr
+    }
+
+    /// Build a request of the provided type from the client with the provided MAC address
+    fn request<'a>(
+        buf: &'a mut [DhcpOption<'a>; 4],
+        mac: [u8; 6],
+        message_type: MessageType,
+        ip: Option<Ipv4Addr>,
+    ) -> Packet<'a> {
+        // RELEASE carries the address in `ciaddr`, the rest of the requests in the Requested IP Address option
+        let release = matches!(message_type, MessageType::Release);
+
+        buf[0] = DhcpOption::MessageType(message_type);
+        let mut len = 1;
+
+        if !matches!(message_type, MessageType::Discover) {
+            buf[len] = DhcpOption::ServerIdentifier(SERVER_IP);
+            len += 1;
+        }
+
+        if let Some(ip) = ip.filter(|_| !release) {
+            buf[len] = DhcpOption::RequestedIpAddress(ip);
+            len += 1;
+        }
+
+        if matches!(message_type, MessageType::Discover | MessageType::Request) {
+            buf[len] = DhcpOption::ParameterRequestList(&[
+                DhcpOption::CODE_ROUTER,
+                DhcpOption::CODE_DNS,
+                DhcpOption::CODE_SUBNET,
+            ]);
+            len += 1;
+        }
+
+        let buf: &'a [DhcpOption<'a>] = buf;
+
+        Packet::new_request(
+            mac,
+            0x1234,
+            0,
+            ip.filter(|_| release),
+            false,
+            Options::new(&buf[..len]),
+        )
+    }
+
+    /// Return the type of the reply and the address in it
+    fn summary(reply: Option<Packet>) -> Option<(MessageType, Ipv4Addr)> {
+        let reply = reply?;
+
+        let message_type = reply.options.iter().find_map(|option| {
+            if let DhcpOption::MessageType(message_type) = option {
+                Some(message_type)
+            } else {
+                None
+            }
+        })?;
+
+        Some((message_type, reply.yiaddr))
+    }
+
+    fn exchange<F, const N: usize, S, H>(
+        server: &mut Server<F, N, S, H>,
+        options: &ServerOptions,
+        mac: [u8; 6],
+        message_type: MessageType,
+        ip: Option<Ipv4Addr>,
+    ) -> Option<(MessageType, Ipv4Addr)>
+    where
+        F: FnMut() -> u64,
+        S: LeaseStore,
+        H: HostnameRegistry,
+    {
+        let mut buf = [DhcpOption::Message(""); 4];
+        let request = request(&mut buf, mac, message_type, ip);
+
+        let mut opt_buf = Options::buf();
+
+        summary(server.handle_request(&mut opt_buf, options, &request))
+    }
+
+    /// DISCOVER followed by REQUEST for the offered address
+    fn acquire<F, const N: usize, S, H>(
+        server: &mut Server<F, N, S, H>,
+        options: &ServerOptions,
+        mac: [u8; 6],
+    ) -> Option<Ipv4Addr>
+    where
+        F: FnMut() -> u64,
+        S: LeaseStore,
+        H: HostnameRegistry,
+    {
+        let (_, ip) = exchange(server, options, mac, MessageType::Discover, None)?;
+
+        (exchange(server, options, mac, MessageType::Request, Some(ip))
+            == Some((MessageType::Ack, ip)))
+        .then_some(ip)
+    }
+
+    #[test]
+    fn test_lease_store() {
+        let options = ServerOptions::new(SERVER_IP, None);
+
+        let mut leases = heapless::LinearMap::<Ipv4Addr, Lease, 4>::new();
+
+        let ip = {
+            let mut server = Server::<_, 4, _>::new_with_store(|| 0, SERVER_IP, &mut leases);
+
+            acquire(&mut server, &options, MAC).unwrap()
+        };
+
+        let lease = LeaseStore::get(&leases, ip).unwrap();
+        assert!(lease.is_held_by(&chaddr(MAC)));
+        assert_eq!(lease.expires, options.lease_duration_secs as u64);
+
+        // A server restarted with the same store keeps the lease...
+        let mut server = Server::<_, 4, _>::new_with_store(|| 10, SERVER_IP, &mut leases);
+
+        assert_eq!(
+            exchange(&mut server, &options, MAC, MessageType::Request, Some(ip)),
+            Some((MessageType::Ack, ip))
+        );
+
+        // ... and does not hand out its address to other clients
+        let other = acquire(&mut server, &options, OTHER_MAC).unwrap();
+        assert_ne!(other, ip);
+
+        assert_eq!(server.leases.get_by_mac(&chaddr(OTHER_MAC)), Some(other));
+    }
+
+    #[test]
+    fn test_reservation() {
+        let reserved = Ipv4Addr::new(192, 168, 0, 10);
+        let first = Ipv4Addr::new(192, 168, 0, 50);
+
+        let reservations = [
+            Reservation::new(MAC, reserved),
+            // Reserved for a client which is offline, but in the pool
+            Reservation::new([0x02, 0x00, 0x00, 0x00, 0x00, 0x03], first),
+        ];
+
+        let options = ServerOptions {
+            reservations: &reservations,
+            ..ServerOptions::new(SERVER_IP, None)
+        };
+
+        let mut server = Server::<_, 4>::new(|| 0, SERVER_IP);
+
+        // The reserved client gets its address, even though it is outside of the pool and it asks for another one
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                MAC,
+                MessageType::Discover,
+                Some(first)
+            ),
+            Some((MessageType::Offer, reserved))
+        );
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                MAC,
+                MessageType::Request,
+                Some(first)
+            ),
+            Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+        );
+        assert_eq!(acquire(&mut server, &options, MAC), Some(reserved));
+
+        // Other clients never get the reserved addresses, even when asking for them
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                OTHER_MAC,
+                MessageType::Request,
+                Some(first)
+            ),
+            Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+        );
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                OTHER_MAC,
+                MessageType::Discover,
+                Some(reserved)
+            ),
+            Some((MessageType::Offer, Ipv4Addr::new(192, 168, 0, 51)))
+        );
+    }
+
+    #[test]
+    fn test_expiry_release_decline() {
+        let options = ServerOptions::new(SERVER_IP, None);
+        let duration = options.lease_duration_secs as u64;
+
+        let now = Cell::new(0);
+
+        // A pool of a single address
+        let ip = Ipv4Addr::new(192, 168, 0, 50);
+
+        let mut server = Server::<_, 4>::new(|| now.get(), SERVER_IP);
+        server.range_end = ip;
+
+        assert_eq!(acquire(&mut server, &options, MAC), Some(ip));
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                OTHER_MAC,
+                MessageType::Discover,
+                None
+            ),
+            None
+        );
+
+        // Expired leases are reused
+        now.set(duration + 1);
+
+        assert_eq!(acquire(&mut server, &options, OTHER_MAC), Some(ip));
+        assert_eq!(
+            exchange(&mut server, &options, MAC, MessageType::Request, Some(ip)),
+            Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+        );
+
+        // RELEASE is honored only when coming from the client holding the lease
+        assert_eq!(
+            exchange(&mut server, &options, MAC, MessageType::Release, Some(ip)),
+            None
+        );
+        assert_eq!(server.leases.get_by_mac(&chaddr(OTHER_MAC)), Some(ip));
+
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                OTHER_MAC,
+                MessageType::Release,
+                Some(ip)
+            ),
+            None
+        );
+        assert!(LeaseStore::get(&server.leases, ip).is_none());
+
+        // DECLINEd addresses are not handed out to anyone until the lease duration elapses
+        assert_eq!(acquire(&mut server, &options, MAC), Some(ip));
+        assert_eq!(
+            exchange(&mut server, &options, MAC, MessageType::Decline, Some(ip)),
+            None
+        );
+        assert_eq!(server.leases.get_by_mac(&chaddr(MAC)), None);
+
+        assert_eq!(
+            exchange(&mut server, &options, MAC, MessageType::Discover, None),
+            None
+        );
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                OTHER_MAC,
+                MessageType::Request,
+                Some(ip)
+            ),
+            Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+        );
+
+        now.set(now.get() + duration + 1);
+
+        assert_eq!(acquire(&mut server, &options, OTHER_MAC), Some(ip));
+    }
+
+    #[test]
+    fn test_relay() {
+        let options = ServerOptions::new(SERVER_IP, None);
+
+        let mut server = Server::<_, 4>::new(|| 0, SERVER_IP);
+
+        let relay_agent_info = RelayAgentInfo::new(&[RelayAgentInfo::CIRCUIT_ID, 2, 0x00, 0x07]);
+
+        let request_options = [
+            DhcpOption::MessageType(MessageType::Discover),
+            DhcpOption::RelayAgentInformation(relay_agent_info),
+        ];
+
+        let mut request =
+            Packet::new_request(MAC, 0x1234, 0, None, false, Options::new(&request_options));
+        request.hops = 1;
+        request.giaddr = Ipv4Addr::new(192, 168, 0, 254);
+
+        let mut opt_buf = Options::buf();
+        let reply = server
+            .handle_request(&mut opt_buf, &options, &request)
+            .unwrap();
+
+        // The offer goes back via the relay agent, with its information echoed
+        assert_eq!(reply.yiaddr, Ipv4Addr::new(192, 168, 0, 50));
+        assert_eq!(reply.giaddr, request.giaddr);
+        assert_eq!(reply.options.relay_agent_info(), Some(relay_agent_info));
+
+        // Requests relayed from other subnets are not for this server
+        request.giaddr = Ipv4Addr::new(10, 0, 1, 1);
+
+        let mut opt_buf = Options::buf();
+        assert!(server
+            .handle_request(&mut opt_buf, &options, &request)
+            .is_none());
+    }
+
+    #[test]
+    fn test_pools() {
+        fn discover<F, const N: usize>(
+            server: &mut Server<F, N>,
+            pools: &[Pool],
+            interface_ip: Option<Ipv4Addr>,
+            giaddr: Ipv4Addr,
+            mac: [u8; 6],
+        ) -> Option<(MessageType, Ipv4Addr)>
+        where
+            F: FnMut() -> u64,
+        {
+            let mut buf = [DhcpOption::Message(""); 4];
+            let mut request = request(&mut buf, mac, MessageType::Discover, None);
+            request.giaddr = giaddr;
+
+            let mut opt_buf = Options::buf();
+
+            summary(server.handle_pool_request(&mut opt_buf, pools, interface_ip, &request))
+        }
+
+        let local = Ipv4Addr::new(192, 168, 1, 1);
+
+        let pools = [
+            Pool::new(
+                Ipv4Addr::new(192, 168, 0, 50),
+                Ipv4Addr::new(192, 168, 0, 59),
+                ServerOptions::new(SERVER_IP, None),
+            ),
+            Pool::new(
+                Ipv4Addr::new(192, 168, 1, 50),
+                Ipv4Addr::new(192, 168, 1, 59),
+                ServerOptions::new(local, None),
+            ),
+            Pool::new(
+                Ipv4Addr::new(10, 0, 1, 50),
+                Ipv4Addr::new(10, 0, 1, 59),
+                ServerOptions::new(SERVER_IP, None),
+            ),
+        ];
+
+        let mut server = Server::<_, 4>::new(|| 0, SERVER_IP);
+
+        // By the interface the request was received on, or the first pool
+        assert_eq!(
+            discover(&mut server, &pools, Some(local), Ipv4Addr::UNSPECIFIED, MAC),
+            Some((MessageType::Offer, Ipv4Addr::new(192, 168, 1, 50)))
+        );
+        assert_eq!(
+            discover(&mut server, &pools, None, Ipv4Addr::UNSPECIFIED, MAC),
+            Some((MessageType::Offer, Ipv4Addr::new(192, 168, 0, 50)))
+        );
+        assert_eq!(
+            discover(
+                &mut server,
+                &pools,
+                Some(Ipv4Addr::new(192, 168, 2, 1)),
+                Ipv4Addr::UNSPECIFIED,
+                MAC
+            ),
+            None
+        );
+
+        // By the subnet of the relay agent
+        assert_eq!(
+            discover(
+                &mut server,
+                &pools,
+                Some(local),
+                Ipv4Addr::new(10, 0, 1, 1),
+                MAC
+            ),
+            Some((MessageType::Offer, Ipv4Addr::new(10, 0, 1, 50)))
+        );
+        assert_eq!(
+            discover(&mut server, &pools, None, Ipv4Addr::new(10, 0, 2, 1), MAC),
+            None
+        );
+
+        // The pools share the lease store, while the server's own range is left intact
+        let mut buf = [DhcpOption::Message(""); 4];
+        let request = request(
+            &mut buf,
+            OTHER_MAC,
+            MessageType::Request,
+            Some(Ipv4Addr::new(192, 168, 0, 50)),
+        );
+
+        let mut opt_buf = Options::buf();
+        assert_eq!(
+            summary(server.handle_pool_request(&mut opt_buf, &pools, None, &request)),
+            Some((MessageType::Ack, Ipv4Addr::new(192, 168, 0, 50)))
+        );
+
+        assert_eq!(
+            discover(&mut server, &pools, None, Ipv4Addr::UNSPECIFIED, MAC),
+            Some((MessageType::Offer, Ipv4Addr::new(192, 168, 0, 51)))
+        );
+
+        assert_eq!(server.range_start, Ipv4Addr::new(192, 168, 0, 50));
+        assert_eq!(server.range_end, Ipv4Addr::new(192, 168, 0, 200));
+    }
+
+    #[test]
+    fn test_revoke() {
+        let options = ServerOptions::new(SERVER_IP, None);
+
+        let mut server = Server::<_, 4>::new(|| 0, SERVER_IP);
+
+        let ip = acquire(&mut server, &options, MAC).unwrap();
+
+        assert!(!server.revoke(Ipv4Addr::new(192, 168, 0, 100)));
+        assert!(server.revoke(ip));
+
+        // The renewal is NAKed and the client is offered another address
+        assert_eq!(
+            exchange(&mut server, &options, MAC, MessageType::Request, Some(ip)),
+            Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+        );
+
+        let other = acquire(&mut server, &options, MAC).unwrap();
+        assert_ne!(other, ip);
+
+        assert!(server.revoke_by_mac(&chaddr(MAC)));
+        assert!(!server.revoke_by_mac(&chaddr(OTHER_MAC)));
+
+        assert_eq!(
+            exchange(
+                &mut server,
+                &options,
+                MAC,
+                MessageType::Request,
+                Some(other)
+            ),
+            Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+        );
+    }
+
+    #[test]
+    fn test_reconfigure() {
+        const THIRD_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x03];
+        const FOURTH_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x04];
+
+        let options = ServerOptions::new(SERVER_IP, None);
+
+        let now = Cell::new(0);
+
+        let mut server = Server::<_, 4>::new(|| now.get(), SERVER_IP);
+
+        for mac in [MAC, OTHER_MAC, THIRD_MAC, FOURTH_MAC] {
+            acquire(&mut server, &options, mac).unwrap();
+        }
+
+        now.set(100);
+
+        let reservations = [Reservation::new(OTHER_MAC, Ipv4Addr::new(192, 168, 0, 52))];
+        let gateways = [Ipv4Addr::new(192, 168, 0, 254)];
+
+        let new_options = ServerOptions {
+            gateways: &gateways,
+            lease_duration_secs: 600,
+            reservations: &reservations,
+            ..ServerOptions::new(SERVER_IP, None)
+        };
+
+        // Outside of the new range, reserved elsewhere and reserved for another client
+        assert_eq!(
+            server.reconfigure(
+                Ipv4Addr::new(192, 168, 0, 51),
+                Ipv4Addr::new(192, 168, 0, 60),
+                &new_options
+            ),
+            3
+        );
+
+        let kept = Ipv4Addr::new(192, 168, 0, 53);
+
+        assert_eq!(server.leases.get_by_mac(&chaddr(FOURTH_MAC)), Some(kept));
+        assert_eq!(LeaseStore::get(&server.leases, kept).unwrap().expires, 700);
+
+        // The renewal is acknowledged with the new options
+        let mut buf = [DhcpOption::Message(""); 4];
+        let request = request(&mut buf, FOURTH_MAC, MessageType::Request, Some(kept));
+
+        let mut opt_buf = Options::buf();
+        let reply = server
+            .handle_request(&mut opt_buf, &new_options, &request)
+            .unwrap();
+
+        assert_eq!(reply.yiaddr, kept);
+        assert!(reply.options.iter().any(|option| matches!(
+            option,
+            DhcpOption::Router(routers) if routers.iter().eq(gateways)
+        )));
+        assert!(reply
+            .options
+            .iter()
+            .any(|option| matches!(option, DhcpOption::IpAddressLeaseTime(600))));
+
+        for (mac, ip) in [(MAC, 50), (OTHER_MAC, 51), (THIRD_MAC, 52)] {
+            assert_eq!(
+                exchange(
+                    &mut server,
+                    &new_options,
+                    mac,
+                    MessageType::Request,
+                    Some(Ipv4Addr::new(192, 168, 0, ip))
+                ),
+                Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+            );
+        }
+
+        assert_eq!(
+            acquire(&mut server, &new_options, OTHER_MAC),
+            Some(Ipv4Addr::new(192, 168, 0, 52))
+        );
+    }
+}
@@ -0,0 +1,753 @@
+use core::cell::RefCell;
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use domain::base::Rtype;
+
+use edge_nal::io::{Error as _, ErrorType, Read, ReadExactError, Write};
+use edge_nal::{
+    AddrType, Dns, DnsQuery, DnsRecords, RecordType, TcpConnect, TlsUpgrade, UdpBind, UdpReceive,
+    UdpSend, WriteVectored,
+};
+
+use embassy_time::{with_timeout, Duration, Instant};
+
+use log::{debug, warn};
+
+use crate as dns;
+use crate::{Answer, Cache, MAX_NAME_LEN, MAX_REVERSE_NAME_LEN, MAX_UDP_MESSAGE_SIZE};
+
+/// The maximum size of a DNS message received over TCP, i.e. of a response which was truncated over UDP
+pub const MAX_TCP_MESSAGE_SIZE: usize = 2048;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error<E> {
+    Io(E),
+    Format(dns::Error),
+    /// No response arrived in time
+    Timeout,
+    /// The DNS-over-HTTPS server replied with an HTTP status other than 200
+    #[cfg(feature = "http")]
+    Status(u16),
+}
+
+pub type ErrorKind = Error<edge_nal::io::ErrorKind>;
+
+impl<E> Error<E>
+where
+    E: edge_nal::io::Error,
+{
+    pub fn erase(&self) -> Error<edge_nal::io::ErrorKind> {
+        match self {
+            Self::Io(e) => Error::Io(e.kind()),
+            Self::Format(e) => Error::Format(*e),
+            Self::Timeout => Error::Timeout,
+            #[cfg(feature = "http")]
+            Self::Status(status) => Error::Status(*status),
+        }
+    }
+}
+
+impl<E> From<dns::Error> for Error<E> {
+    fn from(value: dns::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl<E> From<ReadExactError<E>> for Error<E> {
+    fn from(value: ReadExactError<E>) -> Self {
+        match value {
+            ReadExactError::UnexpectedEof => Self::Format(dns::Error::InvalidMessage),
+            ReadExactError::Other(e) => Self::Io(e),
+        }
+    }
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "IO error: {err}"),
+            Self::Format(err) => write!(f, "Format error: {err}"),
+            Self::Timeout => write!(f, "Timeout"),
+            #[cfg(feature = "http")]
+            Self::Status(status) => write!(f, "HTTP status {status}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E> where E: std::error::Error {}
+
+impl<E> edge_nal::io::Error for Error<E>
+where
+    E: edge_nal::io::Error,
+{
+    fn kind(&self) -> edge_nal::io::ErrorKind {
+        match self {
+            Self::Io(err) => err.kind(),
+            Self::Format(dns::Error::NotFound) => edge_nal::io::ErrorKind::NotFound,
+            Self::Format(dns::Error::InvalidName) => edge_nal::io::ErrorKind::InvalidInput,
+            Self::Format(dns::Error::ShortBuf) => edge_nal::io::ErrorKind::OutOfMemory,
+            Self::Format(dns::Error::InvalidMessage) => edge_nal::io::ErrorKind::InvalidData,
+            Self::Format(dns::Error::Server(_)) => edge_nal::io::ErrorKind::Other,
+            Self::Timeout => edge_nal::io::ErrorKind::TimedOut,
+            #[cfg(feature = "http")]
+            Self::Status(_) => edge_nal::io::ErrorKind::Other,
+        }
+    }
+}
+
+/// The configuration of a `Resolver`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// The time to wait for the response of a server, before querying the next one
+    pub timeout: Duration,
+    /// How many times the list of servers is gone through, before a query fails
+    pub attempts: u8,
+    /// The maximum time an answer is cached for, regardless of the TTLs of its records
+    pub max_ttl: Duration,
+}
+
+impl Config {
+    /// Create a new configuration, with a timeout of 2 seconds, 2 attempts and a maximum TTL of 1 hour
+    pub const fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            attempts: 2,
+            max_ttl: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A DNS server queried by a `Resolver`, along with the protocol it is queried over
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Upstream<'a> {
+    /// A plain DNS server, usually on port `PORT`, queried over UDP, and over TCP when the response
+    /// does not fit in a UDP message
+    Plain(SocketAddr),
+    /// A DNS-over-TLS server (RFC 7858), usually on port `TLS_PORT`
+    Tls {
+        /// The address of the server
+        addr: SocketAddr,
+        /// The name of the server, as used for SNI and for verifying its certificate
+        server_name: &'a str,
+    },
+    /// A DNS-over-HTTPS server (RFC 8484), usually on port 443
+    #[cfg(feature = "http")]
+    Https {
+        /// The address of the server
+        addr: SocketAddr,
+        /// The name of the server, as used for SNI, for verifying its certificate and in the `Host` header
+        host: &'a str,
+        /// The path of the DNS-over-HTTPS endpoint, usually `/dns-query`
+        path: &'a str,
+    },
+}
+
+impl Upstream<'_> {
+    /// Return the address of the server
+    pub const fn addr(&self) -> SocketAddr {
+        match self {
+            Self::Plain(addr) => *addr,
+            Self::Tls { addr, .. } => *addr,
+            #[cfg(feature = "http")]
+            Self::Https { addr, .. } => *addr,
+        }
+    }
+}
+
+impl From<SocketAddr> for Upstream<'_> {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Plain(addr)
+    }
+}
+
+impl fmt::Display for Upstream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plain(addr) => write!(f, "{addr}"),
+            Self::Tls { addr, server_name } => write!(f, "tls://{server_name} ({addr})"),
+            #[cfg(feature = "http")]
+            Self::Https { addr, host, path } => write!(f, "https://{host}{path} ({addr})"),
+        }
+    }
+}
+
+/// A `TlsUpgrade` implementation which fails all upgrades, for resolvers querying plain DNS servers only
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoTls;
+
+impl<S> TlsUpgrade<S> for NoTls
+where
+    S: Read + Write,
+{
+    type Error = edge_nal::io::ErrorKind;
+
+    type Socket<'a>
+        = NoTlsSocket
+    where
+        Self: 'a;
+
+    async fn upgrade(
+        &self,
+        _socket: S,
+        _server_name: &str,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        Err(edge_nal::io::ErrorKind::Unsupported)
+    }
+}
+
+/// The socket type of `NoTls`, which cannot be instantiated
+pub enum NoTlsSocket {}
+
+impl ErrorType for NoTlsSocket {
+    type Error = edge_nal::io::ErrorKind;
+}
+
+impl Read for NoTlsSocket {
+    async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match *self {}
+    }
+}
+
+impl Write for NoTlsSocket {
+    async fn write(&mut self, _buf: &[u8]) -> Result<usize, Self::Error> {
+        match *self {}
+    }
+}
+
+impl WriteVectored for NoTlsSocket {}
+
+/// A caching DNS stub resolver, which sends recursive queries to a list of DNS servers.
+///
+/// The servers are queried in order, until one of them answers. Each server is queried over its own
+/// protocol (see `Upstream`): plain DNS, DNS-over-TLS or DNS-over-HTTPS (with the `http` feature).
+/// The encrypted protocols need a TLS implementation, as provided with `Resolver::with_tls`,
+/// and keep the queries private on untrusted networks.
+///
+/// The answers are cached in a caller-provided buffer (see `Cache`). Negative answers (i.e. NXDOMAIN
+/// or a name without records of the queried type) are not cached.
+///
+/// Implements the `edge_nal::Dns` and `edge_nal::DnsQuery` traits, so it can be used wherever
+/// a resolver is expected, including on networking stacks without a (capable enough) resolver of their own.
+pub struct Resolver<'a, U, T, L = NoTls> {
+    udp: U,
+    tcp: &'a T,
+    tls: L,
+    servers: &'a [Upstream<'a>],
+    rand: fn(&mut [u8]),
+    config: Config,
+    cache: RefCell<Cache<'a>>,
+}
+
+impl<'a, U, T> Resolver<'a, U, T>
+where
+    U: UdpBind,
+    T: TcpConnect,
+{
+    /// Create a new resolver, which does not support the encrypted protocols
+    ///
+    /// Parameters:
+    /// - `udp`: The UDP stack over which the plain DNS queries are sent
+    /// - `tcp`: The TCP stack over which the queries with truncated UDP responses are repeated,
+    ///   and over which the encrypted protocols run
+    /// - `servers`: The DNS servers
+    /// - `cache`: The buffer in which the answers are cached; an empty buffer disables caching
+    /// - `rand`: A function generating random bytes, used for the IDs of the queries
+    /// - `config`: The configuration of the resolver
+    pub fn new(
+        udp: U,
+        tcp: &'a T,
+        servers: &'a [Upstream<'a>],
+        cache: &'a mut [u8],
+        rand: fn(&mut [u8]),
+        config: Config,
+    ) -> Self {
+        Self {
+            udp,
+            tcp,
+            tls: NoTls,
+            servers,
+            rand,
+            config,
+            cache: RefCell::new(Cache::new(cache)),
+        }
+    }
+}
+
+impl<'a, U, T, L> Resolver<'a, U, T, L>
+where
+    U: UdpBind,
+    T: TcpConnect,
+    L: TlsUpgrade<T::Socket<'a>>,
+{
+    /// Use `tls` for the TLS sessions with the DNS-over-TLS and DNS-over-HTTPS servers
+    pub fn with_tls<L2>(self, tls: L2) -> Resolver<'a, U, T, L2>
+    where
+        L2: TlsUpgrade<T::Socket<'a>>,
+    {
+        Resolver {
+            udp: self.udp,
+            tcp: self.tcp,
+            tls,
+            servers: self.servers,
+            rand: self.rand,
+            config: self.config,
+            cache: self.cache,
+        }
+    }
+
+    /// Return the DNS servers
+    pub fn servers(&self) -> &[Upstream<'a>] {
+        self.servers
+    }
+
+    /// Remove all cached answers, e.g. after the device moved to another network
+    pub fn clear_cache(&self) {
+        self.cache.borrow_mut().clear();
+    }
+
+    /// Resolve the records of type `rtype` of the domain `name`, from the cache or by querying the servers.
+    ///
+    /// The records are stored in `result` in the format of `edge_nal::DnsRecords`; the length of the stored records is returned.
+    /// A name without records of type `rtype` is not an error, and results in a length of 0.
+    pub async fn resolve(
+        &self,
+        name: &str,
+        rtype: Rtype,
+        result: &mut [u8],
+    ) -> Result<usize, ErrorKind> {
+        let now = Instant::now().as_secs();
+
+        if let Some(len) = self.cache.borrow().get(name, rtype, now, result)? {
+            debug!("Resolved {name} ({rtype}) from the cache");
+            return Ok(len);
+        }
+
+        let mut error = Error::Io(edge_nal::io::ErrorKind::NotConnected);
+
+        for _ in 0..self.config.attempts.max(1) {
+            for server in self.servers {
+                match self.query_server(server, name, rtype, result).await {
+                    Ok((len, ttl)) => {
+                        debug!("Resolved {name} ({rtype}) via {server}");
+
+                        if len > 0 {
+                            let ttl = ttl.min(self.config.max_ttl.as_secs() as u32);
+
+                            self.cache.borrow_mut().insert(
+                                name,
+                                rtype,
+                                ttl,
+                                Instant::now().as_secs(),
+                                &result[..len],
+                            );
+                        }
+
+                        return Ok(len);
+                    }
+                    Err(
+                        err @ Error::Format(
+                            dns::Error::NotFound | dns::Error::InvalidName | dns::Error::ShortBuf,
+                        ),
+                    ) => {
+                        // The other servers would not answer differently
+                        return Err(err);
+                    }
+                    Err(err) => {
+                        warn!("Resolving {name} ({rtype}) via {server} failed: {err:?}");
+                        error = err;
+                    }
+                }
+            }
+        }
+
+        Err(error)
+    }
+
+    async fn query_server(
+        &self,
+        server: &Upstream<'_>,
+        name: &str,
+        rtype: Rtype,
+        result: &mut [u8],
+    ) -> Result<(usize, u32), ErrorKind> {
+        let id = self.next_id();
+
+        let answer = match server {
+            Upstream::Plain(addr) => {
+                let answer = self
+                    .query_udp(*addr, id, name, rtype, result)
+                    .await
+                    .map_err(|e| e.erase())?;
+
+                if matches!(answer, Answer::Truncated) {
+                    debug!("Response from {addr} truncated, repeating the query over TCP");
+
+                    self.query_tcp(*addr, id, name, rtype, result)
+                        .await
+                        .map_err(|e| e.erase())?
+                } else {
+                    answer
+                }
+            }
+            Upstream::Tls { addr, server_name } => {
+                self.query_tls(*addr, server_name, id, name, rtype, result)
+                    .await?
+            }
+            #[cfg(feature = "http")]
+            Upstream::Https { addr, host, path } => {
+                self.query_https(*addr, host, path, name, rtype, result)
+                    .await?
+            }
+        };
+
+        match answer {
+            Answer::Records { len, ttl } => Ok((len, ttl)),
+            Answer::Truncated => Err(Error::Format(dns::Error::InvalidMessage)),
+        }
+    }
+
+    async fn query_udp(
+        &self,
+        server: SocketAddr,
+        id: u16,
+        name: &str,
+        rtype: Rtype,
+        result: &mut [u8],
+    ) -> Result<Answer, Error<U::Error>> {
+        let local = match server.ip() {
+            IpAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            IpAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        };
+
+        let mut socket = self.udp.bind(local).await.map_err(Error::Io)?;
+
+        let mut buf = [0; MAX_UDP_MESSAGE_SIZE];
+
+        let len = dns::compose_query(id, name, rtype, &mut buf)?;
+        socket.send(server, &buf[..len]).await.map_err(Error::Io)?;
+
+        with_timeout(self.config.timeout, async {
+            loop {
+                let (len, remote) = socket.receive(&mut buf).await.map_err(Error::Io)?;
+
+                if remote != server {
+                    continue;
+                }
+
+                match dns::parse_response(&buf[..len], id, name, rtype, result)? {
+                    Some(answer) => break Ok(answer),
+                    None => debug!("Ignoring an unexpected packet from {remote}"),
+                }
+            }
+        })
+        .await
+        .map_err(|_| Error::Timeout)?
+    }
+
+    async fn query_tcp(
+        &self,
+        server: SocketAddr,
+        id: u16,
+        name: &str,
+        rtype: Rtype,
+        result: &mut [u8],
+    ) -> Result<Answer, Error<T::Error>> {
+        let mut socket = with_timeout(self.config.timeout, self.tcp.connect(server))
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(Error::Io)?;
+
+        with_timeout(
+            self.config.timeout,
+            query_stream(&mut socket, id, name, rtype, result),
+        )
+        .await
+        .map_err(|_| Error::Timeout)?
+    }
+
+    async fn query_tls(
+        &self,
+        addr: SocketAddr,
+        server_name: &str,
+        id: u16,
+        name: &str,
+        rtype: Rtype,
+        result: &mut [u8],
+    ) -> Result<Answer, ErrorKind> {
+        with_timeout(self.config.timeout, async {
+            let mut socket = self.connect_tls(addr, server_name).await?;
+
+            query_stream(&mut socket, id, name, rtype, result)
+                .await
+                .map_err(|e| e.erase())
+        })
+        .await
+        .map_err(|_| Error::Timeout)?
+    }
+
+    #[cfg(feature = "http")]
+    async fn query_https(
+        &self,
+        addr: SocketAddr,
+        host: &str,
+        path: &str,
+        name: &str,
+        rtype: Rtype,
+        result: &mut [u8],
+    ) -> Result<Answer, ErrorKind> {
+        with_timeout(self.config.timeout, async {
+            let mut socket = self.connect_tls(addr, host).await?;
+
+            query_http(&mut socket, host, path, name, rtype, result).await
+        })
+        .await
+        .map_err(|_| Error::Timeout)?
+    }
+
+    async fn connect_tls(
+        &self,
+        addr: SocketAddr,
+        server_name: &str,
+    ) -> Result<L::Socket<'_>, ErrorKind> {
+        let socket = self
+            .tcp
+            .connect(addr)
+            .await
+            .map_err(|e| Error::Io(e.kind()))?;
+
+        self.tls
+            .upgrade(socket, server_name)
+            .await
+            .map_err(|e| Error::Io(e.kind()))
+    }
+
+    async fn get_host(&self, host: &str, rtype: Rtype) -> Result<Option<IpAddr>, ErrorKind> {
+        let mut result = [0; 256];
+
+        let len = self.resolve(host, rtype, &mut result).await?;
+
+        Ok(DnsRecords::new(&result[..len]).find_map(|record| record.addr()))
+    }
+
+    fn next_id(&self) -> u16 {
+        let mut id = [0; 2];
+        (self.rand)(&mut id);
+
+        u16::from_be_bytes(id)
+    }
+}
+
+impl<'a, U, T, L> Dns for Resolver<'a, U, T, L>
+where
+    U: UdpBind,
+    T: TcpConnect,
+    L: TlsUpgrade<T::Socket<'a>>,
+{
+    type Error = ErrorKind;
+
+    async fn get_host_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, Self::Error> {
+        if let Ok(addr) = host.parse::<IpAddr>() {
+            return Ok(addr);
+        }
+
+        let addr = match addr_type {
+            AddrType::IPv4 => self.get_host(host, Rtype::A).await?,
+            AddrType::IPv6 => self.get_host(host, Rtype::AAAA).await?,
+            AddrType::Either => match self.get_host(host, Rtype::A).await? {
+                Some(addr) => Some(addr),
+                None => self.get_host(host, Rtype::AAAA).await?,
+            },
+        };
+
+        addr.ok_or(Error::Format(dns::Error::NotFound))
+    }
+
+    async fn get_host_by_address(
+        &self,
+        addr: IpAddr,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let mut name = [0; MAX_REVERSE_NAME_LEN];
+        let name = dns::reverse_name(addr, &mut name);
+
+        let mut records = [0; 2 * MAX_NAME_LEN];
+        let len = self.resolve(name, Rtype::PTR, &mut records).await?;
+
+        let host = DnsRecords::new(&records[..len])
+            .find_map(|record| record.name())
+            .ok_or(Error::Format(dns::Error::NotFound))?;
+
+        let mut len = 0;
+
+        for label in host.labels() {
+            let sep = if len > 0 { 1 } else { 0 };
+
+            let out = result
+                .get_mut(len..len + sep + label.len())
+                .ok_or(Error::Format(dns::Error::ShortBuf))?;
+
+            out[..sep].fill(b'.');
+            out[sep..].copy_from_slice(label);
+
+            len += out.len();
+        }
+
+        Ok(len)
+    }
+}
+
+impl<'a, U, T, L> DnsQuery for Resolver<'a, U, T, L>
+where
+    U: UdpBind,
+    T: TcpConnect,
+    L: TlsUpgrade<T::Socket<'a>>,
+{
+    async fn query(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.resolve(name, Rtype::from_int(record_type.0), result)
+            .await
+    }
+}
+
+/// Exchange a query and its response over a stream (TCP or TLS) connection, where each message
+/// is preceded by its length
+async fn query_stream<S>(
+    socket: &mut S,
+    id: u16,
+    name: &str,
+    rtype: Rtype,
+    result: &mut [u8],
+) -> Result<Answer, Error<S::Error>>
+where
+    S: Read + Write,
+{
+    let mut buf = [0; MAX_TCP_MESSAGE_SIZE];
+
+    let len = dns::compose_query(id, name, rtype, &mut buf[2..])?;
+    buf[..2].copy_from_slice(&(len as u16).to_be_bytes());
+
+    socket.write_all(&buf[..len + 2]).await.map_err(Error::Io)?;
+    socket.flush().await.map_err(Error::Io)?;
+
+    let mut len = [0; 2];
+    socket.read_exact(&mut len).await?;
+
+    let response = buf
+        .get_mut(..u16::from_be_bytes(len) as usize)
+        .ok_or(Error::Format(dns::Error::ShortBuf))?;
+
+    socket.read_exact(response).await?;
+
+    dns::parse_response(response, id, name, rtype, result)?
+        .ok_or(Error::Format(dns::Error::InvalidMessage))
+}
+
+/// Exchange a query and its response as a DNS-over-HTTPS `POST` request over `socket`
+#[cfg(feature = "http")]
+async fn query_http<S>(
+    socket: &mut S,
+    host: &str,
+    path: &str,
+    name: &str,
+    rtype: Rtype,
+    result: &mut [u8],
+) -> Result<Answer, ErrorKind>
+where
+    S: Read + WriteVectored,
+{
+    use edge_http::io::{Body, SendBody};
+    use edge_http::{ConnectionType, Method, RequestHeaders, ResponseHeaders};
+
+    const CONTENT_TYPE: &str = "application/dns-message";
+
+    let mut message = [0; MAX_TCP_MESSAGE_SIZE];
+
+    // An ID of 0, as recommended by RFC 8484 so that the responses can be cached by HTTP caches
+    let len = dns::compose_query(0, name, rtype, &mut message)?;
+
+    let mut content_len = heapless::String::new();
+
+    let mut request = RequestHeaders::<'_, 5>::new();
+    request.method = Method::Post;
+    request.path = path;
+    request
+        .headers
+        .set("Host", host)
+        .set_content_type(CONTENT_TYPE)
+        .set("Accept", CONTENT_TYPE)
+        .set_content_len(len as u64, &mut content_len)
+        .set("Connection", "close");
+
+    let (_, body_type) = request.send(false, &mut *socket).await.map_err(http)?;
+
+    let mut body = SendBody::new(body_type, &mut *socket);
+    body.write_all(&message[..len]).await.map_err(http)?;
+    body.finish().await.map_err(http)?;
+
+    let mut buf = [0; 1024];
+
+    let mut response = ResponseHeaders::<'_, 16>::new();
+    let (body_buf, read_len) = response
+        .receive(&mut buf, &mut *socket, true)
+        .await
+        .map_err(http)?;
+
+    if response.code != 200 {
+        Err(Error::Status(response.code))?;
+    }
+
+    let (_, body_type) = response
+        .resolve::<S::Error>(ConnectionType::Close)
+        .map_err(http)?;
+
+    let mut body = Body::new(body_type, body_buf, read_len, &mut *socket);
+
+    let mut len = 0;
+
+    loop {
+        if len == message.len() {
+            Err(Error::Format(dns::Error::ShortBuf))?;
+        }
+
+        match body.read(&mut message[len..]).await.map_err(http)? {
+            0 => break,
+            read => len += read,
+        }
+    }
+
+    dns::parse_response(&message[..len], 0, name, rtype, result)?
+        .ok_or(Error::Format(dns::Error::InvalidMessage))
+}
+
+#[cfg(feature = "http")]
+fn http<E>(err: edge_http::io::Error<E>) -> ErrorKind
+where
+    E: edge_nal::io::Error,
+{
+    match err {
+        edge_http::io::Error::Io(err) => Error::Io(err.kind()),
+        other => {
+            debug!("DNS-over-HTTPS exchange failed: {other:?}");
+            Error::Format(dns::Error::InvalidMessage)
+        }
+    }
+}
@@ -0,0 +1,691 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(async_fn_in_trait)]
+#![warn(clippy::large_futures)]
+
+use core::fmt::{self, Display};
+use core::iter::{self, Chain, Map, Once};
+use core::net::IpAddr;
+use core::str::Split;
+
+use domain::base::iana::{Class, Rcode};
+use domain::base::message::ShortMessage;
+use domain::base::message_builder::PushError;
+use domain::base::name::{Label, ToLabelIter};
+use domain::base::rdata::ComposeRecordData;
+use domain::base::wire::{Composer, ParseError};
+use domain::base::{Message, MessageBuilder, ParsedName, Question, Rtype, ToName};
+use domain::dep::octseq::{OctetsBuilder, ShortBuf, Truncate};
+use domain::rdata::AllRecordData;
+
+use log::debug;
+
+#[cfg(feature = "io")]
+pub mod io;
+
+/// The standard DNS server port
+pub const PORT: u16 = 53;
+
+/// The standard DNS-over-TLS server port
+pub const TLS_PORT: u16 = 853;
+
+/// The maximum size of a DNS message over UDP, without EDNS(0)
+pub const MAX_UDP_MESSAGE_SIZE: usize = 512;
+
+/// The maximum length of a domain name in the wire format
+pub const MAX_NAME_LEN: usize = 255;
+
+/// The maximum length of the name used for the reverse (PTR) lookup of an IP address
+pub const MAX_REVERSE_NAME_LEN: usize = 72;
+
+/// The size of the header preceding the data of each record in the result buffers:
+/// the TTL (4 bytes) and the data length (2 bytes), both in network byte order.
+///
+/// This is the format of `edge_nal::DnsRecords`, so the records can be iterated over with it.
+pub const RECORD_HEADER_SIZE: usize = 6;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Error {
+    ShortBuf,
+    InvalidMessage,
+    /// The queried name is not a valid domain name
+    InvalidName,
+    /// The domain name does not exist (NXDOMAIN)
+    NotFound,
+    /// The server failed to answer the query, with the provided response code
+    Server(u8),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShortBuf => write!(f, "ShortBuf"),
+            Self::InvalidMessage => write!(f, "InvalidMessage"),
+            Self::InvalidName => write!(f, "InvalidName"),
+            Self::NotFound => write!(f, "NotFound"),
+            Self::Server(rcode) => write!(f, "Server({rcode})"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl From<ShortBuf> for Error {
+    fn from(_: ShortBuf) -> Self {
+        Self::ShortBuf
+    }
+}
+
+impl From<PushError> for Error {
+    fn from(_: PushError) -> Self {
+        Self::ShortBuf
+    }
+}
+
+impl From<ShortMessage> for Error {
+    fn from(_: ShortMessage) -> Self {
+        Self::InvalidMessage
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(_: ParseError) -> Self {
+        Self::InvalidMessage
+    }
+}
+
+/// The outcome of `parse_response`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Answer {
+    /// The matching records were stored in the result buffer
+    Records {
+        /// The length of the stored records
+        len: usize,
+        /// The smallest TTL of the stored records, in seconds; 0 if the name has no records of the queried type
+        ttl: u32,
+    },
+    /// The response does not fit in a UDP message, and the query should be repeated over TCP
+    Truncated,
+}
+
+/// Compose a recursive query for the records of type `rtype` of the domain `name` into `buf`.
+///
+/// Returns the length of the query.
+pub fn compose_query(id: u16, name: &str, rtype: Rtype, buf: &mut [u8]) -> Result<usize, Error> {
+    let name = DottedName::new(name)?;
+
+    let mut mb = MessageBuilder::from_target(Buf(buf, 0))?;
+
+    let header = mb.header_mut();
+    header.set_id(id);
+    header.set_rd(true);
+
+    let mut qb = mb.question();
+    qb.push(Question::new(name, rtype, Class::IN))?;
+
+    Ok(qb.finish().1)
+}
+
+/// Parse the response to a query composed with `compose_query`.
+///
+/// The records of type `rtype` in the answer section are stored in `result`, each one preceded by a header
+/// of `RECORD_HEADER_SIZE` bytes. Domain names embedded in the record data are stored uncompressed, so that
+/// the records can be interpreted without the message they originated from. Other records (e.g. the CNAME
+/// records preceding the A records of an alias) are skipped.
+///
+/// Returns `None` if the message is not a response to the query, i.e. it has another ID or question.
+pub fn parse_response(
+    response: &[u8],
+    id: u16,
+    name: &str,
+    rtype: Rtype,
+    result: &mut [u8],
+) -> Result<Option<Answer>, Error> {
+    let name = DottedName::new(name)?;
+
+    let Ok(message) = Message::from_octets(response) else {
+        return Ok(None);
+    };
+
+    let header = message.header();
+
+    if header.id() != id || !header.qr() {
+        return Ok(None);
+    }
+
+    let Some(question) = message.first_question() else {
+        return Ok(None);
+    };
+
+    if question.qtype() != rtype
+        || question.qclass() != Class::IN
+        || !question.qname().name_eq(&name)
+    {
+        return Ok(None);
+    }
+
+    if header.tc() {
+        return Ok(Some(Answer::Truncated));
+    }
+
+    match header.rcode() {
+        Rcode::NOERROR => (),
+        Rcode::NXDOMAIN => Err(Error::NotFound)?,
+        rcode => Err(Error::Server(rcode.to_int()))?,
+    }
+
+    let mut len = 0;
+    let mut ttl = u32::MAX;
+
+    for record in message.answer()? {
+        let Some(record) = record?.into_record::<AllRecordData<&[u8], ParsedName<&[u8]>>>()? else {
+            continue;
+        };
+
+        if record.rtype() != rtype || record.class() != Class::IN {
+            debug!("Skipping record {}", record.rtype());
+            continue;
+        }
+
+        let record_ttl = record.ttl().as_secs();
+
+        result
+            .get_mut(len..len + RECORD_HEADER_SIZE)
+            .ok_or(Error::ShortBuf)?[..4]
+            .copy_from_slice(&record_ttl.to_be_bytes());
+
+        let start = len + RECORD_HEADER_SIZE;
+
+        let mut data = Buf(&mut result[start..], 0);
+        record.data().compose_rdata(&mut data)?;
+
+        let data_len = data.1;
+
+        result[len + 4..start].copy_from_slice(&(data_len as u16).to_be_bytes());
+
+        len = start + data_len;
+        ttl = ttl.min(record_ttl);
+    }
+
+    Ok(Some(Answer::Records {
+        len,
+        ttl: if len > 0 { ttl } else { 0 },
+    }))
+}
+
+/// Render the name used for the reverse (PTR) lookup of `addr` into `buf`
+pub fn reverse_name(addr: IpAddr, buf: &mut [u8; MAX_REVERSE_NAME_LEN]) -> &str {
+    let mut out = Buf(buf, 0);
+
+    // Cannot fail, as the buffer fits the longest (IPv6) reverse name
+    let _ = match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, c, d] = addr.octets();
+            fmt::Write::write_fmt(&mut out, format_args!("{d}.{c}.{b}.{a}.in-addr.arpa"))
+        }
+        IpAddr::V6(addr) => addr
+            .octets()
+            .iter()
+            .rev()
+            .try_for_each(|octet| {
+                fmt::Write::write_fmt(
+                    &mut out,
+                    format_args!("{:x}.{:x}.", octet & 0x0f, octet >> 4),
+                )
+            })
+            .and_then(|_| fmt::Write::write_str(&mut out, "ip6.arpa")),
+    };
+
+    let len = out.1;
+
+    // Safe to unwrap, as the reverse name is ASCII
+    core::str::from_utf8(&buf[..len]).unwrap()
+}
+
+/// A cache of DNS answers, stored in a caller-provided buffer.
+///
+/// Each entry holds the records of one name and record type, in the format of `parse_response`, and expires
+/// with the smallest TTL of its records. When the buffer is full, the expired entries are evicted first,
+/// and then the oldest ones.
+///
+/// The current time is provided by the caller, in seconds since an arbitrary (but fixed) epoch.
+pub struct Cache<'a> {
+    storage: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Cache<'a> {
+    /// The size of the header preceding the name and the records of each entry:
+    /// the expiration time (8 bytes), the record type (2 bytes), the name length (1 byte) and the records length (2 bytes)
+    pub const ENTRY_HEADER_SIZE: usize = 13;
+
+    /// Create a new, empty cache in `storage`
+    pub fn new(storage: &'a mut [u8]) -> Self {
+        Self { storage, len: 0 }
+    }
+
+    /// Return the number of cached entries, including the expired ones which were not evicted yet
+    pub fn len(&self) -> usize {
+        self.entries().count()
+    }
+
+    /// Return `true` if the cache has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Remove all entries
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Look up the records of type `rtype` of the domain `name`, copying them into `result`.
+    ///
+    /// The TTLs of the copied records are set to the remaining lifetime of the entry.
+    /// Returns the length of the copied records, or `None` if there is no unexpired entry for the name and type.
+    pub fn get(
+        &self,
+        name: &str,
+        rtype: Rtype,
+        now: u64,
+        result: &mut [u8],
+    ) -> Result<Option<usize>, Error> {
+        let name = normalize(name);
+
+        let Some((_, entry)) = self
+            .entries()
+            .find(|(_, entry)| entry.matches(name, rtype) && entry.expires > now)
+        else {
+            return Ok(None);
+        };
+
+        let records = entry.records;
+
+        result
+            .get_mut(..records.len())
+            .ok_or(Error::ShortBuf)?
+            .copy_from_slice(records);
+
+        let ttl = u32::try_from(entry.expires - now).unwrap_or(u32::MAX);
+
+        let mut offset = 0;
+
+        while offset + RECORD_HEADER_SIZE <= records.len() {
+            result[offset..offset + 4].copy_from_slice(&ttl.to_be_bytes());
+
+            let data_len = u16::from_be_bytes([result[offset + 4], result[offset + 5]]) as usize;
+            offset += RECORD_HEADER_SIZE + data_len;
+        }
+
+        Ok(Some(records.len()))
+    }
+
+    /// Cache the `records` of type `rtype` of the domain `name` for `ttl` seconds, replacing any previous entry.
+    ///
+    /// Returns `false` if the entry was not cached, because it does not fit in the cache or because `ttl` is 0.
+    pub fn insert(&mut self, name: &str, rtype: Rtype, ttl: u32, now: u64, records: &[u8]) -> bool {
+        let name = normalize(name);
+
+        self.remove(name, rtype);
+
+        let size = Self::ENTRY_HEADER_SIZE + name.len() + records.len();
+
+        if ttl == 0
+            || name.len() > MAX_NAME_LEN
+            || records.len() > u16::MAX as usize
+            || size > self.storage.len()
+        {
+            return false;
+        }
+
+        if self.len + size > self.storage.len() {
+            self.remove_expired(now);
+        }
+
+        while self.len + size > self.storage.len() {
+            let (_, oldest) = self.entries().next().unwrap();
+            let oldest = oldest.size();
+
+            debug!("Cache full, evicting the oldest entry");
+            self.remove_at(0, oldest);
+        }
+
+        let entry = &mut self.storage[self.len..self.len + size];
+
+        entry[..8].copy_from_slice(&(now + ttl as u64).to_be_bytes());
+        entry[8..10].copy_from_slice(&rtype.to_int().to_be_bytes());
+        entry[10] = name.len() as u8;
+        entry[11..13].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        entry[13..13 + name.len()].copy_from_slice(name.as_bytes());
+        entry[13 + name.len()..].copy_from_slice(records);
+
+        self.len += size;
+
+        true
+    }
+
+    /// Remove the entry for the records of type `rtype` of the domain `name`, if any.
+    ///
+    /// Returns `true` if an entry was removed.
+    pub fn remove(&mut self, name: &str, rtype: Rtype) -> bool {
+        let name = normalize(name);
+
+        let found = self
+            .entries()
+            .find(|(_, entry)| entry.matches(name, rtype))
+            .map(|(offset, entry)| (offset, entry.size()));
+
+        if let Some((offset, size)) = found {
+            self.remove_at(offset, size);
+        }
+
+        found.is_some()
+    }
+
+    /// Remove all entries which are expired at `now`
+    pub fn remove_expired(&mut self, now: u64) {
+        let mut offset = 0;
+
+        while offset < self.len {
+            let entry = Entry::parse(&self.storage[offset..self.len]);
+            let size = entry.size();
+
+            if entry.expires <= now {
+                self.remove_at(offset, size);
+            } else {
+                offset += size;
+            }
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (usize, Entry<'_>)> {
+        let storage = &self.storage[..self.len];
+        let mut offset = 0;
+
+        iter::from_fn(move || {
+            (offset < storage.len()).then(|| {
+                let entry = Entry::parse(&storage[offset..]);
+                let entry_offset = offset;
+
+                offset += entry.size();
+
+                (entry_offset, entry)
+            })
+        })
+    }
+
+    fn remove_at(&mut self, offset: usize, size: usize) {
+        self.storage.copy_within(offset + size..self.len, offset);
+        self.len -= size;
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Entry<'a> {
+    expires: u64,
+    rtype: Rtype,
+    name: &'a [u8],
+    records: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    fn parse(data: &'a [u8]) -> Self {
+        let name_len = data[10] as usize;
+        let records_len = u16::from_be_bytes([data[11], data[12]]) as usize;
+
+        let name_start = Cache::ENTRY_HEADER_SIZE;
+        let records_start = name_start + name_len;
+
+        Self {
+            expires: u64::from_be_bytes(data[..8].try_into().unwrap()),
+            rtype: Rtype::from_int(u16::from_be_bytes([data[8], data[9]])),
+            name: &data[name_start..records_start],
+            records: &data[records_start..records_start + records_len],
+        }
+    }
+
+    fn size(&self) -> usize {
+        Cache::ENTRY_HEADER_SIZE + self.name.len() + self.records.len()
+    }
+
+    fn matches(&self, name: &str, rtype: Rtype) -> bool {
+        self.rtype == rtype && self.name.eq_ignore_ascii_case(name.as_bytes())
+    }
+}
+
+fn normalize(name: &str) -> &str {
+    name.strip_suffix('.').unwrap_or(name)
+}
+
+/// A domain name in the usual dotted notation (e.g. `www.example.com`), composable into `domain` messages
+#[derive(Copy, Clone, Debug)]
+struct DottedName<'a>(&'a str);
+
+impl<'a> DottedName<'a> {
+    fn new(name: &'a str) -> Result<Self, Error> {
+        let name = normalize(name);
+
+        // The wire format adds the length of the first label and the terminating empty label
+        if name.is_empty()
+            || name.len() + 2 > MAX_NAME_LEN
+            || name
+                .split('.')
+                .any(|label| label.is_empty() || label.len() > 63)
+        {
+            Err(Error::InvalidName)?;
+        }
+
+        Ok(Self(name))
+    }
+}
+
+impl Display for DottedName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.", self.0)
+    }
+}
+
+impl ToName for DottedName<'_> {}
+
+impl ToLabelIter for DottedName<'_> {
+    type LabelIter<'t>
+        = Chain<Map<Split<'t, char>, fn(&'t str) -> &'t Label>, Once<&'t Label>>
+    where
+        Self: 't;
+
+    fn iter_labels(&self) -> Self::LabelIter<'_> {
+        self.0
+            .split('.')
+            .map(to_label as fn(&str) -> &Label)
+            .chain(iter::once(Label::root()))
+    }
+}
+
+fn to_label(label: &str) -> &Label {
+    // Safe to unwrap, as the label lengths are checked by `DottedName::new`
+    Label::from_slice(label.as_bytes()).unwrap()
+}
+
+struct Buf<'a>(pub &'a mut [u8], pub usize);
+
+impl Composer for Buf<'_> {}
+
+impl OctetsBuilder for Buf<'_> {
+    type AppendError = ShortBuf;
+
+    fn append_slice(&mut self, slice: &[u8]) -> Result<(), Self::AppendError> {
+        if self.1 + slice.len() <= self.0.len() {
+            let end = self.1 + slice.len();
+            self.0[self.1..end].copy_from_slice(slice);
+            self.1 = end;
+
+            Ok(())
+        } else {
+            Err(ShortBuf)
+        }
+    }
+}
+
+impl Truncate for Buf<'_> {
+    fn truncate(&mut self, len: usize) {
+        self.1 = len;
+    }
+}
+
+impl AsMut<[u8]> for Buf<'_> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0[..self.1]
+    }
+}
+
+impl AsRef<[u8]> for Buf<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0[..self.1]
+    }
+}
+
+impl fmt::Write for Buf<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.append_slice(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn query() {
+        let mut buf = [0; MAX_UDP_MESSAGE_SIZE];
+
+        let len = compose_query(0x1234, "www.example.com.", Rtype::A, &mut buf).unwrap();
+
+        assert_eq!(
+            &buf[..len],
+            &[
+                0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'w', b'w', b'w', 7, b'e', b'x',
+                b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, 0, 1, 0, 1
+            ]
+        );
+
+        assert_eq!(
+            compose_query(1, "www..com", Rtype::A, &mut buf),
+            Err(Error::InvalidName)
+        );
+        assert_eq!(
+            compose_query(1, "", Rtype::A, &mut buf),
+            Err(Error::InvalidName)
+        );
+    }
+
+    #[test]
+    fn response() {
+        // www.example.com is a CNAME of example.com, which has one A record
+        let response = [
+            0x12, 0x34, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0, // Header
+            3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm',
+            0, 0, 1, 0, 1, // Question
+            0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 16, // CNAME
+            0xc0, 16, 0, 1, 0, 1, 0, 0, 0, 30, 0, 4, 192, 168, 0, 1, // A
+        ];
+
+        let mut result = [0; 64];
+
+        assert_eq!(
+            parse_response(&response, 0x4321, "www.example.com", Rtype::A, &mut result),
+            Ok(None)
+        );
+        assert_eq!(
+            parse_response(&response, 0x1234, "example.com", Rtype::A, &mut result),
+            Ok(None)
+        );
+
+        let answer =
+            parse_response(&response, 0x1234, "WWW.example.com", Rtype::A, &mut result).unwrap();
+
+        assert_eq!(answer, Some(Answer::Records { len: 10, ttl: 30 }));
+        assert_eq!(&result[..10], &[0, 0, 0, 30, 0, 4, 192, 168, 0, 1]);
+
+        let mut truncated = response;
+        truncated[2] |= 0x02;
+
+        assert_eq!(
+            parse_response(&truncated, 0x1234, "www.example.com", Rtype::A, &mut result),
+            Ok(Some(Answer::Truncated))
+        );
+
+        let mut nxdomain = response;
+        nxdomain[3] |= 0x03;
+
+        assert_eq!(
+            parse_response(&nxdomain, 0x1234, "www.example.com", Rtype::A, &mut result),
+            Err(Error::NotFound)
+        );
+    }
+
+    #[test]
+    fn reverse() {
+        let mut buf = [0; MAX_REVERSE_NAME_LEN];
+
+        assert_eq!(
+            reverse_name(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)), &mut buf),
+            "1.0.168.192.in-addr.arpa"
+        );
+
+        assert_eq!(
+            reverse_name(IpAddr::V6(Ipv6Addr::LOCALHOST), &mut buf),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa"
+        );
+    }
+
+    #[test]
+    fn cache() {
+        let a = [0, 0, 0, 60, 0, 4, 192, 168, 0, 1];
+        let aaaa = [0; RECORD_HEADER_SIZE + 16];
+
+        let mut storage = [0; 64];
+        let mut cache = Cache::new(&mut storage);
+
+        let mut result = [0; 32];
+
+        assert!(cache.insert("example.com", Rtype::A, 60, 100, &a));
+        assert!(!cache.insert("example.org", Rtype::A, 0, 100, &a));
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(
+            cache.get("EXAMPLE.com.", Rtype::A, 130, &mut result),
+            Ok(Some(a.len()))
+        );
+        assert_eq!(&result[..a.len()], &[0, 0, 0, 30, 0, 4, 192, 168, 0, 1]);
+
+        assert_eq!(
+            cache.get("example.com", Rtype::AAAA, 130, &mut result),
+            Ok(None)
+        );
+        assert_eq!(
+            cache.get("example.com", Rtype::A, 160, &mut result),
+            Ok(None)
+        );
+        assert_eq!(
+            cache.get("example.com", Rtype::A, 130, &mut result[..4]),
+            Err(Error::ShortBuf)
+        );
+
+        // Does not fit next to the first entry, so the first entry is evicted
+        assert!(cache.insert("example.org", Rtype::AAAA, 60, 100, &aaaa));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(
+            cache.get("example.com", Rtype::A, 130, &mut result),
+            Ok(None)
+        );
+        assert_eq!(
+            cache.get("example.org", Rtype::AAAA, 130, &mut result),
+            Ok(Some(aaaa.len()))
+        );
+
+        cache.remove_expired(160);
+        assert!(cache.is_empty());
+    }
+}
@@ -2,6 +2,8 @@ use core::cmp::min;
 use core::fmt::{Display, Write as _};
 use core::str;
 
+use edge_nal::WriteVectored;
+
 use embedded_io_async::{ErrorType, Read, Write};
 
 use httparse::Status;
@@ -179,15 +181,20 @@ impl<'b, const N: usize> RequestHeaders<'b, N> {
     pub async fn send<W>(
         &self,
         chunked_if_unspecified: bool,
-        mut output: W,
+        output: W,
     ) -> Result<(ConnectionType, BodyType), Error<W::Error>>
     where
-        W: Write,
+        W: WriteVectored,
     {
-        send_request(self.http11, self.method, self.path, &mut output).await?;
-
         self.headers
-            .send(None, true, self.http11, chunked_if_unspecified, output)
+            .send(
+                &request_line(self.http11, self.method, self.path),
+                None,
+                true,
+                self.http11,
+                chunked_if_unspecified,
+                output,
+            )
             .await
     }
 }
@@ -248,15 +255,16 @@ impl<'b, const N: usize> ResponseHeaders<'b, N> {
         &self,
         request_connection_type: ConnectionType,
         chunked_if_unspecified: bool,
-        mut output: W,
+        output: W,
     ) -> Result<(ConnectionType, BodyType), Error<W::Error>>
     where
-        W: Write,
+        W: WriteVectored,
     {
-        send_status(self.http11, self.code, self.reason, &mut output).await?;
+        let status: heapless::String<5> = self.code.try_into().unwrap();
 
         self.headers
             .send(
+                &status_line(self.http11, &status, self.reason),
                 Some(request_connection_type),
                 false,
                 self.http11,
@@ -267,83 +275,61 @@ impl<'b, const N: usize> ResponseHeaders<'b, N> {
     }
 }
 
-pub(crate) async fn send_request<W>(
-    http11: bool,
-    method: Method,
-    path: &str,
-    mut output: W,
-) -> Result<(), Error<W::Error>>
-where
-    W: Write,
-{
+/// Return the parts of the request line of an HTTP request
+pub(crate) fn request_line(http11: bool, method: Method, path: &str) -> [&[u8]; 6] {
     // RFC 9112:   request-line   = method SP request-target SP HTTP-version
 
-    output
-        .write_all(method.as_str().as_bytes())
-        .await
-        .map_err(Error::Io)?;
-    output.write_all(b" ").await.map_err(Error::Io)?;
-    output.write_all(path.as_bytes()).await.map_err(Error::Io)?;
-    output.write_all(b" ").await.map_err(Error::Io)?;
-    raw::send_version(&mut output, http11).await?;
-    output.write_all(b"\r\n").await.map_err(Error::Io)?;
-
-    Ok(())
+    [
+        method.as_str().as_bytes(),
+        b" ",
+        path.as_bytes(),
+        b" ",
+        raw::version(http11),
+        b"\r\n",
+    ]
 }
 
-pub(crate) async fn send_status<W>(
+/// Return the parts of the status line of an HTTP response
+pub(crate) fn status_line<'a>(
     http11: bool,
-    status: u16,
-    reason: Option<&str>,
-    mut output: W,
-) -> Result<(), Error<W::Error>>
-where
-    W: Write,
-{
+    status: &'a str,
+    reason: Option<&'a str>,
+) -> [&'a [u8]; 6] {
     // RFC 9112:   status-line = HTTP-version SP status-code SP [ reason-phrase ]
 
-    raw::send_version(&mut output, http11).await?;
-    output.write_all(b" ").await.map_err(Error::Io)?;
-    let status_str: heapless::String<5> = status.try_into().unwrap();
-    output
-        .write_all(status_str.as_bytes())
-        .await
-        .map_err(Error::Io)?;
-    output.write_all(b" ").await.map_err(Error::Io)?;
-    if let Some(reason) = reason {
-        output
-            .write_all(reason.as_bytes())
-            .await
-            .map_err(Error::Io)?;
-    }
-    output.write_all(b"\r\n").await.map_err(Error::Io)?;
-
-    Ok(())
+    [
+        raw::version(http11),
+        b" ",
+        status.as_bytes(),
+        b" ",
+        reason.unwrap_or("").as_bytes(),
+        b"\r\n",
+    ]
 }
 
+/// Send the request or status line, followed by the headers
+///
+/// The whole head of the message - including the headers which are added when the connection type
+/// or the body type are not explicitly specified - is sent with a single vectored write.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn send_headers<'a, H, W>(
+    line: &[&[u8]],
     headers: H,
     carry_over_connection_type: Option<ConnectionType>,
     request: bool,
     http11: bool,
     chunked_if_unspecified: bool,
-    mut output: W,
+    output: W,
 ) -> Result<(ConnectionType, BodyType), Error<W::Error>>
 where
-    W: Write,
+    W: WriteVectored,
     H: IntoIterator<Item = &'a (&'a str, &'a str)>,
 {
-    let (headers_connection_type, headers_body_type) = raw::send_headers(
+    send_head(
+        line,
         headers
             .into_iter()
             .map(|(name, value)| (*name, value.as_bytes())),
-        &mut output,
-    )
-    .await?;
-
-    send_headers_end(
-        headers_connection_type,
-        headers_body_type,
         carry_over_connection_type,
         request,
         http11,
@@ -353,18 +339,29 @@ where
     .await
 }
 
-async fn send_headers_end<W>(
-    headers_connection_type: Option<ConnectionType>,
-    headers_body_type: Option<BodyType>,
+#[allow(clippy::too_many_arguments)]
+async fn send_head<'a, H, W>(
+    line: &[&[u8]],
+    headers: H,
     carry_over_connection_type: Option<ConnectionType>,
     request: bool,
     http11: bool,
     chunked_if_unspecified: bool,
-    mut output: W,
+    output: W,
 ) -> Result<(ConnectionType, BodyType), Error<W::Error>>
 where
-    W: Write,
+    W: WriteVectored,
+    H: IntoIterator<Item = (&'a str, &'a [u8])>,
 {
+    let mut body_type_buf = heapless::String::new();
+
+    let mut head = raw::HeadWriter::new(output);
+
+    head.push(line).await?;
+
+    let (headers_connection_type, headers_body_type) =
+        raw::send_headers(headers, &mut head).await?;
+
     let connection_type =
         ConnectionType::resolve(headers_connection_type, carry_over_connection_type, http11)?;
 
@@ -380,19 +377,17 @@ where
         // Send an explicit Connection-Type just in case
         let (name, value) = connection_type.raw_header();
 
-        raw::send_header(name, value, &mut output).await?;
+        head.header(name, value).await?;
     }
 
     if headers_body_type.is_none() {
-        let mut buf = heapless::String::new();
-
-        if let Some((name, value)) = body_type.raw_header(&mut buf) {
+        if let Some((name, value)) = body_type.raw_header(&mut body_type_buf) {
             // Send explicit body type header just in case or if the body type was upgraded
-            raw::send_header(name, value, &mut output).await?;
+            head.header(name, value).await?;
         }
     }
 
-    raw::send_headers_end(output).await?;
+    head.finish().await?;
 
     Ok((connection_type, body_type))
 }
@@ -415,23 +410,22 @@ impl<const N: usize> Headers<'_, N> {
         Ok((connection_type, body_type))
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn send<W>(
         &self,
+        line: &[&[u8]],
         carry_over_connection_type: Option<ConnectionType>,
         request: bool,
         http11: bool,
         chunked_if_unspecified: bool,
-        mut output: W,
+        output: W,
     ) -> Result<(ConnectionType, BodyType), Error<W::Error>>
     where
-        W: Write,
+        W: WriteVectored,
     {
-        let (headers_connection_type, headers_body_type) =
-            raw::send_headers(self.iter_raw(), &mut output).await?;
-
-        send_headers_end(
-            headers_connection_type,
-            headers_body_type,
+        send_head(
+            line,
+            self.iter_raw(),
             carry_over_connection_type,
             request,
             http11,
@@ -495,6 +489,28 @@ where
         }
     }
 
+    /// Iterate over the trailer headers received after a chunked body
+    ///
+    /// The iterator is empty if the body is not chunked, if it has not been completely read yet,
+    /// or if the peer did not send any trailer.
+    pub fn trailers(&self) -> impl Iterator<Item = (&str, &str)> {
+        trailers(self.raw_trailers())
+    }
+
+    /// Get the value of a trailer header by name
+    pub fn trailer(&self, name: &str) -> Option<&str> {
+        self.trailers()
+            .find(|(tname, _)| name.eq_ignore_ascii_case(tname))
+            .map(|(_, value)| value)
+    }
+
+    pub(crate) fn raw_trailers(&self) -> &[u8] {
+        match self {
+            Self::Chunked(r) => r.trailers(),
+            _ => &[],
+        }
+    }
+
     /// Return a mutable reference to the underlying raw reader
     pub fn as_raw_reader(&mut self) -> &mut R {
         match self {
@@ -534,6 +550,15 @@ where
     }
 }
 
+pub(crate) fn trailers(raw: &[u8]) -> impl Iterator<Item = (&str, &str)> {
+    raw.split(|b| *b == b'\n').filter_map(|line| {
+        let line = str::from_utf8(line).ok()?.trim_end_matches('\r');
+        let (name, value) = line.split_once(':')?;
+
+        Some((name.trim(), value.trim()))
+    })
+}
+
 pub(crate) struct PartiallyRead<'b, R> {
     buf: &'b [u8],
     read_len: usize,
@@ -646,6 +671,7 @@ pub(crate) struct ChunkedRead<'b, R> {
     input: R,
     remain: u64,
     complete: bool,
+    trailer_len: usize,
 }
 
 impl<'b, R> ChunkedRead<'b, R>
@@ -660,6 +686,7 @@ where
             input,
             remain: 0,
             complete: false,
+            trailer_len: 0,
         }
     }
 
@@ -667,6 +694,16 @@ where
         self.complete
     }
 
+    /// The raw trailer headers (CRLF-terminated lines) received after the last chunk.
+    /// Empty until the body has been completely read.
+    pub fn trailers(&self) -> &[u8] {
+        if self.complete {
+            &self.buf[..self.trailer_len]
+        } else {
+            &[]
+        }
+    }
+
     pub fn release(self) -> R {
         self.input
     }
@@ -778,14 +815,50 @@ where
         Ok(())
     }
 
-    // Consume and discard the optional trailer following the last chunk.
+    // Consume the optional trailer following the last chunk.
+    // The trailer headers are retained at the start of the buffer, as it is no longer
+    // needed for the body itself; the rest of the buffer is used for reading ahead.
     async fn consume_trailer(&mut self) -> Result<(), Error<R::Error>> {
-        while self.consume_header().await? {}
+        self.buf.copy_within(self.buf_offset..self.buf_len, 0);
+        self.buf_len -= self.buf_offset;
+        self.buf_offset = 0;
+
+        loop {
+            let start = self.trailer_len;
+
+            let mut first = self.trailer_fetch().await?;
+
+            loop {
+                let second = self.trailer_fetch().await?;
+
+                if first == b'\r' && second == b'\n' {
+                    break;
+                }
+
+                first = second;
+            }
+
+            if self.trailer_len - start == 2 {
+                // Empty line terminating the trailer
+                self.trailer_len = start;
+                break;
+            }
+        }
 
         Ok(())
     }
 
-    // Consume and discard each header in the optional trailer following the last chunk.
+    // Fetch the next trailer byte and append it to the retained trailer.
+    async fn trailer_fetch(&mut self) -> Result<u8, Error<R::Error>> {
+        let byte = self.input_fetch().await?;
+
+        self.buf[self.trailer_len] = byte;
+        self.trailer_len += 1;
+
+        Ok(byte)
+    }
+
+    // Consume and discard a single CRLF-terminated line.
     async fn consume_header(&mut self) -> Result<bool, Error<R::Error>> {
         let mut first = self.input_fetch().await?;
         let mut len = 1;
@@ -826,11 +899,21 @@ where
 
     async fn input_next(&mut self) -> Result<Option<u8>, Error<R::Error>> {
         if self.buf_offset == self.buf_len {
-            self.buf_len = self.input.read(self.buf).await.map_err(Error::Io)?;
-            self.buf_offset = 0;
+            if self.trailer_len == self.buf.len() {
+                return Err(Error::TooLongHeaders);
+            }
+
+            let len = self
+                .input
+                .read(&mut self.buf[self.trailer_len..])
+                .await
+                .map_err(Error::Io)?;
+
+            self.buf_offset = self.trailer_len;
+            self.buf_len = self.trailer_len + len;
         }
 
-        if self.buf_len > 0 {
+        if self.buf_len > self.buf_offset {
             let byte = self.buf[self.buf_offset];
             self.buf_offset += 1;
 
@@ -913,7 +996,7 @@ where
     /// Finish writing the body (necessary for chunked encoding)
     pub async fn finish(&mut self) -> Result<(), Error<W::Error>>
     where
-        W: Write,
+        W: WriteVectored,
     {
         match self {
             Self::Raw(_) => (),
@@ -930,6 +1013,32 @@ where
         Ok(())
     }
 
+    /// Finish writing a chunked body, sending the provided trailer headers after the last chunk
+    ///
+    /// Useful for metadata only known once the whole body had been streamed, like a `Content-Digest`
+    /// computed over the body. The trailer names should be announced upfront with a `Trailer` header.
+    ///
+    /// Returns `Error::InvalidState` if the body is not chunked or had already been finished.
+    ///
+    /// Parameters:
+    /// - `trailers`: The trailer headers to send
+    pub async fn finish_with_trailers(
+        &mut self,
+        trailers: &[(&str, &str)],
+    ) -> Result<(), Error<W::Error>>
+    where
+        W: WriteVectored,
+    {
+        match self {
+            Self::Chunked(w) => w.finish_with_trailers(trailers).await?,
+            _ => return Err(Error::InvalidState),
+        }
+
+        self.flush().await?;
+
+        Ok(())
+    }
+
     /// Return a mutable reference to the underlying raw writer
     pub fn as_raw_writer(&mut self) -> &mut W {
         match self {
@@ -958,7 +1067,7 @@ where
 
 impl<W> Write for SendBody<W>
 where
-    W: Write,
+    W: WriteVectored,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         match self {
@@ -1056,6 +1165,32 @@ impl<W> ChunkedWrite<W> {
         Ok(())
     }
 
+    pub async fn finish_with_trailers(
+        &mut self,
+        trailers: &[(&str, &str)],
+    ) -> Result<(), Error<W::Error>>
+    where
+        W: WriteVectored,
+    {
+        if self.finished {
+            return Err(Error::InvalidState);
+        }
+
+        let mut head = raw::HeadWriter::new(&mut self.output);
+
+        head.push(&[b"0\r\n"]).await?;
+
+        for (name, value) in trailers {
+            head.header(name, value.as_bytes()).await?;
+        }
+
+        head.finish().await?;
+
+        self.finished = true;
+
+        Ok(())
+    }
+
     pub fn release(self) -> W {
         self.output
     }
@@ -1070,7 +1205,7 @@ where
 
 impl<W> Write for ChunkedWrite<W>
 where
-    W: Write,
+    W: WriteVectored,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         if self.finished {
@@ -1080,14 +1215,10 @@ where
             write!(&mut len_str, "{:x}", buf.len()).unwrap();
 
             self.output
-                .write_all(len_str.as_bytes())
+                .write_all_vectored(&[len_str.as_bytes(), b"\r\n", buf, b"\r\n"])
                 .await
                 .map_err(Error::Io)?;
 
-            self.output.write_all(b"\r\n").await.map_err(Error::Io)?;
-            self.output.write_all(buf).await.map_err(Error::Io)?;
-            self.output.write_all(b"\r\n").await.map_err(Error::Io)?;
-
             Ok(buf.len())
         } else {
             Ok(0)
@@ -1102,7 +1233,9 @@ where
 mod raw {
     use core::str;
 
-    use embedded_io_async::{Read, Write};
+    use edge_nal::WriteVectored;
+
+    use embedded_io_async::Read;
 
     use log::warn;
 
@@ -1204,22 +1337,21 @@ mod raw {
         }
     }
 
-    pub(crate) async fn send_version<W>(mut output: W, http11: bool) -> Result<(), Error<W::Error>>
-    where
-        W: Write,
-    {
-        output
-            .write_all(if http11 { b"HTTP/1.1" } else { b"HTTP/1.0" })
-            .await
-            .map_err(Error::Io)
+    pub(crate) const fn version(http11: bool) -> &'static [u8] {
+        if http11 {
+            b"HTTP/1.1"
+        } else {
+            b"HTTP/1.0"
+        }
     }
 
-    pub(crate) async fn send_headers<'a, H, W>(
+    pub(crate) async fn send_headers<'a, 'b, H, W>(
         headers: H,
-        mut output: W,
+        head: &mut HeadWriter<'b, W>,
     ) -> Result<(Option<ConnectionType>, Option<BodyType>), Error<W::Error>>
     where
-        W: Write,
+        'a: 'b,
+        W: WriteVectored,
         H: IntoIterator<Item = (&'a str, &'a [u8])>,
     {
         let mut connection = None;
@@ -1250,38 +1382,81 @@ mod raw {
                 body = Some(header_body);
             }
 
-            send_header(name, value, &mut output).await?;
+            head.header(name, value).await?;
         }
 
         Ok((connection, body))
     }
 
-    pub(crate) async fn send_header<W>(
-        name: &str,
-        value: &[u8],
-        mut output: W,
-    ) -> Result<(), Error<W::Error>>
-    where
-        W: Write,
-    {
-        output.write_all(name.as_bytes()).await.map_err(Error::Io)?;
-        output.write_all(b": ").await.map_err(Error::Io)?;
-        output.write_all(value).await.map_err(Error::Io)?;
-        output.write_all(b"\r\n").await.map_err(Error::Io)?;
+    /// The maximum number of slices a message head is collected in before being written
+    ///
+    /// Enough for the start line and a dozen of headers. Heads with more headers are sent
+    /// with several vectored writes.
+    const HEAD_SLICES: usize = 64;
 
-        Ok(())
+    /// Collects the slices of a message head (start line, headers and the terminating CRLF),
+    /// so that they are sent with a single `WriteVectored::write_all_vectored` call
+    pub(crate) struct HeadWriter<'a, W> {
+        output: W,
+        slices: heapless::Vec<&'a [u8], HEAD_SLICES>,
     }
 
-    pub(crate) async fn send_headers_end<W>(mut output: W) -> Result<(), Error<W::Error>>
+    impl<'a, W> HeadWriter<'a, W>
     where
-        W: Write,
+        W: WriteVectored,
     {
-        output.write_all(b"\r\n").await.map_err(Error::Io)
+        pub(crate) const fn new(output: W) -> Self {
+            Self {
+                output,
+                slices: heapless::Vec::new(),
+            }
+        }
+
+        pub(crate) async fn push(&mut self, slices: &[&'a [u8]]) -> Result<(), Error<W::Error>> {
+            for slice in slices {
+                if self.slices.is_full() {
+                    self.flush().await?;
+                }
+
+                self.slices.push(slice).unwrap();
+            }
+
+            Ok(())
+        }
+
+        pub(crate) async fn header(
+            &mut self,
+            name: &'a str,
+            value: &'a [u8],
+        ) -> Result<(), Error<W::Error>> {
+            self.push(&[name.as_bytes(), b": ", value, b"\r\n"]).await
+        }
+
+        pub(crate) async fn finish(mut self) -> Result<(), Error<W::Error>> {
+            self.push(&[b"\r\n"]).await?;
+            self.flush().await
+        }
+
+        async fn flush(&mut self) -> Result<(), Error<W::Error>> {
+            self.output
+                .write_all_vectored(&self.slices)
+                .await
+                .map_err(Error::Io)?;
+
+            self.slices.clear();
+
+            Ok(())
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
+    extern crate std;
+
+    use std::boxed::Box;
+    use std::vec::Vec;
+
     use embedded_io_async::{ErrorType, Read};
 
     use super::*;
@@ -1303,6 +1478,72 @@ mod test {
         }
     }
 
+    /// A writer which records the written data, as well as the number of `write_all_vectored` calls
+    #[derive(Default)]
+    struct VecWrite {
+        data: Vec<u8>,
+        vectored_writes: usize,
+    }
+
+    impl ErrorType for VecWrite {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Write for VecWrite {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.data.extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+    }
+
+    impl WriteVectored for VecWrite {
+        async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+            for buf in bufs {
+                self.data.extend_from_slice(buf);
+            }
+
+            self.vectored_writes += 1;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_send_head() {
+        embassy_futures::block_on(async move {
+            let mut request = RequestHeaders::<'_, 4>::new();
+            request.method = Method::Post;
+            request.path = "/upload";
+            request.headers.set("Host", "example.com");
+
+            let mut output = VecWrite::default();
+
+            let (connection_type, body_type) =
+                Box::pin(request.send(true, &mut output)).await.unwrap();
+
+            assert_eq!(connection_type, ConnectionType::KeepAlive);
+            assert_eq!(body_type, BodyType::Chunked);
+
+            // The request line, the headers - including the implicit ones - and the terminating CRLF in one write
+            assert_eq!(output.vectored_writes, 1);
+            assert_eq!(
+                output.data,
+                b"POST /upload HTTP/1.1\r\nHost: example.com\r\nConnection: Keep-Alive\r\nTransfer-Encoding: Chunked\r\n\r\n"
+            );
+
+            let mut body = SendBody::new(body_type, &mut output);
+
+            Box::pin(body.write_all(b"abcd")).await.unwrap();
+            Box::pin(body.finish_with_trailers(&[("X-A", "B")]))
+                .await
+                .unwrap();
+
+            assert!(output.data.ends_with(b"4\r\nabcd\r\n0\r\nX-A: B\r\n\r\n"));
+            assert_eq!(output.vectored_writes, 3);
+        })
+    }
+
     #[test]
     fn test_chunked_bytes() {
         // Normal
@@ -1323,6 +1564,28 @@ mod test {
         expect(b"4\r\nabcdefg", None);
     }
 
+    #[test]
+    fn test_chunked_trailers() {
+        embassy_futures::block_on(async move {
+            let mut buf1 = [0; 64];
+            let mut buf2 = [0; 64];
+
+            let stream =
+                SliceRead(b"4\r\nabcd\r\n0\r\nContent-Digest: sha-256=:AA==:\r\nX-A: B\r\n\r\n");
+            let mut r = Body::new(BodyType::Chunked, &mut buf1, 0, stream);
+
+            assert_eq!(r.trailers().count(), 0);
+
+            assert_eq!(Box::pin(r.read(&mut buf2)).await.unwrap(), 4);
+            assert_eq!(Box::pin(r.read(&mut buf2)).await.unwrap(), 0);
+
+            assert!(r.is_complete());
+            assert_eq!(r.trailer("content-digest"), Some("sha-256=:AA==:"));
+            assert_eq!(r.trailer("X-A"), Some("B"));
+            assert_eq!(r.trailers().count(), 2);
+        })
+    }
+
     fn expect(input: &[u8], expected: Option<&[u8]>) {
         embassy_futures::block_on(async move {
             let mut buf1 = [0; 64];
@@ -1332,16 +1595,18 @@ mod test {
             let mut r = ChunkedRead::new(stream, &mut buf1, 0);
 
             if let Some(expected) = expected {
-                assert!(r.read_exact(&mut buf2[..expected.len()]).await.is_ok());
+                assert!(Box::pin(r.read_exact(&mut buf2[..expected.len()]))
+                    .await
+                    .is_ok());
 
                 assert_eq!(&buf2[..expected.len()], expected);
 
-                let len = r.read(&mut buf2).await;
+                let len = Box::pin(r.read(&mut buf2)).await;
                 assert!(len.is_ok());
 
                 assert_eq!(len.unwrap(), 0);
             } else {
-                assert!(r.read(&mut buf2).await.is_err());
+                assert!(Box::pin(r.read(&mut buf2)).await.is_err());
             }
         })
     }
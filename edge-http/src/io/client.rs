@@ -11,7 +11,7 @@ use crate::{
     ConnectionType, DEFAULT_MAX_HEADERS_COUNT,
 };
 
-use super::{send_headers, send_request, Body, Error, ResponseHeaders, SendBody};
+use super::{request_line, send_headers, trailers, Body, Error, ResponseHeaders, SendBody};
 
 #[allow(unused_imports)]
 #[cfg(feature = "embedded-svc")]
@@ -104,6 +104,25 @@ where
         self.complete_request().await
     }
 
+    /// Initiate an HTTP response, terminating the chunked request body with the provided trailer headers.
+    ///
+    /// The request must have been initiated with a `Transfer-Encoding: chunked` header, and the
+    /// trailer names should be announced in a `Trailer` request header.
+    ///
+    /// Parameters:
+    /// - `trailers`: The trailer headers (e.g. a `Content-Digest` computed while streaming the body) to send
+    pub async fn initiate_response_with_trailers(
+        &mut self,
+        trailers: &[(&str, &str)],
+    ) -> Result<(), Error<T::Error>> {
+        self.request_mut()?
+            .io
+            .finish_with_trailers(trailers)
+            .await?;
+
+        self.complete_request().await
+    }
+
     /// Return `true` if a response has been initiated.
     pub fn is_response_initiated(&self) -> bool {
         matches!(self, Self::Response(_))
@@ -137,6 +156,16 @@ where
         Ok(&response.response)
     }
 
+    /// Iterate over the trailer headers of the response.
+    ///
+    /// The connection must be in response mode and the chunked response body must have been read completely,
+    /// otherwise the iterator is empty.
+    pub fn trailers(&self) -> Result<impl Iterator<Item = (&str, &str)>, Error<T::Error>> {
+        let response = self.response_ref()?;
+
+        Ok(trailers(response.io.raw_trailers()))
+    }
+
     /// Get a mutable reference to the raw connection.
     ///
     /// This can be used to send raw data over the connection.
@@ -173,24 +202,38 @@ where
 
         let mut state = self.unbind();
 
+        let line = request_line(http11, method, uri);
+
         let result = async {
-            match send_request(http11, method, uri, state.io.as_mut().unwrap()).await {
-                Ok(_) => (),
-                Err(Error::Io(_)) => {
-                    if !fresh_connection {
-                        // Attempt to reconnect and re-send the request
-                        state.io = None;
-                        state.io = Some(state.socket.connect(state.addr).await.map_err(Error::Io)?);
-
-                        send_request(http11, method, uri, state.io.as_mut().unwrap()).await?;
-                    }
+            match send_headers(
+                &line,
+                headers,
+                None,
+                true,
+                http11,
+                true,
+                state.io.as_mut().unwrap(),
+            )
+            .await
+            {
+                Err(Error::Io(_)) if !fresh_connection => {
+                    // Attempt to reconnect and re-send the request
+                    state.io = None;
+                    state.io = Some(state.socket.connect(state.addr).await.map_err(Error::Io)?);
+
+                    send_headers(
+                        &line,
+                        headers,
+                        None,
+                        true,
+                        http11,
+                        true,
+                        state.io.as_mut().unwrap(),
+                    )
+                    .await
                 }
-                Err(other) => Err(other)?,
+                other => other,
             }
-
-            let io = state.io.as_mut().unwrap();
-
-            send_headers(headers, None, true, http11, true, &mut *io).await
         }
         .await;
 
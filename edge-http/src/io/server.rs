@@ -1,9 +1,10 @@
+use core::cell::Cell;
 use core::fmt::{self, Debug, Display};
 use core::mem::{self, MaybeUninit};
 use core::pin::pin;
 
 use edge_nal::{
-    with_timeout, Close, Readable, TcpShutdown, TcpSplit, WithTimeout, WithTimeoutError,
+    with_timeout, Readable, TcpShutdown, TcpSplit, WithTimeout, WithTimeoutError, WriteVectored,
 };
 
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
@@ -13,7 +14,7 @@ use embedded_io_async::{ErrorType, Read, Write};
 
 use log::{debug, info, warn};
 
-use super::{send_headers, send_status, Body, Error, RequestHeaders, SendBody};
+use super::{send_headers, status_line, Body, Error, RequestHeaders, SendBody};
 
 use crate::ws::{upgrade_response_headers, MAX_BASE64_KEY_RESPONSE_LEN};
 use crate::{ConnectionType, DEFAULT_MAX_HEADERS_COUNT};
@@ -22,11 +23,16 @@ use crate::{ConnectionType, DEFAULT_MAX_HEADERS_COUNT};
 #[cfg(feature = "embedded-svc")]
 pub use embedded_svc_compat::*;
 
+pub mod dav;
+
 pub const DEFAULT_HANDLER_TASKS_COUNT: usize = 4;
 pub const DEFAULT_BUF_SIZE: usize = 2048;
 
 const COMPLETION_BUF_SIZE: usize = 64;
 
+/// How long to wait for the peer to acknowledge the closing of a connection, before aborting it
+const CLOSE_TIMEOUT_MS: u32 = 5000;
+
 /// A connection state machine for handling HTTP server requests-response cycles.
 #[allow(private_interfaces)]
 pub enum Connection<'b, T, const N: usize = DEFAULT_MAX_HEADERS_COUNT> {
@@ -38,7 +44,7 @@ pub enum Connection<'b, T, const N: usize = DEFAULT_MAX_HEADERS_COUNT> {
 
 impl<'b, T, const N: usize> Connection<'b, T, N>
 where
-    T: Read + Write,
+    T: Read + WriteVectored,
 {
     /// Create a new connection state machine for an incoming request
     ///
@@ -50,14 +56,25 @@ where
     /// Parameters:
     /// - `buf`: A buffer to store the request headers
     /// - `io`: A socket stream
-    pub async fn new(
+    pub async fn new(buf: &'b mut [u8], io: T) -> Result<Connection<'b, T, N>, Error<T::Error>> {
+        Self::new_timed(buf, io, None).await
+    }
+
+    /// Same as `new`, but additionally records the time the request headers were received,
+    /// and the time the response status line was written, with the provided timer
+    async fn new_timed(
         buf: &'b mut [u8],
         mut io: T,
+        timer: Option<ResponseTimer<'b>>,
     ) -> Result<Connection<'b, T, N>, Error<T::Error>> {
         let mut request = RequestHeaders::new();
 
         let (buf, read_len) = request.receive(buf, &mut io, true).await?;
 
+        if let Some(timer) = timer {
+            timer.headers_received.set(timer.clock.now());
+        }
+
         let (connection_type, body_type) = request.resolve::<T::Error>()?;
 
         let io = Body::new(body_type, buf, read_len, io);
@@ -66,6 +83,7 @@ where
             request,
             io,
             connection_type,
+            timer,
         }))
     }
 
@@ -118,6 +136,65 @@ where
         self.initiate_response(101, None, &headers).await
     }
 
+    /// Send a response whose body is produced by the provided `BodyStreamer`
+    ///
+    /// The response is initiated lazily, upon the first write of the streamer, and is completed
+    /// (i.e. the terminating chunk is sent for chunked encoding) once the streamer is done.
+    ///
+    /// If the streamer fails:
+    /// - Before writing anything, a 500 Internal Error response is sent instead
+    /// - Midway, the connection is aborted without completing the response, so that the client
+    ///   does not mistake the truncated body for a complete one. The connection needs to be closed afterwards
+    ///
+    /// Parameters:
+    /// - `status`: The HTTP status code
+    /// - `message`: An optional HTTP status message
+    /// - `headers`: An array of HTTP response headers
+    /// - `streamer`: The producer of the response body
+    pub async fn stream_response<S>(
+        &mut self,
+        status: u16,
+        message: Option<&str>,
+        headers: &[(&str, &str)],
+        mut streamer: S,
+    ) -> Result<(), HandlerError<T::Error, S::Error<Error<T::Error>>>>
+    where
+        S: BodyStreamer,
+    {
+        let result = streamer
+            .stream(&mut LazyResponse {
+                connection: self,
+                status,
+                message,
+                headers,
+            })
+            .await;
+
+        match result {
+            Ok(()) => {
+                if self.is_request_initiated() {
+                    self.initiate_response(status, message, headers).await?;
+                }
+
+                self.complete().await?;
+
+                Ok(())
+            }
+            Err(e) => {
+                if self.is_request_initiated() {
+                    self.complete_err("INTERNAL ERROR").await?;
+                } else {
+                    warn!("Body streaming failed midway, aborting the response");
+
+                    let io = self.unbind_mut();
+                    *self = Self::Unbound(io);
+                }
+
+                Err(HandlerError::Handler(e))
+            }
+        }
+    }
+
     /// Return `true` if the connection is in response state
     pub fn is_response_initiated(&self) -> bool {
         matches!(self, Self::Response(_))
@@ -194,13 +271,15 @@ where
 
         let http11 = request.request.http11;
         let request_connection_type = request.connection_type;
+        let timer = request.timer;
 
         let mut io = self.unbind_mut();
 
-        let result = async {
-            send_status(http11, status, reason, &mut io).await?;
+        let status: heapless::String<5> = status.try_into().unwrap();
 
+        let result = async {
             let (connection_type, body_type) = send_headers(
+                &status_line(http11, &status, reason),
                 headers.iter(),
                 Some(request_connection_type),
                 false,
@@ -210,6 +289,10 @@ where
             )
             .await?;
 
+            if let Some(timer) = timer {
+                timer.first_byte_written.set(Some(timer.clock.now()));
+            }
+
             Ok((connection_type, body_type))
         }
         .await;
@@ -291,7 +374,7 @@ where
 
 impl<T, const N: usize> Read for Connection<'_, T, N>
 where
-    T: Read + Write,
+    T: Read + WriteVectored,
 {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         self.request_mut()?.io.read(buf).await
@@ -300,7 +383,7 @@ where
 
 impl<T, const N: usize> Write for Connection<'_, T, N>
 where
-    T: Read + Write,
+    T: Read + WriteVectored,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         self.response_mut()?.io.write(buf).await
@@ -317,6 +400,15 @@ struct RequestState<'b, T, const N: usize> {
     request: RequestHeaders<'b, N>,
     io: Body<'b, T>,
     connection_type: ConnectionType,
+    timer: Option<ResponseTimer<'b>>,
+}
+
+/// The clock of `handle_request_observed`, and the timestamps recorded by the connection with it
+#[derive(Copy, Clone)]
+struct ResponseTimer<'b> {
+    clock: &'b dyn Clock,
+    headers_received: &'b Cell<u64>,
+    first_byte_written: &'b Cell<Option<u64>>,
 }
 
 struct ResponseState<T> {
@@ -346,6 +438,85 @@ impl<T, E> From<Error<T>> for HandlerError<T, E> {
     }
 }
 
+/// A trait (async callback) producing the body of a response sent with `Connection::stream_response`
+pub trait BodyStreamer {
+    type Error<E>: Debug
+    where
+        E: Debug;
+
+    /// Write the response body
+    ///
+    /// Parameters:
+    /// - `body`: The response body writer
+    async fn stream<W>(&mut self, body: &mut W) -> Result<(), Self::Error<W::Error>>
+    where
+        W: Write;
+}
+
+impl<S> BodyStreamer for &mut S
+where
+    S: BodyStreamer,
+{
+    type Error<E>
+        = S::Error<E>
+    where
+        E: Debug;
+
+    async fn stream<W>(&mut self, body: &mut W) -> Result<(), Self::Error<W::Error>>
+    where
+        W: Write,
+    {
+        (**self).stream(body).await
+    }
+}
+
+/// The body writer of `Connection::stream_response`, which initiates the response upon the first write
+struct LazyResponse<'c, 'b, 'h, T, const N: usize> {
+    connection: &'c mut Connection<'b, T, N>,
+    status: u16,
+    message: Option<&'h str>,
+    headers: &'h [(&'h str, &'h str)],
+}
+
+impl<T, const N: usize> LazyResponse<'_, '_, '_, T, N>
+where
+    T: Read + WriteVectored,
+{
+    async fn initiate(&mut self) -> Result<(), Error<T::Error>> {
+        if self.connection.is_request_initiated() {
+            self.connection
+                .initiate_response(self.status, self.message, self.headers)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, const N: usize> ErrorType for LazyResponse<'_, '_, '_, T, N>
+where
+    T: ErrorType,
+{
+    type Error = Error<T::Error>;
+}
+
+impl<T, const N: usize> Write for LazyResponse<'_, '_, '_, T, N>
+where
+    T: Read + WriteVectored,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.initiate().await?;
+
+        self.connection.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.initiate().await?;
+
+        self.connection.flush().await
+    }
+}
+
 /// A trait (async callback) for handling incoming HTTP requests
 pub trait Handler {
     type Error<E>: Debug
@@ -363,7 +534,7 @@ pub trait Handler {
         connection: &mut Connection<'_, T, N>,
     ) -> Result<(), Self::Error<T::Error>>
     where
-        T: Read + Write + TcpSplit;
+        T: Read + WriteVectored + TcpSplit;
 }
 
 impl<H> Handler for &H
@@ -381,7 +552,7 @@ where
         connection: &mut Connection<'_, T, N>,
     ) -> Result<(), Self::Error<T::Error>>
     where
-        T: Read + Write + TcpSplit,
+        T: Read + WriteVectored + TcpSplit,
     {
         (**self).handle(task_id, connection).await
     }
@@ -402,7 +573,7 @@ where
         connection: &mut Connection<'_, T, N>,
     ) -> Result<(), Self::Error<T::Error>>
     where
-        T: Read + Write + TcpSplit,
+        T: Read + WriteVectored + TcpSplit,
     {
         (**self).handle(task_id, connection).await
     }
@@ -423,7 +594,7 @@ where
         connection: &mut Connection<'_, T, N>,
     ) -> Result<(), Self::Error<T::Error>>
     where
-        T: Read + Write + TcpSplit,
+        T: Read + WriteVectored + TcpSplit,
     {
         let mut io = pin!(self.io().handle(task_id, connection));
 
@@ -433,6 +604,272 @@ where
     }
 }
 
+/// A predicate deciding whether a handler is willing to handle an incoming request
+///
+/// Used by `Fallback` to pick the handler of a request, based on the request headers alone.
+pub trait Matcher {
+    /// Return `true` if the request with the provided headers should be handled
+    fn matches<const N: usize>(&self, headers: &RequestHeaders<'_, N>) -> bool;
+}
+
+impl<M> Matcher for &M
+where
+    M: Matcher,
+{
+    fn matches<const N: usize>(&self, headers: &RequestHeaders<'_, N>) -> bool {
+        (**self).matches(headers)
+    }
+}
+
+/// A matcher for requests whose path starts with the provided prefix
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PathPrefix<'a>(pub &'a str);
+
+impl Matcher for PathPrefix<'_> {
+    fn matches<const N: usize>(&self, headers: &RequestHeaders<'_, N>) -> bool {
+        headers.path.starts_with(self.0)
+    }
+}
+
+/// A handler which only handles the requests accepted by its matcher
+///
+/// Implements both `Handler` and `Matcher`, so that it can be used as the primary handler of a `Fallback`.
+pub struct Matching<M, H> {
+    matcher: M,
+    handler: H,
+}
+
+impl<M, H> Matching<M, H> {
+    /// Create a new matching handler
+    ///
+    /// Parameters:
+    /// - `matcher`: The matcher deciding which requests are handled
+    /// - `handler`: The handler for the matched requests
+    pub const fn new(matcher: M, handler: H) -> Self {
+        Self { matcher, handler }
+    }
+}
+
+impl<M, H> Matcher for Matching<M, H>
+where
+    M: Matcher,
+{
+    fn matches<const N: usize>(&self, headers: &RequestHeaders<'_, N>) -> bool {
+        self.matcher.matches(headers)
+    }
+}
+
+impl<M, H> Handler for Matching<M, H>
+where
+    H: Handler,
+{
+    type Error<E>
+        = H::Error<E>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + WriteVectored + TcpSplit,
+    {
+        self.handler.handle(task_id, connection).await
+    }
+}
+
+/// The error of a `Fallback` handler, i.e. the error of whichever handler handled the request
+#[derive(Debug)]
+pub enum FallbackError<P, F> {
+    /// The primary handler failed
+    Primary(P),
+    /// The fallback handler failed
+    Fallback(F),
+}
+
+impl<P, F> Display for FallbackError<P, F>
+where
+    P: Display,
+    F: Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Primary(e) => write!(f, "{e}"),
+            Self::Fallback(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P, F> std::error::Error for FallbackError<P, F>
+where
+    P: std::error::Error,
+    F: std::error::Error,
+{
+}
+
+/// A handler combinator which passes the requests not matched by the primary handler to a fallback handler
+///
+/// Since `Fallback` is itself a `Matcher` (matching whatever either of its handlers matches),
+/// chains like "API router -> static assets -> captive-portal redirect" can be composed by nesting:
+/// `Fallback::new(api, Fallback::new(assets, redirect))`.
+pub struct Fallback<P, F> {
+    primary: P,
+    fallback: F,
+}
+
+impl<P, F> Fallback<P, F> {
+    /// Create a new fallback handler
+    ///
+    /// Parameters:
+    /// - `primary`: The handler for the requests it matches
+    /// - `fallback`: The handler for all other requests
+    pub const fn new(primary: P, fallback: F) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<P, F> Matcher for Fallback<P, F>
+where
+    P: Matcher,
+    F: Matcher,
+{
+    fn matches<const N: usize>(&self, headers: &RequestHeaders<'_, N>) -> bool {
+        self.primary.matches(headers) || self.fallback.matches(headers)
+    }
+}
+
+impl<P, F> Handler for Fallback<P, F>
+where
+    P: Handler + Matcher,
+    F: Handler,
+{
+    type Error<E>
+        = FallbackError<P::Error<E>, F::Error<E>>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + WriteVectored + TcpSplit,
+    {
+        let primary = connection
+            .headers()
+            .map(|headers| self.primary.matches(headers))
+            .unwrap_or(false);
+
+        if primary {
+            self.primary
+                .handle(task_id, connection)
+                .await
+                .map_err(FallbackError::Primary)
+        } else {
+            self.fallback
+                .handle(task_id, connection)
+                .await
+                .map_err(FallbackError::Fallback)
+        }
+    }
+}
+
+/// A monotonic clock, used for timing the request-response cycles handled by the server
+pub trait Clock {
+    /// Return the current time
+    ///
+    /// The unit (ms, us, ticks) is up to the implementation, as long as the returned values
+    /// are monotonically increasing. All `RequestTimings` values are expressed in that unit.
+    fn now(&self) -> u64;
+}
+
+impl<C> Clock for &C
+where
+    C: Clock,
+{
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+/// Timestamps collected for a single request-response cycle
+///
+/// All values are as returned by the `Clock` used by the server.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RequestTimings {
+    /// When the request headers were received and parsed
+    pub headers_received: u64,
+    /// When the handler was invoked
+    pub handler_start: u64,
+    /// When the head of the response was written to the socket, if a response was sent
+    /// with the connection at all (rather than by the handler, over the unbound or split socket)
+    pub first_byte_written: Option<u64>,
+    /// When the response was completed
+    pub completed: u64,
+}
+
+impl RequestTimings {
+    /// Return the time to first byte, measured from the moment the request headers were received
+    pub fn ttfb(&self) -> Option<u64> {
+        self.first_byte_written
+            .map(|first_byte_written| first_byte_written.saturating_sub(self.headers_received))
+    }
+
+    /// Return the duration of the complete request-response cycle, measured from the moment
+    /// the request headers were received
+    pub fn duration(&self) -> u64 {
+        self.completed.saturating_sub(self.headers_received)
+    }
+}
+
+/// A trait for observing completed request-response cycles
+///
+/// Useful for feeding an access log, or for collecting latency metrics.
+pub trait RequestObserver {
+    /// Called once a request-response cycle is complete
+    ///
+    /// Parameters:
+    /// - `task_id`: An identifier for the task which handled the request
+    /// - `timings`: The timestamps collected while handling the request
+    /// - `handler_ok`: Whether the handler completed successfully
+    fn complete(&self, task_id: impl Display + Copy, timings: &RequestTimings, handler_ok: bool);
+}
+
+impl<O> RequestObserver for &O
+where
+    O: RequestObserver,
+{
+    fn complete(&self, task_id: impl Display + Copy, timings: &RequestTimings, handler_ok: bool) {
+        (**self).complete(task_id, timings, handler_ok)
+    }
+}
+
+/// A clock that always returns 0, for when the requests are not timed
+pub struct NoClock;
+
+impl Clock for NoClock {
+    fn now(&self) -> u64 {
+        0
+    }
+}
+
+/// A no-op observer
+pub struct NoObserver;
+
+impl RequestObserver for NoObserver {
+    fn complete(
+        &self,
+        _task_id: impl Display + Copy,
+        _timings: &RequestTimings,
+        _handler_ok: bool,
+    ) {
+    }
+}
+
 /// A convenience function to handle multiple HTTP requests over a single socket stream,
 /// using the specified handler.
 ///
@@ -442,7 +879,8 @@ where
 /// A note on timeouts:
 /// - The function does NOT - by default - establish any timeouts on the IO operations _except_
 ///   an optional timeout for detecting idle connections, so that they can be closed and thus make
-///   the server available for accepting new connections.
+///   the server available for accepting new connections, and a timeout on the closing of the connection,
+///   after which the connection is aborted.
 ///   It is up to the caller to wrap the acceptor type with `edge_nal::WithTimeout` to establish
 ///   timeouts on the socket produced by the acceptor.
 /// - Similarly, the server does NOT establish any timeouts on the complete request-response cycle.
@@ -458,14 +896,52 @@ where
 /// - `task_id`: An identifier for the task, used for logging purposes
 /// - `handler`: An implementation of `Handler` to handle incoming requests
 pub async fn handle_connection<H, T, const N: usize>(
+    io: T,
+    buf: &mut [u8],
+    keepalive_timeout_ms: Option<u32>,
+    task_id: impl Display + Copy,
+    handler: H,
+) where
+    H: Handler,
+    T: Read + WriteVectored + Readable + TcpSplit + TcpShutdown,
+{
+    handle_connection_observed::<_, _, _, _, N>(
+        io,
+        buf,
+        keepalive_timeout_ms,
+        task_id,
+        handler,
+        NoClock,
+        NoObserver,
+    )
+    .await
+}
+
+/// Same as `handle_connection`, but additionally times each request-response cycle
+/// with the provided clock and reports the timings to the provided observer.
+///
+/// Parameters:
+/// - `io`: A socket stream
+/// - `buf`: A work-area buffer used by the implementation
+/// - `keepalive_timeout_ms`: An optional timeout in milliseconds for detecting an idle keepalive connection
+///   that should be closed. If not provided, the server will not close idle connections.
+/// - `task_id`: An identifier for the task, used for logging purposes
+/// - `handler`: An implementation of `Handler` to handle incoming requests
+/// - `clock`: An implementation of `Clock` used for timing the requests
+/// - `observer`: An implementation of `RequestObserver` which receives the timings of each request
+pub async fn handle_connection_observed<H, T, C, O, const N: usize>(
     mut io: T,
     buf: &mut [u8],
     keepalive_timeout_ms: Option<u32>,
     task_id: impl Display + Copy,
     handler: H,
+    clock: C,
+    observer: O,
 ) where
     H: Handler,
-    T: Read + Write + Readable + TcpSplit + TcpShutdown,
+    T: Read + WriteVectored + Readable + TcpSplit + TcpShutdown,
+    C: Clock,
+    O: RequestObserver,
 {
     let close = loop {
         debug!("Handler task {task_id}: Waiting for a new request");
@@ -485,7 +961,10 @@ pub async fn handle_connection<H, T, const N: usize>(
             }
         }
 
-        let result = handle_request::<_, _, N>(buf, &mut io, task_id, &handler).await;
+        let result = handle_request_observed::<_, _, _, _, N>(
+            buf, &mut io, task_id, &handler, &clock, &observer,
+        )
+        .await;
 
         match result {
             Err(HandlerError::Connection(Error::ConnectionClosed)) => {
@@ -508,8 +987,10 @@ pub async fn handle_connection<H, T, const N: usize>(
     };
 
     if close {
-        if let Err(e) = io.close(Close::Both).await {
-            warn!("Handler task {task_id}: Error when closing the socket: {e:?}");
+        match io.close_with_timeout(CLOSE_TIMEOUT_MS).await {
+            Ok(true) => (),
+            Ok(false) => warn!("Handler task {task_id}: Timeout when closing the socket, aborted"),
+            Err(e) => warn!("Handler task {task_id}: Error when closing the socket: {e:?}"),
         }
     } else {
         let _ = io.abort().await;
@@ -586,12 +1067,55 @@ pub async fn handle_request<H, T, const N: usize>(
 ) -> Result<bool, HandlerError<T::Error, H::Error<T::Error>>>
 where
     H: Handler,
-    T: Read + Write + TcpSplit,
+    T: Read + WriteVectored + TcpSplit,
 {
-    let mut connection = Connection::<_, N>::new(buf, io).await?;
+    handle_request_observed::<_, _, _, _, N>(buf, io, task_id, handler, NoClock, NoObserver).await
+}
+
+/// Same as `handle_request`, but additionally times the request-response cycle
+/// with the provided clock and reports the timings to the provided observer.
+///
+/// The timings are reported only if the request was received and the response was completed
+/// without connection errors.
+///
+/// Parameters:
+/// - `buf`: A work-area buffer used by the implementation
+/// - `io`: A socket stream
+/// - `task_id`: An identifier for the task, used for logging purposes
+/// - `handler`: An implementation of `Handler` to handle incoming requests
+/// - `clock`: An implementation of `Clock` used for timing the request
+/// - `observer`: An implementation of `RequestObserver` which receives the timings of the request
+pub async fn handle_request_observed<H, T, C, O, const N: usize>(
+    buf: &mut [u8],
+    io: T,
+    task_id: impl Display + Copy,
+    handler: H,
+    clock: C,
+    observer: O,
+) -> Result<bool, HandlerError<T::Error, H::Error<T::Error>>>
+where
+    H: Handler,
+    T: Read + WriteVectored + TcpSplit,
+    C: Clock,
+    O: RequestObserver,
+{
+    let headers_received = Cell::new(0);
+    let first_byte_written = Cell::new(None);
+
+    let timer = ResponseTimer {
+        clock: &clock,
+        headers_received: &headers_received,
+        first_byte_written: &first_byte_written,
+    };
+
+    let mut connection = Connection::<_, N>::new_timed(buf, io, Some(timer)).await?;
+
+    let handler_start = clock.now();
 
     let result = handler.handle(task_id, &mut connection).await;
 
+    let handler_ok = result.is_ok();
+
     match result {
         Result::Ok(_) => connection.complete().await?,
         Result::Err(e) => connection
@@ -600,6 +1124,15 @@ where
             .map_err(|_| HandlerError::Handler(e))?,
     }
 
+    let timings = RequestTimings {
+        headers_received: headers_received.get(),
+        handler_start,
+        first_byte_written: first_byte_written.get(),
+        completed: clock.now(),
+    };
+
+    observer.complete(task_id, &timings, handler_ok);
+
     Ok(connection.needs_close())
 }
 
@@ -657,6 +1190,36 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
     where
         A: edge_nal::TcpAccept,
         H: Handler,
+    {
+        self.run_observed(keepalive_timeout_ms, acceptor, handler, NoClock, NoObserver)
+            .await
+    }
+
+    /// Same as `run`, but additionally times each request-response cycle
+    /// with the provided clock and reports the timings to the provided observer.
+    ///
+    /// Parameters:
+    /// - `keepalive_timeout_ms`: An optional timeout in milliseconds for detecting an idle keepalive
+    ///   connection that should be closed.
+    /// - `acceptor`: An implementation of `edge_nal::TcpAccept` to accept incoming connections
+    /// - `handler`: An implementation of `Handler` to handle incoming requests
+    /// - `clock`: An implementation of `Clock` used for timing the requests
+    /// - `observer`: An implementation of `RequestObserver` which receives the timings of each request
+    #[inline(never)]
+    #[cold]
+    pub async fn run_observed<A, H, C, O>(
+        &mut self,
+        keepalive_timeout_ms: Option<u32>,
+        acceptor: A,
+        handler: H,
+        clock: C,
+        observer: O,
+    ) -> Result<(), Error<A::Error>>
+    where
+        A: edge_nal::TcpAccept,
+        H: Handler,
+        C: Clock,
+        O: RequestObserver,
     {
         let mutex = Mutex::<NoopRawMutex, _>::new(());
         let mut tasks = heapless::Vec::<_, P>::new();
@@ -671,6 +1234,8 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
             let acceptor = &acceptor;
             let task_id = index;
             let handler = &handler;
+            let clock = &clock;
+            let observer = &observer;
             let buf: *mut [u8; B] = &mut unsafe { self.0.assume_init_mut() }[index];
 
             tasks
@@ -686,12 +1251,14 @@ impl<const P: usize, const B: usize, const N: usize> Server<P, B, N> {
 
                         debug!("Handler task {task_id}: Got connection request");
 
-                        handle_connection::<_, _, N>(
+                        handle_connection_observed::<_, _, _, _, N>(
                             io,
                             unsafe { buf.as_mut() }.unwrap(),
                             keepalive_timeout_ms,
                             task_id,
                             handler,
+                            clock,
+                            observer,
                         )
                         .await;
                     }
@@ -716,7 +1283,9 @@ impl<const P: usize, const B: usize, const N: usize> Default for Server<P, B, N>
 
 #[cfg(feature = "embedded-svc")]
 mod embedded_svc_compat {
-    use embedded_io_async::{Read, Write};
+    use edge_nal::WriteVectored;
+
+    use embedded_io_async::Read;
 
     use embedded_svc::http::server::asynch::{Connection, Headers, Query};
 
@@ -725,7 +1294,7 @@ mod embedded_svc_compat {
 
     impl<T, const N: usize> Headers for super::Connection<'_, T, N>
     where
-        T: Read + Write,
+        T: Read + WriteVectored,
     {
         fn header(&self, name: &str) -> Option<&'_ str> {
             self.request_ref()
@@ -737,7 +1306,7 @@ mod embedded_svc_compat {
 
     impl<T, const N: usize> Query for super::Connection<'_, T, N>
     where
-        T: Read + Write,
+        T: Read + WriteVectored,
     {
         fn uri(&self) -> &'_ str {
             self.request_ref()
@@ -756,7 +1325,7 @@ mod embedded_svc_compat {
 
     impl<'b, T, const N: usize> Connection for super::Connection<'b, T, N>
     where
-        T: Read + Write,
+        T: Read + WriteVectored,
     {
         type Headers = RequestHeaders<'b, N>;
 
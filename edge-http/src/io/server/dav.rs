@@ -0,0 +1,981 @@
+//! A WebDAV (RFC 4918) handler, exposing a `DavFilesystem` to the native file browsers of the OSes
+//!
+//! The supported subset (class 1 and a no-op class 2) is what the built-in clients of Windows, macOS
+//! and Linux (GVfs, KIO) need for browsing, uploading, downloading, renaming and deleting files.
+
+use core::fmt::{self, Debug, Display, Write as _};
+use core::str;
+
+use edge_nal::{TcpSplit, WriteVectored};
+
+use embedded_io_async::{Read, Write};
+
+use log::{debug, warn};
+
+use crate::dav::{
+    self, Depth, ETag, HttpDate, LockDiscovery, LockToken, Metadata, PropPatchResponse,
+    PropResponse, FINITE_DEPTH_ERROR, MAX_PATH_LEN, MULTISTATUS_END, MULTISTATUS_START,
+};
+use crate::io::Error;
+use crate::Method;
+
+use super::{BodyStreamer, Connection, Handler, HandlerError};
+
+/// The size of the buffer used for transferring file data and composing the XML bodies
+const BUF_SIZE: usize = 1024;
+
+/// The timeout reported for the locks granted by `DavHandler`
+const LOCK_TIMEOUT_SECS: u32 = 3600;
+
+const ALLOW: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+
+const CONTENT_TYPE_XML: &str = "application/xml; charset=utf-8";
+
+/// A filesystem served by `DavHandler`
+///
+/// All paths are decoded and absolute within the filesystem: the root directory is `/`,
+/// and all other paths have no trailing slash, i.e. `/docs/readme.txt`.
+pub trait DavFilesystem {
+    type Error: Debug;
+
+    /// Return the metadata of the entry at `path`, or `None` if there is no such entry
+    async fn metadata(&self, path: &str) -> Result<Option<Metadata>, Self::Error>;
+
+    /// Write the name of the entry number `index` of the directory at `path` at the start of `buf`,
+    /// returning the length of the name and the metadata of the entry, or `None` if the directory has fewer entries
+    ///
+    /// The order of the entries should not change between the calls listing the same directory.
+    async fn read_dir(
+        &self,
+        path: &str,
+        index: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<(usize, Metadata)>, Self::Error>;
+
+    /// Read the data of the file at `path` starting at `offset` into `buf`,
+    /// returning the number of bytes read; `0` at the end of the file
+    async fn read(&self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error>;
+
+    /// Write `data` into the file at `path` starting at `offset`
+    ///
+    /// A write at offset `0` creates the file, or truncates it if it already exists.
+    async fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Create the directory at `path`; its parent directory is known to exist
+    async fn create_dir(&self, path: &str) -> Result<(), Self::Error>;
+
+    /// Remove the file or the directory at `path`; directories are removed with all their contents
+    async fn remove(&self, path: &str) -> Result<(), Self::Error>;
+
+    /// Rename (move) the file or the directory at `from` to `to`
+    ///
+    /// `to` is known not to exist, and its parent directory is known to exist.
+    async fn rename(&self, from: &str, to: &str) -> Result<(), Self::Error>;
+}
+
+impl<F> DavFilesystem for &F
+where
+    F: DavFilesystem,
+{
+    type Error = F::Error;
+
+    async fn metadata(&self, path: &str) -> Result<Option<Metadata>, Self::Error> {
+        (**self).metadata(path).await
+    }
+
+    async fn read_dir(
+        &self,
+        path: &str,
+        index: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<(usize, Metadata)>, Self::Error> {
+        (**self).read_dir(path, index, buf).await
+    }
+
+    async fn read(&self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        (**self).read(path, offset, buf).await
+    }
+
+    async fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<(), Self::Error> {
+        (**self).write(path, offset, data).await
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<(), Self::Error> {
+        (**self).create_dir(path).await
+    }
+
+    async fn remove(&self, path: &str) -> Result<(), Self::Error> {
+        (**self).remove(path).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), Self::Error> {
+        (**self).rename(from, to).await
+    }
+}
+
+/// A handler serving a `DavFilesystem` over WebDAV
+///
+/// Notes:
+/// - `PROPFIND` requests with `Depth: infinity` are refused, as RFC 4918 allows; the clients fall back to `Depth: 1`
+/// - Locks are granted but not enforced, because most clients do not write to servers which do not support locking
+/// - `PROPPATCH` requests are acknowledged without storing the properties
+/// - `COPY` is supported for files only
+///
+/// The handler expects the paths of all requests to start with its prefix; use `Matching` and `PathPrefix`
+/// to serve the filesystem next to other content.
+pub struct DavHandler<'a, F> {
+    prefix: &'a str,
+    fs: F,
+    rand: fn(&mut [u8]),
+}
+
+impl<'a, F> DavHandler<'a, F> {
+    /// Create a new WebDAV handler
+    ///
+    /// Parameters:
+    /// - `prefix`: The path the filesystem is served at without a trailing slash, i.e. `/dav`;
+    ///   empty for serving the filesystem at the root
+    /// - `fs`: The served filesystem
+    /// - `rand`: A function filling a buffer with random bytes, used for generating the lock tokens
+    pub const fn new(prefix: &'a str, fs: F, rand: fn(&mut [u8])) -> Self {
+        Self { prefix, fs, rand }
+    }
+}
+
+impl<F> DavHandler<'_, F>
+where
+    F: DavFilesystem,
+{
+    async fn propfind<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+        depth: Depth,
+        child_buf: &mut [u8],
+        buf: &mut [u8],
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        if depth == Depth::Infinity {
+            respond_with(
+                connection,
+                403,
+                "Forbidden",
+                CONTENT_TYPE_XML,
+                FINITE_DEPTH_ERROR.as_bytes(),
+            )
+            .await?;
+
+            return Ok(());
+        }
+
+        let Some(metadata) = self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?
+        else {
+            return Ok(respond(connection, 404, "Not Found").await?);
+        };
+
+        connection
+            .stream_response(
+                207,
+                Some("Multi-Status"),
+                &[("Content-Type", CONTENT_TYPE_XML)],
+                PropfindBody {
+                    fs: &self.fs,
+                    prefix: self.prefix,
+                    path,
+                    metadata,
+                    depth,
+                    child_buf,
+                    buf,
+                },
+            )
+            .await
+            .map_err(flatten)
+    }
+
+    async fn proppatch<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+        buf: &mut [u8],
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        let Some(metadata) = self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?
+        else {
+            return Ok(respond(connection, 404, "Not Found").await?);
+        };
+
+        let response = PropPatchResponse {
+            prefix: self.prefix,
+            path,
+            dir: metadata.dir,
+        };
+
+        let Some(body) = format(
+            buf,
+            format_args!("{MULTISTATUS_START}{response}{MULTISTATUS_END}"),
+        ) else {
+            return Ok(respond(connection, 414, "URI Too Long").await?);
+        };
+
+        respond_with(connection, 207, "Multi-Status", CONTENT_TYPE_XML, body).await?;
+
+        Ok(())
+    }
+
+    async fn get<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+        head: bool,
+        buf: &mut [u8],
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        let metadata = match self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?
+        {
+            Some(metadata) if metadata.dir => {
+                return Ok(respond(connection, 405, "Method Not Allowed").await?)
+            }
+            Some(metadata) => metadata,
+            None => return Ok(respond(connection, 404, "Not Found").await?),
+        };
+
+        let mut len = heapless::String::<20>::new();
+        let mut etag = heapless::String::<40>::new();
+        let mut modified = heapless::String::<32>::new();
+
+        write!(len, "{}", metadata.len).unwrap();
+        write!(etag, "{}", ETag(&metadata)).unwrap();
+
+        if let Some(secs) = metadata.modified {
+            write!(modified, "{}", HttpDate(secs)).unwrap();
+        }
+
+        let headers = [
+            ("Content-Type", "application/octet-stream"),
+            ("Content-Length", len.as_str()),
+            ("ETag", etag.as_str()),
+            if modified.is_empty() {
+                ("", "")
+            } else {
+                ("Last-Modified", modified.as_str())
+            },
+        ];
+
+        if head {
+            connection
+                .initiate_response(200, Some("OK"), &headers)
+                .await?;
+
+            return Ok(());
+        }
+
+        connection
+            .stream_response(
+                200,
+                Some("OK"),
+                &headers,
+                FileBody {
+                    fs: &self.fs,
+                    path,
+                    len: metadata.len,
+                    buf,
+                },
+            )
+            .await
+            .map_err(flatten)
+    }
+
+    async fn put<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+        buf: &mut [u8],
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        let existing = self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?;
+
+        if existing.is_some_and(|metadata| metadata.dir) {
+            return Ok(respond(connection, 405, "Method Not Allowed").await?);
+        }
+
+        if !self.is_parent_dir(path).await? {
+            return Ok(respond(connection, 409, "Conflict").await?);
+        }
+
+        let mut offset = 0;
+
+        loop {
+            let len = connection.read(buf).await?;
+
+            // Write even an empty body, so that the file is created
+            if len > 0 || offset == 0 {
+                self.fs
+                    .write(path, offset, &buf[..len])
+                    .await
+                    .map_err(HandlerError::Handler)?;
+            }
+
+            if len == 0 {
+                break;
+            }
+
+            offset += len as u64;
+        }
+
+        if existing.is_some() {
+            respond(connection, 204, "No Content").await?;
+        } else {
+            respond(connection, 201, "Created").await?;
+        }
+
+        Ok(())
+    }
+
+    async fn delete<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        if path == "/" {
+            return Ok(respond(connection, 403, "Forbidden").await?);
+        }
+
+        if self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?
+            .is_none()
+        {
+            return Ok(respond(connection, 404, "Not Found").await?);
+        }
+
+        self.fs.remove(path).await.map_err(HandlerError::Handler)?;
+
+        Ok(respond(connection, 204, "No Content").await?)
+    }
+
+    async fn mkcol<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+        buf: &mut [u8],
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        if self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?
+            .is_some()
+        {
+            return Ok(respond(connection, 405, "Method Not Allowed").await?);
+        }
+
+        if !self.is_parent_dir(path).await? {
+            return Ok(respond(connection, 409, "Conflict").await?);
+        }
+
+        // MKCOL request bodies are not defined by RFC 4918
+        if connection.read(&mut buf[..1]).await? > 0 {
+            return Ok(respond(connection, 415, "Unsupported Media Type").await?);
+        }
+
+        self.fs
+            .create_dir(path)
+            .await
+            .map_err(HandlerError::Handler)?;
+
+        Ok(respond(connection, 201, "Created").await?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_move<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+        destination: &str,
+        overwrite: bool,
+        move_: bool,
+        buf: &mut [u8],
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        let Some(metadata) = self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?
+        else {
+            return Ok(respond(connection, 404, "Not Found").await?);
+        };
+
+        // Neither the root, nor a directory into itself can be moved or copied
+        let into_itself = destination
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+
+        if path == "/" || destination == "/" || into_itself || (!move_ && metadata.dir) {
+            return Ok(respond(connection, 403, "Forbidden").await?);
+        }
+
+        let existing = self
+            .fs
+            .metadata(destination)
+            .await
+            .map_err(HandlerError::Handler)?;
+
+        if existing.is_some() {
+            if !overwrite {
+                return Ok(respond(connection, 412, "Precondition Failed").await?);
+            }
+
+            self.fs
+                .remove(destination)
+                .await
+                .map_err(HandlerError::Handler)?;
+        }
+
+        if !self.is_parent_dir(destination).await? {
+            return Ok(respond(connection, 409, "Conflict").await?);
+        }
+
+        if move_ {
+            self.fs
+                .rename(path, destination)
+                .await
+                .map_err(HandlerError::Handler)?;
+        } else {
+            let mut offset = 0;
+
+            loop {
+                let len = self
+                    .fs
+                    .read(path, offset, buf)
+                    .await
+                    .map_err(HandlerError::Handler)?;
+
+                if len > 0 || offset == 0 {
+                    self.fs
+                        .write(destination, offset, &buf[..len])
+                        .await
+                        .map_err(HandlerError::Handler)?;
+                }
+
+                if len == 0 {
+                    break;
+                }
+
+                offset += len as u64;
+            }
+        }
+
+        if existing.is_some() {
+            respond(connection, 204, "No Content").await?;
+        } else {
+            respond(connection, 201, "Created").await?;
+        }
+
+        Ok(())
+    }
+
+    async fn lock<T, const N: usize>(
+        &self,
+        connection: &mut Connection<'_, T, N>,
+        path: &str,
+        depth: Depth,
+        buf: &mut [u8],
+    ) -> Result<(), HandlerError<T::Error, F::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        // Locking an unmapped path creates an empty file, which is what clients do before uploading a new file
+        let created = if self
+            .fs
+            .metadata(path)
+            .await
+            .map_err(HandlerError::Handler)?
+            .is_none()
+        {
+            if !self.is_parent_dir(path).await? {
+                return Ok(respond(connection, 409, "Conflict").await?);
+            }
+
+            self.fs
+                .write(path, 0, &[])
+                .await
+                .map_err(HandlerError::Handler)?;
+
+            true
+        } else {
+            false
+        };
+
+        let mut uuid = [0; 16];
+        (self.rand)(&mut uuid);
+
+        let mut token = heapless::String::<64>::new();
+        write!(token, "{}", LockToken(uuid)).unwrap();
+
+        let mut token_header = heapless::String::<66>::new();
+        write!(token_header, "<{token}>").unwrap();
+
+        let discovery = LockDiscovery {
+            token: &token,
+            depth: if depth == Depth::Zero {
+                Depth::Zero
+            } else {
+                Depth::Infinity
+            },
+            timeout: LOCK_TIMEOUT_SECS,
+        };
+
+        let Some(body) = format(buf, discovery) else {
+            return Ok(respond(connection, 500, "Internal Error").await?);
+        };
+
+        let mut len = heapless::String::<20>::new();
+        write!(len, "{}", body.len()).unwrap();
+
+        let (status, message) = if created {
+            (201, "Created")
+        } else {
+            (200, "OK")
+        };
+
+        connection
+            .initiate_response(
+                status,
+                Some(message),
+                &[
+                    ("Content-Type", CONTENT_TYPE_XML),
+                    ("Content-Length", &len),
+                    ("Lock-Token", &token_header),
+                ],
+            )
+            .await?;
+
+        connection.write_all(body).await?;
+
+        Ok(())
+    }
+
+    async fn is_parent_dir<E>(&self, path: &str) -> Result<bool, HandlerError<E, F::Error>> {
+        let Some(parent) = dav::parent(path) else {
+            return Ok(false);
+        };
+
+        Ok(self
+            .fs
+            .metadata(parent)
+            .await
+            .map_err(HandlerError::Handler)?
+            .is_some_and(|metadata| metadata.dir))
+    }
+}
+
+impl<F> Handler for DavHandler<'_, F>
+where
+    F: DavFilesystem,
+{
+    type Error<E>
+        = HandlerError<E, F::Error>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        _task_id: impl Display + Copy,
+        connection: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + WriteVectored + TcpSplit,
+    {
+        let mut path_buf = [0; MAX_PATH_LEN];
+        let mut aux_buf = [0; MAX_PATH_LEN];
+        let mut buf = [0; BUF_SIZE];
+
+        let headers = connection.headers()?;
+        let method = headers.method;
+
+        debug!("WebDAV request: {method} {}", headers.path);
+
+        let Some(path) = headers
+            .path
+            .strip_prefix(self.prefix)
+            .and_then(|path| dav::decode_path(path, &mut path_buf))
+        else {
+            return Ok(respond(connection, 404, "Not Found").await?);
+        };
+
+        let depth = match headers.headers.get("Depth").map(Depth::parse) {
+            Some(Some(depth)) => Some(depth),
+            Some(None) => return Ok(respond(connection, 400, "Bad Request").await?),
+            None => None,
+        };
+
+        let overwrite = !headers
+            .headers
+            .get("Overwrite")
+            .is_some_and(|overwrite| overwrite.trim().eq_ignore_ascii_case("F"));
+
+        // `None` if there is no `Destination` header, `Some(None)` if it points outside of the filesystem
+        let destination = headers.headers.get("Destination").map(|destination| {
+            dav::destination_path(destination)
+                .strip_prefix(self.prefix)
+                .and_then(|destination| dav::decode_path(destination, &mut aux_buf))
+        });
+
+        match method {
+            Method::Options => {
+                connection
+                    .initiate_response(
+                        200,
+                        Some("OK"),
+                        &[
+                            ("DAV", "1, 2"),
+                            ("MS-Author-Via", "DAV"),
+                            ("Allow", ALLOW),
+                            ("Content-Length", "0"),
+                        ],
+                    )
+                    .await?
+            }
+            Method::Propfind => {
+                self.propfind(
+                    connection,
+                    path,
+                    depth.unwrap_or(Depth::Infinity),
+                    &mut aux_buf,
+                    &mut buf,
+                )
+                .await?
+            }
+            Method::Proppatch => self.proppatch(connection, path, &mut buf).await?,
+            Method::Get | Method::Head => {
+                self.get(connection, path, method == Method::Head, &mut buf)
+                    .await?
+            }
+            Method::Put => self.put(connection, path, &mut buf).await?,
+            Method::Delete => self.delete(connection, path).await?,
+            Method::MkCol => self.mkcol(connection, path, &mut buf).await?,
+            Method::Copy | Method::Move => match destination {
+                Some(Some(destination)) => {
+                    self.copy_move(
+                        connection,
+                        path,
+                        destination,
+                        overwrite,
+                        method == Method::Move,
+                        &mut buf,
+                    )
+                    .await?
+                }
+                Some(None) => respond(connection, 502, "Bad Gateway").await?,
+                None => respond(connection, 400, "Bad Request").await?,
+            },
+            Method::Lock => {
+                self.lock(connection, path, depth.unwrap_or(Depth::Infinity), &mut buf)
+                    .await?
+            }
+            Method::Unlock => respond(connection, 204, "No Content").await?,
+            _ => {
+                connection
+                    .initiate_response(
+                        405,
+                        Some("Method Not Allowed"),
+                        &[("Allow", ALLOW), ("Content-Length", "0")],
+                    )
+                    .await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The error of the body streamers, which is flattened into the error of the handler
+#[derive(Debug)]
+enum StreamError<E, F> {
+    Io(E),
+    Fs(F),
+}
+
+fn flatten<E, F>(error: HandlerError<E, StreamError<Error<E>, F>>) -> HandlerError<E, F> {
+    match error {
+        HandlerError::Io(e) => HandlerError::Io(e),
+        HandlerError::Connection(e) | HandlerError::Handler(StreamError::Io(e)) => {
+            HandlerError::Connection(e)
+        }
+        HandlerError::Handler(StreamError::Fs(e)) => HandlerError::Handler(e),
+    }
+}
+
+/// The body of a `207 Multi-Status` response to a `PROPFIND` request
+struct PropfindBody<'a, F> {
+    fs: &'a F,
+    prefix: &'a str,
+    path: &'a str,
+    metadata: Metadata,
+    depth: Depth,
+    child_buf: &'a mut [u8],
+    buf: &'a mut [u8],
+}
+
+impl<F> PropfindBody<'_, F> {
+    async fn write_response<W>(
+        body: &mut W,
+        buf: &mut [u8],
+        response: PropResponse<'_>,
+    ) -> Result<(), W::Error>
+    where
+        W: Write,
+    {
+        if let Some(xml) = format(buf, &response) {
+            body.write_all(xml).await
+        } else {
+            warn!("Skipping {}: too long", response.path);
+
+            Ok(())
+        }
+    }
+}
+
+impl<F> BodyStreamer for PropfindBody<'_, F>
+where
+    F: DavFilesystem,
+{
+    type Error<E>
+        = StreamError<E, F::Error>
+    where
+        E: Debug;
+
+    async fn stream<W>(&mut self, body: &mut W) -> Result<(), Self::Error<W::Error>>
+    where
+        W: Write,
+    {
+        body.write_all(MULTISTATUS_START.as_bytes())
+            .await
+            .map_err(StreamError::Io)?;
+
+        Self::write_response(
+            body,
+            self.buf,
+            PropResponse {
+                prefix: self.prefix,
+                path: self.path,
+                metadata: &self.metadata,
+            },
+        )
+        .await
+        .map_err(StreamError::Io)?;
+
+        if self.metadata.dir && self.depth == Depth::One {
+            // The paths of the children are composed in `child_buf`, with the names read right after their parent path
+            let parent_len = if self.path == "/" {
+                1
+            } else {
+                self.path.len() + 1
+            };
+
+            if parent_len < self.child_buf.len() {
+                self.child_buf[..parent_len - 1]
+                    .copy_from_slice(&self.path.as_bytes()[..parent_len - 1]);
+                self.child_buf[parent_len - 1] = b'/';
+
+                let mut index = 0;
+
+                while let Some((name_len, metadata)) = self
+                    .fs
+                    .read_dir(self.path, index, &mut self.child_buf[parent_len..])
+                    .await
+                    .map_err(StreamError::Fs)?
+                {
+                    index += 1;
+
+                    let path = self
+                        .child_buf
+                        .get(..parent_len + name_len)
+                        .and_then(|path| str::from_utf8(path).ok())
+                        .filter(|path| {
+                            let name = &path[parent_len..];
+
+                            !name.is_empty() && !name.contains('/')
+                        });
+
+                    let Some(path) = path else {
+                        warn!("Skipping an entry of {}: invalid name", self.path);
+                        continue;
+                    };
+
+                    Self::write_response(
+                        body,
+                        self.buf,
+                        PropResponse {
+                            prefix: self.prefix,
+                            path,
+                            metadata: &metadata,
+                        },
+                    )
+                    .await
+                    .map_err(StreamError::Io)?;
+                }
+            } else {
+                warn!("Not listing {}: path too long", self.path);
+            }
+        }
+
+        body.write_all(MULTISTATUS_END.as_bytes())
+            .await
+            .map_err(StreamError::Io)
+    }
+}
+
+/// The body of a `GET` response
+struct FileBody<'a, F> {
+    fs: &'a F,
+    path: &'a str,
+    len: u64,
+    buf: &'a mut [u8],
+}
+
+impl<F> BodyStreamer for FileBody<'_, F>
+where
+    F: DavFilesystem,
+{
+    type Error<E>
+        = StreamError<E, F::Error>
+    where
+        E: Debug;
+
+    async fn stream<W>(&mut self, body: &mut W) -> Result<(), Self::Error<W::Error>>
+    where
+        W: Write,
+    {
+        let mut offset = 0;
+
+        while offset < self.len {
+            let max_len = (self.len - offset).min(self.buf.len() as u64) as usize;
+
+            let len = self
+                .fs
+                .read(self.path, offset, &mut self.buf[..max_len])
+                .await
+                .map_err(StreamError::Fs)?;
+
+            if len == 0 {
+                // The file was truncated in the meantime
+                break;
+            }
+
+            body.write_all(&self.buf[..len])
+                .await
+                .map_err(StreamError::Io)?;
+
+            offset += len as u64;
+        }
+
+        Ok(())
+    }
+}
+
+async fn respond<T, const N: usize>(
+    connection: &mut Connection<'_, T, N>,
+    status: u16,
+    message: &str,
+) -> Result<(), Error<T::Error>>
+where
+    T: Read + WriteVectored,
+{
+    connection
+        .initiate_response(status, Some(message), &[("Content-Length", "0")])
+        .await
+}
+
+async fn respond_with<T, const N: usize>(
+    connection: &mut Connection<'_, T, N>,
+    status: u16,
+    message: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), Error<T::Error>>
+where
+    T: Read + WriteVectored,
+{
+    let mut len = heapless::String::<20>::new();
+    write!(len, "{}", body.len()).unwrap();
+
+    connection
+        .initiate_response(
+            status,
+            Some(message),
+            &[("Content-Type", content_type), ("Content-Length", &len)],
+        )
+        .await?;
+
+    connection.write_all(body).await
+}
+
+/// Format `value` into `buf`, returning `None` if it does not fit
+fn format(buf: &mut [u8], value: impl Display) -> Option<&[u8]> {
+    struct Cursor<'a> {
+        buf: &'a mut [u8],
+        len: usize,
+    }
+
+    impl fmt::Write for Cursor<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let dest = self
+                .buf
+                .get_mut(self.len..self.len + s.len())
+                .ok_or(fmt::Error)?;
+
+            dest.copy_from_slice(s.as_bytes());
+            self.len += s.len();
+
+            Ok(())
+        }
+    }
+
+    let mut cursor = Cursor {
+        buf: &mut *buf,
+        len: 0,
+    };
+
+    write!(cursor, "{value}").ok()?;
+
+    let len = cursor.len;
+
+    Some(&buf[..len])
+}
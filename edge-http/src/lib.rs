@@ -531,7 +531,7 @@ impl ConnectionType {
     }
 
     /// Create a raw header from the connection type
-    pub fn raw_header(&self) -> (&str, &[u8]) {
+    pub fn raw_header(&self) -> (&'static str, &'static [u8]) {
         let connection = match self {
             Self::KeepAlive => "Keep-Alive",
             Self::Close => "Close",
@@ -676,7 +676,10 @@ impl BodyType {
     /// If the body type is `Raw`, this method returns `None` as a raw body cannot be
     /// represented in a header and is rather, a consequence of using connection type `Close`
     /// with HTTP server responses
-    pub fn raw_header<'a>(&self, buf: &'a mut heapless::String<20>) -> Option<(&str, &'a [u8])> {
+    pub fn raw_header<'a>(
+        &self,
+        buf: &'a mut heapless::String<20>,
+    ) -> Option<(&'static str, &'a [u8])> {
         match self {
             Self::Chunked => Some(("Transfer-Encoding", "Chunked".as_bytes())),
             Self::ContentLen(len) => {
@@ -1040,9 +1043,409 @@ pub mod ws {
     }
 }
 
+/// WebDAV (RFC 4918) utilities
+pub mod dav {
+    use core::fmt::{self, Display};
+    use core::str;
+
+    /// The maximum length of a decoded resource path
+    pub const MAX_PATH_LEN: usize = 256;
+
+    /// The start of a `207 Multi-Status` response body, to be followed by the responses
+    /// of the individual resources (i.e. `PropResponse`) and `MULTISTATUS_END`
+    pub const MULTISTATUS_START: &str =
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><D:multistatus xmlns:D=\"DAV:\">";
+
+    /// The end of a `207 Multi-Status` response body
+    pub const MULTISTATUS_END: &str = "</D:multistatus>";
+
+    /// The body of a `403 Forbidden` response to a `PROPFIND` request with `Depth: infinity`
+    pub const FINITE_DEPTH_ERROR: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?><D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>";
+
+    /// The value of the `Depth` header of a WebDAV request
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+    pub enum Depth {
+        /// The resource only
+        Zero,
+        /// The resource and its immediate children
+        One,
+        /// The resource and all its descendants
+        Infinity,
+    }
+
+    impl Depth {
+        /// Parse the value of a `Depth` header
+        pub fn parse(value: &str) -> Option<Self> {
+            match value.trim() {
+                "0" => Some(Self::Zero),
+                "1" => Some(Self::One),
+                value if value.eq_ignore_ascii_case("infinity") => Some(Self::Infinity),
+                _ => None,
+            }
+        }
+
+        /// Return the value of the `Depth` header
+        pub const fn as_str(&self) -> &'static str {
+            match self {
+                Self::Zero => "0",
+                Self::One => "1",
+                Self::Infinity => "infinity",
+            }
+        }
+    }
+
+    impl Display for Depth {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.as_str())
+        }
+    }
+
+    /// The metadata of a file or a directory
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+    pub struct Metadata {
+        /// Whether the entry is a directory, i.e. a WebDAV collection
+        pub dir: bool,
+        /// The length of the file in bytes; ignored for directories
+        pub len: u64,
+        /// The time of the last modification in seconds since the Unix epoch, if known
+        pub modified: Option<u64>,
+    }
+
+    /// Decode the percent-encoded path of a request into `buf`
+    ///
+    /// The query (if any) and a trailing slash are dropped. An empty path is decoded as the root path `/`.
+    ///
+    /// Return `None` if the path is not absolute, does not fit in `buf` or is not valid UTF-8 once decoded.
+    /// Paths with empty, `.` or `..` segments are rejected as well, so that the decoded paths can never
+    /// escape the root of the served filesystem.
+    pub fn decode_path<'a>(path: &str, buf: &'a mut [u8]) -> Option<&'a str> {
+        let path = path.split('?').next().unwrap_or_default();
+        let path = if path.is_empty() { "/" } else { path };
+
+        if !path.starts_with('/') {
+            return None;
+        }
+
+        let mut bytes = path.bytes();
+        let mut len = 0;
+
+        while let Some(byte) = bytes.next() {
+            let byte = if byte == b'%' {
+                let high = hex_digit(bytes.next()?)?;
+                let low = hex_digit(bytes.next()?)?;
+
+                (high << 4) | low
+            } else {
+                byte
+            };
+
+            *buf.get_mut(len)? = byte;
+            len += 1;
+        }
+
+        if len > 1 && buf[len - 1] == b'/' {
+            len -= 1;
+        }
+
+        let path = str::from_utf8(&buf[..len]).ok()?;
+
+        let valid = path == "/"
+            || path[1..].split('/').all(|segment| {
+                !segment.is_empty()
+                    && segment != "."
+                    && segment != ".."
+                    && !segment.contains(['\\', '\0'])
+            });
+
+        valid.then_some(path)
+    }
+
+    fn hex_digit(digit: u8) -> Option<u8> {
+        (digit as char).to_digit(16).map(|digit| digit as u8)
+    }
+
+    /// Return the path of the URL in a `Destination` header, i.e. the URL without its scheme and authority
+    pub fn destination_path(destination: &str) -> &str {
+        let destination = destination.trim();
+
+        match destination.find("://") {
+            Some(index) => {
+                let rest = &destination[index + 3..];
+
+                rest.find('/').map(|index| &rest[index..]).unwrap_or("/")
+            }
+            None => destination,
+        }
+    }
+
+    /// Return the parent of a decoded path, or `None` for the root path
+    pub fn parent(path: &str) -> Option<&str> {
+        if path == "/" {
+            None
+        } else {
+            path.rfind('/')
+                .map(|index| if index == 0 { "/" } else { &path[..index] })
+        }
+    }
+
+    /// Return the last segment of a decoded path; empty for the root path
+    pub fn name(path: &str) -> &str {
+        path.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// A `Display` wrapper percent-encoding a decoded path for use in a URL
+    pub struct Href<'a>(pub &'a str);
+
+    impl Display for Href<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for byte in self.0.bytes() {
+                if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+                    write!(f, "{}", byte as char)?;
+                } else {
+                    write!(f, "%{byte:02X}")?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// A `Display` wrapper escaping a text for use in XML content
+    pub struct XmlEscaped<'a>(pub &'a str);
+
+    impl Display for XmlEscaped<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for ch in self.0.chars() {
+                match ch {
+                    '&' => write!(f, "&amp;")?,
+                    '<' => write!(f, "&lt;")?,
+                    '>' => write!(f, "&gt;")?,
+                    '"' => write!(f, "&quot;")?,
+                    '\'' => write!(f, "&apos;")?,
+                    ch => write!(f, "{ch}")?,
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// A `Display` wrapper formatting a time in seconds since the Unix epoch as an HTTP date,
+    /// i.e. `Sun, 06 Nov 1994 08:49:37 GMT`
+    pub struct HttpDate(pub u64);
+
+    impl Display for HttpDate {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+            const MONTHS: [&str; 12] = [
+                "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+            ];
+
+            let days = self.0 / 86400;
+            let secs = self.0 % 86400;
+
+            // Civil date from the days since the Unix epoch, as per http://howardhinnant.github.io/date_algorithms.html
+            let days_from_era = days + 719468;
+            let era = days_from_era / 146097;
+            let doe = days_from_era % 146097;
+            let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+            let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+            let mp = (5 * doy + 2) / 153;
+            let day = doy - (153 * mp + 2) / 5 + 1;
+            let month = if mp < 10 { mp + 3 } else { mp - 9 };
+            let year = yoe + era * 400 + u64::from(month <= 2);
+
+            write!(
+                f,
+                "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+                DAYS[(days % 7) as usize],
+                MONTHS[month as usize - 1],
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            )
+        }
+    }
+
+    /// A `Display` wrapper formatting the (quoted) entity tag of a file, derived from its length and modification time
+    pub struct ETag<'a>(pub &'a Metadata);
+
+    impl Display for ETag<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "\"{:x}-{:x}\"",
+                self.0.len,
+                self.0.modified.unwrap_or_default()
+            )
+        }
+    }
+
+    /// A `Display` wrapper formatting the `response` element of a resource in a `207 Multi-Status` response body
+    /// to a `PROPFIND` request
+    ///
+    /// All live properties of the resource are reported, regardless of the properties requested:
+    /// `displayname`, `resourcetype`, `supportedlock` and - if known - `getlastmodified`, as well as
+    /// `getcontentlength`, `getcontenttype` and `getetag` for files.
+    pub struct PropResponse<'a> {
+        /// The path the filesystem is served at, i.e. `/dav`; written as-is at the start of the `href` of the resource
+        pub prefix: &'a str,
+        /// The decoded path of the resource, relative to `prefix`
+        pub path: &'a str,
+        /// The metadata of the resource
+        pub metadata: &'a Metadata,
+    }
+
+    impl Display for PropResponse<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "<D:response>")?;
+            write_href(f, self.prefix, self.path, self.metadata.dir)?;
+            write!(f, "<D:propstat><D:prop>")?;
+
+            write!(
+                f,
+                "<D:displayname>{}</D:displayname>",
+                XmlEscaped(name(self.path))
+            )?;
+
+            if self.metadata.dir {
+                write!(f, "<D:resourcetype><D:collection/></D:resourcetype>")?;
+            } else {
+                write!(f, "<D:resourcetype/>")?;
+                write!(
+                    f,
+                    "<D:getcontentlength>{}</D:getcontentlength>",
+                    self.metadata.len
+                )?;
+                write!(
+                    f,
+                    "<D:getcontenttype>application/octet-stream</D:getcontenttype>"
+                )?;
+                write!(f, "<D:getetag>{}</D:getetag>", ETag(self.metadata))?;
+            }
+
+            if let Some(modified) = self.metadata.modified {
+                write!(
+                    f,
+                    "<D:getlastmodified>{}</D:getlastmodified>",
+                    HttpDate(modified)
+                )?;
+            }
+
+            write!(f, "<D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>")?;
+
+            write!(
+                f,
+                "</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>"
+            )
+        }
+    }
+
+    /// A `Display` wrapper formatting the `response` element of a resource in a `207 Multi-Status` response body
+    /// to a `PROPPATCH` request
+    ///
+    /// Dead properties are not supported, so the update is acknowledged without storing anything.
+    /// This keeps happy the clients which set their own properties (i.e. file times) after uploading a file.
+    pub struct PropPatchResponse<'a> {
+        /// The path the filesystem is served at; written as-is at the start of the `href` of the resource
+        pub prefix: &'a str,
+        /// The decoded path of the resource, relative to `prefix`
+        pub path: &'a str,
+        /// Whether the resource is a directory
+        pub dir: bool,
+    }
+
+    impl Display for PropPatchResponse<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "<D:response>")?;
+            write_href(f, self.prefix, self.path, self.dir)?;
+            write!(
+                f,
+                "<D:propstat><D:prop/><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>"
+            )
+        }
+    }
+
+    /// A `Display` wrapper formatting the body of a successful `LOCK` response,
+    /// i.e. the `lockdiscovery` property of the locked resource
+    pub struct LockDiscovery<'a> {
+        /// The lock token, i.e. `opaquelocktoken:<UUID>`
+        pub token: &'a str,
+        /// The depth of the lock
+        pub depth: Depth,
+        /// The timeout of the lock in seconds
+        pub timeout: u32,
+    }
+
+    impl Display for LockDiscovery<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?><D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>"
+            )?;
+            write!(
+                f,
+                "<D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>"
+            )?;
+            write!(
+                f,
+                "<D:depth>{}</D:depth><D:timeout>Second-{}</D:timeout>",
+                self.depth, self.timeout
+            )?;
+            write!(
+                f,
+                "<D:locktoken><D:href>{}</D:href></D:locktoken>",
+                XmlEscaped(self.token)
+            )?;
+            write!(f, "</D:activelock></D:lockdiscovery></D:prop>")
+        }
+    }
+
+    /// A `Display` wrapper formatting a lock token as an `opaquelocktoken` URI with a version 4 (random) UUID
+    ///
+    /// The bytes are expected to be random; the version and the variant bits of the UUID are set when formatting.
+    pub struct LockToken(pub [u8; 16]);
+
+    impl Display for LockToken {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut uuid = self.0;
+            uuid[6] = (uuid[6] & 0x0f) | 0x40;
+            uuid[8] = (uuid[8] & 0x3f) | 0x80;
+
+            write!(f, "opaquelocktoken:")?;
+
+            for (index, byte) in uuid.iter().enumerate() {
+                if matches!(index, 4 | 6 | 8 | 10) {
+                    write!(f, "-")?;
+                }
+
+                write!(f, "{byte:02x}")?;
+            }
+
+            Ok(())
+        }
+    }
+
+    fn write_href(f: &mut fmt::Formatter<'_>, prefix: &str, path: &str, dir: bool) -> fmt::Result {
+        write!(f, "<D:href>{}{}", XmlEscaped(prefix), Href(path))?;
+
+        if dir && path != "/" {
+            write!(f, "/")?;
+        }
+
+        write!(f, "</D:href>")
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use core::fmt::Write;
+
     use crate::{
+        dav::{
+            decode_path, destination_path, name, parent, Depth, Href, HttpDate, LockToken,
+            Metadata, PropResponse, MAX_PATH_LEN,
+        },
         ws::{sec_key_response, MAX_BASE64_KEY_RESPONSE_LEN},
         BodyType, ConnectionType,
     };
@@ -1323,6 +1726,117 @@ mod test {
             BodyType::Raw
         );
     }
+
+    #[test]
+    fn test_dav_decode_path() {
+        let mut buf = [0_u8; MAX_PATH_LEN];
+
+        assert_eq!(decode_path("", &mut buf), Some("/"));
+        assert_eq!(decode_path("/", &mut buf), Some("/"));
+        assert_eq!(decode_path("/a/b/", &mut buf), Some("/a/b"));
+        assert_eq!(
+            decode_path("/My%20Files/%C3%A4.txt?x=1", &mut buf),
+            Some("/My Files/ä.txt")
+        );
+
+        assert_eq!(decode_path("a", &mut buf), None);
+        assert_eq!(decode_path("/a//b", &mut buf), None);
+        assert_eq!(decode_path("/a/../b", &mut buf), None);
+        assert_eq!(decode_path("/a/%2E%2E/b", &mut buf), None);
+        assert_eq!(decode_path("/a%2", &mut buf), None);
+        assert_eq!(decode_path("/%FF", &mut buf), None);
+        assert_eq!(decode_path("/abc", &mut buf[..3]), None);
+    }
+
+    #[test]
+    fn test_dav_paths() {
+        assert_eq!(destination_path("http://host:80/dav/a%20b"), "/dav/a%20b");
+        assert_eq!(destination_path("https://host"), "/");
+        assert_eq!(destination_path("/dav/a"), "/dav/a");
+
+        assert_eq!(parent("/"), None);
+        assert_eq!(parent("/a"), Some("/"));
+        assert_eq!(parent("/a/b"), Some("/a"));
+
+        assert_eq!(name("/"), "");
+        assert_eq!(name("/a/b"), "b");
+
+        assert_eq!(Depth::parse("Infinity"), Some(Depth::Infinity));
+        assert_eq!(Depth::parse(" 1"), Some(Depth::One));
+        assert_eq!(Depth::parse("2"), None);
+    }
+
+    #[test]
+    fn test_dav_format() {
+        let mut out = heapless::String::<1024>::new();
+
+        write!(out, "{}", HttpDate(784111777)).unwrap();
+        assert_eq!(out, "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        out.clear();
+        write!(out, "{}", HttpDate(951782400)).unwrap();
+        assert_eq!(out, "Tue, 29 Feb 2000 00:00:00 GMT");
+
+        out.clear();
+        write!(out, "{}", Href("/a b/ä&.txt")).unwrap();
+        assert_eq!(out, "/a%20b/%C3%A4%26.txt");
+
+        out.clear();
+        write!(out, "{}", LockToken([0xff; 16])).unwrap();
+        assert_eq!(out, "opaquelocktoken:ffffffff-ffff-4fff-bfff-ffffffffffff");
+
+        let metadata = Metadata {
+            dir: true,
+            len: 0,
+            modified: None,
+        };
+
+        out.clear();
+        write!(
+            out,
+            "{}",
+            PropResponse {
+                prefix: "/dav",
+                path: "/a&b",
+                metadata: &metadata,
+            }
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "<D:response><D:href>/dav/a%26b/</D:href><D:propstat><D:prop>\
+            <D:displayname>a&amp;b</D:displayname><D:resourcetype><D:collection/></D:resourcetype>\
+            <D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>\
+            </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>"
+        );
+
+        let metadata = Metadata {
+            dir: false,
+            len: 5,
+            modified: Some(784111777),
+        };
+
+        out.clear();
+        write!(
+            out,
+            "{}",
+            PropResponse {
+                prefix: "",
+                path: "/f",
+                metadata: &metadata,
+            }
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "<D:response><D:href>/f</D:href><D:propstat><D:prop>\
+            <D:displayname>f</D:displayname><D:resourcetype/><D:getcontentlength>5</D:getcontentlength>\
+            <D:getcontenttype>application/octet-stream</D:getcontenttype><D:getetag>\"5-2ebc98a1\"</D:getetag>\
+            <D:getlastmodified>Sun, 06 Nov 1994 08:49:37 GMT</D:getlastmodified>\
+            <D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>\
+            </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>"
+        );
+    }
 }
 
 #[cfg(feature = "embedded-svc")]
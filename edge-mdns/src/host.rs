@@ -1,5 +1,8 @@
 use core::net::{Ipv4Addr, Ipv6Addr};
 
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
 use crate::domain::base::{iana::Class, Record, Ttl};
 use crate::domain::rdata::{Aaaa, AllRecordData, Ptr, Srv, A};
 
@@ -190,3 +193,168 @@ impl HostAnswers for ServiceAnswers<'_> {
         self.service.visit_answers(self.host, &mut f)
     }
 }
+
+/// An owned variant of `Host`, for hosts whose data is only known at runtime
+/// (i.e. a hostname read from NVS).
+///
+/// This structure implements the `HostAnswers` trait, just like `Host` does.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct HostBuf {
+    /// The name of the host. I.e. a name "foo" will be pingable as "foo.local"
+    pub hostname: String,
+    /// The IPv4 address of the host.
+    /// Leaving it as `Ipv4Addr::UNSPECIFIED` means that the host will not aswer it to A queries.
+    pub ipv4: Ipv4Addr,
+    /// The IPv6 address of the host.
+    /// Leaving it as `Ipv6Addr::UNSPECIFIED` means that the host will not aswer it to AAAA queries.
+    pub ipv6: Ipv6Addr,
+    /// The time-to-live of the mDNS answers.
+    pub ttl: Ttl,
+}
+
+#[cfg(feature = "alloc")]
+impl HostBuf {
+    /// Return a `Host` instance borrowing the data of this `HostBuf` instance.
+    pub fn as_host(&self) -> Host<'_> {
+        Host {
+            hostname: &self.hostname,
+            ipv4: self.ipv4,
+            ipv6: self.ipv6,
+            ttl: self.ttl,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&Host<'_>> for HostBuf {
+    fn from(host: &Host<'_>) -> Self {
+        Self {
+            hostname: host.hostname.into(),
+            ipv4: host.ipv4,
+            ipv6: host.ipv6,
+            ttl: host.ttl,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl HostAnswers for HostBuf {
+    fn visit<F, E>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(HostAnswer) -> Result<(), E>,
+        E: From<MdnsError>,
+    {
+        self.as_host().visit_answers(&mut f)
+    }
+}
+
+/// An owned variant of `Service`, for services whose data is only known at runtime.
+///
+/// Just like `Service`, this structure implements the `HostAnswers` trait indirectly - via the
+/// `ServiceBufAnswers` wrapper which also provides the hostname.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct ServiceBuf {
+    /// The name of the service.
+    pub name: String,
+    /// The priority of the service.
+    pub priority: u16,
+    /// The weight of the service.
+    pub weight: u16,
+    /// The service type. I.e. "_http"
+    pub service: String,
+    /// The protocol of the service. I.e. "_tcp" or "_udp"
+    pub protocol: String,
+    /// The TCP/UDP port where the service listens for incoming requests.
+    pub port: u16,
+    /// The subtypes of the service, if any.
+    pub service_subtypes: Vec<String>,
+    /// The key-value pairs that will be included in the TXT record, as per the DNS-SD spec.
+    pub txt_kvs: Vec<(String, String)>,
+}
+
+#[cfg(feature = "alloc")]
+impl ServiceBuf {
+    fn visit_answers<F, E>(&self, host: &Host, f: F) -> Result<(), E>
+    where
+        F: FnMut(HostAnswer) -> Result<(), E>,
+        E: From<MdnsError>,
+    {
+        let service_subtypes = self
+            .service_subtypes
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        let txt_kvs = self
+            .txt_kvs
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+
+        let service = Service {
+            name: &self.name,
+            priority: self.priority,
+            weight: self.weight,
+            service: &self.service,
+            protocol: &self.protocol,
+            port: self.port,
+            service_subtypes: &service_subtypes,
+            txt_kvs: &txt_kvs,
+        };
+
+        service.visit_answers(host, f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&Service<'_>> for ServiceBuf {
+    fn from(service: &Service<'_>) -> Self {
+        Self {
+            name: service.name.into(),
+            priority: service.priority,
+            weight: service.weight,
+            service: service.service.into(),
+            protocol: service.protocol.into(),
+            port: service.port,
+            service_subtypes: service
+                .service_subtypes
+                .iter()
+                .map(|subtype| (*subtype).into())
+                .collect(),
+            txt_kvs: service
+                .txt_kvs
+                .iter()
+                .map(|(k, v)| ((*k).into(), (*v).into()))
+                .collect(),
+        }
+    }
+}
+
+/// A wrapper around a `ServiceBuf` that also provides the host of the service
+/// and thus allows the `HostAnswers` trait contract to be fullfilled for a `ServiceBuf` instance.
+#[cfg(feature = "alloc")]
+pub struct ServiceBufAnswers<'a> {
+    host: &'a HostBuf,
+    service: &'a ServiceBuf,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> ServiceBufAnswers<'a> {
+    /// Create a new `ServiceBufAnswers` instance.
+    pub const fn new(host: &'a HostBuf, service: &'a ServiceBuf) -> Self {
+        Self { host, service }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl HostAnswers for ServiceBufAnswers<'_> {
+    fn visit<F, E>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(HostAnswer) -> Result<(), E>,
+        E: From<MdnsError>,
+    {
+        self.service.visit_answers(&self.host.as_host(), &mut f)
+    }
+}
@@ -1,4 +1,4 @@
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::fmt;
 use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
@@ -12,7 +12,7 @@ use embassy_sync::blocking_mutex::raw::RawMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_sync::signal::Signal;
 
-use edge_nal::{MulticastV4, MulticastV6, Readable, UdpBind, UdpReceive, UdpSend};
+use edge_nal::{BindOptions, MulticastV4, MulticastV6, Readable, UdpBind, UdpReceive, UdpSend};
 
 use embassy_time::{Duration, Timer};
 
@@ -20,6 +20,8 @@ use log::{debug, warn};
 
 use super::*;
 
+use crate::domain::base::{Message, ParsedName, Rtype};
+
 /// Socket address that binds to any IPv4-configured interface available
 pub const IPV4_DEFAULT_SOCKET: SocketAddr =
     SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), PORT);
@@ -40,7 +42,10 @@ pub const IP_BROADCAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
 pub const IPV6_BROADCAST_ADDR: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x00fb);
 
 /// The mDNS port, as per spec.
-pub const PORT: u16 = 5353;
+pub const PORT: u16 = MDNS_PORT;
+
+/// The TTL (IPv4) or hop limit (IPv6) of the mDNS multicast packets, as per spec.
+pub const MULTICAST_TTL: u8 = 255;
 
 /// A wrapper for mDNS and IO errors.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -90,11 +95,137 @@ where
 #[cfg(feature = "std")]
 impl<E> std::error::Error for MdnsIoError<E> where E: std::error::Error {}
 
+/// A trait for observing the activity of the `Mdns` runner.
+///
+/// Useful for surfacing discovery health in device diagnostics, i.e. how many
+/// queries were received, how many answers were sent and how many malformed packets were dropped.
+///
+/// All methods have a default no-op implementation, so implementors only need to
+/// override the notifications they are interested in.
+pub trait MdnsObserver {
+    /// Called for each question of an incoming mDNS query.
+    fn query(&self, _name: &ParsedName<&[u8]>, _rtype: Rtype, _remote: SocketAddr) {}
+
+    /// Called after an answer of `len` bytes was sent.
+    ///
+    /// `remote` is `Some` when the answer was sent privately (unicast) to a peer,
+    /// and `None` when the answer was broadcasted to the multicast address(es).
+    fn answer(&self, _remote: Option<SocketAddr>, _len: usize) {}
+
+    /// Called when an incoming packet from `remote` was dropped, because it was malformed.
+    fn malformed(&self, _remote: SocketAddr) {}
+}
+
+impl<T> MdnsObserver for &T
+where
+    T: MdnsObserver,
+{
+    fn query(&self, name: &ParsedName<&[u8]>, rtype: Rtype, remote: SocketAddr) {
+        (*self).query(name, rtype, remote)
+    }
+
+    fn answer(&self, remote: Option<SocketAddr>, len: usize) {
+        (*self).answer(remote, len)
+    }
+
+    fn malformed(&self, remote: SocketAddr) {
+        (*self).malformed(remote)
+    }
+}
+
+/// An `MdnsObserver` that ignores all notifications.
+pub struct NoObserver;
+
+impl MdnsObserver for NoObserver {}
+
+/// Statistics about the activity of the `Mdns` runner, as collected by `StatsObserver`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct MdnsStats {
+    /// The number of questions received in incoming mDNS queries
+    pub queries: u32,
+    /// The number of answers sent, either privately or via multicast
+    pub answers: u32,
+    /// The number of incoming packets dropped, because they were malformed
+    pub malformed: u32,
+}
+
+/// An `MdnsObserver` that counts the activity of the `Mdns` runner.
+pub struct StatsObserver<M>(blocking_mutex::Mutex<M, Cell<MdnsStats>>)
+where
+    M: RawMutex;
+
+impl<M> StatsObserver<M>
+where
+    M: RawMutex,
+{
+    /// Create a new `StatsObserver` instance with all counters set to zero.
+    pub const fn new() -> Self {
+        Self(blocking_mutex::Mutex::new(Cell::new(MdnsStats {
+            queries: 0,
+            answers: 0,
+            malformed: 0,
+        })))
+    }
+
+    /// Get a snapshot of the collected statistics.
+    pub fn stats(&self) -> MdnsStats {
+        self.0.lock(|stats| stats.get())
+    }
+
+    /// Reset all counters to zero.
+    pub fn reset(&self) {
+        self.0.lock(|stats| stats.set(MdnsStats::default()));
+    }
+
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut MdnsStats),
+    {
+        self.0.lock(|stats| {
+            let mut current = stats.get();
+            f(&mut current);
+            stats.set(current);
+        });
+    }
+}
+
+impl<M> Default for StatsObserver<M>
+where
+    M: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> MdnsObserver for StatsObserver<M>
+where
+    M: RawMutex,
+{
+    fn query(&self, _name: &ParsedName<&[u8]>, _rtype: Rtype, _remote: SocketAddr) {
+        self.update(|stats| stats.queries = stats.queries.wrapping_add(1));
+    }
+
+    fn answer(&self, _remote: Option<SocketAddr>, _len: usize) {
+        self.update(|stats| stats.answers = stats.answers.wrapping_add(1));
+    }
+
+    fn malformed(&self, _remote: SocketAddr) {
+        self.update(|stats| stats.malformed = stats.malformed.wrapping_add(1));
+    }
+}
+
 /// A utility method to bind a socket suitable for mDNS, by using the provided
 /// stack and address, and optionally joining the provided interfaces via multicast.
 ///
 /// Note that mDNS is pointless without multicast, so at least one - or both - of the
 /// ipv4 and ipv6 interfaces need to be provided.
+///
+/// The multicast packets sent by the socket go out through the provided interfaces and - as per RFC 6762 -
+/// have their TTL (hop limit) set to 255.
+///
+/// The socket is bound with `SO_REUSEADDR` and `SO_REUSEPORT`, so that the responder can share the mDNS port
+/// with other responders running on the same host (i.e. Avahi or the Apple mDNSResponder).
 pub async fn bind<S>(
     stack: &S,
     addr: SocketAddr,
@@ -104,13 +235,30 @@ pub async fn bind<S>(
 where
     S: UdpBind,
 {
-    let mut socket = stack.bind(addr).await.map_err(MdnsIoError::IoError)?;
+    let options = BindOptions::new()
+        .with_reuse_addr(true)
+        .with_reuse_port(true);
+
+    let mut socket = stack
+        .bind_with_options(addr, &options)
+        .await
+        .map_err(MdnsIoError::IoError)?;
 
     if let Some(v4) = ipv4_interface {
         socket
             .join_v4(IP_BROADCAST_ADDR, v4)
             .await
             .map_err(MdnsIoError::IoError)?;
+
+        socket
+            .set_multicast_if_v4(v4)
+            .await
+            .map_err(MdnsIoError::IoError)?;
+
+        socket
+            .set_multicast_ttl_v4(MULTICAST_TTL)
+            .await
+            .map_err(MdnsIoError::IoError)?;
     }
 
     if let Some(v6) = ipv6_interface {
@@ -118,6 +266,16 @@ where
             .join_v6(IPV6_BROADCAST_ADDR, v6)
             .await
             .map_err(MdnsIoError::IoError)?;
+
+        socket
+            .set_multicast_if_v6(v6)
+            .await
+            .map_err(MdnsIoError::IoError)?;
+
+        socket
+            .set_multicast_hops_v6(MULTICAST_TTL)
+            .await
+            .map_err(MdnsIoError::IoError)?;
     }
 
     Ok(socket)
@@ -197,11 +355,25 @@ where
     pub async fn run<T>(&self, handler: T) -> Result<(), MdnsIoError<S::Error>>
     where
         T: MdnsHandler,
+    {
+        self.run_observed(handler, NoObserver).await
+    }
+
+    /// Same as `run`, but additionally reports the activity of the mDNS service
+    /// (queries received, answers sent and malformed packets dropped) to the provided observer.
+    pub async fn run_observed<T, O>(
+        &self,
+        handler: T,
+        observer: O,
+    ) -> Result<(), MdnsIoError<S::Error>>
+    where
+        T: MdnsHandler,
+        O: MdnsObserver,
     {
         let handler = blocking_mutex::Mutex::<M, _>::new(RefCell::new(handler));
 
-        let mut broadcast = pin!(self.broadcast(&handler));
-        let mut respond = pin!(self.respond(&handler));
+        let mut broadcast = pin!(self.broadcast(&handler, &observer));
+        let mut respond = pin!(self.respond(&handler, &observer));
 
         let result = select(&mut broadcast, &mut respond).await;
 
@@ -241,12 +413,14 @@ where
         Ok(())
     }
 
-    async fn broadcast<T>(
+    async fn broadcast<T, O>(
         &self,
         handler: &blocking_mutex::Mutex<M, RefCell<T>>,
+        observer: &O,
     ) -> Result<(), MdnsIoError<S::Error>>
     where
         T: MdnsHandler,
+        O: MdnsObserver,
     {
         loop {
             {
@@ -259,19 +433,33 @@ where
                 let mut send_guard = self.send.lock().await;
                 let send = &mut *send_guard;
 
-                let response = handler.lock(|handler| {
-                    handler
-                        .borrow_mut()
-                        .handle(MdnsRequest::None, send_buf.as_mut())
-                })?;
+                let mut page = 0;
+
+                loop {
+                    let response = handler.lock(|handler| {
+                        handler
+                            .borrow_mut()
+                            .handle_page(MdnsRequest::None, page, send_buf.as_mut())
+                    })?;
 
-                if let MdnsResponse::Reply { data, delay } = response {
-                    if delay {
+                    let MdnsResponse::Reply { data, delay, more } = response else {
+                        break;
+                    };
+
+                    if delay && page == 0 {
                         // TODO: Not ideal, as we hold the lock during the delay
                         self.delay().await;
                     }
 
                     self.broadcast_once(send, data).await?;
+
+                    observer.answer(None, data.len());
+
+                    if !more {
+                        break;
+                    }
+
+                    page += 1;
                 }
             }
 
@@ -279,12 +467,14 @@ where
         }
     }
 
-    async fn respond<T>(
+    async fn respond<T, O>(
         &self,
         handler: &blocking_mutex::Mutex<M, RefCell<T>>,
+        observer: &O,
     ) -> Result<(), MdnsIoError<S::Error>>
     where
         T: MdnsHandler,
+        O: MdnsObserver,
     {
         let mut recv = self.recv.lock().await;
 
@@ -307,6 +497,8 @@ where
 
                 debug!("Got mDNS query from {remote}");
 
+                Self::observe_query(observer, &recv_buf.as_mut()[..len], remote);
+
                 {
                     let mut send_buf = self
                         .send_buf
@@ -317,47 +509,70 @@ where
                     let mut send_guard = self.send.lock().await;
                     let send = &mut *send_guard;
 
-                    let response = match handler.lock(|handler| {
-                        handler.borrow_mut().handle(
-                            MdnsRequest::Request {
-                                data: &recv_buf.as_mut()[..len],
-                                legacy: remote.port() != PORT,
-                                multicast: true, // TODO: Cannot determine this
+                    // Large responses are sent as multiple packets
+                    let mut page = 0;
+
+                    loop {
+                        let response = match handler.lock(|handler| {
+                            handle_packet_page(
+                                &mut *handler.borrow_mut(),
+                                &recv_buf.as_mut()[..len],
+                                remote.port(),
+                                true, // TODO: Cannot determine this
+                                page,
+                                send_buf.as_mut(),
+                            )
+                        }) {
+                            Ok(response) => response,
+                            Err(err) => match err {
+                                MdnsError::InvalidMessage => {
+                                    warn!("Got invalid message from {remote}, skipping");
+                                    observer.malformed(remote);
+                                    break;
+                                }
+                                other => Err(other)?,
                             },
-                            send_buf.as_mut(),
-                        )
-                    }) {
-                        Ok(len) => len,
-                        Err(err) => match err {
-                            MdnsError::InvalidMessage => {
-                                warn!("Got invalid message from {remote}, skipping");
-                                continue;
-                            }
-                            other => Err(other)?,
-                        },
-                    };
+                        };
 
-                    if let MdnsResponse::Reply { data, delay } = response {
-                        if remote.port() != PORT {
-                            // Support one-shot legacy queries by replying privately
-                            // to the remote address, if the query was not sent from the mDNS port (as per the spec)
+                        let more = match response {
+                            Some((data, MdnsReplyTarget::Unicast, more)) => {
+                                // Support one-shot legacy queries by replying privately
+                                // to the remote address, if the query was not sent from the mDNS port (as per the spec)
 
-                            debug!("Replying privately to a one-shot mDNS query from {remote}");
+                                debug!("Replying privately to a one-shot mDNS query from {remote}");
 
-                            if let Err(err) = send.send(remote, data).await {
-                                warn!("Failed to reply privately to {remote}: {err:?}");
-                            }
-                        } else {
-                            // Otherwise, re-broadcast the response
+                                if let Err(err) = send.send(remote, data).await {
+                                    warn!("Failed to reply privately to {remote}: {err:?}");
+                                    break;
+                                }
 
-                            if delay {
-                                self.delay().await;
+                                observer.answer(Some(remote), data.len());
+
+                                more
                             }
+                            Some((data, MdnsReplyTarget::Multicast { delay }, more)) => {
+                                // Otherwise, re-broadcast the response
+
+                                if delay && page == 0 {
+                                    self.delay().await;
+                                }
+
+                                debug!("Re-broadcasting due to mDNS query from {remote}");
 
-                            debug!("Re-broadcasting due to mDNS query from {remote}");
+                                self.broadcast_once(send, data).await?;
 
-                            self.broadcast_once(send, data).await?;
+                                observer.answer(None, data.len());
+
+                                more
+                            }
+                            None => false,
+                        };
+
+                        if !more {
+                            break;
                         }
+
+                        page += 1;
                     }
                 }
             }
@@ -393,12 +608,26 @@ where
         Ok(())
     }
 
-    async fn delay(&self) {
-        let mut b = [0];
-        (self.rand)(&mut b);
+    fn observe_query<O>(observer: &O, data: &[u8], remote: SocketAddr)
+    where
+        O: MdnsObserver,
+    {
+        let Ok(message) = Message::from_octets(data) else {
+            return;
+        };
 
-        // Generate a delay between 20 and 120 ms, as per spec
-        let delay_ms = 20 + (b[0] as u32 * 100 / 256);
+        if message.header().qr() {
+            // Not a query but a response
+            return;
+        }
+
+        for question in message.question().flatten() {
+            observer.query(question.qname(), question.qtype(), remote);
+        }
+    }
+
+    async fn delay(&self) {
+        let delay_ms = reply_delay_ms(self.rand);
 
         Timer::after(Duration::from_millis(delay_ms as _)).await;
     }
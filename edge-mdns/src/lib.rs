@@ -2,7 +2,9 @@
 #![warn(clippy::large_futures)]
 #![allow(async_fn_in_trait)]
 
-use core::cmp::Ordering;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 use core::fmt::{self, Display};
 use core::ops::RangeBounds;
 
@@ -14,7 +16,7 @@ use domain::base::name::{FromStrError, Label, ToLabelIter};
 use domain::base::rdata::ComposeRecordData;
 use domain::base::wire::{Composer, ParseError};
 use domain::base::{
-    Message, MessageBuilder, ParsedName, Question, Record, RecordData, Rtype, ToName,
+    Message, MessageBuilder, ParsedName, Question, Record, RecordData, Rtype, ToName, Ttl,
 };
 use domain::dep::octseq::{FreezeBuilder, FromBuilder, Octets, OctetsBuilder, ShortBuf, Truncate};
 use domain::rdata::AllRecordData;
@@ -32,9 +34,16 @@ pub mod host;
 #[cfg(feature = "io")]
 pub mod io;
 
+/// The mDNS port, as per spec.
+pub const MDNS_PORT: u16 = 5353;
+
 /// The DNS-SD owner name.
 pub const DNS_SD_OWNER: NameSlice = NameSlice::new(&["_services", "_dns-sd", "_udp", "local"]);
 
+/// The maximum TTL of the answers sent in reply to legacy unicast queries
+/// (i.e. queries not originating from the mDNS port), as per RFC 6762, section 6.7.
+pub const LEGACY_UNICAST_MAX_TTL: Ttl = Ttl::from_secs(10);
+
 /// A wrapper type for the errors returned by the `domain` library during parsing and
 /// constructing mDNS messages.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -111,44 +120,45 @@ impl fmt::Display for NameSlice<'_> {
 
 impl ToName for NameSlice<'_> {}
 
-/// An iterator over the labels in a `NameSlice` instance.
+/// An iterator over the labels in a `NameSlice` instance, including the terminating root label.
 #[derive(Clone)]
 pub struct NameSliceIter<'a> {
     name: &'a NameSlice<'a>,
-    index: usize,
+    front: usize,
+    back: usize,
+}
+
+impl<'a> NameSliceIter<'a> {
+    fn label(&self, index: usize) -> &'a Label {
+        if index == self.name.0.len() {
+            Label::root()
+        } else {
+            Label::from_slice(self.name.0[index].as_bytes()).unwrap()
+        }
+    }
 }
 
 impl<'a> Iterator for NameSliceIter<'a> {
     type Item = &'a Label;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.index.cmp(&self.name.0.len()) {
-            Ordering::Less => {
-                let label = Label::from_slice(self.name.0[self.index].as_bytes()).unwrap();
-                self.index += 1;
-                Some(label)
-            }
-            Ordering::Equal => {
-                let label = Label::root();
-                self.index += 1;
-                Some(label)
-            }
-            Ordering::Greater => None,
+        if self.front < self.back {
+            let label = self.label(self.front);
+            self.front += 1;
+
+            Some(label)
+        } else {
+            None
         }
     }
 }
 
 impl DoubleEndedIterator for NameSliceIter<'_> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        if self.index > 0 {
-            self.index -= 1;
-            if self.index == self.name.0.len() {
-                let label = Label::root();
-                Some(label)
-            } else {
-                let label = Label::from_slice(self.name.0[self.index].as_bytes()).unwrap();
-                Some(label)
-            }
+        if self.front < self.back {
+            self.back -= 1;
+
+            Some(self.label(self.back))
         } else {
             None
         }
@@ -164,7 +174,80 @@ impl ToLabelIter for NameSlice<'_> {
     fn iter_labels(&self) -> Self::LabelIter<'_> {
         NameSliceIter {
             name: self,
-            index: 0,
+            front: 0,
+            back: self.0.len() + 1,
+        }
+    }
+}
+
+/// Compare two DNS labels for equality, ignoring the ASCII case, as per DNS rules.
+///
+/// No punycode (IDNA) decoding is performed - non-ASCII bytes are compared as-is.
+pub fn label_eq(label: &[u8], other: &[u8]) -> bool {
+    label.eq_ignore_ascii_case(other)
+}
+
+/// Compare two names label by label, ignoring the ASCII case, as per DNS rules.
+pub fn name_eq<N, O>(name: &N, other: &O) -> bool
+where
+    N: ToLabelIter + ?Sized,
+    O: ToLabelIter + ?Sized,
+{
+    let mut labels = name.iter_labels();
+    let mut other_labels = other.iter_labels();
+
+    loop {
+        match (labels.next(), other_labels.next()) {
+            (Some(label), Some(other_label)) => {
+                if !label_eq(label.as_slice(), other_label.as_slice()) {
+                    break false;
+                }
+            }
+            (None, None) => break true,
+            _ => break false,
+        }
+    }
+}
+
+/// Return `true` if `name` is equal to, or is a subdomain of `suffix`
+/// (e.g. `foo._http._tcp.local` ends with `_tcp.local`), ignoring the ASCII case.
+pub fn name_ends_with<N, S>(name: &N, suffix: &S) -> bool
+where
+    N: ToLabelIter + ?Sized,
+    S: ToLabelIter + ?Sized,
+{
+    let mut labels = name.iter_labels().rev();
+
+    suffix.iter_labels().rev().all(|suffix_label| {
+        labels
+            .next()
+            .map(|label| label_eq(label.as_slice(), suffix_label.as_slice()))
+            .unwrap_or(false)
+    })
+}
+
+/// Compare a name with a dotted string representation of a name (e.g. `myhost.local`),
+/// label by label and ignoring the ASCII case.
+///
+/// The trailing dot in `other` is optional. Escape sequences in `other` are not supported.
+pub fn name_eq_str<N>(name: &N, other: &str) -> bool
+where
+    N: ToLabelIter + ?Sized,
+{
+    let other = other.strip_suffix('.').unwrap_or(other);
+
+    let mut labels = name.iter_labels().filter(|label| !label.is_root());
+    let mut other_labels = other.split('.').filter(|_| !other.is_empty());
+
+    loop {
+        match (labels.next(), other_labels.next()) {
+            (Some(label), Some(other_label)) => {
+                if !label_eq(label.as_slice(), other_label.as_bytes()) {
+                    break false;
+                }
+            }
+            (None, None) => break true,
+            _ => break false,
         }
     }
 }
@@ -399,7 +482,122 @@ pub enum MdnsRequest<'a> {
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum MdnsResponse<'a> {
     None,
-    Reply { data: &'a [u8], delay: bool },
+    Reply {
+        data: &'a [u8],
+        delay: bool,
+        /// Whether the response continues in the next page (see `MdnsHandler::handle_page`)
+        more: bool,
+    },
+}
+
+impl<'a> MdnsRequest<'a> {
+    /// Classify an incoming mDNS packet as an `MdnsRequest`.
+    ///
+    /// Parameters:
+    /// - `data`: The payload of the incoming packet
+    /// - `remote_port`: The source port of the packet; queries not originating from
+    ///   the mDNS port are legacy (one-shot) queries, as per spec
+    /// - `multicast`: Whether the packet arrived on the mDNS multicast address
+    pub const fn from_packet(data: &'a [u8], remote_port: u16, multicast: bool) -> Self {
+        Self::Request {
+            legacy: remote_port != MDNS_PORT,
+            multicast,
+            data,
+        }
+    }
+}
+
+/// Instructs the caller of `handle_packet` how to send the prepared reply.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MdnsReplyTarget {
+    /// Reply privately (unicast) to the address and port the query came from,
+    /// without any delay. Used for legacy queries, as per spec.
+    Unicast,
+    /// Send the reply to the mDNS multicast address(es).
+    ///
+    /// If `delay` is `true`, the caller should wait for a random period
+    /// (see `reply_delay_ms`) before sending it, as per spec.
+    Multicast { delay: bool },
+}
+
+/// Process a single incoming mDNS packet with the provided handler.
+///
+/// This is what the `io::Mdns` runner does for each received packet, and is useful
+/// for running mDNS over transports which do not implement the `edge-nal` UDP traits
+/// (esp-now, Thread, custom transports and so on).
+///
+/// Parameters:
+/// - `handler`: The handler to process the packet
+/// - `data`: The payload of the incoming packet
+/// - `remote_port`: The source port of the packet
+/// - `multicast`: Whether the packet arrived on the mDNS multicast address
+/// - `response_buf`: A buffer where the reply (if any) will be prepared
+///
+/// Returns the reply to send (if any) and how it should be sent.
+///
+/// Only the first packet of replies which do not fit in `response_buf` is prepared;
+/// use `handle_packet_page` to prepare the rest.
+pub fn handle_packet<'a, T>(
+    handler: T,
+    data: &[u8],
+    remote_port: u16,
+    multicast: bool,
+    response_buf: &'a mut [u8],
+) -> Result<Option<(&'a [u8], MdnsReplyTarget)>, MdnsError>
+where
+    T: MdnsHandler,
+{
+    let reply = handle_packet_page(handler, data, remote_port, multicast, 0, response_buf)?;
+
+    Ok(reply.map(|(data, target, _)| (data, target)))
+}
+
+/// Same as `handle_packet`, but prepares the `page`-th packet of the reply
+/// (see `MdnsHandler::handle_page`).
+///
+/// Returns the reply to send (if any), how it should be sent and whether the reply continues
+/// in the next page. Pages after the first one should be sent right away, without any delay.
+pub fn handle_packet_page<'a, T>(
+    mut handler: T,
+    data: &[u8],
+    remote_port: u16,
+    multicast: bool,
+    page: usize,
+    response_buf: &'a mut [u8],
+) -> Result<Option<(&'a [u8], MdnsReplyTarget, bool)>, MdnsError>
+where
+    T: MdnsHandler,
+{
+    let request = MdnsRequest::from_packet(data, remote_port, multicast);
+    let legacy = matches!(request, MdnsRequest::Request { legacy: true, .. });
+
+    match handler.handle_page(request, page, response_buf)? {
+        MdnsResponse::None => Ok(None),
+        MdnsResponse::Reply { data, delay, more } => {
+            let target = if legacy {
+                MdnsReplyTarget::Unicast
+            } else {
+                MdnsReplyTarget::Multicast { delay }
+            };
+
+            Ok(Some((data, target, more)))
+        }
+    }
+}
+
+/// Compute the random delay (in milliseconds) to wait before sending a delayed
+/// multicast reply, as per spec (20 to 120ms).
+///
+/// Parameters:
+/// - `rand`: A function which fills the provided buffer with random bytes
+pub fn reply_delay_ms<F>(rand: F) -> u32
+where
+    F: FnOnce(&mut [u8]),
+{
+    let mut b = [0];
+    rand(&mut b);
+
+    20 + (b[0] as u32 * 100 / 256)
 }
 
 /// A trait that abstracts the processing logic for an incoming mDNS message.
@@ -418,6 +616,26 @@ pub trait MdnsHandler {
         request: MdnsRequest<'_>,
         response_buf: &'a mut [u8],
     ) -> Result<MdnsResponse<'a>, MdnsError>;
+
+    /// Same as `handle`, but prepares the `page`-th packet of a response which might not fit
+    /// in a single packet (e.g. the answers of a registry with dozens of services).
+    ///
+    /// Callers start with page 0 and keep asking for the next page for as long as the returned reply
+    /// has its `more` flag set. Each page is a complete, well-formed mDNS message on its own.
+    ///
+    /// The default implementation returns the response of `handle` as the one and only page.
+    fn handle_page<'a>(
+        &mut self,
+        request: MdnsRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<MdnsResponse<'a>, MdnsError> {
+        if page == 0 {
+            self.handle(request, response_buf)
+        } else {
+            Ok(MdnsResponse::None)
+        }
+    }
 }
 
 impl<T> MdnsHandler for &mut T
@@ -431,6 +649,15 @@ where
     ) -> Result<MdnsResponse<'a>, MdnsError> {
         (**self).handle(request, response_buf)
     }
+
+    fn handle_page<'a>(
+        &mut self,
+        request: MdnsRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<MdnsResponse<'a>, MdnsError> {
+        (**self).handle_page(request, page, response_buf)
+    }
 }
 
 /// A structure representing a handler that does not do any processing.
@@ -489,14 +716,29 @@ where
         request: MdnsRequest<'_>,
         response_buf: &'a mut [u8],
     ) -> Result<MdnsResponse<'a>, MdnsError> {
-        match self.first.handle(request.clone(), response_buf)? {
-            MdnsResponse::None => self.second.handle(request, response_buf),
-            MdnsResponse::Reply { data, delay } => {
+        self.handle_page(request, 0, response_buf)
+    }
+
+    fn handle_page<'a>(
+        &mut self,
+        request: MdnsRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<MdnsResponse<'a>, MdnsError> {
+        // Since the pages are asked for only while the previous page has more to come,
+        // the handler which replied to the first page is the only one replying to the next pages
+        match self
+            .first
+            .handle_page(request.clone(), page, response_buf)?
+        {
+            MdnsResponse::None => self.second.handle_page(request, page, response_buf),
+            MdnsResponse::Reply { data, delay, more } => {
                 let len = data.len();
 
                 Ok(MdnsResponse::Reply {
                     data: &response_buf[..len],
                     delay,
+                    more,
                 })
             }
         }
@@ -748,41 +990,55 @@ where
 /// mDNS queries - i.e. this is the "responder" aspect of the mDNS protocol.
 pub struct HostAnswersMdnsHandler<T> {
     answers: T,
+    /// The page expected to be asked for next, and the number of answers in the pages preceding it
+    next_page: Option<(usize, usize)>,
 }
 
 impl<T> HostAnswersMdnsHandler<T> {
     /// Create a new `HostAnswersMdnsHandler` instance from an entity that provides answers.
     pub const fn new(answers: T) -> Self {
-        Self { answers }
+        Self {
+            answers,
+            next_page: None,
+        }
+    }
+
+    /// As per spec, replies to legacy unicast queries should not carry TTLs larger than 10 seconds
+    fn cap_ttl(mut answer: HostAnswer<'_>, legacy: bool) -> HostAnswer<'_> {
+        if legacy && answer.ttl() > LEGACY_UNICAST_MAX_TTL {
+            answer.set_ttl(LEGACY_UNICAST_MAX_TTL);
+        }
+
+        answer
     }
 }
 
-impl<T> MdnsHandler for HostAnswersMdnsHandler<T>
+impl<T> HostAnswersMdnsHandler<T>
 where
     T: HostAnswers,
 {
-    fn handle<'a>(
-        &mut self,
-        request: MdnsRequest<'_>,
-        response_buf: &'a mut [u8],
-    ) -> Result<MdnsResponse<'a>, MdnsError> {
+    /// Compose a single packet of the response, skipping the first `skip` answers
+    /// (as those were sent in the previous packets) and with at most `max` answers.
+    ///
+    /// Answers are pushed until the packet is full. The additional records are then
+    /// computed only for the answers which did make it into the packet.
+    fn compose(
+        &self,
+        request: Option<(&Message<&[u8]>, bool)>,
+        skip: usize,
+        max: usize,
+        response_buf: &mut [u8],
+    ) -> Result<Page, MdnsError> {
         let buf = Buf(response_buf, 0);
 
         let mut mb = MessageBuilder::from_target(buf)?;
 
-        let mut pushed = false;
-
-        let buf = if let MdnsRequest::Request { legacy, data, .. } = request {
-            let message = Message::from_octets(data)?;
+        let mut page = Page::new(max);
 
-            if !matches!(message.header().opcode(), Opcode::QUERY)
-                || !matches!(message.header().rcode(), Rcode::NOERROR)
-                || message.header().qr()
-            // Not a query but a response
-            {
-                return Ok(MdnsResponse::None);
-            }
+        // The index of the current answer, counting from the first packet
+        let mut index = 0;
 
+        let buf = if let Some((message, legacy)) = request {
             let mut ab = if legacy {
                 set_header(&mut mb, message.header().id(), true);
 
@@ -807,26 +1063,28 @@ where
                 let question = question?;
 
                 self.answers.visit(|answer| {
-                    if matches!(answer.data(), RecordDataChain::Next(AllRecordData::Srv(_))) {
-                        additional_a = true;
+                    if !name_eq(question.qname(), answer.owner()) {
+                        return Ok(());
                     }
 
-                    if !answer.owner().name_eq(&DNS_SD_OWNER)
-                        && matches!(answer.data(), RecordDataChain::Next(AllRecordData::Ptr(_)))
-                    {
-                        additional_a = true;
+                    index += 1;
 
-                        // Over-simplifying here in that we'll send all our SRV and TXT records, however
-                        // sending only some SRV and PTR records is too complex to implement.
-                        additional_srv_txt = true;
+                    if index <= skip {
+                        return Ok(());
                     }
 
-                    if question.qname().name_eq(&answer.owner()) {
-                        debug!("Answering question [{question}] with: [{answer}]");
+                    let srv = matches!(answer.data(), RecordDataChain::Next(AllRecordData::Srv(_)));
+                    let ptr = !name_eq(answer.owner(), &DNS_SD_OWNER)
+                        && matches!(answer.data(), RecordDataChain::Next(AllRecordData::Ptr(_)));
 
-                        ab.push(answer)?;
+                    debug!("Answering question [{question}] with: [{answer}]");
 
-                        pushed = true;
+                    if page.push(|| ab.push(Self::cap_ttl(answer, legacy)))? {
+                        // SRV records need the A/AAAA records of their target, while service instance PTR records
+                        // need the SRV and TXT records of the instance as well. These are shared by many answers,
+                        // so they are repeated in each packet, as every packet should be usable on its own.
+                        additional_a |= srv || ptr;
+                        additional_srv_txt |= ptr;
                     }
 
                     Ok::<_, MdnsError>(())
@@ -839,19 +1097,24 @@ where
                 let mut aa = ab.additional();
 
                 self.answers.visit(|answer| {
-                    if matches!(
+                    let a = matches!(
                         answer.data(),
                         RecordDataChain::Next(AllRecordData::A(_))
                             | RecordDataChain::Next(AllRecordData::Aaaa(_))
-                            | RecordDataChain::Next(AllRecordData::Srv(_))
+                    );
+                    let srv_txt = matches!(
+                        answer.data(),
+                        RecordDataChain::Next(AllRecordData::Srv(_))
                             | RecordDataChain::Next(AllRecordData::Txt(_))
                             | RecordDataChain::This(Txt(_))
-                    ) {
-                        debug!("Additional answer: [{answer}]");
+                    );
 
-                        aa.push(answer)?;
+                    if page.additional && (additional_a && a || additional_srv_txt && srv_txt) {
+                        debug!("Additional answer: [{answer}]");
 
-                        pushed = true;
+                        if aa.push(Self::cap_ttl(answer, legacy)).is_err() {
+                            page.additional = false;
+                        }
                     }
 
                     Ok::<_, MdnsError>(())
@@ -867,9 +1130,11 @@ where
             let mut ab = mb.answer();
 
             self.answers.visit(|answer| {
-                ab.push(answer)?;
+                index += 1;
 
-                pushed = true;
+                if index > skip {
+                    page.push(|| ab.push(answer))?;
+                }
 
                 Ok::<_, MdnsError>(())
             })?;
@@ -877,13 +1142,139 @@ where
             ab.finish()
         };
 
-        if pushed {
-            Ok(MdnsResponse::Reply {
-                data: &buf.0[..buf.1],
-                delay: false,
-            })
+        page.len = buf.1;
+
+        Ok(page)
+    }
+}
+
+impl<T> MdnsHandler for HostAnswersMdnsHandler<T>
+where
+    T: HostAnswers,
+{
+    fn handle<'a>(
+        &mut self,
+        request: MdnsRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<MdnsResponse<'a>, MdnsError> {
+        self.handle_page(request, 0, response_buf)
+    }
+
+    fn handle_page<'a>(
+        &mut self,
+        request: MdnsRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<MdnsResponse<'a>, MdnsError> {
+        let request = if let MdnsRequest::Request { legacy, data, .. } = request {
+            let message = Message::from_octets(data)?;
+
+            if !matches!(message.header().opcode(), Opcode::QUERY)
+                || !matches!(message.header().rcode(), Rcode::NOERROR)
+                || message.header().qr()
+            // Not a query but a response
+            {
+                return Ok(MdnsResponse::None);
+            }
+
+            Some((message, legacy))
         } else {
-            Ok(MdnsResponse::None)
+            None
+        };
+
+        let request = request.as_ref().map(|(message, legacy)| (message, *legacy));
+
+        // The packet boundaries depend on the size of the answers, so the answer the next page starts with
+        // is remembered; the previous pages are only re-composed if the pages are not asked for in order
+        let (mut current, mut skip) = match self.next_page.take() {
+            Some((next, skip)) if next == page => (next, skip),
+            _ => (0, 0),
+        };
+
+        loop {
+            let mut max = usize::MAX;
+
+            let composed = loop {
+                let composed = self.compose(request, skip, max, response_buf)?;
+
+                if composed.additional || composed.answers <= 1 {
+                    break composed;
+                }
+
+                // Not all additional records needed by the answers did fit;
+                // leave some of the answers for the next packet
+                max = composed.answers - 1;
+            };
+
+            if composed.answers == 0 {
+                break Ok(MdnsResponse::None);
+            }
+
+            if current == page {
+                self.next_page = composed.more.then_some((page + 1, skip + composed.answers));
+
+                break Ok(MdnsResponse::Reply {
+                    data: &response_buf[..composed.len],
+                    delay: false,
+                    more: composed.more,
+                });
+            }
+
+            if !composed.more {
+                break Ok(MdnsResponse::None);
+            }
+
+            skip += composed.answers;
+            current += 1;
+        }
+    }
+}
+
+/// A single packet of a response composed by `HostAnswersMdnsHandler`
+struct Page {
+    /// The maximum number of answers in the packet
+    max: usize,
+    /// The length of the packet
+    len: usize,
+    /// The number of answers in the packet
+    answers: usize,
+    /// Whether some answers did not fit and should go in the next packet
+    more: bool,
+    /// Whether all additional records needed by the answers did fit
+    additional: bool,
+}
+
+impl Page {
+    const fn new(max: usize) -> Self {
+        Self {
+            max,
+            len: 0,
+            answers: 0,
+            more: false,
+            additional: true,
+        }
+    }
+
+    /// Push an answer with the provided closure, unless the packet is already full
+    ///
+    /// Returns `true` if the answer was pushed.
+    fn push<F>(&mut self, push: F) -> Result<bool, MdnsError>
+    where
+        F: FnOnce() -> Result<(), PushError>,
+    {
+        if self.more {
+            Ok(false)
+        } else if self.answers < self.max && push().is_ok() {
+            self.answers += 1;
+
+            Ok(true)
+        } else if self.answers == 0 {
+            // Not even a single answer fits in the buffer
+            Err(MdnsError::ShortBuf)
+        } else {
+            self.more = true;
+
+            Ok(false)
         }
     }
 }
@@ -1022,3 +1413,91 @@ pub fn set_header<T: Composer>(answer: &mut MessageBuilder<T>, id: u16, response
     flags.aa = response;
     header.set_flags(flags);
 }
+
+#[cfg(test)]
+mod test {
+    use domain::base::name::{Label, Name, ToLabelIter};
+
+    use crate::{name_ends_with, name_eq, name_eq_str, NameSlice};
+
+    #[test]
+    fn name_slice_labels() {
+        let name = NameSlice::new(&["foo", "local"]);
+
+        let mut labels = name.iter_labels();
+        assert_eq!(labels.next().unwrap().as_slice(), b"foo");
+        assert_eq!(labels.next().unwrap().as_slice(), b"local");
+        assert!(labels.next().unwrap().is_root());
+        assert!(labels.next().is_none());
+
+        let mut labels = name.iter_labels().rev();
+        assert!(labels.next().unwrap().is_root());
+        assert_eq!(labels.next().unwrap().as_slice(), b"local");
+        assert_eq!(labels.next().unwrap().as_slice(), b"foo");
+        assert!(labels.next().is_none());
+
+        // Both ends meet in the middle
+        let mut labels = name.iter_labels();
+        assert_eq!(labels.next().unwrap().as_slice(), b"foo");
+        assert!(labels.next_back().unwrap().is_root());
+        assert_eq!(labels.next_back().unwrap().as_slice(), b"local");
+        assert!(labels.next().is_none());
+        assert!(labels.next_back().is_none());
+
+        let root = NameSlice::new(&[]);
+        assert_eq!(root.iter_labels().count(), 1);
+        assert_eq!(root.iter_labels().rev().count(), 1);
+        assert!(root.iter_labels().next().is_some_and(Label::is_root));
+    }
+
+    #[test]
+    fn name_slice_comparisons() {
+        let name = NameSlice::new(&["Foo", "_http", "_TCP", "local"]);
+
+        assert!(name_eq(
+            &name,
+            &NameSlice::new(&["foo", "_HTTP", "_tcp", "LOCAL"])
+        ));
+        assert!(!name_eq(&name, &NameSlice::new(&["foo", "_http", "_tcp"])));
+
+        assert!(name_ends_with(&name, &NameSlice::new(&["_tcp", "local"])));
+        assert!(name_ends_with(&name, &name));
+        assert!(name_ends_with(&name, &NameSlice::new(&[])));
+        assert!(!name_ends_with(&name, &NameSlice::new(&["_udp", "local"])));
+        assert!(!name_ends_with(&NameSlice::new(&["local"]), &name));
+
+        assert!(name_eq_str(&name, "foo._http._tcp.local"));
+        assert!(name_eq_str(&name, "foo._http._tcp.local."));
+        assert!(!name_eq_str(&name, "foo._http._tcp"));
+        assert!(name_eq_str(&NameSlice::new(&[]), ""));
+    }
+
+    #[test]
+    fn domain_name_comparisons() {
+        let name = Name::<[u8]>::from_slice(b"\x03Foo\x05_http\x04_TCP\x05local\x00").unwrap();
+        let suffix = Name::<[u8]>::from_slice(b"\x04_tcp\x05LOCAL\x00").unwrap();
+        let other = Name::<[u8]>::from_slice(b"\x04_udp\x05local\x00").unwrap();
+
+        assert!(name_eq(
+            name,
+            &NameSlice::new(&["foo", "_http", "_tcp", "local"])
+        ));
+        assert!(name_eq(
+            &NameSlice::new(&["foo", "_http", "_tcp", "local"]),
+            name
+        ));
+        assert!(!name_eq(name, suffix));
+
+        assert!(name_ends_with(name, suffix));
+        assert!(name_ends_with(name, &NameSlice::new(&["_tcp", "local"])));
+        assert!(name_ends_with(
+            &NameSlice::new(&["foo", "_tcp", "local"]),
+            suffix
+        ));
+        assert!(!name_ends_with(name, other));
+        assert!(!name_ends_with(suffix, name));
+
+        assert!(name_eq_str(name, "foo._http._tcp.local"));
+        assert!(!name_eq_str(suffix, "foo._http._tcp.local"));
+    }
+}
@@ -0,0 +1,1667 @@
+//! A native, `no_std` and no-alloc MQTT 3.1.1 (and, with the `v5` feature, MQTT 5) client over any `embedded-io-async` transport
+
+use core::fmt::{self, Debug};
+
+use embassy_futures::select::{select, Either};
+
+use embedded_io_async::{Read, Write};
+
+use log::{debug, warn};
+
+use crate as mqtt;
+use crate::{Ack, Connect, Packet, Properties, Publish, QoS, Reason, Version};
+
+#[cfg(feature = "v5")]
+use alias::TopicAliases;
+
+pub use reconnect::*;
+pub use store::*;
+
+#[cfg(feature = "v5")]
+mod alias;
+mod reconnect;
+mod store;
+pub mod ws;
+
+/// The reason code of an AUTH packet continuing the enhanced authentication of MQTT 5
+#[cfg(feature = "v5")]
+const CONTINUE_AUTHENTICATION: u8 = 0x18;
+
+/// The reason code of an AUTH packet starting a re-authentication
+#[cfg(feature = "v5")]
+const REAUTHENTICATE: u8 = 0x19;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error<E> {
+    Io(E),
+    Format(mqtt::Error),
+    /// The broker refused the connection with the contained CONNACK return code
+    Refused(u8),
+    /// The broker did not answer a CONNECT or a PINGREQ in time
+    Timeout,
+    /// The broker closed the connection
+    Disconnected,
+    /// The broker closed the connection with a DISCONNECT packet, carrying the contained reason code (MQTT 5)
+    ServerDisconnect(u8),
+    /// The client is not connected
+    NotConnected,
+    /// The session store failed, i.e. there is no room for another message
+    Store,
+    /// The `Authenticator` could not answer the AUTH challenge of the broker (MQTT 5)
+    Auth,
+}
+
+pub type ErrorKind = Error<edge_nal::io::ErrorKind>;
+
+impl<E> Error<E>
+where
+    E: edge_nal::io::Error,
+{
+    pub fn erase(&self) -> Error<edge_nal::io::ErrorKind> {
+        match self {
+            Self::Io(e) => Error::Io(e.kind()),
+            Self::Format(e) => Error::Format(*e),
+            Self::Refused(code) => Error::Refused(*code),
+            Self::Timeout => Error::Timeout,
+            Self::Disconnected => Error::Disconnected,
+            Self::ServerDisconnect(code) => Error::ServerDisconnect(*code),
+            Self::NotConnected => Error::NotConnected,
+            Self::Store => Error::Store,
+            Self::Auth => Error::Auth,
+        }
+    }
+}
+
+impl<E> From<mqtt::Error> for Error<E> {
+    fn from(value: mqtt::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "IO error: {err}"),
+            Self::Format(err) => write!(f, "Format error: {err}"),
+            Self::Refused(code) => write!(f, "Connection refused with code {code}"),
+            Self::Timeout => write!(f, "Timeout"),
+            Self::Disconnected => write!(f, "Disconnected by the broker"),
+            Self::ServerDisconnect(code) => {
+                write!(f, "Disconnected by the broker with reason code {code}")
+            }
+            Self::NotConnected => write!(f, "Not connected"),
+            Self::Store => write!(f, "Session store error"),
+            Self::Auth => write!(f, "Authentication error"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E> where E: std::error::Error {}
+
+/// A monotonic clock, driving all time-dependent behavior of a `Client`:
+/// the keepalive pings, the retransmissions and the expiry of unacknowledged messages
+pub trait Clock {
+    /// Return the current time in milliseconds
+    fn now(&self) -> u64;
+
+    /// Wait until the clock reaches `deadline` (in milliseconds)
+    async fn wait_until(&self, deadline: u64);
+}
+
+impl<C> Clock for &C
+where
+    C: Clock,
+{
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+
+    async fn wait_until(&self, deadline: u64) {
+        (**self).wait_until(deadline).await
+    }
+}
+
+/// A `Clock` on top of `embassy-time`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EmbassyClock;
+
+impl Clock for EmbassyClock {
+    fn now(&self) -> u64 {
+        embassy_time::Instant::now().as_millis()
+    }
+
+    async fn wait_until(&self, deadline: u64) {
+        embassy_time::Timer::at(embassy_time::Instant::from_millis(deadline)).await
+    }
+}
+
+/// The client side of the enhanced authentication of MQTT 5, i.e. a SCRAM or Kerberos exchange
+///
+/// The exchange starts with the authentication method (and the initial authentication data) in the properties
+/// of the `Connect` packet, or in the properties passed to `Client::reauthenticate`. The broker then
+/// either accepts the client right away, or sends AUTH challenges, each of which is answered
+/// by the authenticator.
+#[cfg(feature = "v5")]
+pub trait Authenticator {
+    /// Answer an AUTH challenge of the broker, whose properties carry the authentication method
+    /// and the authentication data of the challenge
+    ///
+    /// Returns the properties of the answer - the authentication method and the authentication data -
+    /// or `None` to abort the exchange, which fails with `Error::Auth`.
+    fn authenticate(&mut self, challenge: Properties<'_>) -> Option<Properties<'_>>;
+}
+
+/// The configuration of a `Client`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// The version of the MQTT protocol spoken with the broker
+    pub version: Version,
+    /// The time in milliseconds to wait for the CONNACK of the broker
+    pub connect_timeout_ms: u64,
+    /// The time in milliseconds after which an unacknowledged message is re-sent with the DUP flag set
+    ///
+    /// With `None`, unacknowledged messages are only re-sent after reconnecting, which is
+    /// the only retransmission MQTT 3.1.1 requires.
+    pub retransmit_timeout_ms: Option<u64>,
+    /// The time in milliseconds after which a message which is still not acknowledged is dropped
+    /// and reported with `Event::Expired`, rather than re-sent
+    pub message_expiry_ms: Option<u64>,
+}
+
+impl Config {
+    /// Create a new MQTT 3.1.1 configuration with a connect timeout of 10 seconds, retransmissions
+    /// every 20 seconds and no message expiry
+    pub const fn new() -> Self {
+        Self {
+            version: Version::V3_1_1,
+            connect_timeout_ms: 10_000,
+            retransmit_timeout_ms: Some(20_000),
+            message_expiry_ms: None,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An event reported by `Client::next`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Event<'a> {
+    /// A message was received on one of the subscribed topics
+    Received(Publish<'a>),
+    /// The message with the contained packet identifier was acknowledged by the broker
+    Published(u16),
+    /// The broker refused the message with packet identifier `id`, with a reason code of 0x80 and above (MQTT 5)
+    Rejected { id: u16, code: u8 },
+    /// The broker answered the subscription with the contained packet identifier
+    Subscribed {
+        id: u16,
+        /// The granted QoS (0 - 2) of each of the subscribed filters, or a failure code of 0x80 and above
+        codes: &'a [u8],
+    },
+    /// The broker answered the unsubscription with the contained packet identifier
+    Unsubscribed {
+        id: u16,
+        /// The reason code of each of the unsubscribed filters (MQTT 5, always empty with MQTT 3.1.1)
+        codes: &'a [u8],
+    },
+    /// The message with the contained packet identifier expired before the broker acknowledged it, and was dropped
+    Expired(u16),
+    /// The client (re)connected to the broker; only reported by `ReconnectingClient::next`
+    Connected {
+        /// The session-present flag of the CONNACK of the broker
+        session_present: bool,
+    },
+    /// The broker accepted the re-authentication started with `Client::reauthenticate` (MQTT 5)
+    Authenticated,
+}
+
+/// What `Client::next` should do after processing the timers
+enum Timers {
+    /// Report the expiry of the message with the contained packet identifier
+    Expired(u16),
+    /// Wait for the next packet, but not longer than the contained deadline
+    Wait(Option<u64>),
+}
+
+/// A native MQTT 3.1.1 or MQTT 5 client
+///
+/// The client runs over any `embedded-io-async` transport (i.e. a TCP socket of an `edge-nal` stack),
+/// and all of its time-dependent behavior is driven by the injected `Clock`, so the same code runs
+/// on top of `embassy-time`, ESP-IDF or STD.
+///
+/// The session state - the messages published with QoS 1 and 2 which are not completed yet, and the
+/// incoming QoS 2 messages which are not released yet - is kept in the injected `Store`. The outgoing
+/// messages are re-sent periodically (see `Config::retransmit_timeout_ms`), and re-sent when the client
+/// reconnects; with `clean_session` set to `false` and a persistent store, this also works across
+/// restarts of the device.
+///
+/// Received messages are parsed in place, in the receive buffer passed to the client, so that buffer
+/// should fit the largest expected message.
+///
+/// With MQTT 5 (see `Config::version`), the properties of the messages and the reason codes of the broker
+/// are reported as well, and - with a buffer for them passed to `with_topic_aliases` - topics are replaced
+/// by topic aliases in both directions.
+pub struct Client<'b, T, C, S> {
+    io: Option<T>,
+    clock: C,
+    store: S,
+    config: Config,
+    rx_buf: &'b mut [u8],
+    rx_len: usize,
+    rx_consumed: usize,
+    tx_buf: &'b mut [u8],
+    keep_alive_ms: u64,
+    last_sent: u64,
+    ping_sent: Option<u64>,
+    last_id: u16,
+    #[cfg(feature = "v5")]
+    aliases: TopicAliases<'b>,
+    #[cfg(feature = "v5")]
+    authenticator: Option<&'b mut dyn Authenticator>,
+}
+
+impl<'b, T, C, S> Client<'b, T, C, S>
+where
+    T: Read + Write,
+    C: Clock,
+    S: Store,
+{
+    /// Create a new, not yet connected client
+    ///
+    /// Parameters:
+    /// - `clock`: The clock driving the keepalive pings, retransmissions and message expiry
+    /// - `store`: The store for the session state, i.e. a `MemoryStore`
+    /// - `config`: The configuration of the client
+    /// - `rx_buf`: The buffer for the received packets
+    /// - `tx_buf`: The buffer for the sent packets
+    pub fn new(
+        clock: C,
+        store: S,
+        config: Config,
+        rx_buf: &'b mut [u8],
+        tx_buf: &'b mut [u8],
+    ) -> Self {
+        Self {
+            io: None,
+            clock,
+            store,
+            config,
+            rx_buf,
+            rx_len: 0,
+            rx_consumed: 0,
+            tx_buf,
+            keep_alive_ms: 0,
+            last_sent: 0,
+            ping_sent: None,
+            last_id: 0,
+            #[cfg(feature = "v5")]
+            aliases: TopicAliases::new(&mut []),
+            #[cfg(feature = "v5")]
+            authenticator: None,
+        }
+    }
+
+    /// Use `buf` for the topic aliases of MQTT 5
+    ///
+    /// Published messages get a topic alias - as long as the broker accepts more of them (according to
+    /// the topic alias maximum of its CONNACK) and there is room in `buf` - so that only the first message
+    /// published to a topic carries the topic. Messages received with a topic alias only are reported with
+    /// the topic the broker assigned to the alias earlier; to enable these, set the topic alias maximum
+    /// in the properties of `Connect`.
+    ///
+    /// The aliases of a connection are forgotten when reconnecting.
+    #[cfg(feature = "v5")]
+    pub fn with_topic_aliases(self, buf: &'b mut [u8]) -> Self {
+        Self {
+            aliases: TopicAliases::new(buf),
+            ..self
+        }
+    }
+
+    /// Use `authenticator` to answer the AUTH challenges of the enhanced authentication of MQTT 5
+    ///
+    /// The authentication method is set in the properties of `Connect`, and the challenges of the broker
+    /// are answered while connecting, as well as during a re-authentication started with `reauthenticate`.
+    #[cfg(feature = "v5")]
+    pub fn with_authenticator(self, authenticator: &'b mut dyn Authenticator) -> Self {
+        Self {
+            authenticator: Some(authenticator),
+            ..self
+        }
+    }
+
+    /// Return `true` if the client is connected
+    pub fn is_connected(&self) -> bool {
+        self.io.is_some()
+    }
+
+    /// Return the store of the session state
+    pub fn store(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Connect to the broker over `io`, an already established connection to it
+    ///
+    /// Any previous connection is dropped. With `clean_session` set to `true`, the session state in the store
+    /// is discarded before connecting. Once the broker accepts the connection, the outgoing messages which
+    /// are not completed yet are re-sent (or only their PUBREL, if the broker already received them).
+    /// If the broker did not keep the session, the packet identifiers of the incoming QoS 2 messages are
+    /// forgotten, as the broker will not release them.
+    ///
+    /// Returns the session-present flag of the CONNACK of the broker.
+    pub async fn connect(&mut self, io: T, connect: &Connect<'_>) -> Result<bool, Error<T::Error>> {
+        self.io = Some(io);
+        self.rx_len = 0;
+        self.rx_consumed = 0;
+        self.ping_sent = None;
+        self.keep_alive_ms = connect.keep_alive_secs as u64 * 1000;
+
+        #[cfg(feature = "v5")]
+        self.aliases.reset(0);
+
+        let result = self.handshake(connect).await;
+
+        if result.is_err() {
+            self.io = None;
+        }
+
+        result
+    }
+
+    /// Send a DISCONNECT packet to the broker and drop the connection
+    pub async fn disconnect(&mut self) -> Result<(), Error<T::Error>> {
+        let result = self.send(&Packet::Disconnect(Reason::new(0))).await;
+
+        self.io = None;
+
+        result
+    }
+
+    /// Start a re-authentication with the MQTT 5 enhanced authentication
+    ///
+    /// `properties` carry the authentication method of the connection and the initial authentication data.
+    /// The challenges of the broker are answered by the `Authenticator` passed to `with_authenticator`, and
+    /// the success of the re-authentication is reported by `next` with `Event::Authenticated`; on failure,
+    /// the broker closes the connection.
+    #[cfg(feature = "v5")]
+    pub async fn reauthenticate(
+        &mut self,
+        properties: Properties<'_>,
+    ) -> Result<(), Error<T::Error>> {
+        self.send(&Packet::Auth(Reason {
+            code: REAUTHENTICATE,
+            properties,
+        }))
+        .await
+    }
+
+    /// Publish a message
+    ///
+    /// Messages with QoS 1 and 2 are kept in the store until the broker acknowledges them (PUBACK), or
+    /// completes their delivery (PUBCOMP), which is reported by `next` with `Event::Published`. If the client
+    /// is disconnected, or the message could not be sent, it is re-sent once the client reconnects.
+    ///
+    /// Returns the packet identifier of the message (0 with `QoS::AtMostOnce`).
+    pub async fn publish(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+    ) -> Result<u16, Error<T::Error>> {
+        self.send_publish(topic, qos, retain, payload, Properties::EMPTY)
+            .await
+    }
+
+    /// Publish a message with MQTT 5 properties, i.e. the message expiry interval or the response topic
+    ///
+    /// See `publish` for the details.
+    #[cfg(feature = "v5")]
+    pub async fn publish_with_properties(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+        properties: Properties<'_>,
+    ) -> Result<u16, Error<T::Error>> {
+        self.send_publish(topic, qos, retain, payload, properties)
+            .await
+    }
+
+    /// Subscribe to the provided topic filters, each with its maximum QoS
+    ///
+    /// The answer of the broker is reported by `next` with `Event::Subscribed`.
+    ///
+    /// Returns the packet identifier of the subscription.
+    pub async fn subscribe(&mut self, filters: &[(&str, QoS)]) -> Result<u16, Error<T::Error>> {
+        self.send_subscribe(filters, Properties::EMPTY).await
+    }
+
+    /// Subscribe to the provided topic filters with MQTT 5 properties, i.e. the subscription identifier
+    ///
+    /// See `subscribe` for the details.
+    #[cfg(feature = "v5")]
+    pub async fn subscribe_with_properties(
+        &mut self,
+        filters: &[(&str, QoS)],
+        properties: Properties<'_>,
+    ) -> Result<u16, Error<T::Error>> {
+        self.send_subscribe(filters, properties).await
+    }
+
+    /// Unsubscribe from the provided topic filters
+    ///
+    /// The answer of the broker is reported by `next` with `Event::Unsubscribed`.
+    ///
+    /// Returns the packet identifier of the unsubscription.
+    pub async fn unsubscribe(&mut self, filters: &[&str]) -> Result<u16, Error<T::Error>> {
+        let id = self.next_id().await?;
+
+        self.send(&Packet::Unsubscribe {
+            id,
+            filters,
+            properties: Properties::EMPTY,
+        })
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Wait for the next event, while sending the keepalive pings and the retransmissions when due
+    ///
+    /// Incoming QoS 1 messages are acknowledged, and incoming QoS 2 messages are delivered only once,
+    /// even if the broker re-sends them before releasing them.
+    ///
+    /// Returns `Error::Timeout` if the broker did not answer a PINGREQ within the keepalive interval,
+    /// and `Error::Disconnected` if it closed the connection. These, as well as IO errors and packets
+    /// which cannot be decoded, drop the connection (see `is_connected`), and the client needs
+    /// to reconnect - which `ReconnectingClient` does automatically.
+    ///
+    /// Waiting for the packets of the broker is cancel-safe (as long as reading from the transport is),
+    /// so `next` can be raced against the application's own events, i.e. with `select`.
+    pub async fn next(&mut self) -> Result<Event<'_>, Error<T::Error>> {
+        loop {
+            let deadline = match self.process_timers().await? {
+                Timers::Expired(id) => return Ok(Event::Expired(id)),
+                Timers::Wait(deadline) => deadline,
+            };
+
+            let Some(len) = self.recv(deadline).await? else {
+                continue;
+            };
+
+            self.rx_consumed = len;
+
+            match Packet::decode(&self.rx_buf[..len], self.config.version)?.0 {
+                Packet::Publish(publish) => {
+                    #[cfg(feature = "v5")]
+                    if let Some(alias) = publish.properties.topic_alias() {
+                        if !publish.topic.is_empty() {
+                            if !self.aliases.set_incoming(alias, publish.topic) {
+                                Err(mqtt::Error::BufferOverflow)?;
+                            }
+                        } else if self.aliases.incoming(alias).is_none() {
+                            warn!("Unknown topic alias {alias}");
+                            Err(mqtt::Error::Invalid)?;
+                        }
+                    }
+
+                    let (qos, id) = (publish.qos, publish.id);
+
+                    match qos {
+                        QoS::AtMostOnce => break,
+                        QoS::AtLeastOnce => {
+                            self.send(&Packet::PubAck(Ack::new(id))).await?;
+                            break;
+                        }
+                        QoS::ExactlyOnce => {
+                            let first = self.store.receive(id).await.map_err(store_error)?;
+
+                            self.send(&Packet::PubRec(Ack::new(id))).await?;
+
+                            if first {
+                                break;
+                            }
+
+                            debug!("Ignoring duplicate message {id}");
+                        }
+                    }
+                }
+                Packet::PubRel(Ack { id, .. }) => {
+                    self.store.release(id).await.map_err(store_error)?;
+                    self.send(&Packet::PubComp(Ack::new(id))).await?;
+                }
+                Packet::PubAck(Ack { id, code, .. }) | Packet::PubComp(Ack { id, code, .. }) => {
+                    if self.store.remove(id).await.map_err(store_error)?.is_some() {
+                        return Ok(completed(id, code));
+                    }
+
+                    debug!("Ignoring acknowledgement of unknown message {id}");
+                }
+                // A failure code in the PUBREC ends the delivery of a QoS 2 message
+                Packet::PubRec(Ack { id, code, .. }) if code >= 0x80 => {
+                    if self.store.remove(id).await.map_err(store_error)?.is_some() {
+                        return Ok(completed(id, code));
+                    }
+
+                    debug!("Ignoring PUBREC of unknown message {id}");
+                }
+                Packet::PubRec(Ack { id, .. }) => {
+                    let now = self.clock.now();
+
+                    if let Some(mut outgoing) = self.find(id).await? {
+                        outgoing.released = true;
+                        outgoing.sent_at = now;
+
+                        self.store.update(&outgoing).await.map_err(store_error)?;
+                    } else {
+                        debug!("PUBREC for unknown message {id}");
+                    }
+
+                    self.send(&Packet::PubRel(Ack::new(id))).await?;
+                }
+                Packet::SubAck { .. } | Packet::UnsubAck { .. } => break,
+                Packet::PingResp => self.ping_sent = None,
+                #[cfg(feature = "v5")]
+                Packet::Auth(Reason {
+                    code: CONTINUE_AUTHENTICATION,
+                    ..
+                }) => {
+                    let len = self.answer_auth()?;
+
+                    self.write(len).await?;
+                }
+                #[cfg(feature = "v5")]
+                Packet::Auth(Reason { code: 0, .. }) => return Ok(Event::Authenticated),
+                Packet::Disconnect(Reason { code, .. }) => {
+                    self.io = None;
+
+                    Err(Error::ServerDisconnect(code))?;
+                }
+                packet => {
+                    warn!("Unexpected packet {packet:?}");
+                    Err(mqtt::Error::Invalid)?;
+                }
+            }
+        }
+
+        match Packet::decode(&self.rx_buf[..self.rx_consumed], self.config.version)?.0 {
+            #[cfg(not(feature = "v5"))]
+            Packet::Publish(publish) => Ok(Event::Received(publish)),
+            #[cfg(feature = "v5")]
+            Packet::Publish(mut publish) => {
+                if let Some(alias) = publish.properties.topic_alias() {
+                    if let Some(topic) = self.aliases.incoming(alias) {
+                        publish.topic = topic;
+                    }
+                }
+
+                Ok(Event::Received(publish))
+            }
+            Packet::SubAck { id, codes, .. } => Ok(Event::Subscribed { id, codes }),
+            Packet::UnsubAck { id, codes, .. } => Ok(Event::Unsubscribed { id, codes }),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn handshake(&mut self, connect: &Connect<'_>) -> Result<bool, Error<T::Error>> {
+        if connect.clean_session {
+            self.store.clear().await.map_err(store_error)?;
+        }
+
+        self.send(&Packet::Connect(*connect)).await?;
+
+        let deadline = self.clock.now() + self.config.connect_timeout_ms;
+
+        // With the enhanced authentication of MQTT 5, the CONNACK is preceded by the AUTH challenges of the broker
+        let session_present = loop {
+            let Some(len) = self.recv(Some(deadline)).await? else {
+                return Err(Error::Timeout);
+            };
+
+            self.rx_consumed = len;
+
+            match Packet::decode(&self.rx_buf[..len], self.config.version)?.0 {
+                #[cfg(not(feature = "v5"))]
+                Packet::ConnAck {
+                    session_present,
+                    code: 0,
+                    ..
+                } => break session_present,
+                #[cfg(feature = "v5")]
+                Packet::ConnAck {
+                    session_present,
+                    code: 0,
+                    properties,
+                } => {
+                    self.aliases
+                        .reset(properties.topic_alias_maximum().unwrap_or(0));
+
+                    // The keepalive interval assigned by the broker overrides the one of the CONNECT
+                    if let Some(keep_alive) = properties.server_keep_alive() {
+                        self.keep_alive_ms = keep_alive as u64 * 1000;
+                    }
+
+                    break session_present;
+                }
+                Packet::ConnAck { code, .. } => Err(Error::Refused(code))?,
+                #[cfg(feature = "v5")]
+                Packet::Auth(Reason {
+                    code: CONTINUE_AUTHENTICATION,
+                    ..
+                }) => {
+                    let len = self.answer_auth()?;
+
+                    self.write(len).await?;
+                }
+                _ => Err(mqtt::Error::Invalid)?,
+            }
+        };
+
+        if !session_present {
+            self.store.clear_received().await.map_err(store_error)?;
+        }
+
+        let mut index = 0;
+
+        while let Some(outgoing) = self.store.outgoing(index).await.map_err(store_error)? {
+            self.resend(outgoing).await?;
+
+            index += 1;
+        }
+
+        Ok(session_present)
+    }
+
+    /// Encode the answer of the `Authenticator` to the AUTH challenge which was just received
+    ///
+    /// Returns the length of the encoded AUTH packet.
+    #[cfg(feature = "v5")]
+    fn answer_auth(&mut self) -> Result<usize, Error<T::Error>> {
+        let version = self.config.version;
+
+        let Packet::Auth(challenge) = Packet::decode(&self.rx_buf[..self.rx_consumed], version)?.0
+        else {
+            unreachable!();
+        };
+
+        let properties = self
+            .authenticator
+            .as_mut()
+            .and_then(|authenticator| authenticator.authenticate(challenge.properties))
+            .ok_or(Error::Auth)?;
+
+        let len = Packet::Auth(Reason {
+            code: CONTINUE_AUTHENTICATION,
+            properties,
+        })
+        .encode(self.tx_buf, version)?;
+
+        Ok(len)
+    }
+
+    async fn send_publish(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+        properties: Properties<'_>,
+    ) -> Result<u16, Error<T::Error>> {
+        let id = if qos == QoS::AtMostOnce {
+            0
+        } else {
+            self.next_id().await?
+        };
+
+        let publish = Publish {
+            dup: false,
+            qos,
+            retain,
+            topic,
+            id,
+            payload,
+            properties,
+        };
+
+        let len = Packet::Publish(publish).encode(self.tx_buf, self.config.version)?;
+
+        if qos != QoS::AtMostOnce {
+            let now = self.clock.now();
+
+            let outgoing = Outgoing {
+                id,
+                qos,
+                released: false,
+                sent_at: now,
+                expires_at: self.config.message_expiry_ms.map(|expiry| now + expiry),
+            };
+
+            self.store
+                .push(&outgoing, &self.tx_buf[..len])
+                .await
+                .map_err(store_error)?;
+
+            if self.io.is_none() {
+                return Ok(id);
+            }
+        }
+
+        // The stored message keeps its topic, as the topic aliases do not survive reconnecting
+        #[cfg(feature = "v5")]
+        let len = {
+            let alias = if self.io.is_some() && properties.topic_alias().is_none() {
+                self.aliases.outgoing(topic)
+            } else {
+                None
+            };
+
+            if let Some((alias, known)) = alias {
+                let topic = if known { "" } else { topic };
+
+                Packet::Publish(Publish { topic, ..publish }).encode_aliased(
+                    self.tx_buf,
+                    self.config.version,
+                    Some(alias),
+                )?
+            } else {
+                len
+            }
+        };
+
+        self.write(len).await?;
+
+        Ok(id)
+    }
+
+    async fn send_subscribe(
+        &mut self,
+        filters: &[(&str, QoS)],
+        properties: Properties<'_>,
+    ) -> Result<u16, Error<T::Error>> {
+        let id = self.next_id().await?;
+
+        self.send(&Packet::Subscribe {
+            id,
+            filters,
+            properties,
+        })
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Drop the expired messages, and send the retransmissions and the keepalive ping when due
+    async fn process_timers(&mut self) -> Result<Timers, Error<T::Error>> {
+        let now = self.clock.now();
+
+        let mut deadline = None;
+        let mut index = 0;
+
+        while let Some(outgoing) = self.store.outgoing(index).await.map_err(store_error)? {
+            // Once the broker received a QoS 2 message, it is too late for it to expire
+            if let Some(expires_at) = outgoing.expires_at.filter(|_| !outgoing.released) {
+                if expires_at <= now {
+                    self.store.remove(outgoing.id).await.map_err(store_error)?;
+
+                    return Ok(Timers::Expired(outgoing.id));
+                }
+
+                deadline = min_deadline(deadline, expires_at);
+            }
+
+            if let Some(timeout) = self.config.retransmit_timeout_ms {
+                if self.io.is_some() && outgoing.sent_at + timeout <= now {
+                    debug!("Re-sending unacknowledged message {}", outgoing.id);
+
+                    self.resend(outgoing).await?;
+
+                    deadline = min_deadline(deadline, now + timeout);
+                } else {
+                    deadline = min_deadline(deadline, outgoing.sent_at + timeout);
+                }
+            }
+
+            index += 1;
+        }
+
+        if self.io.is_none() {
+            Err(Error::NotConnected)?;
+        }
+
+        if self.keep_alive_ms > 0 {
+            if let Some(ping_sent) = self.ping_sent {
+                if ping_sent + self.keep_alive_ms <= now {
+                    self.io = None;
+
+                    Err(Error::Timeout)?;
+                }
+            } else if self.last_sent + self.keep_alive_ms <= now {
+                self.send(&Packet::PingReq).await?;
+                self.ping_sent = Some(now);
+            }
+
+            let keep_alive = self.ping_sent.unwrap_or(self.last_sent) + self.keep_alive_ms;
+
+            deadline = min_deadline(deadline, keep_alive);
+        }
+
+        Ok(Timers::Wait(deadline))
+    }
+
+    /// Re-send an outgoing message - with the DUP flag set - or only its PUBREL, if the broker
+    /// already received it, and mark it as sent now
+    async fn resend(&mut self, mut outgoing: Outgoing) -> Result<(), Error<T::Error>> {
+        if outgoing.released {
+            self.send(&Packet::PubRel(Ack::new(outgoing.id))).await?;
+        } else {
+            let Some(len) = self
+                .store
+                .load(outgoing.id, self.tx_buf)
+                .await
+                .map_err(store_error)?
+            else {
+                return Ok(());
+            };
+
+            self.tx_buf[0] |= 0x08;
+
+            self.write(len).await?;
+        }
+
+        outgoing.sent_at = self.clock.now();
+
+        self.store.update(&outgoing).await.map_err(store_error)
+    }
+
+    /// Receive the next packet into the receive buffer, dropping the previously returned one
+    ///
+    /// Returns the length of the packet, or `None` if `deadline` was reached first.
+    async fn recv(&mut self, deadline: Option<u64>) -> Result<Option<usize>, Error<T::Error>> {
+        if self.rx_consumed > 0 {
+            self.rx_buf.copy_within(self.rx_consumed..self.rx_len, 0);
+            self.rx_len -= self.rx_consumed;
+            self.rx_consumed = 0;
+        }
+
+        loop {
+            // A packet which cannot be decoded cannot be skipped either, so the connection is dropped
+            match Packet::decode(&self.rx_buf[..self.rx_len], self.config.version) {
+                Ok((_, len)) => break Ok(Some(len)),
+                Err(mqtt::Error::Incomplete) if self.rx_len == self.rx_buf.len() => {
+                    self.io = None;
+
+                    Err(mqtt::Error::BufferOverflow)?
+                }
+                Err(mqtt::Error::Incomplete) => (),
+                Err(err) => {
+                    self.io = None;
+
+                    Err(err)?
+                }
+            }
+
+            let io = self.io.as_mut().ok_or(Error::NotConnected)?;
+            let read = io.read(&mut self.rx_buf[self.rx_len..]);
+
+            let result = if let Some(deadline) = deadline {
+                match select(read, self.clock.wait_until(deadline)).await {
+                    Either::First(result) => result,
+                    Either::Second(_) => break Ok(None),
+                }
+            } else {
+                read.await
+            };
+
+            let len = result.map_err(|err| {
+                self.io = None;
+
+                Error::Io(err)
+            })?;
+
+            if len == 0 {
+                self.io = None;
+
+                Err(Error::Disconnected)?;
+            }
+
+            self.rx_len += len;
+        }
+    }
+
+    async fn send(&mut self, packet: &Packet<'_>) -> Result<(), Error<T::Error>> {
+        let len = packet.encode(self.tx_buf, self.config.version)?;
+
+        self.write(len).await
+    }
+
+    async fn write(&mut self, len: usize) -> Result<(), Error<T::Error>> {
+        let io = self.io.as_mut().ok_or(Error::NotConnected)?;
+
+        let result = match io.write_all(&self.tx_buf[..len]).await {
+            Ok(()) => io.flush().await,
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            self.io = None;
+
+            Err(Error::Io(err))?;
+        }
+
+        self.last_sent = self.clock.now();
+
+        Ok(())
+    }
+
+    /// Return the outgoing message with packet identifier `id`
+    async fn find(&mut self, id: u16) -> Result<Option<Outgoing>, Error<T::Error>> {
+        let mut index = 0;
+
+        while let Some(outgoing) = self.store.outgoing(index).await.map_err(store_error)? {
+            if outgoing.id == id {
+                return Ok(Some(outgoing));
+            }
+
+            index += 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Allocate a packet identifier which is not used by any of the outgoing messages in the store
+    async fn next_id(&mut self) -> Result<u16, Error<T::Error>> {
+        loop {
+            self.last_id = self.last_id.wrapping_add(1).max(1);
+
+            if self.find(self.last_id).await?.is_none() {
+                break Ok(self.last_id);
+            }
+        }
+    }
+}
+
+/// The event reporting the completion of the delivery of a message, with reason code `code`
+fn completed(id: u16, code: u8) -> Event<'static> {
+    if code < 0x80 {
+        Event::Published(id)
+    } else {
+        Event::Rejected { id, code }
+    }
+}
+
+fn min_deadline(deadline: Option<u64>, other: u64) -> Option<u64> {
+    Some(
+        deadline
+            .map(|deadline| deadline.min(other))
+            .unwrap_or(other),
+    )
+}
+
+fn store_error<E, SE>(err: SE) -> Error<E>
+where
+    SE: Debug,
+{
+    warn!("Session store error: {err:?}");
+
+    Error::Store
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use embedded_io_async::{ErrorType, Read, Write};
+
+    use super::*;
+
+    /// A clock which jumps straight to the deadline it is asked to wait for
+    struct TestClock(Cell<u64>);
+
+    impl Clock for TestClock {
+        fn now(&self) -> u64 {
+            self.0.get()
+        }
+
+        async fn wait_until(&self, deadline: u64) {
+            if self.0.get() < deadline {
+                self.0.set(deadline);
+            }
+        }
+    }
+
+    /// A transport which returns the scripted input and records the output;
+    /// once the input is exhausted, reading blocks forever
+    struct TestIo<'a> {
+        input: &'a [u8],
+        output: [u8; 256],
+        output_len: usize,
+    }
+
+    impl<'a> TestIo<'a> {
+        fn new(input: &'a [u8]) -> Self {
+            Self {
+                input,
+                output: [0; 256],
+                output_len: 0,
+            }
+        }
+    }
+
+    impl ErrorType for TestIo<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for TestIo<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.input.is_empty() {
+                core::future::pending().await
+            }
+
+            let len = buf.len().min(self.input.len());
+
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input = &self.input[len..];
+
+            Ok(len)
+        }
+    }
+
+    impl Write for TestIo<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output[self.output_len..self.output_len + buf.len()].copy_from_slice(buf);
+            self.output_len += buf.len();
+
+            Ok(buf.len())
+        }
+    }
+
+    const CONNACK: &[u8] = b"\x20\x02\x00\x00";
+
+    #[test]
+    fn test_keepalive() {
+        let clock = TestClock(Cell::new(0));
+        let mut io = TestIo::new(CONNACK);
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            Config::new(),
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        let connect = Connect {
+            keep_alive_secs: 10,
+            ..Connect::new("dev")
+        };
+
+        assert_eq!(block_on(client.connect(&mut io, &connect)), Ok(false));
+        assert_eq!(block_on(client.next()), Err(Error::Timeout));
+        assert!(!client.is_connected());
+
+        drop(client);
+
+        // PINGREQ after one keepalive interval without traffic, and the timeout
+        // after another interval without PINGRESP
+        assert_eq!(clock.now(), 20_000);
+        assert!(io.output[..io.output_len].ends_with(b"\xc0\x00"));
+    }
+
+    #[test]
+    fn test_retransmission_and_expiry() {
+        let clock = TestClock(Cell::new(0));
+        let mut io = TestIo::new(CONNACK);
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let config = Config {
+            retransmit_timeout_ms: Some(1000),
+            message_expiry_ms: Some(2500),
+            ..Config::new()
+        };
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            config,
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        let connect = Connect {
+            keep_alive_secs: 0,
+            ..Connect::new("dev")
+        };
+
+        assert_eq!(block_on(client.connect(&mut io, &connect)), Ok(false));
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtLeastOnce, false, b"x")),
+            Ok(1)
+        );
+        assert_eq!(block_on(client.next()), Ok(Event::Expired(1)));
+
+        drop(client);
+
+        assert_eq!(clock.now(), 2500);
+
+        let connect_len = 2 + 15;
+        assert_eq!(
+            &io.output[connect_len..io.output_len],
+            b"\x32\x06\x00\x01t\x00\x01x\x3a\x06\x00\x01t\x00\x01x\x3a\x06\x00\x01t\x00\x01x"
+        );
+    }
+
+    #[test]
+    fn test_publish_and_receive() {
+        let clock = TestClock(Cell::new(0));
+        let mut io = TestIo::new(
+            b"\x20\x02\x01\x00\x90\x03\x00\x02\x01\x40\x02\x00\x01\x32\x06\x00\x01t\x00\x07y",
+        );
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            Config::new(),
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        let connect = Connect {
+            clean_session: false,
+            ..Connect::new("dev")
+        };
+
+        assert_eq!(block_on(client.connect(&mut io, &connect)), Ok(true));
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtLeastOnce, false, b"x")),
+            Ok(1)
+        );
+        assert_eq!(
+            block_on(client.subscribe(&[("t", QoS::AtLeastOnce)])),
+            Ok(2)
+        );
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Subscribed { id: 2, codes: &[1] })
+        );
+        assert_eq!(block_on(client.next()), Ok(Event::Published(1)));
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Received(Publish {
+                qos: QoS::AtLeastOnce,
+                id: 7,
+                ..Publish::new("t", b"y")
+            }))
+        );
+
+        drop(client);
+
+        assert!(io.output[..io.output_len].ends_with(b"\x40\x02\x00\x07"));
+    }
+
+    #[test]
+    fn test_qos2_outgoing() {
+        let clock = TestClock(Cell::new(0));
+        let mut io = TestIo::new(b"\x20\x02\x00\x00\x50\x02\x00\x01\x70\x02\x00\x01");
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            Config::new(),
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        assert_eq!(
+            block_on(client.connect(&mut io, &Connect::new("dev"))),
+            Ok(false)
+        );
+        assert_eq!(
+            block_on(client.publish("t", QoS::ExactlyOnce, false, b"x")),
+            Ok(1)
+        );
+        // The PUBREC is answered with PUBREL, and the delivery completes with PUBCOMP
+        assert_eq!(block_on(client.next()), Ok(Event::Published(1)));
+        assert_eq!(block_on(client.store().outgoing(0)), Ok(None));
+
+        drop(client);
+
+        assert!(io.output[..io.output_len].ends_with(b"\x34\x06\x00\x01t\x00\x01x\x62\x02\x00\x01"));
+    }
+
+    #[test]
+    fn test_qos2_incoming() {
+        let clock = TestClock(Cell::new(0));
+        let mut io = TestIo::new(
+            b"\x20\x02\x00\x00\x34\x06\x00\x01t\x00\x05a\x3c\x06\x00\x01t\x00\x05a\x62\x02\x00\x05\x34\x06\x00\x01t\x00\x05b",
+        );
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            Config::new(),
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        assert_eq!(
+            block_on(client.connect(&mut io, &Connect::new("dev"))),
+            Ok(false)
+        );
+
+        let message = |payload| {
+            Event::Received(Publish {
+                qos: QoS::ExactlyOnce,
+                id: 5,
+                ..Publish::new("t", payload)
+            })
+        };
+
+        assert_eq!(block_on(client.next()), Ok(message(b"a")));
+        // The re-delivery (before the PUBREL) is not reported, while the message
+        // with the same packet identifier after the PUBREL is
+        assert_eq!(block_on(client.next()), Ok(message(b"b")));
+
+        drop(client);
+
+        let connect_len = 2 + 15;
+        assert_eq!(
+            &io.output[connect_len..io.output_len],
+            b"\x50\x02\x00\x05\x50\x02\x00\x05\x70\x02\x00\x05\x50\x02\x00\x05"
+        );
+    }
+
+    #[test]
+    fn test_session() {
+        let clock = TestClock(Cell::new(0));
+        let mut store = MemoryStore::<4, 64>::new();
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let connect = Connect {
+            keep_alive_secs: 10,
+            clean_session: false,
+            ..Connect::new("dev")
+        };
+
+        let config = Config {
+            retransmit_timeout_ms: None,
+            ..Config::new()
+        };
+
+        let mut io = TestIo::new(b"\x20\x02\x00\x00\x50\x02\x00\x02");
+
+        let mut client = Client::new(&clock, &mut store, config, &mut rx_buf, &mut tx_buf);
+
+        assert_eq!(block_on(client.connect(&mut io, &connect)), Ok(false));
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtLeastOnce, false, b"x")),
+            Ok(1)
+        );
+        assert_eq!(
+            block_on(client.publish("t", QoS::ExactlyOnce, false, b"y")),
+            Ok(2)
+        );
+        // The PUBREC of the QoS 2 message arrives, but then the connection is lost
+        assert_eq!(block_on(client.next()), Err(Error::Timeout));
+
+        assert_eq!(
+            block_on(store.outgoing(1))
+                .unwrap()
+                .map(|outgoing| outgoing.released),
+            Some(true)
+        );
+
+        // The broker kept the session: the QoS 1 message is re-sent, and only
+        // the PUBREL of the QoS 2 one
+        let mut io = TestIo::new(b"\x20\x02\x01\x00");
+
+        let mut client = Client::new(&clock, &mut store, config, &mut rx_buf, &mut tx_buf);
+
+        assert_eq!(block_on(client.connect(&mut io, &connect)), Ok(true));
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtLeastOnce, false, b"z")),
+            Ok(3)
+        );
+
+        let connect_len = 2 + 15;
+        assert_eq!(
+            &io.output[connect_len..io.output_len],
+            b"\x3a\x06\x00\x01t\x00\x01x\x62\x02\x00\x02\x32\x06\x00\x01t\x00\x03z"
+        );
+
+        // A clean session discards the session state
+        let mut io = TestIo::new(b"\x20\x02\x00\x00");
+
+        let mut client = Client::new(&clock, &mut store, config, &mut rx_buf, &mut tx_buf);
+
+        assert_eq!(
+            block_on(client.connect(&mut io, &Connect::new("dev"))),
+            Ok(false)
+        );
+
+        assert_eq!(io.output_len, connect_len);
+        assert_eq!(block_on(store.outgoing(0)), Ok(None));
+    }
+
+    #[cfg(feature = "v5")]
+    #[test]
+    fn test_v5_topic_aliases() {
+        let clock = TestClock(Cell::new(0));
+        // A CONNACK with a topic alias maximum of 2 and a server keepalive of 5 seconds,
+        // followed by a message which assigns topic alias 1, and one which only carries it
+        let mut io = TestIo::new(
+            b"\x20\x09\x00\x00\x06\x22\x00\x02\x13\x00\x05\x30\x0a\x00\x03a/b\x03\x23\x00\x01x\x30\x07\x00\x00\x03\x23\x00\x01y",
+        );
+        let (mut rx_buf, mut tx_buf, mut aliases_buf) = ([0; 64], [0; 64], [0; 64]);
+
+        let config = Config {
+            version: Version::V5,
+            ..Config::new()
+        };
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            config,
+            &mut rx_buf,
+            &mut tx_buf,
+        )
+        .with_topic_aliases(&mut aliases_buf);
+
+        let connect = Connect {
+            properties: Properties::new(b"\x22\x00\x04"),
+            ..Connect::new("dev")
+        };
+
+        assert_eq!(block_on(client.connect(&mut io, &connect)), Ok(false));
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtMostOnce, false, b"1")),
+            Ok(0)
+        );
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtMostOnce, false, b"2")),
+            Ok(0)
+        );
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtLeastOnce, false, b"3")),
+            Ok(1)
+        );
+
+        // The stored message keeps its topic, for re-sending it after reconnecting
+        let mut buf = [0; 16];
+        let len = block_on(client.store().load(1, &mut buf)).unwrap().unwrap();
+        assert_eq!(&buf[..len], b"\x32\x07\x00\x01t\x00\x01\x003");
+
+        let properties = Properties::new(b"\x23\x00\x01");
+
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Received(Publish {
+                properties,
+                ..Publish::new("a/b", b"x")
+            }))
+        );
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Received(Publish {
+                properties,
+                ..Publish::new("a/b", b"y")
+            }))
+        );
+        // The keepalive interval of the broker applies
+        assert_eq!(block_on(client.next()), Err(Error::Timeout));
+        assert_eq!(clock.now(), 10_000);
+
+        let connect_len = 2 + 19;
+        assert_eq!(
+            &io.output[connect_len..io.output_len],
+            b"\x30\x08\x00\x01t\x03\x23\x00\x011\x30\x07\x00\x00\x03\x23\x00\x012\x32\x09\x00\x00\x00\x01\x03\x23\x00\x013\xc0\x00"
+        );
+    }
+
+    #[cfg(feature = "v5")]
+    #[test]
+    fn test_v5_reason_codes() {
+        let clock = TestClock(Cell::new(0));
+        let mut io = TestIo::new(
+            b"\x20\x03\x00\x00\x00\x40\x03\x00\x01\x10\x50\x03\x00\x02\x87\xb0\x04\x00\x03\x00\x11\xe0\x01\x8e",
+        );
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let config = Config {
+            version: Version::V5,
+            ..Config::new()
+        };
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            config,
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        assert_eq!(
+            block_on(client.connect(&mut io, &Connect::new("dev"))),
+            Ok(false)
+        );
+        assert_eq!(
+            block_on(client.publish("t", QoS::AtLeastOnce, false, b"x")),
+            Ok(1)
+        );
+        assert_eq!(
+            block_on(client.publish("t", QoS::ExactlyOnce, false, b"y")),
+            Ok(2)
+        );
+        assert_eq!(block_on(client.unsubscribe(&["t"])), Ok(3));
+
+        // "No matching subscribers" is a success, while "Not authorized" ends the QoS 2 delivery
+        assert_eq!(block_on(client.next()), Ok(Event::Published(1)));
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Rejected { id: 2, code: 0x87 })
+        );
+        assert_eq!(block_on(client.store().outgoing(0)), Ok(None));
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Unsubscribed {
+                id: 3,
+                codes: &[0x11]
+            })
+        );
+        assert_eq!(block_on(client.next()), Err(Error::ServerDisconnect(0x8e)));
+        assert!(!client.is_connected());
+
+        drop(client);
+
+        // No PUBREL for the rejected message
+        assert!(io.output[..io.output_len].ends_with(b"\xa2\x06\x00\x03\x00\x00\x01t"));
+    }
+
+    /// A connector which hands out the scripted connections
+    struct TestConnector<'a, 'b>(core::slice::IterMut<'a, TestIo<'b>>);
+
+    impl<'a, 'b> Connector for TestConnector<'a, 'b> {
+        type Connection = &'a mut TestIo<'b>;
+
+        async fn connect(&mut self) -> Result<Self::Connection, Infallible> {
+            Ok(self.0.next().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_reconnect() {
+        const DISCONNECT: &[u8] = b"\x20\x02\x00\x00\xe0\x00";
+
+        let clock = TestClock(Cell::new(0));
+        let mut io = [
+            TestIo::new(DISCONNECT),
+            // Refused
+            TestIo::new(b"\x20\x02\x00\x05"),
+            // Session present
+            TestIo::new(b"\x20\x02\x01\x00\xe0\x00"),
+            TestIo::new(CONNACK),
+        ];
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            Config::new(),
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        let subscriptions = [("t", QoS::AtMostOnce)];
+
+        let mut client = ReconnectingClient::new(
+            client,
+            TestConnector(io.iter_mut()),
+            Connect::new("dev"),
+            ReconnectPolicy::new(),
+        )
+        .with_subscriptions(&subscriptions);
+
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Connected {
+                session_present: false
+            })
+        );
+        assert_eq!(block_on(client.next()), Err(Error::ServerDisconnect(0)));
+        assert_eq!(block_on(client.next()), Err(Error::Refused(5)));
+        assert_eq!(clock.now(), 1_000);
+        // The delay doubles after the failed attempt
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Connected {
+                session_present: true
+            })
+        );
+        assert_eq!(clock.now(), 3_000);
+        assert_eq!(block_on(client.next()), Err(Error::ServerDisconnect(0)));
+        // ... and is reset once connected
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Connected {
+                session_present: false
+            })
+        );
+        assert_eq!(clock.now(), 4_000);
+
+        drop(client);
+
+        // The subscriptions are renewed only when the broker did not keep the session
+        let connect_len = 2 + 15;
+        let subscribe: &[u8] = b"\x82\x06\x00\x01\x00\x01t\x00";
+
+        assert_eq!(&io[0].output[connect_len..io[0].output_len], subscribe);
+        assert_eq!(io[2].output_len, connect_len);
+        assert_eq!(
+            &io[3].output[connect_len..io[3].output_len],
+            b"\x82\x06\x00\x02\x00\x01t\x00"
+        );
+    }
+
+    /// An authenticator which answers the challenge "c" with "r"
+    #[cfg(feature = "v5")]
+    struct TestAuthenticator([u8; 16]);
+
+    #[cfg(feature = "v5")]
+    impl Authenticator for TestAuthenticator {
+        fn authenticate(&mut self, challenge: Properties<'_>) -> Option<Properties<'_>> {
+            use crate::Property;
+
+            let mut properties = challenge.iter();
+
+            let (
+                Some(Property::AuthenticationMethod(method)),
+                Some(Property::AuthenticationData(b"c")),
+            ) = (properties.next(), properties.next())
+            else {
+                return None;
+            };
+
+            Properties::encode(
+                &[
+                    Property::AuthenticationMethod(method),
+                    Property::AuthenticationData(b"r"),
+                ],
+                &mut self.0,
+            )
+            .ok()
+        }
+    }
+
+    #[cfg(feature = "v5")]
+    #[test]
+    fn test_v5_enhanced_auth() {
+        const ANSWER: &[u8] = b"\xf0\x0a\x18\x08\x15\x00\x01M\x16\x00\x01r";
+
+        let clock = TestClock(Cell::new(0));
+        // A challenge before the CONNACK, and another one after the re-authentication starts,
+        // followed by its success
+        let mut io = TestIo::new(
+            b"\xf0\x0a\x18\x08\x15\x00\x01M\x16\x00\x01c\x20\x03\x00\x00\x00\xf0\x0a\x18\x08\x15\x00\x01M\x16\x00\x01c\xf0\x02\x00\x00",
+        );
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+        let mut authenticator = TestAuthenticator([0; 16]);
+
+        let config = Config {
+            version: Version::V5,
+            ..Config::new()
+        };
+
+        let mut client = Client::new(
+            &clock,
+            MemoryStore::<4, 64>::new(),
+            config,
+            &mut rx_buf,
+            &mut tx_buf,
+        )
+        .with_authenticator(&mut authenticator);
+
+        let connect = Connect {
+            properties: Properties::new(b"\x15\x00\x01M"),
+            ..Connect::new("dev")
+        };
+
+        assert_eq!(block_on(client.connect(&mut io, &connect)), Ok(false));
+        assert_eq!(
+            block_on(client.reauthenticate(Properties::new(b"\x15\x00\x01M\x16\x00\x01i"))),
+            Ok(())
+        );
+        assert_eq!(block_on(client.next()), Ok(Event::Authenticated));
+
+        drop(client);
+
+        let connect_len = 2 + 20;
+        let output = &io.output[connect_len..io.output_len];
+
+        let (answer, output) = output.split_at(ANSWER.len());
+        let (reauthenticate, answer2) = output.split_at(ANSWER.len());
+
+        assert_eq!(answer, ANSWER);
+        assert_eq!(
+            reauthenticate,
+            b"\xf0\x0a\x19\x08\x15\x00\x01M\x16\x00\x01i"
+        );
+        assert_eq!(answer2, ANSWER);
+    }
+}
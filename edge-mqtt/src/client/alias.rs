@@ -0,0 +1,129 @@
+use core::str;
+
+const INCOMING: u8 = 0;
+const OUTGOING: u8 = 1;
+
+/// The length of the direction, the alias and the topic length which precede the topic of each entry
+const ENTRY_HEADER_LEN: usize = 5;
+
+/// The topic aliases of an MQTT 5 connection, in both directions
+///
+/// The aliases are kept without allocating, as a sequence of entries - the direction, the alias,
+/// and the length-prefixed topic - in the buffer passed to `Client::with_topic_aliases`.
+pub(super) struct TopicAliases<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+    /// The highest outgoing alias the broker accepts (the topic alias maximum of its CONNACK)
+    outgoing_max: u16,
+    outgoing_len: u16,
+}
+
+impl<'b> TopicAliases<'b> {
+    pub(super) fn new(buf: &'b mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            outgoing_max: 0,
+            outgoing_len: 0,
+        }
+    }
+
+    /// Forget all aliases, as these only live as long as the connection
+    pub(super) fn reset(&mut self, outgoing_max: u16) {
+        self.len = 0;
+        self.outgoing_max = outgoing_max;
+        self.outgoing_len = 0;
+    }
+
+    /// Return the alias for publishing to `topic`, and whether the broker already knows it, or it needs
+    /// to be sent along with the topic
+    ///
+    /// New aliases are assigned until the topic alias maximum of the broker is reached, or the buffer is full;
+    /// from then on, only the topics which already have an alias are published with one.
+    pub(super) fn outgoing(&mut self, topic: &str) -> Option<(u16, bool)> {
+        if topic.is_empty() {
+            return None;
+        }
+
+        if let Some((_, alias, _)) = self.find(OUTGOING, |_, entry| entry == topic) {
+            return Some((alias, true));
+        }
+
+        if self.outgoing_len < self.outgoing_max
+            && self.push(OUTGOING, self.outgoing_len + 1, topic)
+        {
+            self.outgoing_len += 1;
+
+            Some((self.outgoing_len, false))
+        } else {
+            None
+        }
+    }
+
+    /// Return the topic of the incoming alias `alias`
+    pub(super) fn incoming(&self, alias: u16) -> Option<&str> {
+        self.find(INCOMING, |entry, _| entry == alias)
+            .map(|(offset, _, len)| {
+                str::from_utf8(&self.buf[offset + ENTRY_HEADER_LEN..][..len]).unwrap()
+            })
+    }
+
+    /// Assign `topic` to the incoming alias `alias`, replacing its previous topic
+    ///
+    /// Returns `false` if there is no room for the topic in the buffer.
+    pub(super) fn set_incoming(&mut self, alias: u16, topic: &str) -> bool {
+        if let Some((offset, _, len)) = self.find(INCOMING, |entry, _| entry == alias) {
+            let end = offset + ENTRY_HEADER_LEN + len;
+
+            self.buf.copy_within(end..self.len, offset);
+            self.len -= end - offset;
+        }
+
+        self.push(INCOMING, alias, topic)
+    }
+
+    /// Return the offset, the alias and the topic length of the first entry in `direction`
+    /// matching `f` (called with the alias and the topic of the entry)
+    fn find<F>(&self, direction: u8, f: F) -> Option<(usize, u16, usize)>
+    where
+        F: Fn(u16, &str) -> bool,
+    {
+        let mut offset = 0;
+
+        while offset < self.len {
+            let entry = &self.buf[offset..];
+
+            let alias = u16::from_be_bytes([entry[1], entry[2]]);
+            let len = u16::from_be_bytes([entry[3], entry[4]]) as usize;
+
+            let topic = str::from_utf8(&entry[ENTRY_HEADER_LEN..][..len]).unwrap();
+
+            if entry[0] == direction && f(alias, topic) {
+                return Some((offset, alias, len));
+            }
+
+            offset += ENTRY_HEADER_LEN + len;
+        }
+
+        None
+    }
+
+    fn push(&mut self, direction: u8, alias: u16, topic: &str) -> bool {
+        let end = self.len + ENTRY_HEADER_LEN + topic.len();
+
+        if end > self.buf.len() || topic.len() > u16::MAX as usize {
+            return false;
+        }
+
+        let entry = &mut self.buf[self.len..end];
+
+        entry[0] = direction;
+        entry[1..3].copy_from_slice(&alias.to_be_bytes());
+        entry[3..5].copy_from_slice(&(topic.len() as u16).to_be_bytes());
+        entry[ENTRY_HEADER_LEN..].copy_from_slice(topic.as_bytes());
+
+        self.len = end;
+
+        true
+    }
+}
@@ -0,0 +1,217 @@
+use core::net::SocketAddr;
+use core::pin::pin;
+
+use edge_nal::TcpConnect;
+
+use embedded_io_async::{ErrorType, Read, Write};
+
+use log::{info, warn};
+
+use crate::{Connect, QoS};
+
+use super::{Client, Clock, Error, Event, Store};
+
+/// Establishes the connections to the broker for a `ReconnectingClient`
+///
+/// Implement it to connect over TLS or WebSocket (see `ws`); `TcpConnector` connects over plain TCP.
+pub trait Connector {
+    /// The connection to the broker
+    type Connection: Read + Write;
+
+    /// Establish a new connection to the broker
+    async fn connect(&mut self)
+        -> Result<Self::Connection, <Self::Connection as ErrorType>::Error>;
+}
+
+/// A `Connector` establishing plain TCP connections to the broker with an `edge-nal` stack
+pub struct TcpConnector<'a, T> {
+    stack: &'a T,
+    addr: SocketAddr,
+}
+
+impl<'a, T> TcpConnector<'a, T>
+where
+    T: TcpConnect,
+{
+    /// Create a new connector to the broker at `addr`
+    pub const fn new(stack: &'a T, addr: SocketAddr) -> Self {
+        Self { stack, addr }
+    }
+}
+
+impl<'a, T> Connector for TcpConnector<'a, T>
+where
+    T: TcpConnect,
+{
+    type Connection = T::Socket<'a>;
+
+    async fn connect(&mut self) -> Result<Self::Connection, T::Error> {
+        self.stack.connect(self.addr).await
+    }
+}
+
+/// The exponential backoff between the connection attempts of a `ReconnectingClient`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReconnectPolicy {
+    /// The time in milliseconds to wait before reconnecting after the connection is lost
+    pub initial_delay_ms: u64,
+    /// The maximum time in milliseconds to wait between the connection attempts; the delay doubles
+    /// after each failed attempt, up to this maximum
+    pub max_delay_ms: u64,
+}
+
+impl ReconnectPolicy {
+    /// Create a new policy, which waits 1 second before reconnecting, and up to 1 minute between the attempts
+    pub const fn new() -> Self {
+        Self {
+            initial_delay_ms: 1_000,
+            max_delay_ms: 60_000,
+        }
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `Client` which keeps itself connected to the broker
+///
+/// Each call of `next` which finds the client disconnected establishes a new connection with the `Connector`
+/// and connects the client over it, after a delay which grows exponentially with the failed attempts (see
+/// `ReconnectPolicy`), and which is measured with the `Clock` of the client. Once connected, the subscriptions
+/// passed to `with_subscriptions` are renewed - unless the broker kept the session - and `Event::Connected`
+/// is reported.
+///
+/// The errors - of the connection attempts as well as of the connection - are returned by `next` as usual,
+/// so it is simply called in a loop, which only logs the errors.
+///
+/// The messages are published with the wrapped client, see `client`.
+pub struct ReconnectingClient<'b, 'c, N, C, S>
+where
+    N: Connector,
+{
+    client: Client<'b, N::Connection, C, S>,
+    connector: N,
+    connect: Connect<'c>,
+    subscriptions: &'c [(&'c str, QoS)],
+    policy: ReconnectPolicy,
+    /// The delay before the last scheduled connection attempt, if any
+    delay: Option<u64>,
+    /// The time of the next connection attempt, if it has to wait
+    retry_at: Option<u64>,
+    connected: bool,
+}
+
+impl<'b, 'c, N, C, S> ReconnectingClient<'b, 'c, N, C, S>
+where
+    N: Connector,
+    C: Clock,
+    S: Store,
+{
+    /// Create a new reconnecting client
+    ///
+    /// Parameters:
+    /// - `client`: The wrapped, not yet connected client
+    /// - `connector`: The connector establishing the connections to the broker
+    /// - `connect`: The CONNECT packet sent over each connection, with the last will and the credentials
+    /// - `policy`: The backoff between the connection attempts
+    pub fn new(
+        client: Client<'b, N::Connection, C, S>,
+        connector: N,
+        connect: Connect<'c>,
+        policy: ReconnectPolicy,
+    ) -> Self {
+        Self {
+            client,
+            connector,
+            connect,
+            subscriptions: &[],
+            policy,
+            delay: None,
+            retry_at: None,
+            connected: false,
+        }
+    }
+
+    /// Subscribe to `subscriptions` after each connection with which the broker did not keep the session
+    pub fn with_subscriptions(self, subscriptions: &'c [(&'c str, QoS)]) -> Self {
+        Self {
+            subscriptions,
+            ..self
+        }
+    }
+
+    /// Return the wrapped client, i.e. for publishing
+    pub fn client(&mut self) -> &mut Client<'b, N::Connection, C, S> {
+        &mut self.client
+    }
+
+    /// Wait for the next event, reconnecting first if the client is disconnected
+    ///
+    /// See `Client::next` for the details.
+    pub async fn next(&mut self) -> Result<Event<'_>, Error<<N::Connection as ErrorType>::Error>> {
+        if !self.client.is_connected() {
+            if self.connected {
+                warn!("Connection lost");
+
+                self.connected = false;
+                self.backoff();
+            }
+
+            let result = {
+                let reconnect = pin!(self.reconnect());
+
+                reconnect.await
+            };
+
+            let session_present = match result {
+                Ok(session_present) => session_present,
+                Err(err) => {
+                    self.backoff();
+
+                    return Err(err);
+                }
+            };
+
+            info!("Connected, session present: {session_present}");
+
+            self.connected = true;
+            self.delay = None;
+            self.retry_at = None;
+
+            // An IO error here drops the connection, so the next call reconnects and subscribes again
+            if !session_present && !self.subscriptions.is_empty() {
+                self.client.subscribe(self.subscriptions).await?;
+            }
+
+            return Ok(Event::Connected { session_present });
+        }
+
+        self.client.next().await
+    }
+
+    /// Wait for the scheduled connection attempt, and connect
+    async fn reconnect(&mut self) -> Result<bool, Error<<N::Connection as ErrorType>::Error>> {
+        if let Some(retry_at) = self.retry_at {
+            self.client.clock.wait_until(retry_at).await;
+        }
+
+        let io = self.connector.connect().await.map_err(Error::Io)?;
+
+        let connect = pin!(self.client.connect(io, &self.connect));
+
+        connect.await
+    }
+
+    /// Schedule the next connection attempt, doubling the delay of the previous one
+    fn backoff(&mut self) {
+        let delay = self.delay.map_or(self.policy.initial_delay_ms, |delay| {
+            (delay * 2).min(self.policy.max_delay_ms)
+        });
+
+        self.delay = Some(delay);
+        self.retry_at = Some(self.client.clock.now() + delay);
+    }
+}
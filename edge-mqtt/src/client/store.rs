@@ -0,0 +1,244 @@
+use core::fmt::{self, Debug};
+
+use crate::QoS;
+
+/// The state of an outgoing QoS 1 or QoS 2 message which is not completed yet
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Outgoing {
+    /// The packet identifier of the message
+    pub id: u16,
+    pub qos: QoS,
+    /// Whether the broker already received the QoS 2 message (i.e. it answered with PUBREC),
+    /// so that only the PUBREL of the message is (re-)sent
+    pub released: bool,
+    /// When the message - or its PUBREL - was last sent, as returned by the `Clock` of the client
+    pub sent_at: u64,
+    /// When the message expires, unless the broker receives it before that
+    pub expires_at: Option<u64>,
+}
+
+/// The session state of a `Client`:
+/// - The outgoing QoS 1 and QoS 2 messages which are not completed yet, in the order they were published,
+///   together with their encoded PUBLISH packets
+/// - The packet identifiers of the incoming QoS 2 messages which are not released by the broker yet
+///
+/// The state is needed to re-send the messages after reconnecting to the broker, and to de-duplicate
+/// the incoming QoS 2 messages. Implementations might keep it in flash, so that - with `clean_session`
+/// set to `false` - it survives a restart of the device as well, and not just a reconnect.
+pub trait Store {
+    /// The error type of the store, i.e. when there is no room for another message
+    type Error: Debug;
+
+    /// Return the outgoing message at `index`, or `None` if `index` is past the last one
+    async fn outgoing(&mut self, index: usize) -> Result<Option<Outgoing>, Self::Error>;
+
+    /// Copy the PUBLISH packet of the outgoing message with packet identifier `id` into `buf`,
+    /// returning its length, or `None` if there is no such message
+    async fn load(&mut self, id: u16, buf: &mut [u8]) -> Result<Option<usize>, Self::Error>;
+
+    /// Add an outgoing message with its encoded PUBLISH packet
+    async fn push(&mut self, outgoing: &Outgoing, packet: &[u8]) -> Result<(), Self::Error>;
+
+    /// Update the state of the outgoing message with the packet identifier of `outgoing`
+    async fn update(&mut self, outgoing: &Outgoing) -> Result<(), Self::Error>;
+
+    /// Remove the outgoing message with packet identifier `id`, returning its state
+    async fn remove(&mut self, id: u16) -> Result<Option<Outgoing>, Self::Error>;
+
+    /// Record the packet identifier of an incoming QoS 2 message
+    ///
+    /// Returns `false` if the identifier was already recorded, i.e. the message is a duplicate
+    /// which was already delivered.
+    async fn receive(&mut self, id: u16) -> Result<bool, Self::Error>;
+
+    /// Forget the packet identifier of an incoming QoS 2 message, once the broker released it
+    async fn release(&mut self, id: u16) -> Result<(), Self::Error>;
+
+    /// Forget the packet identifiers of all incoming QoS 2 messages, as the broker did not keep the session
+    async fn clear_received(&mut self) -> Result<(), Self::Error>;
+
+    /// Forget the complete session state
+    async fn clear(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<S> Store for &mut S
+where
+    S: Store,
+{
+    type Error = S::Error;
+
+    async fn outgoing(&mut self, index: usize) -> Result<Option<Outgoing>, Self::Error> {
+        (**self).outgoing(index).await
+    }
+
+    async fn load(&mut self, id: u16, buf: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        (**self).load(id, buf).await
+    }
+
+    async fn push(&mut self, outgoing: &Outgoing, packet: &[u8]) -> Result<(), Self::Error> {
+        (**self).push(outgoing, packet).await
+    }
+
+    async fn update(&mut self, outgoing: &Outgoing) -> Result<(), Self::Error> {
+        (**self).update(outgoing).await
+    }
+
+    async fn remove(&mut self, id: u16) -> Result<Option<Outgoing>, Self::Error> {
+        (**self).remove(id).await
+    }
+
+    async fn receive(&mut self, id: u16) -> Result<bool, Self::Error> {
+        (**self).receive(id).await
+    }
+
+    async fn release(&mut self, id: u16) -> Result<(), Self::Error> {
+        (**self).release(id).await
+    }
+
+    async fn clear_received(&mut self) -> Result<(), Self::Error> {
+        (**self).clear_received().await
+    }
+
+    async fn clear(&mut self) -> Result<(), Self::Error> {
+        (**self).clear().await
+    }
+}
+
+/// The error returned by `MemoryStore`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MemoryStoreError {
+    /// There is no room for another message
+    Full,
+    /// The PUBLISH packet does not fit in the store
+    TooLarge,
+}
+
+impl fmt::Display for MemoryStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "Store full"),
+            Self::TooLarge => write!(f, "Packet too large"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MemoryStoreError {}
+
+struct Entry<const P: usize> {
+    outgoing: Outgoing,
+    len: usize,
+    packet: [u8; P],
+}
+
+/// A `Store` which keeps the session state in memory, so that it survives reconnects, but not restarts
+///
+/// It fits up to `N` outgoing messages with PUBLISH packets of up to `P` bytes,
+/// and up to `N` incoming QoS 2 messages.
+pub struct MemoryStore<const N: usize = 4, const P: usize = 256> {
+    outgoing: heapless::Vec<Entry<P>, N>,
+    received: heapless::Vec<u16, N>,
+}
+
+impl<const N: usize, const P: usize> MemoryStore<N, P> {
+    /// Create a new, empty store
+    pub const fn new() -> Self {
+        Self {
+            outgoing: heapless::Vec::new(),
+            received: heapless::Vec::new(),
+        }
+    }
+
+    fn position(&self, id: u16) -> Option<usize> {
+        self.outgoing
+            .iter()
+            .position(|entry| entry.outgoing.id == id)
+    }
+}
+
+impl<const N: usize, const P: usize> Default for MemoryStore<N, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const P: usize> Store for MemoryStore<N, P> {
+    type Error = MemoryStoreError;
+
+    async fn outgoing(&mut self, index: usize) -> Result<Option<Outgoing>, Self::Error> {
+        Ok(self.outgoing.get(index).map(|entry| entry.outgoing))
+    }
+
+    async fn load(&mut self, id: u16, buf: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        let Some(entry) = self.position(id).map(|index| &self.outgoing[index]) else {
+            return Ok(None);
+        };
+
+        let buf = buf.get_mut(..entry.len).ok_or(MemoryStoreError::TooLarge)?;
+
+        buf.copy_from_slice(&entry.packet[..entry.len]);
+
+        Ok(Some(entry.len))
+    }
+
+    async fn push(&mut self, outgoing: &Outgoing, packet: &[u8]) -> Result<(), Self::Error> {
+        if packet.len() > P {
+            Err(MemoryStoreError::TooLarge)?;
+        }
+
+        let mut entry = Entry {
+            outgoing: *outgoing,
+            len: packet.len(),
+            packet: [0; P],
+        };
+
+        entry.packet[..packet.len()].copy_from_slice(packet);
+
+        self.outgoing
+            .push(entry)
+            .map_err(|_| MemoryStoreError::Full)
+    }
+
+    async fn update(&mut self, outgoing: &Outgoing) -> Result<(), Self::Error> {
+        if let Some(index) = self.position(outgoing.id) {
+            self.outgoing[index].outgoing = *outgoing;
+        }
+
+        Ok(())
+    }
+
+    async fn remove(&mut self, id: u16) -> Result<Option<Outgoing>, Self::Error> {
+        Ok(self
+            .position(id)
+            .map(|index| self.outgoing.remove(index).outgoing))
+    }
+
+    async fn receive(&mut self, id: u16) -> Result<bool, Self::Error> {
+        if self.received.contains(&id) {
+            Ok(false)
+        } else {
+            self.received.push(id).map_err(|_| MemoryStoreError::Full)?;
+
+            Ok(true)
+        }
+    }
+
+    async fn release(&mut self, id: u16) -> Result<(), Self::Error> {
+        self.received.retain(|received| *received != id);
+
+        Ok(())
+    }
+
+    async fn clear_received(&mut self) -> Result<(), Self::Error> {
+        self.received.clear();
+
+        Ok(())
+    }
+
+    async fn clear(&mut self) -> Result<(), Self::Error> {
+        self.outgoing.clear();
+        self.received.clear();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,488 @@
+//! MQTT over WebSocket, i.e. for brokers which only accept connections on port 443
+//!
+//! The connection to the broker - plain, or TLS with the `edge-nal` `TlsUpgrade` trait - is first upgraded
+//! to a WebSocket connection with the `mqtt` subprotocol by `upgrade`, and then wrapped in `WsTransport`,
+//! which carries the MQTT packets in binary WebSocket frames, and which the `Client` can run over.
+
+use core::cmp::min;
+use core::str;
+
+use edge_http::ws::{is_upgrade_accepted, upgrade_request_headers, MAX_BASE64_KEY_LEN};
+use edge_http::ws::{MAX_BASE64_KEY_RESPONSE_LEN, NONCE_LEN};
+
+use edge_nal::WriteVectored;
+use edge_ws::io::{send, Error};
+
+use edge_ws::{FrameHeader, FrameType};
+
+use embedded_io_async::{ErrorType, Read, Write};
+
+/// The WebSocket subprotocol of MQTT
+pub const SUBPROTOCOL: &str = "mqtt";
+
+/// The maximum number of headers in the upgrade response of the broker
+const MAX_RESPONSE_HEADERS: usize = 16;
+
+/// The maximum length of the payload of a WebSocket control frame
+const MAX_CONTROL_PAYLOAD_LEN: usize = 125;
+
+/// Upgrade `socket` - a plain or TLS connection to the broker - to a WebSocket connection
+/// with the `mqtt` subprotocol
+///
+/// Parameters:
+/// - `socket`: The connection to the broker
+/// - `host`: The value of the `Host` header, i.e. the name of the broker
+/// - `path`: The path of the MQTT endpoint of the broker, usually `/mqtt`
+/// - `nonce`: A random nonce for the `Sec-WebSocket-Key` header
+/// - `buf`: A buffer for the upgrade request and response, which should fit the response headers of the broker
+///
+/// Returns `Error::Invalid` if the broker did not accept the upgrade, or did not select the `mqtt` subprotocol.
+pub async fn upgrade<T>(
+    mut socket: T,
+    host: &str,
+    path: &str,
+    nonce: &[u8; NONCE_LEN],
+    buf: &mut [u8],
+) -> Result<(), Error<T::Error>>
+where
+    T: Read + Write,
+{
+    let mut key_buf = [0; MAX_BASE64_KEY_LEN];
+
+    let headers = upgrade_request_headers(Some(host), None, None, nonce, &mut key_buf);
+
+    let mut len = 0;
+    let mut append = |data: &[u8]| -> Result<(), Error<T::Error>> {
+        let end = len + data.len();
+
+        buf.get_mut(len..end)
+            .ok_or(Error::BufferOverflow)?
+            .copy_from_slice(data);
+
+        len = end;
+
+        Ok(())
+    };
+
+    for data in [b"GET ", path.as_bytes(), b" HTTP/1.1\r\n"] {
+        append(data)?;
+    }
+
+    for (name, value) in headers
+        .iter()
+        .filter(|(name, _)| !name.is_empty())
+        .chain(core::iter::once(&("Sec-WebSocket-Protocol", SUBPROTOCOL)))
+    {
+        for data in [name.as_bytes(), b": ", value.as_bytes(), b"\r\n"] {
+            append(data)?;
+        }
+    }
+
+    append(b"\r\n")?;
+
+    socket.write_all(&buf[..len]).await.map_err(Error::Io)?;
+    socket.flush().await.map_err(Error::Io)?;
+
+    // Read the response byte by byte, so as not to consume anything past its headers
+    let mut len = 0;
+
+    while !buf[..len].ends_with(b"\r\n\r\n") {
+        let byte = buf.get_mut(len..len + 1).ok_or(Error::BufferOverflow)?;
+
+        socket.read_exact(byte).await?;
+
+        len += 1;
+    }
+
+    let mut headers = [httparse::EMPTY_HEADER; MAX_RESPONSE_HEADERS];
+    let mut response = httparse::Response::new(&mut headers);
+
+    if !matches!(
+        response.parse(&buf[..len]),
+        Ok(httparse::Status::Complete(_))
+    ) {
+        Err(Error::Invalid)?;
+    }
+
+    let headers = response
+        .headers
+        .iter()
+        .map(|header| (header.name, str::from_utf8(header.value).unwrap_or("")));
+
+    let subprotocol = headers.clone().any(|(name, value)| {
+        name.eq_ignore_ascii_case("Sec-WebSocket-Protocol")
+            && value.eq_ignore_ascii_case(SUBPROTOCOL)
+    });
+
+    let mut accept_buf = [0; MAX_BASE64_KEY_RESPONSE_LEN];
+
+    if !subprotocol
+        || !is_upgrade_accepted(response.code.unwrap_or(0), headers, nonce, &mut accept_buf)
+    {
+        Err(Error::Invalid)?;
+    }
+
+    Ok(())
+}
+
+/// A transport carrying the MQTT packets in binary WebSocket frames, over a connection
+/// which was already upgraded with `upgrade`
+///
+/// Each write is sent as a single masked binary frame. Pings of the broker are answered with pongs,
+/// and a Close frame of the broker is reported as the end of the stream.
+///
+/// Reading is cancel-safe - as long as reading from the wrapped connection is - except while
+/// the (small) payload of a control frame is being received.
+pub struct WsTransport<T, M> {
+    io: T,
+    mask_gen: M,
+    header_buf: [u8; FrameHeader::MAX_LEN],
+    header_len: usize,
+    remaining: u64,
+    mask_key: Option<u32>,
+    payload_offset: usize,
+}
+
+impl<T, M> WsTransport<T, M>
+where
+    T: Read + WriteVectored,
+    M: FnMut() -> u32,
+{
+    /// Create a new transport
+    ///
+    /// Parameters:
+    /// - `io`: The upgraded connection
+    /// - `mask_gen`: A closure generating the (random) mask keys of the sent frames
+    pub fn new(io: T, mask_gen: M) -> Self {
+        Self {
+            io,
+            mask_gen,
+            header_buf: [0; FrameHeader::MAX_LEN],
+            header_len: 0,
+            remaining: 0,
+            mask_key: None,
+            payload_offset: 0,
+        }
+    }
+
+    /// Return the wrapped connection
+    pub fn release(self) -> T {
+        self.io
+    }
+
+    /// Receive the header of the next frame, keeping the bytes received so far across cancellations
+    async fn recv_header(&mut self) -> Result<Option<FrameHeader>, Error<T::Error>> {
+        loop {
+            match FrameHeader::deserialize(&self.header_buf[..self.header_len]) {
+                Ok((header, _)) => {
+                    self.header_len = 0;
+
+                    break Ok(Some(header));
+                }
+                Err(Error::Incomplete(more)) => {
+                    let end = self.header_len + more;
+
+                    let len = self
+                        .io
+                        .read(&mut self.header_buf[self.header_len..end])
+                        .await
+                        .map_err(Error::Io)?;
+
+                    if len == 0 {
+                        if self.header_len > 0 {
+                            Err(Error::Invalid)?;
+                        }
+
+                        break Ok(None);
+                    }
+
+                    self.header_len += len;
+                }
+                Err(err) => Err(err.recast())?,
+            }
+        }
+    }
+}
+
+impl<T, M> ErrorType for WsTransport<T, M>
+where
+    T: ErrorType,
+{
+    type Error = Error<T::Error>;
+}
+
+impl<T, M> Read for WsTransport<T, M>
+where
+    T: Read + WriteVectored,
+    M: FnMut() -> u32,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.remaining > 0 {
+                let len = min(buf.len() as u64, self.remaining) as usize;
+                let len = self.io.read(&mut buf[..len]).await.map_err(Error::Io)?;
+
+                if len == 0 {
+                    Err(Error::Invalid)?;
+                }
+
+                FrameHeader::mask_with(&mut buf[..len], self.mask_key, self.payload_offset);
+
+                self.payload_offset += len;
+                self.remaining -= len as u64;
+
+                break Ok(len);
+            }
+
+            let Some(header) = self.recv_header().await? else {
+                break Ok(0);
+            };
+
+            match header.frame_type {
+                FrameType::Binary(_) | FrameType::Continue(_) => {
+                    self.remaining = header.payload_len;
+                    self.mask_key = header.mask_key;
+                    self.payload_offset = 0;
+                }
+                FrameType::Ping | FrameType::Pong | FrameType::Close => {
+                    let mut payload_buf = [0; MAX_CONTROL_PAYLOAD_LEN];
+                    let payload = header.recv_payload(&mut self.io, &mut payload_buf).await?;
+
+                    match header.frame_type {
+                        FrameType::Ping => {
+                            send(
+                                &mut self.io,
+                                FrameType::Pong,
+                                Some((self.mask_gen)()),
+                                payload,
+                            )
+                            .await?
+                        }
+                        FrameType::Close => break Ok(0),
+                        _ => (),
+                    }
+                }
+                FrameType::Text(_) => Err(Error::Invalid)?,
+            }
+        }
+    }
+}
+
+impl<T, M> Write for WsTransport<T, M>
+where
+    T: Read + WriteVectored,
+    M: FnMut() -> u32,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        send(
+            &mut self.io,
+            FrameType::Binary(false),
+            Some((self.mask_gen)()),
+            buf,
+        )
+        .await?;
+
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.io.flush().await.map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use crate::client::{Client, Clock, Config, Event, MemoryStore};
+    use crate::{Connect, Publish};
+
+    use super::*;
+
+    /// A clock which never reaches any deadline
+    struct TestClock;
+
+    impl Clock for TestClock {
+        fn now(&self) -> u64 {
+            0
+        }
+
+        async fn wait_until(&self, _deadline: u64) {
+            core::future::pending().await
+        }
+    }
+
+    /// A transport which returns the scripted input in chunks of up to 3 bytes and records the output
+    struct TestIo<'a> {
+        input: &'a [u8],
+        output: [u8; 512],
+        output_len: usize,
+    }
+
+    impl<'a> TestIo<'a> {
+        fn new(input: &'a [u8]) -> Self {
+            Self {
+                input,
+                output: [0; 512],
+                output_len: 0,
+            }
+        }
+
+        fn output(&self) -> &[u8] {
+            &self.output[..self.output_len]
+        }
+    }
+
+    impl ErrorType for TestIo<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for TestIo<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.input.len()).min(3);
+
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input = &self.input[len..];
+
+            Ok(len)
+        }
+    }
+
+    impl Write for TestIo<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output[self.output_len..self.output_len + buf.len()].copy_from_slice(buf);
+            self.output_len += buf.len();
+
+            Ok(buf.len())
+        }
+    }
+
+    impl WriteVectored for TestIo<'_> {}
+
+    // The sample nonce of RFC 6455
+    const NONCE: &[u8; NONCE_LEN] = b"the sample nonce";
+
+    #[test]
+    fn test_upgrade() {
+        let mut io = TestIo::new(
+            b"HTTP/1.1 101 Switching Protocols\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+            Sec-WebSocket-Protocol: mqtt\r\n\
+            \r\n\
+            \x82\x04\x20\x02\x00\x00",
+        );
+        let mut buf = [0; 256];
+
+        assert_eq!(
+            block_on(upgrade(&mut io, "broker", "/mqtt", NONCE, &mut buf)),
+            Ok(())
+        );
+
+        // The first frame is left for the transport
+        assert_eq!(io.input, b"\x82\x04\x20\x02\x00\x00");
+
+        let request = str::from_utf8(io.output()).unwrap();
+
+        assert!(request.starts_with("GET /mqtt HTTP/1.1\r\n"));
+        assert!(request.contains("Host: broker\r\n"));
+        assert!(request.contains("Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n"));
+        assert!(request.contains("Sec-WebSocket-Protocol: mqtt\r\n"));
+        assert!(request.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_upgrade_rejected() {
+        // No `mqtt` subprotocol
+        let mut io = TestIo::new(
+            b"HTTP/1.1 101 Switching Protocols\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+            \r\n",
+        );
+        let mut buf = [0; 256];
+
+        assert_eq!(
+            block_on(upgrade(&mut io, "broker", "/mqtt", NONCE, &mut buf)),
+            Err(Error::Invalid)
+        );
+
+        // No upgrade at all
+        let mut io = TestIo::new(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+
+        assert_eq!(
+            block_on(upgrade(&mut io, "broker", "/mqtt", NONCE, &mut buf)),
+            Err(Error::Invalid)
+        );
+
+        // The request does not fit in the buffer
+        let mut io = TestIo::new(b"HTTP/1.1 101 Switching Protocols\r\n\r\n");
+        let mut buf = [0; 24];
+
+        assert_eq!(
+            block_on(upgrade(&mut io, "b", "/", NONCE, &mut buf[..])),
+            Err(Error::BufferOverflow)
+        );
+    }
+
+    #[test]
+    fn test_transport() {
+        // CONNACK, a ping, and a PUBLISH fragmented over a binary and a continuation frame,
+        // followed by a close frame
+        let mut io = TestIo::new(
+            b"\x82\x04\x20\x02\x00\x00\
+            \x89\x01x\
+            \x02\x03\x30\x05\x00\
+            \x80\x04\x01thi\
+            \x88\x00",
+        );
+        let (mut rx_buf, mut tx_buf) = ([0; 64], [0; 64]);
+
+        let mut client = Client::new(
+            TestClock,
+            MemoryStore::<4, 64>::new(),
+            Config::new(),
+            &mut rx_buf,
+            &mut tx_buf,
+        );
+
+        let transport = WsTransport::new(&mut io, || 0x01020304);
+
+        assert_eq!(
+            block_on(client.connect(transport, &Connect::new("dev"))),
+            Ok(false)
+        );
+        assert_eq!(
+            block_on(client.next()),
+            Ok(Event::Received(Publish::new("t", b"hi")))
+        );
+        assert!(block_on(client.next()).is_err());
+
+        drop(client);
+
+        let mut connect = [0; 17];
+        let len = crate::Packet::Connect(Connect::new("dev"))
+            .encode(&mut connect, crate::Version::V3_1_1)
+            .unwrap();
+
+        assert_eq!(len, connect.len());
+
+        FrameHeader::mask_with(&mut connect, Some(0x01020304), 0);
+
+        let output = io.output();
+
+        // The CONNECT packet in a masked binary frame, followed by the pong
+        assert_eq!(&output[..6], b"\x82\x91\x01\x02\x03\x04");
+        assert_eq!(&output[6..23], &connect);
+        assert_eq!(&output[23..29], b"\x8a\x81\x01\x02\x03\x04");
+        assert_eq!(&output[29..], &[b'x' ^ 0x01]);
+    }
+}
@@ -9,17 +9,86 @@ mod embedded_svc_compat {
         Client, Connection, Details, ErrorType, Event, EventPayload, MessageId, Publish, QoS,
     };
 
-    use log::trace;
+    use std::sync::{Arc, Mutex};
 
-    use rumqttc::{self, AsyncClient, EventLoop, PubAck, SubAck, UnsubAck};
+    use log::{info, trace};
+
+    use rumqttc::{
+        self, AsyncClient, ConnAck, EventLoop, PubAck, PubComp, Request, SubAck, Subscribe,
+        UnsubAck,
+    };
 
     pub use rumqttc::{ClientError, ConnectionError, RecvError};
 
-    pub struct MqttClient(AsyncClient);
+    /// The subscriptions of an `MqttClient`, shared with its `MqttConnection`, which renews them
+    /// after reconnecting to a broker which did not keep the session (i.e. with `clean_session=true`)
+    #[derive(Clone, Default)]
+    pub struct Subscriptions(Arc<Mutex<Vec<(String, rumqttc::QoS)>>>);
+
+    impl Subscriptions {
+        /// Create a new, empty set of subscriptions
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        fn add(&self, topic: &str, qos: rumqttc::QoS) {
+            let mut subscriptions = self.0.lock().unwrap();
+
+            subscriptions.retain(|(t, _)| t != topic);
+            subscriptions.push((topic.to_string(), qos));
+        }
+
+        fn remove(&self, topic: &str) {
+            self.0.lock().unwrap().retain(|(t, _)| t != topic);
+        }
+    }
+
+    /// The policy for the delays between the reconnection attempts of an `MqttConnection`
+    ///
+    /// The delay starts at `initial_delay` after the first failed attempt, and doubles with every further one
+    /// up to `max_delay`. It is reset once the connection to the broker is established again.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct ReconnectPolicy {
+        pub initial_delay: embassy_time::Duration,
+        pub max_delay: embassy_time::Duration,
+    }
+
+    impl ReconnectPolicy {
+        /// Create a new policy with an initial delay of 1 second and a maximum delay of 60 seconds
+        pub const fn new() -> Self {
+            Self {
+                initial_delay: embassy_time::Duration::from_secs(1),
+                max_delay: embassy_time::Duration::from_secs(60),
+            }
+        }
+    }
+
+    impl Default for ReconnectPolicy {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    pub struct MqttClient {
+        client: AsyncClient,
+        subscriptions: Option<Subscriptions>,
+    }
 
     impl MqttClient {
         pub const fn new(client: AsyncClient) -> Self {
-            Self(client)
+            Self {
+                client,
+                subscriptions: None,
+            }
+        }
+
+        /// Record the subscriptions of the client in `subscriptions`, so that the `MqttConnection`
+        /// created with the same `subscriptions` renews them on reconnect
+        pub fn with_subscriptions(self, subscriptions: Subscriptions) -> Self {
+            Self {
+                subscriptions: Some(subscriptions),
+                ..self
+            }
         }
     }
 
@@ -29,13 +98,21 @@ mod embedded_svc_compat {
 
     impl Client for MqttClient {
         async fn subscribe(&mut self, topic: &str, qos: QoS) -> Result<MessageId, Self::Error> {
-            self.0.subscribe(topic, to_qos(qos)).await?;
+            self.client.subscribe(topic, to_qos(qos)).await?;
+
+            if let Some(subscriptions) = &self.subscriptions {
+                subscriptions.add(topic, to_qos(qos));
+            }
 
             Ok(0)
         }
 
         async fn unsubscribe(&mut self, topic: &str) -> Result<MessageId, Self::Error> {
-            self.0.unsubscribe(topic).await?;
+            self.client.unsubscribe(topic).await?;
+
+            if let Some(subscriptions) = &self.subscriptions {
+                subscriptions.remove(topic);
+            }
 
             Ok(0)
         }
@@ -49,7 +126,9 @@ mod embedded_svc_compat {
             retain: bool,
             payload: &[u8],
         ) -> Result<MessageId, Self::Error> {
-            self.0.publish(topic, to_qos(qos), retain, payload).await?;
+            self.client
+                .publish(topic, to_qos(qos), retain, payload)
+                .await?;
 
             Ok(0)
         }
@@ -67,9 +146,14 @@ mod embedded_svc_compat {
                 Ok(event) => match event {
                     rumqttc::Event::Incoming(incoming) => match incoming {
                         rumqttc::Packet::Connect(_) => Some(EventPayload::BeforeConnect),
-                        rumqttc::Packet::ConnAck(_) => Some(EventPayload::Connected(true)),
+                        rumqttc::Packet::ConnAck(ConnAck {
+                            session_present, ..
+                        }) => Some(EventPayload::Connected(*session_present)),
                         rumqttc::Packet::Disconnect => Some(EventPayload::Disconnected),
-                        rumqttc::Packet::PubAck(PubAck { pkid, .. }) => {
+                        // A QoS 1 publish completes with a PUBACK, while a QoS 2 one completes with a PUBCOMP
+                        // (the PUBREC/PUBREL exchange in between is handled by `rumqttc`)
+                        rumqttc::Packet::PubAck(PubAck { pkid, .. })
+                        | rumqttc::Packet::PubComp(PubComp { pkid, .. }) => {
                             Some(EventPayload::Published(*pkid as _))
                         }
                         rumqttc::Packet::SubAck(SubAck { pkid, .. }) => {
@@ -108,11 +192,87 @@ mod embedded_svc_compat {
         }
     }
 
-    pub struct MqttConnection(EventLoop, bool);
+    /// Polling the connection after it reported an error reconnects to the broker, which `rumqttc` does
+    /// immediately; see `with_reconnect_policy` for delaying the reconnection attempts.
+    pub struct MqttConnection {
+        event_loop: EventLoop,
+        done: bool,
+        connected: bool,
+        subscriptions: Option<Subscriptions>,
+        policy: Option<ReconnectPolicy>,
+        delay: Option<embassy_time::Duration>,
+    }
 
     impl MqttConnection {
         pub const fn new(event_loop: EventLoop) -> Self {
-            Self(event_loop, false)
+            Self {
+                event_loop,
+                done: false,
+                connected: false,
+                subscriptions: None,
+                policy: None,
+                delay: None,
+            }
+        }
+
+        /// Renew the subscriptions recorded by the `MqttClient` created with the same `subscriptions`,
+        /// when reconnecting to a broker which did not keep the session
+        pub fn with_subscriptions(self, subscriptions: Subscriptions) -> Self {
+            Self {
+                subscriptions: Some(subscriptions),
+                ..self
+            }
+        }
+
+        /// Delay the reconnection attempts which follow a connection error according to `policy`
+        pub fn with_reconnect_policy(self, policy: ReconnectPolicy) -> Self {
+            Self {
+                policy: Some(policy),
+                ..self
+            }
+        }
+
+        fn on_event(&mut self, event: &Result<rumqttc::Event, ConnectionError>) {
+            match event {
+                Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(ConnAck {
+                    session_present,
+                    ..
+                }))) => {
+                    self.delay = None;
+
+                    if self.connected && !*session_present {
+                        self.resubscribe();
+                    }
+
+                    self.connected = true;
+                }
+                Err(ConnectionError::RequestsDone) => (),
+                Err(_) => {
+                    if let Some(policy) = self.policy {
+                        self.delay = Some(
+                            self.delay
+                                .map(|delay| (delay * 2).min(policy.max_delay))
+                                .unwrap_or(policy.initial_delay),
+                        );
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        fn resubscribe(&mut self) {
+            let Some(subscriptions) = &self.subscriptions else {
+                return;
+            };
+
+            for (topic, qos) in subscriptions.0.lock().unwrap().iter() {
+                info!("Renewing the subscription to topic \"{topic}\"");
+
+                // Pending requests are sent before any new ones
+                self.event_loop
+                    .pending
+                    .push_back(Request::Subscribe(Subscribe::new(topic, *qos)));
+            }
         }
     }
 
@@ -128,17 +288,24 @@ mod embedded_svc_compat {
 
         #[allow(clippy::large_futures)]
         async fn next(&mut self) -> Result<Self::Event<'_>, Self::Error> {
-            if self.1 {
+            if self.done {
                 Err(RecvError)
             } else {
                 loop {
-                    let event = self.0.poll().await;
+                    if let Some(delay) = self.delay {
+                        trace!("Reconnecting in {}ms", delay.as_millis());
+                        embassy_time::Timer::after(delay).await;
+                    }
+
+                    let event = self.event_loop.poll().await;
                     trace!("Got event: {:?}", event);
 
+                    self.on_event(&event);
+
                     let event = MqttEvent(event);
                     if let Some(payload) = event.maybe_payload() {
                         if matches!(payload, EventPayload::Error(ConnectionError::RequestsDone)) {
-                            self.1 = true;
+                            self.done = true;
                             trace!("Done with requests");
                             break Err(RecvError);
                         } else {
@@ -158,3 +325,183 @@ mod embedded_svc_compat {
         }
     }
 }
+
+/// MQTT 5 support: the `rumqttc` MQTT 5 client, and its adaptation to the async MQTT traits of `embedded-svc`
+///
+/// The v5-only features (properties such as the message expiry interval, topic aliases, reason codes)
+/// are available through the `rumqttc` client and event loop, i.e. `AsyncClient::publish_with_properties`.
+#[cfg(feature = "v5")]
+pub mod v5 {
+    pub use rumqttc::v5::*;
+
+    #[cfg(feature = "embedded-svc")]
+    pub use embedded_svc_compat::*;
+
+    #[cfg(feature = "embedded-svc")]
+    mod embedded_svc_compat {
+        use embedded_svc::mqtt::client::asynch::{
+            Client, Connection, Details, ErrorType, Event, EventPayload, MessageId, Publish, QoS,
+        };
+
+        use log::trace;
+
+        use rumqttc::v5::mqttbytes::v5::{ConnAck, Packet, PubAck, PubComp, SubAck, UnsubAck};
+        use rumqttc::v5::{self, AsyncClient, EventLoop};
+
+        pub use rumqttc::v5::{ClientError, ConnectionError};
+        pub use rumqttc::RecvError;
+
+        pub struct MqttClient(AsyncClient);
+
+        impl MqttClient {
+            pub const fn new(client: AsyncClient) -> Self {
+                Self(client)
+            }
+        }
+
+        impl ErrorType for MqttClient {
+            type Error = ClientError;
+        }
+
+        impl Client for MqttClient {
+            async fn subscribe(&mut self, topic: &str, qos: QoS) -> Result<MessageId, Self::Error> {
+                self.0.subscribe(topic, to_qos(qos)).await?;
+
+                Ok(0)
+            }
+
+            async fn unsubscribe(&mut self, topic: &str) -> Result<MessageId, Self::Error> {
+                self.0.unsubscribe(topic).await?;
+
+                Ok(0)
+            }
+        }
+
+        impl Publish for MqttClient {
+            async fn publish(
+                &mut self,
+                topic: &str,
+                qos: QoS,
+                retain: bool,
+                payload: &[u8],
+            ) -> Result<MessageId, Self::Error> {
+                self.0
+                    .publish(topic, to_qos(qos), retain, payload.to_vec())
+                    .await?;
+
+                Ok(0)
+            }
+        }
+
+        pub struct MqttEvent(Result<v5::Event, ConnectionError>);
+
+        impl MqttEvent {
+            fn payload(&self) -> EventPayload<'_, ConnectionError> {
+                self.maybe_payload().unwrap()
+            }
+
+            fn maybe_payload(&self) -> Option<EventPayload<'_, ConnectionError>> {
+                match &self.0 {
+                    Ok(event) => match event {
+                        v5::Event::Incoming(incoming) => match incoming {
+                            Packet::Connect(..) => Some(EventPayload::BeforeConnect),
+                            Packet::ConnAck(ConnAck {
+                                session_present, ..
+                            }) => Some(EventPayload::Connected(*session_present)),
+                            Packet::Disconnect(_) => Some(EventPayload::Disconnected),
+                            // The reason codes of the acknowledgements cannot be reported through `embedded-svc`
+                            Packet::PubAck(PubAck { pkid, .. })
+                            | Packet::PubComp(PubComp { pkid, .. }) => {
+                                Some(EventPayload::Published(*pkid as _))
+                            }
+                            Packet::SubAck(SubAck { pkid, .. }) => {
+                                Some(EventPayload::Subscribed(*pkid as _))
+                            }
+                            Packet::UnsubAck(UnsubAck { pkid, .. }) => {
+                                Some(EventPayload::Unsubscribed(*pkid as _))
+                            }
+                            Packet::Publish(v5::mqttbytes::v5::Publish {
+                                pkid,
+                                topic,
+                                payload,
+                                ..
+                            }) => Some(EventPayload::Received {
+                                id: *pkid as _,
+                                // The topic is empty when the broker only sends the topic alias
+                                topic: core::str::from_utf8(topic)
+                                    .ok()
+                                    .filter(|topic| !topic.is_empty()),
+                                data: payload,
+                                details: Details::Complete,
+                            }),
+                            _ => None,
+                        },
+                        v5::Event::Outgoing(_) => None,
+                    },
+                    Err(err) => Some(EventPayload::Error(err)),
+                }
+            }
+        }
+
+        impl ErrorType for MqttEvent {
+            type Error = ConnectionError;
+        }
+
+        impl Event for MqttEvent {
+            fn payload(&self) -> EventPayload<'_, Self::Error> {
+                MqttEvent::payload(self)
+            }
+        }
+
+        pub struct MqttConnection(EventLoop, bool);
+
+        impl MqttConnection {
+            pub const fn new(event_loop: EventLoop) -> Self {
+                Self(event_loop, false)
+            }
+        }
+
+        impl ErrorType for MqttConnection {
+            type Error = RecvError;
+        }
+
+        impl Connection for MqttConnection {
+            type Event<'a>
+                = MqttEvent
+            where
+                Self: 'a;
+
+            #[allow(clippy::large_futures)]
+            async fn next(&mut self) -> Result<Self::Event<'_>, Self::Error> {
+                if self.1 {
+                    Err(RecvError)
+                } else {
+                    loop {
+                        let event = self.0.poll().await;
+                        trace!("Got event: {:?}", event);
+
+                        let event = MqttEvent(event);
+                        if let Some(payload) = event.maybe_payload() {
+                            if matches!(payload, EventPayload::Error(ConnectionError::RequestsDone))
+                            {
+                                self.1 = true;
+                                trace!("Done with requests");
+                                break Err(RecvError);
+                            } else {
+                                break Ok(event);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        fn to_qos(qos: QoS) -> v5::mqttbytes::QoS {
+            match qos {
+                QoS::AtMostOnce => v5::mqttbytes::QoS::AtMostOnce,
+                QoS::AtLeastOnce => v5::mqttbytes::QoS::AtLeastOnce,
+                QoS::ExactlyOnce => v5::mqttbytes::QoS::ExactlyOnce,
+            }
+        }
+    }
+}
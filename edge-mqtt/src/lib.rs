@@ -1,3 +1,1294 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(async_fn_in_trait)]
 #![warn(clippy::large_futures)]
 
+use core::fmt;
+use core::str;
+
+pub use properties::*;
+
+#[cfg(feature = "io")]
+pub mod client;
+#[cfg(feature = "std")]
 pub mod io;
+
+mod properties;
+
+/// The default port of MQTT brokers
+pub const PORT: u16 = 1883;
+
+/// The default port of MQTT brokers over TLS
+pub const TLS_PORT: u16 = 8883;
+
+/// The maximum length of the fixed header of an MQTT packet
+pub const MAX_FIXED_HEADER_LEN: usize = 5;
+
+/// The maximum value of the "remaining length" field of the fixed header, and of the other variable byte integers
+const MAX_REMAINING_LEN: usize = 268_435_455;
+
+/// The protocol level of MQTT 3.1.1
+const PROTOCOL_LEVEL: u8 = 4;
+
+/// The protocol level of MQTT 5
+const PROTOCOL_LEVEL_V5: u8 = 5;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Error {
+    /// The buffer does not contain a complete packet yet
+    Incomplete,
+    /// The packet is malformed
+    Invalid,
+    /// The packet is only ever sent by clients (i.e. CONNECT, SUBSCRIBE or UNSUBSCRIBE) and cannot be decoded
+    Unsupported,
+    /// The packet does not fit in the buffer
+    BufferOverflow,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete => write!(f, "Incomplete packet"),
+            Self::Invalid => write!(f, "Invalid packet"),
+            Self::Unsupported => write!(f, "Unsupported packet"),
+            Self::BufferOverflow => write!(f, "Buffer overflow"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The version of the MQTT protocol the packets are encoded and decoded with
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Version {
+    /// MQTT 3.1.1
+    #[default]
+    V3_1_1,
+    /// MQTT 5, with the properties and the reason codes of the packets (`v5` feature)
+    #[cfg(feature = "v5")]
+    V5,
+}
+
+impl Version {
+    /// Return `true` for MQTT 5
+    pub const fn is_v5(&self) -> bool {
+        match self {
+            Self::V3_1_1 => false,
+            #[cfg(feature = "v5")]
+            Self::V5 => true,
+        }
+    }
+
+    const fn level(&self) -> u8 {
+        if self.is_v5() {
+            PROTOCOL_LEVEL_V5
+        } else {
+            PROTOCOL_LEVEL
+        }
+    }
+}
+
+/// The quality of service of a message
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum QoS {
+    AtMostOnce = 0,
+    AtLeastOnce = 1,
+    ExactlyOnce = 2,
+}
+
+impl QoS {
+    fn from_u8(value: u8) -> Result<Self, Error> {
+        match value {
+            0 => Ok(Self::AtMostOnce),
+            1 => Ok(Self::AtLeastOnce),
+            2 => Ok(Self::ExactlyOnce),
+            _ => Err(Error::Invalid),
+        }
+    }
+}
+
+/// The last will of a client, published by the broker on the client's behalf when the client
+/// disconnects without sending a DISCONNECT packet
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LastWill<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    pub qos: QoS,
+    pub retain: bool,
+    /// The will properties, i.e. the will delay interval (MQTT 5)
+    pub properties: Properties<'a>,
+}
+
+impl<'a> LastWill<'a> {
+    /// Create a new last will with `QoS::AtMostOnce`, which is not retained
+    pub const fn new(topic: &'a str, payload: &'a [u8]) -> Self {
+        Self {
+            topic,
+            payload,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            properties: Properties::EMPTY,
+        }
+    }
+}
+
+/// A CONNECT packet
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Connect<'a> {
+    pub client_id: &'a str,
+    /// The keepalive interval in seconds; 0 disables the keepalive pings
+    pub keep_alive_secs: u16,
+    /// Whether the broker should discard the session state of the client ("clean start" with MQTT 5)
+    pub clean_session: bool,
+    pub last_will: Option<LastWill<'a>>,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a [u8]>,
+    /// The properties of the connection, i.e. the session expiry interval or the topic alias maximum (MQTT 5)
+    pub properties: Properties<'a>,
+}
+
+impl<'a> Connect<'a> {
+    /// Create a new CONNECT packet for a clean session, with a keepalive interval of 60 seconds
+    /// and without a last will, credentials or properties
+    pub const fn new(client_id: &'a str) -> Self {
+        Self {
+            client_id,
+            keep_alive_secs: 60,
+            clean_session: true,
+            last_will: None,
+            username: None,
+            password: None,
+            properties: Properties::EMPTY,
+        }
+    }
+}
+
+/// A PUBLISH packet
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Publish<'a> {
+    /// Whether this is a re-delivery of a message sent earlier
+    pub dup: bool,
+    pub qos: QoS,
+    pub retain: bool,
+    /// The topic; empty with MQTT 5, when the message only carries the topic alias of its topic
+    pub topic: &'a str,
+    /// The packet identifier; ignored (and not encoded) with `QoS::AtMostOnce`
+    pub id: u16,
+    pub payload: &'a [u8],
+    /// The properties of the message, i.e. the message expiry interval or the topic alias (MQTT 5)
+    pub properties: Properties<'a>,
+}
+
+impl<'a> Publish<'a> {
+    /// Create a new PUBLISH packet with `QoS::AtMostOnce`, which is not retained
+    pub const fn new(topic: &'a str, payload: &'a [u8]) -> Self {
+        Self {
+            dup: false,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            topic,
+            id: 0,
+            payload,
+            properties: Properties::EMPTY,
+        }
+    }
+}
+
+/// The acknowledgement of a PUBLISH packet (PUBACK, PUBREC, PUBREL or PUBCOMP)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Ack<'a> {
+    /// The packet identifier of the acknowledged message
+    pub id: u16,
+    /// The reason code; 0 means success, and codes of 0x80 and above mean failure (MQTT 5, always 0 with MQTT 3.1.1)
+    pub code: u8,
+    /// The properties of the acknowledgement, i.e. the reason string (MQTT 5)
+    pub properties: Properties<'a>,
+}
+
+impl Ack<'_> {
+    /// Create a new, successful acknowledgement of the message with packet identifier `id`
+    pub const fn new(id: u16) -> Self {
+        Self {
+            id,
+            code: 0,
+            properties: Properties::EMPTY,
+        }
+    }
+}
+
+/// The reason code and the properties of a DISCONNECT or AUTH packet
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Reason<'a> {
+    /// The reason code (MQTT 5, always 0 with MQTT 3.1.1)
+    pub code: u8,
+    /// The properties, i.e. the reason string (MQTT 5)
+    pub properties: Properties<'a>,
+}
+
+impl Reason<'_> {
+    /// Create a new reason with code `code` and without properties
+    pub const fn new(code: u8) -> Self {
+        Self {
+            code,
+            properties: Properties::EMPTY,
+        }
+    }
+}
+
+/// An MQTT 3.1.1 or MQTT 5 control packet
+///
+/// The properties and the reason codes only exist in MQTT 5; with MQTT 3.1.1 they are ignored
+/// (and not encoded), and decoded as empty and 0 respectively.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Packet<'a> {
+    Connect(Connect<'a>),
+    ConnAck {
+        session_present: bool,
+        /// The return code (MQTT 3.1.1) or reason code (MQTT 5); 0 means that the connection was accepted
+        code: u8,
+        /// The properties of the connection, i.e. the topic alias maximum of the broker (MQTT 5)
+        properties: Properties<'a>,
+    },
+    Publish(Publish<'a>),
+    PubAck(Ack<'a>),
+    PubRec(Ack<'a>),
+    PubRel(Ack<'a>),
+    PubComp(Ack<'a>),
+    Subscribe {
+        id: u16,
+        /// The topic filters, each with its maximum QoS; with MQTT 5, these can also be
+        /// shared subscriptions (`$share/<group>/<filter>`)
+        filters: &'a [(&'a str, QoS)],
+        /// The properties of the subscription, i.e. the subscription identifier (MQTT 5)
+        properties: Properties<'a>,
+    },
+    SubAck {
+        id: u16,
+        /// The granted QoS (0 - 2) of each of the subscribed filters, or a failure code of 0x80 and above
+        codes: &'a [u8],
+        properties: Properties<'a>,
+    },
+    Unsubscribe {
+        id: u16,
+        filters: &'a [&'a str],
+        properties: Properties<'a>,
+    },
+    UnsubAck {
+        id: u16,
+        /// The reason code of each of the unsubscribed filters (MQTT 5, always empty with MQTT 3.1.1)
+        codes: &'a [u8],
+        properties: Properties<'a>,
+    },
+    PingReq,
+    PingResp,
+    Disconnect(Reason<'a>),
+    /// An AUTH packet of the enhanced authentication (MQTT 5 only)
+    Auth(Reason<'a>),
+}
+
+impl<'a> Packet<'a> {
+    /// Decode the packet at the start of `buf`, according to `version`
+    ///
+    /// Returns the packet and its length, or `Error::Incomplete` if `buf` does not contain
+    /// a complete packet yet.
+    pub fn decode(buf: &'a [u8], version: Version) -> Result<(Self, usize), Error> {
+        let (header_len, remaining_len) = decode_fixed_header(buf)?;
+
+        let len = header_len + remaining_len;
+        if buf.len() < len {
+            Err(Error::Incomplete)?;
+        }
+
+        let packet_type = buf[0] >> 4;
+        let flags = buf[0] & 0x0f;
+
+        let v5 = version.is_v5();
+
+        let mut reader = Reader(&buf[header_len..len]);
+
+        let packet = match (packet_type, flags) {
+            (1, 0) | (8, 2) | (10, 2) => Err(Error::Unsupported)?,
+            (2, 0) => {
+                let ack_flags = reader.u8()?;
+                if ack_flags & 0xfe != 0 {
+                    Err(Error::Invalid)?;
+                }
+
+                Self::ConnAck {
+                    session_present: ack_flags & 0x01 != 0,
+                    code: reader.u8()?,
+                    properties: reader.properties(v5)?,
+                }
+            }
+            (3, flags) => {
+                let qos = QoS::from_u8((flags >> 1) & 0x03)?;
+                let topic = reader.str()?;
+                let id = if qos == QoS::AtMostOnce {
+                    0
+                } else {
+                    reader.id()?
+                };
+
+                Self::Publish(Publish {
+                    dup: flags & 0x08 != 0,
+                    qos,
+                    retain: flags & 0x01 != 0,
+                    topic,
+                    id,
+                    properties: reader.properties(v5)?,
+                    payload: reader.rest(),
+                })
+            }
+            (4, 0) => Self::PubAck(reader.ack(v5)?),
+            (5, 0) => Self::PubRec(reader.ack(v5)?),
+            (6, 2) => Self::PubRel(reader.ack(v5)?),
+            (7, 0) => Self::PubComp(reader.ack(v5)?),
+            (9, 0) => {
+                let id = reader.id()?;
+                let properties = reader.properties(v5)?;
+                let codes = reader.rest();
+                if codes.is_empty() {
+                    Err(Error::Invalid)?;
+                }
+
+                Self::SubAck {
+                    id,
+                    codes,
+                    properties,
+                }
+            }
+            (11, 0) => {
+                let id = reader.id()?;
+                let properties = reader.properties(v5)?;
+                let codes = if v5 { reader.rest() } else { &[] };
+                if v5 && codes.is_empty() {
+                    Err(Error::Invalid)?;
+                }
+
+                Self::UnsubAck {
+                    id,
+                    codes,
+                    properties,
+                }
+            }
+            (12, 0) => Self::PingReq,
+            (13, 0) => Self::PingResp,
+            (14, 0) => Self::Disconnect(reader.reason(v5)?),
+            (15, 0) if v5 => Self::Auth(reader.reason(v5)?),
+            _ => Err(Error::Invalid)?,
+        };
+
+        if !reader.0.is_empty() {
+            Err(Error::Invalid)?;
+        }
+
+        Ok((packet, len))
+    }
+
+    /// Encode the packet into `buf` according to `version`, returning the length of the encoded packet
+    pub fn encode(&self, buf: &mut [u8], version: Version) -> Result<usize, Error> {
+        self.encode_aliased(buf, version, None)
+    }
+
+    /// Encode the packet, adding the topic alias `alias` to the properties of a PUBLISH packet (MQTT 5)
+    pub(crate) fn encode_aliased(
+        &self,
+        buf: &mut [u8],
+        version: Version,
+        alias: Option<u16>,
+    ) -> Result<usize, Error> {
+        let mut counter = Writer::counter();
+        self.encode_body(&mut counter, version, alias)?;
+
+        let mut writer = Writer::new(buf);
+
+        writer.u8(self.first_byte())?;
+        writer.varint(counter.offset)?;
+
+        self.encode_body(&mut writer, version, alias)?;
+
+        Ok(writer.offset)
+    }
+
+    /// Encode everything following the fixed header
+    fn encode_body(
+        &self,
+        writer: &mut Writer<'_>,
+        version: Version,
+        alias: Option<u16>,
+    ) -> Result<(), Error> {
+        let v5 = version.is_v5();
+
+        match self {
+            Self::Connect(connect) => {
+                let mut flags = 0;
+                if connect.clean_session {
+                    flags |= 0x02;
+                }
+                if let Some(will) = &connect.last_will {
+                    flags |= 0x04 | ((will.qos as u8) << 3);
+                    if will.retain {
+                        flags |= 0x20;
+                    }
+                }
+                if connect.password.is_some() {
+                    flags |= 0x40;
+                }
+                if connect.username.is_some() {
+                    flags |= 0x80;
+                }
+
+                writer.str("MQTT")?;
+                writer.u8(version.level())?;
+                writer.u8(flags)?;
+                writer.u16(connect.keep_alive_secs)?;
+                writer.properties(v5, connect.properties, None)?;
+                writer.str(connect.client_id)?;
+
+                if let Some(will) = &connect.last_will {
+                    writer.properties(v5, will.properties, None)?;
+                    writer.str(will.topic)?;
+                    writer.bytes(will.payload)?;
+                }
+
+                if let Some(username) = connect.username {
+                    writer.str(username)?;
+                }
+
+                if let Some(password) = connect.password {
+                    writer.bytes(password)?;
+                }
+            }
+            Self::ConnAck {
+                session_present,
+                code,
+                properties,
+            } => {
+                writer.u8(*session_present as u8)?;
+                writer.u8(*code)?;
+                writer.properties(v5, *properties, None)?;
+            }
+            Self::Publish(publish) => {
+                writer.str(publish.topic)?;
+                if publish.qos != QoS::AtMostOnce {
+                    writer.u16(publish.id)?;
+                }
+                writer.properties(v5, publish.properties, alias)?;
+                writer.raw(publish.payload)?;
+            }
+            Self::PubAck(ack) | Self::PubRec(ack) | Self::PubRel(ack) | Self::PubComp(ack) => {
+                writer.u16(ack.id)?;
+                writer.reason(v5, ack.code, ack.properties)?;
+            }
+            Self::Subscribe {
+                id,
+                filters,
+                properties,
+            } => {
+                if filters.is_empty() {
+                    Err(Error::Invalid)?;
+                }
+
+                writer.u16(*id)?;
+                writer.properties(v5, *properties, None)?;
+
+                for (filter, qos) in filters.iter() {
+                    if v5 {
+                        check_shared_filter(filter)?;
+                    }
+
+                    writer.str(filter)?;
+                    writer.u8(*qos as u8)?;
+                }
+            }
+            Self::SubAck {
+                id,
+                codes,
+                properties,
+            } => {
+                writer.u16(*id)?;
+                writer.properties(v5, *properties, None)?;
+                writer.raw(codes)?;
+            }
+            Self::Unsubscribe {
+                id,
+                filters,
+                properties,
+            } => {
+                if filters.is_empty() {
+                    Err(Error::Invalid)?;
+                }
+
+                writer.u16(*id)?;
+                writer.properties(v5, *properties, None)?;
+
+                for filter in filters.iter() {
+                    writer.str(filter)?;
+                }
+            }
+            Self::UnsubAck {
+                id,
+                codes,
+                properties,
+            } => {
+                writer.u16(*id)?;
+
+                if v5 {
+                    writer.properties(v5, *properties, None)?;
+                    writer.raw(codes)?;
+                }
+            }
+            Self::PingReq | Self::PingResp => (),
+            Self::Auth(_) if !v5 => Err(Error::Invalid)?,
+            Self::Disconnect(reason) | Self::Auth(reason) => {
+                writer.reason(v5, reason.code, reason.properties)?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn first_byte(&self) -> u8 {
+        match self {
+            Self::Connect(_) => 0x10,
+            Self::ConnAck { .. } => 0x20,
+            Self::Publish(publish) => {
+                let mut byte = 0x30 | ((publish.qos as u8) << 1);
+                if publish.dup {
+                    byte |= 0x08;
+                }
+                if publish.retain {
+                    byte |= 0x01;
+                }
+
+                byte
+            }
+            Self::PubAck(_) => 0x40,
+            Self::PubRec(_) => 0x50,
+            Self::PubRel(_) => 0x62,
+            Self::PubComp(_) => 0x70,
+            Self::Subscribe { .. } => 0x82,
+            Self::SubAck { .. } => 0x90,
+            Self::Unsubscribe { .. } => 0xa2,
+            Self::UnsubAck { .. } => 0xb0,
+            Self::PingReq => 0xc0,
+            Self::PingResp => 0xd0,
+            Self::Disconnect(_) => 0xe0,
+            Self::Auth(_) => 0xf0,
+        }
+    }
+}
+
+/// Decode the fixed header at the start of `buf`, returning its length and the value of its "remaining length" field
+fn decode_fixed_header(buf: &[u8]) -> Result<(usize, usize), Error> {
+    let mut remaining_len = 0;
+
+    for (index, byte) in buf.iter().enumerate().skip(1).take(4) {
+        remaining_len |= ((byte & 0x7f) as usize) << (7 * (index - 1));
+
+        if byte & 0x80 == 0 {
+            return Ok((index + 1, remaining_len));
+        }
+    }
+
+    if buf.len() >= MAX_FIXED_HEADER_LEN {
+        Err(Error::Invalid)
+    } else {
+        Err(Error::Incomplete)
+    }
+}
+
+/// Check the format of a shared subscription filter (`$share/<group>/<filter>`) of MQTT 5
+fn check_shared_filter(filter: &str) -> Result<(), Error> {
+    if let Some(shared) = filter.strip_prefix("$share/") {
+        let (group, filter) = shared.split_once('/').ok_or(Error::Invalid)?;
+
+        if group.is_empty() || group.contains(['+', '#']) || filter.is_empty() {
+            Err(Error::Invalid)?;
+        }
+    }
+
+    Ok(())
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8, Error> {
+        let (byte, rest) = self.0.split_first().ok_or(Error::Invalid)?;
+
+        self.0 = rest;
+
+        Ok(*byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    #[cfg(feature = "v5")]
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes([
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+        ]))
+    }
+
+    /// A variable byte integer of up to 4 bytes
+    fn varint(&mut self) -> Result<usize, Error> {
+        let mut value = 0;
+
+        for index in 0..4 {
+            let byte = self.u8()?;
+
+            value |= ((byte & 0x7f) as usize) << (7 * index);
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::Invalid)
+    }
+
+    /// A packet identifier, which is never 0
+    fn id(&mut self) -> Result<u16, Error> {
+        match self.u16()? {
+            0 => Err(Error::Invalid),
+            id => Ok(id),
+        }
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        str::from_utf8(self.bytes()?).map_err(|_| Error::Invalid)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.u16()? as usize;
+
+        self.take(len)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            Err(Error::Invalid)?;
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+
+        self.0 = rest;
+
+        Ok(bytes)
+    }
+
+    /// The properties of an MQTT 5 packet; always empty with MQTT 3.1.1
+    fn properties(&mut self, v5: bool) -> Result<Properties<'a>, Error> {
+        if !v5 {
+            return Ok(Properties::EMPTY);
+        }
+
+        let len = self.varint()?;
+        let properties = Properties::new(self.take(len)?);
+
+        #[cfg(feature = "v5")]
+        properties.validate()?;
+
+        Ok(properties)
+    }
+
+    /// The reason code and the properties which end an MQTT 5 PUBACK, PUBREC, PUBREL, PUBCOMP,
+    /// DISCONNECT or AUTH packet, and which are both omitted when the code is 0 and there are no properties
+    fn reason(&mut self, v5: bool) -> Result<Reason<'a>, Error> {
+        let code = if v5 && !self.0.is_empty() {
+            self.u8()?
+        } else {
+            0
+        };
+
+        let properties = if self.0.is_empty() {
+            Properties::EMPTY
+        } else {
+            self.properties(v5)?
+        };
+
+        Ok(Reason { code, properties })
+    }
+
+    fn ack(&mut self, v5: bool) -> Result<Ack<'a>, Error> {
+        let id = self.id()?;
+        let Reason { code, properties } = self.reason(v5)?;
+
+        Ok(Ack {
+            id,
+            code,
+            properties,
+        })
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = self.0;
+
+        self.0 = &[];
+
+        rest
+    }
+}
+
+struct Writer<'a> {
+    /// `None` when only counting the length of the encoded data
+    buf: Option<&'a mut [u8]>,
+    offset: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf: Some(buf),
+            offset: 0,
+        }
+    }
+
+    fn counter() -> Self {
+        Self {
+            buf: None,
+            offset: 0,
+        }
+    }
+
+    fn u8(&mut self, byte: u8) -> Result<(), Error> {
+        self.raw(&[byte])
+    }
+
+    fn u16(&mut self, value: u16) -> Result<(), Error> {
+        self.raw(&value.to_be_bytes())
+    }
+
+    #[cfg(feature = "v5")]
+    fn u32(&mut self, value: u32) -> Result<(), Error> {
+        self.raw(&value.to_be_bytes())
+    }
+
+    /// A variable byte integer, i.e. the "remaining length" field of the fixed header
+    fn varint(&mut self, mut value: usize) -> Result<(), Error> {
+        if value > MAX_REMAINING_LEN {
+            Err(Error::Invalid)?;
+        }
+
+        loop {
+            let mut byte = (value & 0x7f) as u8;
+
+            value >>= 7;
+            if value > 0 {
+                byte |= 0x80;
+            }
+
+            self.u8(byte)?;
+
+            if value == 0 {
+                break Ok(());
+            }
+        }
+    }
+
+    fn str(&mut self, str: &str) -> Result<(), Error> {
+        self.bytes(str.as_bytes())
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if bytes.len() > u16::MAX as usize {
+            Err(Error::Invalid)?;
+        }
+
+        self.u16(bytes.len() as u16)?;
+        self.raw(bytes)
+    }
+
+    /// The properties of an MQTT 5 packet, followed by the topic alias `alias`; nothing with MQTT 3.1.1
+    fn properties(
+        &mut self,
+        v5: bool,
+        properties: Properties<'_>,
+        alias: Option<u16>,
+    ) -> Result<(), Error> {
+        if !v5 {
+            return Ok(());
+        }
+
+        #[cfg(feature = "v5")]
+        properties.validate()?;
+
+        let alias_len = if alias.is_some() { 3 } else { 0 };
+
+        self.varint(properties.as_bytes().len() + alias_len)?;
+        self.raw(properties.as_bytes())?;
+
+        if let Some(alias) = alias {
+            // The identifier of the topic alias property
+            self.u8(0x23)?;
+            self.u16(alias)?;
+        }
+
+        Ok(())
+    }
+
+    /// The reason code and the properties which end an MQTT 5 PUBACK, PUBREC, PUBREL, PUBCOMP,
+    /// DISCONNECT or AUTH packet; both are omitted when the code is 0 and there are no properties
+    fn reason(&mut self, v5: bool, code: u8, properties: Properties<'_>) -> Result<(), Error> {
+        if v5 && (code != 0 || !properties.is_empty()) {
+            self.u8(code)?;
+
+            if !properties.is_empty() {
+                self.properties(v5, properties, None)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn raw(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.offset + bytes.len();
+
+        if let Some(buf) = self.buf.as_deref_mut() {
+            if end > buf.len() {
+                Err(Error::BufferOverflow)?;
+            }
+
+            buf[self.offset..end].copy_from_slice(bytes);
+        }
+
+        self.offset = end;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const V3_1_1: Version = Version::V3_1_1;
+
+    fn round_trip(packet: Packet<'_>, encoded: &[u8], version: Version) {
+        let mut buf = [0; 64];
+
+        let len = packet.encode(&mut buf, version).unwrap();
+        assert_eq!(&buf[..len], encoded);
+
+        assert_eq!(
+            Packet::decode(encoded, version),
+            Ok((packet, encoded.len()))
+        );
+    }
+
+    #[test]
+    fn test_connect() {
+        let mut buf = [0; 64];
+
+        let len = Packet::Connect(Connect::new("dev"))
+            .encode(&mut buf, V3_1_1)
+            .unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"\x10\x0f\x00\x04MQTT\x04\x02\x00\x3c\x00\x03dev"
+        );
+
+        let connect = Connect {
+            keep_alive_secs: 10,
+            clean_session: false,
+            last_will: Some(LastWill {
+                qos: QoS::AtLeastOnce,
+                retain: true,
+                ..LastWill::new("w", b"x")
+            }),
+            username: Some("u"),
+            password: Some(b"p"),
+            ..Connect::new("dev")
+        };
+
+        let len = Packet::Connect(connect).encode(&mut buf, V3_1_1).unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"\x10\x1b\x00\x04MQTT\x04\xec\x00\x0a\x00\x03dev\x00\x01w\x00\x01x\x00\x01u\x00\x01p"
+        );
+
+        assert_eq!(Packet::decode(&buf[..len], V3_1_1), Err(Error::Unsupported));
+        assert_eq!(
+            Packet::Connect(connect).encode(&mut buf[..len - 1], V3_1_1),
+            Err(Error::BufferOverflow)
+        );
+    }
+
+    #[test]
+    fn test_packets() {
+        round_trip(
+            Packet::ConnAck {
+                session_present: true,
+                code: 0,
+                properties: Properties::EMPTY,
+            },
+            b"\x20\x02\x01\x00",
+            V3_1_1,
+        );
+        round_trip(
+            Packet::Publish(Publish {
+                retain: true,
+                ..Publish::new("a/b", b"hi")
+            }),
+            b"\x31\x07\x00\x03a/bhi",
+            V3_1_1,
+        );
+        round_trip(
+            Packet::Publish(Publish {
+                dup: true,
+                qos: QoS::ExactlyOnce,
+                id: 0x1234,
+                ..Publish::new("a/b", b"")
+            }),
+            b"\x3c\x07\x00\x03a/b\x12\x34",
+            V3_1_1,
+        );
+        round_trip(Packet::PubAck(Ack::new(1)), b"\x40\x02\x00\x01", V3_1_1);
+        round_trip(Packet::PubRec(Ack::new(2)), b"\x50\x02\x00\x02", V3_1_1);
+        round_trip(Packet::PubRel(Ack::new(3)), b"\x62\x02\x00\x03", V3_1_1);
+        round_trip(Packet::PubComp(Ack::new(4)), b"\x70\x02\x00\x04", V3_1_1);
+        round_trip(
+            Packet::SubAck {
+                id: 5,
+                codes: &[0, 2, 0x80],
+                properties: Properties::EMPTY,
+            },
+            b"\x90\x05\x00\x05\x00\x02\x80",
+            V3_1_1,
+        );
+        round_trip(
+            Packet::UnsubAck {
+                id: 6,
+                codes: &[],
+                properties: Properties::EMPTY,
+            },
+            b"\xb0\x02\x00\x06",
+            V3_1_1,
+        );
+        round_trip(Packet::PingReq, b"\xc0\x00", V3_1_1);
+        round_trip(Packet::PingResp, b"\xd0\x00", V3_1_1);
+        round_trip(Packet::Disconnect(Reason::new(0)), b"\xe0\x00", V3_1_1);
+
+        let mut buf = [0; 64];
+
+        let len = Packet::Subscribe {
+            id: 7,
+            filters: &[("a/#", QoS::AtLeastOnce), ("b", QoS::AtMostOnce)],
+            properties: Properties::EMPTY,
+        }
+        .encode(&mut buf, V3_1_1)
+        .unwrap();
+        assert_eq!(&buf[..len], b"\x82\x0c\x00\x07\x00\x03a/#\x01\x00\x01b\x00");
+
+        let len = Packet::Unsubscribe {
+            id: 8,
+            filters: &["a/#"],
+            properties: Properties::EMPTY,
+        }
+        .encode(&mut buf, V3_1_1)
+        .unwrap();
+        assert_eq!(&buf[..len], b"\xa2\x07\x00\x08\x00\x03a/#");
+    }
+
+    #[test]
+    fn test_remaining_len() {
+        let payload = [0x55; 200];
+        let mut buf = [0; 256];
+
+        let len = Packet::Publish(Publish {
+            qos: QoS::AtLeastOnce,
+            id: 1,
+            ..Publish::new("t", &payload)
+        })
+        .encode(&mut buf, V3_1_1)
+        .unwrap();
+
+        // 3 (topic) + 2 (id) + 200 = 205 = 0x4d + 0x01 * 128
+        assert_eq!(&buf[..5], b"\x32\xcd\x01\x00\x01");
+        assert_eq!(len, 3 + 205);
+
+        let (packet, decoded_len) = Packet::decode(&buf[..len + 10], V3_1_1).unwrap();
+        assert_eq!(decoded_len, len);
+        assert!(matches!(packet, Packet::Publish(publish) if publish.payload == payload));
+
+        assert_eq!(
+            Packet::decode(&buf[..len - 1], V3_1_1),
+            Err(Error::Incomplete)
+        );
+        assert_eq!(Packet::decode(&buf[..2], V3_1_1), Err(Error::Incomplete));
+        assert_eq!(Packet::decode(&[], V3_1_1), Err(Error::Incomplete));
+    }
+
+    #[test]
+    fn test_invalid() {
+        // Remaining length longer than 4 bytes
+        assert_eq!(
+            Packet::decode(b"\x30\xff\xff\xff\xff\x01", V3_1_1),
+            Err(Error::Invalid)
+        );
+        // PUBREL with wrong flags
+        assert_eq!(
+            Packet::decode(b"\x60\x02\x00\x01", V3_1_1),
+            Err(Error::Invalid)
+        );
+        // PUBLISH with QoS 3
+        assert_eq!(
+            Packet::decode(b"\x36\x05\x00\x01a\x00\x01", V3_1_1),
+            Err(Error::Invalid)
+        );
+        // PUBACK with packet identifier 0
+        assert_eq!(
+            Packet::decode(b"\x40\x02\x00\x00", V3_1_1),
+            Err(Error::Invalid)
+        );
+        // PINGRESP with a body
+        assert_eq!(Packet::decode(b"\xd0\x01\x00", V3_1_1), Err(Error::Invalid));
+        // Topic which is not UTF-8
+        assert_eq!(
+            Packet::decode(b"\x30\x03\x00\x01\xff", V3_1_1),
+            Err(Error::Invalid)
+        );
+        // Topic longer than the packet
+        assert_eq!(
+            Packet::decode(b"\x30\x03\x00\x05a", V3_1_1),
+            Err(Error::Invalid)
+        );
+    }
+
+    #[cfg(feature = "v5")]
+    #[test]
+    fn test_v5_connect() {
+        let mut buf = [0; 64];
+
+        let mut properties = [0; 16];
+        let properties = Properties::encode(
+            &[
+                Property::SessionExpiryInterval(60),
+                Property::TopicAliasMaximum(4),
+            ],
+            &mut properties,
+        )
+        .unwrap();
+
+        let connect = Connect {
+            properties,
+            ..Connect::new("dev")
+        };
+
+        let len = Packet::Connect(connect)
+            .encode(&mut buf, Version::V5)
+            .unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"\x10\x18\x00\x04MQTT\x05\x02\x00\x3c\x08\x11\x00\x00\x00\x3c\x22\x00\x04\x00\x03dev"
+        );
+
+        let connect = Connect {
+            last_will: Some(LastWill::new("w", b"x")),
+            ..Connect::new("dev")
+        };
+
+        let len = Packet::Connect(connect)
+            .encode(&mut buf, Version::V5)
+            .unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"\x10\x17\x00\x04MQTT\x05\x06\x00\x3c\x00\x00\x03dev\x00\x00\x01w\x00\x01x"
+        );
+
+        // The properties are not encoded with MQTT 3.1.1
+        let len = Packet::Connect(Connect {
+            properties,
+            ..Connect::new("dev")
+        })
+        .encode(&mut buf, V3_1_1)
+        .unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"\x10\x0f\x00\x04MQTT\x04\x02\x00\x3c\x00\x03dev"
+        );
+    }
+
+    #[cfg(feature = "v5")]
+    #[test]
+    fn test_v5_packets() {
+        const V5: Version = Version::V5;
+
+        let properties = Properties::new(b"\x22\x00\x0a\x13\x00\x1e");
+
+        round_trip(
+            Packet::ConnAck {
+                session_present: false,
+                code: 0,
+                properties,
+            },
+            b"\x20\x09\x00\x00\x06\x22\x00\x0a\x13\x00\x1e",
+            V5,
+        );
+        assert_eq!(properties.topic_alias_maximum(), Some(10));
+        assert_eq!(properties.server_keep_alive(), Some(30));
+
+        round_trip(
+            Packet::Publish(Publish {
+                qos: QoS::AtLeastOnce,
+                id: 1,
+                properties: Properties::new(b"\x02\x00\x00\x00\x3c"),
+                ..Publish::new("a", b"hi")
+            }),
+            b"\x32\x0d\x00\x01a\x00\x01\x05\x02\x00\x00\x00\x3chi",
+            V5,
+        );
+        round_trip(
+            Packet::Publish(Publish::new("a", b"")),
+            b"\x30\x04\x00\x01a\x00",
+            V5,
+        );
+        round_trip(Packet::PubAck(Ack::new(1)), b"\x40\x02\x00\x01", V5);
+        round_trip(
+            Packet::PubRec(Ack {
+                code: 0x10,
+                ..Ack::new(2)
+            }),
+            b"\x50\x03\x00\x02\x10",
+            V5,
+        );
+        round_trip(
+            Packet::PubAck(Ack {
+                code: 0x80,
+                properties: Properties::new(b"\x1f\x00\x02no"),
+                ..Ack::new(3)
+            }),
+            b"\x40\x09\x00\x03\x80\x05\x1f\x00\x02no",
+            V5,
+        );
+        round_trip(
+            Packet::SubAck {
+                id: 5,
+                codes: &[0, 0x9e],
+                properties: Properties::EMPTY,
+            },
+            b"\x90\x05\x00\x05\x00\x00\x9e",
+            V5,
+        );
+        round_trip(
+            Packet::UnsubAck {
+                id: 6,
+                codes: &[0x11],
+                properties: Properties::EMPTY,
+            },
+            b"\xb0\x04\x00\x06\x00\x11",
+            V5,
+        );
+        round_trip(Packet::Disconnect(Reason::new(0)), b"\xe0\x00", V5);
+        round_trip(Packet::Disconnect(Reason::new(0x8e)), b"\xe0\x01\x8e", V5);
+        round_trip(
+            Packet::Auth(Reason {
+                code: 0x18,
+                properties: Properties::new(b"\x15\x00\x01m"),
+            }),
+            b"\xf0\x06\x18\x04\x15\x00\x01m",
+            V5,
+        );
+
+        let mut buf = [0; 64];
+
+        // A shared subscription with a subscription identifier
+        let len = Packet::Subscribe {
+            id: 7,
+            filters: &[("$share/g/a/#", QoS::AtLeastOnce)],
+            properties: Properties::new(b"\x0b\x07"),
+        }
+        .encode(&mut buf, V5)
+        .unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"\x82\x14\x00\x07\x02\x0b\x07\x00\x0c$share/g/a/#\x01"
+        );
+
+        for filter in ["$share/g", "$share//a", "$share/g+/a", "$share/g/"] {
+            assert_eq!(
+                Packet::Subscribe {
+                    id: 7,
+                    filters: &[(filter, QoS::AtMostOnce)],
+                    properties: Properties::EMPTY,
+                }
+                .encode(&mut buf, V5),
+                Err(Error::Invalid)
+            );
+        }
+
+        let len = Packet::Unsubscribe {
+            id: 8,
+            filters: &["a"],
+            properties: Properties::EMPTY,
+        }
+        .encode(&mut buf, V5)
+        .unwrap();
+        assert_eq!(&buf[..len], b"\xa2\x06\x00\x08\x00\x00\x01a");
+
+        // AUTH only exists in MQTT 5
+        assert_eq!(
+            Packet::Auth(Reason::new(0)).encode(&mut buf, V3_1_1),
+            Err(Error::Invalid)
+        );
+        assert_eq!(Packet::decode(b"\xf0\x00", V3_1_1), Err(Error::Invalid));
+    }
+
+    #[cfg(feature = "v5")]
+    #[test]
+    fn test_v5_properties() {
+        let mut buf = [0; 32];
+
+        let properties = Properties::encode(
+            &[
+                Property::MessageExpiryInterval(60),
+                Property::UserProperty("k", "v"),
+                Property::SubscriptionIdentifier(200),
+                Property::TopicAlias(3),
+            ],
+            &mut buf,
+        )
+        .unwrap();
+
+        assert_eq!(
+            properties.as_bytes(),
+            b"\x02\x00\x00\x00\x3c\x26\x00\x01k\x00\x01v\x0b\xc8\x01\x23\x00\x03"
+        );
+        assert!(properties.iter().eq([
+            Property::MessageExpiryInterval(60),
+            Property::UserProperty("k", "v"),
+            Property::SubscriptionIdentifier(200),
+            Property::TopicAlias(3),
+        ]));
+        assert_eq!(properties.message_expiry_interval(), Some(60));
+        assert_eq!(properties.topic_alias(), Some(3));
+        assert_eq!(properties.reason_string(), None);
+        assert!(properties.user_properties().eq([("k", "v")]));
+
+        // Unknown property
+        assert_eq!(
+            Packet::decode(b"\x40\x05\x00\x01\x00\x01\x07", Version::V5),
+            Err(Error::Invalid)
+        );
+        // Properties longer than the packet
+        assert_eq!(
+            Packet::decode(b"\x40\x04\x00\x01\x00\x05", Version::V5),
+            Err(Error::Invalid)
+        );
+        // Truncated property
+        assert_eq!(
+            Packet::PubAck(Ack {
+                properties: Properties::new(b"\x23\x00"),
+                ..Ack::new(1)
+            })
+            .encode(&mut buf, Version::V5),
+            Err(Error::Invalid)
+        );
+    }
+}
@@ -0,0 +1,274 @@
+#[cfg(feature = "v5")]
+use crate::{Error, Reader, Writer};
+
+/// The properties of an MQTT 5 packet, in their encoded form (without the leading property length)
+///
+/// The properties are neither encoded nor decoded with MQTT 3.1.1, where they are always empty.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Properties<'a>(&'a [u8]);
+
+impl<'a> Properties<'a> {
+    /// No properties
+    pub const EMPTY: Self = Self(&[]);
+
+    /// Wrap already encoded properties; these are validated when the packet carrying them is encoded
+    pub const fn new(encoded: &'a [u8]) -> Self {
+        Self(encoded)
+    }
+
+    /// Return the encoded properties
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Return `true` if there are no properties
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(feature = "v5")]
+impl<'a> Properties<'a> {
+    /// Encode `properties` into `buf`
+    pub fn encode(properties: &[Property<'_>], buf: &'a mut [u8]) -> Result<Self, Error> {
+        let mut writer = Writer::new(&mut *buf);
+
+        for property in properties {
+            property.encode(&mut writer)?;
+        }
+
+        let len = writer.offset;
+        let buf: &'a [u8] = buf;
+
+        Ok(Self(&buf[..len]))
+    }
+
+    /// Iterate over the properties
+    pub fn iter(&self) -> PropertiesIter<'a> {
+        PropertiesIter(Reader(self.0))
+    }
+
+    /// The topic alias of a PUBLISH packet
+    pub fn topic_alias(&self) -> Option<u16> {
+        self.iter().find_map(|property| match property {
+            Property::TopicAlias(alias) => Some(alias),
+            _ => None,
+        })
+    }
+
+    /// The highest topic alias the sender of a CONNECT or CONNACK packet accepts
+    pub fn topic_alias_maximum(&self) -> Option<u16> {
+        self.iter().find_map(|property| match property {
+            Property::TopicAliasMaximum(maximum) => Some(maximum),
+            _ => None,
+        })
+    }
+
+    /// The lifetime of the message of a PUBLISH packet in seconds
+    pub fn message_expiry_interval(&self) -> Option<u32> {
+        self.iter().find_map(|property| match property {
+            Property::MessageExpiryInterval(interval) => Some(interval),
+            _ => None,
+        })
+    }
+
+    /// The keepalive interval in seconds the broker assigned in its CONNACK, overriding the one of the CONNECT
+    pub fn server_keep_alive(&self) -> Option<u16> {
+        self.iter().find_map(|property| match property {
+            Property::ServerKeepAlive(keep_alive) => Some(keep_alive),
+            _ => None,
+        })
+    }
+
+    /// The human-readable diagnostic of the reason code of an acknowledgement
+    pub fn reason_string(&self) -> Option<&'a str> {
+        self.iter().find_map(|property| match property {
+            Property::ReasonString(reason) => Some(reason),
+            _ => None,
+        })
+    }
+
+    /// Iterate over the user properties (name-value pairs)
+    pub fn user_properties(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.iter().filter_map(|property| match property {
+            Property::UserProperty(name, value) => Some((name, value)),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let mut reader = Reader(self.0);
+
+        while !reader.0.is_empty() {
+            Property::decode(&mut reader)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An iterator over the `Properties` of a packet
+#[cfg(feature = "v5")]
+pub struct PropertiesIter<'a>(Reader<'a>);
+
+#[cfg(feature = "v5")]
+impl<'a> Iterator for PropertiesIter<'a> {
+    type Item = Property<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 .0.is_empty() {
+            return None;
+        }
+
+        let property = Property::decode(&mut self.0);
+
+        if property.is_err() {
+            self.0 .0 = &[];
+        }
+
+        property.ok()
+    }
+}
+
+/// An MQTT 5 property
+#[cfg(feature = "v5")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Property<'a> {
+    PayloadFormatIndicator(u8),
+    /// The lifetime of the message in seconds, after which the broker no longer delivers it
+    MessageExpiryInterval(u32),
+    ContentType(&'a str),
+    ResponseTopic(&'a str),
+    CorrelationData(&'a [u8]),
+    SubscriptionIdentifier(u32),
+    /// The time in seconds the broker keeps the session after the connection is closed
+    SessionExpiryInterval(u32),
+    AssignedClientIdentifier(&'a str),
+    ServerKeepAlive(u16),
+    AuthenticationMethod(&'a str),
+    AuthenticationData(&'a [u8]),
+    RequestProblemInformation(u8),
+    WillDelayInterval(u32),
+    RequestResponseInformation(u8),
+    ResponseInformation(&'a str),
+    ServerReference(&'a str),
+    ReasonString(&'a str),
+    ReceiveMaximum(u16),
+    TopicAliasMaximum(u16),
+    TopicAlias(u16),
+    MaximumQoS(u8),
+    RetainAvailable(u8),
+    /// A name-value pair
+    UserProperty(&'a str, &'a str),
+    MaximumPacketSize(u32),
+    WildcardSubscriptionAvailable(u8),
+    SubscriptionIdentifierAvailable(u8),
+    SharedSubscriptionAvailable(u8),
+}
+
+#[cfg(feature = "v5")]
+impl<'a> Property<'a> {
+    /// Return the identifier of the property
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::PayloadFormatIndicator(_) => 0x01,
+            Self::MessageExpiryInterval(_) => 0x02,
+            Self::ContentType(_) => 0x03,
+            Self::ResponseTopic(_) => 0x08,
+            Self::CorrelationData(_) => 0x09,
+            Self::SubscriptionIdentifier(_) => 0x0b,
+            Self::SessionExpiryInterval(_) => 0x11,
+            Self::AssignedClientIdentifier(_) => 0x12,
+            Self::ServerKeepAlive(_) => 0x13,
+            Self::AuthenticationMethod(_) => 0x15,
+            Self::AuthenticationData(_) => 0x16,
+            Self::RequestProblemInformation(_) => 0x17,
+            Self::WillDelayInterval(_) => 0x18,
+            Self::RequestResponseInformation(_) => 0x19,
+            Self::ResponseInformation(_) => 0x1a,
+            Self::ServerReference(_) => 0x1c,
+            Self::ReasonString(_) => 0x1f,
+            Self::ReceiveMaximum(_) => 0x21,
+            Self::TopicAliasMaximum(_) => 0x22,
+            Self::TopicAlias(_) => 0x23,
+            Self::MaximumQoS(_) => 0x24,
+            Self::RetainAvailable(_) => 0x25,
+            Self::UserProperty(_, _) => 0x26,
+            Self::MaximumPacketSize(_) => 0x27,
+            Self::WildcardSubscriptionAvailable(_) => 0x28,
+            Self::SubscriptionIdentifierAvailable(_) => 0x29,
+            Self::SharedSubscriptionAvailable(_) => 0x2a,
+        }
+    }
+
+    fn decode(reader: &mut Reader<'a>) -> Result<Self, Error> {
+        let property = match reader.varint()? {
+            0x01 => Self::PayloadFormatIndicator(reader.u8()?),
+            0x02 => Self::MessageExpiryInterval(reader.u32()?),
+            0x03 => Self::ContentType(reader.str()?),
+            0x08 => Self::ResponseTopic(reader.str()?),
+            0x09 => Self::CorrelationData(reader.bytes()?),
+            0x0b => Self::SubscriptionIdentifier(reader.varint()? as u32),
+            0x11 => Self::SessionExpiryInterval(reader.u32()?),
+            0x12 => Self::AssignedClientIdentifier(reader.str()?),
+            0x13 => Self::ServerKeepAlive(reader.u16()?),
+            0x15 => Self::AuthenticationMethod(reader.str()?),
+            0x16 => Self::AuthenticationData(reader.bytes()?),
+            0x17 => Self::RequestProblemInformation(reader.u8()?),
+            0x18 => Self::WillDelayInterval(reader.u32()?),
+            0x19 => Self::RequestResponseInformation(reader.u8()?),
+            0x1a => Self::ResponseInformation(reader.str()?),
+            0x1c => Self::ServerReference(reader.str()?),
+            0x1f => Self::ReasonString(reader.str()?),
+            0x21 => Self::ReceiveMaximum(reader.u16()?),
+            0x22 => Self::TopicAliasMaximum(reader.u16()?),
+            0x23 => Self::TopicAlias(reader.u16()?),
+            0x24 => Self::MaximumQoS(reader.u8()?),
+            0x25 => Self::RetainAvailable(reader.u8()?),
+            0x26 => Self::UserProperty(reader.str()?, reader.str()?),
+            0x27 => Self::MaximumPacketSize(reader.u32()?),
+            0x28 => Self::WildcardSubscriptionAvailable(reader.u8()?),
+            0x29 => Self::SubscriptionIdentifierAvailable(reader.u8()?),
+            0x2a => Self::SharedSubscriptionAvailable(reader.u8()?),
+            _ => Err(Error::Invalid)?,
+        };
+
+        Ok(property)
+    }
+
+    fn encode(&self, writer: &mut Writer<'_>) -> Result<(), Error> {
+        writer.u8(self.id())?;
+
+        match self {
+            Self::PayloadFormatIndicator(value)
+            | Self::RequestProblemInformation(value)
+            | Self::RequestResponseInformation(value)
+            | Self::MaximumQoS(value)
+            | Self::RetainAvailable(value)
+            | Self::WildcardSubscriptionAvailable(value)
+            | Self::SubscriptionIdentifierAvailable(value)
+            | Self::SharedSubscriptionAvailable(value) => writer.u8(*value),
+            Self::ServerKeepAlive(value)
+            | Self::ReceiveMaximum(value)
+            | Self::TopicAliasMaximum(value)
+            | Self::TopicAlias(value) => writer.u16(*value),
+            Self::MessageExpiryInterval(value)
+            | Self::SessionExpiryInterval(value)
+            | Self::WillDelayInterval(value)
+            | Self::MaximumPacketSize(value) => writer.u32(*value),
+            Self::SubscriptionIdentifier(value) => writer.varint(*value as usize),
+            Self::ContentType(value)
+            | Self::ResponseTopic(value)
+            | Self::AssignedClientIdentifier(value)
+            | Self::AuthenticationMethod(value)
+            | Self::ResponseInformation(value)
+            | Self::ServerReference(value)
+            | Self::ReasonString(value) => writer.str(value),
+            Self::CorrelationData(value) | Self::AuthenticationData(value) => writer.bytes(value),
+            Self::UserProperty(name, value) => {
+                writer.str(name)?;
+                writer.str(value)
+            }
+        }
+    }
+}
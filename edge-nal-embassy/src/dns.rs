@@ -1,6 +1,7 @@
-use core::net::IpAddr;
+use core::net::{IpAddr, SocketAddr};
+use core::pin::pin;
 
-use edge_nal::AddrType;
+use edge_nal::{AddrType, Dns as _, DnsQueryError, UdpDns, DNS_PORT};
 
 use embassy_net::{
     dns::{DnsQueryType, Error},
@@ -8,9 +9,36 @@ use embassy_net::{
 };
 use embedded_io_async::ErrorKind;
 
+use crate::{GenericUdp, UdpBufferPool, UdpBuffers, UdpError};
+
+/// The maximum number of DNS servers which can be added with `Dns::with_servers`
+pub const MAX_DNS_SERVERS: usize = 3;
+
+/// The maximum number of DNS servers carried by the IPv4 and IPv6 configurations of the stack
+const MAX_STACK_DNS_SERVERS: usize = 6;
+
+/// How the DNS servers added with `Dns::with_servers` are used
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DnsServerMode {
+    /// Query the added servers only when the query over the DNS servers of the stack configuration
+    /// (i.e. the ones provided by DHCP) fails
+    Fallback,
+    /// Query the added servers only, ignoring the DNS servers of the stack configuration
+    Override,
+}
+
 /// A struct that implements the `Dns` trait from `edge-nal`
-pub struct Dns<'a> {
+///
+/// `B` is the buffer pool of the `Udp` (or `SlabUdp`) instance passed to `Dns::with_servers`;
+/// without added servers, it is an unused placeholder.
+pub struct Dns<'a, B = UdpBuffers<0, 0, 0, 0>>
+where
+    B: UdpBufferPool,
+{
     stack: Stack<'a>,
+    udp: Option<&'a GenericUdp<'a, B>>,
+    servers: heapless::Vec<IpAddr, MAX_DNS_SERVERS>,
+    mode: DnsServerMode,
 }
 
 impl<'a> Dns<'a> {
@@ -18,11 +46,173 @@ impl<'a> Dns<'a> {
     ///
     /// NOTE: If using DHCP, make sure it has reconfigured the stack to ensure the DNS servers are updated
     pub fn new(stack: Stack<'a>) -> Self {
-        Self { stack }
+        Self {
+            stack,
+            udp: None,
+            servers: heapless::Vec::new(),
+            mode: DnsServerMode::Fallback,
+        }
     }
 }
 
-impl edge_nal::Dns for Dns<'_> {
+impl<'a, B> Dns<'a, B>
+where
+    B: UdpBufferPool,
+{
+    /// Add DNS servers which are used according to `mode`, on top of the ones of the stack configuration
+    /// (i.e. a fixed `1.1.1.1` server as a fallback for when the DHCP-provided servers are broken)
+    ///
+    /// `embassy-net` only queries the servers of the stack configuration, so the added servers are queried
+    /// in order directly, with sockets of `udp`. As a side effect, reverse lookups are supported with added servers.
+    ///
+    /// # Panics
+    ///
+    /// If more than `MAX_DNS_SERVERS` servers are provided.
+    pub fn with_servers<U>(
+        self,
+        udp: &'a GenericUdp<'a, U>,
+        servers: &[IpAddr],
+        mode: DnsServerMode,
+    ) -> Dns<'a, U>
+    where
+        U: UdpBufferPool,
+    {
+        assert!(
+            servers.len() <= MAX_DNS_SERVERS,
+            "At most {MAX_DNS_SERVERS} DNS servers can be added"
+        );
+
+        Dns {
+            stack: self.stack,
+            udp: Some(udp),
+            servers: heapless::Vec::from_slice(servers).unwrap(),
+            mode,
+        }
+    }
+
+    /// Return the DNS servers currently in use, in the order they are queried:
+    /// the ones of the IPv4 and IPv6 stack configurations (unless overridden), followed by the added ones
+    pub fn servers(&self) -> heapless::Vec<IpAddr, { MAX_STACK_DNS_SERVERS + MAX_DNS_SERVERS }> {
+        let mut servers = heapless::Vec::new();
+
+        if self.mode == DnsServerMode::Fallback {
+            if let Some(config) = self.stack.config_v4() {
+                for server in &config.dns_servers {
+                    let _ = servers.push(IpAddr::V4(*server));
+                }
+            }
+
+            if let Some(config) = self.stack.config_v6() {
+                for server in &config.dns_servers {
+                    let _ = servers.push(IpAddr::V6(*server));
+                }
+            }
+        }
+
+        for server in &self.servers {
+            let _ = servers.push(*server);
+        }
+
+        servers
+    }
+
+    async fn stack_get_host_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, Error> {
+        // With either address type acceptable, prefer the family the stack has an address of,
+        // and fall back to the other one should the host have no addresses of the preferred family
+        let qtypes: &[DnsQueryType] = match addr_type {
+            AddrType::IPv4 => &[DnsQueryType::A],
+            AddrType::IPv6 => &[DnsQueryType::Aaaa],
+            AddrType::Either
+                if self.stack.config_v4().is_none() && self.stack.config_v6().is_some() =>
+            {
+                &[DnsQueryType::Aaaa, DnsQueryType::A]
+            }
+            AddrType::Either => &[DnsQueryType::A, DnsQueryType::Aaaa],
+        };
+
+        let mut result = Err(Error::Failed);
+
+        for qtype in qtypes {
+            result = self
+                .stack
+                .dns_query(host, *qtype)
+                .await
+                .and_then(|addrs| addrs.first().copied().ok_or(Error::Failed));
+
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        Ok(result?.into())
+    }
+
+    async fn servers_get_host_by_name(
+        &self,
+        udp: &GenericUdp<'_, B>,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, DnsError> {
+        let mut result = Err(DnsError::Dns(Error::Failed));
+
+        for server in &self.servers {
+            let dns = self.server(udp, *server);
+            let fut = pin!(dns.get_host_by_name(host, addr_type.clone()));
+
+            match fut.await {
+                Ok(addr) => return Ok(addr),
+                // The server answered, so there is no point in asking the next one
+                Err(DnsQueryError::NotFound) => return Err(DnsError::Dns(Error::Failed)),
+                Err(e) => result = Err(e.into()),
+            }
+        }
+
+        result
+    }
+
+    async fn servers_get_host_by_address(
+        &self,
+        udp: &GenericUdp<'_, B>,
+        addr: IpAddr,
+        result: &mut [u8],
+    ) -> Result<usize, DnsError> {
+        let mut outcome = Err(DnsError::Dns(Error::Failed));
+
+        for server in &self.servers {
+            let dns = self.server(udp, *server);
+            let fut = pin!(dns.get_host_by_address(addr, result));
+
+            match fut.await {
+                Ok(len) => return Ok(len),
+                Err(DnsQueryError::NotFound) => return Err(DnsError::Dns(Error::Failed)),
+                Err(e) => outcome = Err(e.into()),
+            }
+        }
+
+        outcome
+    }
+
+    fn server<'u>(
+        &self,
+        udp: &'u GenericUdp<'u, B>,
+        server: IpAddr,
+    ) -> UdpDns<&'u GenericUdp<'u, B>> {
+        // The query IDs need not be unpredictable, as each query is sent from a new socket and
+        // the responses are matched on the address of the server too
+        let seed = embassy_time::Instant::now().as_ticks() as u16;
+
+        UdpDns::new(udp, SocketAddr::new(server, DNS_PORT), seed)
+    }
+}
+
+impl<B> edge_nal::Dns for Dns<'_, B>
+where
+    B: UdpBufferPool,
+{
     type Error = DnsError;
 
     async fn get_host_by_name(
@@ -30,39 +220,85 @@ impl edge_nal::Dns for Dns<'_> {
         host: &str,
         addr_type: AddrType,
     ) -> Result<IpAddr, Self::Error> {
-        let qtype = match addr_type {
-            AddrType::IPv6 => DnsQueryType::Aaaa,
-            _ => DnsQueryType::A,
+        let Some(udp) = self.udp else {
+            return Ok(self.stack_get_host_by_name(host, addr_type).await?);
         };
-        let addrs = self.stack.dns_query(host, qtype).await?;
-        if let Some(first) = addrs.first() {
-            Ok((*first).into())
-        } else {
-            Err(Error::Failed.into())
+
+        if self.mode == DnsServerMode::Fallback {
+            match self.stack_get_host_by_name(host, addr_type.clone()).await {
+                Err(Error::Failed) => (),
+                other => return Ok(other?),
+            }
         }
+
+        let fut = pin!(self.servers_get_host_by_name(udp, host, addr_type));
+
+        fut.await
     }
 
     async fn get_host_by_address(
         &self,
-        _addr: IpAddr,
-        _result: &mut [u8],
+        addr: IpAddr,
+        result: &mut [u8],
     ) -> Result<usize, Self::Error> {
-        todo!()
+        // `embassy-net` can only resolve `A` and `AAAA` records, not the `PTR` records of the reverse lookups,
+        // so these are only possible with added servers, which are queried directly
+        let Some(udp) = self.udp else {
+            return Err(DnsError::Unsupported);
+        };
+
+        let fut = pin!(self.servers_get_host_by_address(udp, addr, result));
+
+        fut.await
     }
 }
 
+/// The error type of the `Dns` implementation
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
-pub struct DnsError(Error);
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DnsError {
+    /// The query failed, or the host has no addresses of the requested type
+    Dns(Error),
+    /// Querying an added DNS server failed with an I/O error
+    Udp(UdpError),
+    /// No added DNS server responded in time
+    Timeout,
+    /// The result of a reverse lookup does not fit in the provided buffer
+    BufferOverflow,
+    /// The operation is not supported by `embassy-net`
+    Unsupported,
+}
 
 impl From<Error> for DnsError {
     fn from(e: Error) -> Self {
-        DnsError(e)
+        DnsError::Dns(e)
+    }
+}
+
+impl From<DnsQueryError<UdpError>> for DnsError {
+    fn from(e: DnsQueryError<UdpError>) -> Self {
+        match e {
+            DnsQueryError::Io(e) => DnsError::Udp(e),
+            DnsQueryError::Timeout => DnsError::Timeout,
+            DnsQueryError::InvalidName => DnsError::Dns(Error::InvalidName),
+            DnsQueryError::BufferOverflow => DnsError::BufferOverflow,
+            DnsQueryError::NotFound
+            | DnsQueryError::Server(_)
+            | DnsQueryError::InvalidResponse
+            | DnsQueryError::Truncated => DnsError::Dns(Error::Failed),
+        }
     }
 }
 
-// TODO
 impl embedded_io_async::Error for DnsError {
     fn kind(&self) -> ErrorKind {
-        ErrorKind::Other
+        match self {
+            DnsError::Dns(Error::InvalidName | Error::NameTooLong) => ErrorKind::InvalidInput,
+            DnsError::Dns(Error::Failed) => ErrorKind::Other,
+            DnsError::Udp(e) => e.kind(),
+            DnsError::Timeout => ErrorKind::TimedOut,
+            DnsError::BufferOverflow => ErrorKind::OutOfMemory,
+            DnsError::Unsupported => ErrorKind::Unsupported,
+        }
     }
 }
@@ -2,61 +2,62 @@
 #![allow(async_fn_in_trait)]
 #![warn(clippy::large_futures)]
 
-use core::cell::{Cell, UnsafeCell};
-use core::mem::MaybeUninit;
+use core::cell::Cell;
 use core::net::SocketAddr;
-use core::ptr::NonNull;
+
+use edge_nal::{NetError, NetErrorStats};
 
 use embassy_net::IpEndpoint;
 
+/// Trace with `defmt` if the `defmt` feature is enabled, or with `log` otherwise
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::trace!($($arg)*);
+        #[cfg(not(feature = "defmt"))]
+        log::trace!($($arg)*);
+    };
+}
+
 pub use dns::*;
+pub use raw::*;
 pub use tcp::*;
 pub use udp::*;
 
+pub(crate) use pool::{alloc_wait, BufferPool, MetaBuffers, Metadata, Pool, Slab, SocketBuffers};
+
 mod dns;
+mod pool;
+mod raw;
 mod tcp;
 mod udp;
 
-pub(crate) struct Pool<T, const N: usize> {
-    used: [Cell<bool>; N],
-    data: [UnsafeCell<MaybeUninit<T>>; N],
+/// The occupancy of a pool of socket buffers, as reported by the `NoBuffers` errors when the pool is exhausted
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PoolUsage {
+    /// The number of sockets using buffers of the pool
+    pub used: usize,
+    /// The maximum number of sockets which can use buffers of the pool at the same time
+    ///
+    /// With the pools of `TcpSlabBuffers` and `UdpSlabBuffers`, fewer sockets than that in use
+    /// means that the slab itself is exhausted or too fragmented for the requested buffer sizes.
+    pub capacity: usize,
 }
 
-impl<T, const N: usize> Pool<T, N> {
-    #[allow(clippy::declare_interior_mutable_const)]
-    const VALUE: Cell<bool> = Cell::new(false);
-    #[allow(clippy::declare_interior_mutable_const)]
-    const UNINIT: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+/// Count the error of `result` (if any) in `stats`, and return `result` unchanged
+pub(crate) fn track<T, E>(stats: &Cell<NetErrorStats>, result: Result<T, E>) -> Result<T, E>
+where
+    E: NetError,
+{
+    if let Err(e) = &result {
+        let mut updated = stats.get();
+        updated.record(e.net_kind());
 
-    const fn new() -> Self {
-        Self {
-            used: [Self::VALUE; N],
-            data: [Self::UNINIT; N],
-        }
-    }
-}
-
-impl<T, const N: usize> Pool<T, N> {
-    fn alloc(&self) -> Option<NonNull<T>> {
-        for n in 0..N {
-            // this can't race because Pool is not Sync.
-            if !self.used[n].get() {
-                self.used[n].set(true);
-                let p = self.data[n].get() as *mut T;
-                return Some(unsafe { NonNull::new_unchecked(p) });
-            }
-        }
-        None
+        stats.set(updated);
     }
 
-    /// safety: p must be a pointer obtained from self.alloc that hasn't been freed yet.
-    unsafe fn free(&self, p: NonNull<T>) {
-        let origin = self.data.as_ptr() as *mut T;
-        let n = p.as_ptr().offset_from(origin);
-        assert!(n >= 0);
-        assert!((n as usize) < N);
-        self.used[n as usize].set(false);
-    }
+    result
 }
 
 pub(crate) fn to_net_socket(socket: IpEndpoint) -> SocketAddr {
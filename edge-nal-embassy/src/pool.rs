@@ -0,0 +1,282 @@
+//! The pools of socket buffers
+//!
+//! Their types are `pub` only so that the sealed traits of the pools of the sockets
+//! (`TcpBufferPool`, `UdpBufferPool`) can name them; the module itself is private.
+
+use core::cell::{Cell, RefCell, UnsafeCell};
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use core::task::{Poll, Waker};
+
+use embassy_sync::waitqueue::MultiWakerRegistration;
+
+use crate::PoolUsage;
+
+/// The TX and RX buffers of a socket, as allocated from a `BufferPool`
+pub struct SocketBuffers {
+    pub(crate) index: usize,
+    pub(crate) tx: NonNull<[u8]>,
+    pub(crate) rx: NonNull<[u8]>,
+}
+
+/// A pool of socket buffers: either one of fixed-size buffers (`TcpBuffers`, `UdpBuffers`),
+/// or one carving the buffers at runtime out of a user-provided slab (`TcpSlabBuffers`, `UdpSlabBuffers`)
+pub trait BufferPool {
+    /// Allocate the buffers of a socket, or return `None` if the pool is exhausted.
+    ///
+    /// Pools of fixed-size buffers ignore the requested sizes.
+    fn alloc(&self, tx_size: usize, rx_size: usize) -> Option<SocketBuffers>;
+
+    /// Return the current occupancy of the pool
+    fn usage(&self) -> PoolUsage;
+
+    /// Register a waker to be woken once buffers are freed
+    fn register(&self, waker: &Waker);
+
+    /// safety: `buffers` must be obtained from `self.alloc`, must not be freed yet,
+    /// and must no longer be in use by a socket.
+    unsafe fn free(&self, buffers: &SocketBuffers);
+}
+
+/// Wait until the buffers of a socket can be allocated, rather than failing like `BufferPool::alloc` does.
+///
+/// All waiters are woken when buffers are freed, and those which lose the race re-register.
+pub(crate) async fn alloc_wait<P>(pool: &P, tx_size: usize, rx_size: usize) -> SocketBuffers
+where
+    P: BufferPool,
+{
+    poll_fn(|cx| {
+        if let Some(buffers) = pool.alloc(tx_size, rx_size) {
+            return Poll::Ready(buffers);
+        }
+
+        pool.register(cx.waker());
+
+        Poll::Pending
+    })
+    .await
+}
+
+pub struct Pool<T, const N: usize> {
+    used: [Cell<bool>; N],
+    data: [UnsafeCell<MaybeUninit<T>>; N],
+    waiters: RefCell<MultiWakerRegistration<N>>,
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const VALUE: Cell<bool> = Cell::new(false);
+    #[allow(clippy::declare_interior_mutable_const)]
+    const UNINIT: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+
+    pub(crate) const fn new() -> Self {
+        Self {
+            used: [Self::VALUE; N],
+            data: [Self::UNINIT; N],
+            waiters: RefCell::new(MultiWakerRegistration::new()),
+        }
+    }
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    fn alloc(&self) -> Option<(usize, NonNull<T>)> {
+        for n in 0..N {
+            // this can't race because Pool is not Sync.
+            if !self.used[n].get() {
+                self.used[n].set(true);
+                let p = self.data[n].get() as *mut T;
+                return Some((n, unsafe { NonNull::new_unchecked(p) }));
+            }
+        }
+        None
+    }
+
+    fn usage(&self) -> PoolUsage {
+        PoolUsage {
+            used: self.used.iter().filter(|used| used.get()).count(),
+            capacity: N,
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        self.waiters.borrow_mut().register(waker);
+    }
+
+    /// safety: n must be an index obtained from self.alloc that hasn't been freed yet.
+    unsafe fn free(&self, n: usize) {
+        assert!(n < N);
+        self.used[n].set(false);
+        self.waiters.borrow_mut().wake();
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> BufferPool
+    for Pool<([u8; TX_SZ], [u8; RX_SZ]), N>
+{
+    fn alloc(&self, _tx_size: usize, _rx_size: usize) -> Option<SocketBuffers> {
+        let (index, p) = Pool::alloc(self)?;
+        let p = p.as_ptr();
+
+        // Only derive raw pointers to the two halves, as the memory is uninitialized
+        unsafe {
+            Some(SocketBuffers {
+                index,
+                tx: NonNull::new_unchecked(core::ptr::addr_of_mut!((*p).0) as *mut [u8]),
+                rx: NonNull::new_unchecked(core::ptr::addr_of_mut!((*p).1) as *mut [u8]),
+            })
+        }
+    }
+
+    fn usage(&self) -> PoolUsage {
+        Pool::usage(self)
+    }
+
+    fn register(&self, waker: &Waker) {
+        Pool::register(self, waker)
+    }
+
+    unsafe fn free(&self, buffers: &SocketBuffers) {
+        Pool::free(self, buffers.index)
+    }
+}
+
+/// A pool of up to `N` buffers of arbitrary sizes, carved at runtime out of a slab.
+///
+/// Allocations are first-fit, so sockets of different sizes can share the slab as long as
+/// it is not too fragmented.
+pub struct Slab<'s, const N: usize> {
+    slab: NonNull<[u8]>,
+    allocs: [Cell<Option<(usize, usize)>>; N],
+    waiters: RefCell<MultiWakerRegistration<N>>,
+    _slab: PhantomData<&'s mut [u8]>,
+}
+
+impl<'s, const N: usize> Slab<'s, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const FREE: Cell<Option<(usize, usize)>> = Cell::new(None);
+
+    pub(crate) fn new(slab: &'s mut [u8]) -> Self {
+        Self {
+            slab: NonNull::from(slab),
+            allocs: [Self::FREE; N],
+            waiters: RefCell::new(MultiWakerRegistration::new()),
+            _slab: PhantomData,
+        }
+    }
+
+    /// The size of the slab
+    pub(crate) fn len(&self) -> usize {
+        self.slab.len()
+    }
+
+    fn alloc(&self, size: usize) -> Option<(usize, NonNull<u8>)> {
+        let index = self.allocs.iter().position(|alloc| alloc.get().is_none())?;
+
+        let mut offset: usize = 0;
+
+        // Skip past the allocations overlapping the candidate range, until a gap large enough is found
+        loop {
+            let end = offset.checked_add(size).filter(|end| *end <= self.len())?;
+
+            let next = self
+                .allocs
+                .iter()
+                .filter_map(Cell::get)
+                .filter(|(start, len)| *start < end && offset < *start + *len)
+                .map(|(start, len)| start + len)
+                .max();
+
+            match next {
+                Some(next) => offset = next,
+                None => break,
+            }
+        }
+
+        self.allocs[index].set(Some((offset, size)));
+
+        let p = unsafe { (self.slab.as_ptr() as *mut u8).add(offset) };
+
+        Some((index, unsafe { NonNull::new_unchecked(p) }))
+    }
+
+    fn usage(&self) -> PoolUsage {
+        PoolUsage {
+            used: self
+                .allocs
+                .iter()
+                .filter(|alloc| alloc.get().is_some())
+                .count(),
+            capacity: N,
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        self.waiters.borrow_mut().register(waker);
+    }
+
+    /// safety: n must be an index obtained from self.alloc that hasn't been freed yet.
+    unsafe fn free(&self, n: usize) {
+        self.allocs[n].set(None);
+        self.waiters.borrow_mut().wake();
+    }
+}
+
+impl<const N: usize> BufferPool for Slab<'_, N> {
+    fn alloc(&self, tx_size: usize, rx_size: usize) -> Option<SocketBuffers> {
+        let (index, p) = Slab::alloc(self, tx_size.checked_add(rx_size)?)?;
+
+        Some(SocketBuffers {
+            index,
+            tx: NonNull::slice_from_raw_parts(p, tx_size),
+            rx: NonNull::slice_from_raw_parts(
+                unsafe { NonNull::new_unchecked(p.as_ptr().add(tx_size)) },
+                rx_size,
+            ),
+        })
+    }
+
+    fn usage(&self) -> PoolUsage {
+        Slab::usage(self)
+    }
+
+    fn register(&self, waker: &Waker) {
+        Slab::register(self, waker)
+    }
+
+    unsafe fn free(&self, buffers: &SocketBuffers) {
+        Slab::free(self, buffers.index)
+    }
+}
+
+/// The packet metadata of the UDP and raw sockets of `embassy-net`
+pub(crate) trait Metadata: Sized {
+    const EMPTY: Self;
+}
+
+/// The TX and RX packet metadata buffers of the `N` sockets of a pool, indexed like their TX and RX buffers
+pub(crate) struct MetaBuffers<T, const N: usize, const M: usize>([UnsafeCell<([T; M], [T; M])>; N]);
+
+impl<T, const N: usize, const M: usize> MetaBuffers<T, N, M>
+where
+    T: Metadata,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: UnsafeCell<([T; M], [T; M])> = UnsafeCell::new(([T::EMPTY; M], [T::EMPTY; M]));
+
+    pub(crate) const fn new() -> Self {
+        Self([Self::EMPTY; N])
+    }
+
+    pub(crate) fn get(&self, index: usize) -> (NonNull<[T]>, NonNull<[T]>) {
+        let p = self.0[index].get();
+
+        unsafe {
+            (
+                NonNull::new_unchecked(core::ptr::addr_of_mut!((*p).0) as *mut [T]),
+                NonNull::new_unchecked(core::ptr::addr_of_mut!((*p).1) as *mut [T]),
+            )
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use core::cell::Cell;
+use core::task::Context;
+
+use edge_nal::{
+    MacAddr, NetError, NetErrorKind, NetErrorStats, RawBind, RawReceive, RawSend, RawSplit,
+    Readable, Writable,
+};
+
+use embassy_net::raw::{IpProtocol, IpVersion, PacketMetadata, RecvError};
+use embassy_net::Stack;
+
+use embassy_net_driver::{Capabilities, HardwareAddress, LinkState};
+
+use embedded_io_async::{ErrorKind, ErrorType};
+
+use crate::{track, BufferPool, MetaBuffers, Metadata, Pool, PoolUsage, SocketBuffers};
+
+/// A struct that implements the `RawBind` factory trait from `edge-nal`
+///
+/// The sockets send and receive IP packets (including the IP header) of a single IP version and protocol,
+/// as `smoltcp` raw sockets operate on the IP rather than on the link layer:
+/// - The MAC address passed to `RawSend::send` is ignored, as the stack resolves the MAC address
+///   of the destination IP address by itself (broadcast IP packets are sent to the broadcast MAC address)
+/// - The MAC address returned by `RawReceive::receive` is always all zeros, as the stack does not report it
+pub struct Raw<
+    'd,
+    const N: usize,
+    const TX_SZ: usize = 1500,
+    const RX_SZ: usize = 1500,
+    const M: usize = 2,
+> {
+    stack: Stack<'d>,
+    buffers: &'d RawBuffers<N, TX_SZ, RX_SZ, M>,
+    ip_version: IpVersion,
+    ip_protocol: IpProtocol,
+    stats: Cell<NetErrorStats>,
+}
+
+impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
+    Raw<'d, N, TX_SZ, RX_SZ, M>
+{
+    /// Create a new `Raw` instance for the provided Embassy networking stack, using the provided raw buffers,
+    /// whose sockets send and receive IPv4 UDP packets (as used by the DHCP client and server of `edge-dhcp`)
+    ///
+    /// Ensure that the number of buffers `N` fits within StackResources<N> of
+    /// [embassy_net::Stack], while taking into account the sockets used for DHCP, DNS, etc. else
+    /// [smoltcp::iface::SocketSet] will panic with `adding a socket to a full SocketSet`.
+    pub fn new(stack: Stack<'d>, buffers: &'d RawBuffers<N, TX_SZ, RX_SZ, M>) -> Self {
+        Self::new_with_protocol(stack, buffers, IpVersion::Ipv4, IpProtocol::Udp)
+    }
+
+    /// Create a new `Raw` instance for the provided Embassy networking stack, using the provided raw buffers,
+    /// whose sockets send and receive packets of the provided IP version and protocol (e.g. `IpProtocol::Icmp`)
+    pub fn new_with_protocol(
+        stack: Stack<'d>,
+        buffers: &'d RawBuffers<N, TX_SZ, RX_SZ, M>,
+        ip_version: IpVersion,
+        ip_protocol: IpProtocol,
+    ) -> Self {
+        Self {
+            stack,
+            buffers,
+            ip_version,
+            ip_protocol,
+            stats: Cell::new(NetErrorStats::new()),
+        }
+    }
+
+    /// Return the counters of the errors of the bind operations
+    ///
+    /// Errors which occur on already bound sockets are counted by the sockets themselves
+    /// (see `RawSocket::error_stats`).
+    pub fn error_stats(&self) -> NetErrorStats {
+        self.stats.get()
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> RawBind
+    for Raw<'_, N, TX_SZ, RX_SZ, M>
+{
+    type Error = RawError;
+
+    type Socket<'a>
+        = RawSocket<'a, N, TX_SZ, RX_SZ, M>
+    where
+        Self: 'a;
+
+    async fn bind(&self) -> Result<Self::Socket<'_>, Self::Error> {
+        let pool = &self.buffers.pool;
+
+        let socket_buffers = track(
+            &self.stats,
+            pool.alloc(TX_SZ, RX_SZ)
+                .ok_or_else(|| RawError::NoBuffers(pool.usage())),
+        )?;
+
+        Ok(RawSocket::new(
+            self.stack,
+            self.buffers,
+            socket_buffers,
+            self.ip_version,
+            self.ip_protocol,
+        ))
+    }
+}
+
+/// A raw socket
+/// Implements the `RawReceive`, `RawSend` and `RawSplit` traits from `edge-nal`
+pub struct RawSocket<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
+    socket: embassy_net::raw::RawSocket<'d>,
+    stack_buffers: &'d RawBuffers<N, TX_SZ, RX_SZ, M>,
+    socket_buffers: SocketBuffers,
+    stats: Cell<NetErrorStats>,
+}
+
+impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
+    RawSocket<'d, N, TX_SZ, RX_SZ, M>
+{
+    fn new(
+        stack: Stack<'d>,
+        stack_buffers: &'d RawBuffers<N, TX_SZ, RX_SZ, M>,
+        socket_buffers: SocketBuffers,
+        ip_version: IpVersion,
+        ip_protocol: IpProtocol,
+    ) -> Self {
+        let (tx_meta, rx_meta) = stack_buffers.meta.get(socket_buffers.index);
+
+        let usage = stack_buffers.pool.usage();
+
+        trace!(
+            "Raw socket {} opened ({}/{} sockets in use)",
+            socket_buffers.index,
+            usage.used,
+            usage.capacity
+        );
+
+        Self {
+            socket: unsafe {
+                embassy_net::raw::RawSocket::new::<NoDriver>(
+                    stack,
+                    ip_version,
+                    ip_protocol,
+                    &mut *rx_meta.as_ptr(),
+                    &mut *socket_buffers.rx.as_ptr(),
+                    &mut *tx_meta.as_ptr(),
+                    &mut *socket_buffers.tx.as_ptr(),
+                )
+            },
+            stack_buffers,
+            socket_buffers,
+            stats: Cell::new(NetErrorStats::new()),
+        }
+    }
+
+    /// Return the counters of the errors which occurred on this socket, including on its split halves
+    pub fn error_stats(&self) -> NetErrorStats {
+        self.stats.get()
+    }
+
+    /// Reset the error counters of this socket
+    pub fn clear_error_stats(&mut self) {
+        self.stats.set(NetErrorStats::new());
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Drop
+    for RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    fn drop(&mut self) {
+        unsafe {
+            self.stack_buffers.pool.free(&self.socket_buffers);
+        }
+
+        trace!("Raw socket {} closed", self.socket_buffers.index);
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> ErrorType
+    for RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    type Error = RawError;
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> RawReceive
+    for RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+        let mut rself = &*self;
+
+        rself.receive(buffer).await
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> RawSend
+    for RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn send(&mut self, addr: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.send(addr, data).await
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Readable
+    for RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.socket.wait_recv_ready().await;
+        Ok(())
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Writable
+    for RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.wait_send_ready().await;
+        Ok(())
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> ErrorType
+    for &RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    type Error = RawError;
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> RawReceive
+    for &RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+        // `smoltcp` drops the packets which do not fit in the buffer with a `Truncated` error, without reporting their size
+        let result = self.socket.recv(buffer).await.map_err(RawError::from);
+
+        let len = track(&self.stats, result)?;
+
+        Ok((len, [0; 6]))
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> RawSend
+    for &RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn send(&mut self, _addr: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+        self.socket.send(data).await;
+
+        Ok(())
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Readable
+    for &RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.socket.wait_recv_ready().await;
+        Ok(())
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Writable
+    for &RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.wait_send_ready().await;
+        Ok(())
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> RawSplit
+    for RawSocket<'_, N, TX_SZ, RX_SZ, M>
+{
+    type Receive<'a>
+        = &'a Self
+    where
+        Self: 'a;
+
+    type Send<'a>
+        = &'a Self
+    where
+        Self: 'a;
+
+    fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
+        (&*self, &*self)
+    }
+}
+
+/// A shared error type that is used by the raw factory trait implementation as well as the raw socket
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RawError {
+    Recv(RecvError),
+    /// All sockets of the pool are in use
+    NoBuffers(PoolUsage),
+}
+
+impl From<RecvError> for RawError {
+    fn from(e: RecvError) -> Self {
+        RawError::Recv(e)
+    }
+}
+
+impl embedded_io_async::Error for RawError {
+    fn kind(&self) -> ErrorKind {
+        self.net_kind().io_kind()
+    }
+}
+
+impl NetError for RawError {
+    fn net_kind(&self) -> NetErrorKind {
+        match self {
+            RawError::Recv(RecvError::Truncated) => NetErrorKind::Truncated,
+            RawError::NoBuffers(_) => NetErrorKind::NoBuffers,
+        }
+    }
+}
+
+impl Metadata for PacketMetadata {
+    const EMPTY: Self = PacketMetadata::EMPTY;
+}
+
+/// A struct that holds a pool of N raw buffers, each with TX and RX buffers of TX_SZ and RX_SZ bytes,
+/// and TX and RX packet metadata buffers for M packets
+pub struct RawBuffers<
+    const N: usize,
+    const TX_SZ: usize = 1500,
+    const RX_SZ: usize = 1500,
+    const M: usize = 2,
+> {
+    pool: Pool<([u8; TX_SZ], [u8; RX_SZ]), N>,
+    meta: MetaBuffers<PacketMetadata, N, M>,
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Default
+    for RawBuffers<N, TX_SZ, RX_SZ, M>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
+    RawBuffers<N, TX_SZ, RX_SZ, M>
+{
+    /// Create a new `RawBuffers` instance
+    pub const fn new() -> Self {
+        Self {
+            pool: Pool::new(),
+            meta: MetaBuffers::new(),
+        }
+    }
+}
+
+/// `RawSocket::new` of `embassy-net` 0.5 still has a `D: Driver` type parameter, which is unused
+/// since the stack is no longer generic over its driver, so it is instantiated with this uninhabited type
+enum NoDriver {}
+
+impl embassy_net_driver::Driver for NoDriver {
+    type RxToken<'a> = NoDriver;
+    type TxToken<'a> = NoDriver;
+
+    fn receive(&mut self, _cx: &mut Context) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        match *self {}
+    }
+
+    fn transmit(&mut self, _cx: &mut Context) -> Option<Self::TxToken<'_>> {
+        match *self {}
+    }
+
+    fn link_state(&mut self, _cx: &mut Context) -> LinkState {
+        match *self {}
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        match *self {}
+    }
+
+    fn hardware_address(&self) -> HardwareAddress {
+        match *self {}
+    }
+}
+
+impl embassy_net_driver::RxToken for NoDriver {
+    fn consume<R, F>(self, _f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        match self {}
+    }
+}
+
+impl embassy_net_driver::TxToken for NoDriver {
+    fn consume<R, F>(self, _len: usize, _f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        match self {}
+    }
+}
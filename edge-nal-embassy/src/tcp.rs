@@ -1,8 +1,13 @@
+use core::cell::Cell;
 use core::net::SocketAddr;
 use core::pin::pin;
-use core::ptr::NonNull;
+use core::time::Duration;
 
-use edge_nal::{Close, Readable, TcpBind, TcpConnect, TcpShutdown, TcpSplit};
+use edge_nal::{
+    BindToInterface, Close, IpOptions, NetError, NetErrorKind, NetErrorStats, Readable,
+    SocketStats, Stats, TcpBind, TcpConnect, TcpOptions, TcpShutdown, TcpSplit, VectoredCursor,
+    Writable, WriteVectored,
+};
 
 use embassy_futures::join::join;
 
@@ -11,13 +16,28 @@ use embassy_net::Stack;
 
 use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
 
-use crate::{to_net_socket, Pool};
+use crate::{alloc_wait, to_net_socket, track, BufferPool, Pool, PoolUsage, Slab, SocketBuffers};
 
 /// A struct that implements the `TcpConnect` and `TcpBind` factory traits from `edge-nal`
 /// Capable of managing up to N concurrent connections with TX and RX buffers according to TX_SZ and RX_SZ.
-pub struct Tcp<'d, const N: usize, const TX_SZ: usize = 1024, const RX_SZ: usize = 1024> {
+pub type Tcp<'d, const N: usize, const TX_SZ: usize = 1024, const RX_SZ: usize = 1024> =
+    GenericTcp<'d, TcpBuffers<N, TX_SZ, RX_SZ>>;
+
+/// A struct that implements the `TcpConnect` and `TcpBind` factory traits from `edge-nal`
+/// Capable of managing up to N concurrent connections with TX and RX buffers carved out of the slab of a `TcpSlabBuffers`.
+pub type SlabTcp<'d, 's, const N: usize> = GenericTcp<'d, TcpSlabBuffers<'s, N>>;
+
+/// The `Tcp` and `SlabTcp` factories, which only differ in the pool their sockets allocate their buffers from
+pub struct GenericTcp<'d, B>
+where
+    B: TcpBufferPool,
+{
     stack: Stack<'d>,
-    buffers: &'d TcpBuffers<N, TX_SZ, RX_SZ>,
+    buffers: &'d B,
+    tx_size: usize,
+    rx_size: usize,
+    stats: Cell<NetErrorStats>,
+    wait_for_buffers: bool,
 }
 
 impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> Tcp<'d, N, TX_SZ, RX_SZ> {
@@ -27,98 +47,337 @@ impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> Tcp<'d, N, TX_S
     /// [embassy_net::Stack], while taking into account the sockets used for DHCP, DNS, etc. else
     /// [smoltcp::iface::SocketSet] will panic with `adding a socket to a full SocketSet`.
     pub fn new(stack: Stack<'d>, buffers: &'d TcpBuffers<N, TX_SZ, RX_SZ>) -> Self {
-        Self { stack, buffers }
+        Self::wrap(stack, buffers, TX_SZ, RX_SZ)
+    }
+}
+
+impl<'d, 's, const N: usize> SlabTcp<'d, 's, N> {
+    /// Create a new `SlabTcp` instance for the provided Embassy networking stack, whose sockets have
+    /// TX and RX buffers of `tx_size` and `rx_size` bytes carved out of the slab of the provided TCP buffers.
+    ///
+    /// Multiple instances with different buffer sizes can share the same `TcpSlabBuffers`,
+    /// i.e. one with a few large sockets for HTTP and another with many small ones.
+    /// The same limit of `N` sockets as with `Tcp::new` applies.
+    ///
+    /// # Panics
+    ///
+    /// If the buffers of a single socket do not fit in the slab.
+    pub fn new(
+        stack: Stack<'d>,
+        buffers: &'d TcpSlabBuffers<'s, N>,
+        tx_size: usize,
+        rx_size: usize,
+    ) -> Self {
+        assert!(
+            tx_size.saturating_add(rx_size) <= buffers.slab.len(),
+            "The socket buffers do not fit in the slab"
+        );
+
+        Self::wrap(stack, buffers, tx_size, rx_size)
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpConnect
-    for Tcp<'_, N, TX_SZ, RX_SZ>
+impl<'d, B> GenericTcp<'d, B>
+where
+    B: TcpBufferPool,
+{
+    fn wrap(stack: Stack<'d>, buffers: &'d B, tx_size: usize, rx_size: usize) -> Self {
+        Self {
+            stack,
+            buffers,
+            tx_size,
+            rx_size,
+            stats: Cell::new(NetErrorStats::new()),
+            wait_for_buffers: false,
+        }
+    }
+
+    /// Make `connect` and `accept` wait until a socket of the pool is dropped when the pool is exhausted,
+    /// rather than failing with `TcpError::NoBuffers`.
+    ///
+    /// This way, a burst of connections exceeding the pool is queued instead of erroring out.
+    /// Use `try_connect` and `TcpAccept::try_accept` where failing is preferred even then.
+    pub fn with_wait_for_buffers(self, wait_for_buffers: bool) -> Self {
+        Self {
+            wait_for_buffers,
+            ..self
+        }
+    }
+
+    /// Same as `TcpConnect::connect`, except that it fails with `TcpError::NoBuffers` when all sockets
+    /// of the pool are in use, even if `with_wait_for_buffers` is enabled
+    pub async fn try_connect(
+        &self,
+        remote: SocketAddr,
+    ) -> Result<GenericTcpSocket<'_, B>, TcpError> {
+        let fut = pin!(self.connect_with(remote, false));
+
+        fut.await
+    }
+
+    /// Return the counters of the errors of the connect and accept operations
+    ///
+    /// Errors which occur on already connected or accepted sockets are counted by the sockets themselves
+    /// (see `TcpSocket::error_stats`).
+    pub fn error_stats(&self) -> NetErrorStats {
+        self.stats.get()
+    }
+
+    async fn socket(&self, wait: bool) -> Result<GenericTcpSocket<'_, B>, TcpError> {
+        let pool = self.buffers.pool();
+
+        let socket_buffers = if wait {
+            alloc_wait(pool, self.tx_size, self.rx_size).await
+        } else {
+            track(
+                &self.stats,
+                pool.alloc(self.tx_size, self.rx_size)
+                    .ok_or_else(|| TcpError::NoBuffers(pool.usage())),
+            )?
+        };
+
+        Ok(GenericTcpSocket::new(
+            self.stack,
+            self.buffers,
+            socket_buffers,
+        ))
+    }
+
+    async fn connect_with(
+        &self,
+        remote: SocketAddr,
+        wait: bool,
+    ) -> Result<GenericTcpSocket<'_, B>, TcpError> {
+        let mut socket = self.socket(wait).await?;
+
+        track(
+            &self.stats,
+            socket.socket.connect(remote).await.map_err(TcpError::from),
+        )?;
+
+        Ok(socket)
+    }
+}
+
+impl<B> TcpConnect for GenericTcp<'_, B>
+where
+    B: TcpBufferPool,
 {
     type Error = TcpError;
 
     type Socket<'a>
-        = TcpSocket<'a, N, TX_SZ, RX_SZ>
+        = GenericTcpSocket<'a, B>
     where
         Self: 'a;
 
     async fn connect(&self, remote: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
-        let mut socket = TcpSocket::new(self.stack, self.buffers)?;
-
-        socket.socket.connect(remote).await?;
+        let fut = pin!(self.connect_with(remote, self.wait_for_buffers));
 
-        Ok(socket)
+        fut.await
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpBind for Tcp<'_, N, TX_SZ, RX_SZ> {
+impl<B> TcpBind for GenericTcp<'_, B>
+where
+    B: TcpBufferPool,
+{
     type Error = TcpError;
 
     type Accept<'a>
-        = TcpAccept<'a, N, TX_SZ, RX_SZ>
+        = GenericTcpAccept<'a, B>
     where
         Self: 'a;
 
     async fn bind(&self, local: SocketAddr) -> Result<Self::Accept<'_>, Self::Error> {
-        Ok(TcpAccept { stack: self, local })
+        Ok(GenericTcpAccept { stack: self, local })
     }
 }
 
 /// Represents an acceptor for incoming TCP client connections. Implements the `TcpAccept` factory trait from `edge-nal`
-pub struct TcpAccept<'d, const N: usize, const TX_SZ: usize = 1024, const RX_SZ: usize = 1024> {
-    stack: &'d Tcp<'d, N, TX_SZ, RX_SZ>,
+pub type TcpAccept<'d, const N: usize, const TX_SZ: usize = 1024, const RX_SZ: usize = 1024> =
+    GenericTcpAccept<'d, TcpBuffers<N, TX_SZ, RX_SZ>>;
+
+/// Represents an acceptor for incoming TCP client connections of a `SlabTcp`. Implements the `TcpAccept` factory trait from `edge-nal`
+pub type SlabTcpAccept<'d, 's, const N: usize> = GenericTcpAccept<'d, TcpSlabBuffers<'s, N>>;
+
+/// The acceptors of `Tcp` and `SlabTcp`
+pub struct GenericTcpAccept<'d, B>
+where
+    B: TcpBufferPool,
+{
+    stack: &'d GenericTcp<'d, B>,
     local: SocketAddr,
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> edge_nal::TcpAccept
-    for TcpAccept<'_, N, TX_SZ, RX_SZ>
+impl<B> GenericTcpAccept<'_, B>
+where
+    B: TcpBufferPool,
+{
+    /// Same as `edge_nal::TcpAccept::accept`, except that it fails with `TcpError::NoBuffers` when all sockets
+    /// of the pool are in use, even if `Tcp::with_wait_for_buffers` is enabled
+    pub async fn try_accept(&self) -> Result<(SocketAddr, GenericTcpSocket<'_, B>), TcpError> {
+        let fut = pin!(self.accept_with(false));
+
+        fut.await
+    }
+
+    async fn accept_with(
+        &self,
+        wait: bool,
+    ) -> Result<(SocketAddr, GenericTcpSocket<'_, B>), TcpError> {
+        let mut socket = self.stack.socket(wait).await?;
+
+        track(
+            &self.stack.stats,
+            socket
+                .socket
+                .accept(self.local)
+                .await
+                .map_err(TcpError::from),
+        )?;
+
+        let local_endpoint = socket.socket.local_endpoint().unwrap();
+
+        Ok((to_net_socket(local_endpoint), socket))
+    }
+}
+
+impl<B> edge_nal::TcpAccept for GenericTcpAccept<'_, B>
+where
+    B: TcpBufferPool,
 {
     type Error = TcpError;
 
     type Socket<'a>
-        = TcpSocket<'a, N, TX_SZ, RX_SZ>
+        = GenericTcpSocket<'a, B>
     where
         Self: 'a;
 
     async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error> {
-        let mut socket = TcpSocket::new(self.stack.stack, self.stack.buffers)?;
-
-        socket.socket.accept(self.local).await?;
-
-        let local_endpoint = socket.socket.local_endpoint().unwrap();
+        let fut = pin!(self.accept_with(self.stack.wait_for_buffers));
 
-        Ok((to_net_socket(local_endpoint), socket))
+        fut.await
     }
 }
 
 /// A TCP socket
 /// Implements the `Read` and `Write` traits from `embedded-io-async`, as well as the `TcpSplit` factory trait from `edge-nal`
-pub struct TcpSocket<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> {
+pub type TcpSocket<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> =
+    GenericTcpSocket<'d, TcpBuffers<N, TX_SZ, RX_SZ>>;
+
+/// A TCP socket of a `SlabTcp`
+/// Implements the `Read` and `Write` traits from `embedded-io-async`, as well as the `TcpSplit` factory trait from `edge-nal`
+pub type SlabTcpSocket<'d, 's, const N: usize> = GenericTcpSocket<'d, TcpSlabBuffers<'s, N>>;
+
+/// The sockets of `Tcp` and `SlabTcp`
+pub struct GenericTcpSocket<'d, B>
+where
+    B: TcpBufferPool,
+{
     socket: embassy_net::tcp::TcpSocket<'d>,
-    stack_buffers: &'d TcpBuffers<N, TX_SZ, RX_SZ>,
-    socket_buffers: NonNull<([u8; TX_SZ], [u8; RX_SZ])>,
+    stack_buffers: &'d B,
+    socket_buffers: SocketBuffers,
+    stats: Cell<NetErrorStats>,
+    sent: Cell<u64>,
+    received: Cell<u64>,
+    keepalive: Option<Duration>,
+    keepalive_count: Option<u32>,
+    user_timeout: Option<Duration>,
 }
 
-impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpSocket<'d, N, TX_SZ, RX_SZ> {
-    fn new(
-        stack: Stack<'d>,
-        stack_buffers: &'d TcpBuffers<N, TX_SZ, RX_SZ>,
-    ) -> Result<Self, TcpError> {
-        let mut socket_buffers = stack_buffers.pool.alloc().ok_or(TcpError::NoBuffers)?;
+impl<'d, B> GenericTcpSocket<'d, B>
+where
+    B: TcpBufferPool,
+{
+    fn new(stack: Stack<'d>, stack_buffers: &'d B, socket_buffers: SocketBuffers) -> Self {
+        let usage = stack_buffers.pool().usage();
+
+        trace!(
+            "TCP socket {} opened ({}/{} sockets in use)",
+            socket_buffers.index,
+            usage.used,
+            usage.capacity
+        );
 
-        Ok(Self {
+        Self {
             socket: unsafe {
                 embassy_net::tcp::TcpSocket::new(
                     stack,
-                    &mut socket_buffers.as_mut().1,
-                    &mut socket_buffers.as_mut().0,
+                    &mut *socket_buffers.rx.as_ptr(),
+                    &mut *socket_buffers.tx.as_ptr(),
                 )
             },
             stack_buffers,
             socket_buffers,
-        })
+            stats: Cell::new(NetErrorStats::new()),
+            sent: Cell::new(0),
+            received: Cell::new(0),
+            keepalive: None,
+            keepalive_count: None,
+            user_timeout: None,
+        }
+    }
+
+    /// Return the counters of the errors which occurred on this socket, including on its split halves
+    pub fn error_stats(&self) -> NetErrorStats {
+        self.stats.get()
+    }
+
+    /// Reset the error counters of this socket
+    pub fn clear_error_stats(&mut self) {
+        self.stats.set(NetErrorStats::new());
+    }
+
+    /// Return `true` if the peer might still send data, i.e. the connection is established
+    /// and the peer has not closed its write half yet (no FIN or RST was received).
+    ///
+    /// A cheap check of the connection liveness, i.e. before reusing a kept-alive HTTP connection.
+    /// Note that a peer which vanished without closing the connection is only detected by the keep-alive
+    /// probes or the timeout (see `TcpOptions`).
+    pub fn may_recv(&self) -> bool {
+        self.socket.may_recv()
+    }
+
+    /// Return `true` if data can still be sent, i.e. the connection is established
+    /// and neither was it closed locally, nor reset by the peer.
+    pub fn may_send(&self) -> bool {
+        self.socket.may_send()
+    }
+
+    /// Provides scoped access to the underlying `embassy-net` TCP socket (which in turn wraps the `smoltcp` one),
+    /// so that socket options not (yet) modeled by `edge-nal` - like the timeout - can be tweaked.
+    ///
+    /// The socket is passed to the closure with a lifetime unique to the call, which prevents the closure
+    /// from swapping it with another socket (and thus from mixing up the buffers of this socket's pool).
+    pub fn with_smoltcp_socket<F, R>(&mut self, f: F) -> R
+    where
+        F: for<'s> FnOnce(&mut embassy_net::tcp::TcpSocket<'s>) -> R,
+    {
+        f(&mut self.socket)
+    }
+
+    /// Apply the `smoltcp` timeout, which is the time without any segment from the peer after which
+    /// the connection is aborted.
+    ///
+    /// An explicit user timeout wins; otherwise, with keep-alive probes sent every `idle` interval,
+    /// the connection is dropped after `count` unanswered probes, like on other stacks.
+    fn apply_timeout(&mut self) {
+        let timeout = self.user_timeout.or_else(|| {
+            self.keepalive
+                .zip(self.keepalive_count)
+                .map(|(idle, count)| idle.saturating_mul(count.saturating_add(1)))
+        });
+
+        self.socket.set_timeout(timeout.map(to_embassy_duration));
     }
 
     async fn close(&mut self, what: Close) -> Result<(), TcpError> {
+        let result = self.close_inner(what).await;
+
+        track(&self.stats, result)
+    }
+
+    async fn close_inner(&mut self, what: Close) -> Result<(), TcpError> {
         async fn discard_all_data(rx: &mut TcpReader<'_>) -> Result<(), TcpError> {
             let mut buf = [0; 32];
 
@@ -153,53 +412,93 @@ impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpSocket<'d, N
 
     async fn abort(&mut self) -> Result<(), TcpError> {
         self.socket.abort();
-        self.socket.flush().await?;
 
-        Ok(())
+        let result = self.socket.flush().await.map_err(TcpError::from);
+
+        track(&self.stats, result)
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> Drop
-    for TcpSocket<'_, N, TX_SZ, RX_SZ>
+impl<B> Drop for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
 {
     fn drop(&mut self) {
         unsafe {
             self.socket.close();
-            self.stack_buffers.pool.free(self.socket_buffers);
+            self.stack_buffers.pool().free(&self.socket_buffers);
         }
+
+        trace!("TCP socket {} closed", self.socket_buffers.index);
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> ErrorType
-    for TcpSocket<'_, N, TX_SZ, RX_SZ>
+impl<B> ErrorType for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
 {
     type Error = TcpError;
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> Read
-    for TcpSocket<'_, N, TX_SZ, RX_SZ>
+impl<B> Read for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
 {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        Ok(self.socket.read(buf).await?)
+        let result = self.socket.read(buf).await.map_err(TcpError::from);
+
+        count(&self.received, track(&self.stats, result))
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> Write
-    for TcpSocket<'_, N, TX_SZ, RX_SZ>
+impl<B> Write for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-        Ok(self.socket.write(buf).await?)
+        let result = self.socket.write(buf).await.map_err(TcpError::from);
+
+        count(&self.sent, track(&self.stats, result))
     }
 
     async fn flush(&mut self) -> Result<(), Self::Error> {
-        self.socket.flush().await?;
+        let result = self.socket.flush().await.map_err(TcpError::from);
+
+        track(&self.stats, result)
+    }
+}
+
+// The slices are copied straight into the TX buffer of the socket, so that they are sent together
+impl<B> WriteVectored for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
+{
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        let mut cursor = VectoredCursor::new(bufs);
+
+        while !cursor.is_empty() {
+            let result = self
+                .socket
+                .write_with(|buf| {
+                    let len = cursor.copy_to(buf);
+                    (len, len)
+                })
+                .await
+                .map_err(TcpError::from);
+
+            count(&self.sent, track(&self.stats, result))?;
+        }
 
         Ok(())
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> Readable
-    for TcpSocket<'_, N, TX_SZ, RX_SZ>
+// The readiness is waited for on the wakers of the `smoltcp` socket. `embassy-net` also reports readiness
+// once the peer closes the connection (see `may_recv` and `may_send`), so that the EOF or the error is
+// observed by the following read or write rather than waiting forever
+impl<B> Readable for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
 {
     async fn readable(&mut self) -> Result<(), Self::Error> {
         self.socket.wait_read_ready().await;
@@ -207,21 +506,128 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> Readable
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpShutdown
-    for TcpSocket<'_, N, TX_SZ, RX_SZ>
+impl<B> Writable for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.wait_write_ready().await;
+        Ok(())
+    }
+}
+
+impl<B> TcpShutdown for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
 {
     async fn close(&mut self, what: Close) -> Result<(), Self::Error> {
-        TcpSocket::close(self, what).await
+        GenericTcpSocket::close(self, what).await
     }
 
     async fn abort(&mut self) -> Result<(), Self::Error> {
-        TcpSocket::abort(self).await
+        GenericTcpSocket::abort(self).await
+    }
+}
+
+impl<B> TcpOptions for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
+{
+    async fn set_nodelay(&mut self, nodelay: bool) -> Result<(), Self::Error> {
+        self.socket.set_nagle_enabled(!nodelay);
+
+        Ok(())
+    }
+
+    async fn set_keepalive(&mut self, idle: Option<Duration>) -> Result<(), Self::Error> {
+        self.socket.set_keep_alive(idle.map(to_embassy_duration));
+
+        self.keepalive = idle;
+        self.apply_timeout();
+
+        Ok(())
+    }
+
+    async fn set_keepalive_probes(
+        &mut self,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> Result<(), Self::Error> {
+        // `smoltcp` sends the probes at the idle interval, so only the count can be honored
+        if interval.is_some() {
+            return track(&self.stats, Err(TcpError::Unsupported));
+        }
+
+        self.keepalive_count = count;
+        self.apply_timeout();
+
+        Ok(())
+    }
+
+    async fn set_user_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        self.user_timeout = timeout;
+        self.apply_timeout();
+
+        Ok(())
+    }
+
+    async fn set_linger(&mut self, linger: Option<Duration>) -> Result<(), Self::Error> {
+        // `smoltcp` always tries to deliver the unsent data on close, without blocking the closing of the socket
+        if linger.is_some() {
+            track(&self.stats, Err(TcpError::Unsupported))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<B> BindToInterface for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
+{
+    async fn bind_to_interface(&mut self, _interface: Option<u32>) -> Result<(), Self::Error> {
+        // An `embassy-net` stack has a single interface, so the socket is always bound to it
+        Ok(())
+    }
+}
+
+impl<B> IpOptions for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
+{
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        self.socket.set_hop_limit(Some(ttl));
+
+        Ok(())
+    }
+
+    async fn set_tos(&mut self, _tos: u8) -> Result<(), Self::Error> {
+        track(&self.stats, Err(TcpError::Unsupported))
+    }
+}
+
+impl<B> Stats for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
+{
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        let mut stats = SocketStats::new();
+
+        // The bytes are counted as written to / read from the socket buffers,
+        // as `smoltcp` neither tracks the traffic, nor exposes its RTT estimate
+        stats.local = self.socket.local_endpoint().map(to_net_socket);
+        stats.remote = self.socket.remote_endpoint().map(to_net_socket);
+        stats.bytes_sent = Some(self.sent.get());
+        stats.bytes_received = Some(self.received.get());
+        stats.send_queue = Some(self.socket.send_queue());
+
+        Ok(stats)
     }
 }
 
 /// Represents the read half of a split TCP socket
 /// Implements the `Read` trait from `embedded-io-async`
-pub struct TcpSocketRead<'a>(TcpReader<'a>);
+pub struct TcpSocketRead<'a>(TcpReader<'a>, &'a Cell<NetErrorStats>, &'a Cell<u64>);
 
 impl ErrorType for TcpSocketRead<'_> {
     type Error = TcpError;
@@ -229,7 +635,9 @@ impl ErrorType for TcpSocketRead<'_> {
 
 impl Read for TcpSocketRead<'_> {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        self.0.read(buf).await.map_err(TcpError::from)
+        let result = self.0.read(buf).await.map_err(TcpError::from);
+
+        count(self.2, track(self.1, result))
     }
 }
 
@@ -242,7 +650,7 @@ impl Readable for TcpSocketRead<'_> {
 
 /// Represents the write half of a split TCP socket
 /// Implements the `Write` trait from `embedded-io-async`
-pub struct TcpSocketWrite<'a>(TcpWriter<'a>);
+pub struct TcpSocketWrite<'a>(TcpWriter<'a>, &'a Cell<NetErrorStats>, &'a Cell<u64>);
 
 impl ErrorType for TcpSocketWrite<'_> {
     type Error = TcpError;
@@ -250,16 +658,49 @@ impl ErrorType for TcpSocketWrite<'_> {
 
 impl Write for TcpSocketWrite<'_> {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-        self.0.write(buf).await.map_err(TcpError::from)
+        let result = self.0.write(buf).await.map_err(TcpError::from);
+
+        count(self.2, track(self.1, result))
     }
 
     async fn flush(&mut self) -> Result<(), Self::Error> {
-        self.0.flush().await.map_err(TcpError::from)
+        let result = self.0.flush().await.map_err(TcpError::from);
+
+        track(self.1, result)
+    }
+}
+
+impl WriteVectored for TcpSocketWrite<'_> {
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        let mut cursor = VectoredCursor::new(bufs);
+
+        while !cursor.is_empty() {
+            let result = self
+                .0
+                .write_with(|buf| {
+                    let len = cursor.copy_to(buf);
+                    (len, len)
+                })
+                .await
+                .map_err(TcpError::from);
+
+            count(self.2, track(self.1, result))?;
+        }
+
+        Ok(())
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpSplit
-    for TcpSocket<'_, N, TX_SZ, RX_SZ>
+impl Writable for TcpSocketWrite<'_> {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_write_ready().await;
+        Ok(())
+    }
+}
+
+impl<B> TcpSplit for GenericTcpSocket<'_, B>
+where
+    B: TcpBufferPool,
 {
     type Read<'a>
         = TcpSocketRead<'a>
@@ -274,17 +715,32 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpSplit
     fn split(&mut self) -> (Self::Read<'_>, Self::Write<'_>) {
         let (read, write) = self.socket.split();
 
-        (TcpSocketRead(read), TcpSocketWrite(write))
+        (
+            TcpSocketRead(read, &self.stats, &self.received),
+            TcpSocketWrite(write, &self.stats, &self.sent),
+        )
+    }
+}
+
+/// Add the number of bytes transferred by a successful read or write to `counter`
+fn count(counter: &Cell<u64>, result: Result<usize, TcpError>) -> Result<usize, TcpError> {
+    if let Ok(len) = result {
+        counter.set(counter.get() + len as u64);
     }
+
+    result
 }
 
 /// A shared error type that is used by the TCP factory traits implementation as well as the TCP socket
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum TcpError {
     General(Error),
     Connect(ConnectError),
     Accept(AcceptError),
-    NoBuffers,
+    /// All sockets of the pool are in use
+    NoBuffers(PoolUsage),
+    Unsupported,
 }
 
 impl From<Error> for TcpError {
@@ -305,19 +761,36 @@ impl From<AcceptError> for TcpError {
     }
 }
 
-// TODO
 impl embedded_io_async::Error for TcpError {
     fn kind(&self) -> ErrorKind {
+        self.net_kind().io_kind()
+    }
+}
+
+impl NetError for TcpError {
+    fn net_kind(&self) -> NetErrorKind {
         match self {
-            TcpError::General(_) => ErrorKind::Other,
-            TcpError::Connect(_) => ErrorKind::Other,
-            TcpError::Accept(_) => ErrorKind::Other,
-            TcpError::NoBuffers => ErrorKind::OutOfMemory,
+            TcpError::General(Error::ConnectionReset) => NetErrorKind::ConnectionReset,
+            TcpError::Connect(ConnectError::InvalidState) => NetErrorKind::InvalidState,
+            // A RST in reply to the SYN
+            TcpError::Connect(ConnectError::ConnectionReset) => NetErrorKind::ConnectionRefused,
+            TcpError::Connect(ConnectError::TimedOut) => NetErrorKind::TimedOut,
+            TcpError::Connect(ConnectError::NoRoute) => NetErrorKind::NoRoute,
+            TcpError::Accept(AcceptError::InvalidState) => NetErrorKind::InvalidState,
+            TcpError::Accept(AcceptError::InvalidPort) => NetErrorKind::AddrNotAvailable,
+            // A RST before the handshake completed
+            TcpError::Accept(AcceptError::ConnectionReset) => NetErrorKind::ConnectionAborted,
+            TcpError::NoBuffers(_) => NetErrorKind::NoBuffers,
+            TcpError::Unsupported => NetErrorKind::Unsupported,
         }
     }
 }
 
-/// A struct that holds a pool of TCP buffers
+fn to_embassy_duration(duration: Duration) -> embassy_time::Duration {
+    embassy_time::Duration::from_micros(duration.as_micros() as _)
+}
+
+/// A struct that holds a pool of N TCP buffers, each with TX and RX buffers of TX_SZ and RX_SZ bytes
 pub struct TcpBuffers<const N: usize, const TX_SZ: usize, const RX_SZ: usize> {
     pool: Pool<([u8; TX_SZ], [u8; RX_SZ]), N>,
 }
@@ -336,3 +809,58 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpBuffers<N, TX_SZ
         Self { pool: Pool::new() }
     }
 }
+
+/// A struct that holds a pool of up to N TCP buffers, which are carved at runtime out of a user-provided slab
+/// (i.e. one placed in PSRAM), with the sizes requested by `SlabTcp::new`.
+///
+/// Buffers are allocated first-fit, so the slab might get fragmented when sockets of different sizes
+/// come and go; in that case, allocating a socket fails (or waits) even if the slab has enough free space in total.
+pub struct TcpSlabBuffers<'s, const N: usize> {
+    slab: Slab<'s, N>,
+}
+
+impl<'s, const N: usize> TcpSlabBuffers<'s, N> {
+    /// Create a new `TcpSlabBuffers` instance, carving the buffers out of the provided slab
+    pub fn new(slab: &'s mut [u8]) -> Self {
+        Self {
+            slab: Slab::new(slab),
+        }
+    }
+}
+
+/// The pool the sockets of a `GenericTcp` allocate their buffers from:
+/// either a `TcpBuffers` (for `Tcp`) or a `TcpSlabBuffers` (for `SlabTcp`)
+///
+/// This trait is sealed, i.e. it cannot be implemented outside of this crate.
+pub trait TcpBufferPool: sealed::TcpBufferPool {}
+
+impl<T> TcpBufferPool for T where T: sealed::TcpBufferPool {}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> sealed::TcpBufferPool
+    for TcpBuffers<N, TX_SZ, RX_SZ>
+{
+    type Pool = Pool<([u8; TX_SZ], [u8; RX_SZ]), N>;
+
+    fn pool(&self) -> &Self::Pool {
+        &self.pool
+    }
+}
+
+impl<'s, const N: usize> sealed::TcpBufferPool for TcpSlabBuffers<'s, N> {
+    type Pool = Slab<'s, N>;
+
+    fn pool(&self) -> &Self::Pool {
+        &self.slab
+    }
+}
+
+mod sealed {
+    use crate::BufferPool;
+
+    pub trait TcpBufferPool {
+        /// The pool of the TX and RX buffers
+        type Pool: BufferPool;
+
+        fn pool(&self) -> &Self::Pool;
+    }
+}
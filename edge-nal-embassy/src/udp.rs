@@ -1,26 +1,50 @@
+use core::cell::{Cell, RefCell};
 use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use core::ptr::NonNull;
 
-use edge_nal::{MulticastV4, MulticastV6, Readable, UdpBind, UdpReceive, UdpSend, UdpSplit};
+use edge_nal::{
+    BindToInterface, MulticastV4, MulticastV6, NetError, NetErrorKind, NetErrorStats, Readable,
+    UdpBind, UdpConnectPeer, UdpPeek, UdpReceive, UdpReceiveInfo, UdpReceiveMeta, UdpSend,
+    UdpSplit, UdpSplitOwned, Writable,
+};
 
-use embassy_net::udp::{BindError, PacketMetadata, RecvError, SendError};
+use embassy_net::udp::{BindError, PacketMetadata, RecvError, SendError, UdpMetadata};
 use embassy_net::{MulticastError, Stack};
 
 use embedded_io_async::{ErrorKind, ErrorType};
 
-use crate::{to_net_socket, Pool};
+use crate::{
+    alloc_wait, to_net_socket, track, BufferPool, MetaBuffers, Metadata, Pool, PoolUsage, Slab,
+    SocketBuffers,
+};
 
 /// A struct that implements the `UdpBind` factory trait from `edge-nal`
 /// Capable of managing up to N concurrent connections with TX and RX buffers according to TX_SZ and RX_SZ, and packet metadata according to `M`.
-pub struct Udp<
+pub type Udp<
     'd,
     const N: usize,
     const TX_SZ: usize = 1500,
     const RX_SZ: usize = 1500,
     const M: usize = 2,
-> {
+> = GenericUdp<'d, UdpBuffers<N, TX_SZ, RX_SZ, M>>;
+
+/// A struct that implements the `UdpBind` factory trait from `edge-nal`
+/// Capable of managing up to N concurrent connections with TX and RX buffers carved out of the slab of a `UdpSlabBuffers`,
+/// and packet metadata according to `M`.
+pub type SlabUdp<'d, 's, const N: usize, const M: usize = 2> =
+    GenericUdp<'d, UdpSlabBuffers<'s, N, M>>;
+
+/// The `Udp` and `SlabUdp` factories, which only differ in the pool their sockets allocate their buffers from
+pub struct GenericUdp<'d, B>
+where
+    B: UdpBufferPool,
+{
     stack: Stack<'d>,
-    buffers: &'d UdpBuffers<N, TX_SZ, RX_SZ, M>,
+    buffers: &'d B,
+    tx_size: usize,
+    rx_size: usize,
+    stats: Cell<NetErrorStats>,
+    wait_for_buffers: bool,
 }
 
 impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
@@ -32,133 +56,538 @@ impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
     /// [embassy_net::Stack], while taking into account the sockets used for DHCP, DNS, etc. else
     /// [smoltcp::iface::SocketSet] will panic with `adding a socket to a full SocketSet`.
     pub fn new(stack: Stack<'d>, buffers: &'d UdpBuffers<N, TX_SZ, RX_SZ, M>) -> Self {
-        Self { stack, buffers }
+        Self::wrap(stack, buffers, TX_SZ, RX_SZ)
+    }
+}
+
+impl<'d, 's, const N: usize, const M: usize> SlabUdp<'d, 's, N, M> {
+    /// Create a new `SlabUdp` instance for the provided Embassy networking stack, whose sockets have
+    /// TX and RX buffers of `tx_size` and `rx_size` bytes carved out of the slab of the provided UDP buffers.
+    ///
+    /// Multiple instances with different buffer sizes can share the same `UdpSlabBuffers`.
+    /// The same limit of `N` sockets as with `Udp::new` applies.
+    ///
+    /// # Panics
+    ///
+    /// If the buffers of a single socket do not fit in the slab.
+    pub fn new(
+        stack: Stack<'d>,
+        buffers: &'d UdpSlabBuffers<'s, N, M>,
+        tx_size: usize,
+        rx_size: usize,
+    ) -> Self {
+        assert!(
+            tx_size.saturating_add(rx_size) <= buffers.slab.len(),
+            "The socket buffers do not fit in the slab"
+        );
+
+        Self::wrap(stack, buffers, tx_size, rx_size)
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpBind
-    for Udp<'_, N, TX_SZ, RX_SZ, M>
+impl<'d, B> GenericUdp<'d, B>
+where
+    B: UdpBufferPool,
+{
+    fn wrap(stack: Stack<'d>, buffers: &'d B, tx_size: usize, rx_size: usize) -> Self {
+        Self {
+            stack,
+            buffers,
+            tx_size,
+            rx_size,
+            stats: Cell::new(NetErrorStats::new()),
+            wait_for_buffers: false,
+        }
+    }
+
+    /// Make `bind` wait until a socket of the pool is dropped when the pool is exhausted,
+    /// rather than failing with `UdpError::NoBuffers`
+    pub fn with_wait_for_buffers(self, wait_for_buffers: bool) -> Self {
+        Self {
+            wait_for_buffers,
+            ..self
+        }
+    }
+
+    /// Same as `UdpBind::bind`, except that it fails with `UdpError::NoBuffers` when all sockets
+    /// of the pool are in use, even if `with_wait_for_buffers` is enabled
+    pub async fn try_bind(&self, local: SocketAddr) -> Result<GenericUdpSocket<'_, B>, UdpError> {
+        self.bind_with(local, false).await
+    }
+
+    /// Return the counters of the errors of the bind operations
+    ///
+    /// Errors which occur on already bound sockets are counted by the sockets themselves
+    /// (see `UdpSocket::error_stats`).
+    pub fn error_stats(&self) -> NetErrorStats {
+        self.stats.get()
+    }
+
+    async fn bind_with(
+        &self,
+        local: SocketAddr,
+        wait: bool,
+    ) -> Result<GenericUdpSocket<'_, B>, UdpError> {
+        let pool = self.buffers.pool();
+
+        let socket_buffers = if wait {
+            alloc_wait(pool, self.tx_size, self.rx_size).await
+        } else {
+            track(
+                &self.stats,
+                pool.alloc(self.tx_size, self.rx_size)
+                    .ok_or_else(|| UdpError::NoBuffers(pool.usage())),
+            )?
+        };
+
+        let mut socket = GenericUdpSocket::new(self.stack, self.buffers, socket_buffers);
+
+        track(
+            &self.stats,
+            socket.socket.bind(local).map_err(UdpError::from),
+        )?;
+
+        Ok(socket)
+    }
+}
+
+impl<B> UdpBind for GenericUdp<'_, B>
+where
+    B: UdpBufferPool,
 {
     type Error = UdpError;
 
     type Socket<'a>
-        = UdpSocket<'a, N, TX_SZ, RX_SZ, M>
+        = GenericUdpSocket<'a, B>
     where
         Self: 'a;
 
     async fn bind(&self, local: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
-        let mut socket = UdpSocket::new(self.stack, self.buffers)?;
-
-        socket.socket.bind(local)?;
-
-        Ok(socket)
+        self.bind_with(local, self.wait_for_buffers).await
     }
 }
 
 /// A UDP socket
 /// Implements the `UdpReceive` `UdpSend` and `UdpSplit` traits from `edge-nal`
-pub struct UdpSocket<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
+pub type UdpSocket<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> =
+    GenericUdpSocket<'d, UdpBuffers<N, TX_SZ, RX_SZ, M>>;
+
+/// A UDP socket of a `SlabUdp`
+/// Implements the `UdpReceive` `UdpSend` and `UdpSplit` traits from `edge-nal`
+pub type SlabUdpSocket<'d, 's, const N: usize, const M: usize> =
+    GenericUdpSocket<'d, UdpSlabBuffers<'s, N, M>>;
+
+/// The sockets of `Udp` and `SlabUdp`
+pub struct GenericUdpSocket<'d, B>
+where
+    B: UdpBufferPool,
+{
     stack: embassy_net::Stack<'d>,
     socket: embassy_net::udp::UdpSocket<'d>,
-    stack_buffers: &'d UdpBuffers<N, TX_SZ, RX_SZ, M>,
-    socket_buffers: NonNull<([u8; TX_SZ], [u8; RX_SZ])>,
-    socket_meta_buffers: NonNull<([PacketMetadata; M], [PacketMetadata; M])>,
+    stack_buffers: &'d B,
+    socket_buffers: SocketBuffers,
+    stats: Cell<NetErrorStats>,
+    peer: Cell<Option<SocketAddr>>,
+    groups: heapless::Vec<IpAddr, MAX_MULTICAST_GROUPS>,
 }
 
-impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
-    UdpSocket<'d, N, TX_SZ, RX_SZ, M>
+impl<'d, B> GenericUdpSocket<'d, B>
+where
+    B: UdpBufferPool,
 {
-    fn new(
-        stack: Stack<'d>,
-        stack_buffers: &'d UdpBuffers<N, TX_SZ, RX_SZ, M>,
-    ) -> Result<Self, UdpError> {
-        let mut socket_buffers = stack_buffers.pool.alloc().ok_or(UdpError::NoBuffers)?;
-        let mut socket_meta_buffers = stack_buffers.meta_pool.alloc().unwrap();
+    fn new(stack: Stack<'d>, stack_buffers: &'d B, socket_buffers: SocketBuffers) -> Self {
+        let (tx_meta, rx_meta) = stack_buffers.meta(socket_buffers.index);
+
+        let usage = stack_buffers.pool().usage();
 
-        Ok(Self {
+        trace!(
+            "UDP socket {} opened ({}/{} sockets in use)",
+            socket_buffers.index,
+            usage.used,
+            usage.capacity
+        );
+
+        Self {
             stack,
             socket: unsafe {
                 embassy_net::udp::UdpSocket::new(
                     stack,
-                    &mut socket_meta_buffers.as_mut().1,
-                    &mut socket_buffers.as_mut().1,
-                    &mut socket_meta_buffers.as_mut().0,
-                    &mut socket_buffers.as_mut().0,
+                    &mut *rx_meta.as_ptr(),
+                    &mut *socket_buffers.rx.as_ptr(),
+                    &mut *tx_meta.as_ptr(),
+                    &mut *socket_buffers.tx.as_ptr(),
                 )
             },
             stack_buffers,
             socket_buffers,
-            socket_meta_buffers,
-        })
+            stats: Cell::new(NetErrorStats::new()),
+            peer: Cell::new(None),
+            groups: heapless::Vec::new(),
+        }
+    }
+
+    /// Return the counters of the errors which occurred on this socket, including on its split halves
+    pub fn error_stats(&self) -> NetErrorStats {
+        self.stats.get()
+    }
+
+    /// Reset the error counters of this socket
+    pub fn clear_error_stats(&mut self) {
+        self.stats.set(NetErrorStats::new());
+    }
+
+    /// Provides scoped access to the underlying `embassy-net` UDP socket (which in turn wraps the `smoltcp` one),
+    /// so that socket options not (yet) modeled by `edge-nal` can be tweaked.
+    ///
+    /// The socket is passed to the closure with a lifetime unique to the call, which prevents the closure
+    /// from swapping it with another socket (and thus from mixing up the buffers of this socket's pool).
+    pub fn with_smoltcp_socket<F, R>(&mut self, f: F) -> R
+    where
+        F: for<'s> FnOnce(&mut embassy_net::udp::UdpSocket<'s>) -> R,
+    {
+        f(&mut self.socket)
+    }
+
+    /// Receive the next datagram and process it in place with `f`, which gets the payload
+    /// borrowed from the RX buffer of the socket, as well as the address of the sender.
+    ///
+    /// Unlike `UdpReceive::receive`, this saves the copy of the datagram into a caller-provided buffer,
+    /// and datagrams cannot be truncated. As with `receive`, the datagrams of other peers are dropped
+    /// if the socket is connected.
+    ///
+    /// `f` is called with the networking stack locked, so it should not take long, and it cannot
+    /// use the stack itself (i.e. reply with this or another socket); copy out what is needed for that instead.
+    pub async fn receive_with<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&[u8], SocketAddr) -> R,
+    {
+        let peer = self.peer.get();
+        let mut f = Some(f);
+
+        loop {
+            let result = self
+                .socket
+                .recv_from_with(|data, meta| {
+                    let remote = to_net_socket(meta.endpoint);
+
+                    if peer.map(|peer| peer == remote).unwrap_or(true) {
+                        // `f` is always present, as it is only taken for the datagram which ends the loop
+                        f.take().map(|f| f(data, remote))
+                    } else {
+                        None
+                    }
+                })
+                .await;
+
+            if let Some(result) = result {
+                break result;
+            }
+        }
+    }
+
+    /// Receive the next datagram, dropping the datagrams of other peers if the socket is connected
+    async fn recv_from_peer(&self, buffer: &mut [u8]) -> Result<(usize, UdpMetadata), UdpError> {
+        loop {
+            let result = self.socket.recv_from(buffer).await.map_err(UdpError::from);
+
+            let (len, meta) = track(&self.stats, result)?;
+
+            if self.is_peer(&meta) {
+                break Ok((len, meta));
+            }
+        }
+    }
+
+    /// Peek at the next datagram, dropping the datagrams of other peers if the socket is connected
+    async fn peek_from_peer(&self, buffer: &mut [u8]) -> Result<(usize, UdpMetadata), UdpError> {
+        loop {
+            let result = self.socket.peek_from(buffer).await.map_err(UdpError::from);
+
+            let (len, meta) = track(&self.stats, result)?;
+
+            if self.is_peer(&meta) {
+                break Ok((len, meta));
+            }
+
+            // Receiving into an empty buffer fails with `RecvError::Truncated`, yet dequeues the datagram
+            let _ = self.socket.recv_from(&mut []).await;
+        }
+    }
+
+    /// Join a multicast group, unless this socket has already joined it
+    ///
+    /// The memberships of `embassy-net` are per interface rather than per socket, so they are reference-counted
+    /// across the sockets of the pool, and only the first socket joining a group joins it on the interface.
+    fn join(&mut self, multicast_addr: IpAddr) -> Result<(), UdpError> {
+        if self.groups.contains(&multicast_addr) {
+            return Ok(());
+        }
+
+        if self.groups.is_full() {
+            Err(MulticastError::GroupTableFull)?;
+        }
+
+        let mut memberships = self.stack_buffers.memberships().borrow_mut();
+
+        if let Some((_, count)) = memberships
+            .iter_mut()
+            .find(|(group, _)| *group == multicast_addr)
+        {
+            *count += 1;
+        } else {
+            memberships
+                .push((multicast_addr, 1))
+                .map_err(|_| MulticastError::GroupTableFull)?;
+
+            if let Err(e) = self.stack.join_multicast_group(multicast_addr) {
+                memberships.pop();
+                Err(e)?;
+            }
+        }
+
+        // Cannot fail, as the socket's memberships are not full
+        let _ = self.groups.push(multicast_addr);
+
+        Ok(())
+    }
+
+    /// Leave a multicast group joined by this socket, leaving it on the interface
+    /// once no other socket of the pool is a member
+    fn leave(&mut self, multicast_addr: IpAddr) -> Result<(), UdpError> {
+        let index = self
+            .groups
+            .iter()
+            .position(|group| *group == multicast_addr)
+            .ok_or(MulticastError::Unaddressable)?;
+
+        self.groups.swap_remove(index);
+
+        Self::release(self.stack, self.stack_buffers, multicast_addr)
+    }
+
+    fn release(
+        stack: Stack<'_>,
+        stack_buffers: &B,
+        multicast_addr: IpAddr,
+    ) -> Result<(), UdpError> {
+        let mut memberships = stack_buffers.memberships().borrow_mut();
+
+        let Some(index) = memberships
+            .iter()
+            .position(|(group, _)| *group == multicast_addr)
+        else {
+            return Ok(());
+        };
+
+        memberships[index].1 -= 1;
+
+        if memberships[index].1 == 0 {
+            memberships.swap_remove(index);
+            stack.leave_multicast_group(multicast_addr)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_peer(&self, meta: &UdpMetadata) -> bool {
+        self.peer
+            .get()
+            .map(|peer| peer == to_net_socket(meta.endpoint))
+            .unwrap_or(true)
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Drop
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> Drop for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     fn drop(&mut self) {
+        for group in core::mem::take(&mut self.groups) {
+            let _ = Self::release(self.stack, self.stack_buffers, group);
+        }
+
         unsafe {
             self.socket.close();
-            self.stack_buffers.pool.free(self.socket_buffers);
-            self.stack_buffers.meta_pool.free(self.socket_meta_buffers);
+            self.stack_buffers.pool().free(&self.socket_buffers);
         }
+
+        trace!("UDP socket {} closed", self.socket_buffers.index);
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> ErrorType
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> ErrorType for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     type Error = UdpError;
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpReceive
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> UdpReceive for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
-        let (len, remote_endpoint) = self.socket.recv_from(buffer).await?;
+        let (len, remote_endpoint) = self.recv_from_peer(buffer).await?;
 
         Ok((len, to_net_socket(remote_endpoint.endpoint)))
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpSend
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> UdpReceiveInfo for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        let (len, remote_endpoint) = self.recv_from_peer(buffer).await?;
+
+        // An `embassy-net` stack has a single interface, which has no index,
+        // and it drops the truncated datagrams with an error
+        let meta = UdpReceiveMeta {
+            local: remote_endpoint.local_address.map(Into::into),
+            interface: None,
+            truncated: false,
+        };
+
+        Ok((len, to_net_socket(remote_endpoint.endpoint), meta))
+    }
+}
+
+impl<B> UdpPeek for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let (len, remote_endpoint) = self.peek_from_peer(buffer).await?;
+
+        Ok((len, to_net_socket(remote_endpoint.endpoint)))
+    }
+}
+
+impl<B> UdpSend for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
-        self.socket.send_to(data, remote).await?;
+        let result = self
+            .socket
+            .send_to(data, self.peer.get().unwrap_or(remote))
+            .await
+            .map_err(UdpError::from);
 
-        Ok(())
+        track(&self.stats, result)
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> ErrorType
-    for &UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> ErrorType for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     type Error = UdpError;
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpReceive
-    for &UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> UdpReceive for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
-        let (len, remote_endpoint) = self.socket.recv_from(buffer).await?;
+        let (len, remote_endpoint) = self.recv_from_peer(buffer).await?;
 
         Ok((len, to_net_socket(remote_endpoint.endpoint)))
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpSend
-    for &UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> UdpReceiveInfo for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        let (len, remote_endpoint) = self.recv_from_peer(buffer).await?;
+
+        // An `embassy-net` stack has a single interface, which has no index,
+        // and it drops the truncated datagrams with an error
+        let meta = UdpReceiveMeta {
+            local: remote_endpoint.local_address.map(Into::into),
+            interface: None,
+            truncated: false,
+        };
+
+        Ok((len, to_net_socket(remote_endpoint.endpoint), meta))
+    }
+}
+
+impl<B> UdpPeek for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let (len, remote_endpoint) = self.peek_from_peer(buffer).await?;
+
+        Ok((len, to_net_socket(remote_endpoint.endpoint)))
+    }
+}
+
+impl<B> UdpSend for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
-        self.socket.send_to(data, remote).await?;
+        let result = self
+            .socket
+            .send_to(data, self.peer.get().unwrap_or(remote))
+            .await
+            .map_err(UdpError::from);
+
+        track(&self.stats, result)
+    }
+}
+
+/// `embassy-net` has no connected UDP sockets, so these are emulated: while connected, the socket
+/// sends to its peer and drops the datagrams received from other peers
+impl<B> UdpConnectPeer for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error> {
+        self.peer.set(Some(remote));
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Self::Error> {
+        self.peer.set(None);
 
         Ok(())
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Readable
-    for &UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> BindToInterface for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn bind_to_interface(&mut self, _interface: Option<u32>) -> Result<(), Self::Error> {
+        // An `embassy-net` stack has a single interface, so the socket is always bound to it
+        Ok(())
+    }
+}
+
+/// `smoltcp` never loops back the multicast packets it sends, so only disabling the loopback is supported
+fn set_multicast_loop(stats: &Cell<NetErrorStats>, enabled: bool) -> Result<(), UdpError> {
+    if enabled {
+        track(stats, Err(UdpError::Unsupported))
+    } else {
+        Ok(())
+    }
+}
+
+impl<B> Readable for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn readable(&mut self) -> Result<(), Self::Error> {
         self.socket.wait_recv_ready().await;
@@ -166,8 +595,19 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Rea
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpSplit
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> Writable for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.wait_send_ready().await;
+        Ok(())
+    }
+}
+
+impl<B> UdpSplit for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     type Receive<'a>
         = &'a Self
@@ -184,18 +624,35 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Udp
     }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> MulticastV4
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+/// Splitting a shared reference to the socket into owned halves
+///
+/// The halves are plain shared references, so the socket (and its pool buffers) stays owned by
+/// its original location. Placing the socket in a `static` (e.g. with `StaticCell`) results in
+/// `'static` halves, which can be moved into separate embassy tasks.
+impl<B> UdpSplitOwned for &GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    type Receive = Self;
+    type Send = Self;
+
+    fn into_split(self) -> (Self::Receive, Self::Send) {
+        (self, self)
+    }
+}
+
+impl<B> MulticastV4 for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn join_v4(
         &mut self,
         multicast_addr: Ipv4Addr,
         _interface: Ipv4Addr,
     ) -> Result<(), Self::Error> {
-        self.stack
-            .join_multicast_group(IpAddr::V4(multicast_addr))?;
+        let result = self.join(IpAddr::V4(multicast_addr));
 
-        Ok(())
+        track(&self.stats, result)
     }
 
     async fn leave_v4(
@@ -203,25 +660,39 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Mul
         multicast_addr: Ipv4Addr,
         _interface: Ipv4Addr,
     ) -> Result<(), Self::Error> {
-        self.stack
-            .leave_multicast_group(IpAddr::V4(multicast_addr))?;
+        let result = self.leave(IpAddr::V4(multicast_addr));
+
+        track(&self.stats, result)
+    }
+
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        self.socket.set_hop_limit(Some(ttl));
 
         Ok(())
     }
+
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        set_multicast_loop(&self.stats, enabled)
+    }
+
+    async fn set_multicast_if_v4(&mut self, _interface: Ipv4Addr) -> Result<(), Self::Error> {
+        // An `embassy-net` stack has a single interface
+        Ok(())
+    }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> MulticastV6
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> MulticastV6 for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn join_v6(
         &mut self,
         multicast_addr: Ipv6Addr,
         _interface: u32,
     ) -> Result<(), Self::Error> {
-        self.stack
-            .join_multicast_group(IpAddr::V6(multicast_addr))?;
+        let result = self.join(IpAddr::V6(multicast_addr));
 
-        Ok(())
+        track(&self.stats, result)
     }
 
     async fn leave_v6(
@@ -229,15 +700,30 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Mul
         multicast_addr: Ipv6Addr,
         _interface: u32,
     ) -> Result<(), Self::Error> {
-        self.stack
-            .leave_multicast_group(IpAddr::V6(multicast_addr))?;
+        let result = self.leave(IpAddr::V6(multicast_addr));
+
+        track(&self.stats, result)
+    }
+
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error> {
+        self.socket.set_hop_limit(Some(hops));
 
         Ok(())
     }
+
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        set_multicast_loop(&self.stats, enabled)
+    }
+
+    async fn set_multicast_if_v6(&mut self, _interface: u32) -> Result<(), Self::Error> {
+        // An `embassy-net` stack has a single interface
+        Ok(())
+    }
 }
 
-impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Readable
-    for UdpSocket<'_, N, TX_SZ, RX_SZ, M>
+impl<B> Readable for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
 {
     async fn readable(&mut self) -> Result<(), Self::Error> {
         self.socket.wait_recv_ready().await;
@@ -245,14 +731,27 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Rea
     }
 }
 
+impl<B> Writable for GenericUdpSocket<'_, B>
+where
+    B: UdpBufferPool,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.wait_send_ready().await;
+        Ok(())
+    }
+}
+
 /// A shared error type that is used by the UDP factory trait implementation as well as the UDP socket
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum UdpError {
     Recv(RecvError),
     Send(SendError),
     Bind(BindError),
     Multicast(MulticastError),
-    NoBuffers,
+    /// All sockets of the pool are in use
+    NoBuffers(PoolUsage),
+    Unsupported,
 }
 
 impl From<RecvError> for UdpError {
@@ -279,29 +778,46 @@ impl From<MulticastError> for UdpError {
     }
 }
 
-// TODO
 impl embedded_io_async::Error for UdpError {
     fn kind(&self) -> ErrorKind {
+        self.net_kind().io_kind()
+    }
+}
+
+impl NetError for UdpError {
+    fn net_kind(&self) -> NetErrorKind {
         match self {
-            UdpError::Recv(_) => ErrorKind::Other,
-            UdpError::Send(_) => ErrorKind::Other,
-            UdpError::Bind(_) => ErrorKind::Other,
-            UdpError::Multicast(_) => ErrorKind::Other,
-            UdpError::NoBuffers => ErrorKind::OutOfMemory,
+            UdpError::Recv(RecvError::Truncated) => NetErrorKind::Truncated,
+            UdpError::Send(SendError::NoRoute) => NetErrorKind::NoRoute,
+            UdpError::Send(SendError::SocketNotBound) => NetErrorKind::NotConnected,
+            // `SendError::PacketTooLarge`, which only newer `embassy-net` versions report
+            #[allow(unreachable_patterns)]
+            UdpError::Send(_) => NetErrorKind::PacketTooLarge,
+            UdpError::Bind(BindError::InvalidState) => NetErrorKind::InvalidState,
+            UdpError::Bind(BindError::NoRoute) => NetErrorKind::NoRoute,
+            UdpError::Multicast(MulticastError::GroupTableFull) => NetErrorKind::GroupTableFull,
+            UdpError::Multicast(MulticastError::Unaddressable) => NetErrorKind::AddrNotAvailable,
+            UdpError::NoBuffers(_) => NetErrorKind::NoBuffers,
+            UdpError::Unsupported => NetErrorKind::Unsupported,
         }
     }
 }
 
-/// A struct that holds a pool of UDP buffers
+/// The maximum number of multicast groups the sockets of a `UdpBuffers` (or `UdpSlabBuffers`) pool can join
+///
+/// Matches the default size of the multicast group table of `smoltcp`.
+pub const MAX_MULTICAST_GROUPS: usize = 4;
+
+impl Metadata for PacketMetadata {
+    const EMPTY: Self = PacketMetadata::EMPTY;
+}
+
+/// A struct that holds a pool of N UDP buffers, each with TX and RX buffers of TX_SZ and RX_SZ bytes,
+/// and TX and RX packet metadata buffers for M packets
 pub struct UdpBuffers<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
     pool: Pool<([u8; TX_SZ], [u8; RX_SZ]), N>,
-    meta_pool: Pool<
-        (
-            [embassy_net::udp::PacketMetadata; M],
-            [embassy_net::udp::PacketMetadata; M],
-        ),
-        N,
-    >,
+    meta: MetaBuffers<PacketMetadata, N, M>,
+    memberships: RefCell<heapless::Vec<(IpAddr, usize), MAX_MULTICAST_GROUPS>>,
 }
 
 impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Default
@@ -319,7 +835,98 @@ impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize>
     pub const fn new() -> Self {
         Self {
             pool: Pool::new(),
-            meta_pool: Pool::new(),
+            meta: MetaBuffers::new(),
+            memberships: RefCell::new(heapless::Vec::new()),
+        }
+    }
+}
+
+impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> sealed::UdpBufferPool
+    for UdpBuffers<N, TX_SZ, RX_SZ, M>
+{
+    type Pool = Pool<([u8; TX_SZ], [u8; RX_SZ]), N>;
+
+    fn pool(&self) -> &Self::Pool {
+        &self.pool
+    }
+
+    fn meta(&self, index: usize) -> (NonNull<[PacketMetadata]>, NonNull<[PacketMetadata]>) {
+        self.meta.get(index)
+    }
+
+    fn memberships(&self) -> &RefCell<heapless::Vec<(IpAddr, usize), MAX_MULTICAST_GROUPS>> {
+        &self.memberships
+    }
+}
+
+/// A struct that holds a pool of up to N UDP buffers, whose TX and RX buffers are carved at runtime
+/// out of a user-provided slab (i.e. one placed in PSRAM), with the sizes requested by `SlabUdp::new`.
+/// The TX and RX packet metadata buffers for M packets are held by the struct itself.
+///
+/// Buffers are allocated first-fit, so the slab might get fragmented when sockets of different sizes
+/// come and go; in that case, allocating a socket fails (or waits) even if the slab has enough free space in total.
+pub struct UdpSlabBuffers<'s, const N: usize, const M: usize = 2> {
+    slab: Slab<'s, N>,
+    meta: MetaBuffers<PacketMetadata, N, M>,
+    memberships: RefCell<heapless::Vec<(IpAddr, usize), MAX_MULTICAST_GROUPS>>,
+}
+
+impl<'s, const N: usize, const M: usize> UdpSlabBuffers<'s, N, M> {
+    /// Create a new `UdpSlabBuffers` instance, carving the TX and RX buffers out of the provided slab
+    pub fn new(slab: &'s mut [u8]) -> Self {
+        Self {
+            slab: Slab::new(slab),
+            meta: MetaBuffers::new(),
+            memberships: RefCell::new(heapless::Vec::new()),
         }
     }
 }
+
+impl<'s, const N: usize, const M: usize> sealed::UdpBufferPool for UdpSlabBuffers<'s, N, M> {
+    type Pool = Slab<'s, N>;
+
+    fn pool(&self) -> &Self::Pool {
+        &self.slab
+    }
+
+    fn meta(&self, index: usize) -> (NonNull<[PacketMetadata]>, NonNull<[PacketMetadata]>) {
+        self.meta.get(index)
+    }
+
+    fn memberships(&self) -> &RefCell<heapless::Vec<(IpAddr, usize), MAX_MULTICAST_GROUPS>> {
+        &self.memberships
+    }
+}
+
+/// The pool the sockets of a `GenericUdp` allocate their buffers from:
+/// either a `UdpBuffers` (for `Udp`) or a `UdpSlabBuffers` (for `SlabUdp`)
+///
+/// This trait is sealed, i.e. it cannot be implemented outside of this crate.
+pub trait UdpBufferPool: sealed::UdpBufferPool {}
+
+impl<T> UdpBufferPool for T where T: sealed::UdpBufferPool {}
+
+mod sealed {
+    use core::cell::RefCell;
+    use core::net::IpAddr;
+    use core::ptr::NonNull;
+
+    use embassy_net::udp::PacketMetadata;
+
+    use crate::BufferPool;
+
+    use super::MAX_MULTICAST_GROUPS;
+
+    pub trait UdpBufferPool {
+        /// The pool of the TX and RX buffers
+        type Pool: BufferPool;
+
+        fn pool(&self) -> &Self::Pool;
+
+        /// The TX and RX packet metadata buffers of the socket whose TX and RX buffers have the provided index
+        fn meta(&self, index: usize) -> (NonNull<[PacketMetadata]>, NonNull<[PacketMetadata]>);
+
+        /// The multicast groups joined by the sockets, with the number of sockets which joined each of them
+        fn memberships(&self) -> &RefCell<heapless::Vec<(IpAddr, usize), MAX_MULTICAST_GROUPS>>;
+    }
+}
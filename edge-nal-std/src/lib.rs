@@ -4,9 +4,11 @@
 use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use core::ops::Deref;
 use core::pin::pin;
+use core::time::Duration;
 
-use std::io;
+use std::io::{self, IoSlice};
 use std::net::{self, Shutdown, TcpStream, ToSocketAddrs, UdpSocket as StdUdpSocket};
+use std::sync::Arc;
 
 #[cfg(not(feature = "async-io-mini"))]
 use async_io::Async;
@@ -18,19 +20,38 @@ use futures_lite::io::{AsyncReadExt, AsyncWriteExt};
 use embedded_io_async::{ErrorType, Read, Write};
 
 use edge_nal::{
-    AddrType, Dns, MulticastV4, MulticastV6, Readable, TcpAccept, TcpBind, TcpConnect, TcpShutdown,
-    TcpSplit, UdpBind, UdpConnect, UdpReceive, UdpSend, UdpSplit,
+    dns_query, AddrType, BindOptions, BindToInterface, Dns, DnsQuery, DnsQueryError, DnsRecords,
+    IpOptions, MulticastSourceV4, MulticastSourceV6, MulticastV4, MulticastV6, Readable,
+    RecordType, SocketStats, Stats, TcpAccept, TcpBind, TcpConnect, TcpOptions, TcpShutdown,
+    TcpSplit, UdpBind, UdpConnect, UdpConnectPeer, UdpPeek, UdpReceive, UdpReceiveInfo,
+    UdpReceiveMeta, UdpSend, UdpSplit, UdpSplitOwned, VectoredCursor, Writable, WriteVectored,
+    DNS_MAX_MESSAGE_SIZE, DNS_PORT,
 };
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use raw::*;
 
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "dragonfly"))]
+pub use bpf::*;
+
+#[cfg(all(feature = "interfaces", unix, not(target_os = "espidf")))]
+pub use interfaces::*;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
 #[derive(Default, Clone)]
-pub struct Stack(());
+pub struct Stack(Option<Arc<DnsConfig>>);
 
 impl Stack {
     pub const fn new() -> Self {
-        Self(())
+        Self(None)
+    }
+
+    /// Create a stack which resolves host names as per the provided configuration,
+    /// rather than only with the resolver of the operating system
+    pub fn with_dns_config(config: DnsConfig) -> Self {
+        Self(Some(Arc::new(config)))
     }
 }
 
@@ -62,6 +83,16 @@ impl TcpBind for Stack {
 
         Ok(acceptor)
     }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Accept<'_>, Self::Error> {
+        let listener = options::tcp_listener(local, options)?;
+
+        Ok(TcpAcceptor(Async::new(listener)?))
+    }
 }
 
 pub struct TcpAcceptor(Async<net::TcpListener>);
@@ -83,30 +114,32 @@ impl TcpAccept for TcpAcceptor {
 
     #[cfg(target_os = "espidf")]
     async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error> {
-        // ESP IDF (lwIP actually) does not really support `select`-ing on
-        // socket accept: https://groups.google.com/g/osdeve_mirror_tcpip_lwip/c/Vsz7SVa6a2M
-        //
-        // If we do this, `select` would block and not return with our accepting socket `fd`
-        // marked as ready even if our accepting socket has a pending connection.
+        #[cfg(not(feature = "async-io-mini"))]
+        use async_io::Timer;
+        #[cfg(feature = "async-io-mini")]
+        use async_io_mini::Timer;
+
+        // Older lwIP versions did not mark the listening socket as readable when a connection is pending:
+        // https://groups.google.com/g/osdeve_mirror_tcpip_lwip/c/Vsz7SVa6a2M
         //
-        // (Note also that since the time when the above link was posted on the internet,
-        // the lwIP `accept` API has improved a bit in that it would now return `EWOULDBLOCK`
-        // instead of blocking indefinitely
-        // - and we take advantage of that in the "async" implementation below.)
+        // Hence, rather than going through `Async::accept` (which only retries once `select` reports
+        // the socket as readable), `accept` is called directly - lwIP returns `EWOULDBLOCK` for a non-blocking
+        // socket without pending connections - and then retried as soon as the socket becomes readable.
         //
-        // The workaround below is not ideal in that
-        // it uses a timer to poll the socket, but it avoids spinning a hidden,
-        // separate thread just to accept connections - which would be the alternative.
+        // The timer only guards against a `select` which never reports the socket as readable,
+        // and is therefore long enough to not cause idle wakeups worth mentioning.
+        const READABLE_FALLBACK: core::time::Duration = core::time::Duration::from_secs(1);
+
         loop {
             match self.0.as_ref().accept() {
                 Ok((socket, _)) => break Ok((socket.peer_addr()?, TcpSocket(Async::new(socket)?))),
                 Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
-                    #[cfg(not(feature = "async-io-mini"))]
-                    use async_io::Timer;
-                    #[cfg(feature = "async-io-mini")]
-                    use async_io_mini::Timer;
+                    let timeout = async {
+                        Timer::after(READABLE_FALLBACK).await;
+                        Ok(())
+                    };
 
-                    Timer::after(core::time::Duration::from_millis(20)).await;
+                    futures_lite::future::or(self.0.readable(), timeout).await?;
                 }
                 Err(err) => break Err(err),
             }
@@ -154,12 +187,48 @@ impl Write for TcpSocket {
     }
 }
 
+impl WriteVectored for TcpSocket {
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        let mut cursor = VectoredCursor::new(bufs);
+
+        while !cursor.is_empty() {
+            let len = self.0.write_vectored(&io_slices(&cursor)).await?;
+
+            advance(&mut cursor, len)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Readable for TcpSocket {
     async fn readable(&mut self) -> Result<(), Self::Error> {
         self.0.readable().await
     }
 }
 
+impl Writable for TcpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0.writable().await
+    }
+}
+
+impl Stats for TcpSocket {
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        let socket = self.0.as_ref();
+
+        let mut stats = SocketStats::new();
+
+        stats.local = Some(socket.local_addr()?);
+        stats.remote = socket.peer_addr().ok();
+        stats.send_queue = options::send_queue(socket);
+
+        options::tcp_stats(socket, &mut stats)?;
+
+        Ok(stats)
+    }
+}
+
 impl ErrorType for &TcpSocket {
     type Error = io::Error;
 }
@@ -180,12 +249,32 @@ impl Write for &TcpSocket {
     }
 }
 
+impl WriteVectored for &TcpSocket {
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        let mut cursor = VectoredCursor::new(bufs);
+
+        while !cursor.is_empty() {
+            let len = (&self.0).write_vectored(&io_slices(&cursor)).await?;
+
+            advance(&mut cursor, len)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Readable for &TcpSocket {
     async fn readable(&mut self) -> Result<(), Self::Error> {
         self.0.readable().await
     }
 }
 
+impl Writable for &TcpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0.writable().await
+    }
+}
+
 impl TcpSplit for TcpSocket {
     type Read<'a>
         = &'a TcpSocket
@@ -216,9 +305,98 @@ impl TcpShutdown for TcpSocket {
     }
 
     async fn abort(&mut self) -> Result<(), Self::Error> {
-        // No-op, STD will abort the socket on drop anyway
+        // A zero linger time makes STD reset the connection once the socket is dropped,
+        // rather than closing it gracefully in the background
+        options::set_linger(self.0.as_ref(), Some(Duration::ZERO))
+    }
 
-        Ok(())
+    async fn close_with_timeout(&mut self, timeout_ms: u32) -> Result<bool, Self::Error> {
+        #[cfg(not(feature = "async-io-mini"))]
+        use async_io::Timer;
+        #[cfg(feature = "async-io-mini")]
+        use async_io_mini::Timer;
+
+        // `close(Close::Both)` does not wait for the FIN packet of the peer with STD sockets,
+        // so send ours and then drain the socket until the peer closes its half as well
+        let close = async {
+            self.0.as_ref().shutdown(Shutdown::Write)?;
+
+            let mut buf = [0; 64];
+            while self.0.read(&mut buf).await? > 0 {}
+
+            Ok::<_, io::Error>(true)
+        };
+
+        let timeout = async {
+            Timer::after(Duration::from_millis(timeout_ms as _)).await;
+            Ok(false)
+        };
+
+        match futures_lite::future::or(close, timeout).await {
+            Ok(true) => Ok(true),
+            Ok(false) => {
+                self.abort().await?;
+
+                Ok(false)
+            }
+            Err(err) => {
+                let _ = self.abort().await;
+
+                Err(err)
+            }
+        }
+    }
+}
+
+impl TcpOptions for TcpSocket {
+    async fn set_nodelay(&mut self, nodelay: bool) -> Result<(), Self::Error> {
+        self.0.as_ref().set_nodelay(nodelay)
+    }
+
+    async fn set_keepalive(&mut self, idle: Option<Duration>) -> Result<(), Self::Error> {
+        options::set_keepalive(self.0.as_ref(), idle)
+    }
+
+    async fn set_keepalive_probes(
+        &mut self,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> Result<(), Self::Error> {
+        options::set_keepalive_probes(self.0.as_ref(), interval, count)
+    }
+
+    async fn set_user_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        options::set_user_timeout(self.0.as_ref(), timeout)
+    }
+
+    async fn set_linger(&mut self, linger: Option<Duration>) -> Result<(), Self::Error> {
+        options::set_linger(self.0.as_ref(), linger)
+    }
+}
+
+impl BindToInterface for TcpSocket {
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error> {
+        let socket = self.0.as_ref();
+
+        options::bind_to_interface(socket, socket.local_addr()?.is_ipv6(), interface)
+    }
+}
+
+impl IpOptions for TcpSocket {
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        let socket = self.0.as_ref();
+
+        if socket.local_addr()?.is_ipv6() {
+            options::set_hop_limit_v6(socket, ttl)
+        } else {
+            socket.set_ttl(ttl as _)
+        }
+    }
+
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error> {
+        let socket = self.0.as_ref();
+
+        options::set_tos(socket, socket.local_addr()?.is_ipv6(), tos)
     }
 }
 
@@ -239,7 +417,7 @@ impl UdpConnect for Stack {
 
         socket.as_ref().connect(remote)?;
 
-        Ok(UdpSocket(socket))
+        Ok(UdpSocket::new(socket))
     }
 }
 
@@ -256,15 +434,27 @@ impl UdpBind for Stack {
 
         socket.as_ref().set_broadcast(true)?;
 
-        Ok(UdpSocket(socket))
+        Ok(UdpSocket::new(socket))
+    }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        let socket = Async::new(options::udp_socket(local, options)?)?;
+
+        socket.as_ref().set_broadcast(true)?;
+
+        Ok(UdpSocket::new(socket))
     }
 }
 
-pub struct UdpSocket(Async<StdUdpSocket>);
+pub struct UdpSocket(Async<StdUdpSocket>, pktinfo::Enabled);
 
 impl UdpSocket {
     pub const fn new(socket: Async<StdUdpSocket>) -> Self {
-        Self(socket)
+        Self(socket, pktinfo::Enabled::new())
     }
 
     pub fn release(self) -> Async<StdUdpSocket> {
@@ -375,6 +565,49 @@ impl UdpReceive for &UdpSocket {
     }
 }
 
+impl UdpReceiveInfo for &UdpSocket {
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let socket = self.0.as_ref();
+
+            // Datagrams queued before the first call might still come without the packet info
+            self.1
+                .ensure(socket, || Ok(socket.local_addr()?.is_ipv6()))?;
+
+            let fut = pin!(self.0.read_with(|io| pktinfo::receive(io, buffer, 0)));
+            fut.await
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let fut = pin!(self.receive(buffer));
+            let (len, remote) = fut.await?;
+
+            Ok((len, remote, UdpReceiveMeta::new()))
+        }
+    }
+}
+
+impl UdpPeek for &UdpSocket {
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let fut = pin!(self.0.read_with(|io| {
+            pktinfo::receive(io, buffer, libc::MSG_PEEK | libc::MSG_TRUNC)
+                .map(|(len, remote, _)| (len, remote))
+        }));
+
+        // Elsewhere, the returned length is capped to the buffer's length
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let fut = pin!(self.0.read_with(|io| io.peek_from(buffer)));
+
+        fut.await
+    }
+}
+
 impl UdpSend for &UdpSocket {
     async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
         let is_remote = self.0.as_ref().peer_addr().is_ok();
@@ -425,6 +658,18 @@ impl MulticastV4 for &UdpSocket {
     ) -> Result<(), Self::Error> {
         self.leave_multicast_v4(&multicast_addr, &interface)
     }
+
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        self.0.as_ref().set_multicast_ttl_v4(ttl as _)
+    }
+
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        self.0.as_ref().set_multicast_loop_v4(enabled)
+    }
+
+    async fn set_multicast_if_v4(&mut self, interface: Ipv4Addr) -> Result<(), Self::Error> {
+        options::set_multicast_if_v4(self.0.as_ref(), interface)
+    }
 }
 
 impl MulticastV6 for &UdpSocket {
@@ -447,6 +692,114 @@ impl MulticastV6 for &UdpSocket {
             .as_ref()
             .leave_multicast_v6(&multicast_addr, interface)
     }
+
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error> {
+        options::set_multicast_hops_v6(self.0.as_ref(), hops)
+    }
+
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        self.0.as_ref().set_multicast_loop_v6(enabled)
+    }
+
+    async fn set_multicast_if_v6(&mut self, interface: u32) -> Result<(), Self::Error> {
+        options::set_multicast_if_v6(self.0.as_ref(), interface)
+    }
+}
+
+impl MulticastSourceV4 for &UdpSocket {
+    async fn join_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v4(self.0.as_ref(), multicast_addr, source, interface, true)
+    }
+
+    async fn leave_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v4(self.0.as_ref(), multicast_addr, source, interface, false)
+    }
+}
+
+impl MulticastSourceV6 for &UdpSocket {
+    async fn join_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v6(self.0.as_ref(), multicast_addr, source, interface, true)
+    }
+
+    async fn leave_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v6(self.0.as_ref(), multicast_addr, source, interface, false)
+    }
+}
+
+impl IpOptions for &UdpSocket {
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        let socket = self.0.as_ref();
+
+        if socket.local_addr()?.is_ipv6() {
+            options::set_hop_limit_v6(socket, ttl)
+        } else {
+            socket.set_ttl(ttl as _)
+        }
+    }
+
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error> {
+        let socket = self.0.as_ref();
+
+        options::set_tos(socket, socket.local_addr()?.is_ipv6(), tos)
+    }
+}
+
+impl UdpConnectPeer for &UdpSocket {
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error> {
+        self.0.as_ref().connect(remote)
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Self::Error> {
+        options::disconnect(self.0.as_ref())
+    }
+}
+
+impl Stats for &UdpSocket {
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        (**self).stats()
+    }
+}
+
+impl Stats for UdpSocket {
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        let socket = self.0.as_ref();
+
+        let mut stats = SocketStats::new();
+
+        stats.local = Some(socket.local_addr()?);
+        stats.remote = socket.peer_addr().ok();
+        stats.send_queue = options::send_queue(socket);
+
+        Ok(stats)
+    }
+}
+
+impl BindToInterface for &UdpSocket {
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error> {
+        let socket = self.0.as_ref();
+
+        options::bind_to_interface(socket, socket.local_addr()?.is_ipv6(), interface)
+    }
 }
 
 impl Readable for &UdpSocket {
@@ -455,6 +808,12 @@ impl Readable for &UdpSocket {
     }
 }
 
+impl Writable for &UdpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0.writable().await
+    }
+}
+
 impl ErrorType for UdpSocket {
     type Error = io::Error;
 }
@@ -468,6 +827,27 @@ impl UdpReceive for UdpSocket {
     }
 }
 
+impl UdpReceiveInfo for UdpSocket {
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.receive_info(buffer));
+        fut.await
+    }
+}
+
+impl UdpPeek for UdpSocket {
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.peek(buffer));
+        fut.await
+    }
+}
+
 impl UdpSend for UdpSocket {
     async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
         let mut rself = &*self;
@@ -493,6 +873,18 @@ impl MulticastV4 for UdpSocket {
     ) -> Result<(), Self::Error> {
         self.leave_multicast_v4(&multicast_addr, &interface)
     }
+
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        self.0.as_ref().set_multicast_ttl_v4(ttl as _)
+    }
+
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        self.0.as_ref().set_multicast_loop_v4(enabled)
+    }
+
+    async fn set_multicast_if_v4(&mut self, interface: Ipv4Addr) -> Result<(), Self::Error> {
+        options::set_multicast_if_v4(self.0.as_ref(), interface)
+    }
 }
 
 impl MulticastV6 for UdpSocket {
@@ -515,99 +907,2337 @@ impl MulticastV6 for UdpSocket {
             .as_ref()
             .leave_multicast_v6(&multicast_addr, interface)
     }
-}
 
-impl Readable for UdpSocket {
-    async fn readable(&mut self) -> Result<(), Self::Error> {
-        let mut rself = &*self;
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error> {
+        options::set_multicast_hops_v6(self.0.as_ref(), hops)
+    }
 
-        let fut = pin!(rself.readable());
-        fut.await
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        self.0.as_ref().set_multicast_loop_v6(enabled)
+    }
+
+    async fn set_multicast_if_v6(&mut self, interface: u32) -> Result<(), Self::Error> {
+        options::set_multicast_if_v6(self.0.as_ref(), interface)
     }
 }
 
-impl UdpSplit for UdpSocket {
-    type Receive<'a>
-        = &'a Self
-    where
-        Self: 'a;
+impl MulticastSourceV4 for UdpSocket {
+    async fn join_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v4(self.0.as_ref(), multicast_addr, source, interface, true)
+    }
 
-    type Send<'a>
-        = &'a Self
-    where
-        Self: 'a;
+    async fn leave_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v4(self.0.as_ref(), multicast_addr, source, interface, false)
+    }
+}
 
-    fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
-        let socket = &*self;
+impl MulticastSourceV6 for UdpSocket {
+    async fn join_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v6(self.0.as_ref(), multicast_addr, source, interface, true)
+    }
 
-        (socket, socket)
+    async fn leave_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v6(self.0.as_ref(), multicast_addr, source, interface, false)
     }
 }
 
-impl Dns for Stack {
-    type Error = io::Error;
-
-    async fn get_host_by_name(
-        &self,
-        host: &str,
-        addr_type: AddrType,
-    ) -> Result<IpAddr, Self::Error> {
-        let host = host.to_string();
+impl IpOptions for UdpSocket {
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        let mut rself = &*self;
 
-        dns_lookup_host(&host, addr_type)
+        rself.set_ttl(ttl).await
     }
 
-    async fn get_host_by_address(
-        &self,
-        _addr: IpAddr,
-        _result: &mut [u8],
-    ) -> Result<usize, Self::Error> {
-        Err(io::ErrorKind::Unsupported.into())
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_tos(tos).await
     }
 }
 
-fn dns_lookup_host(host: &str, addr_type: AddrType) -> Result<IpAddr, io::Error> {
-    (host, 0_u16)
-        .to_socket_addrs()?
-        .find(|addr| match addr_type {
-            AddrType::IPv4 => matches!(addr, std::net::SocketAddr::V4(_)),
-            AddrType::IPv6 => matches!(addr, std::net::SocketAddr::V6(_)),
-            AddrType::Either => true,
-        })
-        .map(|addr| match addr {
-            std::net::SocketAddr::V4(v4) => v4.ip().octets().into(),
-            std::net::SocketAddr::V6(v6) => v6.ip().octets().into(),
-        })
-        .ok_or_else(|| io::ErrorKind::AddrNotAvailable.into())
-}
+impl UdpConnectPeer for UdpSocket {
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error> {
+        let mut rself = &*self;
 
-// TODO: Figure out if the RAW socket implementation can be used on any other OS.
-// It seems, that would be difficult on Darwin; wondering about the other BSDs though?
-#[cfg(any(target_os = "linux", target_os = "android"))]
+        let fut = pin!(rself.connect(remote));
+        fut.await
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.disconnect());
+        fut.await
+    }
+}
+
+impl BindToInterface for UdpSocket {
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.bind_to_interface(interface).await
+    }
+}
+
+impl Readable for UdpSocket {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.readable());
+        fut.await
+    }
+}
+
+impl Writable for UdpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.writable());
+        fut.await
+    }
+}
+
+impl UdpSplit for UdpSocket {
+    type Receive<'a>
+        = &'a Self
+    where
+        Self: 'a;
+
+    type Send<'a>
+        = &'a Self
+    where
+        Self: 'a;
+
+    fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
+        let socket = &*self;
+
+        (socket, socket)
+    }
+}
+
+impl UdpSplitOwned for UdpSocket {
+    type Receive = SharedUdpSocket;
+    type Send = SharedUdpSocket;
+
+    fn into_split(self) -> (Self::Receive, Self::Send) {
+        let socket = SharedUdpSocket(Arc::new(self));
+
+        (socket.clone(), socket)
+    }
+}
+
+/// An owned, reference-counted half of a `UdpSocket`, as returned by `UdpSplitOwned::into_split`
+#[derive(Clone)]
+pub struct SharedUdpSocket(Arc<UdpSocket>);
+
+impl Deref for SharedUdpSocket {
+    type Target = UdpSocket;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ErrorType for SharedUdpSocket {
+    type Error = io::Error;
+}
+
+impl UdpReceive for SharedUdpSocket {
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let mut socket = &*self.0;
+
+        let fut = pin!(socket.receive(buffer));
+        fut.await
+    }
+}
+
+impl UdpReceiveInfo for SharedUdpSocket {
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        let mut socket = &*self.0;
+
+        let fut = pin!(socket.receive_info(buffer));
+        fut.await
+    }
+}
+
+impl UdpPeek for SharedUdpSocket {
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let mut socket = &*self.0;
+
+        let fut = pin!(socket.peek(buffer));
+        fut.await
+    }
+}
+
+impl UdpSend for SharedUdpSocket {
+    async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+        let mut socket = &*self.0;
+
+        let fut = pin!(socket.send(remote, data));
+        fut.await
+    }
+}
+
+impl Readable for SharedUdpSocket {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        let mut socket = &*self.0;
+
+        socket.readable().await
+    }
+}
+
+impl Writable for SharedUdpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        let mut socket = &*self.0;
+
+        socket.writable().await
+    }
+}
+
+impl UdpConnectPeer for SharedUdpSocket {
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error> {
+        let mut socket = &*self.0;
+
+        socket.connect(remote).await
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Self::Error> {
+        let mut socket = &*self.0;
+
+        socket.disconnect().await
+    }
+}
+
+impl Stats for SharedUdpSocket {
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        let socket = &*self.0;
+
+        socket.stats()
+    }
+}
+
+/// The configuration of the resolver behind the `Dns` and `DnsQuery` implementations of `Stack`
+///
+/// Without any name servers, host names are resolved with the resolver of the operating system
+/// (which blocks the executor while waiting for the answer), and `DnsQuery` sends its queries
+/// to the first name server listed in `/etc/resolv.conf`.
+#[derive(Clone, Debug, Default)]
+pub struct DnsConfig {
+    nameservers: Vec<SocketAddr>,
+    hosts: Vec<(String, IpAddr)>,
+}
+
+impl DnsConfig {
+    /// Create a new configuration with no name servers and no static host entries
+    pub const fn new() -> Self {
+        Self {
+            nameservers: Vec::new(),
+            hosts: Vec::new(),
+        }
+    }
+
+    /// Add a name server to send the queries to, asynchronously.
+    ///
+    /// The name servers are tried in the order they were added, moving on to the next one
+    /// when a name server does not respond.
+    pub fn with_nameserver(mut self, server: SocketAddr) -> Self {
+        self.nameservers.push(server);
+        self
+    }
+
+    /// Add a static host entry, which takes precedence over the name servers and the resolver of the OS
+    pub fn with_host(mut self, name: &str, addr: IpAddr) -> Self {
+        self.hosts.push((normalize_host(name).to_string(), addr));
+        self
+    }
+
+    /// Add the static host entries of a file in the `/etc/hosts` format,
+    /// i.e. lines of an address followed by one or more host names, with `#` starting a comment
+    pub fn with_hosts(mut self, hosts: &str) -> Self {
+        for line in hosts.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+
+            let Some(addr) = fields.next().and_then(|addr| addr.parse::<IpAddr>().ok()) else {
+                continue;
+            };
+
+            for name in fields {
+                self = self.with_host(name, addr);
+            }
+        }
+
+        self
+    }
+
+    /// The name servers configured with `with_nameserver`
+    pub fn nameservers(&self) -> &[SocketAddr] {
+        &self.nameservers
+    }
+
+    fn host_addrs<'a>(&'a self, name: &'a str) -> impl Iterator<Item = IpAddr> + 'a {
+        let name = normalize_host(name);
+
+        self.hosts
+            .iter()
+            .filter(move |(host, _)| host.eq_ignore_ascii_case(name))
+            .map(|(_, addr)| *addr)
+    }
+
+    fn host_name(&self, addr: IpAddr) -> Option<&str> {
+        self.hosts
+            .iter()
+            .find(|(_, host_addr)| *host_addr == addr)
+            .map(|(host, _)| host.as_str())
+    }
+}
+
+impl Stack {
+    /// Resolve all addresses of a host, given its host name and the desired address type
+    ///
+    /// The addresses are sorted in the spirit of "Happy Eyeballs" (RFC 8305), i.e. interleaving
+    /// the IPv6 and IPv4 addresses, starting with the address family of the first address returned
+    /// by the resolver, so that connecting to them in order quickly falls back to the other family.
+    pub async fn get_hosts_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<Vec<IpAddr>, io::Error> {
+        let matches = |addr: &IpAddr| match addr_type {
+            AddrType::IPv4 => addr.is_ipv4(),
+            AddrType::IPv6 => addr.is_ipv6(),
+            AddrType::Either => true,
+        };
+
+        if let Ok(addr) = host.parse::<IpAddr>() {
+            return Ok(vec![addr]);
+        }
+
+        let mut addrs = Vec::new();
+
+        if let Some(config) = self.0.as_deref() {
+            addrs.extend(config.host_addrs(host).filter(matches));
+
+            if addrs.is_empty() && !config.nameservers.is_empty() {
+                // Query the IPv6 addresses first, as these are the preferred ones
+                if !matches!(addr_type, AddrType::IPv4) {
+                    Box::pin(self.query_addrs(host, RecordType::AAAA, &mut addrs)).await?;
+                }
+
+                if !matches!(addr_type, AddrType::IPv6) {
+                    Box::pin(self.query_addrs(host, RecordType::A, &mut addrs)).await?;
+                }
+
+                return dns_sort_addrs(addrs);
+            }
+        }
+
+        if addrs.is_empty() {
+            addrs.extend(
+                (host, 0_u16)
+                    .to_socket_addrs()?
+                    .map(|addr| addr.ip())
+                    .filter(matches),
+            );
+        }
+
+        dns_sort_addrs(addrs)
+    }
+
+    async fn query_addrs(
+        &self,
+        host: &str,
+        record_type: RecordType,
+        addrs: &mut Vec<IpAddr>,
+    ) -> Result<(), io::Error> {
+        let mut records = vec![0; DNS_MAX_MESSAGE_SIZE];
+
+        match self.query(host, record_type, &mut records).await {
+            Ok(len) => {
+                addrs.extend(DnsRecords::new(&records[..len]).filter_map(|record| record.addr()));
+
+                Ok(())
+            }
+            // No records of that type
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Resolve the host name of an address
+    ///
+    /// The static host entries of the `DnsConfig` of the stack are consulted first, followed by
+    /// a PTR query to its name servers or - if there are none - by the resolver of the OS (`getnameinfo`).
+    pub async fn get_host_name_by_address(&self, addr: IpAddr) -> Result<String, io::Error> {
+        let config = self.0.as_deref();
+
+        if let Some(host) = config.and_then(|config| config.host_name(addr)) {
+            return Ok(host.to_string());
+        }
+
+        if config.is_some_and(|config| !config.nameservers.is_empty()) {
+            let mut records = vec![0; DNS_MAX_MESSAGE_SIZE];
+
+            let len = self
+                .query(&dns_reverse_name(addr), RecordType::PTR, &mut records)
+                .await?;
+
+            return DnsRecords::new(&records[..len])
+                .find_map(|record| record.name())
+                .map(|name| name.to_string())
+                .ok_or_else(|| io::ErrorKind::NotFound.into());
+        }
+
+        options::host_name(addr)
+    }
+
+    /// The name servers to send the DNS queries to
+    fn nameservers(&self) -> Result<Vec<SocketAddr>, io::Error> {
+        match self.0.as_deref() {
+            Some(config) if !config.nameservers.is_empty() => Ok(config.nameservers.clone()),
+            _ => Ok(vec![dns_server()?]),
+        }
+    }
+}
+
+impl Dns for Stack {
+    type Error = io::Error;
+
+    async fn get_host_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, Self::Error> {
+        let addrs = self.get_hosts_by_name(host, addr_type).await?;
+
+        // Safe to index, as an empty result is reported as an error
+        Ok(addrs[0])
+    }
+
+    async fn get_host_by_address(
+        &self,
+        addr: IpAddr,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let host = self.get_host_name_by_address(addr).await?;
+
+        dns_copy_host(&host, result)
+    }
+}
+
+/// Queries the name servers of the `DnsConfig` of the stack directly or - if there are none - the first
+/// name server configured in `/etc/resolv.conf`, as the STD library does not expose the resolver of the OS
+/// beyond the address lookups.
+impl DnsQuery for Stack {
+    async fn query(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let mut outcome = Err(io::ErrorKind::NotFound.into());
+
+        for server in self.nameservers()? {
+            // Boxed, as the query future carries the whole DNS message exchange
+            outcome = Box::pin(self.query_server(server, name, record_type, result)).await;
+
+            if !matches!(&outcome, Err(err) if err.kind() == io::ErrorKind::TimedOut) {
+                break;
+            }
+        }
+
+        outcome
+    }
+}
+
+impl Stack {
+    async fn query_server(
+        &self,
+        server: SocketAddr,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, io::Error> {
+        #[cfg(not(feature = "async-io-mini"))]
+        use async_io::Timer;
+        #[cfg(feature = "async-io-mini")]
+        use async_io_mini::Timer;
+
+        const TIMEOUT: Duration = Duration::from_secs(2);
+        const RETRIES: usize = 2;
+
+        let local = match server {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        };
+
+        let mut attempt = 0;
+
+        loop {
+            let mut socket = UdpBind::bind(self, local).await?;
+
+            let query = dns_query(
+                &mut socket,
+                server,
+                dns_query_id(),
+                name,
+                record_type,
+                result,
+            );
+            let timeout = async {
+                Timer::after(TIMEOUT).await;
+                Err(DnsQueryError::Timeout)
+            };
+
+            match futures_lite::future::or(Box::pin(query), timeout).await {
+                Err(DnsQueryError::Timeout) if attempt < RETRIES => attempt += 1,
+                other => break other.map_err(dns_query_error),
+            }
+        }
+    }
+}
+
+fn io_slices<'a>(cursor: &VectoredCursor<'a>) -> Vec<IoSlice<'a>> {
+    cursor.slices().map(IoSlice::new).collect()
+}
+
+fn advance(cursor: &mut VectoredCursor<'_>, len: usize) -> Result<(), io::Error> {
+    if len == 0 {
+        Err(io::ErrorKind::WriteZero)?;
+    }
+
+    cursor.advance(len);
+
+    Ok(())
+}
+
+fn dns_server() -> Result<SocketAddr, io::Error> {
+    std::fs::read_to_string("/etc/resolv.conf")?
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        // Strip the scope of link-local IPv6 addresses, if any
+        .filter_map(|addr| addr.trim().split('%').next()?.parse::<IpAddr>().ok())
+        .map(|addr| SocketAddr::new(addr, DNS_PORT))
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No name server configured"))
+}
+
+fn dns_query_id() -> u16 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    // `RandomState` is seeded randomly, which is good enough to make the query IDs unpredictable
+    RandomState::new().build_hasher().finish() as u16
+}
+
+fn dns_query_error(err: DnsQueryError<io::Error>) -> io::Error {
+    let kind = match err {
+        DnsQueryError::Io(err) => return err,
+        DnsQueryError::Timeout => io::ErrorKind::TimedOut,
+        DnsQueryError::InvalidName => io::ErrorKind::InvalidInput,
+        DnsQueryError::InvalidResponse | DnsQueryError::Truncated => io::ErrorKind::InvalidData,
+        DnsQueryError::NotFound => io::ErrorKind::NotFound,
+        DnsQueryError::Server(_) => io::ErrorKind::Other,
+        DnsQueryError::BufferOverflow => io::ErrorKind::OutOfMemory,
+    };
+
+    io::Error::new(kind, err.to_string())
+}
+
+/// Remove the duplicates and interleave the address families, starting with the family of the first address
+fn dns_sort_addrs(addrs: Vec<IpAddr>) -> Result<Vec<IpAddr>, io::Error> {
+    let first = addrs
+        .first()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?
+        .is_ipv6();
+
+    let mut unique = Vec::with_capacity(addrs.len());
+
+    for addr in addrs {
+        if !unique.contains(&addr) {
+            unique.push(addr);
+        }
+    }
+
+    let (preferred, other): (Vec<_>, Vec<_>) =
+        unique.into_iter().partition(|addr| addr.is_ipv6() == first);
+
+    let mut sorted = Vec::with_capacity(preferred.len() + other.len());
+
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (preferred, other) => sorted.extend(preferred.into_iter().chain(other)),
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// The name used for the reverse (PTR) lookup of `addr`
+fn dns_reverse_name(addr: IpAddr) -> String {
+    match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, c, d] = addr.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa")
+        }
+        IpAddr::V6(addr) => {
+            let mut name = String::with_capacity(72);
+
+            for octet in addr.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", octet & 0x0f, octet >> 4));
+            }
+
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
+
+/// Copy a resolved host name into the buffer passed to `Dns::get_host_by_address`
+fn dns_copy_host(host: &str, result: &mut [u8]) -> Result<usize, io::Error> {
+    result
+        .get_mut(..host.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::OutOfMemory, "Host name buffer too small"))?
+        .copy_from_slice(host.as_bytes());
+
+    Ok(host.len())
+}
+
+/// Host names are case-insensitive, and might be fully qualified with a trailing dot
+fn normalize_host(host: &str) -> &str {
+    host.strip_suffix('.').unwrap_or(host)
+}
+
+/// Setters for the socket options which are not exposed by the STD sockets, as well as
+/// the reverse address lookup of the OS resolver, which STD does not expose either
+///
+/// These are available on the Unix-like platforms (minus ESP-IDF, whose `libc` bindings do not carry
+/// all of the necessary constants) and - mostly - on Windows; elsewhere, the setters fail with
+/// `io::ErrorKind::Unsupported`.
+#[cfg(all(unix, not(target_os = "espidf")))]
+mod options {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use core::time::Duration;
+
+    use std::io;
+    use std::net::{TcpListener, UdpSocket};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    use edge_nal::{BindOptions, SocketStats};
+
+    /// The listen backlog used by `std::net::TcpListener::bind`
+    const DEFAULT_BACKLOG: u32 = 128;
+
+    fn setsockopt<T>(
+        socket: &impl AsRawFd,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: T,
+    ) -> io::Result<()> {
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                &value as *const T as *const _,
+                core::mem::size_of::<T>() as _,
+            )
+        };
+
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn secs(duration: Duration) -> libc::c_int {
+        duration.as_secs().min(libc::c_int::MAX as u64) as _
+    }
+
+    pub fn set_keepalive(socket: &impl AsRawFd, idle: Option<Duration>) -> io::Result<()> {
+        setsockopt(
+            socket,
+            libc::SOL_SOCKET,
+            libc::SO_KEEPALIVE,
+            idle.is_some() as libc::c_int,
+        )?;
+
+        if let Some(idle) = idle {
+            // The idle time cannot be less than a second
+            let idle = secs(idle).max(1);
+
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, idle)?;
+
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_KEEPALIVE, idle)?;
+
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "ios"
+            )))]
+            let _ = idle;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    ))]
+    pub fn set_keepalive_probes(
+        socket: &impl AsRawFd,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> io::Result<()> {
+        if let Some(interval) = interval {
+            setsockopt(
+                socket,
+                libc::IPPROTO_TCP,
+                libc::TCP_KEEPINTVL,
+                secs(interval).max(1),
+            )?;
+        }
+
+        if let Some(count) = count {
+            setsockopt(
+                socket,
+                libc::IPPROTO_TCP,
+                libc::TCP_KEEPCNT,
+                count.clamp(1, libc::c_int::MAX as u32) as libc::c_int,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    )))]
+    pub fn set_keepalive_probes(
+        _socket: &impl AsRawFd,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> io::Result<()> {
+        if interval.is_none() && count.is_none() {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    /// Linux and Android only, where zero restores the default behavior
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_user_timeout(socket: &impl AsRawFd, timeout: Option<Duration>) -> io::Result<()> {
+        let millis = timeout
+            .map(|timeout| timeout.as_millis().clamp(1, libc::c_uint::MAX as u128))
+            .unwrap_or(0);
+
+        setsockopt(
+            socket,
+            libc::IPPROTO_TCP,
+            libc::TCP_USER_TIMEOUT,
+            millis as libc::c_uint,
+        )
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn set_user_timeout(_socket: &impl AsRawFd, timeout: Option<Duration>) -> io::Result<()> {
+        if timeout.is_none() {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    pub fn set_linger(socket: &impl AsRawFd, linger: Option<Duration>) -> io::Result<()> {
+        let linger = libc::linger {
+            l_onoff: linger.is_some() as _,
+            l_linger: linger.map(secs).unwrap_or(0) as _,
+        };
+
+        setsockopt(socket, libc::SOL_SOCKET, libc::SO_LINGER, linger)
+    }
+
+    pub fn set_hop_limit_v6(socket: &impl AsRawFd, hop_limit: u8) -> io::Result<()> {
+        setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_UNICAST_HOPS,
+            hop_limit as libc::c_int,
+        )
+    }
+
+    pub fn set_multicast_hops_v6(socket: &impl AsRawFd, hops: u8) -> io::Result<()> {
+        setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MULTICAST_HOPS,
+            hops as libc::c_int,
+        )
+    }
+
+    pub fn set_multicast_if_v4(socket: &impl AsRawFd, interface: Ipv4Addr) -> io::Result<()> {
+        let addr = libc::in_addr {
+            s_addr: u32::from_ne_bytes(interface.octets()),
+        };
+
+        setsockopt(socket, libc::IPPROTO_IP, libc::IP_MULTICAST_IF, addr)
+    }
+
+    pub fn set_multicast_if_v6(socket: &impl AsRawFd, interface: u32) -> io::Result<()> {
+        setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MULTICAST_IF,
+            interface as libc::c_uint,
+        )
+    }
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    ))]
+    pub fn set_source_membership_v4(
+        socket: &impl AsRawFd,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+        join: bool,
+    ) -> io::Result<()> {
+        let in_addr = |addr: Ipv4Addr| libc::in_addr {
+            s_addr: u32::from_ne_bytes(addr.octets()),
+        };
+
+        // The order of the fields differs between Linux and the BSDs
+        let mreq = libc::ip_mreq_source {
+            imr_multiaddr: in_addr(multicast_addr),
+            imr_sourceaddr: in_addr(source),
+            imr_interface: in_addr(interface),
+        };
+
+        let name = if join {
+            libc::IP_ADD_SOURCE_MEMBERSHIP
+        } else {
+            libc::IP_DROP_SOURCE_MEMBERSHIP
+        };
+
+        setsockopt(socket, libc::IPPROTO_IP, name, mreq)
+    }
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    ))]
+    pub fn set_source_membership_v6(
+        socket: &impl AsRawFd,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+        join: bool,
+    ) -> io::Result<()> {
+        let mut req: libc::group_source_req = unsafe { core::mem::zeroed() };
+
+        req.gsr_interface = interface;
+        req.gsr_group = sockaddr(SocketAddr::new(multicast_addr.into(), 0)).0;
+        req.gsr_source = sockaddr(SocketAddr::new(source.into(), 0)).0;
+
+        let name = if join {
+            libc::MCAST_JOIN_SOURCE_GROUP
+        } else {
+            libc::MCAST_LEAVE_SOURCE_GROUP
+        };
+
+        setsockopt(socket, libc::IPPROTO_IPV6, name, req)
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )))]
+    pub fn set_source_membership_v4(
+        _socket: &impl AsRawFd,
+        _multicast_addr: Ipv4Addr,
+        _source: Ipv4Addr,
+        _interface: Ipv4Addr,
+        _join: bool,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )))]
+    pub fn set_source_membership_v6(
+        _socket: &impl AsRawFd,
+        _multicast_addr: Ipv6Addr,
+        _source: Ipv6Addr,
+        _interface: u32,
+        _join: bool,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_to_interface(
+        socket: &impl AsRawFd,
+        _ipv6: bool,
+        interface: Option<u32>,
+    ) -> io::Result<()> {
+        // `SO_BINDTODEVICE` expects the name of the interface; an empty name removes the binding
+        let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+
+        if let Some(interface) = interface {
+            if unsafe { libc::if_indextoname(interface, name.as_mut_ptr()) }.is_null() {
+                Err(io::Error::last_os_error())?;
+            }
+        }
+
+        setsockopt(socket, libc::SOL_SOCKET, libc::SO_BINDTODEVICE, name)
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn bind_to_interface(
+        socket: &impl AsRawFd,
+        ipv6: bool,
+        interface: Option<u32>,
+    ) -> io::Result<()> {
+        // An index of 0 removes the binding
+        let interface = interface.unwrap_or(0) as libc::c_uint;
+
+        if ipv6 {
+            setsockopt(socket, libc::IPPROTO_IPV6, libc::IPV6_BOUND_IF, interface)
+        } else {
+            setsockopt(socket, libc::IPPROTO_IP, libc::IP_BOUND_IF, interface)
+        }
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios"
+    )))]
+    pub fn bind_to_interface(
+        _socket: &impl AsRawFd,
+        _ipv6: bool,
+        _interface: Option<u32>,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Fill in the traffic counters and the RTT reported by `TCP_INFO`
+    ///
+    /// Older kernels return a shorter `tcp_info` structure, so the fields
+    /// past the returned length are left unknown.
+    #[cfg(target_os = "linux")]
+    pub fn tcp_stats(socket: &impl AsRawFd, stats: &mut SocketStats) -> io::Result<()> {
+        use core::mem::{offset_of, size_of};
+
+        let mut info: libc::tcp_info = unsafe { core::mem::zeroed() };
+        let mut len = size_of::<libc::tcp_info>() as libc::socklen_t;
+
+        let result = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                &mut info as *mut _ as *mut _,
+                &mut len,
+            )
+        };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let len = len as usize;
+
+        if len >= offset_of!(libc::tcp_info, tcpi_rtt) + size_of::<u32>() {
+            stats.rtt = Some(Duration::from_micros(info.tcpi_rtt as _));
+        }
+
+        if len >= offset_of!(libc::tcp_info, tcpi_bytes_received) + size_of::<u64>() {
+            stats.bytes_received = Some(info.tcpi_bytes_received);
+        }
+
+        // Not `tcpi_bytes_acked`, which also counts the SYN of the connections initiated by the host
+        if len >= offset_of!(libc::tcp_info, tcpi_bytes_retrans) + size_of::<u64>() {
+            stats.bytes_sent = Some(info.tcpi_bytes_sent.saturating_sub(info.tcpi_bytes_retrans));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn tcp_stats(_socket: &impl AsRawFd, _stats: &mut SocketStats) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The number of bytes in the send queue of the socket
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn send_queue(socket: &impl AsRawFd) -> Option<usize> {
+        let mut queued: libc::c_int = 0;
+
+        let result = unsafe { libc::ioctl(socket.as_raw_fd(), libc::TIOCOUTQ, &mut queued) };
+
+        (result != -1).then_some(queued as _)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn send_queue(_socket: &impl AsRawFd) -> Option<usize> {
+        None
+    }
+
+    pub fn tcp_listener(local: SocketAddr, options: &BindOptions) -> io::Result<TcpListener> {
+        let socket = bind(local, libc::SOCK_STREAM, options)?;
+
+        let backlog = options
+            .backlog
+            .unwrap_or(DEFAULT_BACKLOG)
+            .min(libc::c_int::MAX as u32);
+
+        if unsafe { libc::listen(socket.as_raw_fd(), backlog as _) } == -1 {
+            Err(io::Error::last_os_error())?;
+        }
+
+        Ok(socket.into())
+    }
+
+    pub fn udp_socket(local: SocketAddr, options: &BindOptions) -> io::Result<UdpSocket> {
+        bind(local, libc::SOCK_DGRAM, options).map(Into::into)
+    }
+
+    /// Create a socket of the provided type, apply the options to it and bind it to `local`
+    fn bind(local: SocketAddr, ty: libc::c_int, options: &BindOptions) -> io::Result<OwnedFd> {
+        let family = if local.is_ipv4() {
+            libc::AF_INET
+        } else {
+            libc::AF_INET6
+        };
+
+        let fd = unsafe { libc::socket(family, ty, 0) };
+        if fd == -1 {
+            Err(io::Error::last_os_error())?;
+        }
+
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            Err(io::Error::last_os_error())?;
+        }
+
+        if options.reuse_addr {
+            setsockopt(
+                &socket,
+                libc::SOL_SOCKET,
+                libc::SO_REUSEADDR,
+                1 as libc::c_int,
+            )?;
+        }
+
+        if options.reuse_port {
+            setsockopt(
+                &socket,
+                libc::SOL_SOCKET,
+                libc::SO_REUSEPORT,
+                1 as libc::c_int,
+            )?;
+        }
+
+        if let Some(size) = options.recv_buffer_size {
+            setsockopt(
+                &socket,
+                libc::SOL_SOCKET,
+                libc::SO_RCVBUF,
+                size.min(libc::c_int::MAX as usize) as libc::c_int,
+            )?;
+        }
+
+        if let Some(size) = options.send_buffer_size {
+            setsockopt(
+                &socket,
+                libc::SOL_SOCKET,
+                libc::SO_SNDBUF,
+                size.min(libc::c_int::MAX as usize) as libc::c_int,
+            )?;
+        }
+
+        if let (true, Some(v6_only)) = (local.is_ipv6(), options.v6_only) {
+            setsockopt(
+                &socket,
+                libc::IPPROTO_IPV6,
+                libc::IPV6_V6ONLY,
+                v6_only as libc::c_int,
+            )?;
+        }
+
+        let (storage, len) = sockaddr(local);
+
+        let result = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                &storage as *const _ as *const _,
+                len as _,
+            )
+        };
+
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(socket)
+        }
+    }
+
+    fn sockaddr(addr: SocketAddr) -> (libc::sockaddr_storage, usize) {
+        let mut storage: libc::sockaddr_storage = unsafe { core::mem::zeroed() };
+
+        let len = match addr {
+            SocketAddr::V4(addr) => {
+                let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+
+                sin.sin_family = libc::AF_INET as _;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from_ne_bytes(addr.ip().octets());
+
+                #[cfg(any(
+                    target_vendor = "apple",
+                    target_os = "freebsd",
+                    target_os = "dragonfly",
+                    target_os = "netbsd",
+                    target_os = "openbsd"
+                ))]
+                {
+                    sin.sin_len = core::mem::size_of::<libc::sockaddr_in>() as _;
+                }
+
+                core::mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(addr) => {
+                let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+
+                sin6.sin6_family = libc::AF_INET6 as _;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_scope_id = addr.scope_id();
+
+                #[cfg(any(
+                    target_vendor = "apple",
+                    target_os = "freebsd",
+                    target_os = "dragonfly",
+                    target_os = "netbsd",
+                    target_os = "openbsd"
+                ))]
+                {
+                    sin6.sin6_len = core::mem::size_of::<libc::sockaddr_in6>() as _;
+                }
+
+                core::mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+
+        (storage, len)
+    }
+
+    /// Look up the host name of an address with `getnameinfo`, which - unlike `to_socket_addrs` for the
+    /// forward lookups - STD does not wrap. Blocks the current thread for the duration of the lookup
+    pub fn host_name(addr: IpAddr) -> io::Result<String> {
+        /// `NI_MAXHOST`, which is not defined by the `libc` crate for all platforms
+        const MAX_HOST: usize = 1025;
+
+        let (storage, len) = sockaddr(SocketAddr::new(addr, 0));
+
+        let mut host = [0 as libc::c_char; MAX_HOST];
+
+        let result = unsafe {
+            libc::getnameinfo(
+                &storage as *const _ as *const _,
+                len as _,
+                host.as_mut_ptr(),
+                host.len() as _,
+                core::ptr::null_mut(),
+                0,
+                libc::NI_NAMEREQD,
+            )
+        };
+
+        match result {
+            0 => {
+                let host = unsafe { core::ffi::CStr::from_ptr(host.as_ptr()) };
+
+                host.to_str()
+                    .map(str::to_string)
+                    .map_err(|_| io::ErrorKind::InvalidData.into())
+            }
+            libc::EAI_SYSTEM => Err(io::Error::last_os_error()),
+            libc::EAI_NONAME => Err(io::ErrorKind::NotFound.into()),
+            libc::EAI_AGAIN => Err(io::ErrorKind::TimedOut.into()),
+            _ => {
+                let msg = unsafe { core::ffi::CStr::from_ptr(libc::gai_strerror(result)) };
+
+                Err(io::Error::other(msg.to_string_lossy().into_owned()))
+            }
+        }
+    }
+
+    /// Dissolve the association of a connected UDP socket with its peer,
+    /// by connecting it to an address of the `AF_UNSPEC` family
+    ///
+    /// Note that on Linux, this also releases the local port of sockets which were bound to port 0.
+    pub fn disconnect(socket: &impl AsRawFd) -> io::Result<()> {
+        let mut addr: libc::sockaddr = unsafe { core::mem::zeroed() };
+        addr.sa_family = libc::AF_UNSPEC as _;
+
+        let result = unsafe {
+            libc::connect(
+                socket.as_raw_fd(),
+                &addr,
+                core::mem::size_of::<libc::sockaddr>() as _,
+            )
+        };
+
+        if result == -1 {
+            let err = io::Error::last_os_error();
+
+            // The BSDs (including the Apple OSes) do dissolve the association, yet report `EAFNOSUPPORT`
+            if cfg!(not(any(target_os = "linux", target_os = "android")))
+                && err.raw_os_error() == Some(libc::EAFNOSUPPORT)
+            {
+                Ok(())
+            } else {
+                Err(err)
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_tos(socket: &impl AsRawFd, ipv6: bool, tos: u8) -> io::Result<()> {
+        if ipv6 {
+            setsockopt(
+                socket,
+                libc::IPPROTO_IPV6,
+                libc::IPV6_TCLASS,
+                tos as libc::c_int,
+            )
+        } else {
+            setsockopt(socket, libc::IPPROTO_IP, libc::IP_TOS, tos as libc::c_int)
+        }
+    }
+}
+
+/// The Windows flavor of the socket option setters, on top of WinSock
+///
+/// Windows has no notion of the send queue and `TCP_INFO` of the Unix-like platforms, and ignores `IP_TOS`.
+#[cfg(windows)]
+mod options {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use core::time::Duration;
+
+    use std::io;
+    use std::net::{TcpListener, UdpSocket};
+    use std::os::windows::io::{AsRawSocket, FromRawSocket, OwnedSocket};
+
+    use windows_sys::Win32::Networking::WinSock as ws;
+
+    use edge_nal::{BindOptions, SocketStats};
+
+    /// The listen backlog used by `std::net::TcpListener::bind`
+    const DEFAULT_BACKLOG: u32 = 128;
+
+    fn setsockopt<T>(socket: &impl AsRawSocket, level: i32, name: i32, value: T) -> io::Result<()> {
+        let result = unsafe {
+            ws::setsockopt(
+                socket.as_raw_socket() as _,
+                level,
+                name,
+                &value as *const T as *const _,
+                core::mem::size_of::<T>() as _,
+            )
+        };
+
+        if result == ws::SOCKET_ERROR {
+            Err(last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn last_error() -> io::Error {
+        io::Error::from_raw_os_error(unsafe { ws::WSAGetLastError() })
+    }
+
+    fn secs(duration: Duration) -> u32 {
+        duration.as_secs().min(u32::MAX as u64) as _
+    }
+
+    /// STD initializes WinSock only once it creates its first socket; the initialization is reference-counted
+    fn startup() -> io::Result<()> {
+        let mut data: ws::WSADATA = unsafe { core::mem::zeroed() };
+
+        let result = unsafe { ws::WSAStartup(0x202, &mut data) };
+        if result != 0 {
+            Err(io::Error::from_raw_os_error(result))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn sockaddr(addr: SocketAddr) -> (ws::SOCKADDR_STORAGE, usize) {
+        let mut storage: ws::SOCKADDR_STORAGE = unsafe { core::mem::zeroed() };
+
+        let len = match addr {
+            SocketAddr::V4(addr) => {
+                let sin = unsafe { &mut *(&mut storage as *mut _ as *mut ws::SOCKADDR_IN) };
+
+                sin.sin_family = ws::AF_INET;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.S_un.S_addr = u32::from_ne_bytes(addr.ip().octets());
+
+                core::mem::size_of::<ws::SOCKADDR_IN>()
+            }
+            SocketAddr::V6(addr) => {
+                let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut ws::SOCKADDR_IN6) };
+
+                sin6.sin6_family = ws::AF_INET6;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_addr.u.Byte = addr.ip().octets();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.Anonymous.sin6_scope_id = addr.scope_id();
+
+                core::mem::size_of::<ws::SOCKADDR_IN6>()
+            }
+        };
+
+        (storage, len)
+    }
+
+    pub fn tcp_listener(local: SocketAddr, options: &BindOptions) -> io::Result<TcpListener> {
+        if *options == BindOptions::new() {
+            return TcpListener::bind(local);
+        }
+
+        let socket = bind(local, ws::SOCK_STREAM, options)?;
+
+        let backlog = options
+            .backlog
+            .unwrap_or(DEFAULT_BACKLOG)
+            .min(i32::MAX as u32);
+
+        if unsafe { ws::listen(socket.as_raw_socket() as _, backlog as _) } == ws::SOCKET_ERROR {
+            Err(last_error())?;
+        }
+
+        Ok(socket.into())
+    }
+
+    pub fn udp_socket(local: SocketAddr, options: &BindOptions) -> io::Result<UdpSocket> {
+        if *options == BindOptions::new() {
+            return UdpSocket::bind(local);
+        }
+
+        bind(local, ws::SOCK_DGRAM, options).map(Into::into)
+    }
+
+    /// Note that on Windows, `SO_REUSEADDR` alone provides what the other operating systems split between
+    /// `SO_REUSEADDR` and `SO_REUSEPORT`, so both options map to it
+    fn bind(local: SocketAddr, ty: i32, options: &BindOptions) -> io::Result<OwnedSocket> {
+        startup()?;
+
+        let family = if local.is_ipv4() {
+            ws::AF_INET
+        } else {
+            ws::AF_INET6
+        };
+
+        let socket = unsafe {
+            ws::WSASocketW(
+                family as _,
+                ty,
+                0,
+                core::ptr::null(),
+                0,
+                ws::WSA_FLAG_OVERLAPPED | ws::WSA_FLAG_NO_HANDLE_INHERIT,
+            )
+        };
+
+        if socket == ws::INVALID_SOCKET {
+            Err(last_error())?;
+        }
+
+        let socket = unsafe { OwnedSocket::from_raw_socket(socket as _) };
+
+        if options.reuse_addr || options.reuse_port {
+            setsockopt(&socket, ws::SOL_SOCKET, ws::SO_REUSEADDR, 1_u32)?;
+        }
+
+        if let Some(size) = options.recv_buffer_size {
+            setsockopt(
+                &socket,
+                ws::SOL_SOCKET,
+                ws::SO_RCVBUF,
+                size.min(i32::MAX as usize) as i32,
+            )?;
+        }
+
+        if let Some(size) = options.send_buffer_size {
+            setsockopt(
+                &socket,
+                ws::SOL_SOCKET,
+                ws::SO_SNDBUF,
+                size.min(i32::MAX as usize) as i32,
+            )?;
+        }
+
+        if let (true, Some(v6_only)) = (local.is_ipv6(), options.v6_only) {
+            setsockopt(&socket, ws::IPPROTO_IPV6, ws::IPV6_V6ONLY, v6_only as u32)?;
+        }
+
+        let (storage, len) = sockaddr(local);
+
+        let result = unsafe {
+            ws::bind(
+                socket.as_raw_socket() as _,
+                &storage as *const _ as *const _,
+                len as _,
+            )
+        };
+
+        if result == ws::SOCKET_ERROR {
+            Err(last_error())
+        } else {
+            Ok(socket)
+        }
+    }
+
+    pub fn set_keepalive(socket: &impl AsRawSocket, idle: Option<Duration>) -> io::Result<()> {
+        setsockopt(
+            socket,
+            ws::SOL_SOCKET,
+            ws::SO_KEEPALIVE,
+            idle.is_some() as u32,
+        )?;
+
+        if let Some(idle) = idle {
+            // Available since Windows 10 1709
+            setsockopt(socket, ws::IPPROTO_TCP, ws::TCP_KEEPIDLE, secs(idle).max(1))?;
+        }
+
+        Ok(())
+    }
+
+    /// Available since Windows 10 1709 (the interval) and 1703 (the count)
+    pub fn set_keepalive_probes(
+        socket: &impl AsRawSocket,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> io::Result<()> {
+        if let Some(interval) = interval {
+            setsockopt(
+                socket,
+                ws::IPPROTO_TCP,
+                ws::TCP_KEEPINTVL,
+                secs(interval).max(1),
+            )?;
+        }
+
+        if let Some(count) = count {
+            setsockopt(socket, ws::IPPROTO_TCP, ws::TCP_KEEPCNT, count.max(1))?;
+        }
+
+        Ok(())
+    }
+
+    /// Windows has no `TCP_USER_TIMEOUT`; its `TCP_MAXRT` bounds the retransmissions instead,
+    /// yet cannot be reliably reset to the system default
+    pub fn set_user_timeout<S>(_socket: &S, timeout: Option<Duration>) -> io::Result<()> {
+        if timeout.is_none() {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    pub fn set_linger(socket: &impl AsRawSocket, linger: Option<Duration>) -> io::Result<()> {
+        let linger = ws::LINGER {
+            l_onoff: linger.is_some() as _,
+            l_linger: linger.map(secs).unwrap_or(0).min(u16::MAX as u32) as _,
+        };
+
+        setsockopt(socket, ws::SOL_SOCKET, ws::SO_LINGER, linger)
+    }
+
+    pub fn set_hop_limit_v6(socket: &impl AsRawSocket, hop_limit: u8) -> io::Result<()> {
+        setsockopt(
+            socket,
+            ws::IPPROTO_IPV6,
+            ws::IPV6_UNICAST_HOPS,
+            hop_limit as u32,
+        )
+    }
+
+    pub fn set_multicast_hops_v6(socket: &impl AsRawSocket, hops: u8) -> io::Result<()> {
+        setsockopt(
+            socket,
+            ws::IPPROTO_IPV6,
+            ws::IPV6_MULTICAST_HOPS,
+            hops as u32,
+        )
+    }
+
+    pub fn set_multicast_if_v4(socket: &impl AsRawSocket, interface: Ipv4Addr) -> io::Result<()> {
+        // The address, in network byte order
+        setsockopt(
+            socket,
+            ws::IPPROTO_IP,
+            ws::IP_MULTICAST_IF,
+            u32::from_ne_bytes(interface.octets()),
+        )
+    }
+
+    pub fn set_multicast_if_v6(socket: &impl AsRawSocket, interface: u32) -> io::Result<()> {
+        setsockopt(socket, ws::IPPROTO_IPV6, ws::IPV6_MULTICAST_IF, interface)
+    }
+
+    pub fn set_source_membership_v4(
+        socket: &impl AsRawSocket,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+        join: bool,
+    ) -> io::Result<()> {
+        let in_addr = |addr: Ipv4Addr| ws::IN_ADDR {
+            S_un: ws::IN_ADDR_0 {
+                S_addr: u32::from_ne_bytes(addr.octets()),
+            },
+        };
+
+        let mreq = ws::IP_MREQ_SOURCE {
+            imr_multiaddr: in_addr(multicast_addr),
+            imr_sourceaddr: in_addr(source),
+            imr_interface: in_addr(interface),
+        };
+
+        let name = if join {
+            ws::IP_ADD_SOURCE_MEMBERSHIP
+        } else {
+            ws::IP_DROP_SOURCE_MEMBERSHIP
+        };
+
+        setsockopt(socket, ws::IPPROTO_IP, name, mreq)
+    }
+
+    pub fn set_source_membership_v6(
+        socket: &impl AsRawSocket,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+        join: bool,
+    ) -> io::Result<()> {
+        let req = ws::GROUP_SOURCE_REQ {
+            gsr_interface: interface,
+            gsr_group: sockaddr(SocketAddr::new(multicast_addr.into(), 0)).0,
+            gsr_source: sockaddr(SocketAddr::new(source.into(), 0)).0,
+        };
+
+        let name = if join {
+            ws::MCAST_JOIN_SOURCE_GROUP
+        } else {
+            ws::MCAST_LEAVE_SOURCE_GROUP
+        };
+
+        setsockopt(socket, ws::IPPROTO_IPV6, name as _, req)
+    }
+
+    pub fn bind_to_interface(
+        socket: &impl AsRawSocket,
+        ipv6: bool,
+        interface: Option<u32>,
+    ) -> io::Result<()> {
+        // An index of 0 removes the binding
+        let interface = interface.unwrap_or(0);
+
+        if ipv6 {
+            setsockopt(socket, ws::IPPROTO_IPV6, ws::IPV6_UNICAST_IF, interface)
+        } else {
+            // Unlike its IPv6 counterpart, `IP_UNICAST_IF` expects the index in network byte order
+            setsockopt(socket, ws::IPPROTO_IP, ws::IP_UNICAST_IF, interface.to_be())
+        }
+    }
+
+    pub fn set_tos<S>(_socket: &S, _ipv6: bool, _tos: u8) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Dissolve the association of a connected UDP socket with its peer,
+    /// by connecting it to the all-zeroes address of its family
+    pub fn disconnect(socket: &impl AsRawSocket) -> io::Result<()> {
+        let mut storage: ws::SOCKADDR_STORAGE = unsafe { core::mem::zeroed() };
+        let mut len = core::mem::size_of::<ws::SOCKADDR_STORAGE>() as i32;
+
+        let result = unsafe {
+            ws::getsockname(
+                socket.as_raw_socket() as _,
+                &mut storage as *mut _ as *mut _,
+                &mut len,
+            )
+        };
+
+        if result == ws::SOCKET_ERROR {
+            Err(last_error())?;
+        }
+
+        let family = storage.ss_family;
+
+        let mut storage: ws::SOCKADDR_STORAGE = unsafe { core::mem::zeroed() };
+        storage.ss_family = family;
+
+        let len = if family == ws::AF_INET6 {
+            core::mem::size_of::<ws::SOCKADDR_IN6>()
+        } else {
+            core::mem::size_of::<ws::SOCKADDR_IN>()
+        };
+
+        let result = unsafe {
+            ws::connect(
+                socket.as_raw_socket() as _,
+                &storage as *const _ as *const _,
+                len as _,
+            )
+        };
+
+        if result == ws::SOCKET_ERROR {
+            Err(last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Look up the host name of an address with `getnameinfo`. Blocks the current thread for the duration of the lookup
+    pub fn host_name(addr: IpAddr) -> io::Result<String> {
+        /// `NI_MAXHOST`
+        const MAX_HOST: usize = 1025;
+
+        startup()?;
+
+        let (storage, len) = sockaddr(SocketAddr::new(addr, 0));
+
+        let mut host = [0_u8; MAX_HOST];
+
+        let result = unsafe {
+            ws::getnameinfo(
+                &storage as *const _ as *const _,
+                len as _,
+                host.as_mut_ptr(),
+                host.len() as _,
+                core::ptr::null_mut(),
+                0,
+                ws::NI_NAMEREQD as _,
+            )
+        };
+
+        match result {
+            0 => {
+                let host = core::ffi::CStr::from_bytes_until_nul(&host)
+                    .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+
+                host.to_str()
+                    .map(str::to_string)
+                    .map_err(|_| io::ErrorKind::InvalidData.into())
+            }
+            ws::WSAHOST_NOT_FOUND => Err(io::ErrorKind::NotFound.into()),
+            ws::WSATRY_AGAIN => Err(io::ErrorKind::TimedOut.into()),
+            _ => Err(io::Error::from_raw_os_error(result)),
+        }
+    }
+
+    pub fn tcp_stats<S>(_socket: &S, _stats: &mut SocketStats) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn send_queue<S>(_socket: &S) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(not(any(all(unix, not(target_os = "espidf")), windows)))]
+mod options {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use core::time::Duration;
+
+    use std::io;
+    use std::net::{TcpListener, UdpSocket};
+
+    use edge_nal::{BindOptions, SocketStats};
+
+    /// Only the default options are supported, which leave the socket as created by STD
+    pub fn tcp_listener(local: SocketAddr, options: &BindOptions) -> io::Result<TcpListener> {
+        if *options == BindOptions::new() {
+            TcpListener::bind(local)
+        } else {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    pub fn udp_socket(local: SocketAddr, options: &BindOptions) -> io::Result<UdpSocket> {
+        if *options == BindOptions::new() {
+            UdpSocket::bind(local)
+        } else {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    pub fn set_keepalive<S>(_socket: &S, _idle: Option<Duration>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_keepalive_probes<S>(
+        _socket: &S,
+        _interval: Option<Duration>,
+        _count: Option<u32>,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_user_timeout<S>(_socket: &S, _timeout: Option<Duration>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_linger<S>(_socket: &S, _linger: Option<Duration>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_hop_limit_v6<S>(_socket: &S, _hop_limit: u8) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_multicast_hops_v6<S>(_socket: &S, _hops: u8) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_multicast_if_v4<S>(_socket: &S, _interface: Ipv4Addr) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_multicast_if_v6<S>(_socket: &S, _interface: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_source_membership_v4<S>(
+        _socket: &S,
+        _multicast_addr: Ipv4Addr,
+        _source: Ipv4Addr,
+        _interface: Ipv4Addr,
+        _join: bool,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_source_membership_v6<S>(
+        _socket: &S,
+        _multicast_addr: Ipv6Addr,
+        _source: Ipv6Addr,
+        _interface: u32,
+        _join: bool,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn bind_to_interface<S>(
+        _socket: &S,
+        _ipv6: bool,
+        _interface: Option<u32>,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set_tos<S>(_socket: &S, _ipv6: bool, _tos: u8) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn disconnect<S>(_socket: &S) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn host_name(_addr: IpAddr) -> io::Result<String> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn tcp_stats<S>(_socket: &S, _stats: &mut SocketStats) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn send_queue<S>(_socket: &S) -> Option<usize> {
+        None
+    }
+}
+
+/// Receiving of datagrams along with the `IP_PKTINFO` / `IPV6_PKTINFO` control messages and the truncation flag
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod pktinfo {
+    use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use std::io;
+    use std::os::fd::AsRawFd;
+
+    use edge_nal::UdpReceiveMeta;
+
+    /// Tracks whether the packet info is enabled on a socket, so that it is only enabled once
+    pub struct Enabled(AtomicBool);
+
+    impl Enabled {
+        pub const fn new() -> Self {
+            Self(AtomicBool::new(false))
+        }
+
+        /// Enable the packet info on the socket, unless that was done already
+        pub fn ensure<S, F>(&self, socket: &S, ipv6: F) -> io::Result<()>
+        where
+            S: AsRawFd,
+            F: FnOnce() -> io::Result<bool>,
+        {
+            // Racing callers at worst enable it twice, which is harmless
+            if !self.0.load(Ordering::Relaxed) {
+                enable(socket, ipv6()?)?;
+                self.0.store(true, Ordering::Relaxed);
+            }
+
+            Ok(())
+        }
+    }
+
+    fn setsockopt<S: AsRawFd>(
+        socket: &S,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: libc::c_int,
+    ) -> io::Result<()> {
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                &value as *const _ as *const _,
+                core::mem::size_of::<libc::c_int>() as _,
+            )
+        };
+
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn enable<S: AsRawFd>(socket: &S, ipv6: bool) -> io::Result<()> {
+        if ipv6 {
+            setsockopt(socket, libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO, 1)?;
+        }
+
+        // Also necessary on dual-stack IPv6 sockets, for them to report the IPv4 datagrams
+        setsockopt(socket, libc::IPPROTO_IP, libc::IP_PKTINFO, 1)
+    }
+
+    /// An interface index of 0 means the interface is not known
+    /// (i.e. for datagrams which were queued before the packet info got enabled)
+    fn interface(index: u32) -> Option<u32> {
+        (index != 0).then_some(index)
+    }
+
+    /// Receive a datagram with `recvmsg`, passing it the provided flags
+    ///
+    /// With `MSG_TRUNC` the full length of the datagram is returned, even if it does not fit in the buffer.
+    pub fn receive<S: AsRawFd>(
+        socket: &S,
+        buffer: &mut [u8],
+        flags: libc::c_int,
+    ) -> io::Result<(usize, SocketAddr, UdpReceiveMeta)> {
+        let mut storage: libc::sockaddr_storage = unsafe { core::mem::zeroed() };
+
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut _,
+            iov_len: buffer.len(),
+        };
+
+        // Large enough for one `in_pktinfo` and one `in6_pktinfo` control message, and aligned for `cmsghdr`
+        let mut control = [0_u64; 16];
+
+        let mut msg: libc::msghdr = unsafe { core::mem::zeroed() };
+        msg.msg_name = &mut storage as *mut _ as *mut _;
+        msg.msg_namelen = core::mem::size_of_val(&storage) as _;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut _;
+        msg.msg_controllen = core::mem::size_of_val(&control) as _;
+
+        let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, flags) };
+        if len == -1 {
+            Err(io::Error::last_os_error())?;
+        }
+
+        let mut meta = UdpReceiveMeta::new();
+        meta.truncated = msg.msg_flags & libc::MSG_TRUNC != 0;
+
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+
+        while let Some(hdr) = unsafe { cmsg.as_ref() } {
+            let data = unsafe { libc::CMSG_DATA(hdr) };
+
+            match (hdr.cmsg_level, hdr.cmsg_type) {
+                (libc::IPPROTO_IP, libc::IP_PKTINFO) => {
+                    let info = unsafe { (data as *const libc::in_pktinfo).read_unaligned() };
+
+                    meta.local = Some(Ipv4Addr::from(u32::from_be(info.ipi_addr.s_addr)).into());
+                    meta.interface = interface(info.ipi_ifindex as _);
+                }
+                (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO) => {
+                    let info = unsafe { (data as *const libc::in6_pktinfo).read_unaligned() };
+
+                    // IPv4 datagrams received by dual-stack sockets have an IPv4-mapped local address
+                    meta.local = Some(Ipv6Addr::from(info.ipi6_addr.s6_addr).to_canonical());
+                    meta.interface = interface(info.ipi6_ifindex as _);
+                }
+                _ => (),
+            }
+
+            cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+        }
+
+        let remote = match storage.ss_family as libc::c_int {
+            libc::AF_INET => {
+                let addr = unsafe { &*(&storage as *const _ as *const libc::sockaddr_in) };
+
+                SocketAddrV4::new(
+                    u32::from_be(addr.sin_addr.s_addr).into(),
+                    u16::from_be(addr.sin_port),
+                )
+                .into()
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(&storage as *const _ as *const libc::sockaddr_in6) };
+
+                SocketAddrV6::new(
+                    addr.sin6_addr.s6_addr.into(),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )
+                .into()
+            }
+            _ => Err(io::ErrorKind::InvalidData)?,
+        };
+
+        Ok((len as usize, remote, meta))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod pktinfo {
+    /// The packet info is only received on Linux and Android
+    pub struct Enabled;
+
+    impl Enabled {
+        pub const fn new() -> Self {
+            Self
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
 mod raw {
     use core::ops::Deref;
     use core::pin::pin;
 
     use std::io::{self, ErrorKind};
-    use std::os::fd::{AsFd, AsRawFd};
+    use std::os::fd::{AsFd, AsRawFd};
+
+    #[cfg(not(feature = "async-io-mini"))]
+    use async_io::Async;
+    #[cfg(feature = "async-io-mini")]
+    use async_io_mini::Async;
+
+    use edge_nal::{MacAddr, RawBind, RawReceive, RawSend, RawSplit, Readable, Writable};
+    use embedded_io_async::ErrorType;
+
+    use crate::sys;
+    use crate::syscall_los;
+
+    pub struct Interface(u32, u16);
+
+    impl Interface {
+        /// Create a raw socket factory for the interface with the provided index,
+        /// sending and receiving IPv4 packets
+        pub const fn new(interface: u32) -> Self {
+            Self::new_with_ether_type(interface, sys::ETH_P_IP as _)
+        }
+
+        /// Create a raw socket factory for the interface with the provided index,
+        /// sending and receiving packets of the provided EtherType (e.g. `0x0806` for ARP)
+        pub const fn new_with_ether_type(interface: u32, ether_type: u16) -> Self {
+            Self(interface, ether_type)
+        }
+    }
+
+    impl Default for Interface {
+        fn default() -> Self {
+            Self::new(0)
+        }
+    }
+
+    impl RawBind for Interface {
+        type Error = io::Error;
+
+        type Socket<'a>
+            = RawSocket
+        where
+            Self: 'a;
+
+        async fn bind(&self) -> Result<Self::Socket<'_>, Self::Error> {
+            let socket = syscall_los!(unsafe {
+                sys::socket(sys::PF_PACKET, sys::SOCK_DGRAM, self.1.to_be() as _)
+            })?;
+
+            let sockaddr = sys::sockaddr_ll {
+                sll_family: sys::AF_PACKET as _,
+                sll_protocol: self.1.to_be() as _,
+                sll_ifindex: self.0 as _,
+                sll_hatype: 0,
+                sll_pkttype: 0,
+                sll_halen: 0,
+                sll_addr: Default::default(),
+            };
+
+            syscall_los!(unsafe {
+                sys::bind(
+                    socket,
+                    &sockaddr as *const _ as *const _,
+                    core::mem::size_of::<sys::sockaddr_ll>() as _,
+                )
+            })?;
+
+            // TODO
+            // syscall_los!(unsafe {
+            //     sys::setsockopt(socket, sys::SOL_PACKET, sys::PACKET_AUXDATA, &1_u32 as *const _ as *const _, 4)
+            // })?;
+
+            let socket = {
+                use std::os::fd::FromRawFd;
+
+                unsafe { std::net::UdpSocket::from_raw_fd(socket) }
+            };
+
+            socket.set_broadcast(true)?;
+
+            Ok(RawSocket::new_with_ether_type(
+                Async::new(socket)?,
+                self.0 as _,
+                self.1,
+            ))
+        }
+    }
+
+    pub struct RawSocket(Async<std::net::UdpSocket>, u32, u16);
+
+    impl RawSocket {
+        pub const fn new(socket: Async<std::net::UdpSocket>, interface: u32) -> Self {
+            Self::new_with_ether_type(socket, interface, sys::ETH_P_IP as _)
+        }
+
+        pub const fn new_with_ether_type(
+            socket: Async<std::net::UdpSocket>,
+            interface: u32,
+            ether_type: u16,
+        ) -> Self {
+            Self(socket, interface, ether_type)
+        }
+
+        pub fn release(self) -> (Async<std::net::UdpSocket>, u32, u16) {
+            (self.0, self.1, self.2)
+        }
+    }
+
+    impl Deref for RawSocket {
+        type Target = Async<std::net::UdpSocket>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl ErrorType for &RawSocket {
+        type Error = io::Error;
+    }
+
+    impl RawReceive for &RawSocket {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+            let fut = pin!(self.0.read_with(|io| {
+                let mut storage: sys::sockaddr_storage = unsafe { core::mem::zeroed() };
+                let mut addrlen = core::mem::size_of_val(&storage) as sys::socklen_t;
+
+                let ret = syscall_los!(unsafe {
+                    sys::recvfrom(
+                        io.as_fd().as_raw_fd(),
+                        buffer.as_mut_ptr() as *mut _,
+                        buffer.len(),
+                        0,
+                        &mut storage as *mut _ as *mut _,
+                        &mut addrlen,
+                    )
+                })?;
+
+                let sockaddr = as_sockaddr_ll(&storage, addrlen as usize)?;
+
+                let mut mac = [0; 6];
+                mac.copy_from_slice(&sockaddr.sll_addr[..6]);
+
+                Ok((ret as usize, mac))
+            }));
+
+            fut.await
+        }
+    }
+
+    impl RawSend for &RawSocket {
+        async fn send(&mut self, mac: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+            let mut sockaddr = sys::sockaddr_ll {
+                sll_family: sys::AF_PACKET as _,
+                sll_protocol: self.2.to_be() as _,
+                sll_ifindex: self.1 as _,
+                sll_hatype: 0,
+                sll_pkttype: 0,
+                sll_halen: 0,
+                sll_addr: Default::default(),
+            };
+
+            sockaddr.sll_halen = mac.len() as _;
+            sockaddr.sll_addr[..mac.len()].copy_from_slice(&mac);
+
+            let fut = pin!(self.0.write_with(|io| {
+                let len = core::cmp::min(data.len(), u16::MAX as usize);
+
+                let ret = syscall_los!(unsafe {
+                    sys::sendto(
+                        io.as_fd().as_raw_fd(),
+                        data.as_ptr() as *const _,
+                        len,
+                        sys::MSG_NOSIGNAL,
+                        &sockaddr as *const _ as *const _,
+                        core::mem::size_of::<sys::sockaddr_ll>() as _,
+                    )
+                })?;
+                Ok(ret as usize)
+            }));
+
+            let len = fut.await?;
+
+            assert_eq!(len, data.len());
+
+            Ok(())
+        }
+    }
+
+    impl Readable for &RawSocket {
+        async fn readable(&mut self) -> Result<(), Self::Error> {
+            self.0.readable().await
+        }
+    }
+
+    impl Writable for &RawSocket {
+        async fn writable(&mut self) -> Result<(), Self::Error> {
+            self.0.writable().await
+        }
+    }
+
+    impl ErrorType for RawSocket {
+        type Error = io::Error;
+    }
+
+    impl RawReceive for RawSocket {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+            let mut rself = &*self;
+
+            let fut = pin!(rself.receive(buffer));
+
+            fut.await
+        }
+    }
+
+    impl RawSend for RawSocket {
+        async fn send(&mut self, mac: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+            let mut rself = &*self;
+
+            let fut = pin!(rself.send(mac, data));
+
+            fut.await
+        }
+    }
+
+    impl RawSplit for RawSocket {
+        type Receive<'a>
+            = &'a Self
+        where
+            Self: 'a;
+
+        type Send<'a>
+            = &'a Self
+        where
+            Self: 'a;
+
+        fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
+            let socket = &*self;
+
+            (socket, socket)
+        }
+    }
+
+    impl Readable for RawSocket {
+        async fn readable(&mut self) -> Result<(), Self::Error> {
+            self.0.readable().await
+        }
+    }
+
+    impl Writable for RawSocket {
+        async fn writable(&mut self) -> Result<(), Self::Error> {
+            self.0.writable().await
+        }
+    }
+
+    fn as_sockaddr_ll(
+        storage: &sys::sockaddr_storage,
+        len: usize,
+    ) -> io::Result<&sys::sockaddr_ll> {
+        match storage.ss_family as core::ffi::c_int {
+            sys::AF_PACKET => {
+                assert!(len >= core::mem::size_of::<sys::sockaddr_ll>());
+                Ok(unsafe { (storage as *const _ as *const sys::sockaddr_ll).as_ref() }.unwrap())
+            }
+            _ => Err(io::Error::new(ErrorKind::InvalidInput, "invalid argument")),
+        }
+    }
+}
+
+/// Raw sockets on top of the Berkeley Packet Filter devices of macOS and the BSDs
+///
+/// Unlike the `AF_PACKET` sockets of Linux, BPF devices send and receive complete Ethernet frames,
+/// so the Ethernet header is added on send and stripped on receive, which makes these sockets
+/// interchangeable with the Linux ones.
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "dragonfly"))]
+mod bpf {
+    use core::ops::Deref;
+    use core::pin::pin;
+
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, ErrorKind};
+    use std::os::fd::AsRawFd;
+    use std::sync::Mutex;
 
     #[cfg(not(feature = "async-io-mini"))]
     use async_io::Async;
     #[cfg(feature = "async-io-mini")]
     use async_io_mini::Async;
 
-    use edge_nal::{MacAddr, RawBind, RawReceive, RawSend, RawSplit, Readable};
+    use edge_nal::{MacAddr, RawBind, RawReceive, RawSend, RawSplit, Readable, Writable};
     use embedded_io_async::ErrorType;
 
-    use crate::sys;
-    use crate::syscall_los;
+    const ETHER_TYPE_IPV4: u16 = 0x0800;
+
+    const ETHER_HEADER_LEN: usize = 14;
+
+    /// `_IOW('B', 119, u_int)`, which the `libc` crate only exports for some of the BSDs
+    const BIOCSSEESENT: libc::c_ulong = 0x80044277;
+
+    /// The alignment of the frames in the buffers returned by the BPF devices
+    #[cfg(target_os = "macos")]
+    const BPF_ALIGNMENT: usize = core::mem::size_of::<i32>();
+    #[cfg(not(target_os = "macos"))]
+    const BPF_ALIGNMENT: usize = core::mem::size_of::<libc::c_long>();
+
+    #[repr(C)]
+    struct BpfInsn {
+        code: u16,
+        jt: u8,
+        jf: u8,
+        k: u32,
+    }
 
-    #[derive(Default)]
-    pub struct Interface(u32);
+    #[repr(C)]
+    struct BpfProgram {
+        len: libc::c_uint,
+        insns: *const BpfInsn,
+    }
+
+    pub struct Interface(u32, u16);
 
     impl Interface {
+        /// Create a raw socket factory for the interface with the provided index,
+        /// sending and receiving IPv4 packets
         pub const fn new(interface: u32) -> Self {
-            Self(interface)
+            Self::new_with_ether_type(interface, ETHER_TYPE_IPV4)
+        }
+
+        /// Create a raw socket factory for the interface with the provided index,
+        /// sending and receiving packets of the provided EtherType (e.g. `0x0806` for ARP)
+        ///
+        /// BPF devices are always attached to a single interface, so - unlike on Linux -
+        /// the index cannot be 0.
+        pub const fn new_with_ether_type(interface: u32, ether_type: u16) -> Self {
+            Self(interface, ether_type)
         }
     }
 
@@ -620,66 +3250,93 @@ mod raw {
             Self: 'a;
 
         async fn bind(&self) -> Result<Self::Socket<'_>, Self::Error> {
-            let socket = syscall_los!(unsafe {
-                sys::socket(
-                    sys::PF_PACKET,
-                    sys::SOCK_DGRAM,
-                    (sys::ETH_P_IP as u16).to_be() as _,
-                )
-            })?;
-
-            let sockaddr = sys::sockaddr_ll {
-                sll_family: sys::AF_PACKET as _,
-                sll_protocol: (sys::ETH_P_IP as u16).to_be() as _,
-                sll_ifindex: self.0 as _,
-                sll_hatype: 0,
-                sll_pkttype: 0,
-                sll_halen: 0,
-                sll_addr: Default::default(),
-            };
-
-            syscall_los!(unsafe {
-                sys::bind(
-                    socket,
-                    &sockaddr as *const _ as *const _,
-                    core::mem::size_of::<sys::sockaddr_ll>() as _,
-                )
-            })?;
+            if self.0 == 0 {
+                Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "BPF devices need an interface",
+                ))?;
+            }
 
-            // TODO
-            // syscall_los!(unsafe {
-            //     sys::setsockopt(socket, sys::SOL_PACKET, sys::PACKET_AUXDATA, &1_u32 as *const _ as *const _, 4)
-            // })?;
+            let device = open()?;
 
-            let socket = {
-                use std::os::fd::FromRawFd;
+            let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+            if unsafe { libc::if_indextoname(self.0, name.as_mut_ptr()) }.is_null() {
+                Err(io::Error::last_os_error())?;
+            }
 
-                unsafe { std::net::UdpSocket::from_raw_fd(socket) }
+            // `struct ifreq`, of which `BIOCSETIF` only needs the name
+            let mut ifreq = [0 as libc::c_char; 32];
+            ifreq[..name.len()].copy_from_slice(&name);
+
+            ioctl(&device, libc::BIOCSETIF, &mut ifreq)?;
+
+            // Deliver the frames as they arrive, rather than when the buffer fills up
+            ioctl(&device, libc::BIOCIMMEDIATE, &mut (1 as libc::c_uint))?;
+            // Do not loop back the frames sent through the device
+            ioctl(&device, BIOCSSEESENT, &mut (0 as libc::c_uint))?;
+
+            // Only let through the frames of our EtherType
+            let insns = [
+                // ldh [12]
+                BpfInsn {
+                    code: 0x28,
+                    jt: 0,
+                    jf: 0,
+                    k: 12,
+                },
+                // jeq #ether_type, accept, drop
+                BpfInsn {
+                    code: 0x15,
+                    jt: 0,
+                    jf: 1,
+                    k: self.1 as _,
+                },
+                // accept: ret #-1
+                BpfInsn {
+                    code: 0x06,
+                    jt: 0,
+                    jf: 0,
+                    k: u32::MAX,
+                },
+                // drop: ret #0
+                BpfInsn {
+                    code: 0x06,
+                    jt: 0,
+                    jf: 0,
+                    k: 0,
+                },
+            ];
+
+            let mut program = BpfProgram {
+                len: insns.len() as _,
+                insns: insns.as_ptr(),
             };
 
-            socket.set_broadcast(true)?;
-
-            Ok(RawSocket(Async::new(socket)?, self.0 as _))
-        }
-    }
+            ioctl(&device, libc::BIOCSETF, &mut program)?;
 
-    pub struct RawSocket(Async<std::net::UdpSocket>, u32);
+            // Reads from a BPF device have to use a buffer of exactly the device's buffer length
+            let mut len: libc::c_uint = 0;
+            ioctl(&device, libc::BIOCGBLEN, &mut len)?;
 
-    impl RawSocket {
-        pub const fn new(socket: Async<std::net::UdpSocket>, interface: u32) -> Self {
-            Self(socket, interface)
+            Ok(RawSocket {
+                device: Async::new(device)?,
+                buffer: Mutex::new(Frames::new(len as _)),
+                ether_type: self.1,
+            })
         }
+    }
 
-        pub fn release(self) -> (Async<std::net::UdpSocket>, u32) {
-            (self.0, self.1)
-        }
+    pub struct RawSocket {
+        device: Async<File>,
+        buffer: Mutex<Frames>,
+        ether_type: u16,
     }
 
     impl Deref for RawSocket {
-        type Target = Async<std::net::UdpSocket>;
+        type Target = Async<File>;
 
         fn deref(&self) -> &Self::Target {
-            &self.0
+            &self.device
         }
     }
 
@@ -689,67 +3346,44 @@ mod raw {
 
     impl RawReceive for &RawSocket {
         async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
-            let fut = pin!(self.0.read_with(|io| {
-                let mut storage: sys::sockaddr_storage = unsafe { core::mem::zeroed() };
-                let mut addrlen = core::mem::size_of_val(&storage) as sys::socklen_t;
-
-                let ret = syscall_los!(unsafe {
-                    sys::recvfrom(
-                        io.as_fd().as_raw_fd(),
-                        buffer.as_mut_ptr() as *mut _,
-                        buffer.len(),
-                        0,
-                        &mut storage as *mut _ as *mut _,
-                        &mut addrlen,
-                    )
-                })?;
-
-                let sockaddr = as_sockaddr_ll(&storage, addrlen as usize)?;
-
-                let mut mac = [0; 6];
-                mac.copy_from_slice(&sockaddr.sll_addr[..6]);
+            loop {
+                if let Some(received) = self.buffer.lock().unwrap().next(self.ether_type, buffer) {
+                    break Ok(received);
+                }
 
-                Ok((ret as usize, mac))
-            }));
+                let fut = pin!(self
+                    .device
+                    .read_with(|io| self.buffer.lock().unwrap().fill(io)));
 
-            fut.await
+                fut.await?;
+            }
         }
     }
 
     impl RawSend for &RawSocket {
         async fn send(&mut self, mac: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
-            let mut sockaddr = sys::sockaddr_ll {
-                sll_family: sys::AF_PACKET as _,
-                sll_protocol: (sys::ETH_P_IP as u16).to_be() as _,
-                sll_ifindex: self.1 as _,
-                sll_hatype: 0,
-                sll_pkttype: 0,
-                sll_halen: 0,
-                sll_addr: Default::default(),
-            };
-
-            sockaddr.sll_halen = mac.len() as _;
-            sockaddr.sll_addr[..mac.len()].copy_from_slice(&mac);
-
-            let fut = pin!(self.0.write_with(|io| {
-                let len = core::cmp::min(data.len(), u16::MAX as usize);
-
-                let ret = syscall_los!(unsafe {
-                    sys::sendto(
-                        io.as_fd().as_raw_fd(),
-                        data.as_ptr() as *const _,
-                        len,
-                        sys::MSG_NOSIGNAL,
-                        &sockaddr as *const _ as *const _,
-                        core::mem::size_of::<sys::sockaddr_ll>() as _,
-                    )
-                })?;
-                Ok(ret as usize)
+            let mut frame = Vec::with_capacity(ETHER_HEADER_LEN + data.len());
+
+            // The source address is filled in by the device
+            frame.extend_from_slice(&mac);
+            frame.extend_from_slice(&[0; 6]);
+            frame.extend_from_slice(&self.ether_type.to_be_bytes());
+            frame.extend_from_slice(data);
+
+            let fut = pin!(self.device.write_with(|io| {
+                let ret =
+                    unsafe { libc::write(io.as_raw_fd(), frame.as_ptr() as *const _, frame.len()) };
+
+                if ret == -1 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(ret as usize)
+                }
             }));
 
             let len = fut.await?;
 
-            assert_eq!(len, data.len());
+            assert_eq!(len, frame.len());
 
             Ok(())
         }
@@ -757,7 +3391,17 @@ mod raw {
 
     impl Readable for &RawSocket {
         async fn readable(&mut self) -> Result<(), Self::Error> {
-            self.0.readable().await
+            if self.buffer.lock().unwrap().is_empty() {
+                self.device.readable().await
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Writable for &RawSocket {
+        async fn writable(&mut self) -> Result<(), Self::Error> {
+            self.device.writable().await
         }
     }
 
@@ -805,21 +3449,314 @@ mod raw {
 
     impl Readable for RawSocket {
         async fn readable(&mut self) -> Result<(), Self::Error> {
-            self.0.readable().await
+            let mut rself = &*self;
+
+            rself.readable().await
         }
     }
 
-    fn as_sockaddr_ll(
-        storage: &sys::sockaddr_storage,
+    impl Writable for RawSocket {
+        async fn writable(&mut self) -> Result<(), Self::Error> {
+            self.device.writable().await
+        }
+    }
+
+    /// The frames returned by the last read from a BPF device, each one prefixed with a `bpf_hdr`
+    struct Frames {
+        data: Vec<u8>,
+        offset: usize,
         len: usize,
-    ) -> io::Result<&sys::sockaddr_ll> {
-        match storage.ss_family as core::ffi::c_int {
-            sys::AF_PACKET => {
-                assert!(len >= core::mem::size_of::<sys::sockaddr_ll>());
-                Ok(unsafe { (storage as *const _ as *const sys::sockaddr_ll).as_ref() }.unwrap())
+    }
+
+    impl Frames {
+        fn new(size: usize) -> Self {
+            Self {
+                data: vec![0; size],
+                offset: 0,
+                len: 0,
+            }
+        }
+
+        fn is_empty(&self) -> bool {
+            self.offset >= self.len
+        }
+
+        fn fill(&mut self, device: &File) -> io::Result<()> {
+            let ret = unsafe {
+                libc::read(
+                    device.as_raw_fd(),
+                    self.data.as_mut_ptr() as *mut _,
+                    self.data.len(),
+                )
+            };
+
+            if ret == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                self.offset = 0;
+                self.len = ret as _;
+
+                Ok(())
+            }
+        }
+
+        /// Copy the payload of the next frame of the provided EtherType into `buffer`,
+        /// returning its (possibly truncated) length and the MAC address of the sender
+        fn next(&mut self, ether_type: u16, buffer: &mut [u8]) -> Option<(usize, MacAddr)> {
+            while self.len - self.offset.min(self.len) >= core::mem::size_of::<libc::bpf_hdr>() {
+                let data = &self.data[self.offset..self.len];
+
+                let header =
+                    unsafe { core::ptr::read_unaligned(data.as_ptr() as *const libc::bpf_hdr) };
+
+                let start = header.bh_hdrlen as usize;
+                let end = start + header.bh_caplen as usize;
+
+                self.offset += (end + BPF_ALIGNMENT - 1) & !(BPF_ALIGNMENT - 1);
+
+                let Some(frame) = data.get(start..end) else {
+                    break;
+                };
+
+                if frame.len() < ETHER_HEADER_LEN
+                    || u16::from_be_bytes([frame[12], frame[13]]) != ether_type
+                {
+                    continue;
+                }
+
+                let payload = &frame[ETHER_HEADER_LEN..];
+                let len = payload.len().min(buffer.len());
+
+                buffer[..len].copy_from_slice(&payload[..len]);
+
+                let mut mac = [0; 6];
+                mac.copy_from_slice(&frame[6..12]);
+
+                return Some((len, mac));
+            }
+
+            self.offset = self.len;
+
+            None
+        }
+    }
+
+    /// Open the first available BPF device
+    fn open() -> io::Result<File> {
+        // FreeBSD and DragonFly clone `/dev/bpf` on each open; macOS only has the numbered devices
+        let mut last = match OpenOptions::new().read(true).write(true).open("/dev/bpf") {
+            Ok(device) => return Ok(device),
+            Err(err) => err,
+        };
+
+        for index in 0..256 {
+            match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(format!("/dev/bpf{index}"))
+            {
+                Ok(device) => return Ok(device),
+                Err(err) if err.raw_os_error() == Some(libc::EBUSY) => last = err,
+                Err(err) if err.kind() == ErrorKind::NotFound => break,
+                Err(err) => return Err(err),
             }
-            _ => Err(io::Error::new(ErrorKind::InvalidInput, "invalid argument")),
         }
+
+        Err(last)
+    }
+
+    fn ioctl<T>(device: &File, request: libc::c_ulong, arg: &mut T) -> io::Result<()> {
+        if unsafe { libc::ioctl(device.as_raw_fd(), request as _, arg as *mut T) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(feature = "interfaces", unix, not(target_os = "espidf")))]
+mod interfaces {
+    use core::ffi::CStr;
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use std::io;
+
+    use edge_nal::MacAddr;
+
+    /// A network interface of the host, as returned by `interfaces`
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct InterfaceInfo {
+        /// The name of the interface (e.g. `eth0` or `lo`)
+        pub name: String,
+        /// The interface index, as expected by e.g. the IPv6 multicast APIs
+        /// and the raw `Interface::new` constructor
+        pub index: u32,
+        /// The hardware (MAC) address of the interface, if it has one
+        pub mac: Option<MacAddr>,
+        /// The IP addresses assigned to the interface
+        pub addrs: Vec<IpAddr>,
+        /// `true` if the interface is up
+        pub up: bool,
+        /// `true` if this is a loopback interface
+        pub loopback: bool,
+        /// `true` if the interface supports multicast
+        pub multicast: bool,
+    }
+
+    impl InterfaceInfo {
+        /// Return the first IPv4 address of the interface, if any
+        pub fn ipv4(&self) -> Option<Ipv4Addr> {
+            self.addrs.iter().find_map(|addr| match addr {
+                IpAddr::V4(addr) => Some(*addr),
+                _ => None,
+            })
+        }
+
+        /// Return the first IPv6 address of the interface, if any
+        pub fn ipv6(&self) -> Option<Ipv6Addr> {
+            self.addrs.iter().find_map(|addr| match addr {
+                IpAddr::V6(addr) => Some(*addr),
+                _ => None,
+            })
+        }
+    }
+
+    /// Enumerate the network interfaces of the host, including the loopback one
+    pub fn interfaces() -> io::Result<Vec<InterfaceInfo>> {
+        let mut ifaddrs = core::ptr::null_mut();
+
+        if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut interfaces = Vec::<InterfaceInfo>::new();
+
+        let mut current = ifaddrs;
+
+        while let Some(ifaddr) = unsafe { current.as_ref() } {
+            current = ifaddr.ifa_next;
+
+            let name = unsafe { CStr::from_ptr(ifaddr.ifa_name) };
+
+            let index = match interfaces
+                .iter()
+                .position(|interface| interface.name.as_bytes() == name.to_bytes())
+            {
+                Some(index) => index,
+                None => {
+                    let flags = ifaddr.ifa_flags as libc::c_int;
+
+                    interfaces.push(InterfaceInfo {
+                        name: name.to_string_lossy().into_owned(),
+                        index: unsafe { libc::if_nametoindex(ifaddr.ifa_name) },
+                        mac: None,
+                        addrs: Vec::new(),
+                        up: flags & libc::IFF_UP != 0,
+                        loopback: flags & libc::IFF_LOOPBACK != 0,
+                        multicast: flags & libc::IFF_MULTICAST != 0,
+                    });
+
+                    interfaces.len() - 1
+                }
+            };
+
+            let interface = &mut interfaces[index];
+
+            let Some(addr) = (unsafe { ifaddr.ifa_addr.as_ref() }) else {
+                continue;
+            };
+
+            match addr.sa_family as libc::c_int {
+                libc::AF_INET => {
+                    let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in) };
+
+                    interface
+                        .addrs
+                        .push(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into());
+                }
+                libc::AF_INET6 => {
+                    let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in6) };
+
+                    interface
+                        .addrs
+                        .push(Ipv6Addr::from(addr.sin6_addr.s6_addr).into());
+                }
+                _ => {
+                    if let Some(mac) = mac(addr) {
+                        interface.mac = Some(mac);
+                    }
+                }
+            }
+        }
+
+        unsafe {
+            libc::freeifaddrs(ifaddrs);
+        }
+
+        Ok(interfaces)
+    }
+
+    /// Return the loopback interface of the host, if any
+    pub fn loopback_interface() -> io::Result<Option<InterfaceInfo>> {
+        Ok(interfaces()?
+            .into_iter()
+            .find(|interface| interface.loopback))
+    }
+
+    /// Return the index of the interface with the provided name
+    pub fn interface_index(name: &str) -> io::Result<u32> {
+        let name = std::ffi::CString::new(name)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+
+        match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+            0 => Err(io::Error::last_os_error()),
+            index => Ok(index),
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn mac(addr: &libc::sockaddr) -> Option<MacAddr> {
+        if addr.sa_family as libc::c_int != libc::AF_PACKET {
+            return None;
+        }
+
+        let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_ll) };
+
+        (addr.sll_halen == 6).then(|| addr.sll_addr[..6].try_into().unwrap())
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    fn mac(addr: &libc::sockaddr) -> Option<MacAddr> {
+        if addr.sa_family as libc::c_int != libc::AF_LINK {
+            return None;
+        }
+
+        let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_dl) };
+
+        if addr.sdl_alen != 6 {
+            return None;
+        }
+
+        // The link-layer address follows the interface name in `sdl_data`
+        let data = unsafe {
+            core::slice::from_raw_parts(
+                addr.sdl_data.as_ptr() as *const u8,
+                addr.sdl_nlen as usize + 6,
+            )
+        };
+
+        data[addr.sdl_nlen as usize..].try_into().ok()
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )))]
+    fn mac(_addr: &libc::sockaddr) -> Option<MacAddr> {
+        None
     }
 }
 
@@ -0,0 +1,1054 @@
+//! An implementation of the `edge-nal` traits on top of the [tokio](https://tokio.rs) runtime.
+//!
+//! The sockets are registered with the reactor of the tokio runtime they are created in,
+//! so applications which already run a tokio runtime do not need the reactor thread of `async-io` in addition.
+//! All sockets must therefore be created and used from within a tokio runtime with the IO driver enabled
+//! (and the time driver as well, for `DnsQuery`).
+
+use core::future::poll_fn;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::ops::Deref;
+use core::pin::pin;
+use core::time::Duration;
+
+use std::io::{self, IoSlice};
+use std::net::Shutdown;
+
+use ::tokio::io::Interest;
+use ::tokio::net;
+
+use embedded_io_async::{ErrorType, Read, Write};
+
+use edge_nal::{
+    dns_query, AddrType, BindOptions, BindToInterface, Dns, DnsQuery, DnsQueryError, IpOptions,
+    MulticastSourceV4, MulticastSourceV6, MulticastV4, MulticastV6, Readable, RecordType,
+    SocketStats, Stats, TcpAccept, TcpBind, TcpConnect, TcpOptions, TcpShutdown, TcpSplit, UdpBind,
+    UdpConnect, UdpConnectPeer, UdpPeek, UdpReceive, UdpReceiveInfo, UdpReceiveMeta, UdpSend,
+    UdpSplit, VectoredCursor, Writable, WriteVectored,
+};
+
+use crate::{
+    advance, dns_copy_host, dns_query_error, dns_query_id, dns_server, io_slices, options, pktinfo,
+};
+
+#[derive(Default, Clone)]
+pub struct Stack(());
+
+impl Stack {
+    pub const fn new() -> Self {
+        Self(())
+    }
+}
+
+impl TcpConnect for Stack {
+    type Error = io::Error;
+
+    type Socket<'a>
+        = TcpSocket
+    where
+        Self: 'a;
+
+    async fn connect(&self, remote: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
+        let socket = net::TcpStream::connect(remote).await?;
+
+        Ok(TcpSocket(socket))
+    }
+}
+
+impl TcpBind for Stack {
+    type Error = io::Error;
+
+    type Accept<'a>
+        = TcpAcceptor
+    where
+        Self: 'a;
+
+    async fn bind(&self, local: SocketAddr) -> Result<Self::Accept<'_>, Self::Error> {
+        let acceptor = net::TcpListener::bind(local).await.map(TcpAcceptor)?;
+
+        Ok(acceptor)
+    }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Accept<'_>, Self::Error> {
+        let listener = options::tcp_listener(local, options)?;
+
+        // Tokio expects the socket to be in non-blocking mode already
+        listener.set_nonblocking(true)?;
+
+        Ok(TcpAcceptor(net::TcpListener::from_std(listener)?))
+    }
+}
+
+pub struct TcpAcceptor(net::TcpListener);
+
+impl TcpAcceptor {
+    pub const fn new(listener: net::TcpListener) -> Self {
+        Self(listener)
+    }
+
+    pub fn release(self) -> net::TcpListener {
+        self.0
+    }
+}
+
+impl Deref for TcpAcceptor {
+    type Target = net::TcpListener;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TcpAccept for TcpAcceptor {
+    type Error = io::Error;
+
+    type Socket<'a>
+        = TcpSocket
+    where
+        Self: 'a;
+
+    async fn accept(&self) -> Result<(SocketAddr, Self::Socket<'_>), Self::Error> {
+        let (socket, remote) = self.0.accept().await?;
+
+        Ok((remote, TcpSocket(socket)))
+    }
+}
+
+pub struct TcpSocket(net::TcpStream);
+
+impl TcpSocket {
+    pub const fn new(socket: net::TcpStream) -> Self {
+        Self(socket)
+    }
+
+    pub fn release(self) -> net::TcpStream {
+        self.0
+    }
+}
+
+impl Deref for TcpSocket {
+    type Target = net::TcpStream;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ErrorType for TcpSocket {
+    type Error = io::Error;
+}
+
+impl Read for TcpSocket {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        read(&self.0, buf).await
+    }
+}
+
+impl Write for TcpSocket {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        write(&self.0, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // Tokio does not buffer the written data
+        Ok(())
+    }
+}
+
+impl WriteVectored for TcpSocket {
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        let mut cursor = VectoredCursor::new(bufs);
+
+        while !cursor.is_empty() {
+            let len = write_vectored(&self.0, &io_slices(&cursor)).await?;
+
+            advance(&mut cursor, len)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Readable for TcpSocket {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.0.readable().await
+    }
+}
+
+impl Writable for TcpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0.writable().await
+    }
+}
+
+impl Stats for TcpSocket {
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        let socket = &self.0;
+
+        let mut stats = SocketStats::new();
+
+        stats.local = Some(socket.local_addr()?);
+        stats.remote = socket.peer_addr().ok();
+        stats.send_queue = options::send_queue(socket);
+
+        options::tcp_stats(socket, &mut stats)?;
+
+        Ok(stats)
+    }
+}
+
+impl ErrorType for &TcpSocket {
+    type Error = io::Error;
+}
+
+impl Read for &TcpSocket {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        read(&self.0, buf).await
+    }
+}
+
+impl Write for &TcpSocket {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        write(&self.0, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl WriteVectored for &TcpSocket {
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        let mut cursor = VectoredCursor::new(bufs);
+
+        while !cursor.is_empty() {
+            let len = write_vectored(&self.0, &io_slices(&cursor)).await?;
+
+            advance(&mut cursor, len)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Readable for &TcpSocket {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.0.readable().await
+    }
+}
+
+impl Writable for &TcpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0.writable().await
+    }
+}
+
+impl TcpSplit for TcpSocket {
+    type Read<'a>
+        = &'a TcpSocket
+    where
+        Self: 'a;
+
+    type Write<'a>
+        = &'a TcpSocket
+    where
+        Self: 'a;
+
+    fn split(&mut self) -> (Self::Read<'_>, Self::Write<'_>) {
+        let socket = &*self;
+
+        (socket, socket)
+    }
+}
+
+impl TcpShutdown for TcpSocket {
+    async fn close(&mut self, what: edge_nal::Close) -> Result<(), Self::Error> {
+        // Tokio can only shut down the write half of a socket, so go through `socket2` instead
+        let socket = socket2::SockRef::from(&self.0);
+
+        match what {
+            edge_nal::Close::Read => socket.shutdown(Shutdown::Read)?,
+            edge_nal::Close::Write => socket.shutdown(Shutdown::Write)?,
+            edge_nal::Close::Both => socket.shutdown(Shutdown::Both)?,
+        }
+
+        Ok(())
+    }
+
+    async fn abort(&mut self) -> Result<(), Self::Error> {
+        // A zero linger time makes the OS reset the connection once the socket is dropped,
+        // rather than closing it gracefully in the background
+        options::set_linger(&self.0, Some(Duration::ZERO))
+    }
+
+    async fn close_with_timeout(&mut self, timeout_ms: u32) -> Result<bool, Self::Error> {
+        // `close(Close::Both)` does not wait for the FIN packet of the peer,
+        // so send ours and then drain the socket until the peer closes its half as well
+        let close = async {
+            socket2::SockRef::from(&self.0).shutdown(Shutdown::Write)?;
+
+            let mut buf = [0; 64];
+            while read(&self.0, &mut buf).await? > 0 {}
+
+            Ok::<_, io::Error>(())
+        };
+
+        let result =
+            ::tokio::time::timeout(Duration::from_millis(timeout_ms as _), Box::pin(close)).await;
+
+        match result {
+            Ok(Ok(())) => Ok(true),
+            Ok(Err(err)) => {
+                let _ = self.abort().await;
+
+                Err(err)
+            }
+            Err(_) => {
+                self.abort().await?;
+
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl TcpOptions for TcpSocket {
+    async fn set_nodelay(&mut self, nodelay: bool) -> Result<(), Self::Error> {
+        self.0.set_nodelay(nodelay)
+    }
+
+    async fn set_keepalive(&mut self, idle: Option<Duration>) -> Result<(), Self::Error> {
+        options::set_keepalive(&self.0, idle)
+    }
+
+    async fn set_keepalive_probes(
+        &mut self,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> Result<(), Self::Error> {
+        options::set_keepalive_probes(&self.0, interval, count)
+    }
+
+    async fn set_user_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        options::set_user_timeout(&self.0, timeout)
+    }
+
+    async fn set_linger(&mut self, linger: Option<Duration>) -> Result<(), Self::Error> {
+        options::set_linger(&self.0, linger)
+    }
+}
+
+impl BindToInterface for TcpSocket {
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error> {
+        let socket = &self.0;
+
+        options::bind_to_interface(socket, socket.local_addr()?.is_ipv6(), interface)
+    }
+}
+
+impl IpOptions for TcpSocket {
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        let socket = &self.0;
+
+        if socket.local_addr()?.is_ipv6() {
+            options::set_hop_limit_v6(socket, ttl)
+        } else {
+            socket.set_ttl(ttl as _)
+        }
+    }
+
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error> {
+        let socket = &self.0;
+
+        options::set_tos(socket, socket.local_addr()?.is_ipv6(), tos)
+    }
+}
+
+async fn read(socket: &net::TcpStream, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        socket.readable().await?;
+
+        match socket.try_read(buf) {
+            // The readiness event was a false positive
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            other => break other,
+        }
+    }
+}
+
+async fn write(socket: &net::TcpStream, buf: &[u8]) -> io::Result<usize> {
+    loop {
+        socket.writable().await?;
+
+        match socket.try_write(buf) {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            other => break other,
+        }
+    }
+}
+
+async fn write_vectored(socket: &net::TcpStream, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+    loop {
+        socket.writable().await?;
+
+        match socket.try_write_vectored(bufs) {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            other => break other,
+        }
+    }
+}
+
+impl UdpConnect for Stack {
+    type Error = io::Error;
+
+    type Socket<'a>
+        = UdpSocket
+    where
+        Self: 'a;
+
+    async fn connect(
+        &self,
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        let socket = net::UdpSocket::bind(local).await?;
+
+        socket.connect(remote).await?;
+
+        Ok(UdpSocket::new(socket))
+    }
+}
+
+impl UdpBind for Stack {
+    type Error = io::Error;
+
+    type Socket<'a>
+        = UdpSocket
+    where
+        Self: 'a;
+
+    async fn bind(&self, local: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
+        let socket = net::UdpSocket::bind(local).await?;
+
+        socket.set_broadcast(true)?;
+
+        Ok(UdpSocket::new(socket))
+    }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        let socket = options::udp_socket(local, options)?;
+
+        socket.set_nonblocking(true)?;
+
+        let socket = net::UdpSocket::from_std(socket)?;
+
+        socket.set_broadcast(true)?;
+
+        Ok(UdpSocket::new(socket))
+    }
+}
+
+pub struct UdpSocket(net::UdpSocket, pktinfo::Enabled);
+
+impl UdpSocket {
+    pub const fn new(socket: net::UdpSocket) -> Self {
+        Self(socket, pktinfo::Enabled::new())
+    }
+
+    pub fn release(self) -> net::UdpSocket {
+        self.0
+    }
+}
+
+impl Deref for UdpSocket {
+    type Target = net::UdpSocket;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ErrorType for &UdpSocket {
+    type Error = io::Error;
+}
+
+impl UdpReceive for &UdpSocket {
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let remote = self.0.peer_addr();
+
+        let (len, remote) = if let Ok(remote) = remote {
+            // Connected socket
+            let len = self.0.recv(buffer).await?;
+
+            (len, remote)
+        } else {
+            // Unconnected socket
+            self.0.recv_from(buffer).await?
+        };
+
+        Ok((len, remote))
+    }
+}
+
+impl UdpReceiveInfo for &UdpSocket {
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            // Datagrams queued before the first call might still come without the packet info
+            self.1
+                .ensure(&self.0, || Ok(self.0.local_addr()?.is_ipv6()))?;
+
+            self.0
+                .async_io(Interest::READABLE, || pktinfo::receive(&self.0, buffer, 0))
+                .await
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let (len, remote) = self.receive(buffer).await?;
+
+            Ok((len, remote, UdpReceiveMeta::new()))
+        }
+    }
+}
+
+/// Unlike the `async-io` based sockets, the returned length is capped to the buffer's length on all platforms
+impl UdpPeek for &UdpSocket {
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        self.0.peek_from(buffer).await
+    }
+}
+
+impl UdpSend for &UdpSocket {
+    async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+        let is_remote = self.0.peer_addr().is_ok();
+
+        let mut offset = 0;
+
+        loop {
+            // Polling directly, as the `send` and `send_to` futures of tokio are rather large
+            offset += if is_remote {
+                // Connected socket
+                poll_fn(|cx| self.0.poll_send(cx, &data[offset..])).await?
+            } else {
+                // Unconnected socket
+                poll_fn(|cx| self.0.poll_send_to(cx, &data[offset..], remote)).await?
+            };
+
+            if offset == data.len() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MulticastV4 for &UdpSocket {
+    async fn join_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        self.0.join_multicast_v4(multicast_addr, interface)
+    }
+
+    async fn leave_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        self.0.leave_multicast_v4(multicast_addr, interface)
+    }
+
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        self.0.set_multicast_ttl_v4(ttl as _)
+    }
+
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        self.0.set_multicast_loop_v4(enabled)
+    }
+
+    async fn set_multicast_if_v4(&mut self, interface: Ipv4Addr) -> Result<(), Self::Error> {
+        options::set_multicast_if_v4(&self.0, interface)
+    }
+}
+
+impl MulticastV6 for &UdpSocket {
+    async fn join_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        self.0.join_multicast_v6(&multicast_addr, interface)
+    }
+
+    async fn leave_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        self.0.leave_multicast_v6(&multicast_addr, interface)
+    }
+
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error> {
+        options::set_multicast_hops_v6(&self.0, hops)
+    }
+
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        self.0.set_multicast_loop_v6(enabled)
+    }
+
+    async fn set_multicast_if_v6(&mut self, interface: u32) -> Result<(), Self::Error> {
+        options::set_multicast_if_v6(&self.0, interface)
+    }
+}
+
+impl MulticastSourceV4 for &UdpSocket {
+    async fn join_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v4(&self.0, multicast_addr, source, interface, true)
+    }
+
+    async fn leave_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v4(&self.0, multicast_addr, source, interface, false)
+    }
+}
+
+impl MulticastSourceV6 for &UdpSocket {
+    async fn join_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v6(&self.0, multicast_addr, source, interface, true)
+    }
+
+    async fn leave_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        options::set_source_membership_v6(&self.0, multicast_addr, source, interface, false)
+    }
+}
+
+impl IpOptions for &UdpSocket {
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        let socket = &self.0;
+
+        if socket.local_addr()?.is_ipv6() {
+            options::set_hop_limit_v6(socket, ttl)
+        } else {
+            socket.set_ttl(ttl as _)
+        }
+    }
+
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error> {
+        let socket = &self.0;
+
+        options::set_tos(socket, socket.local_addr()?.is_ipv6(), tos)
+    }
+}
+
+impl UdpConnectPeer for &UdpSocket {
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error> {
+        self.0.connect(remote).await
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Self::Error> {
+        options::disconnect(&self.0)
+    }
+}
+
+impl Stats for &UdpSocket {
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        (**self).stats()
+    }
+}
+
+impl Stats for UdpSocket {
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        let socket = &self.0;
+
+        let mut stats = SocketStats::new();
+
+        stats.local = Some(socket.local_addr()?);
+        stats.remote = socket.peer_addr().ok();
+        stats.send_queue = options::send_queue(socket);
+
+        Ok(stats)
+    }
+}
+
+impl BindToInterface for &UdpSocket {
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error> {
+        let socket = &self.0;
+
+        options::bind_to_interface(socket, socket.local_addr()?.is_ipv6(), interface)
+    }
+}
+
+impl Readable for &UdpSocket {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.0.readable().await
+    }
+}
+
+impl Writable for &UdpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.0.writable().await
+    }
+}
+
+impl ErrorType for UdpSocket {
+    type Error = io::Error;
+}
+
+impl UdpReceive for UdpSocket {
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.receive(buffer));
+        fut.await
+    }
+}
+
+impl UdpReceiveInfo for UdpSocket {
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.receive_info(buffer));
+        fut.await
+    }
+}
+
+impl UdpPeek for UdpSocket {
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.peek(buffer));
+        fut.await
+    }
+}
+
+impl UdpSend for UdpSocket {
+    async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.send(remote, data));
+        fut.await
+    }
+}
+
+impl MulticastV4 for UdpSocket {
+    async fn join_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.join_v4(multicast_addr, interface).await
+    }
+
+    async fn leave_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.leave_v4(multicast_addr, interface).await
+    }
+
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_multicast_ttl_v4(ttl).await
+    }
+
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_multicast_loop_v4(enabled).await
+    }
+
+    async fn set_multicast_if_v4(&mut self, interface: Ipv4Addr) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_multicast_if_v4(interface).await
+    }
+}
+
+impl MulticastV6 for UdpSocket {
+    async fn join_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.join_v6(multicast_addr, interface).await
+    }
+
+    async fn leave_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.leave_v6(multicast_addr, interface).await
+    }
+
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_multicast_hops_v6(hops).await
+    }
+
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_multicast_loop_v6(enabled).await
+    }
+
+    async fn set_multicast_if_v6(&mut self, interface: u32) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_multicast_if_v6(interface).await
+    }
+}
+
+impl MulticastSourceV4 for UdpSocket {
+    async fn join_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself
+            .join_source_v4(multicast_addr, source, interface)
+            .await
+    }
+
+    async fn leave_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself
+            .leave_source_v4(multicast_addr, source, interface)
+            .await
+    }
+}
+
+impl MulticastSourceV6 for UdpSocket {
+    async fn join_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself
+            .join_source_v6(multicast_addr, source, interface)
+            .await
+    }
+
+    async fn leave_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself
+            .leave_source_v6(multicast_addr, source, interface)
+            .await
+    }
+}
+
+impl IpOptions for UdpSocket {
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_ttl(ttl).await
+    }
+
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.set_tos(tos).await
+    }
+}
+
+impl UdpConnectPeer for UdpSocket {
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.connect(remote));
+        fut.await
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.disconnect());
+        fut.await
+    }
+}
+
+impl BindToInterface for UdpSocket {
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        rself.bind_to_interface(interface).await
+    }
+}
+
+impl Readable for UdpSocket {
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.readable());
+        fut.await
+    }
+}
+
+impl Writable for UdpSocket {
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        let mut rself = &*self;
+
+        let fut = pin!(rself.writable());
+        fut.await
+    }
+}
+
+impl UdpSplit for UdpSocket {
+    type Receive<'a>
+        = &'a Self
+    where
+        Self: 'a;
+
+    type Send<'a>
+        = &'a Self
+    where
+        Self: 'a;
+
+    fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
+        let socket = &*self;
+
+        (socket, socket)
+    }
+}
+
+impl Dns for Stack {
+    type Error = io::Error;
+
+    async fn get_host_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, Self::Error> {
+        net::lookup_host((host, 0_u16))
+            .await?
+            .find(|addr| match addr_type {
+                AddrType::IPv4 => addr.is_ipv4(),
+                AddrType::IPv6 => addr.is_ipv6(),
+                AddrType::Either => true,
+            })
+            .map(|addr| addr.ip())
+            .ok_or_else(|| io::ErrorKind::AddrNotAvailable.into())
+    }
+
+    async fn get_host_by_address(
+        &self,
+        addr: IpAddr,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let host = ::tokio::task::spawn_blocking(move || options::host_name(addr))
+            .await
+            .map_err(io::Error::other)??;
+
+        dns_copy_host(&host, result)
+    }
+}
+
+/// Queries the first name server configured in `/etc/resolv.conf` directly,
+/// just like the `DnsQuery` implementation of the `async-io` based stack
+impl DnsQuery for Stack {
+    async fn query(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        const TIMEOUT: Duration = Duration::from_secs(2);
+        const RETRIES: usize = 2;
+
+        let server = dns_server()?;
+
+        let local = match server {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        };
+
+        let mut attempt = 0;
+
+        loop {
+            let mut socket = UdpBind::bind(self, local).await?;
+
+            let query = dns_query(
+                &mut socket,
+                server,
+                dns_query_id(),
+                name,
+                record_type,
+                result,
+            );
+
+            let outcome = ::tokio::time::timeout(TIMEOUT, Box::pin(query))
+                .await
+                .unwrap_or(Err(DnsQueryError::Timeout));
+
+            match outcome {
+                Err(DnsQueryError::Timeout) if attempt < RETRIES => attempt += 1,
+                other => break other.map_err(dns_query_error),
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use embedded_io_async::ErrorKind;
+
+/// A networking-specific classification of errors.
+///
+/// `embedded_io_async::ErrorKind` has no notion of e.g. a missing route or a truncated datagram,
+/// so these - along with most other conditions reported by the networking stacks - end up as `ErrorKind::Other`.
+/// Error types implementing `NetError` provide this finer-grained classification in addition,
+/// while still retaining the original error of the networking stack.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum NetErrorKind {
+    /// The connection was reset by the peer (i.e. a RST was received on an established connection)
+    ConnectionReset,
+    /// The peer rejected the connection attempt
+    ConnectionRefused,
+    /// The connection was aborted before it got established
+    ConnectionAborted,
+    /// The operation timed out
+    TimedOut,
+    /// There is no route to the peer
+    NoRoute,
+    /// The socket is not connected or bound
+    NotConnected,
+    /// The socket is not in a state which allows the operation
+    InvalidState,
+    /// The local address is already in use
+    AddrInUse,
+    /// The address is not valid or not available on the host
+    AddrNotAvailable,
+    /// A received datagram did not fit in the supplied buffer
+    Truncated,
+    /// The datagram to be sent does not fit in the buffers of the networking stack
+    PacketTooLarge,
+    /// No more multicast groups can be joined
+    GroupTableFull,
+    /// No socket buffers are available
+    NoBuffers,
+    /// The operation - or the socket option - is not supported by the networking stack
+    Unsupported,
+    /// Any other error
+    Other,
+}
+
+impl NetErrorKind {
+    /// The number of error kinds
+    pub const COUNT: usize = Self::Other as usize + 1;
+
+    /// All error kinds
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::ConnectionReset,
+        Self::ConnectionRefused,
+        Self::ConnectionAborted,
+        Self::TimedOut,
+        Self::NoRoute,
+        Self::NotConnected,
+        Self::InvalidState,
+        Self::AddrInUse,
+        Self::AddrNotAvailable,
+        Self::Truncated,
+        Self::PacketTooLarge,
+        Self::GroupTableFull,
+        Self::NoBuffers,
+        Self::Unsupported,
+        Self::Other,
+    ];
+
+    /// Return the closest `embedded_io_async::ErrorKind` for this error kind
+    pub const fn io_kind(&self) -> ErrorKind {
+        match self {
+            Self::ConnectionReset => ErrorKind::ConnectionReset,
+            Self::ConnectionRefused => ErrorKind::ConnectionRefused,
+            Self::ConnectionAborted => ErrorKind::ConnectionAborted,
+            Self::TimedOut => ErrorKind::TimedOut,
+            Self::NotConnected => ErrorKind::NotConnected,
+            Self::InvalidState | Self::Truncated | Self::PacketTooLarge => ErrorKind::InvalidInput,
+            Self::AddrInUse => ErrorKind::AddrInUse,
+            Self::AddrNotAvailable => ErrorKind::AddrNotAvailable,
+            Self::GroupTableFull | Self::NoBuffers => ErrorKind::OutOfMemory,
+            Self::Unsupported => ErrorKind::Unsupported,
+            Self::NoRoute | Self::Other => ErrorKind::Other,
+        }
+    }
+}
+
+impl From<NetErrorKind> for ErrorKind {
+    fn from(kind: NetErrorKind) -> Self {
+        kind.io_kind()
+    }
+}
+
+/// An IO error which can also be classified with a `NetErrorKind`.
+///
+/// Useful for retry logic which needs to e.g. tell apart a peer rejecting a connection
+/// from the peer not being reachable at all.
+pub trait NetError: embedded_io_async::Error {
+    /// Get the networking-specific kind of this error
+    fn net_kind(&self) -> NetErrorKind;
+}
+
+/// Counters of the errors which occurred on a socket, per error kind
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct NetErrorStats {
+    counts: [u32; NetErrorKind::COUNT],
+}
+
+impl NetErrorStats {
+    /// Create a new set of counters, all set to 0
+    pub const fn new() -> Self {
+        Self {
+            counts: [0; NetErrorKind::COUNT],
+        }
+    }
+
+    /// Count one occurrence of an error of the provided kind
+    pub fn record(&mut self, kind: NetErrorKind) {
+        let count = &mut self.counts[kind as usize];
+
+        *count = count.saturating_add(1);
+    }
+
+    /// Return the number of errors of the provided kind
+    pub fn count(&self, kind: NetErrorKind) -> u32 {
+        self.counts[kind as usize]
+    }
+
+    /// Return the number of errors of all kinds
+    pub fn total(&self) -> u32 {
+        self.counts
+            .iter()
+            .fold(0, |total, count| total.saturating_add(*count))
+    }
+
+    /// Iterate over the error kinds which occurred at least once, along with their counts
+    pub fn iter(&self) -> impl Iterator<Item = (NetErrorKind, u32)> + '_ {
+        NetErrorKind::ALL
+            .into_iter()
+            .map(|kind| (kind, self.count(kind)))
+            .filter(|(_, count)| *count > 0)
+    }
+
+    /// Reset all counters to 0
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
@@ -1,22 +1,36 @@
 #![no_std]
 #![allow(async_fn_in_trait)]
 
+pub use error::*;
 pub use multicast::*;
+pub use options::*;
 pub use raw::*;
 pub use readable::*;
+pub use resolver::*;
+pub use stats::*;
 pub use tcp::*;
 pub use timeout::*;
+pub use tls::*;
 pub use udp::*;
+pub use vectored::*;
+pub use writable::*;
 
 pub use stack::*;
 
+mod error;
 mod multicast;
+mod options;
 mod raw;
 mod readable;
+mod resolver;
 mod stack;
+mod stats;
 mod tcp;
 mod timeout;
+mod tls;
 mod udp;
+mod vectored;
+mod writable;
 
 pub mod io {
     pub use embedded_io_async::*;
@@ -13,6 +13,19 @@ pub trait MulticastV4: ErrorType {
         multicast_addr: Ipv4Addr,
         interface: Ipv4Addr,
     ) -> Result<(), Self::Error>;
+
+    /// Set the time-to-live of the IPv4 multicast packets sent by the socket
+    ///
+    /// Note that on some stacks (i.e. the networking stack of Embassy) this is the TTL of all packets sent by the socket.
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error>;
+
+    /// Enable or disable the looping back of the IPv4 multicast packets sent by the socket to the local host
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error>;
+
+    /// Select the interface - identified by its IPv4 address - through which the IPv4 multicast packets are sent
+    ///
+    /// `Ipv4Addr::UNSPECIFIED` restores the default, where the interface is selected by the routing table.
+    async fn set_multicast_if_v4(&mut self, interface: Ipv4Addr) -> Result<(), Self::Error>;
 }
 
 impl<T> MulticastV4 for &mut T
@@ -34,6 +47,18 @@ where
     ) -> Result<(), Self::Error> {
         (**self).leave_v4(multicast_addr, interface).await
     }
+
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        (**self).set_multicast_ttl_v4(ttl).await
+    }
+
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        (**self).set_multicast_loop_v4(enabled).await
+    }
+
+    async fn set_multicast_if_v4(&mut self, interface: Ipv4Addr) -> Result<(), Self::Error> {
+        (**self).set_multicast_if_v4(interface).await
+    }
 }
 
 pub trait MulticastV6: ErrorType {
@@ -47,6 +72,19 @@ pub trait MulticastV6: ErrorType {
         multicast_addr: Ipv6Addr,
         interface: u32,
     ) -> Result<(), Self::Error>;
+
+    /// Set the hop limit of the IPv6 multicast packets sent by the socket
+    ///
+    /// Note that on some stacks (i.e. the networking stack of Embassy) this is the hop limit of all packets sent by the socket.
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error>;
+
+    /// Enable or disable the looping back of the IPv6 multicast packets sent by the socket to the local host
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error>;
+
+    /// Select the interface - identified by its index - through which the IPv6 multicast packets are sent
+    ///
+    /// An index of 0 restores the default, where the interface is selected by the routing table.
+    async fn set_multicast_if_v6(&mut self, interface: u32) -> Result<(), Self::Error>;
 }
 
 impl<T> MulticastV6 for &mut T
@@ -68,4 +106,116 @@ where
     ) -> Result<(), Self::Error> {
         (**self).leave_v6(multicast_addr, interface).await
     }
+
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error> {
+        (**self).set_multicast_hops_v6(hops).await
+    }
+
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        (**self).set_multicast_loop_v6(enabled).await
+    }
+
+    async fn set_multicast_if_v6(&mut self, interface: u32) -> Result<(), Self::Error> {
+        (**self).set_multicast_if_v6(interface).await
+    }
+}
+
+/// Source-specific multicast (SSM) for IPv4, i.e. receiving the packets of a multicast group
+/// only from the provided source (IGMPv3).
+///
+/// Implemented by the UDP sockets of stacks which support source filters, in addition to `MulticastV4`.
+pub trait MulticastSourceV4: ErrorType {
+    /// Join the multicast group on the interface - identified by its IPv4 address -
+    /// receiving only the packets sent by `source`
+    async fn join_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error>;
+
+    /// Leave a source-specific membership joined with `join_source_v4`
+    async fn leave_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error>;
+}
+
+impl<T> MulticastSourceV4 for &mut T
+where
+    T: MulticastSourceV4,
+{
+    async fn join_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        (**self)
+            .join_source_v4(multicast_addr, source, interface)
+            .await
+    }
+
+    async fn leave_source_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        source: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        (**self)
+            .leave_source_v4(multicast_addr, source, interface)
+            .await
+    }
+}
+
+/// Source-specific multicast (SSM) for IPv6, i.e. receiving the packets of a multicast group
+/// only from the provided source (MLDv2).
+///
+/// Implemented by the UDP sockets of stacks which support source filters, in addition to `MulticastV6`.
+pub trait MulticastSourceV6: ErrorType {
+    /// Join the multicast group on the interface - identified by its index, with 0 letting
+    /// the stack choose - receiving only the packets sent by `source`
+    async fn join_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error>;
+
+    /// Leave a source-specific membership joined with `join_source_v6`
+    async fn leave_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error>;
+}
+
+impl<T> MulticastSourceV6 for &mut T
+where
+    T: MulticastSourceV6,
+{
+    async fn join_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        (**self)
+            .join_source_v6(multicast_addr, source, interface)
+            .await
+    }
+
+    async fn leave_source_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        source: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        (**self)
+            .leave_source_v6(multicast_addr, source, interface)
+            .await
+    }
 }
@@ -0,0 +1,205 @@
+//! Traits for modeling TCP and IP socket options
+
+use core::time::Duration;
+
+use embedded_io_async::ErrorType;
+
+/// This trait is implemented by TCP sockets which allow tweaking the TCP-specific socket options.
+///
+/// Options which are not supported by the underlying networking stack are reported with an error
+/// of kind `NetErrorKind::Unsupported` (or `embedded_io_async::ErrorKind::Unsupported`).
+pub trait TcpOptions: ErrorType {
+    /// Enable or disable the `TCP_NODELAY` option, i.e. disable or enable Nagle's algorithm.
+    ///
+    /// With Nagle's algorithm disabled, small writes are sent immediately rather than being
+    /// coalesced while previously sent data is not yet ACKed by the peer, which trades bandwidth for latency.
+    async fn set_nodelay(&mut self, nodelay: bool) -> Result<(), Self::Error>;
+
+    /// Enable or disable the TCP keep-alive probes.
+    ///
+    /// `Some(idle)` enables the probes, which are sent after the connection has been idle for `idle`
+    /// (stacks which do not support a per-socket idle time use their default one);
+    /// `None` disables them.
+    async fn set_keepalive(&mut self, idle: Option<Duration>) -> Result<(), Self::Error>;
+
+    /// Tune the keep-alive probes enabled with `set_keepalive`: `interval` is the time between two
+    /// unanswered probes, and `count` is the number of unanswered probes after which the connection is dropped.
+    ///
+    /// `None` keeps the stack default of the respective setting. Together with the idle time, the two
+    /// settings bound the time it takes to detect a dead peer on an otherwise idle connection.
+    async fn set_keepalive_probes(
+        &mut self,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> Result<(), Self::Error>;
+
+    /// Set the `TCP_USER_TIMEOUT` option, i.e. the maximum time the sent data might remain unacknowledged
+    /// by the peer before the connection is dropped; `None` restores the stack default.
+    ///
+    /// Unlike the keep-alive probes, this detects dead peers on connections with outstanding data.
+    async fn set_user_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error>;
+
+    /// Set the `SO_LINGER` option.
+    ///
+    /// `Some(timeout)` makes closing the socket wait for up to `timeout` for the unsent data to be sent,
+    /// where a timeout of zero aborts the connection with a RST instead;
+    /// `None` restores the default (non-lingering) behavior.
+    async fn set_linger(&mut self, linger: Option<Duration>) -> Result<(), Self::Error>;
+}
+
+impl<T> TcpOptions for &mut T
+where
+    T: TcpOptions,
+{
+    async fn set_nodelay(&mut self, nodelay: bool) -> Result<(), Self::Error> {
+        (**self).set_nodelay(nodelay).await
+    }
+
+    async fn set_keepalive(&mut self, idle: Option<Duration>) -> Result<(), Self::Error> {
+        (**self).set_keepalive(idle).await
+    }
+
+    async fn set_keepalive_probes(
+        &mut self,
+        interval: Option<Duration>,
+        count: Option<u32>,
+    ) -> Result<(), Self::Error> {
+        (**self).set_keepalive_probes(interval, count).await
+    }
+
+    async fn set_user_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        (**self).set_user_timeout(timeout).await
+    }
+
+    async fn set_linger(&mut self, linger: Option<Duration>) -> Result<(), Self::Error> {
+        (**self).set_linger(linger).await
+    }
+}
+
+/// This trait is implemented by TCP and UDP sockets which allow tweaking the IP-level socket options.
+///
+/// Options which are not supported by the underlying networking stack are reported with an error
+/// of kind `NetErrorKind::Unsupported` (or `embedded_io_async::ErrorKind::Unsupported`).
+pub trait IpOptions: ErrorType {
+    /// Set the time-to-live (IPv4) or the hop limit (IPv6) of the unicast packets sent by the socket
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error>;
+
+    /// Set the type-of-service (IPv4) or the traffic class (IPv6) of the packets sent by the socket
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error>;
+}
+
+impl<T> IpOptions for &mut T
+where
+    T: IpOptions,
+{
+    async fn set_ttl(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        (**self).set_ttl(ttl).await
+    }
+
+    async fn set_tos(&mut self, tos: u8) -> Result<(), Self::Error> {
+        (**self).set_tos(tos).await
+    }
+}
+
+/// This trait is implemented by TCP and UDP sockets which can be bound to a specific network interface,
+/// so that they only send and receive packets through it.
+///
+/// Useful on multi-homed hosts (i.e. a gateway with both a Wi-Fi and an Ethernet interface),
+/// where the routing table alone might pick the wrong interface for e.g. the DHCP or mDNS traffic.
+pub trait BindToInterface: ErrorType {
+    /// Bind the socket to the network interface with the provided index,
+    /// or remove the binding if `interface` is `None`
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error>;
+}
+
+impl<T> BindToInterface for &mut T
+where
+    T: BindToInterface,
+{
+    async fn bind_to_interface(&mut self, interface: Option<u32>) -> Result<(), Self::Error> {
+        (**self).bind_to_interface(interface).await
+    }
+}
+
+/// Options applied to TCP and UDP sockets before they are bound, as accepted by
+/// `TcpBind::bind_with_options` and `UdpBind::bind_with_options`.
+///
+/// The default options leave all settings to the networking stack.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct BindOptions {
+    /// Set the `SO_REUSEADDR` option, allowing the socket to be bound to an address which is still in use
+    /// (i.e. by connections in the `TIME_WAIT` state, or by UDP sockets bound to the same port on another address)
+    pub reuse_addr: bool,
+    /// Set the `SO_REUSEPORT` option, allowing multiple sockets - possibly in different processes -
+    /// to be bound to the same address and port (i.e. multiple mDNS responders on port 5353)
+    pub reuse_port: bool,
+    /// Set the `IPV6_V6ONLY` option of IPv6 sockets, where `Some(false)` makes a socket bound to `[::]`
+    /// accept IPv4 traffic as well; `None` keeps the stack default
+    pub v6_only: Option<bool>,
+    /// The maximum length of the queue of pending incoming TCP connections; `None` keeps the stack default.
+    /// Ignored for UDP sockets
+    pub backlog: Option<u32>,
+    /// Set the `SO_RCVBUF` option, i.e. the size of the receive buffer; `None` keeps the stack default.
+    ///
+    /// Sockets accepted by a TCP listener inherit its buffer sizes, and the TCP window scale is negotiated
+    /// during the handshake, so for TCP this has to be set before listening rather than on the accepted sockets
+    pub recv_buffer_size: Option<usize>,
+    /// Set the `SO_SNDBUF` option, i.e. the size of the send buffer; `None` keeps the stack default
+    pub send_buffer_size: Option<usize>,
+}
+
+impl BindOptions {
+    /// Create a new instance with the default options
+    pub const fn new() -> Self {
+        Self {
+            reuse_addr: false,
+            reuse_port: false,
+            v6_only: None,
+            backlog: None,
+            recv_buffer_size: None,
+            send_buffer_size: None,
+        }
+    }
+
+    /// Enable or disable the `SO_REUSEADDR` option
+    pub const fn with_reuse_addr(self, reuse_addr: bool) -> Self {
+        Self { reuse_addr, ..self }
+    }
+
+    /// Enable or disable the `SO_REUSEPORT` option
+    pub const fn with_reuse_port(self, reuse_port: bool) -> Self {
+        Self { reuse_port, ..self }
+    }
+
+    /// Set the `IPV6_V6ONLY` option
+    pub const fn with_v6_only(self, v6_only: bool) -> Self {
+        Self {
+            v6_only: Some(v6_only),
+            ..self
+        }
+    }
+
+    /// Set the length of the queue of pending incoming TCP connections
+    pub const fn with_backlog(self, backlog: u32) -> Self {
+        Self {
+            backlog: Some(backlog),
+            ..self
+        }
+    }
+
+    /// Set the size of the receive buffer
+    pub const fn with_recv_buffer_size(self, size: usize) -> Self {
+        Self {
+            recv_buffer_size: Some(size),
+            ..self
+        }
+    }
+
+    /// Set the size of the send buffer
+    pub const fn with_send_buffer_size(self, size: usize) -> Self {
+        Self {
+            send_buffer_size: Some(size),
+            ..self
+        }
+    }
+}
@@ -0,0 +1,909 @@
+//! A minimal DNS query engine over UDP, for networking stacks which do not come with a resolver
+//! capable of answering `DnsQuery` queries.
+//!
+//! `dns_query` performs a single query/response exchange over a UDP socket,
+//! while `UdpDns` implements `Dns` and `DnsQuery` on top of any `UdpBind` stack by querying
+//! an upstream DNS server, with a timeout and retransmissions.
+//!
+//! Like the `timeout` module, this is a utility rather than a trait, and might be moved to another crate in future.
+
+use core::cell::Cell;
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use embedded_io_async::ErrorKind;
+
+use crate::{
+    with_timeout, AddrType, Dns, DnsQuery, DnsRecord, DnsRecords, RecordType, UdpBind, UdpReceive,
+    UdpSend, WithTimeoutError,
+};
+
+/// The maximum size of a DNS message over UDP, without EDNS(0)
+pub const DNS_MAX_MESSAGE_SIZE: usize = 512;
+
+/// The standard DNS server port
+pub const DNS_PORT: u16 = 53;
+
+const HEADER_SIZE: usize = 12;
+const MAX_NAME_LEN: usize = 255;
+const MAX_POINTERS: usize = 64;
+
+const CLASS_IN: u16 = 1;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+const RCODE_NAME_ERROR: u8 = 3;
+
+/// Error type for `dns_query` and `UdpDns`
+#[derive(Debug)]
+pub enum DnsQueryError<E> {
+    /// An error occurred while sending or receiving the DNS messages
+    Io(E),
+    /// No response was received in time
+    Timeout,
+    /// The queried name is not a valid domain name
+    InvalidName,
+    /// The response of the server is malformed
+    InvalidResponse,
+    /// The response does not fit in a UDP message and would need to be re-queried over TCP, which is not supported
+    Truncated,
+    /// The domain name does not exist, or has no records of the requested type
+    NotFound,
+    /// The server failed to answer the query, with the provided response code
+    Server(u8),
+    /// The records do not fit in the result buffer
+    BufferOverflow,
+}
+
+impl<E> From<E> for DnsQueryError<E> {
+    fn from(e: E) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl<E> From<WithTimeoutError<DnsQueryError<E>>> for DnsQueryError<E> {
+    fn from(e: WithTimeoutError<DnsQueryError<E>>) -> Self {
+        match e {
+            WithTimeoutError::Error(e) => e,
+            WithTimeoutError::Timeout => Self::Timeout,
+        }
+    }
+}
+
+impl<E> fmt::Display for DnsQueryError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "IO error: {}", e),
+            Self::Timeout => write!(f, "DNS query timed out"),
+            Self::InvalidName => write!(f, "Invalid domain name"),
+            Self::InvalidResponse => write!(f, "Invalid DNS response"),
+            Self::Truncated => write!(f, "Truncated DNS response"),
+            Self::NotFound => write!(f, "Domain name not found"),
+            Self::Server(rcode) => write!(f, "DNS server error, response code {}", rcode),
+            Self::BufferOverflow => write!(f, "Buffer overflow"),
+        }
+    }
+}
+
+impl<E> embedded_io_async::Error for DnsQueryError<E>
+where
+    E: embedded_io_async::Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Io(e) => e.kind(),
+            Self::Timeout => ErrorKind::TimedOut,
+            Self::InvalidName => ErrorKind::InvalidInput,
+            Self::InvalidResponse | Self::Truncated => ErrorKind::InvalidData,
+            Self::NotFound => ErrorKind::NotFound,
+            Self::Server(_) => ErrorKind::Other,
+            Self::BufferOverflow => ErrorKind::OutOfMemory,
+        }
+    }
+}
+
+/// Perform a single DNS query over `socket`, by sending it to `server`.
+///
+/// The records of type `record_type` in the answer are stored in `result`, in the format described
+/// in `DnsQuery::query`, and the length of the stored data is returned.
+/// A response without such records is not an error, and results in a length of 0.
+///
+/// Responses from other peers or with an ID other than `id` are ignored, so the call only returns once the
+/// matching response arrives; wrap it with a timeout if the server might not respond.
+pub async fn dns_query<S>(
+    socket: &mut S,
+    server: SocketAddr,
+    id: u16,
+    name: &str,
+    record_type: RecordType,
+    result: &mut [u8],
+) -> Result<usize, DnsQueryError<S::Error>>
+where
+    S: UdpSend + UdpReceive,
+{
+    let mut buf = [0; DNS_MAX_MESSAGE_SIZE];
+
+    let len = encode_query(&mut buf, id, name, record_type)?;
+
+    // Remember the question, as the response is received in the same buffer
+    let mut question = [0; MAX_NAME_LEN + 4];
+    let question_len = len - HEADER_SIZE;
+    question[..question_len].copy_from_slice(&buf[HEADER_SIZE..len]);
+
+    socket.send(server, &buf[..len]).await?;
+
+    loop {
+        let (len, remote) = socket.receive(&mut buf).await?;
+
+        let response = &buf[..len];
+
+        if remote != server
+            || response.len() < HEADER_SIZE
+            || u16::from_be_bytes([response[0], response[1]]) != id
+            || response[HEADER_SIZE..].get(..question_len) != Some(&question[..question_len])
+        {
+            // Not a response to our query
+            continue;
+        }
+
+        break decode_response(response, question_len, record_type, result);
+    }
+}
+
+fn encode_query<E>(
+    buf: &mut [u8],
+    id: u16,
+    name: &str,
+    record_type: RecordType,
+) -> Result<usize, DnsQueryError<E>> {
+    buf[0..2].copy_from_slice(&id.to_be_bytes());
+    buf[2..4].copy_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    // One question, no answer, authority or additional records
+    buf[4..6].copy_from_slice(&1_u16.to_be_bytes());
+    buf[6..HEADER_SIZE].fill(0);
+
+    let mut offset = HEADER_SIZE;
+
+    let name = name.strip_suffix('.').unwrap_or(name);
+
+    if !name.is_empty() {
+        for label in name.split('.') {
+            if label.is_empty() || label.len() > 63 {
+                Err(DnsQueryError::InvalidName)?;
+            }
+
+            buf[offset] = label.len() as u8;
+            offset += 1;
+
+            buf[offset..offset + label.len()].copy_from_slice(label.as_bytes());
+            offset += label.len();
+
+            if offset - HEADER_SIZE >= MAX_NAME_LEN {
+                Err(DnsQueryError::InvalidName)?;
+            }
+        }
+    }
+
+    buf[offset] = 0;
+    offset += 1;
+
+    buf[offset..offset + 2].copy_from_slice(&record_type.0.to_be_bytes());
+    buf[offset + 2..offset + 4].copy_from_slice(&CLASS_IN.to_be_bytes());
+
+    Ok(offset + 4)
+}
+
+fn decode_response<E>(
+    response: &[u8],
+    question_len: usize,
+    record_type: RecordType,
+    result: &mut [u8],
+) -> Result<usize, DnsQueryError<E>> {
+    let flags = u16::from_be_bytes([response[2], response[3]]);
+
+    if flags & FLAG_RESPONSE == 0 {
+        Err(DnsQueryError::InvalidResponse)?;
+    }
+
+    if flags & FLAG_TRUNCATED != 0 {
+        Err(DnsQueryError::Truncated)?;
+    }
+
+    match (flags & 0x0f) as u8 {
+        0 => (),
+        RCODE_NAME_ERROR => Err(DnsQueryError::NotFound)?,
+        rcode => Err(DnsQueryError::Server(rcode))?,
+    }
+
+    let questions = u16::from_be_bytes([response[4], response[5]]);
+    let answers = u16::from_be_bytes([response[6], response[7]]);
+
+    if questions != 1 {
+        Err(DnsQueryError::InvalidResponse)?;
+    }
+
+    let mut offset = HEADER_SIZE + question_len;
+    let mut len = 0;
+
+    for _ in 0..answers {
+        offset = skip_name(response, offset)?;
+
+        let header = response
+            .get(offset..offset + 10)
+            .ok_or(DnsQueryError::InvalidResponse)?;
+
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let class = u16::from_be_bytes([header[2], header[3]]);
+        let ttl = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let data_len = u16::from_be_bytes([header[8], header[9]]) as usize;
+
+        let data_start = offset + 10;
+        let data_end = data_start + data_len;
+
+        if data_end > response.len() {
+            Err(DnsQueryError::InvalidResponse)?;
+        }
+
+        offset = data_end;
+
+        if rtype != record_type.0 || class != CLASS_IN {
+            // E.g. the CNAME records preceding the A records of an alias
+            continue;
+        }
+
+        let out = result
+            .get_mut(len..len + DnsRecord::HEADER_SIZE)
+            .ok_or(DnsQueryError::BufferOverflow)?;
+        out[..4].copy_from_slice(&ttl.to_be_bytes());
+
+        let start = len + DnsRecord::HEADER_SIZE;
+
+        let copied = copy_data(
+            response,
+            data_start,
+            data_end,
+            record_type,
+            &mut result[start..],
+        )?;
+
+        result[len + 4..start].copy_from_slice(&(copied as u16).to_be_bytes());
+
+        len = start + copied;
+    }
+
+    Ok(len)
+}
+
+/// Copy the data of a record, uncompressing the domain names in it
+fn copy_data<E>(
+    response: &[u8],
+    start: usize,
+    end: usize,
+    record_type: RecordType,
+    out: &mut [u8],
+) -> Result<usize, DnsQueryError<E>> {
+    let prefix_len = match record_type {
+        RecordType::NS | RecordType::CNAME | RecordType::PTR => Some(0),
+        RecordType::MX => Some(2),
+        RecordType::SRV => Some(6),
+        _ => None,
+    };
+
+    let Some(prefix_len) = prefix_len else {
+        let data = &response[start..end];
+
+        out.get_mut(..data.len())
+            .ok_or(DnsQueryError::BufferOverflow)?
+            .copy_from_slice(data);
+
+        return Ok(data.len());
+    };
+
+    if end - start < prefix_len {
+        Err(DnsQueryError::InvalidResponse)?;
+    }
+
+    out.get_mut(..prefix_len)
+        .ok_or(DnsQueryError::BufferOverflow)?
+        .copy_from_slice(&response[start..start + prefix_len]);
+
+    let name_len = copy_name(response, start + prefix_len, &mut out[prefix_len..])?;
+
+    Ok(prefix_len + name_len)
+}
+
+/// Copy the - possibly compressed - name at `offset` into `out`, returning the length of the uncompressed name
+fn copy_name<E>(
+    response: &[u8],
+    mut offset: usize,
+    out: &mut [u8],
+) -> Result<usize, DnsQueryError<E>> {
+    let mut len = 0;
+    let mut pointers = 0;
+
+    loop {
+        let label_len = *response.get(offset).ok_or(DnsQueryError::InvalidResponse)? as usize;
+
+        if label_len & 0xc0 == 0xc0 {
+            let low = *response
+                .get(offset + 1)
+                .ok_or(DnsQueryError::InvalidResponse)? as usize;
+
+            pointers += 1;
+            if pointers > MAX_POINTERS {
+                Err(DnsQueryError::InvalidResponse)?;
+            }
+
+            offset = ((label_len & 0x3f) << 8) | low;
+            continue;
+        }
+
+        if label_len > 63 || len + label_len + 1 > MAX_NAME_LEN {
+            Err(DnsQueryError::InvalidResponse)?;
+        }
+
+        let label = response
+            .get(offset..offset + label_len + 1)
+            .ok_or(DnsQueryError::InvalidResponse)?;
+
+        out.get_mut(len..len + label.len())
+            .ok_or(DnsQueryError::BufferOverflow)?
+            .copy_from_slice(label);
+
+        len += label.len();
+        offset += label.len();
+
+        if label_len == 0 {
+            break Ok(len);
+        }
+    }
+}
+
+/// Skip the - possibly compressed - name at `offset`, returning the offset of the data following it
+fn skip_name<E>(response: &[u8], mut offset: usize) -> Result<usize, DnsQueryError<E>> {
+    loop {
+        let label_len = *response.get(offset).ok_or(DnsQueryError::InvalidResponse)? as usize;
+
+        if label_len & 0xc0 == 0xc0 {
+            break Ok(offset + 2);
+        } else if label_len > 63 {
+            Err(DnsQueryError::InvalidResponse)?;
+        }
+
+        offset += label_len + 1;
+
+        if label_len == 0 {
+            break Ok(offset);
+        }
+    }
+}
+
+/// Implements `Dns` and `DnsQuery` by querying the DNS server at `server` over any `UdpBind` stack.
+///
+/// Each query is sent from a new socket and retransmitted up to `retries` times, in case no response
+/// arrives within `timeout_ms`. Responses which do not fit in a UDP message are reported with
+/// `DnsQueryError::Truncated`, as querying over TCP is not supported.
+pub struct UdpDns<U> {
+    stack: U,
+    server: SocketAddr,
+    timeout_ms: u32,
+    retries: u8,
+    id: Cell<u16>,
+}
+
+impl<U> UdpDns<U>
+where
+    U: UdpBind,
+{
+    /// Create a new `UdpDns` instance.
+    ///
+    /// Parameters:
+    /// - `stack`: The UDP stack over which the queries are sent
+    /// - `server`: The address of the DNS server, usually on port `DNS_PORT`
+    /// - `seed`: A random number from which the IDs of the queries are generated
+    pub const fn new(stack: U, server: SocketAddr, seed: u16) -> Self {
+        Self {
+            stack,
+            server,
+            timeout_ms: 2000,
+            retries: 2,
+            id: Cell::new(if seed == 0 { 1 } else { seed }),
+        }
+    }
+
+    /// Set the time to wait for a response, before retransmitting the query (2 seconds by default)
+    pub const fn with_timeout_ms(mut self, timeout_ms: u32) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Set the number of times a query is retransmitted when no response arrives (2 by default)
+    pub const fn with_retries(mut self, retries: u8) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    fn next_id(&self) -> u16 {
+        // Xorshift, so that the IDs are not trivially predictable
+        let mut id = self.id.get();
+
+        id ^= id << 7;
+        id ^= id >> 9;
+        id ^= id << 8;
+
+        self.id.set(id);
+
+        id
+    }
+
+    async fn query_once(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, DnsQueryError<U::Error>> {
+        let local = match self.server {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        };
+
+        let mut socket = self.stack.bind(local).await?;
+
+        let id = self.next_id();
+
+        with_timeout(
+            self.timeout_ms,
+            dns_query(&mut socket, self.server, id, name, record_type, result),
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    async fn get_host(
+        &self,
+        host: &str,
+        record_type: RecordType,
+    ) -> Result<Option<IpAddr>, DnsQueryError<U::Error>> {
+        let mut result = [0; 256];
+
+        let len = self.query(host, record_type, &mut result).await?;
+
+        Ok(DnsRecords::new(&result[..len]).find_map(|record| record.addr()))
+    }
+}
+
+impl<U> Dns for UdpDns<U>
+where
+    U: UdpBind,
+{
+    type Error = DnsQueryError<U::Error>;
+
+    async fn get_host_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, Self::Error> {
+        if let Ok(addr) = host.parse::<IpAddr>() {
+            return Ok(addr);
+        }
+
+        let addr = match addr_type {
+            AddrType::IPv4 => self.get_host(host, RecordType::A).await?,
+            AddrType::IPv6 => self.get_host(host, RecordType::AAAA).await?,
+            AddrType::Either => match self.get_host(host, RecordType::A).await? {
+                Some(addr) => Some(addr),
+                None => self.get_host(host, RecordType::AAAA).await?,
+            },
+        };
+
+        addr.ok_or(DnsQueryError::NotFound)
+    }
+
+    async fn get_host_by_address(
+        &self,
+        addr: IpAddr,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let mut name = [0; 72];
+        let name_len = reverse_name(addr, &mut name);
+
+        // Safe to unwrap, as the reverse name is ASCII
+        let name = core::str::from_utf8(&name[..name_len]).unwrap();
+
+        let mut records = [0; DnsRecord::HEADER_SIZE + MAX_NAME_LEN];
+        let len = self.query(name, RecordType::PTR, &mut records).await?;
+
+        let host = DnsRecords::new(&records[..len])
+            .find_map(|record| record.name())
+            .ok_or(DnsQueryError::NotFound)?;
+
+        let mut len = 0;
+
+        for label in host.labels() {
+            let sep = if len > 0 { 1 } else { 0 };
+
+            let out = result
+                .get_mut(len..len + sep + label.len())
+                .ok_or(DnsQueryError::BufferOverflow)?;
+
+            out[..sep].fill(b'.');
+            out[sep..].copy_from_slice(label);
+
+            len += out.len();
+        }
+
+        Ok(len)
+    }
+}
+
+impl<U> DnsQuery for UdpDns<U>
+where
+    U: UdpBind,
+{
+    async fn query(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let mut attempt = 0;
+
+        loop {
+            match self.query_once(name, record_type, result).await {
+                Err(DnsQueryError::Timeout) if attempt < self.retries => attempt += 1,
+                other => break other,
+            }
+        }
+    }
+}
+
+/// Render the name used for the reverse (PTR) lookup of `addr` into `buf`, returning its length
+fn reverse_name(addr: IpAddr, buf: &mut [u8; 72]) -> usize {
+    struct Buf<'a>(&'a mut [u8], usize);
+
+    impl fmt::Write for Buf<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let out = self.0.get_mut(self.1..self.1 + s.len()).ok_or(fmt::Error)?;
+            out.copy_from_slice(s.as_bytes());
+            self.1 += s.len();
+
+            Ok(())
+        }
+    }
+
+    let mut out = Buf(buf, 0);
+
+    // Cannot fail, as the buffer fits the longest (IPv6) reverse name
+    let _ = match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, c, d] = addr.octets();
+            fmt::Write::write_fmt(&mut out, format_args!("{d}.{c}.{b}.{a}.in-addr.arpa"))
+        }
+        IpAddr::V6(addr) => addr
+            .octets()
+            .iter()
+            .rev()
+            .try_for_each(|octet| {
+                fmt::Write::write_fmt(
+                    &mut out,
+                    format_args!("{:x}.{:x}.", octet & 0x0f, octet >> 4),
+                )
+            })
+            .and_then(|_| fmt::Write::write_str(&mut out, "ip6.arpa")),
+    };
+
+    out.1
+}
+
+#[cfg(test)]
+mod test {
+    use embassy_futures::block_on;
+
+    use embedded_io_async::ErrorType;
+
+    use super::*;
+
+    const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), DNS_PORT);
+
+    /// A recursive query for the A records of `www.example.com`, with ID 0x1234
+    const QUERY: [u8; 33] = [
+        0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x03, 0x77, 0x77, 0x77, //
+        0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, //
+        0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, //
+        0x01, //
+    ];
+
+    /// The response to `QUERY`: `www.example.com` is a CNAME (TTL 300) of `example.com`,
+    /// which has an A record (TTL 3600) of 93.184.215.14; both names in the answers are compressed
+    const RESPONSE: [u8; 63] = [
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02, //
+        0x00, 0x00, 0x00, 0x00, 0x03, 0x77, 0x77, 0x77, //
+        0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, //
+        0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, //
+        0x01, 0xc0, 0x0c, 0x00, 0x05, 0x00, 0x01, 0x00, //
+        0x00, 0x01, 0x2c, 0x00, 0x02, 0xc0, 0x10, 0xc0, //
+        0x10, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, //
+        0x10, 0x00, 0x04, 0x5d, 0xb8, 0xd7, 0x0e, //
+    ];
+
+    /// A socket which records the sent datagram, and receives the provided datagrams in order
+    struct MockSocket<'a> {
+        responses: &'a [(SocketAddr, &'a [u8])],
+        sent: [u8; DNS_MAX_MESSAGE_SIZE],
+        sent_len: usize,
+    }
+
+    impl<'a> MockSocket<'a> {
+        fn new(responses: &'a [(SocketAddr, &'a [u8])]) -> Self {
+            Self {
+                responses,
+                sent: [0; DNS_MAX_MESSAGE_SIZE],
+                sent_len: 0,
+            }
+        }
+
+        fn sent(&self) -> &[u8] {
+            &self.sent[..self.sent_len]
+        }
+    }
+
+    impl ErrorType for MockSocket<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl UdpSend for MockSocket<'_> {
+        async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+            assert_eq!(remote, SERVER);
+
+            self.sent[..data.len()].copy_from_slice(data);
+            self.sent_len = data.len();
+
+            Ok(())
+        }
+    }
+
+    impl UdpReceive for MockSocket<'_> {
+        async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+            // No more datagrams; a real socket would block until the query times out
+            let ((remote, data), rest) = self.responses.split_first().ok_or(ErrorKind::TimedOut)?;
+
+            self.responses = rest;
+
+            buffer[..data.len()].copy_from_slice(data);
+
+            Ok((data.len(), *remote))
+        }
+    }
+
+    fn query(
+        socket: &mut MockSocket,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, DnsQueryError<ErrorKind>> {
+        block_on(dns_query(socket, SERVER, 0x1234, name, record_type, result))
+    }
+
+    #[test]
+    fn test_query() {
+        let responses = [(SERVER, &RESPONSE[..])];
+        let mut socket = MockSocket::new(&responses);
+        let mut result = [0; 64];
+
+        let len = query(&mut socket, "www.example.com", RecordType::A, &mut result).unwrap();
+
+        assert_eq!(socket.sent(), QUERY);
+
+        // The CNAME record is skipped
+        let mut records = DnsRecords::new(&result[..len]);
+
+        let record = records.next().unwrap();
+        assert_eq!(record.ttl, 3600);
+        assert_eq!(
+            record.addr(),
+            Some(IpAddr::V4(Ipv4Addr::new(93, 184, 215, 14)))
+        );
+
+        assert!(records.next().is_none());
+
+        // A trailing dot is accepted
+        let responses = [(SERVER, &RESPONSE[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert_eq!(
+            query(&mut socket, "www.example.com.", RecordType::A, &mut result).unwrap(),
+            len
+        );
+        assert_eq!(socket.sent(), QUERY);
+    }
+
+    #[test]
+    fn test_query_cname() {
+        let mut response = RESPONSE;
+        response[30] = RecordType::CNAME.0 as u8;
+
+        let responses = [(SERVER, &response[..])];
+        let mut socket = MockSocket::new(&responses);
+        let mut result = [0; 64];
+
+        let len = query(
+            &mut socket,
+            "www.example.com",
+            RecordType::CNAME,
+            &mut result,
+        )
+        .unwrap();
+
+        assert_eq!(socket.sent()[..30], QUERY[..30]);
+        assert_eq!(socket.sent()[30], RecordType::CNAME.0 as u8);
+
+        // The compressed name is stored uncompressed
+        let mut records = DnsRecords::new(&result[..len]);
+
+        let record = records.next().unwrap();
+        assert_eq!(record.ttl, 300);
+        assert_eq!(record.data, b"\x07example\x03com\x00");
+
+        assert!(records.next().is_none());
+
+        // The name does not fit in the result buffer
+        let responses = [(SERVER, &response[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert!(matches!(
+            query(
+                &mut socket,
+                "www.example.com",
+                RecordType::CNAME,
+                &mut result[..10]
+            ),
+            Err(DnsQueryError::BufferOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_query_ignored() {
+        let mut other_id = RESPONSE;
+        other_id[1] = 0x35;
+
+        let mut other_question = RESPONSE;
+        other_question[13] = b'v';
+
+        let other_server = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), DNS_PORT);
+
+        let responses = [
+            (SERVER, &other_id[..]),
+            (SERVER, &other_question[..]),
+            (other_server, &RESPONSE[..]),
+            (SERVER, &RESPONSE[..HEADER_SIZE - 1]),
+        ];
+        let mut socket = MockSocket::new(&responses);
+        let mut result = [0; 64];
+
+        // None of the datagrams is a response to the query
+        assert!(matches!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result),
+            Err(DnsQueryError::Io(ErrorKind::TimedOut))
+        ));
+
+        let responses = [(SERVER, &other_id[..]), (SERVER, &RESPONSE[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert_eq!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result).unwrap(),
+            DnsRecord::HEADER_SIZE + 4
+        );
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let mut result = [0; 64];
+
+        let mut truncated = RESPONSE;
+        truncated[2] |= (FLAG_TRUNCATED >> 8) as u8;
+
+        let responses = [(SERVER, &truncated[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert!(matches!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result),
+            Err(DnsQueryError::Truncated)
+        ));
+
+        let mut nxdomain = RESPONSE;
+        nxdomain[3] |= RCODE_NAME_ERROR;
+        nxdomain[7] = 0;
+
+        let responses = [(SERVER, &nxdomain[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert!(matches!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result),
+            Err(DnsQueryError::NotFound)
+        ));
+
+        // SERVFAIL
+        let mut failed = RESPONSE;
+        failed[3] |= 2;
+
+        let responses = [(SERVER, &failed[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert!(matches!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result),
+            Err(DnsQueryError::Server(2))
+        ));
+
+        // No answers is not an error
+        let mut empty = RESPONSE;
+        empty[7] = 0;
+
+        let responses = [(SERVER, &empty[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert_eq!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result).unwrap(),
+            0
+        );
+
+        // More answers than the response contains
+        let mut short = RESPONSE;
+        short[7] = 3;
+
+        let responses = [(SERVER, &short[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert!(matches!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result),
+            Err(DnsQueryError::InvalidResponse)
+        ));
+
+        // Not a response
+        let mut request = RESPONSE;
+        request[2] &= !((FLAG_RESPONSE >> 8) as u8);
+
+        let responses = [(SERVER, &request[..])];
+        let mut socket = MockSocket::new(&responses);
+
+        assert!(matches!(
+            query(&mut socket, "www.example.com", RecordType::A, &mut result),
+            Err(DnsQueryError::InvalidResponse)
+        ));
+
+        // Invalid names are not sent
+        let long_label = core::str::from_utf8(&[b'a'; 64]).unwrap();
+
+        for name in ["www..example.com", "a.b..", ".com", long_label] {
+            let mut socket = MockSocket::new(&[]);
+
+            assert!(matches!(
+                query(&mut socket, name, RecordType::A, &mut result),
+                Err(DnsQueryError::InvalidName)
+            ));
+            assert!(socket.sent().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_reverse_name() {
+        let mut buf = [0; 72];
+
+        let len = reverse_name(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), &mut buf);
+        assert_eq!(&buf[..len], b"1.2.0.192.in-addr.arpa");
+
+        let len = reverse_name(
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            &mut buf,
+        );
+        assert_eq!(
+            &buf[..len],
+            b"1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+}
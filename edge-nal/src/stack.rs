@@ -1,9 +1,11 @@
 pub use dns::*;
+pub use net::*;
 pub use raw::*;
 pub use tcp::*;
 pub use udp::*;
 
 mod dns;
+mod net;
 mod raw;
 mod tcp;
 mod udp;
@@ -100,3 +100,298 @@ where
         T::get_host_by_address(self, addr, result).await
     }
 }
+
+/// The type of the DNS records to be queried with `DnsQuery`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RecordType(pub u16);
+
+impl RecordType {
+    /// IPv4 host address
+    pub const A: Self = Self(1);
+    /// Authoritative name server
+    pub const NS: Self = Self(2);
+    /// Canonical name of an alias
+    pub const CNAME: Self = Self(5);
+    /// Domain name pointer, as used for reverse lookups
+    pub const PTR: Self = Self(12);
+    /// Mail exchange
+    pub const MX: Self = Self(15);
+    /// Text strings
+    pub const TXT: Self = Self(16);
+    /// IPv6 host address
+    pub const AAAA: Self = Self(28);
+    /// Service location, as per RFC 2782
+    pub const SRV: Self = Self(33);
+}
+
+/// This trait extends `Dns` with queries for arbitrary record types,
+/// as needed for service discovery (e.g. looking up the `_mqtt._tcp` SRV records of a domain).
+///
+/// The records are stored in a caller-provided buffer, in a format which can be
+/// iterated over with `DnsRecords`. Domain names embedded in the data of the
+/// `NS`, `CNAME`, `PTR`, `MX` and `SRV` records are stored uncompressed, so that the
+/// record data can be interpreted without the DNS message it originated from.
+pub trait DnsQuery: Dns {
+    /// Query the records of type `record_type` of the domain `name`.
+    ///
+    /// The records are stored at the beginning of `result`; the length of the stored data is returned,
+    /// i.e. the records can be iterated with `DnsRecords::new(&result[..len])`.
+    ///
+    /// If the buffer is too small to hold all records, an error should be returned.
+    async fn query(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error>;
+
+    /// Query the SRV records of `service` (e.g. `_mqtt._tcp.example.com`)
+    async fn query_srv<'r>(
+        &self,
+        service: &str,
+        result: &'r mut [u8],
+    ) -> Result<SrvRecords<'r>, Self::Error> {
+        let len = self.query(service, RecordType::SRV, result).await?;
+
+        Ok(SrvRecords(DnsRecords::new(&result[..len])))
+    }
+
+    /// Query the TXT records of the domain `name`
+    async fn query_txt<'r>(
+        &self,
+        name: &str,
+        result: &'r mut [u8],
+    ) -> Result<DnsRecords<'r>, Self::Error> {
+        let len = self.query(name, RecordType::TXT, result).await?;
+
+        Ok(DnsRecords::new(&result[..len]))
+    }
+}
+
+impl<T> DnsQuery for &T
+where
+    T: DnsQuery,
+{
+    async fn query(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        T::query(self, name, record_type, result).await
+    }
+}
+
+impl<T> DnsQuery for &mut T
+where
+    T: DnsQuery,
+{
+    async fn query(
+        &self,
+        name: &str,
+        record_type: RecordType,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        T::query(self, name, record_type, result).await
+    }
+}
+
+/// A single record, as returned by `DnsQuery::query`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DnsRecord<'a> {
+    /// The time-to-live of the record, in seconds
+    pub ttl: u32,
+    /// The record data, with any embedded domain names uncompressed
+    pub data: &'a [u8],
+}
+
+impl<'a> DnsRecord<'a> {
+    /// The size of the header preceding the data of each record in the `DnsQuery::query` result buffer:
+    /// the TTL (4 bytes) and the data length (2 bytes), both in network byte order
+    pub const HEADER_SIZE: usize = 6;
+
+    /// Interpret the record data as an IPv4 (`A`) or an IPv6 (`AAAA`) address
+    pub fn addr(&self) -> Option<IpAddr> {
+        if let Ok(octets) = <[u8; 4]>::try_from(self.data) {
+            Some(IpAddr::from(octets))
+        } else if let Ok(octets) = <[u8; 16]>::try_from(self.data) {
+            Some(IpAddr::from(octets))
+        } else {
+            None
+        }
+    }
+
+    /// Interpret the record data as a domain name (`NS`, `CNAME` and `PTR` records)
+    pub fn name(&self) -> Option<DnsName<'a>> {
+        DnsName::new(self.data)
+    }
+
+    /// Interpret the record data as an `SRV` record
+    pub fn srv(&self) -> Option<SrvRecord<'a>> {
+        if self.data.len() < 6 {
+            return None;
+        }
+
+        Some(SrvRecord {
+            priority: u16::from_be_bytes([self.data[0], self.data[1]]),
+            weight: u16::from_be_bytes([self.data[2], self.data[3]]),
+            port: u16::from_be_bytes([self.data[4], self.data[5]]),
+            target: DnsName::new(&self.data[6..])?,
+        })
+    }
+
+    /// Interpret the record data as the character strings of a `TXT` record
+    pub fn txt(&self) -> TxtStrings<'a> {
+        TxtStrings(self.data)
+    }
+}
+
+/// An iterator over the records stored by `DnsQuery::query`
+#[derive(Clone, Debug)]
+pub struct DnsRecords<'a>(&'a [u8]);
+
+impl<'a> DnsRecords<'a> {
+    /// Create an iterator over the records in `data`, which is the part of the
+    /// `DnsQuery::query` result buffer that was filled in
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+}
+
+impl<'a> Iterator for DnsRecords<'a> {
+    type Item = DnsRecord<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.len() < DnsRecord::HEADER_SIZE {
+            return None;
+        }
+
+        let ttl = u32::from_be_bytes([self.0[0], self.0[1], self.0[2], self.0[3]]);
+        let len = u16::from_be_bytes([self.0[4], self.0[5]]) as usize;
+
+        let Some(data) = self
+            .0
+            .get(DnsRecord::HEADER_SIZE..DnsRecord::HEADER_SIZE + len)
+        else {
+            self.0 = &[];
+            return None;
+        };
+
+        self.0 = &self.0[DnsRecord::HEADER_SIZE + len..];
+
+        Some(DnsRecord { ttl, data })
+    }
+}
+
+/// An iterator over the records returned by `DnsQuery::query_srv`
+#[derive(Clone, Debug)]
+pub struct SrvRecords<'a>(DnsRecords<'a>);
+
+impl<'a> Iterator for SrvRecords<'a> {
+    type Item = SrvRecord<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(|record| record.srv())
+    }
+}
+
+/// An `SRV` record, as per RFC 2782
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SrvRecord<'a> {
+    /// The priority of the target host; lower values are preferred
+    pub priority: u16,
+    /// The relative weight of the targets with the same priority
+    pub weight: u16,
+    /// The port of the service on the target host
+    pub port: u16,
+    /// The target host; `.` means that the service is not available in the domain
+    pub target: DnsName<'a>,
+}
+
+/// An iterator over the character strings of a `TXT` record
+#[derive(Clone, Debug)]
+pub struct TxtStrings<'a>(&'a [u8]);
+
+impl<'a> Iterator for TxtStrings<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (len, rest) = self.0.split_first()?;
+
+        let Some(string) = rest.get(..*len as usize) else {
+            self.0 = &[];
+            return None;
+        };
+
+        self.0 = &rest[*len as usize..];
+
+        Some(string)
+    }
+}
+
+/// An uncompressed domain name, in the wire format (i.e. a sequence of length-prefixed labels,
+/// terminated by an empty label)
+///
+/// The `Display` implementation renders the name in the usual dotted notation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DnsName<'a>(&'a [u8]);
+
+impl<'a> DnsName<'a> {
+    /// Create a name from its wire format, if `data` is a valid, uncompressed domain name
+    /// (possibly followed by other data, which is ignored)
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let mut offset = 0;
+
+        loop {
+            let len = *data.get(offset)? as usize;
+
+            if len > 63 {
+                return None;
+            }
+
+            offset += len + 1;
+
+            if len == 0 {
+                break (offset <= 255 && offset <= data.len()).then(|| Self(&data[..offset]));
+            }
+        }
+    }
+
+    /// The name in the wire format
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// An iterator over the labels of the name, excluding the terminating empty label
+    pub fn labels(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        TxtStrings(self.0).take_while(|label| !label.is_empty())
+    }
+}
+
+impl core::fmt::Display for DnsName<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut empty = true;
+
+        for label in self.labels() {
+            if !empty {
+                write!(f, ".")?;
+            }
+
+            for byte in label {
+                if byte.is_ascii_graphic() && *byte != b'.' && *byte != b'\\' {
+                    write!(f, "{}", *byte as char)?;
+                } else {
+                    write!(f, "\\{:03}", byte)?;
+                }
+            }
+
+            empty = false;
+        }
+
+        if empty {
+            write!(f, ".")?;
+        }
+
+        Ok(())
+    }
+}
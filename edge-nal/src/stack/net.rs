@@ -0,0 +1,15 @@
+//! A convenience trait bundling the factory traits of a full TCP/IP stack
+
+use crate::{Dns, TcpBind, TcpConnect, UdpBind, UdpConnect};
+
+/// A TCP/IP stack capable of connecting to and accepting TCP connections,
+/// binding and connecting UDP sockets, and resolving host names.
+///
+/// Functions which need more than one of these capabilities can take a single `NetStack`
+/// generic parameter rather than a bound for each of the individual factory traits.
+///
+/// The trait is blanket-implemented for all types implementing the individual traits
+/// (e.g. the `Stack` type of `edge-nal-std`), and is not meant to be implemented directly.
+pub trait NetStack: TcpConnect + TcpBind + UdpBind + UdpConnect + Dns {}
+
+impl<T> NetStack for T where T: TcpConnect + TcpBind + UdpBind + UdpConnect + Dns {}
@@ -2,9 +2,9 @@
 
 use core::net::SocketAddr;
 
-use embedded_io_async::{Error, ErrorType, Read, Write};
+use embedded_io_async::{Error, ErrorType, Read};
 
-use crate::{Readable, TcpShutdown};
+use crate::{BindOptions, Readable, TcpShutdown, WriteVectored};
 
 /// This trait is implemented by TCP sockets that can be split into separate `send` and `receive` halves that can operate
 /// independently from each other (i.e., a full-duplex connection).
@@ -12,7 +12,7 @@ pub trait TcpSplit: ErrorType {
     type Read<'a>: Read<Error = Self::Error> + Readable<Error = Self::Error>
     where
         Self: 'a;
-    type Write<'a>: Write<Error = Self::Error>
+    type Write<'a>: WriteVectored<Error = Self::Error>
     where
         Self: 'a;
 
@@ -44,7 +44,7 @@ pub trait TcpConnect {
 
     /// The socket type returned by the factory
     type Socket<'a>: Read<Error = Self::Error>
-        + Write<Error = Self::Error>
+        + WriteVectored<Error = Self::Error>
         + Readable<Error = Self::Error>
         + TcpSplit<Error = Self::Error>
         + TcpShutdown<Error = Self::Error>
@@ -71,6 +71,19 @@ pub trait TcpBind {
     /// implementation, that does the actual binding.
     /// Platforms that do not maintain internal acceptor queue (Embassy networking stack and `smoltcp`) are such examples.
     async fn bind(&self, local: SocketAddr) -> Result<Self::Accept<'_>, Self::Error>;
+    /// Bind to a local socket address, applying the provided options to the socket before binding it
+    ///
+    /// The default implementation ignores the options, which is what stacks without the notion of
+    /// address sharing between sockets and processes (i.e. the Embassy networking stack) should do.
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Accept<'_>, Self::Error> {
+        let _ = options;
+
+        self.bind(local).await
+    }
 }
 
 /// This is a factory trait for accepting incoming connections on server-side TCP sockets
@@ -80,7 +93,7 @@ pub trait TcpAccept {
 
     /// The socket type returned by the factory
     type Socket<'a>: Read<Error = Self::Error>
-        + Write<Error = Self::Error>
+        + WriteVectored<Error = Self::Error>
         + Readable<Error = Self::Error>
         + TcpSplit<Error = Self::Error>
         + TcpShutdown<Error = Self::Error>
@@ -138,6 +151,14 @@ where
     async fn bind(&self, local: SocketAddr) -> Result<Self::Accept<'_>, Self::Error> {
         (*self).bind(local).await
     }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Accept<'_>, Self::Error> {
+        (*self).bind_with_options(local, options).await
+    }
 }
 
 impl<T> TcpBind for &mut T
@@ -154,6 +175,14 @@ where
     async fn bind(&self, local: SocketAddr) -> Result<Self::Accept<'_>, Self::Error> {
         (**self).bind(local).await
     }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Accept<'_>, Self::Error> {
+        (**self).bind_with_options(local, options).await
+    }
 }
 
 impl<T> TcpAccept for &T
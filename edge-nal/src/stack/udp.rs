@@ -5,7 +5,7 @@ use core::net::SocketAddr;
 use embedded_io_async::ErrorType;
 
 use crate::udp::{UdpReceive, UdpSend};
-use crate::{MulticastV4, MulticastV6, Readable};
+use crate::{BindOptions, MulticastV4, MulticastV6, Readable};
 
 /// This trait is implemented by UDP sockets that can be split into separate `send` and `receive` halves that can operate
 /// independently from each other (i.e., a full-duplex connection)
@@ -38,6 +38,17 @@ where
     }
 }
 
+/// This trait is implemented by UDP sockets that can be split into owned `send` and `receive` halves.
+///
+/// Unlike the halves returned by `UdpSplit`, these do not borrow from the socket, and can therefore be moved
+/// into separate tasks (e.g. a receive loop running in one task, and a transmit path running in another).
+pub trait UdpSplitOwned: ErrorType {
+    type Receive: UdpReceive<Error = Self::Error> + Readable<Error = Self::Error>;
+    type Send: UdpSend<Error = Self::Error>;
+
+    fn into_split(self) -> (Self::Receive, Self::Send);
+}
+
 /// This is a factory trait for creating connected UDP sockets
 pub trait UdpConnect {
     /// Error type returned on socket creation failure
@@ -78,6 +89,19 @@ pub trait UdpBind {
 
     /// Bind to a local socket address
     async fn bind(&self, local: SocketAddr) -> Result<Self::Socket<'_>, Self::Error>;
+    /// Bind to a local socket address, applying the provided options to the socket before binding it
+    ///
+    /// The default implementation ignores the options, which is what stacks without the notion of
+    /// address sharing between sockets and processes (i.e. the Embassy networking stack) should do.
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        let _ = options;
+
+        self.bind(local).await
+    }
 }
 
 impl<T> UdpConnect for &T
@@ -131,6 +155,14 @@ where
     async fn bind(&self, local: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
         (*self).bind(local).await
     }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        (*self).bind_with_options(local, options).await
+    }
 }
 
 impl<T> UdpBind for &mut T
@@ -146,4 +178,12 @@ where
     async fn bind(&self, local: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
         (**self).bind(local).await
     }
+
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        (**self).bind_with_options(local, options).await
+    }
 }
@@ -0,0 +1,55 @@
+use core::net::SocketAddr;
+use core::time::Duration;
+
+use embedded_io_async::ErrorType;
+
+/// A snapshot of the addresses and the traffic of a socket, as returned by `Stats::stats`
+///
+/// Stacks report the values they do not track as `None`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SocketStats {
+    /// The local address the socket is bound to
+    pub local: Option<SocketAddr>,
+    /// The address of the peer the socket is connected to
+    pub remote: Option<SocketAddr>,
+    /// The number of bytes sent by the socket
+    ///
+    /// Depending on the stack, these are either the bytes written to the socket, or the bytes acknowledged by the peer.
+    pub bytes_sent: Option<u64>,
+    /// The number of bytes received by the socket
+    pub bytes_received: Option<u64>,
+    /// The smoothed round-trip time to the peer
+    pub rtt: Option<Duration>,
+    /// The number of bytes written to the socket, which are not yet sent or - with TCP - not yet acknowledged by the peer
+    pub send_queue: Option<usize>,
+}
+
+impl SocketStats {
+    /// Create a new instance with all values unknown
+    pub const fn new() -> Self {
+        Self {
+            local: None,
+            remote: None,
+            bytes_sent: None,
+            bytes_received: None,
+            rtt: None,
+            send_queue: None,
+        }
+    }
+}
+
+/// This trait is implemented by TCP and UDP sockets which can report their addresses and traffic,
+/// i.e. for displaying the health of each connection in a device dashboard.
+pub trait Stats: ErrorType {
+    /// Return a snapshot of the addresses and the traffic of the socket
+    fn stats(&self) -> Result<SocketStats, Self::Error>;
+}
+
+impl<T> Stats for &mut T
+where
+    T: Stats,
+{
+    fn stats(&self) -> Result<SocketStats, Self::Error> {
+        (**self).stats()
+    }
+}
@@ -2,6 +2,8 @@
 
 use embedded_io_async::ErrorType;
 
+use crate::{with_timeout, WithTimeoutError};
+
 /// Enum representing the different ways to close a TCP socket
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Close {
@@ -39,12 +41,38 @@ pub trait TcpShutdown: ErrorType {
     ///
     /// This method will not wait forever, because the RST packet is not ACKed by the peer.
     ///
-    /// Note that on certain platforms (STD for example) this method might be a no-op
+    /// Note that on certain platforms this method might be a no-op
     /// as the connection there is automatically aborted when the socket is dropped.
     ///
     /// Also note that calling this function multiple times may result in different behavior,
     /// depending on the platform.
     async fn abort(&mut self) -> Result<(), Self::Error>;
+
+    /// Gracefully close the connection, giving up and aborting it once `timeout_ms` elapses.
+    ///
+    /// Closes both halves of the socket as per `close(Close::Both)`, i.e. flushes the pending data, sends a FIN packet
+    /// and waits for the FIN packet of the peer. If that does not complete within `timeout_ms` milliseconds,
+    /// or if it fails, the connection is aborted with `abort`.
+    ///
+    /// Returns `true` if the connection was closed gracefully, and `false` if it had to be aborted
+    /// because the timeout elapsed. If the graceful close fails, its error is returned.
+    async fn close_with_timeout(&mut self, timeout_ms: u32) -> Result<bool, Self::Error> {
+        let result = with_timeout(timeout_ms, self.close(Close::Both)).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(WithTimeoutError::Timeout) => {
+                self.abort().await?;
+
+                Ok(false)
+            }
+            Err(WithTimeoutError::Error(e)) => {
+                let _ = self.abort().await;
+
+                Err(e)
+            }
+        }
+    }
 }
 
 impl<T> TcpShutdown for &mut T
@@ -58,4 +86,8 @@ where
     async fn abort(&mut self) -> Result<(), Self::Error> {
         (**self).abort().await
     }
+
+    async fn close_with_timeout(&mut self, timeout_ms: u32) -> Result<bool, Self::Error> {
+        (**self).close_with_timeout(timeout_ms).await
+    }
 }
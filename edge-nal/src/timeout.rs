@@ -10,13 +10,17 @@
 use core::{
     fmt::{self, Display},
     future::Future,
-    net::SocketAddr,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 
 use embassy_time::Duration;
 use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
 
-use crate::{Readable, TcpAccept, TcpConnect, TcpShutdown, TcpSplit};
+use crate::{
+    BindOptions, MulticastV4, MulticastV6, NetError, NetErrorKind, Readable, TcpAccept, TcpBind,
+    TcpConnect, TcpShutdown, TcpSplit, UdpBind, UdpConnect, UdpReceive, UdpSend, UdpSplit,
+    WriteVectored,
+};
 
 /// Error type for the `with_timeout` function and `WithTimeout` struct.
 #[derive(Debug)]
@@ -57,6 +61,18 @@ where
     }
 }
 
+impl<E> NetError for WithTimeoutError<E>
+where
+    E: NetError,
+{
+    fn net_kind(&self) -> NetErrorKind {
+        match self {
+            Self::Error(e) => e.net_kind(),
+            Self::Timeout => NetErrorKind::TimedOut,
+        }
+    }
+}
+
 /// Run a fallible future with a timeout.
 ///
 /// A future is a fallible future if it resolves to a `Result<T, E>`.
@@ -79,13 +95,25 @@ where
 /// The operations decorated with a timeout are the ones offered via the following traits:
 /// - `embedded_io_async::Read`
 /// - `embedded_io_async::Write`
+/// - `WriteVectored`
 /// - `Readable`
 /// - `TcpConnect`
+/// - `TcpBind`
 /// - `TcpShutdown`
+/// - `UdpConnect`
+/// - `UdpBind`
+/// - `UdpReceive`
+/// - `UdpSend`
+/// - `MulticastV4`
+/// - `MulticastV6`
+///
+/// The sockets returned by the decorated factory traits, as well as the halves of the split sockets,
+/// are readily wrapped with the same timeout.
 ///
 /// Additionally, wrapping with `WithTimeout` an IO type that implements `TcpAccept` will result
 /// in a `TcpAccept` implementation that - while waiting potentially indefinitely for an incoming
 /// connection - will return a connected socket readily wrapped with a timeout.
+/// Use `WithTimeout::accept_timeout` where waiting for the incoming connection should time out as well.
 pub struct WithTimeout<T>(T, u32);
 
 impl<T> WithTimeout<T> {
@@ -119,6 +147,21 @@ impl<T> WithTimeout<T> {
     }
 }
 
+impl<T> WithTimeout<T>
+where
+    T: TcpAccept,
+{
+    /// Accept an incoming connection, like `TcpAccept::accept` does, but with the wait for
+    /// the connection bounded by the timeout as well.
+    pub async fn accept_timeout(
+        &self,
+    ) -> Result<(SocketAddr, WithTimeout<T::Socket<'_>>), WithTimeoutError<T::Error>> {
+        with_timeout(self.1, self.0.accept())
+            .await
+            .map(|(addr, socket)| (addr, WithTimeout::new(self.1, socket)))
+    }
+}
+
 impl<T> ErrorType for WithTimeout<T>
 where
     T: ErrorType,
@@ -148,6 +191,15 @@ where
     }
 }
 
+impl<T> WriteVectored for WithTimeout<T>
+where
+    T: WriteVectored,
+{
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.write_all_vectored(bufs)).await
+    }
+}
+
 impl<T> TcpConnect for WithTimeout<T>
 where
     T: TcpConnect,
@@ -226,6 +278,186 @@ where
     }
 }
 
+impl<T> TcpBind for WithTimeout<T>
+where
+    T: TcpBind,
+{
+    type Error = WithTimeoutError<T::Error>;
+
+    type Accept<'a>
+        = WithTimeout<T::Accept<'a>>
+    where
+        Self: 'a;
+
+    async fn bind(&self, local: SocketAddr) -> Result<Self::Accept<'_>, Self::Error> {
+        with_timeout(self.1, self.0.bind(local))
+            .await
+            .map(|a| WithTimeout::new(self.1, a))
+    }
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Accept<'_>, Self::Error> {
+        with_timeout(self.1, self.0.bind_with_options(local, options))
+            .await
+            .map(|a| WithTimeout::new(self.1, a))
+    }
+}
+
+impl<T> UdpReceive for WithTimeout<T>
+where
+    T: UdpReceive,
+{
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        with_timeout(self.1, self.0.receive(buffer)).await
+    }
+}
+
+impl<T> UdpSend for WithTimeout<T>
+where
+    T: UdpSend,
+{
+    async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.send(remote, data)).await
+    }
+}
+
+impl<T> UdpSplit for WithTimeout<T>
+where
+    T: UdpSplit,
+{
+    type Receive<'a>
+        = WithTimeout<T::Receive<'a>>
+    where
+        Self: 'a;
+
+    type Send<'a>
+        = WithTimeout<T::Send<'a>>
+    where
+        Self: 'a;
+
+    fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
+        let (r, s) = self.0.split();
+        (WithTimeout::new(self.1, r), WithTimeout::new(self.1, s))
+    }
+}
+
+impl<T> MulticastV4 for WithTimeout<T>
+where
+    T: MulticastV4,
+{
+    async fn join_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.join_v4(multicast_addr, interface)).await
+    }
+
+    async fn leave_v4(
+        &mut self,
+        multicast_addr: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.leave_v4(multicast_addr, interface)).await
+    }
+
+    async fn set_multicast_ttl_v4(&mut self, ttl: u8) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.set_multicast_ttl_v4(ttl)).await
+    }
+
+    async fn set_multicast_loop_v4(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.set_multicast_loop_v4(enabled)).await
+    }
+
+    async fn set_multicast_if_v4(&mut self, interface: Ipv4Addr) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.set_multicast_if_v4(interface)).await
+    }
+}
+
+impl<T> MulticastV6 for WithTimeout<T>
+where
+    T: MulticastV6,
+{
+    async fn join_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.join_v6(multicast_addr, interface)).await
+    }
+
+    async fn leave_v6(
+        &mut self,
+        multicast_addr: Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.leave_v6(multicast_addr, interface)).await
+    }
+
+    async fn set_multicast_hops_v6(&mut self, hops: u8) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.set_multicast_hops_v6(hops)).await
+    }
+
+    async fn set_multicast_loop_v6(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.set_multicast_loop_v6(enabled)).await
+    }
+
+    async fn set_multicast_if_v6(&mut self, interface: u32) -> Result<(), Self::Error> {
+        with_timeout(self.1, self.0.set_multicast_if_v6(interface)).await
+    }
+}
+
+impl<T> UdpConnect for WithTimeout<T>
+where
+    T: UdpConnect,
+{
+    type Error = WithTimeoutError<T::Error>;
+
+    type Socket<'a>
+        = WithTimeout<T::Socket<'a>>
+    where
+        Self: 'a;
+
+    async fn connect(
+        &self,
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        with_timeout(self.1, self.0.connect(local, remote))
+            .await
+            .map(|s| WithTimeout::new(self.1, s))
+    }
+}
+
+impl<T> UdpBind for WithTimeout<T>
+where
+    T: UdpBind,
+{
+    type Error = WithTimeoutError<T::Error>;
+
+    type Socket<'a>
+        = WithTimeout<T::Socket<'a>>
+    where
+        Self: 'a;
+
+    async fn bind(&self, local: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
+        with_timeout(self.1, self.0.bind(local))
+            .await
+            .map(|s| WithTimeout::new(self.1, s))
+    }
+    async fn bind_with_options(
+        &self,
+        local: SocketAddr,
+        options: &BindOptions,
+    ) -> Result<Self::Socket<'_>, Self::Error> {
+        with_timeout(self.1, self.0.bind_with_options(local, options))
+            .await
+            .map(|s| WithTimeout::new(self.1, s))
+    }
+}
+
 fn map_result<T, E>(
     result: Result<Result<T, E>, embassy_time::TimeoutError>,
 ) -> Result<T, WithTimeoutError<E>> {
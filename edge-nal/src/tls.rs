@@ -0,0 +1,50 @@
+//! A trait for upgrading established plaintext connections to TLS
+
+use embedded_io_async::{Error, Read, Write};
+
+use crate::WriteVectored;
+
+/// This is a factory trait for upgrading an already established plaintext connection to TLS mid-stream,
+/// as necessary for protocols which negotiate TLS in-band, rather than at connect time:
+/// - SMTP, IMAP and XMPP with their `STARTTLS` commands
+/// - HTTP `CONNECT` tunnels through a proxy, where the TLS session with the origin server starts
+///   once the proxy has established the tunnel
+///
+/// The TLS session runs over the plaintext socket `S`, which is consumed by the upgrade.
+/// Implementations would typically borrow from `self` the buffers for the TLS records.
+pub trait TlsUpgrade<S>
+where
+    S: Read + Write,
+{
+    /// Error type returned on handshake failure, as well as by the upgraded socket
+    type Error: Error;
+
+    /// The TLS socket type returned by the factory
+    type Socket<'a>: Read<Error = Self::Error> + WriteVectored<Error = Self::Error>
+    where
+        Self: 'a;
+
+    /// Perform the TLS client handshake over `socket`.
+    ///
+    /// `server_name` is the name of the server as used for Server Name Indication and for the verification
+    /// of its certificate. Note that with `HTTP CONNECT` tunnels this is the name of the origin server,
+    /// not of the proxy.
+    async fn upgrade(&self, socket: S, server_name: &str) -> Result<Self::Socket<'_>, Self::Error>;
+}
+
+impl<T, S> TlsUpgrade<S> for &T
+where
+    T: TlsUpgrade<S>,
+    S: Read + Write,
+{
+    type Error = T::Error;
+
+    type Socket<'a>
+        = T::Socket<'a>
+    where
+        Self: 'a;
+
+    async fn upgrade(&self, socket: S, server_name: &str) -> Result<Self::Socket<'_>, Self::Error> {
+        T::upgrade(self, socket, server_name).await
+    }
+}
@@ -1,6 +1,6 @@
 //! Traits for modeling UDP sending/receiving functionality on embedded devices
 
-use core::net::SocketAddr;
+use core::net::{IpAddr, SocketAddr};
 
 use embedded_io_async::ErrorType;
 
@@ -17,14 +17,75 @@ use embedded_io_async::ErrorType;
 pub trait UdpReceive: ErrorType {
     /// Receive a datagram into the provided buffer.
     ///
-    /// If the received datagram exceeds the buffer's length, it is received regardless, and the
-    /// remaining bytes are discarded. The full datagram size is still indicated in the result,
-    /// allowing the recipient to detect that truncation.
+    /// If the received datagram exceeds the buffer's length, depending on the stack it is either
+    /// received regardless - with the remaining bytes discarded - or dropped with an error of kind
+    /// `NetErrorKind::Truncated`. Use `UdpReceiveInfo` or `UdpPeek` where the truncation needs to be detected.
     ///
     /// The remote addresses is given in the result along with the number of bytes.
     async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error>;
 }
 
+/// Information about the local end of a received datagram, as returned by `UdpReceiveInfo::receive_info`
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct UdpReceiveMeta {
+    /// The local (destination) address of the datagram, if known
+    ///
+    /// For datagrams which were not unicast, this is the multicast or broadcast address they were sent to.
+    pub local: Option<IpAddr>,
+    /// The index of the interface through which the datagram was received, if known
+    pub interface: Option<u32>,
+    /// `true` if the datagram did not fit in the supplied buffer and its remaining bytes were discarded
+    pub truncated: bool,
+}
+
+impl UdpReceiveMeta {
+    /// Create a new instance, where neither the local address, nor the interface are known
+    pub const fn new() -> Self {
+        Self {
+            local: None,
+            interface: None,
+            truncated: false,
+        }
+    }
+
+    /// Return `true` if the datagram is known to have been sent to a multicast address
+    pub fn is_multicast(&self) -> bool {
+        matches!(self.local, Some(local) if local.is_multicast())
+    }
+}
+
+/// This trait is implemented by UDP sockets which - in addition to the remote address - can report
+/// the local destination address and the receiving interface of each received datagram
+/// (i.e. by using `IP_PKTINFO` / `IPV6_RECVPKTINFO` on the BSD sockets stacks).
+///
+/// Useful for protocols like mDNS, which need to know whether a datagram was sent to the multicast group
+/// or directly to the host, and through which interface it arrived.
+pub trait UdpReceiveInfo: UdpReceive {
+    /// Receive a datagram into the provided buffer, exactly like `UdpReceive::receive` does.
+    ///
+    /// In addition to the number of bytes and the remote address, the result contains the local end of the datagram.
+    /// Stacks that cannot tell the local address or the interface of a particular datagram report these as `None`.
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error>;
+}
+
+/// This trait is implemented by UDP sockets which can peek at the next datagram without removing it from the receive queue.
+///
+/// Useful for protocols like DNS, where the consumer might want to learn the size of the next datagram
+/// before receiving it, rather than silently parsing a datagram which was cut off to the size of its buffer.
+pub trait UdpPeek: UdpReceive {
+    /// Peek at the next datagram by copying its beginning into the provided buffer.
+    ///
+    /// A subsequent `receive` call returns the same datagram.
+    ///
+    /// The result contains the full size of the datagram, which might exceed the buffer's length.
+    /// Stacks which cannot tell the full size of a datagram which does not fit in the buffer
+    /// return an error of kind `NetErrorKind::Truncated` instead.
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error>;
+}
+
 /// This trait is implemented by UDP sockets and models their datagram sending functionality.
 ///
 /// The socket it represents might be either bound (has a local IP address, port and interface) or
@@ -42,6 +103,21 @@ pub trait UdpSend: ErrorType {
     async fn send(&mut self, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error>;
 }
 
+/// This trait is implemented by bound UDP sockets which can be connected to a fixed peer - and disconnected again - after the fact.
+///
+/// While connected, datagrams are sent to the peer regardless of the remote address passed to `UdpSend::send`,
+/// and only datagrams coming from the peer are received.
+///
+/// Useful for protocols like DHCP, where the client switches between broadcast traffic (discovery) and unicast traffic
+/// with its server (lease renewal), and would otherwise need to re-bind the socket in-between.
+pub trait UdpConnectPeer: ErrorType {
+    /// Connect the socket to `remote`, replacing the peer the socket is already connected to, if any
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error>;
+
+    /// Disconnect the socket from its peer, so that it can send to and receive from any peer again
+    async fn disconnect(&mut self) -> Result<(), Self::Error>;
+}
+
 pub trait UdpSocket: UdpReceive + UdpSend {}
 
 impl<T> UdpReceive for &mut T
@@ -53,6 +129,27 @@ where
     }
 }
 
+impl<T> UdpReceiveInfo for &mut T
+where
+    T: UdpReceiveInfo,
+{
+    async fn receive_info(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, UdpReceiveMeta), Self::Error> {
+        (**self).receive_info(buffer).await
+    }
+}
+
+impl<T> UdpPeek for &mut T
+where
+    T: UdpPeek,
+{
+    async fn peek(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        (**self).peek(buffer).await
+    }
+}
+
 impl<T> UdpSend for &mut T
 where
     T: UdpSend,
@@ -61,3 +158,16 @@ where
         (**self).send(remote, data).await
     }
 }
+
+impl<T> UdpConnectPeer for &mut T
+where
+    T: UdpConnectPeer,
+{
+    async fn connect(&mut self, remote: SocketAddr) -> Result<(), Self::Error> {
+        (**self).connect(remote).await
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Self::Error> {
+        (**self).disconnect().await
+    }
+}
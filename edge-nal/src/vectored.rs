@@ -0,0 +1,123 @@
+use embedded_io_async::Write;
+
+/// The size of the stack buffer in which `write_all_coalesced` coalesces the slices
+const COALESCE_BUF_SIZE: usize = 128;
+
+/// Writing from multiple slices in one call
+///
+/// Protocols like HTTP tend to assemble their messages out of many small pieces (header names, values,
+/// separators), which - when written one by one - might end up in one TCP segment each on some stacks.
+///
+/// Sockets implement the trait by handing all slices to the stack at once (i.e. with `writev` or by copying
+/// them straight into the TX buffer of the socket). The default implementation coalesces the slices
+/// with `write_all_coalesced`, so other `Write` types can opt in with an empty `impl`.
+pub trait WriteVectored: Write {
+    /// Write all bytes of the provided slices, as if they were concatenated
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        write_all_coalesced(self, bufs).await
+    }
+}
+
+impl<T> WriteVectored for &mut T
+where
+    T: WriteVectored + ?Sized,
+{
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        (**self).write_all_vectored(bufs).await
+    }
+}
+
+/// Write all bytes of the provided slices to any `Write` type, as if they were concatenated
+///
+/// The slices are coalesced in a small stack buffer which is written once full.
+/// Slices which are too large to be buffered are written directly.
+pub async fn write_all_coalesced<W>(write: &mut W, bufs: &[&[u8]]) -> Result<(), W::Error>
+where
+    W: Write + ?Sized,
+{
+    let mut cursor = VectoredCursor::new(bufs);
+    let mut buf = [0; COALESCE_BUF_SIZE];
+
+    while let Some(slice) = cursor.slices().next() {
+        if slice.len() >= buf.len() {
+            write.write_all(slice).await?;
+            cursor.advance(slice.len());
+        } else {
+            let len = cursor.copy_to(&mut buf);
+
+            write.write_all(&buf[..len]).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The part of the slices passed to `WriteVectored::write_all_vectored` which is not written yet,
+/// for implementations which write them in pieces
+#[derive(Clone, Debug)]
+pub struct VectoredCursor<'a> {
+    bufs: &'a [&'a [u8]],
+    offset: usize,
+}
+
+impl<'a> VectoredCursor<'a> {
+    /// Create a new cursor at the start of the provided slices
+    pub const fn new(bufs: &'a [&'a [u8]]) -> Self {
+        Self { bufs, offset: 0 }
+    }
+
+    /// Return `true` if all bytes were written
+    pub fn is_empty(&self) -> bool {
+        self.slices().next().is_none()
+    }
+
+    /// Return the non-empty slices which are not written yet, the first one without the bytes already written
+    pub fn slices(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let offset = self.offset;
+
+        self.bufs
+            .iter()
+            .enumerate()
+            .map(move |(index, buf)| if index == 0 { &buf[offset..] } else { buf })
+            .filter(|buf| !buf.is_empty())
+    }
+
+    /// Mark the next `len` bytes as written
+    pub fn advance(&mut self, mut len: usize) {
+        while let Some((first, rest)) = self.bufs.split_first() {
+            let remaining = first.len() - self.offset;
+
+            if len < remaining {
+                self.offset += len;
+                break;
+            }
+
+            len -= remaining;
+
+            self.bufs = rest;
+            self.offset = 0;
+        }
+    }
+
+    /// Copy as many of the bytes which are not written yet as fit into `buf`, and mark them as written
+    ///
+    /// Returns the number of bytes copied.
+    pub fn copy_to(&mut self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+
+        for slice in self.slices() {
+            let chunk = core::cmp::min(slice.len(), buf.len() - len);
+
+            buf[len..len + chunk].copy_from_slice(&slice[..chunk]);
+            len += chunk;
+
+            if len == buf.len() {
+                break;
+            }
+        }
+
+        self.advance(len);
+
+        len
+    }
+}
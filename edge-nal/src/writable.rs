@@ -0,0 +1,16 @@
+use embedded_io_async::ErrorType;
+
+/// The counterpart of `Readable`: allows waiting until the socket can accept more data to be sent,
+/// so that send loops can apply backpressure rather than retrying a send which would block.
+pub trait Writable: ErrorType {
+    async fn writable(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<T> Writable for &mut T
+where
+    T: Writable,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        (**self).writable().await
+    }
+}
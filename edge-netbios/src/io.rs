@@ -0,0 +1,200 @@
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use edge_nal::{UdpBind, UdpReceive, UdpSend};
+
+use embassy_time::{with_timeout, Duration, Instant};
+
+use log::{debug, info, warn};
+
+use crate as netbios;
+use crate::{compose_registration, compose_release, is_conflict, reply, Host, PORT, SUFFIXES};
+
+/// Socket address that binds to any IPv4-configured interface available
+pub const DEFAULT_SOCKET: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), PORT);
+
+/// The address the registration and release requests are broadcast to
+pub const BROADCAST_SOCKET: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), PORT);
+
+/// The number of times a registration request is broadcast, as per spec
+pub const REGISTRATION_RETRIES: usize = 3;
+
+/// The time to wait for a negative registration response after each registration request, as per spec
+pub const REGISTRATION_TIMEOUT: Duration = Duration::from_millis(250);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error<E> {
+    Io(E),
+    Format(netbios::Error),
+}
+
+pub type ErrorKind = Error<edge_nal::io::ErrorKind>;
+
+impl<E> Error<E>
+where
+    E: edge_nal::io::Error,
+{
+    pub fn erase(&self) -> Error<edge_nal::io::ErrorKind> {
+        match self {
+            Self::Io(e) => Error::Io(e.kind()),
+            Self::Format(e) => Error::Format(*e),
+        }
+    }
+}
+
+impl<E> From<netbios::Error> for Error<E> {
+    fn from(value: netbios::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "IO error: {err}"),
+            Self::Format(err) => write!(f, "Format error: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E> where E: std::error::Error {}
+
+/// Runs the NetBIOS name service on the provided local address.
+///
+/// The name of the host is registered first, by broadcasting registration requests.
+/// If another node on the network owns the name already, `netbios::Error::Conflict` is returned.
+///
+/// Then, the name queries and the node status queries for the name of the host are answered
+/// (see `netbios::reply`), until an IO error occurs.
+///
+/// Parameters:
+/// - `stack`: The UDP stack; its sockets should be able to send broadcast packets
+/// - `local_addr`: The address to bind to; usually `DEFAULT_SOCKET`
+/// - `tx_buf`, `rx_buf`: The buffers for the outgoing and incoming packets (576 bytes are enough)
+/// - `host`: The name and the address of the host
+/// - `rand`: A function which fills the provided buffer with random bytes, used for the transaction IDs
+pub async fn run<S>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    host: &Host<'_>,
+    rand: fn(&mut [u8]),
+) -> Result<(), Error<S::Error>>
+where
+    S: UdpBind,
+{
+    let mut udp = stack.bind(local_addr).await.map_err(Error::Io)?;
+
+    register(&mut udp, tx_buf, rx_buf, host, rand).await?;
+
+    info!(
+        "Registered NetBIOS name {} with address {}",
+        host.name, host.ip
+    );
+
+    loop {
+        let (len, remote) = udp.receive(rx_buf).await.map_err(Error::Io)?;
+
+        let len = match reply(&rx_buf[..len], host, tx_buf) {
+            Ok(len) => len,
+            Err(netbios::Error::InvalidMessage) => {
+                warn!("Got invalid message from {remote}, skipping");
+                continue;
+            }
+            Err(other) => Err(other)?,
+        };
+
+        if len > 0 {
+            udp.send(remote, &tx_buf[..len]).await.map_err(Error::Io)?;
+
+            debug!("Replied to {remote}");
+        }
+    }
+}
+
+/// Broadcasts the release requests of the name of the host from the provided local address,
+/// so that the name can be registered by other nodes.
+///
+/// Should be called once the `run` future is dropped and before the host leaves the network.
+pub async fn release<S>(
+    stack: &S,
+    local_addr: SocketAddr,
+    tx_buf: &mut [u8],
+    host: &Host<'_>,
+    rand: fn(&mut [u8]),
+) -> Result<(), Error<S::Error>>
+where
+    S: UdpBind,
+{
+    let mut udp = stack.bind(local_addr).await.map_err(Error::Io)?;
+
+    let id = new_id(rand);
+
+    for (index, suffix) in SUFFIXES.into_iter().enumerate() {
+        let len = compose_release(id.wrapping_add(index as _), host, suffix, tx_buf)?;
+
+        udp.send(BROADCAST_SOCKET, &tx_buf[..len])
+            .await
+            .map_err(Error::Io)?;
+    }
+
+    Ok(())
+}
+
+async fn register<U>(
+    udp: &mut U,
+    tx_buf: &mut [u8],
+    rx_buf: &mut [u8],
+    host: &Host<'_>,
+    rand: fn(&mut [u8]),
+) -> Result<(), Error<U::Error>>
+where
+    U: UdpReceive + UdpSend,
+{
+    // One transaction ID per registered name
+    let id = new_id(rand);
+
+    for _ in 0..REGISTRATION_RETRIES {
+        for (index, suffix) in SUFFIXES.into_iter().enumerate() {
+            let len = compose_registration(id.wrapping_add(index as _), host, suffix, tx_buf)?;
+
+            udp.send(BROADCAST_SOCKET, &tx_buf[..len])
+                .await
+                .map_err(Error::Io)?;
+        }
+
+        let deadline = Instant::now() + REGISTRATION_TIMEOUT;
+
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            let Ok(result) = with_timeout(timeout, udp.receive(rx_buf)).await else {
+                break;
+            };
+
+            let (len, remote) = result.map_err(Error::Io)?;
+
+            let response = &rx_buf[..len];
+
+            for index in 0..SUFFIXES.len() {
+                if is_conflict(response, id.wrapping_add(index as _)).unwrap_or(false) {
+                    warn!("NetBIOS name {} is already owned by {remote}", host.name);
+
+                    Err(netbios::Error::Conflict)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn new_id(rand: fn(&mut [u8])) -> u16 {
+    let mut id = [0; 2];
+    rand(&mut id);
+
+    u16::from_ne_bytes(id)
+}
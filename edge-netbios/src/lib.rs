@@ -0,0 +1,625 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![warn(clippy::large_futures)]
+#![allow(async_fn_in_trait)]
+
+use core::fmt;
+use core::net::Ipv4Addr;
+use core::time::Duration;
+
+use log::debug;
+
+#[cfg(feature = "io")]
+pub mod io;
+
+/// The NetBIOS name service port, as per spec.
+pub const PORT: u16 = 137;
+
+/// The maximum length of a NetBIOS name (without the suffix).
+pub const MAX_NAME_LEN: usize = 15;
+
+/// The suffix of the workstation name, as queried i.e. by `ping`.
+pub const WORKSTATION: u8 = 0x00;
+/// The suffix of the server name, as queried i.e. when resolving short host names
+/// or opening `\\name` in the Windows Explorer.
+pub const SERVER: u8 = 0x20;
+
+/// The suffixes the name of a `Host` is registered and answered with.
+pub const SUFFIXES: [u8; 2] = [WORKSTATION, SERVER];
+
+const HEADER_LEN: usize = 12;
+/// The length of an encoded name with an empty scope: a length byte, 32 characters and a terminating zero
+const ENCODED_NAME_LEN: usize = 34;
+/// A compression pointer to the name of the question, which always follows the header
+const NAME_POINTER: u16 = 0xc000 | HEADER_LEN as u16;
+
+const OPCODE_QUERY: u8 = 0;
+const OPCODE_REGISTRATION: u8 = 5;
+const OPCODE_RELEASE: u8 = 6;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AA: u16 = 0x0400;
+const FLAG_RD: u16 = 0x0100;
+const FLAG_RA: u16 = 0x0080;
+const FLAG_BROADCAST: u16 = 0x0010;
+
+/// The rcode of a negative registration response: the name is owned by another node
+const RCODE_ACT_ERR: u8 = 6;
+
+const TYPE_NB: u16 = 0x0020;
+const TYPE_NBSTAT: u16 = 0x0021;
+const CLASS_IN: u16 = 0x0001;
+
+/// The name flags of a unique name owned by a B-node, which is active
+const NAME_FLAGS_ACTIVE: u16 = 0x0400;
+
+/// The length of the statistics section of a node status response
+const STATISTICS_LEN: usize = 46;
+
+/// An error while parsing or composing NetBIOS name service packets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Error {
+    /// The buffer is too small for the packet
+    ShortBuf,
+    /// The packet is malformed
+    InvalidMessage,
+    /// The name is not a valid NetBIOS name
+    InvalidName,
+    /// The name is already owned by another node on the network
+    Conflict,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let str = match self {
+            Self::ShortBuf => "ShortBuf",
+            Self::InvalidMessage => "InvalidMessage",
+            Self::InvalidName => "InvalidName",
+            Self::Conflict => "Conflict",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The device, whose name is answered by the NetBIOS name service.
+#[derive(Debug, Clone)]
+pub struct Host<'a> {
+    /// The NetBIOS name of the device, i.e. its short host name.
+    ///
+    /// Up to 15 ASCII characters without dots; the name is case-insensitive.
+    pub name: &'a str,
+    /// The IPv4 address of the device
+    pub ip: Ipv4Addr,
+    /// The time-to-live of the answers
+    pub ttl: Duration,
+}
+
+impl Host<'_> {
+    /// Return the NetBIOS name of the host with the provided suffix, as 16 bytes:
+    /// the upper-cased name, padded with spaces, followed by the suffix.
+    pub fn netbios_name(&self, suffix: u8) -> Result<[u8; 16], Error> {
+        let name = self.name.as_bytes();
+
+        if name.is_empty()
+            || name.len() > MAX_NAME_LEN
+            || name[0] == b'*'
+            || !name.iter().all(|c| c.is_ascii_graphic() && *c != b'.')
+        {
+            return Err(Error::InvalidName);
+        }
+
+        let mut netbios_name = [b' '; 16];
+
+        for (dst, src) in netbios_name.iter_mut().zip(name) {
+            *dst = src.to_ascii_uppercase();
+        }
+
+        netbios_name[15] = suffix;
+
+        Ok(netbios_name)
+    }
+
+    fn ttl_secs(&self) -> u32 {
+        self.ttl.as_secs().min(u32::MAX as _) as _
+    }
+
+    fn owns(&self, name: &[u8; 16]) -> Result<bool, Error> {
+        Ok(SUFFIXES.contains(&name[15]) && self.netbios_name(name[15])? == *name)
+    }
+}
+
+/// Process a NetBIOS name service request and compose the reply into the provided buffer.
+///
+/// The following requests are replied:
+/// - Name queries for the name of the host (with the `WORKSTATION` or `SERVER` suffix),
+///   with the IP address of the host
+/// - Node status queries for the name of the host or for the `*` wildcard name,
+///   with the names of the host
+/// - Registrations of the name of the host by other nodes, with a negative registration response,
+///   so that the name is defended, as per spec
+///
+/// Returns the length of the reply, or 0 if the request should not be replied.
+/// The reply should be sent to the address and port the request came from.
+pub fn reply(request: &[u8], host: &Host, buf: &mut [u8]) -> Result<usize, Error> {
+    let header = Header::parse(request)?;
+
+    if header.flags & FLAG_RESPONSE != 0 || header.qdcount == 0 {
+        return Ok(0);
+    }
+
+    let Some((name, qtype, qclass)) = parse_question(request)? else {
+        // A name with a non-empty scope, which cannot be ours
+        return Ok(0);
+    };
+
+    if qclass != CLASS_IN {
+        return Ok(0);
+    }
+
+    let mut w = Writer::new(buf);
+
+    match (header.opcode(), qtype) {
+        (OPCODE_QUERY, TYPE_NB) if host.owns(&name)? => {
+            debug!("Answering name query for {}", DisplayName(&name));
+
+            Header {
+                id: header.id,
+                flags: FLAG_RESPONSE | FLAG_AA | (header.flags & FLAG_RD),
+                qdcount: 0,
+                ancount: 1,
+                nscount: 0,
+                arcount: 0,
+            }
+            .write(&mut w)?;
+
+            w.name(&name)?;
+            w.u16(TYPE_NB)?;
+            w.u16(CLASS_IN)?;
+            w.u32(host.ttl_secs())?;
+            w.u16(6)?;
+            w.u16(0)?; // Unique name of a B-node
+            w.slice(&host.ip.octets())?;
+        }
+        (OPCODE_QUERY, TYPE_NBSTAT)
+            if name[0] == b'*' && name[1..].iter().all(|c| *c == 0) || host.owns(&name)? =>
+        {
+            debug!("Answering node status query for {}", DisplayName(&name));
+
+            Header {
+                id: header.id,
+                flags: FLAG_RESPONSE | FLAG_AA,
+                qdcount: 0,
+                ancount: 1,
+                nscount: 0,
+                arcount: 0,
+            }
+            .write(&mut w)?;
+
+            w.name(&name)?;
+            w.u16(TYPE_NBSTAT)?;
+            w.u16(CLASS_IN)?;
+            w.u32(0)?;
+            w.u16((1 + SUFFIXES.len() * 18 + STATISTICS_LEN) as _)?;
+            w.u8(SUFFIXES.len() as _)?;
+
+            for suffix in SUFFIXES {
+                w.slice(&host.netbios_name(suffix)?)?;
+                w.u16(NAME_FLAGS_ACTIVE)?;
+            }
+
+            // The statistics (starting with the MAC address of the node) are not provided
+            w.zeroes(STATISTICS_LEN)?;
+        }
+        (OPCODE_REGISTRATION, TYPE_NB) if host.owns(&name)? => {
+            if registered_ip(request, &header)? == Some(host.ip) {
+                // Our own registration, looped back
+                return Ok(0);
+            }
+
+            debug!(
+                "Defending name {} against a registration",
+                DisplayName(&name)
+            );
+
+            Header {
+                id: header.id,
+                flags: FLAG_RESPONSE
+                    | (OPCODE_REGISTRATION as u16) << 11
+                    | FLAG_AA
+                    | FLAG_RD
+                    | FLAG_RA
+                    | RCODE_ACT_ERR as u16,
+                qdcount: 0,
+                ancount: 1,
+                nscount: 0,
+                arcount: 0,
+            }
+            .write(&mut w)?;
+
+            w.name(&name)?;
+            w.u16(TYPE_NB)?;
+            w.u16(CLASS_IN)?;
+            w.u32(0)?;
+            w.u16(6)?;
+            w.u16(0)?;
+            w.slice(&host.ip.octets())?;
+        }
+        _ => return Ok(0),
+    }
+
+    Ok(w.len)
+}
+
+/// Compose a broadcast name registration request for the name of the host with the provided suffix.
+///
+/// B-nodes register their names by broadcasting the request a few times; the name is theirs,
+/// unless another node replies with a negative registration response (see `is_conflict`).
+///
+/// Returns the length of the request.
+pub fn compose_registration(
+    id: u16,
+    host: &Host,
+    suffix: u8,
+    buf: &mut [u8],
+) -> Result<usize, Error> {
+    compose_registration_request(
+        id,
+        OPCODE_REGISTRATION,
+        FLAG_RD | FLAG_BROADCAST,
+        host,
+        suffix,
+        host.ttl_secs(),
+        buf,
+    )
+}
+
+/// Compose a broadcast name release request for the name of the host with the provided suffix.
+///
+/// Should be broadcast before the host leaves the network, so that the name can be registered by other nodes.
+///
+/// Returns the length of the request.
+pub fn compose_release(id: u16, host: &Host, suffix: u8, buf: &mut [u8]) -> Result<usize, Error> {
+    compose_registration_request(id, OPCODE_RELEASE, FLAG_BROADCAST, host, suffix, 0, buf)
+}
+
+/// Return `true` if the provided packet is a negative response to the registration request with the provided id,
+/// i.e. the name is already owned by another node.
+pub fn is_conflict(response: &[u8], id: u16) -> Result<bool, Error> {
+    let header = Header::parse(response)?;
+
+    Ok(header.id == id
+        && header.flags & FLAG_RESPONSE != 0
+        && header.opcode() == OPCODE_REGISTRATION
+        && header.rcode() != 0)
+}
+
+fn compose_registration_request(
+    id: u16,
+    opcode: u8,
+    flags: u16,
+    host: &Host,
+    suffix: u8,
+    ttl: u32,
+    buf: &mut [u8],
+) -> Result<usize, Error> {
+    let name = host.netbios_name(suffix)?;
+
+    let mut w = Writer::new(buf);
+
+    Header {
+        id,
+        flags: (opcode as u16) << 11 | flags,
+        qdcount: 1,
+        ancount: 0,
+        nscount: 0,
+        arcount: 1,
+    }
+    .write(&mut w)?;
+
+    w.name(&name)?;
+    w.u16(TYPE_NB)?;
+    w.u16(CLASS_IN)?;
+
+    w.u16(NAME_POINTER)?;
+    w.u16(TYPE_NB)?;
+    w.u16(CLASS_IN)?;
+    w.u32(ttl)?;
+    w.u16(6)?;
+    w.u16(0)?;
+    w.slice(&host.ip.octets())?;
+
+    Ok(w.len)
+}
+
+/// Parse the (first) question of a packet
+///
+/// Returns the decoded name, the type and the class of the question,
+/// or `None` if the name has a non-empty scope.
+fn parse_question(packet: &[u8]) -> Result<Option<([u8; 16], u16, u16)>, Error> {
+    let question = packet
+        .get(HEADER_LEN..HEADER_LEN + ENCODED_NAME_LEN + 4)
+        .ok_or(Error::InvalidMessage)?;
+
+    if question[0] != 32 {
+        return Err(Error::InvalidMessage);
+    }
+
+    if question[33] != 0 {
+        return Ok(None);
+    }
+
+    let mut name = [0; 16];
+
+    for (index, pair) in question[1..33].chunks_exact(2).enumerate() {
+        if !pair.iter().all(|c| (b'A'..=b'P').contains(c)) {
+            return Err(Error::InvalidMessage);
+        }
+
+        name[index] = (pair[0] - b'A') << 4 | (pair[1] - b'A');
+    }
+
+    let qtype = u16::from_be_bytes([question[34], question[35]]);
+    let qclass = u16::from_be_bytes([question[36], question[37]]);
+
+    Ok(Some((name, qtype, qclass)))
+}
+
+/// Return the address in the additional record of a registration request
+fn registered_ip(request: &[u8], header: &Header) -> Result<Option<Ipv4Addr>, Error> {
+    if header.arcount == 0 {
+        return Ok(None);
+    }
+
+    // The additional record follows the question; its name is either a pointer to the name of the question, or the name itself
+    let offset = HEADER_LEN + ENCODED_NAME_LEN + 4;
+
+    let name_len = match request.get(offset) {
+        Some(len) if len & 0xc0 == 0xc0 => 2,
+        Some(_) => ENCODED_NAME_LEN,
+        None => return Err(Error::InvalidMessage),
+    };
+
+    // Type, class, TTL, RDLENGTH and NB_FLAGS precede the address
+    let offset = offset + name_len + 12;
+
+    let ip = request
+        .get(offset..offset + 4)
+        .ok_or(Error::InvalidMessage)?;
+
+    Ok(Some(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])))
+}
+
+struct Header {
+    id: u16,
+    flags: u16,
+    qdcount: u16,
+    ancount: u16,
+    nscount: u16,
+    arcount: u16,
+}
+
+impl Header {
+    fn parse(packet: &[u8]) -> Result<Self, Error> {
+        let header = packet.get(..HEADER_LEN).ok_or(Error::InvalidMessage)?;
+
+        let u16_at = |offset: usize| u16::from_be_bytes([header[offset], header[offset + 1]]);
+
+        Ok(Self {
+            id: u16_at(0),
+            flags: u16_at(2),
+            qdcount: u16_at(4),
+            ancount: u16_at(6),
+            nscount: u16_at(8),
+            arcount: u16_at(10),
+        })
+    }
+
+    fn opcode(&self) -> u8 {
+        ((self.flags >> 11) & 0x0f) as _
+    }
+
+    fn rcode(&self) -> u8 {
+        (self.flags & 0x0f) as _
+    }
+
+    fn write(&self, w: &mut Writer) -> Result<(), Error> {
+        w.u16(self.id)?;
+        w.u16(self.flags)?;
+        w.u16(self.qdcount)?;
+        w.u16(self.ancount)?;
+        w.u16(self.nscount)?;
+        w.u16(self.arcount)
+    }
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    fn slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        let end = self.len + data.len();
+
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(Error::ShortBuf)?
+            .copy_from_slice(data);
+
+        self.len = end;
+
+        Ok(())
+    }
+
+    fn u8(&mut self, data: u8) -> Result<(), Error> {
+        self.slice(&[data])
+    }
+
+    fn u16(&mut self, data: u16) -> Result<(), Error> {
+        self.slice(&data.to_be_bytes())
+    }
+
+    fn u32(&mut self, data: u32) -> Result<(), Error> {
+        self.slice(&data.to_be_bytes())
+    }
+
+    fn zeroes(&mut self, len: usize) -> Result<(), Error> {
+        for _ in 0..len {
+            self.u8(0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the first-level encoding of a name with an empty scope, as per RFC 1001, section 14.1
+    fn name(&mut self, name: &[u8; 16]) -> Result<(), Error> {
+        self.u8(32)?;
+
+        for c in name {
+            self.u8(b'A' + (c >> 4))?;
+            self.u8(b'A' + (c & 0x0f))?;
+        }
+
+        self.u8(0)
+    }
+}
+
+/// Displays a decoded NetBIOS name in the usual `NAME<XX>` form
+struct DisplayName<'a>(&'a [u8; 16]);
+
+impl fmt::Display for DisplayName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in &self.0[..15] {
+            if c.is_ascii_graphic() {
+                write!(f, "{}", *c as char)?;
+            }
+        }
+
+        write!(f, "<{:02x}>", self.0[15])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HOST: Host = Host {
+        name: "fred",
+        ip: Ipv4Addr::new(192, 168, 1, 10),
+        ttl: Duration::from_secs(300),
+    };
+
+    // The example of RFC 1001, section 14.1
+    const FRED: &[u8; 32] = b"EGFCEFEECACACACACACACACACACACACA";
+
+    fn query(qtype: u16, name: &[u8; 32]) -> [u8; 50] {
+        let mut query = [0; 50];
+
+        query[..12].copy_from_slice(&[0x12, 0x34, 0x01, 0x10, 0, 1, 0, 0, 0, 0, 0, 0]);
+        query[12] = 32;
+        query[13..45].copy_from_slice(name);
+        query[46..48].copy_from_slice(&qtype.to_be_bytes());
+        query[48..50].copy_from_slice(&CLASS_IN.to_be_bytes());
+
+        query
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(&HOST.netbios_name(SERVER).unwrap(), b"FRED           \x20");
+        assert_eq!(
+            &HOST.netbios_name(WORKSTATION).unwrap(),
+            b"FRED           \x00"
+        );
+
+        let mut buf = [0; 34];
+        let mut w = Writer::new(&mut buf);
+        w.name(&HOST.netbios_name(SERVER).unwrap()).unwrap();
+        assert_eq!(&buf[1..33], FRED);
+
+        for name in ["", "a.local", "*", "sixteen-chars-xx", "spa ce"] {
+            let host = Host { name, ..HOST };
+
+            assert_eq!(host.netbios_name(SERVER), Err(Error::InvalidName));
+        }
+    }
+
+    #[test]
+    fn name_query() {
+        let mut buf = [0; 128];
+
+        let len = reply(&query(TYPE_NB, FRED), &HOST, &mut buf).unwrap();
+
+        assert_eq!(len, 12 + 34 + 16);
+        // ID, then a response with AA and RD set and no error
+        assert_eq!(&buf[..4], &[0x12, 0x34, 0x85, 0x00]);
+        assert_eq!(&buf[13..45], FRED);
+        // TTL, RDLENGTH, NB_FLAGS and the address
+        assert_eq!(&buf[50..62], &[0, 0, 1, 44, 0, 6, 0, 0, 192, 168, 1, 10]);
+
+        // Not our name
+        let len = reply(
+            &query(TYPE_NB, b"FHEPFCELEHFCEPFFFACACACACACACAAA"),
+            &HOST,
+            &mut buf,
+        )
+        .unwrap();
+
+        assert_eq!(len, 0);
+    }
+
+    #[test]
+    fn node_status() {
+        let mut buf = [0; 256];
+
+        let len = reply(
+            &query(TYPE_NBSTAT, b"CKAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"),
+            &HOST,
+            &mut buf,
+        )
+        .unwrap();
+
+        assert_eq!(len, 12 + 34 + 10 + 1 + 2 * 18 + STATISTICS_LEN);
+        assert_eq!(buf[56], 2);
+        assert_eq!(&buf[57..73], b"FRED           \x00");
+        assert_eq!(&buf[73..75], &[0x04, 0x00]);
+    }
+
+    #[test]
+    fn registration() {
+        let mut request = [0; 128];
+        let mut buf = [0; 128];
+
+        let len = compose_registration(0x4321, &HOST, SERVER, &mut request).unwrap();
+
+        assert_eq!(len, 12 + 34 + 4 + 2 + 10 + 6);
+        assert_eq!(&request[2..4], &[0x29, 0x10]);
+
+        // Our own registration is not defended against
+        assert_eq!(reply(&request[..len], &HOST, &mut buf).unwrap(), 0);
+
+        // ... but the registration of our name by another node is
+        let other = Host {
+            ip: Ipv4Addr::new(192, 168, 1, 20),
+            ..HOST
+        };
+
+        let len = compose_registration(0x4321, &other, SERVER, &mut request).unwrap();
+        let len = reply(&request[..len], &HOST, &mut buf).unwrap();
+
+        assert!(len > 0);
+        assert!(is_conflict(&buf[..len], 0x4321).unwrap());
+        assert!(!is_conflict(&buf[..len], 0x4322).unwrap());
+
+        let len = compose_release(0x4321, &HOST, SERVER, &mut request).unwrap();
+        assert_eq!(&request[2..4], &[0x30, 0x10]);
+        assert_eq!(reply(&request[..len], &HOST, &mut buf).unwrap(), 0);
+    }
+}
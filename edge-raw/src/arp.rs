@@ -0,0 +1,233 @@
+use log::trace;
+
+use core::net::Ipv4Addr;
+
+use super::bytes::{BytesIn, BytesOut};
+
+use super::Error;
+
+/// Represents a parsed ARP packet for IPv4 over Ethernet, as per RFC 826
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArpPacket {
+    /// The operation; `ArpPacket::REQUEST` or `ArpPacket::REPLY`
+    pub op: u16,
+    /// Sender hardware address
+    pub sender_mac: [u8; 6],
+    /// Sender protocol address
+    pub sender_ip: Ipv4Addr,
+    /// Target hardware address
+    pub target_mac: [u8; 6],
+    /// Target protocol address
+    pub target_ip: Ipv4Addr,
+}
+
+impl ArpPacket {
+    /// The EtherType of ARP packets
+    pub const ETHER_TYPE: u16 = 0x0806;
+
+    pub const SIZE: usize = 28;
+
+    pub const REQUEST: u16 = 1;
+    pub const REPLY: u16 = 2;
+
+    const HTYPE_ETHERNET: u16 = 1;
+    const PTYPE_IPV4: u16 = 0x0800;
+
+    /// Create a new packet instance
+    pub const fn new(
+        op: u16,
+        sender_mac: [u8; 6],
+        sender_ip: Ipv4Addr,
+        target_mac: [u8; 6],
+        target_ip: Ipv4Addr,
+    ) -> Self {
+        Self {
+            op,
+            sender_mac,
+            sender_ip,
+            target_mac,
+            target_ip,
+        }
+    }
+
+    /// Create an ARP request asking for the hardware address of `target_ip`
+    pub const fn request(sender_mac: [u8; 6], sender_ip: Ipv4Addr, target_ip: Ipv4Addr) -> Self {
+        Self::new(Self::REQUEST, sender_mac, sender_ip, [0; 6], target_ip)
+    }
+
+    /// Create an ARP reply telling the requester with `target_mac` and `target_ip` that `sender_ip` is at `sender_mac`
+    pub const fn reply(
+        sender_mac: [u8; 6],
+        sender_ip: Ipv4Addr,
+        target_mac: [u8; 6],
+        target_ip: Ipv4Addr,
+    ) -> Self {
+        Self::new(Self::REPLY, sender_mac, sender_ip, target_mac, target_ip)
+    }
+
+    /// Create an ARP probe for `ip`, as per RFC 5227
+    ///
+    /// A probe is a request with an unspecified sender address, so that it does not pollute the ARP caches
+    /// of the other hosts, should the address turn out to be in use.
+    pub const fn probe(mac: [u8; 6], ip: Ipv4Addr) -> Self {
+        Self::request(mac, Ipv4Addr::UNSPECIFIED, ip)
+    }
+
+    /// Create a gratuitous ARP announcement for `ip`, as per RFC 5227
+    ///
+    /// An announcement is a request where both the sender and the target address are `ip`,
+    /// which makes the other hosts update their ARP caches with the new (or changed) hardware address.
+    pub const fn announcement(mac: [u8; 6], ip: Ipv4Addr) -> Self {
+        Self::request(mac, ip, ip)
+    }
+
+    /// Return `true` if the packet is an ARP probe (see `probe`)
+    pub fn is_probe(&self) -> bool {
+        self.op == Self::REQUEST && self.sender_ip.is_unspecified()
+    }
+
+    /// Return `true` if the packet is a gratuitous ARP announcement (see `announcement`)
+    pub fn is_announcement(&self) -> bool {
+        !self.sender_ip.is_unspecified() && self.sender_ip == self.target_ip
+    }
+
+    /// Return the reply to this packet, if it is a request (including a probe, but not an announcement) for `ip`,
+    /// which is owned by the host with hardware address `mac`
+    pub fn reply_for(&self, mac: [u8; 6], ip: Ipv4Addr) -> Option<Self> {
+        (self.op == Self::REQUEST && self.target_ip == ip && !self.is_announcement())
+            .then(|| Self::reply(mac, ip, self.sender_mac, self.sender_ip))
+    }
+
+    /// Decodes the packet from a byte slice
+    ///
+    /// Packets for hardware types other than Ethernet or for protocol types other than IPv4 are rejected
+    /// with `Error::InvalidFormat`.
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        let htype = u16::from_be_bytes(bytes.arr()?);
+        let ptype = u16::from_be_bytes(bytes.arr()?);
+        let hlen = bytes.byte()?;
+        let plen = bytes.byte()?;
+
+        if htype != Self::HTYPE_ETHERNET || ptype != Self::PTYPE_IPV4 || hlen != 6 || plen != 4 {
+            Err(Error::InvalidFormat)?;
+        }
+
+        let packet = Self {
+            op: u16::from_be_bytes(bytes.arr()?),
+            sender_mac: bytes.arr()?,
+            sender_ip: u32::from_be_bytes(bytes.arr()?).into(),
+            target_mac: bytes.arr()?,
+            target_ip: u32::from_be_bytes(bytes.arr()?).into(),
+        };
+
+        trace!("ARP packet decoded: {packet:?}");
+
+        Ok(packet)
+    }
+
+    /// Encodes the packet into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        bytes
+            .push(&u16::to_be_bytes(Self::HTYPE_ETHERNET))?
+            .push(&u16::to_be_bytes(Self::PTYPE_IPV4))?
+            .byte(6)?
+            .byte(4)?
+            .push(&u16::to_be_bytes(self.op))?
+            .push(&self.sender_mac)?
+            .push(&u32::to_be_bytes(self.sender_ip.into()))?
+            .push(&self.target_mac)?
+            .push(&u32::to_be_bytes(self.target_ip.into()))?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x1b, 0x21, 0x3c, 0x9d, 0xf8];
+    const IP: Ipv4Addr = Ipv4Addr::new(169, 254, 17, 42);
+
+    /// An RFC 5227 ARP probe for 169.254.17.42
+    const PROBE: [u8; ArpPacket::SIZE] = [
+        0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, //
+        0x00, 0x1b, 0x21, 0x3c, 0x9d, 0xf8, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0xa9, 0xfe, 0x11, 0x2a, //
+    ];
+
+    /// An RFC 5227 ARP announcement of 169.254.17.42
+    const ANNOUNCEMENT: [u8; ArpPacket::SIZE] = [
+        0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, //
+        0x00, 0x1b, 0x21, 0x3c, 0x9d, 0xf8, 0xa9, 0xfe, //
+        0x11, 0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0xa9, 0xfe, 0x11, 0x2a, //
+    ];
+
+    #[test]
+    fn test_probe() {
+        let packet = ArpPacket::decode(&PROBE).unwrap();
+
+        assert_eq!(packet, ArpPacket::probe(MAC, IP));
+        assert!(packet.is_probe());
+        assert!(!packet.is_announcement());
+
+        let mut buf = [0; ArpPacket::SIZE];
+        assert_eq!(packet.encode(&mut buf).unwrap(), PROBE);
+
+        // The owner of the address replies to the probe
+        let owner = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+        assert_eq!(
+            packet.reply_for(owner, IP),
+            Some(ArpPacket::reply(owner, IP, MAC, Ipv4Addr::UNSPECIFIED))
+        );
+        assert_eq!(
+            packet.reply_for(owner, Ipv4Addr::new(169, 254, 17, 43)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_announcement() {
+        let packet = ArpPacket::decode(&ANNOUNCEMENT).unwrap();
+
+        assert_eq!(packet, ArpPacket::announcement(MAC, IP));
+        assert!(packet.is_announcement());
+        assert!(!packet.is_probe());
+
+        let mut buf = [0; ArpPacket::SIZE];
+        assert_eq!(packet.encode(&mut buf).unwrap(), ANNOUNCEMENT);
+
+        // Announcements are not answered
+        assert_eq!(
+            packet.reply_for([0x02, 0x00, 0x00, 0x00, 0x00, 0x01], IP),
+            None
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        // ARP for IPv6 (which does not exist) and for a non-Ethernet hardware type
+        let mut packet = PROBE;
+        packet[2] = 0x86;
+        packet[3] = 0xdd;
+        assert_eq!(ArpPacket::decode(&packet), Err(Error::InvalidFormat));
+
+        let mut packet = PROBE;
+        packet[1] = 6;
+        assert_eq!(ArpPacket::decode(&packet), Err(Error::InvalidFormat));
+
+        assert_eq!(
+            ArpPacket::decode(&PROBE[..ArpPacket::SIZE - 1]),
+            Err(Error::DataUnderflow)
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use log::trace;
+
+use super::bytes::{BytesIn, BytesOut};
+
+use super::Error;
+
+#[allow(clippy::type_complexity)]
+pub fn decode(
+    frame: &[u8],
+    filter_dst: Option<[u8; 6]>,
+    filter_ether_type: Option<u16>,
+) -> Result<Option<([u8; 6], [u8; 6], u16, &[u8])>, Error> {
+    let data = EthernetFrameHeader::decode_with_payload(frame, filter_dst, filter_ether_type)?
+        .map(|(hdr, payload)| (hdr.src, hdr.dst, hdr.ether_type, payload));
+
+    Ok(data)
+}
+
+pub fn encode<F>(
+    buf: &mut [u8],
+    src: [u8; 6],
+    dst: [u8; 6],
+    ether_type: u16,
+    vlan: Option<VlanTag>,
+    encoder: F,
+) -> Result<&[u8], Error>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+{
+    let mut hdr = EthernetFrameHeader::new(src, dst, ether_type);
+    hdr.vlan = vlan;
+
+    hdr.encode_with_payload(buf, encoder)
+}
+
+/// Represents an IEEE 802.1Q VLAN tag
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct VlanTag {
+    /// Priority code point (3 bits)
+    pub pcp: u8,
+    /// Drop eligible indicator
+    pub dei: bool,
+    /// VLAN identifier (12 bits)
+    pub vid: u16,
+}
+
+impl VlanTag {
+    /// Create a new tag with the provided VLAN identifier and with the default priority
+    pub const fn new(vid: u16) -> Self {
+        Self {
+            pcp: 0,
+            dei: false,
+            vid,
+        }
+    }
+
+    fn decode(tci: u16) -> Self {
+        Self {
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 != 0,
+            vid: tci & 0x0fff,
+        }
+    }
+
+    fn encode(&self) -> u16 {
+        ((self.pcp as u16 & 0x07) << 13) | (if self.dei { 0x1000 } else { 0 }) | (self.vid & 0x0fff)
+    }
+}
+
+/// Represents a parsed Ethernet II frame header, with an optional 802.1Q VLAN tag
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EthernetFrameHeader {
+    /// Destination MAC address
+    pub dst: [u8; 6],
+    /// Source MAC address
+    pub src: [u8; 6],
+    /// VLAN tag, if the frame is tagged
+    pub vlan: Option<VlanTag>,
+    /// EtherType of the payload
+    pub ether_type: u16,
+}
+
+impl EthernetFrameHeader {
+    pub const SIZE: usize = 14;
+    pub const VLAN_TAG_SIZE: usize = 4;
+
+    /// The minimum size of a frame (excluding the FCS); shorter payloads are padded with zeroes
+    pub const MIN_FRAME_SIZE: usize = 60;
+
+    /// The EtherType of 802.1Q-tagged frames
+    pub const VLAN: u16 = 0x8100;
+    /// The EtherType of IPv4 packets
+    pub const IPV4: u16 = 0x0800;
+    /// The EtherType of IPv6 packets
+    pub const IPV6: u16 = 0x86dd;
+    /// The EtherType of ARP packets
+    pub const ARP: u16 = 0x0806;
+
+    pub const BROADCAST: [u8; 6] = [0xff; 6];
+
+    /// Create a new, untagged header instance
+    pub const fn new(src: [u8; 6], dst: [u8; 6], ether_type: u16) -> Self {
+        Self {
+            dst,
+            src,
+            vlan: None,
+            ether_type,
+        }
+    }
+
+    /// Return the size of the header, which depends on whether it is VLAN-tagged
+    pub const fn size(&self) -> usize {
+        if self.vlan.is_some() {
+            Self::SIZE + Self::VLAN_TAG_SIZE
+        } else {
+            Self::SIZE
+        }
+    }
+
+    /// Decodes the header from a byte slice
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        let dst = bytes.arr()?;
+        let src = bytes.arr()?;
+
+        let mut ether_type = u16::from_be_bytes(bytes.arr()?);
+        let mut vlan = None;
+
+        if ether_type == Self::VLAN {
+            vlan = Some(VlanTag::decode(u16::from_be_bytes(bytes.arr()?)));
+            ether_type = u16::from_be_bytes(bytes.arr()?);
+        }
+
+        if ether_type < 0x0600 {
+            // An IEEE 802.3 length field rather than an EtherType; such frames are not supported
+            Err(Error::InvalidFormat)?;
+        }
+
+        Ok(Self {
+            dst,
+            src,
+            vlan,
+            ether_type,
+        })
+    }
+
+    /// Encodes the header into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        bytes.push(&self.dst)?.push(&self.src)?;
+
+        if let Some(vlan) = self.vlan {
+            bytes
+                .push(&u16::to_be_bytes(Self::VLAN))?
+                .push(&u16::to_be_bytes(vlan.encode()))?;
+        }
+
+        bytes.push(&u16::to_be_bytes(self.ether_type))?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+
+    /// Encodes the header and the provided payload into the provided buf slice
+    ///
+    /// Frames shorter than `MIN_FRAME_SIZE` are padded with zeroes.
+    pub fn encode_with_payload<'o, F>(
+        &self,
+        buf: &'o mut [u8],
+        encoder: F,
+    ) -> Result<&'o [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        let hdr_len = self.encode(buf)?.len();
+
+        let payload_len = encoder(&mut buf[hdr_len..])?;
+
+        let len = hdr_len + payload_len;
+        let padded_len = core::cmp::max(len, Self::MIN_FRAME_SIZE);
+
+        if buf.len() < padded_len {
+            Err(Error::BufferOverflow)?;
+        }
+
+        buf[len..padded_len].fill(0);
+
+        Ok(&buf[..padded_len])
+    }
+
+    /// Decodes the provided frame into a header and a payload slice
+    ///
+    /// Frames addressed to a broadcast or multicast MAC address always pass the `filter_dst` filter.
+    ///
+    /// Note that the payload might contain padding, so the payload protocol should rely on its own length field, if any.
+    pub fn decode_with_payload(
+        frame: &[u8],
+        filter_dst: Option<[u8; 6]>,
+        filter_ether_type: Option<u16>,
+    ) -> Result<Option<(Self, &[u8])>, Error> {
+        let hdr = Self::decode(frame)?;
+
+        trace!(
+            "Ethernet header decoded, size={}, src={:02x?}, dst={:02x?}, vlan={:?}, ether_type={:04x}",
+            frame.len(),
+            hdr.src,
+            hdr.dst,
+            hdr.vlan,
+            hdr.ether_type
+        );
+
+        if let Some(filter_dst) = filter_dst {
+            // The group bit is set for both the broadcast and the multicast addresses
+            if hdr.dst[0] & 0x01 == 0 && filter_dst != hdr.dst {
+                return Ok(None);
+            }
+        }
+
+        if let Some(filter_ether_type) = filter_ether_type {
+            if filter_ether_type != hdr.ether_type {
+                return Ok(None);
+            }
+        }
+
+        let payload = &frame[hdr.size()..];
+
+        Ok(Some((hdr, payload)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arp::ArpPacket;
+
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x1b, 0x21, 0x3c, 0x9d, 0xf8];
+
+    /// A broadcast ARP probe on VLAN 100 with priority 5, padded to the minimum frame size
+    const TAGGED_ARP: [u8; EthernetFrameHeader::MIN_FRAME_SIZE] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x1b, //
+        0x21, 0x3c, 0x9d, 0xf8, 0x81, 0x00, 0xa0, 0x64, //
+        0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, //
+        0x00, 0x01, 0x00, 0x1b, 0x21, 0x3c, 0x9d, 0xf8, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0xc0, 0xa8, 0x01, 0x0a, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, //
+    ];
+
+    #[test]
+    fn test_vlan() {
+        let (hdr, payload) = EthernetFrameHeader::decode_with_payload(
+            &TAGGED_ARP,
+            Some(MAC),
+            Some(EthernetFrameHeader::ARP),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(hdr.dst, EthernetFrameHeader::BROADCAST);
+        assert_eq!(hdr.src, MAC);
+        assert_eq!(
+            hdr.vlan,
+            Some(VlanTag {
+                pcp: 5,
+                dei: false,
+                vid: 100
+            })
+        );
+        assert_eq!(hdr.ether_type, EthernetFrameHeader::ARP);
+        assert_eq!(hdr.size(), 18);
+
+        // The payload includes the padding
+        assert_eq!(payload.len(), 42);
+
+        let arp = ArpPacket::decode(payload).unwrap();
+        assert_eq!(arp, ArpPacket::probe(MAC, [192, 168, 1, 10].into()));
+
+        let mut buf = [0; 128];
+
+        let frame = encode(
+            &mut buf,
+            MAC,
+            EthernetFrameHeader::BROADCAST,
+            EthernetFrameHeader::ARP,
+            hdr.vlan,
+            |buf| Ok(arp.encode(buf)?.len()),
+        )
+        .unwrap();
+
+        assert_eq!(frame, TAGGED_ARP);
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut frame = TAGGED_ARP;
+
+        assert!(EthernetFrameHeader::decode_with_payload(
+            &frame,
+            None,
+            Some(EthernetFrameHeader::IPV4)
+        )
+        .unwrap()
+        .is_none());
+
+        // Unicast to another host
+        frame[..6].copy_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+
+        assert!(
+            EthernetFrameHeader::decode_with_payload(&frame, Some(MAC), None)
+                .unwrap()
+                .is_none()
+        );
+
+        // IEEE 802.3 length instead of an EtherType
+        frame[16] = 0x00;
+        frame[17] = 0x2a;
+
+        assert_eq!(
+            EthernetFrameHeader::decode(&frame),
+            Err(Error::InvalidFormat)
+        );
+
+        assert_eq!(
+            EthernetFrameHeader::decode(&TAGGED_ARP[..16]),
+            Err(Error::DataUnderflow)
+        );
+    }
+}
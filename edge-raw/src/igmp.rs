@@ -0,0 +1,486 @@
+use log::trace;
+
+use core::net::Ipv4Addr;
+
+use super::bytes::{BytesIn, BytesOut};
+use super::ip::Ipv4PacketHeader;
+
+use super::{checksum_accumulate, checksum_finish, Error};
+
+/// The IGMP version used when joining or leaving a multicast group
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum IgmpVersion {
+    /// IGMPv2, as per RFC 2236
+    V2,
+    /// IGMPv3, as per RFC 3376
+    V3,
+}
+
+/// Represents a parsed IGMP message
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IgmpMessage<'a> {
+    /// Membership Query; a general query when the group address is unspecified
+    Query { max_resp_code: u8, group: Ipv4Addr },
+    /// IGMPv2 Membership Report
+    V2Report(Ipv4Addr),
+    /// IGMPv2 Leave Group
+    V2Leave(Ipv4Addr),
+    /// IGMPv3 Membership Report
+    V3Report(GroupRecords<'a>),
+}
+
+impl<'a> IgmpMessage<'a> {
+    pub const PROTO: u8 = 2;
+
+    pub const CHECKSUM_WORD: usize = 1;
+
+    const QUERY: u8 = 0x11;
+    const V2_REPORT: u8 = 0x16;
+    const V2_LEAVE: u8 = 0x17;
+    const V3_REPORT: u8 = 0x22;
+
+    /// All-routers group, the destination of IGMPv2 leave messages
+    pub const ALL_ROUTERS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 2);
+    /// IGMPv3-capable routers group, the destination of IGMPv3 reports
+    pub const V3_ROUTERS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 22);
+
+    /// Create a membership report (join) for the provided group
+    ///
+    /// For IGMPv3, `record_buf` is used for encoding the report's single group record.
+    pub fn join(
+        version: IgmpVersion,
+        group: Ipv4Addr,
+        record_buf: &'a mut [u8; GroupRecords::RECORD_SIZE],
+    ) -> Self {
+        match version {
+            IgmpVersion::V2 => Self::V2Report(group),
+            IgmpVersion::V3 => Self::V3Report(GroupRecords::single(
+                GroupRecords::CHANGE_TO_EXCLUDE_MODE,
+                group,
+                record_buf,
+            )),
+        }
+    }
+
+    /// Create a leave message for the provided group
+    ///
+    /// For IGMPv3, `record_buf` is used for encoding the report's single group record.
+    pub fn leave(
+        version: IgmpVersion,
+        group: Ipv4Addr,
+        record_buf: &'a mut [u8; GroupRecords::RECORD_SIZE],
+    ) -> Self {
+        match version {
+            IgmpVersion::V2 => Self::V2Leave(group),
+            IgmpVersion::V3 => Self::V3Report(GroupRecords::single(
+                GroupRecords::CHANGE_TO_INCLUDE_MODE,
+                group,
+                record_buf,
+            )),
+        }
+    }
+
+    /// Return the IP address the message should be sent to
+    pub fn destination(&self) -> Ipv4Addr {
+        match self {
+            Self::Query { group, .. } if group.is_unspecified() => Ipv4Addr::new(224, 0, 0, 1),
+            Self::Query { group, .. } | Self::V2Report(group) => *group,
+            Self::V2Leave(_) => Self::ALL_ROUTERS,
+            Self::V3Report(_) => Self::V3_ROUTERS,
+        }
+    }
+
+    /// Decodes the message from a byte slice
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        let message_type = bytes.byte()?;
+        let max_resp_code = bytes.byte()?;
+        let sum = u16::from_be_bytes(bytes.arr()?);
+
+        let checksum = Self::checksum(data);
+
+        trace!(
+            "IGMP message decoded, type={}, size={}, checksum={}, ours={}",
+            message_type,
+            data.len(),
+            sum,
+            checksum
+        );
+
+        if checksum != sum {
+            Err(Error::InvalidChecksum)?;
+        }
+
+        let message = match message_type {
+            Self::QUERY => Self::Query {
+                max_resp_code,
+                group: u32::from_be_bytes(bytes.arr()?).into(),
+            },
+            Self::V2_REPORT => Self::V2Report(u32::from_be_bytes(bytes.arr()?).into()),
+            Self::V2_LEAVE => Self::V2Leave(u32::from_be_bytes(bytes.arr()?).into()),
+            Self::V3_REPORT => {
+                let _reserved = bytes.arr::<2>()?;
+                let count = u16::from_be_bytes(bytes.arr()?);
+
+                GroupRecords::new(count, bytes.remaining())
+                    .validate()
+                    .map(Self::V3Report)?
+            }
+            _ => Err(Error::InvalidFormat)?,
+        };
+
+        Ok(message)
+    }
+
+    /// Encodes the message into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        match self {
+            Self::Query {
+                max_resp_code,
+                group,
+            } => {
+                bytes
+                    .byte(Self::QUERY)?
+                    .byte(*max_resp_code)?
+                    .push(&[0, 0])?
+                    .push(&u32::to_be_bytes((*group).into()))?;
+            }
+            Self::V2Report(group) | Self::V2Leave(group) => {
+                bytes
+                    .byte(if matches!(self, Self::V2Report(_)) {
+                        Self::V2_REPORT
+                    } else {
+                        Self::V2_LEAVE
+                    })?
+                    .byte(0)?
+                    .push(&[0, 0])?
+                    .push(&u32::to_be_bytes((*group).into()))?;
+            }
+            Self::V3Report(records) => {
+                bytes
+                    .byte(Self::V3_REPORT)?
+                    .byte(0)?
+                    .push(&[0, 0])?
+                    .push(&[0, 0])?
+                    .push(&u16::to_be_bytes(records.count))?
+                    .push(records.data)?;
+            }
+        }
+
+        let len = bytes.len();
+
+        let checksum = Self::checksum(&buf[..len]);
+        Self::inject_checksum(&mut buf[..len], checksum);
+
+        Ok(&buf[..len])
+    }
+
+    /// Injects the checksum into the provided message
+    pub fn inject_checksum(message: &mut [u8], checksum: u16) {
+        let checksum = checksum.to_be_bytes();
+
+        let offset = Self::CHECKSUM_WORD << 1;
+        message[offset] = checksum[0];
+        message[offset + 1] = checksum[1];
+    }
+
+    /// Computes the checksum for an already encoded message
+    pub fn checksum(message: &[u8]) -> u16 {
+        let sum = checksum_accumulate(message, Self::CHECKSUM_WORD);
+
+        checksum_finish(sum)
+    }
+}
+
+/// The group records of an IGMPv3 Membership Report
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GroupRecords<'a> {
+    count: u16,
+    data: &'a [u8],
+}
+
+impl<'a> GroupRecords<'a> {
+    pub const MODE_IS_INCLUDE: u8 = 1;
+    pub const MODE_IS_EXCLUDE: u8 = 2;
+    pub const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+    pub const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+    pub const ALLOW_NEW_SOURCES: u8 = 5;
+    pub const BLOCK_OLD_SOURCES: u8 = 6;
+
+    /// The size of an encoded group record without sources and auxiliary data
+    pub const RECORD_SIZE: usize = 8;
+
+    /// Create the records from their RFC 3376 wire encoding
+    pub const fn new(count: u16, data: &'a [u8]) -> Self {
+        Self { count, data }
+    }
+
+    /// Return the number of records
+    pub const fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// Iterate over the records as `(record type, group, sources)` triples
+    pub fn iter(&self) -> impl Iterator<Item = (u8, Ipv4Addr, Sources<'a>)> + 'a {
+        let mut bytes = BytesIn::new(self.data);
+
+        (0..self.count).map_while(move |_| Self::decode_record(&mut bytes).ok())
+    }
+
+    fn single(
+        record_type: u8,
+        group: Ipv4Addr,
+        buf: &'a mut [u8; GroupRecords::RECORD_SIZE],
+    ) -> Self {
+        buf[0] = record_type;
+        buf[1] = 0;
+        buf[2..4].fill(0);
+        buf[4..8].copy_from_slice(&group.octets());
+
+        Self::new(1, buf)
+    }
+
+    fn validate(self) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(self.data);
+
+        for _ in 0..self.count {
+            Self::decode_record(&mut bytes)?;
+        }
+
+        Ok(self)
+    }
+
+    fn decode_record(bytes: &mut BytesIn<'a>) -> Result<(u8, Ipv4Addr, Sources<'a>), Error> {
+        let record_type = bytes.byte()?;
+        let aux_len = bytes.byte()? as usize * 4;
+        let sources_count = u16::from_be_bytes(bytes.arr()?) as usize;
+        let group = u32::from_be_bytes(bytes.arr()?).into();
+        let sources = bytes.slice(sources_count * 4)?;
+        let _aux = bytes.slice(aux_len)?;
+
+        Ok((record_type, group, Sources(sources)))
+    }
+}
+
+/// The source addresses of an IGMPv3 group record
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Sources<'a>(&'a [u8]);
+
+impl<'a> Sources<'a> {
+    /// Iterate over the source addresses
+    pub fn iter(&self) -> impl Iterator<Item = Ipv4Addr> + 'a {
+        self.0.chunks_exact(4).map(|octets| {
+            let octets: [u8; 4] = octets.try_into().unwrap();
+
+            octets.into()
+        })
+    }
+}
+
+/// Encodes an IP packet carrying the provided IGMP message
+///
+/// As required by the IGMP RFCs, the packet is sent with a TTL of 1 and with the IP Router Alert option.
+pub fn encode<'o>(
+    buf: &'o mut [u8],
+    src: Ipv4Addr,
+    message: &IgmpMessage,
+) -> Result<&'o [u8], Error> {
+    const ROUTER_ALERT: [u8; 4] = [0x94, 0x04, 0x00, 0x00];
+
+    let mut hdr = Ipv4PacketHeader::new(src, message.destination(), IgmpMessage::PROTO);
+    hdr.hlen = (Ipv4PacketHeader::MIN_SIZE + ROUTER_ALERT.len()) as _;
+    hdr.ttl = 1;
+
+    let len = hdr
+        .encode_with_payload(buf, |buf| Ok(message.encode(buf)?.len()))?
+        .len();
+
+    // The header options are zeroed by the encoder, so inject the Router Alert and re-compute the checksum
+    let packet = &mut buf[..len];
+    packet[Ipv4PacketHeader::MIN_SIZE..Ipv4PacketHeader::MIN_SIZE + ROUTER_ALERT.len()]
+        .copy_from_slice(&ROUTER_ALERT);
+
+    let checksum = Ipv4PacketHeader::checksum(packet);
+    Ipv4PacketHeader::inject_checksum(packet, checksum);
+
+    Ok(packet)
+}
+
+/// Decodes an IP packet and its IGMP payload
+pub fn decode(packet: &[u8]) -> Result<Option<(Ipv4Addr, Ipv4Addr, IgmpMessage<'_>)>, Error> {
+    if let Some((src, dst, _proto, message)) = super::ip::decode(
+        packet,
+        Ipv4Addr::UNSPECIFIED,
+        Ipv4Addr::UNSPECIFIED,
+        Some(IgmpMessage::PROTO),
+    )? {
+        Ok(Some((src, dst, IgmpMessage::decode(message)?)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Return the Ethernet MAC address a packet sent to the provided IPv4 multicast address should be sent to
+pub fn multicast_mac(ip: Ipv4Addr) -> [u8; 6] {
+    let octets = ip.octets();
+
+    [0x01, 0x00, 0x5e, octets[1] & 0x7f, octets[2], octets[3]]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SRC: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+    const SSDP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+
+    /// An IGMPv3 join (CHANGE_TO_EXCLUDE_MODE with no sources) of the SSDP group
+    const JOIN_V3: [u8; 40] = [
+        0x46, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x00, //
+        0x01, 0x02, 0x83, 0x07, 0xc0, 0xa8, 0x01, 0x0a, //
+        0xe0, 0x00, 0x00, 0x16, 0x94, 0x04, 0x00, 0x00, //
+        0x22, 0x00, 0xea, 0x03, 0x00, 0x00, 0x00, 0x01, //
+        0x04, 0x00, 0x00, 0x00, 0xef, 0xff, 0xff, 0xfa, //
+    ];
+
+    /// An IGMPv3 report with a MODE_IS_EXCLUDE record for 224.0.0.251, and an ALLOW_NEW_SOURCES record
+    /// for 232.1.1.1 with two sources and one word of auxiliary data
+    const REPORT_V3: [u8; 60] = [
+        0x46, 0xc0, 0x00, 0x3c, 0x00, 0x00, 0x40, 0x00, //
+        0x01, 0x02, 0x42, 0x33, 0xc0, 0xa8, 0x01, 0x0a, //
+        0xe0, 0x00, 0x00, 0x16, 0x94, 0x04, 0x00, 0x00, //
+        0x22, 0x00, 0x5b, 0x5b, 0x00, 0x00, 0x00, 0x02, //
+        0x02, 0x00, 0x00, 0x00, 0xe0, 0x00, 0x00, 0xfb, //
+        0x05, 0x01, 0x00, 0x02, 0xe8, 0x01, 0x01, 0x01, //
+        0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, //
+        0xde, 0xad, 0xbe, 0xef, //
+    ];
+
+    #[test]
+    fn test_join_v3() {
+        let mut record_buf = [0; GroupRecords::RECORD_SIZE];
+        let join = IgmpMessage::join(IgmpVersion::V3, SSDP, &mut record_buf);
+
+        assert_eq!(join.destination(), IgmpMessage::V3_ROUTERS);
+
+        let mut buf = [0; 64];
+
+        assert_eq!(encode(&mut buf, SRC, &join).unwrap(), JOIN_V3);
+
+        let (src, dst, message) = decode(&JOIN_V3).unwrap().unwrap();
+
+        assert_eq!(src, SRC);
+        assert_eq!(dst, IgmpMessage::V3_ROUTERS);
+        assert_eq!(message, join);
+
+        let IgmpMessage::V3Report(records) = message else {
+            panic!("Not an IGMPv3 report");
+        };
+
+        let mut records = records.iter();
+
+        let (record_type, group, sources) = records.next().unwrap();
+        assert_eq!(record_type, GroupRecords::CHANGE_TO_EXCLUDE_MODE);
+        assert_eq!(group, SSDP);
+        assert_eq!(sources.iter().count(), 0);
+
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn test_leave_v3() {
+        let mut record_buf = [0; GroupRecords::RECORD_SIZE];
+        let leave = IgmpMessage::leave(IgmpVersion::V3, SSDP, &mut record_buf);
+
+        let mut buf = [0; 64];
+        let packet = encode(&mut buf, SRC, &leave).unwrap();
+
+        let (_, dst, message) = decode(packet).unwrap().unwrap();
+
+        assert_eq!(dst, IgmpMessage::V3_ROUTERS);
+
+        let IgmpMessage::V3Report(records) = message else {
+            panic!("Not an IGMPv3 report");
+        };
+
+        let (record_type, group, _) = records.iter().next().unwrap();
+        assert_eq!(record_type, GroupRecords::CHANGE_TO_INCLUDE_MODE);
+        assert_eq!(group, SSDP);
+    }
+
+    #[test]
+    fn test_report_v3() {
+        let (src, dst, message) = decode(&REPORT_V3).unwrap().unwrap();
+
+        assert_eq!(src, SRC);
+        assert_eq!(dst, IgmpMessage::V3_ROUTERS);
+
+        let IgmpMessage::V3Report(records) = message else {
+            panic!("Not an IGMPv3 report");
+        };
+
+        assert_eq!(records.count(), 2);
+
+        let mut records = records.iter();
+
+        let (record_type, group, sources) = records.next().unwrap();
+        assert_eq!(record_type, GroupRecords::MODE_IS_EXCLUDE);
+        assert_eq!(group, Ipv4Addr::new(224, 0, 0, 251));
+        assert_eq!(sources.iter().count(), 0);
+
+        // The auxiliary data is skipped
+        let (record_type, group, sources) = records.next().unwrap();
+        assert_eq!(record_type, GroupRecords::ALLOW_NEW_SOURCES);
+        assert_eq!(group, Ipv4Addr::new(232, 1, 1, 1));
+
+        let mut sources = sources.iter();
+        assert_eq!(sources.next(), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(sources.next(), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(sources.next(), None);
+
+        assert!(records.next().is_none());
+
+        // Re-encoding the report yields the same IGMP message
+        let mut buf = [0; 64];
+        assert_eq!(message.encode(&mut buf).unwrap(), &REPORT_V3[24..]);
+    }
+
+    #[test]
+    fn test_report_v3_invalid() {
+        let mut message = REPORT_V3;
+        let message = &mut message[24..];
+
+        message[2] ^= 0xff;
+
+        assert!(matches!(
+            IgmpMessage::decode(message),
+            Err(Error::InvalidChecksum)
+        ));
+
+        // More records than the message contains
+        message[7] = 3;
+
+        let checksum = IgmpMessage::checksum(message);
+        IgmpMessage::inject_checksum(message, checksum);
+
+        assert!(matches!(
+            IgmpMessage::decode(message),
+            Err(Error::DataUnderflow)
+        ));
+
+        // A record with more sources than the message contains
+        message[7] = 2;
+        message[19] = 3;
+
+        let checksum = IgmpMessage::checksum(message);
+        IgmpMessage::inject_checksum(message, checksum);
+
+        assert!(matches!(
+            IgmpMessage::decode(message),
+            Err(Error::DataUnderflow)
+        ));
+    }
+}
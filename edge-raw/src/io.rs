@@ -4,10 +4,21 @@ use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 
 use embedded_io_async::{ErrorKind, ErrorType};
 
-use edge_nal::{MacAddr, RawReceive, RawSend, RawSplit, Readable, UdpReceive, UdpSend, UdpSplit};
+use edge_nal::{
+    MacAddr, RawReceive, RawSend, RawSplit, Readable, UdpReceive, UdpSend, UdpSplit, Writable,
+};
+
+use log::warn;
 
 use crate as raw;
 
+use raw::arp::ArpPacket;
+use raw::eth::{EthernetFrameHeader, VlanTag};
+use raw::igmp::{self, IgmpMessage, IgmpVersion};
+use raw::{ChecksumMode, ChecksumStatus};
+
+pub mod pcap;
+
 /// An error that can occur when sending or receiving UDP packets over a raw socket.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Error<E> {
@@ -64,6 +75,7 @@ pub struct RawSocket2Udp<T, const N: usize = 1500> {
     filter_local: Option<SocketAddrV4>,
     filter_remote: Option<SocketAddrV4>,
     remote_mac: MacAddr,
+    checksum_mode: ChecksumMode,
 }
 
 impl<T, const N: usize> RawSocket2Udp<T, N> {
@@ -78,8 +90,15 @@ impl<T, const N: usize> RawSocket2Udp<T, N> {
             filter_local,
             filter_remote,
             remote_mac,
+            checksum_mode: ChecksumMode::Strict,
         }
     }
+
+    /// Set how the checksums of the received packets are validated; `ChecksumMode::Strict` by default
+    pub fn with_checksum_mode(mut self, checksum_mode: ChecksumMode) -> Self {
+        self.checksum_mode = checksum_mode;
+        self
+    }
 }
 
 impl<T, const N: usize> ErrorType for RawSocket2Udp<T, N>
@@ -94,10 +113,11 @@ where
     T: RawReceive,
 {
     async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
-        let (len, _local, remote, _) = udp_receive::<_, N>(
+        let (len, _local, remote, _) = udp_receive_with_mode::<_, N>(
             &mut self.socket,
             self.filter_local,
             self.filter_remote,
+            self.checksum_mode,
             buffer,
         )
         .await?;
@@ -115,6 +135,15 @@ where
     }
 }
 
+impl<T, const N: usize> Writable for RawSocket2Udp<T, N>
+where
+    T: Writable,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.writable().await.map_err(Error::Io)
+    }
+}
+
 impl<T, const N: usize> UdpSend for RawSocket2Udp<T, N>
 where
     T: RawSend,
@@ -161,13 +190,168 @@ where
                 self.filter_local,
                 self.filter_remote,
                 self.remote_mac,
-            ),
-            RawSocket2Udp::new(send, self.filter_local, self.filter_remote, self.remote_mac),
+            )
+            .with_checksum_mode(self.checksum_mode),
+            RawSocket2Udp::new(send, self.filter_local, self.filter_remote, self.remote_mac)
+                .with_checksum_mode(self.checksum_mode),
+        )
+    }
+}
+
+/// A utility struct adding Ethernet (L2) framing on top of a raw socket which sends and receives whole Ethernet frames
+/// (e.g. an `AF_PACKET` `SOCK_RAW` socket on Linux, or a Wifi driver interface in promiscuous mode).
+///
+/// The struct itself implements `RawReceive` and `RawSend` the way sockets which are bound to a single EtherType do
+/// (i.e. sending and receiving just the payload of the frames), so all raw-socket-based code
+/// (like `RawSocket2Udp` or the ARP functions) can operate on top of it.
+///
+/// Frames which are not of the configured EtherType and VLAN, or which are addressed to neither the local MAC address
+/// nor to a broadcast or multicast one, are skipped when receiving.
+pub struct EthernetSocket<T, const N: usize = 1518> {
+    socket: T,
+    local_mac: MacAddr,
+    ether_type: u16,
+    vlan: Option<VlanTag>,
+}
+
+impl<T, const N: usize> EthernetSocket<T, N> {
+    /// Create a new socket sending and receiving untagged frames of the provided EtherType
+    pub const fn new(socket: T, local_mac: MacAddr, ether_type: u16) -> Self {
+        Self::new_with_vlan(socket, local_mac, ether_type, None)
+    }
+
+    /// Create a new socket sending and receiving frames of the provided EtherType, tagged with the provided VLAN tag
+    ///
+    /// When receiving, only the VLAN identifier of the tag is matched.
+    pub const fn new_with_vlan(
+        socket: T,
+        local_mac: MacAddr,
+        ether_type: u16,
+        vlan: Option<VlanTag>,
+    ) -> Self {
+        Self {
+            socket,
+            local_mac,
+            ether_type,
+            vlan,
+        }
+    }
+}
+
+impl<T, const N: usize> ErrorType for EthernetSocket<T, N>
+where
+    T: ErrorType,
+{
+    type Error = Error<T::Error>;
+}
+
+impl<T, const N: usize> RawReceive for EthernetSocket<T, N>
+where
+    T: RawReceive,
+{
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+        let mut buf = MaybeUninit::<[u8; N]>::uninit();
+        let buf = unsafe { buf.assume_init_mut() };
+
+        loop {
+            let (len, _) = self.socket.receive(buf).await.map_err(Error::Io)?;
+
+            let frame = &buf[..core::cmp::min(len, buf.len())];
+
+            match EthernetFrameHeader::decode_with_payload(
+                frame,
+                Some(self.local_mac),
+                Some(self.ether_type),
+            ) {
+                Ok(Some((hdr, payload)))
+                    if hdr.vlan.map(|vlan| vlan.vid) == self.vlan.map(|vlan| vlan.vid) =>
+                {
+                    let len = core::cmp::min(payload.len(), buffer.len());
+                    buffer[..len].copy_from_slice(&payload[..len]);
+
+                    break Ok((payload.len(), hdr.src));
+                }
+                Ok(_) => continue,
+                Err(raw::Error::InvalidFormat) | Err(raw::Error::DataUnderflow) => continue,
+                Err(other) => Err(other)?,
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> Readable for EthernetSocket<T, N>
+where
+    T: Readable,
+{
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.socket.readable().await.map_err(Error::Io)
+    }
+}
+
+impl<T, const N: usize> Writable for EthernetSocket<T, N>
+where
+    T: Writable,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.writable().await.map_err(Error::Io)
+    }
+}
+
+impl<T, const N: usize> RawSend for EthernetSocket<T, N>
+where
+    T: RawSend,
+{
+    async fn send(&mut self, remote_mac: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+        let mut buf = MaybeUninit::<[u8; N]>::uninit();
+        let buf = unsafe { buf.assume_init_mut() };
+
+        let frame = raw::eth::encode(
+            buf,
+            self.local_mac,
+            remote_mac,
+            self.ether_type,
+            self.vlan,
+            |buf| {
+                if data.len() <= buf.len() {
+                    buf[..data.len()].copy_from_slice(data);
+
+                    Ok(data.len())
+                } else {
+                    Err(raw::Error::BufferOverflow)
+                }
+            },
+        )?;
+
+        self.socket.send(remote_mac, frame).await.map_err(Error::Io)
+    }
+}
+
+impl<T, const N: usize> RawSplit for EthernetSocket<T, N>
+where
+    T: RawSplit,
+{
+    type Receive<'a>
+        = EthernetSocket<T::Receive<'a>, N>
+    where
+        Self: 'a;
+    type Send<'a>
+        = EthernetSocket<T::Send<'a>, N>
+    where
+        Self: 'a;
+
+    fn split(&mut self) -> (Self::Receive<'_>, Self::Send<'_>) {
+        let (receive, send) = self.socket.split();
+
+        (
+            EthernetSocket::new_with_vlan(receive, self.local_mac, self.ether_type, self.vlan),
+            EthernetSocket::new_with_vlan(send, self.local_mac, self.ether_type, self.vlan),
         )
     }
 }
 
 /// Sends a UDP packet to a remote peer identified by its MAC address
+///
+/// Both IPv4 and IPv6 are supported, as long as the local and the remote addresses are of the same IP version.
 pub async fn udp_send<T: RawSend, const N: usize>(
     mut socket: T,
     local: SocketAddr,
@@ -175,14 +359,14 @@ pub async fn udp_send<T: RawSend, const N: usize>(
     remote_mac: MacAddr,
     data: &[u8],
 ) -> Result<(), Error<T::Error>> {
-    let (SocketAddr::V4(local), SocketAddr::V4(remote)) = (local, remote) else {
-        Err(Error::UnsupportedProtocol)?
-    };
+    if local.is_ipv4() != remote.is_ipv4() {
+        Err(Error::UnsupportedProtocol)?;
+    }
 
     let mut buf = MaybeUninit::<[u8; N]>::uninit();
     let buf = unsafe { buf.assume_init_mut() };
 
-    let data = raw::ip_udp_encode(buf, local, remote, |buf| {
+    let data = raw::ip_udp_encode_any(buf, local, remote, |buf| {
         if data.len() <= buf.len() {
             buf[..data.len()].copy_from_slice(data);
 
@@ -197,9 +381,29 @@ pub async fn udp_send<T: RawSend, const N: usize>(
 
 /// Receives a UDP packet from a remote peer
 pub async fn udp_receive<T: RawReceive, const N: usize>(
+    socket: T,
+    filter_local: Option<SocketAddrV4>,
+    filter_remote: Option<SocketAddrV4>,
+    buffer: &mut [u8],
+) -> Result<(usize, SocketAddr, SocketAddr, MacAddr), Error<T::Error>> {
+    udp_receive_with_mode::<_, N>(
+        socket,
+        filter_local,
+        filter_remote,
+        ChecksumMode::Strict,
+        buffer,
+    )
+    .await
+}
+
+/// Receives a UDP packet from a remote peer, validating the checksums as per the provided mode
+///
+/// In `ChecksumMode::Lenient` mode, packets with invalid checksums are received as any other packet, but with a warning.
+pub async fn udp_receive_with_mode<T: RawReceive, const N: usize>(
     mut socket: T,
     filter_local: Option<SocketAddrV4>,
     filter_remote: Option<SocketAddrV4>,
+    checksum_mode: ChecksumMode,
     buffer: &mut [u8],
 ) -> Result<(usize, SocketAddr, SocketAddr, MacAddr), Error<T::Error>> {
     let mut buf = MaybeUninit::<[u8; N]>::uninit();
@@ -208,8 +412,13 @@ pub async fn udp_receive<T: RawReceive, const N: usize>(
     let (len, local, remote, remote_mac) = loop {
         let (len, remote_mac) = socket.receive(buf).await.map_err(Error::Io)?;
 
-        match raw::ip_udp_decode(&buf[..len], filter_remote, filter_local) {
-            Ok(Some((remote, local, data))) => {
+        match raw::ip_udp_decode_with_mode(&buf[..len], filter_remote, filter_local, checksum_mode)
+        {
+            Ok(Some((remote, local, data, status))) => {
+                if status == ChecksumStatus::Invalid {
+                    warn!("Received a UDP packet from {remote} with an invalid checksum");
+                }
+
                 if data.len() > buffer.len() {
                     Err(Error::RawError(raw::Error::BufferOverflow))?;
                 }
@@ -231,3 +440,118 @@ pub async fn udp_receive<T: RawReceive, const N: usize>(
         remote_mac,
     ))
 }
+
+/// Sends an ARP packet to the peer with the provided MAC address (or to all peers, if `remote_mac` is the broadcast address)
+///
+/// The socket should be a raw socket bound to the ARP EtherType (`ArpPacket::ETHER_TYPE`).
+pub async fn arp_send<T: RawSend>(
+    mut socket: T,
+    remote_mac: MacAddr,
+    packet: &ArpPacket,
+) -> Result<(), Error<T::Error>> {
+    let mut buf = [0; ArpPacket::SIZE];
+
+    let data = packet.encode(&mut buf)?;
+
+    socket.send(remote_mac, data).await.map_err(Error::Io)
+}
+
+/// Broadcasts an ARP probe for the provided address, as per RFC 5227
+///
+/// Any host using the address is expected to answer with an ARP reply (see `arp_receive`).
+pub async fn arp_probe<T: RawSend>(
+    socket: T,
+    local_mac: MacAddr,
+    ip: Ipv4Addr,
+) -> Result<(), Error<T::Error>> {
+    arp_send(socket, ARP_BROADCAST, &ArpPacket::probe(local_mac, ip)).await
+}
+
+/// Broadcasts a gratuitous ARP announcement for the provided address, as per RFC 5227
+///
+/// Useful once an address is assigned (or the MAC address of the interface changes),
+/// so that the neighbors update their ARP caches.
+pub async fn arp_announce<T: RawSend>(
+    socket: T,
+    local_mac: MacAddr,
+    ip: Ipv4Addr,
+) -> Result<(), Error<T::Error>> {
+    arp_send(
+        socket,
+        ARP_BROADCAST,
+        &ArpPacket::announcement(local_mac, ip),
+    )
+    .await
+}
+
+/// Receives an ARP packet, skipping over malformed packets and packets for other hardware or protocol types
+pub async fn arp_receive<T: RawReceive>(mut socket: T) -> Result<ArpPacket, Error<T::Error>> {
+    // Large enough for an ARP packet, including the padding up to the minimum Ethernet frame size
+    let mut buf = [0; 64];
+
+    loop {
+        let (len, _remote_mac) = socket.receive(&mut buf).await.map_err(Error::Io)?;
+
+        match ArpPacket::decode(&buf[..core::cmp::min(len, buf.len())]) {
+            Ok(packet) => break Ok(packet),
+            Err(raw::Error::InvalidFormat) | Err(raw::Error::DataUnderflow) => continue,
+            Err(other) => Err(other)?,
+        }
+    }
+}
+
+const ARP_BROADCAST: MacAddr = [0xff; 6];
+
+/// Sends an IGMP membership report (join) for the provided multicast group
+///
+/// Useful on platforms where neither the OS nor the IP stack announces the multicast group
+/// memberships of raw-socket-based services (i.e. mDNS or SSDP) to the multicast routers and snooping switches.
+/// Since memberships expire, the report should be re-sent in response to (or periodically in place of) IGMP queries.
+pub async fn igmp_join<T: RawSend, const N: usize>(
+    socket: T,
+    local: Ipv4Addr,
+    group: Ipv4Addr,
+    version: IgmpVersion,
+) -> Result<(), Error<T::Error>> {
+    let mut record_buf = [0; igmp::GroupRecords::RECORD_SIZE];
+
+    igmp_send::<_, N>(
+        socket,
+        local,
+        &IgmpMessage::join(version, group, &mut record_buf),
+    )
+    .await
+}
+
+/// Sends an IGMP leave message for the provided multicast group
+pub async fn igmp_leave<T: RawSend, const N: usize>(
+    socket: T,
+    local: Ipv4Addr,
+    group: Ipv4Addr,
+    version: IgmpVersion,
+) -> Result<(), Error<T::Error>> {
+    let mut record_buf = [0; igmp::GroupRecords::RECORD_SIZE];
+
+    igmp_send::<_, N>(
+        socket,
+        local,
+        &IgmpMessage::leave(version, group, &mut record_buf),
+    )
+    .await
+}
+
+async fn igmp_send<T: RawSend, const N: usize>(
+    mut socket: T,
+    local: Ipv4Addr,
+    message: &IgmpMessage<'_>,
+) -> Result<(), Error<T::Error>> {
+    let mut buf = MaybeUninit::<[u8; N]>::uninit();
+    let buf = unsafe { buf.assume_init_mut() };
+
+    let data = igmp::encode(buf, local, message)?;
+
+    socket
+        .send(igmp::multicast_mac(message.destination()), data)
+        .await
+        .map_err(Error::Io)
+}
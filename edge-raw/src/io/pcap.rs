@@ -0,0 +1,200 @@
+use core::time::Duration;
+
+use embedded_io_async::{ErrorType, Write};
+
+use edge_nal::{MacAddr, RawReceive, RawSend, Readable, Writable};
+
+use log::warn;
+
+use crate::pcap::{LinkType, PcapHeader, PcapRecordHeader};
+
+/// The default maximum number of bytes captured per frame
+pub const DEFAULT_SNAPLEN: u32 = 65535;
+
+/// A writer of captured frames in the PCAP file format
+///
+/// The PCAP header is written lazily, just before the first frame.
+pub struct PcapWriter<W> {
+    writer: W,
+    header: PcapHeader,
+    started: bool,
+}
+
+impl<W> PcapWriter<W>
+where
+    W: Write,
+{
+    /// Create a new writer of frames of the provided link type, capturing up to `DEFAULT_SNAPLEN` bytes per frame
+    pub const fn new(writer: W, link_type: LinkType) -> Self {
+        Self::new_with_snaplen(writer, link_type, DEFAULT_SNAPLEN)
+    }
+
+    /// Create a new writer of frames of the provided link type, capturing up to `snaplen` bytes per frame
+    pub const fn new_with_snaplen(writer: W, link_type: LinkType, snaplen: u32) -> Self {
+        Self {
+            writer,
+            header: PcapHeader::new(snaplen, link_type),
+            started: false,
+        }
+    }
+
+    /// Write the provided frame, captured at the provided time (since the UNIX epoch)
+    pub async fn write(&mut self, timestamp: Duration, frame: &[u8]) -> Result<(), W::Error> {
+        if !self.started {
+            let mut buf = [0; PcapHeader::SIZE];
+            let header = self.header.encode(&mut buf).unwrap();
+
+            self.writer.write_all(header).await?;
+            self.started = true;
+        }
+
+        let record = PcapRecordHeader::new(timestamp, frame.len(), self.header.snaplen);
+
+        let mut buf = [0; PcapRecordHeader::SIZE];
+        let record_header = record.encode(&mut buf).unwrap();
+
+        self.writer.write_all(record_header).await?;
+        self.writer
+            .write_all(&frame[..record.captured_len as usize])
+            .await
+    }
+
+    /// Flush the underlying writer
+    pub async fn flush(&mut self) -> Result<(), W::Error> {
+        self.writer.flush().await
+    }
+
+    /// Return the underlying writer
+    pub fn release(self) -> W {
+        self.writer
+    }
+}
+
+/// A raw socket wrapper recording all frames sent and received via the wrapped socket with a `PcapWriter`
+///
+/// The timestamps of the frames are provided by the `clock` callback, as a duration since the UNIX epoch
+/// (see `system_time` for a clock usable with STD). Devices without a wall clock might as well return the time
+/// since boot, as only the relative timestamps matter for analysing a capture.
+///
+/// Failing to record a frame is logged but is otherwise ignored, so that the capture does not interfere with the traffic.
+pub struct PcapSocket<T, W, C> {
+    socket: T,
+    writer: PcapWriter<W>,
+    clock: C,
+}
+
+impl<T, W, C> PcapSocket<T, W, C>
+where
+    W: Write,
+    C: FnMut() -> Duration,
+{
+    /// Create a new wrapper around the provided socket
+    pub const fn new(socket: T, writer: PcapWriter<W>, clock: C) -> Self {
+        Self {
+            socket,
+            writer,
+            clock,
+        }
+    }
+
+    /// Return the wrapped socket and the writer
+    pub fn release(self) -> (T, PcapWriter<W>) {
+        (self.socket, self.writer)
+    }
+
+    async fn record(&mut self, frame: &[u8]) {
+        let timestamp = (self.clock)();
+
+        if let Err(err) = self.writer.write(timestamp, frame).await {
+            warn!("Recording a frame of {} bytes failed: {err:?}", frame.len());
+        }
+    }
+}
+
+impl<T, W, C> ErrorType for PcapSocket<T, W, C>
+where
+    T: ErrorType,
+{
+    type Error = T::Error;
+}
+
+impl<T, W, C> RawReceive for PcapSocket<T, W, C>
+where
+    T: RawReceive,
+    W: Write,
+    C: FnMut() -> Duration,
+{
+    async fn receive(&mut self, buffer: &mut [u8]) -> Result<(usize, MacAddr), Self::Error> {
+        let (len, mac) = self.socket.receive(buffer).await?;
+
+        self.record(&buffer[..core::cmp::min(len, buffer.len())])
+            .await;
+
+        Ok((len, mac))
+    }
+}
+
+impl<T, W, C> Readable for PcapSocket<T, W, C>
+where
+    T: Readable,
+{
+    async fn readable(&mut self) -> Result<(), Self::Error> {
+        self.socket.readable().await
+    }
+}
+
+impl<T, W, C> Writable for PcapSocket<T, W, C>
+where
+    T: Writable,
+{
+    async fn writable(&mut self) -> Result<(), Self::Error> {
+        self.socket.writable().await
+    }
+}
+
+impl<T, W, C> RawSend for PcapSocket<T, W, C>
+where
+    T: RawSend,
+    W: Write,
+    C: FnMut() -> Duration,
+{
+    async fn send(&mut self, addr: MacAddr, data: &[u8]) -> Result<(), Self::Error> {
+        self.record(data).await;
+
+        self.socket.send(addr, data).await
+    }
+}
+
+/// A clock for `PcapSocket` returning the system time
+#[cfg(feature = "std")]
+pub fn system_time() -> Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// An adapter of a blocking `std::io::Write` implementation (like `std::fs::File`) to `embedded_io_async::Write`,
+/// so that captures can be written directly to a file
+///
+/// Note that the writes block the executor, which is usually fine for local files.
+#[cfg(feature = "std")]
+pub struct BlockingWriter<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W> ErrorType for BlockingWriter<W> {
+    type Error = std::io::Error;
+}
+
+#[cfg(feature = "std")]
+impl<W> Write for BlockingWriter<W>
+where
+    W: std::io::Write,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write(buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
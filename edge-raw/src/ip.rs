@@ -4,7 +4,7 @@ use core::net::Ipv4Addr;
 
 use super::bytes::{BytesIn, BytesOut};
 
-use super::{checksum_accumulate, checksum_finish, Error};
+use super::{checksum_accumulate, checksum_finish, ChecksumMode, ChecksumStatus, Error};
 
 #[allow(clippy::type_complexity)]
 pub fn decode(
@@ -19,6 +19,26 @@ pub fn decode(
     Ok(data)
 }
 
+#[allow(clippy::type_complexity)]
+pub fn decode_with_mode(
+    packet: &[u8],
+    filter_src: Ipv4Addr,
+    filter_dst: Ipv4Addr,
+    filter_proto: Option<u8>,
+    mode: ChecksumMode,
+) -> Result<Option<(Ipv4Addr, Ipv4Addr, u8, &[u8], ChecksumStatus)>, Error> {
+    let data = Ipv4PacketHeader::decode_with_payload_mode(
+        packet,
+        filter_src,
+        filter_dst,
+        filter_proto,
+        mode,
+    )?
+    .map(|(hdr, payload, status)| (hdr.src, hdr.dst, hdr.p, payload, status));
+
+    Ok(data)
+}
+
 pub fn encode<F>(
     buf: &mut [u8],
     src: Ipv4Addr,
@@ -168,6 +188,28 @@ impl Ipv4PacketHeader {
         filter_dst: Ipv4Addr,
         filter_proto: Option<u8>,
     ) -> Result<Option<(Self, &[u8])>, Error> {
+        let data = Self::decode_with_payload_mode(
+            packet,
+            filter_src,
+            filter_dst,
+            filter_proto,
+            ChecksumMode::Strict,
+        )?
+        .map(|(hdr, payload, _)| (hdr, payload));
+
+        Ok(data)
+    }
+
+    /// Decodes the provided packet into a header and a payload slice, validating the header checksum
+    /// as per the provided mode
+    #[allow(clippy::type_complexity)]
+    pub fn decode_with_payload_mode(
+        packet: &[u8],
+        filter_src: Ipv4Addr,
+        filter_dst: Ipv4Addr,
+        filter_proto: Option<u8>,
+        mode: ChecksumMode,
+    ) -> Result<Option<(Self, &[u8], ChecksumStatus)>, Error> {
         let hdr = Self::decode(packet)?;
         if hdr.version == 4 {
             // IPv4
@@ -195,9 +237,7 @@ impl Ipv4PacketHeader {
 
             trace!("IP header decoded, total_size={}, src={}, dst={}, hlen={}, size={}, checksum={}, ours={}", packet.len(), hdr.src, hdr.dst, hdr.hlen, hdr.len, hdr.sum, checksum);
 
-            if checksum != hdr.sum {
-                Err(Error::InvalidChecksum)?;
-            }
+            let status = ChecksumStatus::verify(mode, checksum, hdr.sum)?;
 
             let packet = &packet[..len];
             let hdr_len = hdr.hlen as usize;
@@ -205,7 +245,7 @@ impl Ipv4PacketHeader {
                 Err(Error::DataUnderflow)?;
             }
 
-            Ok(Some((hdr, &packet[hdr_len..])))
+            Ok(Some((hdr, &packet[hdr_len..], status)))
         } else {
             Err(Error::InvalidFormat)
         }
@@ -229,3 +269,364 @@ impl Ipv4PacketHeader {
         checksum_finish(sum)
     }
 }
+
+/// A zero-copy view over an encoded IPv4 packet, providing typed access to the header fields and to the payload
+///
+/// Wrapping a `&mut [u8]` (or any other `AsMut<[u8]>` buffer) additionally allows editing the packet in place,
+/// e.g. when rewriting addresses or decrementing the TTL of a forwarded packet,
+/// in which case the checksum should be refreshed with `fill_checksum` afterwards.
+#[derive(Clone, Debug)]
+pub struct Ipv4PacketRef<T>(T);
+
+impl<T> Ipv4PacketRef<T>
+where
+    T: AsRef<[u8]>,
+{
+    /// Wrap the provided buffer without validating it
+    ///
+    /// The accessors panic if the buffer is shorter than the fields they access.
+    pub const fn new_unchecked(buffer: T) -> Self {
+        Self(buffer)
+    }
+
+    /// Wrap the provided buffer, validating that it contains an IPv4 packet with consistent header and total lengths
+    ///
+    /// The checksum is not validated; see `verify_checksum`.
+    pub fn new_checked(buffer: T) -> Result<Self, Error> {
+        let packet = Self(buffer);
+        let data = packet.0.as_ref();
+
+        if data.len() < Ipv4PacketHeader::MIN_SIZE {
+            Err(Error::DataUnderflow)?;
+        }
+
+        if packet.version() != 4 {
+            Err(Error::InvalidFormat)?;
+        }
+
+        let hlen = packet.hlen() as usize;
+        let len = packet.len() as usize;
+
+        if hlen < Ipv4PacketHeader::MIN_SIZE || len < hlen {
+            Err(Error::InvalidFormat)?;
+        }
+
+        if data.len() < len {
+            Err(Error::DataUnderflow)?;
+        }
+
+        Ok(packet)
+    }
+
+    /// Return the wrapped buffer
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+
+    /// Version
+    pub fn version(&self) -> u8 {
+        self.0.as_ref()[0] >> 4
+    }
+
+    /// Header length
+    pub fn hlen(&self) -> u8 {
+        (self.0.as_ref()[0] & 0x0f) * 4
+    }
+
+    /// Type of service
+    pub fn tos(&self) -> u8 {
+        self.0.as_ref()[1]
+    }
+
+    /// Total length
+    pub fn len(&self) -> u16 {
+        self.u16_at(2)
+    }
+
+    /// Return `true` if the packet has no payload
+    pub fn is_empty(&self) -> bool {
+        self.len() == self.hlen() as u16
+    }
+
+    /// Identification
+    pub fn id(&self) -> u16 {
+        self.u16_at(4)
+    }
+
+    /// Fragment offset field, including the `Ipv4PacketHeader::IP_DF` and `Ipv4PacketHeader::IP_MF` flags
+    pub fn off(&self) -> u16 {
+        self.u16_at(6)
+    }
+
+    /// Time to live
+    pub fn ttl(&self) -> u8 {
+        self.0.as_ref()[8]
+    }
+
+    /// Protocol
+    pub fn p(&self) -> u8 {
+        self.0.as_ref()[9]
+    }
+
+    /// Checksum
+    pub fn sum(&self) -> u16 {
+        self.u16_at(10)
+    }
+
+    /// Source address
+    pub fn src(&self) -> Ipv4Addr {
+        self.addr_at(12)
+    }
+
+    /// Dest address
+    pub fn dst(&self) -> Ipv4Addr {
+        self.addr_at(16)
+    }
+
+    /// The header, including the options (if any)
+    pub fn header(&self) -> &[u8] {
+        &self.0.as_ref()[..self.hlen() as usize]
+    }
+
+    /// The payload, excluding any trailing padding of the buffer beyond the total length of the packet
+    pub fn payload(&self) -> &[u8] {
+        &self.0.as_ref()[self.hlen() as usize..self.len() as usize]
+    }
+
+    /// Return `true` if the header checksum is valid
+    pub fn verify_checksum(&self) -> bool {
+        Ipv4PacketHeader::checksum(self.0.as_ref()) == self.sum()
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.0.as_ref()[offset], self.0.as_ref()[offset + 1]])
+    }
+
+    fn addr_at(&self, offset: usize) -> Ipv4Addr {
+        let data = self.0.as_ref();
+
+        Ipv4Addr::new(
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        )
+    }
+}
+
+impl<T> Ipv4PacketRef<T>
+where
+    T: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Set the type of service
+    pub fn set_tos(&mut self, tos: u8) {
+        self.0.as_mut()[1] = tos;
+    }
+
+    /// Set the identification
+    pub fn set_id(&mut self, id: u16) {
+        self.0.as_mut()[4..6].copy_from_slice(&id.to_be_bytes());
+    }
+
+    /// Set the time to live
+    pub fn set_ttl(&mut self, ttl: u8) {
+        self.0.as_mut()[8] = ttl;
+    }
+
+    /// Set the source address
+    pub fn set_src(&mut self, src: Ipv4Addr) {
+        self.0.as_mut()[12..16].copy_from_slice(&src.octets());
+    }
+
+    /// Set the dest address
+    pub fn set_dst(&mut self, dst: Ipv4Addr) {
+        self.0.as_mut()[16..20].copy_from_slice(&dst.octets());
+    }
+
+    /// The payload, for editing in place
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let range = self.hlen() as usize..self.len() as usize;
+
+        &mut self.0.as_mut()[range]
+    }
+
+    /// Recompute the header checksum, after the header was edited
+    pub fn fill_checksum(&mut self) {
+        let checksum = Ipv4PacketHeader::checksum(self.0.as_ref());
+
+        Ipv4PacketHeader::inject_checksum(self.0.as_mut(), checksum);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::udp::{UdpPacketHeader, UdpPacketRef};
+
+    use super::*;
+
+    /// A DNS query for `example.com` sent from 192.168.1.10:53000 to 8.8.8.8:53
+    const DNS_QUERY: [u8; 57] = [
+        0x45, 0x00, 0x00, 0x39, 0xab, 0xcd, 0x40, 0x00, //
+        0x40, 0x11, 0xbd, 0x24, 0xc0, 0xa8, 0x01, 0x0a, //
+        0x08, 0x08, 0x08, 0x08, 0xcf, 0x08, 0x00, 0x35, //
+        0x00, 0x25, 0x7d, 0x02, 0x12, 0x34, 0x01, 0x00, //
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, //
+        0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, //
+        0x01, //
+    ];
+
+    /// The same query, after being forwarded by a NAT router as 100.64.0.5:40000
+    const DNS_QUERY_NAT: [u8; 57] = [
+        0x45, 0x00, 0x00, 0x39, 0xab, 0xcd, 0x40, 0x00, //
+        0x3f, 0x11, 0x1b, 0x92, 0x64, 0x40, 0x00, 0x05, //
+        0x08, 0x08, 0x08, 0x08, 0x9c, 0x40, 0x00, 0x35, //
+        0x00, 0x25, 0x0d, 0x38, 0x12, 0x34, 0x01, 0x00, //
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, //
+        0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, //
+        0x01, //
+    ];
+
+    #[test]
+    fn test_packet_ref() {
+        let ip = Ipv4PacketRef::new_checked(&DNS_QUERY[..]).unwrap();
+
+        assert_eq!(ip.version(), 4);
+        assert_eq!(ip.hlen(), 20);
+        assert_eq!(ip.tos(), 0);
+        assert_eq!(ip.len(), 57);
+        assert_eq!(ip.id(), 0xabcd);
+        assert_eq!(ip.off(), Ipv4PacketHeader::IP_DF);
+        assert_eq!(ip.ttl(), 64);
+        assert_eq!(ip.p(), UdpPacketHeader::PROTO);
+        assert_eq!(ip.sum(), 0xbd24);
+        assert_eq!(ip.src(), Ipv4Addr::new(192, 168, 1, 10));
+        assert_eq!(ip.dst(), Ipv4Addr::new(8, 8, 8, 8));
+        assert_eq!(ip.header(), &DNS_QUERY[..20]);
+        assert!(ip.verify_checksum());
+
+        let udp = UdpPacketRef::new_checked(ip.payload()).unwrap();
+
+        assert_eq!(udp.src(), 53000);
+        assert_eq!(udp.dst(), 53);
+        assert_eq!(udp.len(), 37);
+        assert_eq!(udp.sum(), 0x7d02);
+        assert_eq!(udp.payload(), &DNS_QUERY[28..]);
+        assert!(udp.verify_checksum(ip.src(), ip.dst()));
+        assert!(!udp.verify_checksum(Ipv4Addr::new(192, 168, 1, 11), ip.dst()));
+
+        // The views agree with the owned headers
+        let (header, payload) = Ipv4PacketHeader::decode_with_payload(
+            &DNS_QUERY,
+            Ipv4Addr::UNSPECIFIED,
+            Ipv4Addr::UNSPECIFIED,
+            Some(UdpPacketHeader::PROTO),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(header.id, ip.id());
+        assert_eq!(header.ttl, ip.ttl());
+        assert_eq!(payload, ip.payload());
+
+        // Trailing padding (e.g. of a short Ethernet frame) is not part of the payloads
+        let mut padded = [0; 64];
+        padded[..DNS_QUERY.len()].copy_from_slice(&DNS_QUERY);
+
+        let ip = Ipv4PacketRef::new_checked(&padded[..]).unwrap();
+
+        assert_eq!(ip.payload(), &DNS_QUERY[20..]);
+        assert!(ip.verify_checksum());
+    }
+
+    #[test]
+    fn test_packet_ref_edit() {
+        let mut packet = DNS_QUERY;
+
+        let mut ip = Ipv4PacketRef::new_checked(&mut packet[..]).unwrap();
+
+        let src = Ipv4Addr::new(100, 64, 0, 5);
+        let dst = ip.dst();
+
+        ip.set_ttl(ip.ttl() - 1);
+        ip.set_src(src);
+
+        assert!(!ip.verify_checksum());
+
+        ip.fill_checksum();
+
+        assert!(ip.verify_checksum());
+
+        let mut udp = UdpPacketRef::new_checked(ip.payload_mut()).unwrap();
+
+        udp.set_src(40000);
+
+        assert!(!udp.verify_checksum(src, dst));
+
+        udp.fill_checksum(src, dst);
+
+        assert!(udp.verify_checksum(src, dst));
+
+        assert_eq!(packet, DNS_QUERY_NAT);
+    }
+
+    #[test]
+    fn test_packet_ref_invalid() {
+        assert!(matches!(
+            Ipv4PacketRef::new_checked(&DNS_QUERY[..19]),
+            Err(Error::DataUnderflow)
+        ));
+
+        // Truncated below the total length
+        assert!(matches!(
+            Ipv4PacketRef::new_checked(&DNS_QUERY[..56]),
+            Err(Error::DataUnderflow)
+        ));
+
+        // Not IPv4
+        let mut packet = DNS_QUERY;
+        packet[0] = 0x65;
+
+        assert!(matches!(
+            Ipv4PacketRef::new_checked(&packet[..]),
+            Err(Error::InvalidFormat)
+        ));
+
+        // Header length below the minimum
+        packet[0] = 0x44;
+
+        assert!(matches!(
+            Ipv4PacketRef::new_checked(&packet[..]),
+            Err(Error::InvalidFormat)
+        ));
+
+        // Total length below the header length
+        packet[0] = 0x45;
+        packet[2..4].copy_from_slice(&16_u16.to_be_bytes());
+
+        assert!(matches!(
+            Ipv4PacketRef::new_checked(&packet[..]),
+            Err(Error::InvalidFormat)
+        ));
+
+        assert!(matches!(
+            UdpPacketRef::new_checked(&DNS_QUERY[20..27]),
+            Err(Error::DataUnderflow)
+        ));
+
+        assert!(matches!(
+            UdpPacketRef::new_checked(&DNS_QUERY[20..56]),
+            Err(Error::DataUnderflow)
+        ));
+
+        // UDP length below the header size
+        let mut packet = DNS_QUERY;
+        packet[24..26].copy_from_slice(&4_u16.to_be_bytes());
+
+        assert!(matches!(
+            UdpPacketRef::new_checked(&packet[20..]),
+            Err(Error::InvalidFormat)
+        ));
+    }
+}
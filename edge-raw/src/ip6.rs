@@ -0,0 +1,411 @@
+use log::trace;
+
+use core::net::Ipv6Addr;
+
+use super::bytes::{BytesIn, BytesOut};
+
+use super::Error;
+
+#[allow(clippy::type_complexity)]
+pub fn decode(
+    packet: &[u8],
+    filter_src: Ipv6Addr,
+    filter_dst: Ipv6Addr,
+    filter_proto: Option<u8>,
+) -> Result<Option<(Ipv6Addr, Ipv6Addr, u8, &[u8])>, Error> {
+    let data = Ipv6PacketHeader::decode_with_payload(packet, filter_src, filter_dst, filter_proto)?
+        .map(|(hdr, proto, payload)| (hdr.src, hdr.dst, proto, payload));
+
+    Ok(data)
+}
+
+pub fn encode<F>(
+    buf: &mut [u8],
+    src: Ipv6Addr,
+    dst: Ipv6Addr,
+    proto: u8,
+    encoder: F,
+) -> Result<&[u8], Error>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+{
+    let mut hdr = Ipv6PacketHeader::new(src, dst, proto);
+
+    hdr.encode_with_payload(buf, encoder)
+}
+
+/// Represents a parsed IPv6 header, as per RFC 8200
+#[derive(Clone, Debug)]
+pub struct Ipv6PacketHeader {
+    /// Version
+    pub version: u8,
+    /// Traffic class
+    pub traffic_class: u8,
+    /// Flow label (20 bits)
+    pub flow_label: u32,
+    /// Payload length, including the extension headers
+    pub len: u16,
+    /// Next header; either the protocol of the payload, or the type of the first extension header
+    pub next_header: u8,
+    /// Hop limit
+    pub hop_limit: u8,
+    /// Source address
+    pub src: Ipv6Addr,
+    /// Dest address
+    pub dst: Ipv6Addr,
+}
+
+impl Ipv6PacketHeader {
+    pub const SIZE: usize = 40;
+
+    pub const HOP_BY_HOP: u8 = 0;
+    pub const ROUTING: u8 = 43;
+    pub const FRAGMENT: u8 = 44;
+    pub const AUTHENTICATION: u8 = 51;
+    pub const DESTINATION_OPTIONS: u8 = 60;
+    pub const NO_NEXT_HEADER: u8 = 59;
+
+    /// Create a new header instance
+    pub fn new(src: Ipv6Addr, dst: Ipv6Addr, proto: u8) -> Self {
+        Self {
+            version: 6,
+            traffic_class: 0,
+            flow_label: 0,
+            len: 0,
+            next_header: proto,
+            hop_limit: 64,
+            src,
+            dst,
+        }
+    }
+
+    /// Decodes the header from a byte slice
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        let vtf = u32::from_be_bytes(bytes.arr()?);
+
+        Ok(Self {
+            version: (vtf >> 28) as u8,
+            traffic_class: (vtf >> 20) as u8,
+            flow_label: vtf & 0x000f_ffff,
+            len: u16::from_be_bytes(bytes.arr()?),
+            next_header: bytes.byte()?,
+            hop_limit: bytes.byte()?,
+            src: u128::from_be_bytes(bytes.arr()?).into(),
+            dst: u128::from_be_bytes(bytes.arr()?).into(),
+        })
+    }
+
+    /// Encodes the header into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        let vtf = ((self.version as u32) << 28)
+            | ((self.traffic_class as u32) << 20)
+            | (self.flow_label & 0x000f_ffff);
+
+        bytes
+            .push(&u32::to_be_bytes(vtf))?
+            .push(&u16::to_be_bytes(self.len))?
+            .byte(self.next_header)?
+            .byte(self.hop_limit)?
+            .push(&u128::to_be_bytes(self.src.into()))?
+            .push(&u128::to_be_bytes(self.dst.into()))?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+
+    /// Encodes the header and the provided payload into the provided buf slice
+    ///
+    /// No extension headers are generated, so `next_header` should be the protocol of the payload.
+    pub fn encode_with_payload<'o, F>(
+        &mut self,
+        buf: &'o mut [u8],
+        encoder: F,
+    ) -> Result<&'o [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        if buf.len() < Self::SIZE {
+            Err(Error::BufferOverflow)?;
+        }
+
+        let (hdr_buf, payload_buf) = buf.split_at_mut(Self::SIZE);
+
+        let payload_len = encoder(payload_buf)?;
+        if payload_len > u16::MAX as usize {
+            Err(Error::BufferOverflow)?;
+        }
+
+        self.len = payload_len as _;
+
+        let hdr_len = self.encode(hdr_buf)?.len();
+        assert_eq!(hdr_len, Self::SIZE);
+
+        Ok(&buf[..Self::SIZE + payload_len])
+    }
+
+    /// Decodes the provided packet into a header, the protocol of the payload and a payload slice
+    ///
+    /// Any extension headers preceding the payload are skipped (see `skip_extension_headers`).
+    #[allow(clippy::type_complexity)]
+    pub fn decode_with_payload(
+        packet: &[u8],
+        filter_src: Ipv6Addr,
+        filter_dst: Ipv6Addr,
+        filter_proto: Option<u8>,
+    ) -> Result<Option<(Self, u8, &[u8])>, Error> {
+        let hdr = Self::decode(packet)?;
+        if hdr.version != 6 {
+            Err(Error::InvalidFormat)?;
+        }
+
+        if !filter_src.is_unspecified() && filter_src != hdr.src {
+            return Ok(None);
+        }
+
+        if !filter_dst.is_unspecified() && !hdr.dst.is_multicast() && filter_dst != hdr.dst {
+            return Ok(None);
+        }
+
+        // Jumbograms (a payload length of 0 with a Jumbo Payload hop-by-hop option) are not supported
+        let len = Self::SIZE + hdr.len as usize;
+        if packet.len() < len {
+            Err(Error::DataUnderflow)?;
+        }
+
+        let Some((proto, payload)) =
+            Self::skip_extension_headers(hdr.next_header, &packet[Self::SIZE..len])?
+        else {
+            trace!("IPv6 fragment, skipping");
+            return Ok(None);
+        };
+
+        trace!(
+            "IPv6 header decoded, total_size={}, src={}, dst={}, size={}, proto={}",
+            packet.len(),
+            hdr.src,
+            hdr.dst,
+            hdr.len,
+            proto
+        );
+
+        if let Some(filter_proto) = filter_proto {
+            if filter_proto != proto {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some((hdr, proto, payload)))
+    }
+
+    /// Skips the extension headers (hop-by-hop options, routing, fragment, authentication and destination options)
+    /// at the start of the provided payload, where `next_header` is the `next_header` field of the IPv6 header
+    ///
+    /// Returns the protocol of the upper-layer payload and the payload itself,
+    /// or `None` if the packet is a fragment of a larger packet, as fragments are not reassembled.
+    /// Atomic fragments (RFC 6946) are not part of a larger packet, so their payload is returned.
+    pub fn skip_extension_headers(
+        mut next_header: u8,
+        payload: &[u8],
+    ) -> Result<Option<(u8, &[u8])>, Error> {
+        let mut bytes = BytesIn::new(payload);
+
+        loop {
+            // The second byte of all extension headers is their length, in units which depend on the header type
+            let ext_len = || {
+                payload
+                    .get(bytes.offset() + 1)
+                    .map(|len| *len as usize)
+                    .ok_or(Error::DataUnderflow)
+            };
+
+            let len = match next_header {
+                Self::HOP_BY_HOP | Self::ROUTING | Self::DESTINATION_OPTIONS => {
+                    (ext_len()? + 1) * 8
+                }
+                Self::FRAGMENT => 8,
+                Self::AUTHENTICATION => (ext_len()? + 2) * 4,
+                _ => break,
+            };
+
+            let header = bytes.slice(len)?;
+
+            if next_header == Self::FRAGMENT {
+                // The fragment offset (in 8-byte units), followed by two reserved bits and the M (more fragments) flag
+                let offset_and_flags = u16::from_be_bytes([header[2], header[3]]);
+
+                let offset = offset_and_flags >> 3;
+                let more_fragments = offset_and_flags & 1 != 0;
+
+                if offset != 0 || more_fragments {
+                    return Ok(None);
+                }
+            }
+
+            next_header = header[0];
+        }
+
+        Ok(Some((next_header, bytes.remaining())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::net::SocketAddrV6;
+
+    use crate::udp::UdpPacketHeader;
+
+    use super::*;
+
+    /// A DHCPv6 Solicit, as sent by a client
+    const SOLICIT: [u8; 72] = [
+        0x60, 0x00, 0x00, 0x00, 0x00, 0x20, 0x11, 0x01, //
+        0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x02, 0x00, 0x00, 0xff, 0xfe, 0x00, 0x00, 0x01, //
+        0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, //
+        0x02, 0x22, 0x02, 0x23, 0x00, 0x20, 0xf1, 0x43, //
+        0x01, 0x10, 0x08, 0x74, 0x00, 0x08, 0x00, 0x02, //
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x0a, 0x00, 0x03, //
+        0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, //
+    ];
+
+    /// An MLDv2 report, with a hop-by-hop extension header carrying the Router Alert option
+    const MLD_REPORT: [u8; 76] = [
+        0x60, 0x00, 0x00, 0x00, 0x00, 0x24, 0x00, 0x01, //
+        0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x02, 0x00, 0x00, 0xff, 0xfe, 0x00, 0x00, 0x01, //
+        0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x16, //
+        0x3a, 0x00, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00, //
+        0x8f, 0x00, 0x6e, 0x0f, 0x00, 0x00, 0x00, 0x01, //
+        0x04, 0x00, 0x00, 0x00, 0xff, 0x02, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0xfb, //
+    ];
+
+    const CLIENT: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0x0200, 0x00ff, 0xfe00, 0x0001);
+
+    #[test]
+    fn test_roundtrip() {
+        let servers = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+
+        let hdr = Ipv6PacketHeader::decode(&SOLICIT).unwrap();
+        assert_eq!(hdr.version, 6);
+        assert_eq!(hdr.len, 32);
+        assert_eq!(hdr.next_header, UdpPacketHeader::PROTO);
+        assert_eq!(hdr.hop_limit, 1);
+        assert_eq!(hdr.src, CLIENT);
+        assert_eq!(hdr.dst, servers);
+
+        let (src, dst, payload) = crate::ip6_udp_decode(&SOLICIT, None, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(src, SocketAddrV6::new(CLIENT, 546, 0, 0));
+        assert_eq!(dst, SocketAddrV6::new(servers, 547, 0, 0));
+        assert_eq!(payload, &SOLICIT[48..]);
+
+        let mut buf = [0; 128];
+
+        let mut hdr = Ipv6PacketHeader {
+            hop_limit: 1,
+            ..Ipv6PacketHeader::new(CLIENT, servers, UdpPacketHeader::PROTO)
+        };
+
+        let packet = hdr
+            .encode_with_payload(&mut buf, |buf| {
+                Ok(UdpPacketHeader::new(546, 547)
+                    .encode_with_payload6(buf, CLIENT, servers, |buf| {
+                        buf[..payload.len()].copy_from_slice(payload);
+
+                        Ok(payload.len())
+                    })?
+                    .len())
+            })
+            .unwrap();
+
+        assert_eq!(packet, SOLICIT);
+    }
+
+    #[test]
+    fn test_extension_headers() {
+        let (hdr, proto, payload) = Ipv6PacketHeader::decode_with_payload(
+            &MLD_REPORT,
+            Ipv6Addr::UNSPECIFIED,
+            Ipv6Addr::UNSPECIFIED,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(hdr.next_header, Ipv6PacketHeader::HOP_BY_HOP);
+        assert_eq!(hdr.src, CLIENT);
+        assert_eq!(proto, 58);
+        assert_eq!(payload, &MLD_REPORT[48..]);
+
+        // Filtered by the protocol of the payload, rather than by the first extension header
+        assert!(Ipv6PacketHeader::decode_with_payload(
+            &MLD_REPORT,
+            Ipv6Addr::UNSPECIFIED,
+            Ipv6Addr::UNSPECIFIED,
+            Some(UdpPacketHeader::PROTO)
+        )
+        .unwrap()
+        .is_none());
+
+        assert_eq!(
+            Ipv6PacketHeader::decode_with_payload(
+                &MLD_REPORT[..44],
+                Ipv6Addr::UNSPECIFIED,
+                Ipv6Addr::UNSPECIFIED,
+                None
+            )
+            .err(),
+            Some(Error::DataUnderflow)
+        );
+    }
+
+    /// A UDP payload preceded by a fragment header with the provided offset and M flag
+    fn fragment(offset_and_flags: u16) -> [u8; 12] {
+        let [hi, lo] = offset_and_flags.to_be_bytes();
+
+        [
+            17, 0, hi, lo, 0x12, 0x34, 0x56, 0x78, 0xde, 0xad, 0xbe, 0xef,
+        ]
+    }
+
+    #[test]
+    fn test_fragments() {
+        // Atomic fragment
+        assert_eq!(
+            Ipv6PacketHeader::skip_extension_headers(Ipv6PacketHeader::FRAGMENT, &fragment(0)),
+            Ok(Some((17, &[0xde, 0xad, 0xbe, 0xef][..])))
+        );
+
+        // First fragment, non-first fragment, last fragment
+        for offset_and_flags in [0x0001, (185 << 3) | 1, 185 << 3] {
+            assert_eq!(
+                Ipv6PacketHeader::skip_extension_headers(
+                    Ipv6PacketHeader::FRAGMENT,
+                    &fragment(offset_and_flags)
+                ),
+                Ok(None)
+            );
+        }
+
+        // Reserved bits are ignored
+        assert_eq!(
+            Ipv6PacketHeader::skip_extension_headers(Ipv6PacketHeader::FRAGMENT, &fragment(0x0006)),
+            Ok(Some((17, &[0xde, 0xad, 0xbe, 0xef][..])))
+        );
+
+        assert_eq!(
+            Ipv6PacketHeader::skip_extension_headers(Ipv6PacketHeader::FRAGMENT, &fragment(0)[..6]),
+            Err(Error::DataUnderflow)
+        );
+    }
+}
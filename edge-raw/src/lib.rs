@@ -4,20 +4,26 @@
 
 use core::fmt;
 
-use core::net::{Ipv4Addr, SocketAddrV4};
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
 use self::udp::UdpPacketHeader;
 
 #[cfg(feature = "io")]
 pub mod io;
 
+pub mod arp;
 pub mod bytes;
+pub mod eth;
+pub mod igmp;
 pub mod ip;
+pub mod ip6;
+pub mod pcap;
+pub mod tcp;
 pub mod udp;
 
 use bytes::BytesIn;
 
-/// An error type for decoding and encoding IP and UDP oackets
+/// An error type for decoding and encoding Ethernet frames, and IP, IPv6, UDP, TCP, IGMP and ARP packets
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Error {
     DataUnderflow,
@@ -52,6 +58,53 @@ impl fmt::Display for Error {
 #[cfg(feature = "std")]
 impl std::error::Error for Error {}
 
+/// How the checksums of the decoded packets are validated
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum ChecksumMode {
+    /// Verify the checksums and reject packets with invalid ones with `Error::InvalidChecksum`
+    #[default]
+    Strict,
+    /// Verify the checksums, but only report invalid ones via `ChecksumStatus::Invalid`
+    ///
+    /// Useful with interfaces doing checksum offloading, which deliver the locally-originated packets
+    /// before their checksums are filled in.
+    Lenient,
+    /// Do not verify the checksums at all
+    Skip,
+}
+
+/// The outcome of validating the checksum(s) of a decoded packet
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ChecksumStatus {
+    /// The checksums are valid
+    Valid,
+    /// At least one checksum is invalid; only reported in `ChecksumMode::Lenient` mode
+    Invalid,
+    /// The checksums were not verified, either because of `ChecksumMode::Skip`,
+    /// or because the packet does not carry a checksum (i.e. UDP over IPv4 with a zero checksum)
+    Unverified,
+}
+
+impl ChecksumStatus {
+    /// Combine the status of the checksum of an outer packet with the status of the checksum of its payload
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Invalid, _) | (_, Self::Invalid) => Self::Invalid,
+            (Self::Unverified, _) | (_, Self::Unverified) => Self::Unverified,
+            _ => Self::Valid,
+        }
+    }
+
+    fn verify(mode: ChecksumMode, expected: u16, actual: u16) -> Result<Self, Error> {
+        match mode {
+            ChecksumMode::Skip => Ok(Self::Unverified),
+            _ if expected == actual => Ok(Self::Valid),
+            ChecksumMode::Strict => Err(Error::InvalidChecksum),
+            ChecksumMode::Lenient => Ok(Self::Invalid),
+        }
+    }
+}
+
 /// Decodes an IP packet and its UDP payload
 #[allow(clippy::type_complexity)]
 pub fn ip_udp_decode(
@@ -59,13 +112,77 @@ pub fn ip_udp_decode(
     filter_src: Option<SocketAddrV4>,
     filter_dst: Option<SocketAddrV4>,
 ) -> Result<Option<(SocketAddrV4, SocketAddrV4, &[u8])>, Error> {
-    if let Some((src, dst, _proto, udp_packet)) = ip::decode(
+    let data = ip_udp_decode_with_mode(packet, filter_src, filter_dst, ChecksumMode::Strict)?
+        .map(|(src, dst, payload, _)| (src, dst, payload));
+
+    Ok(data)
+}
+
+/// Decodes an IP packet and its UDP payload, validating the checksums as per the provided mode
+///
+/// The returned status covers both the IP header checksum and the UDP checksum.
+#[allow(clippy::type_complexity)]
+pub fn ip_udp_decode_with_mode(
+    packet: &[u8],
+    filter_src: Option<SocketAddrV4>,
+    filter_dst: Option<SocketAddrV4>,
+    mode: ChecksumMode,
+) -> Result<Option<(SocketAddrV4, SocketAddrV4, &[u8], ChecksumStatus)>, Error> {
+    let Some((src, dst, _proto, udp_packet, ip_status)) = ip::decode_with_mode(
         packet,
         filter_src.map(|a| *a.ip()).unwrap_or(Ipv4Addr::UNSPECIFIED),
         filter_dst.map(|a| *a.ip()).unwrap_or(Ipv4Addr::UNSPECIFIED),
         Some(UdpPacketHeader::PROTO),
+        mode,
+    )?
+    else {
+        return Ok(None);
+    };
+
+    let data = udp::decode_with_mode(
+        src,
+        dst,
+        udp_packet,
+        filter_src.map(|a| a.port()),
+        filter_dst.map(|a| a.port()),
+        mode,
+    )?
+    .map(|(src, dst, payload, udp_status)| (src, dst, payload, ip_status.and(udp_status)));
+
+    Ok(data)
+}
+
+/// Encodes an IP packet and its UDP payload
+pub fn ip_udp_encode<F>(
+    buf: &mut [u8],
+    src: SocketAddrV4,
+    dst: SocketAddrV4,
+    encoder: F,
+) -> Result<&[u8], Error>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+{
+    ip::encode(buf, *src.ip(), *dst.ip(), UdpPacketHeader::PROTO, |buf| {
+        Ok(udp::encode(buf, src, dst, encoder)?.len())
+    })
+}
+
+/// Decodes an IPv6 packet and its UDP payload
+///
+/// Any IPv6 extension headers preceding the UDP payload are skipped.
+#[allow(clippy::type_complexity)]
+pub fn ip6_udp_decode(
+    packet: &[u8],
+    filter_src: Option<SocketAddrV6>,
+    filter_dst: Option<SocketAddrV6>,
+) -> Result<Option<(SocketAddrV6, SocketAddrV6, &[u8])>, Error> {
+    if let Some((src, dst, _proto, udp_packet)) = ip6::decode(
+        packet,
+        filter_src.map(|a| *a.ip()).unwrap_or(Ipv6Addr::UNSPECIFIED),
+        filter_dst.map(|a| *a.ip()).unwrap_or(Ipv6Addr::UNSPECIFIED),
+        Some(UdpPacketHeader::PROTO),
     )? {
-        udp::decode(
+        udp::decode6(
             src,
             dst,
             udp_packet,
@@ -77,21 +194,97 @@ pub fn ip_udp_decode(
     }
 }
 
-/// Encodes an IP packet and its UDP payload
-pub fn ip_udp_encode<F>(
+/// Encodes an IPv6 packet and its UDP payload
+pub fn ip6_udp_encode<F>(
     buf: &mut [u8],
-    src: SocketAddrV4,
-    dst: SocketAddrV4,
+    src: SocketAddrV6,
+    dst: SocketAddrV6,
     encoder: F,
 ) -> Result<&[u8], Error>
 where
     F: FnOnce(&mut [u8]) -> Result<usize, Error>,
 {
-    ip::encode(buf, *src.ip(), *dst.ip(), UdpPacketHeader::PROTO, |buf| {
-        Ok(udp::encode(buf, src, dst, encoder)?.len())
+    ip6::encode(buf, *src.ip(), *dst.ip(), UdpPacketHeader::PROTO, |buf| {
+        Ok(udp::encode6(buf, src, dst, encoder)?.len())
     })
 }
 
+/// Decodes an IPv4 or an IPv6 packet (depending on the version of the packet) and its UDP payload
+///
+/// The filters - if provided - should be of the same IP version as the packets of interest;
+/// packets of the other IP version are ignored.
+#[allow(clippy::type_complexity)]
+pub fn ip_udp_decode_any(
+    packet: &[u8],
+    filter_src: Option<SocketAddr>,
+    filter_dst: Option<SocketAddr>,
+) -> Result<Option<(SocketAddr, SocketAddr, &[u8])>, Error> {
+    let version = packet.first().ok_or(Error::DataUnderflow)? >> 4;
+
+    match version {
+        4 => {
+            let (Some(filter_src), Some(filter_dst)) =
+                (filter_v4(filter_src), filter_v4(filter_dst))
+            else {
+                return Ok(None);
+            };
+
+            Ok(ip_udp_decode(packet, filter_src, filter_dst)?
+                .map(|(src, dst, data)| (SocketAddr::V4(src), SocketAddr::V4(dst), data)))
+        }
+        6 => {
+            let (Some(filter_src), Some(filter_dst)) =
+                (filter_v6(filter_src), filter_v6(filter_dst))
+            else {
+                return Ok(None);
+            };
+
+            Ok(ip6_udp_decode(packet, filter_src, filter_dst)?
+                .map(|(src, dst, data)| (SocketAddr::V6(src), SocketAddr::V6(dst), data)))
+        }
+        _ => Err(Error::InvalidFormat),
+    }
+}
+
+/// Encodes an IPv4 or an IPv6 packet (depending on the version of the addresses) and its UDP payload
+///
+/// Returns `Error::InvalidFormat` if the source and destination addresses are of different IP versions.
+pub fn ip_udp_encode_any<F>(
+    buf: &mut [u8],
+    src: SocketAddr,
+    dst: SocketAddr,
+    encoder: F,
+) -> Result<&[u8], Error>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+{
+    match (src, dst) {
+        (SocketAddr::V4(src), SocketAddr::V4(dst)) => ip_udp_encode(buf, src, dst, encoder),
+        (SocketAddr::V6(src), SocketAddr::V6(dst)) => ip6_udp_encode(buf, src, dst, encoder),
+        _ => Err(Error::InvalidFormat),
+    }
+}
+
+/// Returns `None` if the filter is an IPv6 one, which never matches IPv4 packets
+#[allow(clippy::option_option)]
+fn filter_v4(filter: Option<SocketAddr>) -> Option<Option<SocketAddrV4>> {
+    match filter {
+        None => Some(None),
+        Some(SocketAddr::V4(addr)) => Some(Some(addr)),
+        Some(SocketAddr::V6(_)) => None,
+    }
+}
+
+/// Returns `None` if the filter is an IPv4 one, which never matches IPv6 packets
+#[allow(clippy::option_option)]
+fn filter_v6(filter: Option<SocketAddr>) -> Option<Option<SocketAddrV6>> {
+    match filter {
+        None => Some(None),
+        Some(SocketAddr::V6(addr)) => Some(Some(addr)),
+        Some(SocketAddr::V4(_)) => None,
+    }
+}
+
 pub fn checksum_accumulate(bytes: &[u8], checksum_word: usize) -> u32 {
     let mut bytes = BytesIn::new(bytes);
 
@@ -0,0 +1,161 @@
+use core::time::Duration;
+
+use super::bytes::BytesOut;
+
+use super::Error;
+
+/// The link type of the captured frames, as per the `LINKTYPE_*` registry of tcpdump.org
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct LinkType(pub u32);
+
+impl LinkType {
+    /// Ethernet frames, as sent and received by the sockets wrapped with `io::EthernetSocket`
+    pub const ETHERNET: Self = Self(1);
+    /// Raw IPv4 or IPv6 packets, as sent and received by raw sockets bound to the IP EtherTypes
+    pub const RAW: Self = Self(101);
+    /// Raw IPv4 packets
+    pub const IPV4: Self = Self(228);
+    /// Raw IPv6 packets
+    pub const IPV6: Self = Self(229);
+}
+
+/// Represents the global header of a PCAP file, in the classic format readable by Wireshark and tcpdump
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PcapHeader {
+    /// The maximum number of bytes captured per frame
+    pub snaplen: u32,
+    /// The link type of all frames in the file
+    pub link_type: LinkType,
+}
+
+impl PcapHeader {
+    pub const SIZE: usize = 24;
+
+    /// The magic number of PCAP files with microsecond-resolution timestamps
+    pub const MAGIC: u32 = 0xa1b2c3d4;
+
+    const VERSION_MAJOR: u16 = 2;
+    const VERSION_MINOR: u16 = 4;
+
+    /// Create a new header instance
+    pub const fn new(snaplen: u32, link_type: LinkType) -> Self {
+        Self { snaplen, link_type }
+    }
+
+    /// Encodes the header into the provided buf slice
+    ///
+    /// The header (like the records following it) is encoded in little-endian byte order,
+    /// which the readers detect by the byte order of the magic number.
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        bytes
+            .push(&u32::to_le_bytes(Self::MAGIC))?
+            .push(&u16::to_le_bytes(Self::VERSION_MAJOR))?
+            .push(&u16::to_le_bytes(Self::VERSION_MINOR))?
+            // Time zone offset and timestamp accuracy; always 0 in practice
+            .push(&u32::to_le_bytes(0))?
+            .push(&u32::to_le_bytes(0))?
+            .push(&u32::to_le_bytes(self.snaplen))?
+            .push(&u32::to_le_bytes(self.link_type.0))?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+}
+
+/// Represents the header of a single captured frame in a PCAP file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PcapRecordHeader {
+    /// The time of the capture, since the UNIX epoch
+    pub timestamp: Duration,
+    /// The number of captured bytes of the frame, which follow the header
+    pub captured_len: u32,
+    /// The original length of the frame
+    pub len: u32,
+}
+
+impl PcapRecordHeader {
+    pub const SIZE: usize = 16;
+
+    /// Create a new header for a frame of the provided length, which is captured up to `snaplen` bytes
+    pub fn new(timestamp: Duration, len: usize, snaplen: u32) -> Self {
+        let len = len as u32;
+
+        Self {
+            timestamp,
+            captured_len: core::cmp::min(len, snaplen),
+            len,
+        }
+    }
+
+    /// Encodes the header into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        bytes
+            .push(&u32::to_le_bytes(self.timestamp.as_secs() as u32))?
+            .push(&u32::to_le_bytes(self.timestamp.subsec_micros()))?
+            .push(&u32::to_le_bytes(self.captured_len))?
+            .push(&u32::to_le_bytes(self.len))?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The global header written by `tcpdump -i eth0 -w` (snaplen of 262144 bytes, Ethernet)
+    const HEADER: [u8; PcapHeader::SIZE] = [
+        0xd4, 0xc3, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00, //
+    ];
+
+    /// The header of a 342-byte frame (a DHCP DISCOVER), captured in full at 2024-01-02 10:20:30.123456 UTC
+    const RECORD: [u8; PcapRecordHeader::SIZE] = [
+        0x6e, 0xe3, 0x93, 0x65, 0x40, 0xe2, 0x01, 0x00, //
+        0x56, 0x01, 0x00, 0x00, 0x56, 0x01, 0x00, 0x00, //
+    ];
+
+    #[test]
+    fn test_header() {
+        let mut buf = [0; PcapHeader::SIZE];
+
+        assert_eq!(
+            PcapHeader::new(262144, LinkType::ETHERNET)
+                .encode(&mut buf)
+                .unwrap(),
+            HEADER
+        );
+
+        assert_eq!(
+            PcapHeader::new(262144, LinkType::ETHERNET).encode(&mut buf[..PcapHeader::SIZE - 1]),
+            Err(Error::BufferOverflow)
+        );
+    }
+
+    #[test]
+    fn test_record_header() {
+        let timestamp = Duration::new(1704190830, 123_456_789);
+
+        let mut buf = [0; PcapRecordHeader::SIZE];
+
+        assert_eq!(
+            PcapRecordHeader::new(timestamp, 342, 262144)
+                .encode(&mut buf)
+                .unwrap(),
+            RECORD
+        );
+
+        // Frames longer than the snaplen are truncated
+        let hdr = PcapRecordHeader::new(timestamp, 342, 96);
+        assert_eq!(hdr.captured_len, 96);
+        assert_eq!(hdr.len, 342);
+    }
+}
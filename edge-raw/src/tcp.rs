@@ -0,0 +1,612 @@
+use log::trace;
+
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4};
+
+use super::bytes::{BytesIn, BytesOut};
+
+use super::{checksum_accumulate, checksum_finish, Error};
+
+#[allow(clippy::type_complexity)]
+pub fn decode(
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    packet: &[u8],
+    filter_src: Option<u16>,
+    filter_dst: Option<u16>,
+) -> Result<Option<(SocketAddrV4, SocketAddrV4, TcpPacketHeader, &[u8])>, Error> {
+    let data = TcpPacketHeader::decode_with_payload(packet, src, dst, filter_src, filter_dst)?.map(
+        |(hdr, payload)| {
+            (
+                SocketAddrV4::new(src, hdr.src),
+                SocketAddrV4::new(dst, hdr.dst),
+                hdr,
+                payload,
+            )
+        },
+    );
+
+    Ok(data)
+}
+
+/// Represents a single TCP option
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TcpOption<'a> {
+    /// End of the options list
+    End,
+    /// No-operation (padding)
+    Nop,
+    /// Maximum segment size
+    Mss(u16),
+    /// Window scale shift count
+    WindowScale(u8),
+    /// Selective acknowledgements are supported
+    SackPermitted,
+    /// Selective acknowledgement blocks; pairs of big-endian 32-bit left and right edges
+    Sack(&'a [u8]),
+    /// Timestamp value and timestamp echo reply
+    Timestamps(u32, u32),
+    /// Any other option
+    Other(u8, &'a [u8]),
+}
+
+impl<'a> TcpOption<'a> {
+    const END: u8 = 0;
+    const NOP: u8 = 1;
+    const MSS: u8 = 2;
+    const WINDOW_SCALE: u8 = 3;
+    const SACK_PERMITTED: u8 = 4;
+    const SACK: u8 = 5;
+    const TIMESTAMPS: u8 = 8;
+
+    fn decode(bytes: &mut BytesIn<'a>) -> Result<Self, Error> {
+        let kind = bytes.byte()?;
+
+        let option = match kind {
+            Self::END => Self::End,
+            Self::NOP => Self::Nop,
+            _ => {
+                let len = bytes.byte()? as usize;
+                if len < 2 {
+                    Err(Error::InvalidFormat)?;
+                }
+
+                let mut data = BytesIn::new(bytes.slice(len - 2)?);
+
+                let option = match kind {
+                    Self::MSS => Self::Mss(u16::from_be_bytes(data.remaining_arr()?)),
+                    Self::WINDOW_SCALE => Self::WindowScale(data.remaining_byte()?),
+                    Self::SACK_PERMITTED => Self::SackPermitted,
+                    Self::SACK => Self::Sack(data.remaining()),
+                    Self::TIMESTAMPS => Self::Timestamps(
+                        u32::from_be_bytes(data.arr()?),
+                        u32::from_be_bytes(data.remaining_arr()?),
+                    ),
+                    _ => Self::Other(kind, data.remaining()),
+                };
+
+                if !data.is_empty() {
+                    Err(Error::InvalidFormat)?;
+                }
+
+                option
+            }
+        };
+
+        Ok(option)
+    }
+
+    fn encode(&self, bytes: &mut BytesOut) -> Result<(), Error> {
+        match self {
+            Self::End => {
+                bytes.byte(Self::END)?;
+            }
+            Self::Nop => {
+                bytes.byte(Self::NOP)?;
+            }
+            Self::Mss(mss) => {
+                bytes
+                    .byte(Self::MSS)?
+                    .byte(4)?
+                    .push(&u16::to_be_bytes(*mss))?;
+            }
+            Self::WindowScale(shift) => {
+                bytes.byte(Self::WINDOW_SCALE)?.byte(3)?.byte(*shift)?;
+            }
+            Self::SackPermitted => {
+                bytes.byte(Self::SACK_PERMITTED)?.byte(2)?;
+            }
+            Self::Sack(blocks) => {
+                bytes
+                    .byte(Self::SACK)?
+                    .byte(Self::len(blocks)?)?
+                    .push(blocks)?;
+            }
+            Self::Timestamps(value, echo) => {
+                bytes
+                    .byte(Self::TIMESTAMPS)?
+                    .byte(10)?
+                    .push(&u32::to_be_bytes(*value))?
+                    .push(&u32::to_be_bytes(*echo))?;
+            }
+            Self::Other(kind, data) => {
+                bytes.byte(*kind)?.byte(Self::len(data)?)?.push(data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn len(data: &[u8]) -> Result<u8, Error> {
+        u8::try_from(data.len() + 2).map_err(|_| Error::InvalidFormat)
+    }
+}
+
+/// The options of a parsed TCP header
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TcpOptions<'a>(&'a [u8]);
+
+impl<'a> TcpOptions<'a> {
+    /// Create the options from their encoded form
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    /// Iterate over the options, stopping at the end-of-list option
+    ///
+    /// Malformed options are reported as errors, after which the iteration stops.
+    pub fn iter(&self) -> impl Iterator<Item = Result<TcpOption<'a>, Error>> + 'a {
+        let mut bytes = BytesIn::new(self.0);
+        let mut done = false;
+
+        core::iter::from_fn(move || {
+            if done || bytes.is_empty() {
+                return None;
+            }
+
+            let option = TcpOption::decode(&mut bytes);
+            done = matches!(option, Ok(TcpOption::End) | Err(_));
+
+            Some(option).filter(|option| !matches!(option, Ok(TcpOption::End)))
+        })
+    }
+
+    /// Return the maximum segment size option, if present (and well-formed)
+    pub fn mss(&self) -> Option<u16> {
+        self.iter().find_map(|option| match option {
+            Ok(TcpOption::Mss(mss)) => Some(mss),
+            _ => None,
+        })
+    }
+}
+
+/// Represents a parsed TCP header
+#[derive(Clone, Debug)]
+pub struct TcpPacketHeader {
+    /// Source port
+    pub src: u16,
+    /// Destination port
+    pub dst: u16,
+    /// Sequence number
+    pub seq: u32,
+    /// Acknowledgement number
+    pub ack: u32,
+    /// Header length, including the options
+    pub hlen: u8,
+    /// Flags; see the `TcpPacketHeader::FIN` - `TcpPacketHeader::CWR` constants
+    pub flags: u8,
+    /// Window size
+    pub window: u16,
+    /// Checksum
+    pub sum: u16,
+    /// Urgent pointer
+    pub urgent: u16,
+}
+
+impl TcpPacketHeader {
+    pub const PROTO: u8 = 6;
+
+    pub const MIN_SIZE: usize = 20;
+    pub const MAX_SIZE: usize = 60;
+    pub const CHECKSUM_WORD: usize = 8;
+
+    pub const FIN: u8 = 0x01;
+    pub const SYN: u8 = 0x02;
+    pub const RST: u8 = 0x04;
+    pub const PSH: u8 = 0x08;
+    pub const ACK: u8 = 0x10;
+    pub const URG: u8 = 0x20;
+    pub const ECE: u8 = 0x40;
+    pub const CWR: u8 = 0x80;
+
+    /// Create a new header instance
+    pub fn new(src: u16, dst: u16, seq: u32, ack: u32, flags: u8) -> Self {
+        Self {
+            src,
+            dst,
+            seq,
+            ack,
+            hlen: Self::MIN_SIZE as _,
+            flags,
+            window: 0,
+            sum: 0,
+            urgent: 0,
+        }
+    }
+
+    /// Return `true` if all of the provided flags are set
+    pub fn has_flags(&self, flags: u8) -> bool {
+        self.flags & flags == flags
+    }
+
+    /// Return `true` if this is the header of a segment opening a connection (i.e. SYN without ACK)
+    pub fn is_syn(&self) -> bool {
+        self.has_flags(Self::SYN) && !self.has_flags(Self::ACK)
+    }
+
+    /// Decodes the header (without the options) from a byte slice
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut bytes = BytesIn::new(data);
+
+        let hdr = Self {
+            src: u16::from_be_bytes(bytes.arr()?),
+            dst: u16::from_be_bytes(bytes.arr()?),
+            seq: u32::from_be_bytes(bytes.arr()?),
+            ack: u32::from_be_bytes(bytes.arr()?),
+            hlen: (bytes.byte()? >> 4) * 4,
+            flags: bytes.byte()?,
+            window: u16::from_be_bytes(bytes.arr()?),
+            sum: u16::from_be_bytes(bytes.arr()?),
+            urgent: u16::from_be_bytes(bytes.arr()?),
+        };
+
+        if (hdr.hlen as usize) < Self::MIN_SIZE {
+            Err(Error::InvalidFormat)?;
+        }
+
+        Ok(hdr)
+    }
+
+    /// Decodes the options following the header from a byte slice containing the whole segment
+    pub fn decode_options<'a>(&self, data: &'a [u8]) -> Result<TcpOptions<'a>, Error> {
+        let hdr_len = self.hlen as usize;
+        if data.len() < hdr_len {
+            Err(Error::DataUnderflow)?;
+        }
+
+        Ok(TcpOptions::new(&data[Self::MIN_SIZE..hdr_len]))
+    }
+
+    /// Encodes the header (without the options) into the provided buf slice
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let mut bytes = BytesOut::new(buf);
+
+        bytes
+            .push(&u16::to_be_bytes(self.src))?
+            .push(&u16::to_be_bytes(self.dst))?
+            .push(&u32::to_be_bytes(self.seq))?
+            .push(&u32::to_be_bytes(self.ack))?
+            .byte((self.hlen / 4) << 4)?
+            .byte(self.flags)?
+            .push(&u16::to_be_bytes(self.window))?
+            .push(&u16::to_be_bytes(self.sum))?
+            .push(&u16::to_be_bytes(self.urgent))?;
+
+        let len = bytes.len();
+
+        Ok(&buf[..len])
+    }
+
+    /// Encodes the header, the provided options and the provided payload into the provided buf slice
+    ///
+    /// The options are padded with zeroes (end-of-list) up to a multiple of 4 bytes.
+    pub fn encode_with_payload<'o, F>(
+        &mut self,
+        buf: &'o mut [u8],
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        options: &[TcpOption<'_>],
+        encoder: F,
+    ) -> Result<&'o [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        let packet = self.encode_with_options_and_payload(buf, options, encoder)?;
+
+        let checksum = Self::checksum(packet, src, dst);
+        self.sum = checksum;
+
+        Self::inject_checksum(packet, checksum);
+
+        Ok(packet)
+    }
+
+    /// Encodes the header, the provided options and the provided payload into the provided buf slice,
+    /// computing the checksum over the IPv6 pseudo-header
+    pub fn encode_with_payload6<'o, F>(
+        &mut self,
+        buf: &'o mut [u8],
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        options: &[TcpOption<'_>],
+        encoder: F,
+    ) -> Result<&'o [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        let packet = self.encode_with_options_and_payload(buf, options, encoder)?;
+
+        let checksum = Self::checksum6(packet, src, dst);
+        self.sum = checksum;
+
+        Self::inject_checksum(packet, checksum);
+
+        Ok(packet)
+    }
+
+    /// Decodes the provided packet into a header and a payload slice
+    ///
+    /// Use `decode_options` on the packet to access the options.
+    pub fn decode_with_payload(
+        packet: &[u8],
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        filter_src: Option<u16>,
+        filter_dst: Option<u16>,
+    ) -> Result<Option<(Self, &[u8])>, Error> {
+        Self::decode_with_payload_and_checksum(packet, filter_src, filter_dst, |packet| {
+            Self::checksum(packet, src, dst)
+        })
+    }
+
+    /// Decodes the provided packet - received over IPv6 - into a header and a payload slice
+    pub fn decode_with_payload6(
+        packet: &[u8],
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        filter_src: Option<u16>,
+        filter_dst: Option<u16>,
+    ) -> Result<Option<(Self, &[u8])>, Error> {
+        Self::decode_with_payload_and_checksum(packet, filter_src, filter_dst, |packet| {
+            Self::checksum6(packet, src, dst)
+        })
+    }
+
+    /// Injects the checksum into the provided packet
+    pub fn inject_checksum(packet: &mut [u8], checksum: u16) {
+        let checksum = checksum.to_be_bytes();
+
+        let offset = Self::CHECKSUM_WORD << 1;
+        packet[offset] = checksum[0];
+        packet[offset + 1] = checksum[1];
+    }
+
+    /// Computes the checksum for an already encoded packet
+    pub fn checksum(packet: &[u8], src: Ipv4Addr, dst: Ipv4Addr) -> u16 {
+        let mut buf = [0; 12];
+
+        // Pseudo IP-header for TCP checksum calculation
+        let len = BytesOut::new(&mut buf)
+            .push(&u32::to_be_bytes(src.into()))
+            .unwrap()
+            .push(&u32::to_be_bytes(dst.into()))
+            .unwrap()
+            .byte(0)
+            .unwrap()
+            .byte(Self::PROTO)
+            .unwrap()
+            .push(&u16::to_be_bytes(packet.len() as u16))
+            .unwrap()
+            .len();
+
+        let sum = checksum_accumulate(&buf[..len], usize::MAX)
+            + checksum_accumulate(packet, Self::CHECKSUM_WORD);
+
+        checksum_finish(sum)
+    }
+
+    /// Computes the checksum for an already encoded packet, sent over IPv6
+    pub fn checksum6(packet: &[u8], src: Ipv6Addr, dst: Ipv6Addr) -> u16 {
+        let mut buf = [0; 40];
+
+        // Pseudo IPv6-header for TCP checksum calculation
+        let len = BytesOut::new(&mut buf)
+            .push(&u128::to_be_bytes(src.into()))
+            .unwrap()
+            .push(&u128::to_be_bytes(dst.into()))
+            .unwrap()
+            .push(&u32::to_be_bytes(packet.len() as u32))
+            .unwrap()
+            .push(&[0; 3])
+            .unwrap()
+            .byte(Self::PROTO)
+            .unwrap()
+            .len();
+
+        let sum = checksum_accumulate(&buf[..len], usize::MAX)
+            + checksum_accumulate(packet, Self::CHECKSUM_WORD);
+
+        checksum_finish(sum)
+    }
+
+    fn encode_with_options_and_payload<'o, F>(
+        &mut self,
+        buf: &'o mut [u8],
+        options: &[TcpOption<'_>],
+        encoder: F,
+    ) -> Result<&'o mut [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        if buf.len() < Self::MIN_SIZE {
+            Err(Error::BufferOverflow)?;
+        }
+
+        let options_len = {
+            let options_end = core::cmp::min(buf.len(), Self::MAX_SIZE);
+            let mut bytes = BytesOut::new(&mut buf[Self::MIN_SIZE..options_end]);
+
+            for option in options {
+                option.encode(&mut bytes)?;
+            }
+
+            while bytes.len() % 4 != 0 {
+                bytes.byte(0)?;
+            }
+
+            bytes.len()
+        };
+
+        let hdr_len = Self::MIN_SIZE + options_len;
+        self.hlen = hdr_len as _;
+
+        let (hdr_buf, payload_buf) = buf.split_at_mut(hdr_len);
+
+        let payload_len = encoder(payload_buf)?;
+
+        let min_hdr_len = self.encode(hdr_buf)?.len();
+        assert_eq!(min_hdr_len, Self::MIN_SIZE);
+
+        Ok(&mut buf[..hdr_len + payload_len])
+    }
+
+    fn decode_with_payload_and_checksum<C>(
+        packet: &[u8],
+        filter_src: Option<u16>,
+        filter_dst: Option<u16>,
+        checksum: C,
+    ) -> Result<Option<(Self, &[u8])>, Error>
+    where
+        C: FnOnce(&[u8]) -> u16,
+    {
+        let hdr = Self::decode(packet)?;
+
+        if let Some(filter_src) = filter_src {
+            if filter_src != hdr.src {
+                return Ok(None);
+            }
+        }
+
+        if let Some(filter_dst) = filter_dst {
+            if filter_dst != hdr.dst {
+                return Ok(None);
+            }
+        }
+
+        let hdr_len = hdr.hlen as usize;
+        if packet.len() < hdr_len {
+            Err(Error::DataUnderflow)?;
+        }
+
+        // Unlike UDP, TCP has no length field, so the segment spans till the end of the IP payload
+        let checksum = checksum(packet);
+
+        trace!(
+            "TCP header decoded, src={}, dst={}, seq={}, ack={}, flags={:02x}, size={}, checksum={}, ours={}",
+            hdr.src,
+            hdr.dst,
+            hdr.seq,
+            hdr.ack,
+            hdr.flags,
+            packet.len(),
+            hdr.sum,
+            checksum
+        );
+
+        if checksum != hdr.sum {
+            Err(Error::InvalidChecksum)?;
+        }
+
+        Ok(Some((hdr, &packet[hdr_len..])))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CLIENT: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+    const SERVER: Ipv4Addr = Ipv4Addr::new(93, 184, 216, 34);
+
+    /// A SYN segment with the options of a typical Linux client
+    const SYN: [u8; 40] = [
+        0xc8, 0x22, 0x00, 0x50, 0x1a, 0x2b, 0x3c, 0x4d, //
+        0x00, 0x00, 0x00, 0x00, 0xa0, 0x02, 0xfa, 0xf0, //
+        0x30, 0xf5, 0x00, 0x00, 0x02, 0x04, 0x05, 0xb4, //
+        0x04, 0x02, 0x08, 0x0a, 0x3b, 0x9a, 0xca, 0x07, //
+        0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x03, 0x07, //
+    ];
+
+    const SYN_OPTIONS: [TcpOption<'static>; 5] = [
+        TcpOption::Mss(1460),
+        TcpOption::SackPermitted,
+        TcpOption::Timestamps(0x3b9aca07, 0),
+        TcpOption::Nop,
+        TcpOption::WindowScale(7),
+    ];
+
+    #[test]
+    fn test_roundtrip() {
+        let (src, dst, hdr, payload) = decode(CLIENT, SERVER, &SYN, None, Some(80))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(src, SocketAddrV4::new(CLIENT, 51234));
+        assert_eq!(dst, SocketAddrV4::new(SERVER, 80));
+        assert_eq!(hdr.seq, 0x1a2b3c4d);
+        assert_eq!(hdr.hlen, 40);
+        assert_eq!(hdr.window, 64240);
+        assert!(hdr.is_syn());
+        assert!(payload.is_empty());
+
+        let options = hdr.decode_options(&SYN).unwrap();
+        assert!(options.iter().map(Result::unwrap).eq(SYN_OPTIONS));
+        assert_eq!(options.mss(), Some(1460));
+
+        let mut hdr = TcpPacketHeader {
+            window: 64240,
+            ..TcpPacketHeader::new(51234, 80, 0x1a2b3c4d, 0, TcpPacketHeader::SYN)
+        };
+
+        let mut buf = [0; 128];
+        let packet = hdr
+            .encode_with_payload(&mut buf, CLIENT, SERVER, &SYN_OPTIONS, |_| Ok(0))
+            .unwrap();
+
+        assert_eq!(packet, SYN);
+    }
+
+    #[test]
+    fn test_options() {
+        // SACK blocks, an unknown option and padding after the end-of-list option
+        let data = [1, 1, 5, 10, 0, 0, 0, 1, 0, 0, 0, 2, 30, 3, 0xaa, 0, 0xff];
+
+        let options = TcpOptions::new(&data);
+
+        assert!(options.iter().map(Result::unwrap).eq([
+            TcpOption::Nop,
+            TcpOption::Nop,
+            TcpOption::Sack(&[0, 0, 0, 1, 0, 0, 0, 2]),
+            TcpOption::Other(30, &[0xaa]),
+        ]));
+        assert_eq!(options.mss(), None);
+
+        // Truncated and malformed options
+        assert_eq!(
+            TcpOptions::new(&[2, 4, 5]).iter().next(),
+            Some(Err(Error::DataUnderflow))
+        );
+        assert_eq!(
+            TcpOptions::new(&[2, 1, 5, 0xb4]).iter().next(),
+            Some(Err(Error::InvalidFormat))
+        );
+
+        // A corrupted segment is rejected
+        let mut syn = SYN;
+        syn[35] = 2;
+
+        assert_eq!(
+            decode(CLIENT, SERVER, &syn, None, None).err(),
+            Some(Error::InvalidChecksum)
+        );
+    }
+}
@@ -1,10 +1,10 @@
 use log::trace;
 
-use core::net::{Ipv4Addr, SocketAddrV4};
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
 
 use super::bytes::{BytesIn, BytesOut};
 
-use super::{checksum_accumulate, checksum_finish, Error};
+use super::{checksum_accumulate, checksum_finish, ChecksumMode, ChecksumStatus, Error};
 
 #[allow(clippy::type_complexity)]
 pub fn decode(
@@ -27,6 +27,29 @@ pub fn decode(
     Ok(data)
 }
 
+#[allow(clippy::type_complexity)]
+pub fn decode_with_mode(
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    packet: &[u8],
+    filter_src: Option<u16>,
+    filter_dst: Option<u16>,
+    mode: ChecksumMode,
+) -> Result<Option<(SocketAddrV4, SocketAddrV4, &[u8], ChecksumStatus)>, Error> {
+    let data =
+        UdpPacketHeader::decode_with_payload_mode(packet, src, dst, filter_src, filter_dst, mode)?
+            .map(|(hdr, payload, status)| {
+                (
+                    SocketAddrV4::new(src, hdr.src),
+                    SocketAddrV4::new(dst, hdr.dst),
+                    payload,
+                    status,
+                )
+            });
+
+    Ok(data)
+}
+
 pub fn encode<F>(
     buf: &mut [u8],
     src: SocketAddrV4,
@@ -41,6 +64,43 @@ where
     hdr.encode_with_payload(buf, *src.ip(), *dst.ip(), |buf| payload(buf))
 }
 
+#[allow(clippy::type_complexity)]
+pub fn decode6(
+    src: Ipv6Addr,
+    dst: Ipv6Addr,
+    packet: &[u8],
+    filter_src: Option<u16>,
+    filter_dst: Option<u16>,
+) -> Result<Option<(SocketAddrV6, SocketAddrV6, &[u8])>, Error> {
+    let data = UdpPacketHeader::decode_with_payload6(packet, src, dst, filter_src, filter_dst)?
+        .map(|(hdr, payload)| {
+            (
+                SocketAddrV6::new(src, hdr.src, 0, 0),
+                SocketAddrV6::new(dst, hdr.dst, 0, 0),
+                payload,
+            )
+        });
+
+    Ok(data)
+}
+
+pub fn encode6<F>(
+    buf: &mut [u8],
+    src: SocketAddrV6,
+    dst: SocketAddrV6,
+    payload: F,
+) -> Result<&[u8], Error>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+{
+    let mut hdr = UdpPacketHeader::new(src.port(), dst.port());
+
+    hdr.encode_with_payload6(buf, *src.ip(), *dst.ip(), |buf| payload(buf))
+}
+
+/// A decoded UDP packet: its header, its payload and the outcome of its checksum verification
+pub type UdpDecoded<'a> = (UdpPacketHeader, &'a [u8], ChecksumStatus);
+
 /// Represents a parsed UDP header
 #[derive(Clone, Debug)]
 pub struct UdpPacketHeader {
@@ -107,6 +167,177 @@ impl UdpPacketHeader {
     ) -> Result<&'o [u8], Error>
     where
         F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        self.encode_with_payload_and_checksum(buf, encoder, |packet| {
+            Self::checksum(packet, src, dst)
+        })
+    }
+
+    /// Encodes the header and the provided payload into the provided buf slice,
+    /// computing the checksum over the IPv6 pseudo-header
+    pub fn encode_with_payload6<'o, F>(
+        &mut self,
+        buf: &'o mut [u8],
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        encoder: F,
+    ) -> Result<&'o [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        self.encode_with_payload_and_checksum(buf, encoder, |packet| {
+            Self::checksum6(packet, src, dst)
+        })
+    }
+
+    /// Decodes the provided packet into a header and a payload slice
+    pub fn decode_with_payload(
+        packet: &[u8],
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        filter_src: Option<u16>,
+        filter_dst: Option<u16>,
+    ) -> Result<Option<(Self, &[u8])>, Error> {
+        let data = Self::decode_with_payload_mode(
+            packet,
+            src,
+            dst,
+            filter_src,
+            filter_dst,
+            ChecksumMode::Strict,
+        )?
+        .map(|(hdr, payload, _)| (hdr, payload));
+
+        Ok(data)
+    }
+
+    /// Decodes the provided packet into a header and a payload slice, validating the checksum as per the provided mode
+    ///
+    /// As per RFC 768, a zero checksum means that the sender did not compute one, so it is never verified.
+    pub fn decode_with_payload_mode(
+        packet: &[u8],
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        filter_src: Option<u16>,
+        filter_dst: Option<u16>,
+        mode: ChecksumMode,
+    ) -> Result<Option<UdpDecoded<'_>>, Error> {
+        Self::decode_with_payload_and_checksum(packet, filter_src, filter_dst, |hdr, packet| {
+            if hdr.sum == 0 {
+                Ok(ChecksumStatus::Unverified)
+            } else {
+                ChecksumStatus::verify(mode, Self::checksum(packet, src, dst), hdr.sum)
+            }
+        })
+    }
+
+    /// Decodes the provided packet - received over IPv6 - into a header and a payload slice
+    pub fn decode_with_payload6(
+        packet: &[u8],
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        filter_src: Option<u16>,
+        filter_dst: Option<u16>,
+    ) -> Result<Option<(Self, &[u8])>, Error> {
+        let data = Self::decode_with_payload6_mode(
+            packet,
+            src,
+            dst,
+            filter_src,
+            filter_dst,
+            ChecksumMode::Strict,
+        )?
+        .map(|(hdr, payload, _)| (hdr, payload));
+
+        Ok(data)
+    }
+
+    /// Decodes the provided packet - received over IPv6 - into a header and a payload slice,
+    /// validating the checksum as per the provided mode
+    pub fn decode_with_payload6_mode(
+        packet: &[u8],
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        filter_src: Option<u16>,
+        filter_dst: Option<u16>,
+        mode: ChecksumMode,
+    ) -> Result<Option<UdpDecoded<'_>>, Error> {
+        Self::decode_with_payload_and_checksum(packet, filter_src, filter_dst, |hdr, packet| {
+            ChecksumStatus::verify(mode, Self::checksum6(packet, src, dst), hdr.sum)
+        })
+    }
+
+    /// Injects the checksum into the provided packet
+    pub fn inject_checksum(packet: &mut [u8], checksum: u16) {
+        let checksum = checksum.to_be_bytes();
+
+        let offset = Self::CHECKSUM_WORD << 1;
+        packet[offset] = checksum[0];
+        packet[offset + 1] = checksum[1];
+    }
+
+    /// Computes the checksum for an already encoded packet
+    pub fn checksum(packet: &[u8], src: Ipv4Addr, dst: Ipv4Addr) -> u16 {
+        let mut buf = [0; 12];
+
+        // Pseudo IP-header for UDP checksum calculation
+        let len = BytesOut::new(&mut buf)
+            .push(&u32::to_be_bytes(src.into()))
+            .unwrap()
+            .push(&u32::to_be_bytes(dst.into()))
+            .unwrap()
+            .byte(0)
+            .unwrap()
+            .byte(UdpPacketHeader::PROTO)
+            .unwrap()
+            .push(&u16::to_be_bytes(packet.len() as u16))
+            .unwrap()
+            .len();
+
+        let sum = checksum_accumulate(&buf[..len], usize::MAX)
+            + checksum_accumulate(packet, Self::CHECKSUM_WORD);
+
+        checksum_finish(sum)
+    }
+
+    /// Computes the checksum for an already encoded packet, sent over IPv6
+    ///
+    /// As per RFC 8200, the checksum is mandatory over IPv6, so a computed checksum of zero is returned as all ones.
+    pub fn checksum6(packet: &[u8], src: Ipv6Addr, dst: Ipv6Addr) -> u16 {
+        let mut buf = [0; 40];
+
+        // Pseudo IPv6-header for UDP checksum calculation
+        let len = BytesOut::new(&mut buf)
+            .push(&u128::to_be_bytes(src.into()))
+            .unwrap()
+            .push(&u128::to_be_bytes(dst.into()))
+            .unwrap()
+            .push(&u32::to_be_bytes(packet.len() as u32))
+            .unwrap()
+            .push(&[0; 3])
+            .unwrap()
+            .byte(UdpPacketHeader::PROTO)
+            .unwrap()
+            .len();
+
+        let sum = checksum_accumulate(&buf[..len], usize::MAX)
+            + checksum_accumulate(packet, Self::CHECKSUM_WORD);
+
+        match checksum_finish(sum) {
+            0 => 0xffff,
+            checksum => checksum,
+        }
+    }
+
+    fn encode_with_payload_and_checksum<'o, F, C>(
+        &mut self,
+        buf: &'o mut [u8],
+        encoder: F,
+        checksum: C,
+    ) -> Result<&'o [u8], Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+        C: FnOnce(&[u8]) -> u16,
     {
         if buf.len() < Self::SIZE {
             Err(Error::BufferOverflow)?;
@@ -124,7 +355,7 @@ impl UdpPacketHeader {
 
         let packet = &mut buf[..len];
 
-        let checksum = Self::checksum(packet, src, dst);
+        let checksum = checksum(packet);
         self.sum = checksum;
 
         Self::inject_checksum(packet, checksum);
@@ -132,14 +363,15 @@ impl UdpPacketHeader {
         Ok(packet)
     }
 
-    /// Decodes the provided packet into a header and a payload slice
-    pub fn decode_with_payload(
+    fn decode_with_payload_and_checksum<C>(
         packet: &[u8],
-        src: Ipv4Addr,
-        dst: Ipv4Addr,
         filter_src: Option<u16>,
         filter_dst: Option<u16>,
-    ) -> Result<Option<(Self, &[u8])>, Error> {
+        verify: C,
+    ) -> Result<Option<UdpDecoded<'_>>, Error>
+    where
+        C: FnOnce(&Self, &[u8]) -> Result<ChecksumStatus, Error>,
+    {
         let hdr = Self::decode(packet)?;
 
         if let Some(filter_src) = filter_src {
@@ -155,62 +387,167 @@ impl UdpPacketHeader {
         }
 
         let len = hdr.len as usize;
+        if len < Self::SIZE {
+            Err(Error::InvalidFormat)?;
+        }
+
         if packet.len() < len {
             Err(Error::DataUnderflow)?;
         }
 
-        let checksum = Self::checksum(&packet[..len], src, dst);
+        let packet = &packet[..len];
+
+        let status = verify(&hdr, packet);
 
         trace!(
-            "UDP header decoded, src={}, dst={}, size={}, checksum={}, ours={}",
+            "UDP header decoded, src={}, dst={}, size={}, checksum={}, status={:?}",
             hdr.src,
             hdr.dst,
             hdr.len,
             hdr.sum,
-            checksum
+            status
         );
 
-        if checksum != hdr.sum {
-            Err(Error::InvalidChecksum)?;
+        let status = status?;
+
+        let payload_data = &packet[Self::SIZE..];
+
+        Ok(Some((hdr, payload_data, status)))
+    }
+}
+
+/// A zero-copy view over an encoded UDP packet, providing typed access to the header fields and to the payload
+///
+/// Wrapping a `&mut [u8]` (or any other `AsMut<[u8]>` buffer) additionally allows editing the packet in place,
+/// in which case the checksum should be refreshed with `fill_checksum` afterwards.
+///
+/// Usually obtained from the payload of an `ip::Ipv4PacketRef`.
+#[derive(Clone, Debug)]
+pub struct UdpPacketRef<T>(T);
+
+impl<T> UdpPacketRef<T>
+where
+    T: AsRef<[u8]>,
+{
+    /// Wrap the provided buffer without validating it
+    ///
+    /// The accessors panic if the buffer is shorter than the fields they access.
+    pub const fn new_unchecked(buffer: T) -> Self {
+        Self(buffer)
+    }
+
+    /// Wrap the provided buffer, validating that it contains a UDP packet with a consistent length
+    ///
+    /// The checksum is not validated; see `verify_checksum`.
+    pub fn new_checked(buffer: T) -> Result<Self, Error> {
+        let packet = Self(buffer);
+        let data = packet.0.as_ref();
+
+        if data.len() < UdpPacketHeader::SIZE {
+            Err(Error::DataUnderflow)?;
         }
 
-        let packet = &packet[..len];
+        let len = packet.len() as usize;
 
-        let payload_data = &packet[Self::SIZE..];
+        if len < UdpPacketHeader::SIZE {
+            Err(Error::InvalidFormat)?;
+        }
+
+        if data.len() < len {
+            Err(Error::DataUnderflow)?;
+        }
 
-        Ok(Some((hdr, payload_data)))
+        Ok(packet)
     }
 
-    /// Injects the checksum into the provided packet
-    pub fn inject_checksum(packet: &mut [u8], checksum: u16) {
-        let checksum = checksum.to_be_bytes();
+    /// Return the wrapped buffer
+    pub fn into_inner(self) -> T {
+        self.0
+    }
 
-        let offset = Self::CHECKSUM_WORD << 1;
-        packet[offset] = checksum[0];
-        packet[offset + 1] = checksum[1];
+    /// Source port
+    pub fn src(&self) -> u16 {
+        self.u16_at(0)
     }
 
-    /// Computes the checksum for an already encoded packet
-    pub fn checksum(packet: &[u8], src: Ipv4Addr, dst: Ipv4Addr) -> u16 {
-        let mut buf = [0; 12];
+    /// Destination port
+    pub fn dst(&self) -> u16 {
+        self.u16_at(2)
+    }
 
-        // Pseudo IP-header for UDP checksum calculation
-        let len = BytesOut::new(&mut buf)
-            .push(&u32::to_be_bytes(src.into()))
-            .unwrap()
-            .push(&u32::to_be_bytes(dst.into()))
-            .unwrap()
-            .byte(0)
-            .unwrap()
-            .byte(UdpPacketHeader::PROTO)
-            .unwrap()
-            .push(&u16::to_be_bytes(packet.len() as u16))
-            .unwrap()
-            .len();
+    /// UDP length
+    pub fn len(&self) -> u16 {
+        self.u16_at(4)
+    }
 
-        let sum = checksum_accumulate(&buf[..len], usize::MAX)
-            + checksum_accumulate(packet, Self::CHECKSUM_WORD);
+    /// Return `true` if the packet has no payload
+    pub fn is_empty(&self) -> bool {
+        self.len() as usize == UdpPacketHeader::SIZE
+    }
 
-        checksum_finish(sum)
+    /// UDP checksum
+    pub fn sum(&self) -> u16 {
+        self.u16_at(6)
+    }
+
+    /// The payload, excluding any trailing padding of the buffer beyond the UDP length
+    pub fn payload(&self) -> &[u8] {
+        &self.0.as_ref()[UdpPacketHeader::SIZE..self.len() as usize]
+    }
+
+    /// Return `true` if the checksum is valid, or if there is no checksum (i.e. it is zero)
+    pub fn verify_checksum(&self, src: Ipv4Addr, dst: Ipv4Addr) -> bool {
+        self.sum() == 0 || UdpPacketHeader::checksum(self.packet(), src, dst) == self.sum()
+    }
+
+    /// Return `true` if the checksum - of a packet sent over IPv6 - is valid
+    pub fn verify_checksum6(&self, src: Ipv6Addr, dst: Ipv6Addr) -> bool {
+        UdpPacketHeader::checksum6(self.packet(), src, dst) == self.sum()
+    }
+
+    fn packet(&self) -> &[u8] {
+        &self.0.as_ref()[..self.len() as usize]
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.0.as_ref()[offset], self.0.as_ref()[offset + 1]])
+    }
+}
+
+impl<T> UdpPacketRef<T>
+where
+    T: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Set the source port
+    pub fn set_src(&mut self, src: u16) {
+        self.0.as_mut()[0..2].copy_from_slice(&src.to_be_bytes());
+    }
+
+    /// Set the destination port
+    pub fn set_dst(&mut self, dst: u16) {
+        self.0.as_mut()[2..4].copy_from_slice(&dst.to_be_bytes());
+    }
+
+    /// The payload, for editing in place
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let len = self.len() as usize;
+
+        &mut self.0.as_mut()[UdpPacketHeader::SIZE..len]
+    }
+
+    /// Recompute the checksum, after the packet was edited
+    pub fn fill_checksum(&mut self, src: Ipv4Addr, dst: Ipv4Addr) {
+        let len = self.len() as usize;
+        let checksum = UdpPacketHeader::checksum(self.packet(), src, dst);
+
+        UdpPacketHeader::inject_checksum(&mut self.0.as_mut()[..len], checksum);
+    }
+
+    /// Recompute the checksum of a packet sent over IPv6, after the packet was edited
+    pub fn fill_checksum6(&mut self, src: Ipv6Addr, dst: Ipv6Addr) {
+        let len = self.len() as usize;
+        let checksum = UdpPacketHeader::checksum6(self.packet(), src, dst);
+
+        UdpPacketHeader::inject_checksum(&mut self.0.as_mut()[..len], checksum);
     }
 }
@@ -0,0 +1,268 @@
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use edge_nal::{UdpBind, UdpReceive, UdpSend};
+
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+
+use log::{debug, info, warn};
+
+use rand_core::RngCore;
+
+use crate as sntp;
+use crate::{Packet, Sample, Timestamp, PACKET_SIZE};
+
+/// The maximum number of servers of a `Client`
+pub const MAX_SERVERS: usize = 32;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error<E> {
+    Io(E),
+    Format(sntp::Error),
+    /// No response arrived in time
+    Timeout,
+}
+
+pub type ErrorKind = Error<edge_nal::io::ErrorKind>;
+
+impl<E> Error<E>
+where
+    E: edge_nal::io::Error,
+{
+    pub fn erase(&self) -> Error<edge_nal::io::ErrorKind> {
+        match self {
+            Self::Io(e) => Error::Io(e.kind()),
+            Self::Format(e) => Error::Format(*e),
+            Self::Timeout => Error::Timeout,
+        }
+    }
+}
+
+impl<E> From<sntp::Error> for Error<E> {
+    fn from(value: sntp::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "IO error: {err}"),
+            Self::Format(err) => write!(f, "Format error: {err}"),
+            Self::Timeout => write!(f, "Timeout"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E> where E: std::error::Error {}
+
+/// Query the SNTP server at `server` once, from a new socket of `stack`, waiting up to `timeout` for its response.
+///
+/// Responses which do not match the request (i.e. stale or spoofed ones) are ignored.
+pub async fn query<U, R>(
+    stack: &U,
+    server: SocketAddr,
+    timeout: Duration,
+    rng: &mut R,
+) -> Result<Sample, Error<U::Error>>
+where
+    U: UdpBind,
+    R: RngCore,
+{
+    let local = match server.ip() {
+        IpAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        IpAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+
+    let mut socket = stack.bind(local).await.map_err(Error::Io)?;
+
+    let sent = Timestamp(rng.next_u64());
+
+    let mut buf = [0; PACKET_SIZE];
+
+    let request = Packet::new_request(sent).encode(&mut buf)?;
+    socket.send(server, request).await.map_err(Error::Io)?;
+
+    let start = Instant::now();
+
+    with_timeout(timeout, async {
+        loop {
+            let (len, remote) = socket.receive(&mut buf).await.map_err(Error::Io)?;
+
+            if remote != server {
+                continue;
+            }
+
+            let elapsed = core::time::Duration::from_micros(start.elapsed().as_micros());
+
+            match Packet::decode(&buf[..len]).and_then(|packet| packet.sample(sent, elapsed)) {
+                Err(sntp::Error::DataUnderflow | sntp::Error::UnexpectedResponse) => {
+                    debug!("Ignoring an invalid or unexpected packet from {remote}")
+                }
+                other => break Ok(other?),
+            }
+        }
+    })
+    .await
+    .map_err(|_| Error::Timeout)?
+}
+
+/// The configuration of a `Client`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// The interval between two successful synchronizations
+    pub poll_interval: Duration,
+    /// The upper bound of the random delay added to every interval, so that a fleet of devices
+    /// powered on at the same time does not query the servers in lockstep
+    pub jitter: Duration,
+    /// The interval after a synchronization with all servers failed; it doubles with every further
+    /// failed synchronization, up to `poll_interval`
+    pub retry_interval: Duration,
+    /// The time to wait for the response of a server, before querying the next one
+    pub timeout: Duration,
+}
+
+impl Config {
+    /// Create a new configuration, with a polling interval of ~17 minutes (the maximum NTP polling interval
+    /// of 1024 seconds), a jitter of 1 minute, a retry interval of 15 seconds and a timeout of 2 seconds
+    pub const fn new() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1024),
+            jitter: Duration::from_secs(60),
+            retry_interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An SNTP client, which periodically synchronizes with a list of servers
+///
+/// The servers are queried in order, until one of them responds. The servers which reply
+/// with a `DENY` or `RSTR` Kiss-o'-Death are no longer queried, and a `RATE` Kiss-o'-Death
+/// doubles the polling interval (up to 16 times `Config::poll_interval`).
+pub struct Client<'a, U, R> {
+    stack: U,
+    servers: &'a [SocketAddr],
+    rng: R,
+    config: Config,
+    denied: u32,
+    poll_interval: Duration,
+}
+
+impl<'a, U, R> Client<'a, U, R>
+where
+    U: UdpBind,
+    R: RngCore,
+{
+    /// Create a new client
+    ///
+    /// # Panics
+    ///
+    /// If more than `MAX_SERVERS` servers are provided.
+    pub fn new(stack: U, servers: &'a [SocketAddr], rng: R, config: Config) -> Self {
+        assert!(
+            servers.len() <= MAX_SERVERS,
+            "At most {MAX_SERVERS} servers are supported"
+        );
+
+        Self {
+            stack,
+            servers,
+            rng,
+            config,
+            denied: 0,
+            poll_interval: config.poll_interval,
+        }
+    }
+
+    /// Synchronize once, by querying the servers in order until one of them responds
+    pub async fn sync(&mut self) -> Result<Sample, Error<U::Error>> {
+        let mut result = Err(Error::Timeout);
+
+        for (index, server) in self.servers.iter().enumerate() {
+            if self.denied & (1 << index) != 0 {
+                continue;
+            }
+
+            result = query(&self.stack, *server, self.config.timeout, &mut self.rng).await;
+
+            match &result {
+                Ok(sample) => {
+                    debug!("Got {sample:?} from {server}");
+                    break;
+                }
+                Err(Error::Format(sntp::Error::KissOfDeath(code))) if code.is_deny() => {
+                    warn!("Server {server} denied access ({code}), no longer querying it");
+                    self.denied |= 1 << index;
+                }
+                Err(Error::Format(sntp::Error::KissOfDeath(code))) if code.is_rate() => {
+                    self.poll_interval =
+                        (self.poll_interval * 2).min(self.config.poll_interval * 16);
+                    warn!(
+                        "Server {server} asked to reduce the polling rate, polling every {}s",
+                        self.poll_interval.as_secs()
+                    );
+                }
+                Err(e) => warn!("Querying server {server} failed: {e:?}"),
+            }
+        }
+
+        result
+    }
+
+    /// Synchronize periodically, calling `f` with the sample of every successful synchronization.
+    ///
+    /// Only returns (with the last error) once all servers denied access.
+    pub async fn run<F>(&mut self, mut f: F) -> Result<(), Error<U::Error>>
+    where
+        F: FnMut(&Sample),
+    {
+        let mut retry_interval = self.config.retry_interval;
+
+        loop {
+            let interval = match self.sync().await {
+                Ok(sample) => {
+                    info!(
+                        "Synchronized, round trip {}ms",
+                        sample.round_trip.as_millis()
+                    );
+
+                    f(&sample);
+
+                    retry_interval = self.config.retry_interval;
+
+                    self.poll_interval
+                }
+                Err(e) => {
+                    if self
+                        .servers
+                        .iter()
+                        .enumerate()
+                        .all(|(index, _)| self.denied & (1 << index) != 0)
+                    {
+                        break Err(e);
+                    }
+
+                    let interval = retry_interval;
+                    retry_interval = (retry_interval * 2).min(self.poll_interval);
+
+                    interval
+                }
+            };
+
+            let jitter = self.rng.next_u64() % (self.config.jitter.as_micros() + 1);
+
+            Timer::after(interval + Duration::from_micros(jitter)).await;
+        }
+    }
+}
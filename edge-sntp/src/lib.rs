@@ -0,0 +1,426 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(async_fn_in_trait)]
+#![warn(clippy::large_futures)]
+
+use core::fmt;
+use core::time::Duration;
+
+#[cfg(feature = "io")]
+pub mod io;
+
+/// The UDP port of the NTP servers
+pub const PORT: u16 = 123;
+
+/// The size of an NTP packet without extension fields and MAC
+pub const PACKET_SIZE: usize = 48;
+
+/// The number of seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01)
+const UNIX_EPOCH_SECS: u64 = 2_208_988_800;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The buffer is too small for the packet
+    BufferOverflow,
+    /// The packet is truncated
+    DataUnderflow,
+    /// The packet is not the response to the request (i.e. a stale or a spoofed one)
+    UnexpectedResponse,
+    /// The server is not synchronized to a time source
+    Unsynchronized,
+    /// The server replied with a Kiss-o'-Death packet
+    KissOfDeath(KissCode),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferOverflow => write!(f, "Buffer overflow"),
+            Self::DataUnderflow => write!(f, "Data underflow"),
+            Self::UnexpectedResponse => write!(f, "Unexpected response"),
+            Self::Unsynchronized => write!(f, "Server not synchronized"),
+            Self::KissOfDeath(code) => write!(f, "Kiss-o'-Death: {code}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The code of a Kiss-o'-Death packet (RFC 5905, section 7.4), i.e. a response with stratum 0
+/// by which the server asks the client to change its behavior
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct KissCode(pub [u8; 4]);
+
+impl KissCode {
+    /// Access denied; the client should stop querying the server
+    pub const DENY: Self = Self(*b"DENY");
+    /// Access restricted; the client should stop querying the server
+    pub const RSTR: Self = Self(*b"RSTR");
+    /// Rate exceeded; the client should reduce its polling rate
+    pub const RATE: Self = Self(*b"RATE");
+
+    /// Return `true` if the server asks the client to stop querying it
+    pub fn is_deny(&self) -> bool {
+        *self == Self::DENY || *self == Self::RSTR
+    }
+
+    /// Return `true` if the server asks the client to reduce its polling rate
+    pub fn is_rate(&self) -> bool {
+        *self == Self::RATE
+    }
+}
+
+impl fmt::Display for KissCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0 {
+            if b.is_ascii_graphic() {
+                write!(f, "{}", b as char)?;
+            } else {
+                write!(f, "\\x{b:02x}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An NTP timestamp: the seconds since the NTP epoch in the upper 32 bits,
+/// and the fraction of the second in the lower 32 bits
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Timestamp(pub u64);
+
+impl Timestamp {
+    pub const ZERO: Self = Self(0);
+
+    /// Create a timestamp from the time elapsed since the Unix epoch
+    pub fn from_unix(since_epoch: Duration) -> Self {
+        // The seconds wrap into the next NTP era after 2036
+        let secs = since_epoch.as_secs().wrapping_add(UNIX_EPOCH_SECS) as u32;
+
+        Self(((secs as u64) << 32) | to_fraction(since_epoch.subsec_nanos()) as u64)
+    }
+
+    /// Return the time elapsed since the Unix epoch.
+    ///
+    /// Timestamps with the most significant bit cleared are taken to be in the next NTP era (2036 - 2104)
+    /// rather than before 1968, as described in RFC 4330, section 3.
+    pub fn to_unix(&self) -> Duration {
+        let mut secs = self.0 >> 32;
+
+        if secs & 0x8000_0000 == 0 {
+            secs += 1 << 32;
+        }
+
+        Duration::new(secs - UNIX_EPOCH_SECS, from_fraction(self.0 as u32))
+    }
+}
+
+/// An NTP packet, in the format shared by the requests and the responses (RFC 5905, section 7.3)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Packet {
+    /// The leap indicator, where `LEAP_UNSYNCHRONIZED` means that the clock of the server is not synchronized
+    pub leap: u8,
+    pub version: u8,
+    pub mode: u8,
+    /// The stratum of the server, where 0 denotes a Kiss-o'-Death packet
+    pub stratum: u8,
+    /// The polling interval, as a power of two in seconds
+    pub poll: i8,
+    /// The precision of the clock of the server, as a power of two in seconds
+    pub precision: i8,
+    /// The round-trip delay to the reference clock, in 16.16 fixed-point seconds
+    pub root_delay: u32,
+    /// The dispersion to the reference clock, in 16.16 fixed-point seconds
+    pub root_dispersion: u32,
+    /// The identifier of the reference clock, or the code of a Kiss-o'-Death packet
+    pub reference_id: [u8; 4],
+    /// The time the clock of the server was last set
+    pub reference: Timestamp,
+    /// The time the request was sent by the client, as echoed back from its transmit timestamp
+    pub originate: Timestamp,
+    /// The time the request arrived at the server
+    pub receive: Timestamp,
+    /// The time the response left the server
+    pub transmit: Timestamp,
+}
+
+impl Packet {
+    pub const VERSION: u8 = 4;
+
+    pub const MODE_CLIENT: u8 = 3;
+    pub const MODE_SERVER: u8 = 4;
+
+    pub const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+    /// Create a client request, with the provided transmit timestamp.
+    ///
+    /// Since the server only echoes the transmit timestamp back, it does not have to be the local time:
+    /// a random number is better, as it does not leak the local time and makes spoofed responses harder.
+    pub const fn new_request(transmit: Timestamp) -> Self {
+        Self {
+            leap: 0,
+            version: Self::VERSION,
+            mode: Self::MODE_CLIENT,
+            stratum: 0,
+            poll: 0,
+            precision: 0,
+            root_delay: 0,
+            root_dispersion: 0,
+            reference_id: [0; 4],
+            reference: Timestamp::ZERO,
+            originate: Timestamp::ZERO,
+            receive: Timestamp::ZERO,
+            transmit,
+        }
+    }
+
+    /// Decode a packet, ignoring the extension fields and the MAC which might follow it
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let data: &[u8; PACKET_SIZE] = data
+            .get(..PACKET_SIZE)
+            .and_then(|data| data.try_into().ok())
+            .ok_or(Error::DataUnderflow)?;
+
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+
+        let timestamp_at =
+            |offset: usize| Timestamp(((u32_at(offset) as u64) << 32) | u32_at(offset + 4) as u64);
+
+        Ok(Self {
+            leap: data[0] >> 6,
+            version: (data[0] >> 3) & 0x07,
+            mode: data[0] & 0x07,
+            stratum: data[1],
+            poll: data[2] as i8,
+            precision: data[3] as i8,
+            root_delay: u32_at(4),
+            root_dispersion: u32_at(8),
+            reference_id: [data[12], data[13], data[14], data[15]],
+            reference: timestamp_at(16),
+            originate: timestamp_at(24),
+            receive: timestamp_at(32),
+            transmit: timestamp_at(40),
+        })
+    }
+
+    /// Encode the packet into `buf`, returning the encoded data
+    pub fn encode<'o>(&self, buf: &'o mut [u8]) -> Result<&'o [u8], Error> {
+        let buf = buf.get_mut(..PACKET_SIZE).ok_or(Error::BufferOverflow)?;
+
+        buf[0] = (self.leap << 6) | ((self.version & 0x07) << 3) | (self.mode & 0x07);
+        buf[1] = self.stratum;
+        buf[2] = self.poll as u8;
+        buf[3] = self.precision as u8;
+        buf[4..8].copy_from_slice(&self.root_delay.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.root_dispersion.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.reference_id);
+        buf[16..24].copy_from_slice(&self.reference.0.to_be_bytes());
+        buf[24..32].copy_from_slice(&self.originate.0.to_be_bytes());
+        buf[32..40].copy_from_slice(&self.receive.0.to_be_bytes());
+        buf[40..48].copy_from_slice(&self.transmit.0.to_be_bytes());
+
+        Ok(buf)
+    }
+
+    /// Validate this packet as the response to the request with transmit timestamp `sent`,
+    /// and compute the time sample it carries.
+    ///
+    /// `elapsed` is the time elapsed between sending the request and receiving the response,
+    /// as measured with a local monotonic clock.
+    pub fn sample(&self, sent: Timestamp, elapsed: Duration) -> Result<Sample, Error> {
+        if self.mode != Self::MODE_SERVER || self.originate != sent {
+            Err(Error::UnexpectedResponse)?;
+        }
+
+        if self.stratum == 0 {
+            Err(Error::KissOfDeath(KissCode(self.reference_id)))?;
+        }
+
+        if self.leap == Self::LEAP_UNSYNCHRONIZED
+            || self.stratum > 15
+            || self.transmit == Timestamp::ZERO
+        {
+            Err(Error::Unsynchronized)?;
+        }
+
+        // The time the server took to process the request, which is not part of the round trip
+        let processing = from_fixed(self.transmit.0.wrapping_sub(self.receive.0) as i64)
+            .unwrap_or(Duration::ZERO);
+
+        let round_trip = elapsed.saturating_sub(processing);
+
+        Ok(Sample {
+            time: self.transmit.to_unix() + round_trip / 2,
+            round_trip,
+            stratum: self.stratum,
+        })
+    }
+}
+
+/// A time sample, as computed from the response of an SNTP server
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Sample {
+    /// The time since the Unix epoch at the moment the response was received,
+    /// assuming that the request and the response took the same time in transit
+    pub time: Duration,
+    /// The round-trip delay of the request and the response, excluding the processing time of the server
+    pub round_trip: Duration,
+    /// The stratum of the server
+    pub stratum: u8,
+}
+
+impl Sample {
+    /// Return the offset of the local clock, in microseconds, where `local` is the local time since the Unix epoch
+    /// at the moment the response was received.
+    ///
+    /// A positive offset means that the local clock is behind. Had the request carried the local time,
+    /// this would be the `((T2 - T1) + (T3 - T4)) / 2` offset of RFC 4330.
+    pub fn offset_micros(&self, local: Duration) -> i64 {
+        (self.time.as_micros() as i128 - local.as_micros() as i128) as i64
+    }
+}
+
+/// Convert nanoseconds to the fraction of a second of an NTP timestamp, rounding to the nearest fraction
+fn to_fraction(nanos: u32) -> u32 {
+    ((((nanos as u64) << 32) + 500_000_000) / 1_000_000_000) as u32
+}
+
+/// Convert the fraction of a second of an NTP timestamp to nanoseconds, rounding to the nearest nanosecond
+fn from_fraction(fraction: u32) -> u32 {
+    ((fraction as u64 * 1_000_000_000 + (1 << 31)) >> 32) as u32
+}
+
+/// Convert a difference of two NTP timestamps to a duration, or `None` if the difference is negative
+fn from_fixed(fixed: i64) -> Option<Duration> {
+    (fixed >= 0).then(|| Duration::new(fixed as u64 >> 32, from_fraction(fixed as u32)))
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+
+    use crate::{Error, KissCode, Packet, Timestamp, PACKET_SIZE};
+
+    fn response(sent: Timestamp, receive: Timestamp, transmit: Timestamp) -> Packet {
+        Packet {
+            leap: 0,
+            mode: Packet::MODE_SERVER,
+            stratum: 2,
+            reference_id: *b"GPS\0",
+            originate: sent,
+            receive,
+            transmit,
+            ..Packet::new_request(Timestamp::ZERO)
+        }
+    }
+
+    #[test]
+    fn timestamp_unix() {
+        let time = Duration::new(1_700_000_000, 500_000_000);
+
+        let timestamp = Timestamp::from_unix(time);
+
+        assert_eq!(timestamp.0 >> 32, 1_700_000_000 + 2_208_988_800);
+        assert_eq!(timestamp.0 as u32, 0x8000_0000);
+        assert_eq!(timestamp.to_unix(), time);
+
+        // Past the end of NTP era 0 on 2036-02-07
+        let time = Duration::from_secs(2_085_978_496 + 3600);
+
+        assert_eq!(Timestamp::from_unix(time).0 >> 32, 3600);
+        assert_eq!(Timestamp::from_unix(time).to_unix(), time);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut packet = response(
+            Timestamp(0x0123_4567_89ab_cdef),
+            Timestamp::from_unix(Duration::from_secs(1_700_000_000)),
+            Timestamp::from_unix(Duration::from_secs(1_700_000_001)),
+        );
+        packet.poll = 6;
+        packet.precision = -20;
+        packet.root_delay = 0x0000_0123;
+
+        let mut buf = [0; PACKET_SIZE];
+        let data = packet.encode(&mut buf).unwrap();
+
+        assert_eq!(data[0], 0x24);
+        assert_eq!(Packet::decode(data).unwrap(), packet);
+
+        assert_eq!(
+            Packet::decode(&data[..PACKET_SIZE - 1]),
+            Err(Error::DataUnderflow)
+        );
+        assert_eq!(
+            packet.encode(&mut [0; PACKET_SIZE - 1]),
+            Err(Error::BufferOverflow)
+        );
+    }
+
+    #[test]
+    fn sample() {
+        let sent = Timestamp(0x1234);
+
+        let receive = Duration::from_secs(1_700_000_000);
+        let transmit = receive + Duration::from_millis(10);
+
+        let packet = response(
+            sent,
+            Timestamp::from_unix(receive),
+            Timestamp::from_unix(transmit),
+        );
+
+        let sample = packet.sample(sent, Duration::from_millis(110)).unwrap();
+
+        assert_eq!(sample.round_trip, Duration::from_millis(100));
+        assert_eq!(sample.time, transmit + Duration::from_millis(50));
+        assert_eq!(sample.stratum, 2);
+
+        assert_eq!(sample.offset_micros(transmit), 50_000);
+        assert_eq!(
+            sample.offset_micros(transmit + Duration::from_secs(1)),
+            -950_000
+        );
+
+        assert_eq!(
+            packet.sample(Timestamp(0x4321), Duration::from_millis(110)),
+            Err(Error::UnexpectedResponse)
+        );
+    }
+
+    #[test]
+    fn kiss_of_death() {
+        let sent = Timestamp(0x1234);
+
+        let packet = Packet {
+            stratum: 0,
+            reference_id: *b"RATE",
+            ..response(sent, Timestamp::ZERO, Timestamp::ZERO)
+        };
+
+        let Err(Error::KissOfDeath(code)) = packet.sample(sent, Duration::ZERO) else {
+            panic!("Expected a Kiss-o'-Death");
+        };
+
+        assert_eq!(code, KissCode::RATE);
+        assert!(code.is_rate() && !code.is_deny());
+
+        let packet = Packet {
+            leap: Packet::LEAP_UNSYNCHRONIZED,
+            ..response(sent, Timestamp(1), Timestamp(1))
+        };
+
+        assert_eq!(
+            packet.sample(sent, Duration::ZERO),
+            Err(Error::Unsynchronized)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use core::fmt::{self, Write};
+
+use crate::{Advertisement, Advertisements, SsdpError, Target};
+
+/// The default number of seconds the advertisements of a device are valid for.
+pub const DEFAULT_MAX_AGE: u32 = 1800;
+
+/// A simple representation of a UPnP root device that can be used to generate SSDP advertisements.
+///
+/// This structure implements the `Advertisements` trait, which allows it to be used
+/// as a responder for SSDP searches coming from other network peers.
+#[derive(Debug, Clone)]
+pub struct Device<'a> {
+    /// The UUID of the device (without the `uuid:` prefix).
+    /// Should stay the same across reboots.
+    pub uuid: &'a str,
+    /// The device type, i.e. `urn:schemas-upnp-org:device:Basic:1`
+    pub device_type: &'a str,
+    /// The types of the services of the device, i.e. `urn:schemas-upnp-org:service:SwitchPower:1`
+    pub services: &'a [&'a str],
+    /// The URL of the UPnP description of the device (see `Device::write_description`)
+    pub location: &'a str,
+    /// The `SERVER` header, i.e. `<OS>/<OS version> UPnP/1.0 <product>/<product version>`
+    pub server: &'a str,
+    /// The number of seconds the advertisements are valid for
+    pub max_age: u32,
+}
+
+impl Device<'_> {
+    /// Write the UPnP description of the device, to be served as `text/xml` on the `location` URL.
+    ///
+    /// UPnP control points (i.e. the "Network" folder of Windows) only list the devices whose description
+    /// they could fetch.
+    ///
+    /// Note that the description does not contain a `serviceList` element, because describing
+    /// the services requires their control and eventing URLs; devices which do provide UPnP services
+    /// need to serve their own description.
+    pub fn write_description<W>(&self, description: &Description<'_>, mut w: W) -> fmt::Result
+    where
+        W: Write,
+    {
+        write!(
+            w,
+            "<?xml version=\"1.0\"?>\r\n<root xmlns=\"urn:schemas-upnp-org:device-1-0\">\r\n<specVersion><major>1</major><minor>0</minor></specVersion>\r\n<device>\r\n"
+        )?;
+
+        Self::write_element(&mut w, "deviceType", Some(self.device_type))?;
+        Self::write_element(&mut w, "friendlyName", Some(description.friendly_name))?;
+        Self::write_element(&mut w, "manufacturer", Some(description.manufacturer))?;
+        Self::write_element(&mut w, "manufacturerURL", description.manufacturer_url)?;
+        Self::write_element(&mut w, "modelDescription", description.model_description)?;
+        Self::write_element(&mut w, "modelName", Some(description.model_name))?;
+        Self::write_element(&mut w, "modelNumber", description.model_number)?;
+        Self::write_element(&mut w, "serialNumber", description.serial_number)?;
+
+        write!(w, "<UDN>uuid:{}</UDN>\r\n", Escaped(self.uuid))?;
+
+        Self::write_element(&mut w, "presentationURL", description.presentation_url)?;
+
+        write!(w, "</device>\r\n</root>\r\n")
+    }
+
+    fn write_element<W>(mut w: W, name: &str, value: Option<&str>) -> fmt::Result
+    where
+        W: Write,
+    {
+        if let Some(value) = value {
+            write!(w, "<{name}>{}</{name}>\r\n", Escaped(value))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Advertisements for Device<'_> {
+    fn visit<F, E>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(Advertisement) -> Result<(), E>,
+        E: From<SsdpError>,
+    {
+        let advertisement = |target| Advertisement {
+            target,
+            uuid: self.uuid,
+            location: self.location,
+            server: self.server,
+            max_age: self.max_age,
+        };
+
+        f(advertisement(Target::RootDevice))?;
+        f(advertisement(Target::Uuid))?;
+        f(advertisement(Target::Type(self.device_type)))?;
+
+        for (index, service) in self.services.iter().enumerate() {
+            // Each service type is advertised only once, as per spec
+            if !self.services[..index].contains(service) {
+                f(advertisement(Target::Type(service)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The human-readable part of the UPnP description of a device (see `Device::write_description`).
+#[derive(Debug, Clone)]
+pub struct Description<'a> {
+    /// The name of the device, as displayed by the UPnP control points
+    pub friendly_name: &'a str,
+    /// The name of the manufacturer
+    pub manufacturer: &'a str,
+    /// The web site of the manufacturer
+    pub manufacturer_url: Option<&'a str>,
+    /// A long description of the device
+    pub model_description: Option<&'a str>,
+    /// The model name
+    pub model_name: &'a str,
+    /// The model number
+    pub model_number: Option<&'a str>,
+    /// The serial number
+    pub serial_number: Option<&'a str>,
+    /// The URL of the web UI of the device, opened by the UPnP control points
+    /// (i.e. by double-clicking the device in the "Network" folder of Windows)
+    pub presentation_url: Option<&'a str>,
+}
+
+/// Escapes the XML special characters when displayed
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&apos;")?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use core::cell::RefCell;
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::pin::pin;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex;
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+
+use edge_nal::{BindOptions, MulticastV4, MulticastV6, UdpBind, UdpReceive, UdpSend};
+
+use embassy_time::{Duration, Timer};
+
+use log::{debug, warn};
+
+use super::*;
+
+/// Socket address that binds to any IPv4-configured interface available
+pub const IPV4_DEFAULT_SOCKET: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), PORT);
+
+/// Socket address that binds to any IPv6-configured interface available on single-stack
+/// implementations and to any configured interface available on dual-stack implementations.
+pub const IPV6_DEFAULT_SOCKET: SocketAddr =
+    SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), PORT);
+
+/// A quick-and-dirty socket address that binds to any interface available on dual-stack
+/// implementations.
+/// Don't use in production code.
+pub const DEFAULT_SOCKET: SocketAddr = IPV6_DEFAULT_SOCKET;
+
+/// The TTL (IPv4) or hop limit (IPv6) of the SSDP multicast packets, as per the UPnP Device Architecture spec.
+pub const MULTICAST_TTL: u8 = 2;
+
+/// The default interval between the `ssdp:alive` advertisements.
+///
+/// The advertisements should be repeated before half of their `max-age` elapses,
+/// so it should be lowered for devices with a `max-age` smaller than 30 minutes.
+pub const DEFAULT_NOTIFY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// A wrapper for SSDP and IO errors.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum SsdpIoError<E> {
+    SsdpError(SsdpError),
+    IoError(E),
+}
+
+pub type SsdpIoErrorKind = SsdpIoError<edge_nal::io::ErrorKind>;
+
+impl<E> SsdpIoError<E>
+where
+    E: edge_nal::io::Error,
+{
+    pub fn erase(&self) -> SsdpIoError<edge_nal::io::ErrorKind> {
+        match self {
+            Self::SsdpError(e) => SsdpIoError::SsdpError(*e),
+            Self::IoError(e) => SsdpIoError::IoError(e.kind()),
+        }
+    }
+}
+
+impl<E> From<SsdpError> for SsdpIoError<E> {
+    fn from(err: SsdpError) -> Self {
+        Self::SsdpError(err)
+    }
+}
+
+impl<E> fmt::Display for SsdpIoError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SsdpError(err) => write!(f, "SSDP error: {}", err),
+            Self::IoError(err) => write!(f, "IO error: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for SsdpIoError<E> where E: std::error::Error {}
+
+/// A utility method to bind a socket suitable for SSDP, by using the provided
+/// stack and address, and optionally joining the provided interfaces via multicast.
+///
+/// Note that SSDP is pointless without multicast, so at least one - or both - of the
+/// ipv4 and ipv6 interfaces need to be provided.
+///
+/// The multicast packets sent by the socket go out through the provided interfaces and - as per the
+/// UPnP Device Architecture spec - have their TTL (hop limit) set to 2.
+///
+/// The socket is bound with `SO_REUSEADDR` and `SO_REUSEPORT`, so that the responder can share the SSDP port
+/// with other responders running on the same host (i.e. the Windows SSDP Discovery service or MiniSSDPd).
+pub async fn bind<S>(
+    stack: &S,
+    addr: SocketAddr,
+    ipv4_interface: Option<Ipv4Addr>,
+    ipv6_interface: Option<u32>,
+) -> Result<S::Socket<'_>, SsdpIoError<S::Error>>
+where
+    S: UdpBind,
+{
+    let options = BindOptions::new()
+        .with_reuse_addr(true)
+        .with_reuse_port(true);
+
+    let mut socket = stack
+        .bind_with_options(addr, &options)
+        .await
+        .map_err(SsdpIoError::IoError)?;
+
+    if let Some(v4) = ipv4_interface {
+        socket
+            .join_v4(IPV4_MULTICAST_ADDR, v4)
+            .await
+            .map_err(SsdpIoError::IoError)?;
+
+        socket
+            .set_multicast_if_v4(v4)
+            .await
+            .map_err(SsdpIoError::IoError)?;
+
+        socket
+            .set_multicast_ttl_v4(MULTICAST_TTL)
+            .await
+            .map_err(SsdpIoError::IoError)?;
+    }
+
+    if let Some(v6) = ipv6_interface {
+        socket
+            .join_v6(IPV6_MULTICAST_ADDR, v6)
+            .await
+            .map_err(SsdpIoError::IoError)?;
+
+        socket
+            .set_multicast_if_v6(v6)
+            .await
+            .map_err(SsdpIoError::IoError)?;
+
+        socket
+            .set_multicast_hops_v6(MULTICAST_TTL)
+            .await
+            .map_err(SsdpIoError::IoError)?;
+    }
+
+    Ok(socket)
+}
+
+/// Represents an SSDP service that advertises devices and responds to searches using the provided handler.
+///
+/// This structure is generic over the UDP receiver and sender, the receive and send buffers,
+/// and the raw mutex type.
+///
+/// The handler is expected to be a type that implements the `SsdpHandler` trait, which
+/// allows it to advertise devices and to answer searches, as well as to process the advertisements
+/// of network peers and the responses to the searches we have sent using the `search` method.
+pub struct Ssdp<'a, M, R, S, RB, SB>
+where
+    M: RawMutex,
+{
+    ipv4_interface: Option<Ipv4Addr>,
+    ipv6_interface: Option<u32>,
+    recv: Mutex<M, R>,
+    send: Mutex<M, S>,
+    recv_buf: Mutex<M, RB>,
+    send_buf: Mutex<M, SB>,
+    rand: fn(&mut [u8]),
+    notify_signal: &'a Signal<M, ()>,
+    notify_interval: Duration,
+}
+
+impl<'a, M, R, S, RB, SB> Ssdp<'a, M, R, S, RB, SB>
+where
+    M: RawMutex,
+    R: UdpReceive,
+    S: UdpSend<Error = R::Error>,
+    RB: AsMut<[u8]>,
+    SB: AsMut<[u8]>,
+{
+    /// Creates a new SSDP service with the provided interfaces, UDP receiver and sender, and buffers.
+    ///
+    /// Signalling `notify_signal` makes the service send its `ssdp:alive` advertisements right away,
+    /// i.e. when the advertised devices change.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        ipv4_interface: Option<Ipv4Addr>,
+        ipv6_interface: Option<u32>,
+        recv: R,
+        send: S,
+        recv_buf: RB,
+        send_buf: SB,
+        rand: fn(&mut [u8]),
+        notify_signal: &'a Signal<M, ()>,
+    ) -> Self {
+        Self {
+            ipv4_interface,
+            ipv6_interface,
+            recv: Mutex::new(recv),
+            send: Mutex::new(send),
+            recv_buf: Mutex::new(recv_buf),
+            send_buf: Mutex::new(send_buf),
+            rand,
+            notify_signal,
+            notify_interval: DEFAULT_NOTIFY_INTERVAL,
+        }
+    }
+
+    /// Sets the interval between the `ssdp:alive` advertisements (`DEFAULT_NOTIFY_INTERVAL` by default).
+    pub fn notify_interval(&mut self, notify_interval: Duration) {
+        self.notify_interval = notify_interval;
+    }
+
+    /// Runs the SSDP service, advertising the devices of the handler and answering searches for them,
+    /// as well as processing the advertisements of network peers and the responses to our own searches.
+    ///
+    /// All of the handling logic is expected to be implemented by the provided handler:
+    /// - I.e. processing the advertisements of the network peers cannot happen, unless the supplied handler
+    ///   is capable of doing that (i.e. it is a `PeerAdvertisementsSsdpHandler`, or a chain containing it, or similar).
+    /// - Ditto for advertising devices and answering searches coming from other peers - this can only happen
+    ///   if the handler is capable of doing that. I.e., it is an `AdvertisementsSsdpHandler`, or a chain containing it, or similar.
+    pub async fn run<T>(&self, handler: T) -> Result<(), SsdpIoError<S::Error>>
+    where
+        T: SsdpHandler,
+    {
+        let handler = blocking_mutex::Mutex::<M, _>::new(RefCell::new(handler));
+
+        let mut notify = pin!(self.notify(&handler));
+        let mut respond = pin!(self.respond(&handler));
+
+        let result = select(&mut notify, &mut respond).await;
+
+        match result {
+            Either::First(result) => result,
+            Either::Second(result) => result,
+        }
+    }
+
+    /// Sends the `ssdp:byebye` advertisements of the handler.
+    ///
+    /// Should be called once the `run` future is dropped and before the device leaves the network,
+    /// so that the UPnP control points remove the device right away, rather than once its
+    /// advertisements expire.
+    pub async fn byebye<T>(&self, mut handler: T) -> Result<(), SsdpIoError<S::Error>>
+    where
+        T: SsdpHandler,
+    {
+        self.multicast(|page, buf| handler.handle_page(SsdpRequest::ByeBye, page, buf))
+            .await
+    }
+
+    /// Sends a multicast search with the provided payload.
+    /// It is assumed that the payload represents a valid SSDP `M-SEARCH` message (see `compose_search`).
+    ///
+    /// The responses are processed by the handler passed to `run`.
+    ///
+    /// The payload is constructed via a closure, because this way we can provide to
+    /// the payload-constructing closure a ready-to-use `&mut [u8]` slice, where the
+    /// closure can arrange the message (i.e. we avoid extra memory usage
+    /// by constructing the message directly in the `send_buf` buffer that was supplied
+    /// when the `Ssdp` instance was constructed).
+    pub async fn search<Q>(&self, q: Q) -> Result<(), SsdpIoError<S::Error>>
+    where
+        Q: FnOnce(&mut [u8]) -> Result<usize, SsdpError>,
+    {
+        let mut send_buf = self.send_buf.lock().await;
+
+        let mut send_guard = self.send.lock().await;
+        let send = &mut *send_guard;
+
+        let len = q(send_buf.as_mut())?;
+
+        if len > 0 {
+            self.broadcast_once(send, &send_buf.as_mut()[..len]).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn notify<T>(
+        &self,
+        handler: &blocking_mutex::Mutex<M, RefCell<T>>,
+    ) -> Result<(), SsdpIoError<S::Error>>
+    where
+        T: SsdpHandler,
+    {
+        loop {
+            self.multicast(|page, buf| {
+                handler.lock(|handler| {
+                    handler
+                        .borrow_mut()
+                        .handle_page(SsdpRequest::None, page, buf)
+                })
+            })
+            .await?;
+
+            // Advertisements are sent over UDP, so they are periodically repeated
+            // in case some got lost, as well as for peers which joined the network later
+            select(
+                self.notify_signal.wait(),
+                Timer::after(self.notify_interval),
+            )
+            .await;
+        }
+    }
+
+    async fn multicast<F>(&self, mut handle: F) -> Result<(), SsdpIoError<S::Error>>
+    where
+        F: for<'b> FnMut(usize, &'b mut [u8]) -> Result<SsdpResponse<'b>, SsdpError>,
+    {
+        let mut send_buf = self.send_buf.lock().await;
+
+        let mut send_guard = self.send.lock().await;
+        let send = &mut *send_guard;
+
+        let mut page = 0;
+
+        while let SsdpResponse::Reply { data, more, .. } = handle(page, send_buf.as_mut())? {
+            self.broadcast_once(send, data).await?;
+
+            if !more {
+                break;
+            }
+
+            page += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn respond<T>(
+        &self,
+        handler: &blocking_mutex::Mutex<M, RefCell<T>>,
+    ) -> Result<(), SsdpIoError<S::Error>>
+    where
+        T: SsdpHandler,
+    {
+        let mut recv = self.recv.lock().await;
+        let mut recv_buf = self.recv_buf.lock().await;
+
+        loop {
+            let (len, remote) = recv
+                .receive(recv_buf.as_mut())
+                .await
+                .map_err(SsdpIoError::IoError)?;
+
+            debug!("Got SSDP message from {remote}");
+
+            let mut send_buf = self.send_buf.lock().await;
+
+            let mut send_guard = self.send.lock().await;
+            let send = &mut *send_guard;
+
+            // Searches matching multiple advertisements are answered with multiple responses
+            let mut page = 0;
+
+            loop {
+                let response = match handler.lock(|handler| {
+                    handler.borrow_mut().handle_page(
+                        SsdpRequest::Request {
+                            data: &recv_buf.as_mut()[..len],
+                        },
+                        page,
+                        send_buf.as_mut(),
+                    )
+                }) {
+                    Ok(response) => response,
+                    Err(SsdpError::InvalidMessage) => {
+                        warn!("Got invalid message from {remote}, skipping");
+                        break;
+                    }
+                    Err(other) => Err(other)?,
+                };
+
+                let SsdpResponse::Reply { data, target, more } = response else {
+                    break;
+                };
+
+                match target {
+                    SsdpReplyTarget::Unicast { max_delay_secs } => {
+                        if page == 0 && max_delay_secs > 0 {
+                            // TODO: Not ideal, as we hold the lock during the delay
+                            let delay_ms = reply_delay_ms(self.rand, max_delay_secs);
+
+                            Timer::after(Duration::from_millis(delay_ms as _)).await;
+                        }
+
+                        debug!("Replying privately to an SSDP search from {remote}");
+
+                        if let Err(err) = send.send(remote, data).await {
+                            warn!("Failed to reply privately to {remote}: {err:?}");
+                            break;
+                        }
+                    }
+                    SsdpReplyTarget::Multicast => {
+                        debug!("Multicasting due to SSDP message from {remote}");
+
+                        self.broadcast_once(send, data).await?;
+                    }
+                }
+
+                if !more {
+                    break;
+                }
+
+                page += 1;
+            }
+        }
+    }
+
+    async fn broadcast_once(&self, send: &mut S, data: &[u8]) -> Result<(), SsdpIoError<S::Error>> {
+        for remote_addr in
+            core::iter::once(SocketAddr::V4(SocketAddrV4::new(IPV4_MULTICAST_ADDR, PORT)))
+                .filter(|_| self.ipv4_interface.is_some())
+                .chain(self.ipv6_interface.map(|interface| {
+                    SocketAddr::V6(SocketAddrV6::new(IPV6_MULTICAST_ADDR, PORT, 0, interface))
+                }))
+        {
+            if !data.is_empty() {
+                debug!("Multicasting SSDP message to {remote_addr}");
+
+                let fut = pin!(send.send(remote_addr, data));
+
+                fut.await.map_err(SsdpIoError::IoError)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1127 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![warn(clippy::large_futures)]
+#![allow(async_fn_in_trait)]
+
+use core::fmt::{self, Display, Write};
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+use log::debug;
+
+pub mod host;
+#[cfg(feature = "io")]
+pub mod io;
+
+/// The SSDP port, as per spec.
+pub const PORT: u16 = 1900;
+
+/// The IPv4 SSDP multicast address, as per spec.
+pub const IPV4_MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+/// The IPv6 (link-local) SSDP multicast address, as per spec.
+pub const IPV6_MULTICAST_ADDR: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x000c);
+
+/// The search target matching all devices and services.
+pub const ALL: &str = "ssdp:all";
+/// The search target matching all root devices.
+pub const ROOT_DEVICE: &str = "upnp:rootdevice";
+
+/// The maximum number of seconds a device should wait before replying to a multicast search,
+/// regardless of the `MX` value of the search, as per the UPnP Device Architecture spec.
+pub const MAX_MX: u8 = 5;
+
+/// The maximum number of headers of an incoming SSDP message.
+pub const MAX_HEADERS: usize = 24;
+
+/// The value of the `HOST` header of the multicast messages.
+///
+/// Only the IPv4 multicast address is used, even for the messages sent over IPv6,
+/// because the handlers which compose the messages do not know where those would be sent.
+/// Peers do not use the header for anything but validation anyway.
+const HOST: &str = "239.255.255.250:1900";
+
+/// An error while parsing or composing SSDP messages.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum SsdpError {
+    /// The buffer is too small for the message
+    ShortBuf,
+    /// The message is not a valid SSDP message
+    InvalidMessage,
+}
+
+impl Display for SsdpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShortBuf => write!(f, "ShortBuf"),
+            Self::InvalidMessage => write!(f, "InvalidMessage"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SsdpError {}
+
+impl From<fmt::Error> for SsdpError {
+    fn from(_: fmt::Error) -> Self {
+        Self::ShortBuf
+    }
+}
+
+impl From<httparse::Error> for SsdpError {
+    fn from(_: httparse::Error) -> Self {
+        Self::InvalidMessage
+    }
+}
+
+/// The notification type (`NT`) of an advertisement, which is also the search target (`ST`)
+/// the advertisement is found with.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Target<'a> {
+    /// `upnp:rootdevice` - advertised once per root device
+    RootDevice,
+    /// `uuid:<uuid>` - advertised once per device
+    Uuid,
+    /// A device or a service type, i.e. `urn:schemas-upnp-org:device:Basic:1`
+    Type(&'a str),
+}
+
+/// A single advertisement of a device, i.e. one of the `NOTIFY` messages the device multicasts,
+/// and one of the responses to a search matching it.
+#[derive(Debug, Clone)]
+pub struct Advertisement<'a> {
+    /// What is advertised
+    pub target: Target<'a>,
+    /// The UUID of the device (without the `uuid:` prefix)
+    pub uuid: &'a str,
+    /// The URL of the UPnP description of the device
+    pub location: &'a str,
+    /// The `SERVER` header, i.e. `<OS>/<OS version> UPnP/1.0 <product>/<product version>`
+    pub server: &'a str,
+    /// The number of seconds the advertisement is valid for
+    pub max_age: u32,
+}
+
+impl<'a> Advertisement<'a> {
+    /// Return the notification type (`NT`) of the advertisement.
+    pub fn nt(&self) -> impl Display + 'a {
+        Nt {
+            target: self.target,
+            uuid: self.uuid,
+        }
+    }
+
+    /// Return the unique service name (`USN`) of the advertisement.
+    pub fn usn(&self) -> impl Display + 'a {
+        Usn {
+            target: self.target,
+            uuid: self.uuid,
+        }
+    }
+
+    /// Return `true` if the advertisement matches the provided search target (`ST`).
+    ///
+    /// Searches for an older version of a device or a service type match the newer versions as well,
+    /// as these are backwards-compatible, as per the UPnP Device Architecture spec.
+    pub fn matches(&self, st: &str) -> bool {
+        if st == ALL {
+            return true;
+        }
+
+        match self.target {
+            Target::RootDevice => st == ROOT_DEVICE,
+            Target::Uuid => st
+                .strip_prefix("uuid:")
+                .is_some_and(|uuid| uuid.eq_ignore_ascii_case(self.uuid)),
+            Target::Type(ty) => {
+                st == ty
+                    || match (st.rsplit_once(':'), ty.rsplit_once(':')) {
+                        (Some((st_ty, st_version)), Some((ty, version))) => {
+                            st_ty == ty
+                                && matches!(
+                                    (st_version.parse::<u32>(), version.parse::<u32>()),
+                                    (Ok(st_version), Ok(version)) if st_version <= version
+                                )
+                        }
+                        _ => false,
+                    }
+            }
+        }
+    }
+
+    /// Compose an `ssdp:alive` `NOTIFY` message with the advertisement in `buf`.
+    ///
+    /// Returns the length of the message.
+    pub fn compose_alive(&self, buf: &mut [u8]) -> Result<usize, SsdpError> {
+        compose(buf, |w| {
+            write!(
+                w,
+                "NOTIFY * HTTP/1.1\r\nHOST: {HOST}\r\nCACHE-CONTROL: max-age={}\r\nLOCATION: {}\r\nNT: {}\r\nNTS: ssdp:alive\r\nSERVER: {}\r\nUSN: {}\r\n\r\n",
+                self.max_age,
+                self.location,
+                self.nt(),
+                self.server,
+                self.usn()
+            )
+        })
+    }
+
+    /// Compose an `ssdp:byebye` `NOTIFY` message with the advertisement in `buf`.
+    ///
+    /// Returns the length of the message.
+    pub fn compose_byebye(&self, buf: &mut [u8]) -> Result<usize, SsdpError> {
+        compose(buf, |w| {
+            write!(
+                w,
+                "NOTIFY * HTTP/1.1\r\nHOST: {HOST}\r\nNT: {}\r\nNTS: ssdp:byebye\r\nUSN: {}\r\n\r\n",
+                self.nt(),
+                self.usn()
+            )
+        })
+    }
+
+    /// Compose a response to a search for `st` with the advertisement in `buf`.
+    ///
+    /// Returns the length of the message.
+    pub fn compose_search_response(&self, st: &str, buf: &mut [u8]) -> Result<usize, SsdpError> {
+        compose(buf, |w| {
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nEXT:\r\nLOCATION: {}\r\nSERVER: {}\r\nST: ",
+                self.max_age, self.location, self.server,
+            )?;
+
+            // The response to a search for all targets carries the target of the advertisement,
+            // while the response to any other search - the searched target, as per spec
+            if st == ALL {
+                write!(w, "{}", self.nt())?;
+            } else {
+                write!(w, "{st}")?;
+            }
+
+            write!(w, "\r\nUSN: {}\r\n\r\n", self.usn())
+        })
+    }
+}
+
+impl Display for Advertisement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.usn(), self.location)
+    }
+}
+
+struct Nt<'a> {
+    target: Target<'a>,
+    uuid: &'a str,
+}
+
+impl Display for Nt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            Target::RootDevice => write!(f, "{ROOT_DEVICE}"),
+            Target::Uuid => write!(f, "uuid:{}", self.uuid),
+            Target::Type(ty) => write!(f, "{ty}"),
+        }
+    }
+}
+
+struct Usn<'a> {
+    target: Target<'a>,
+    uuid: &'a str,
+}
+
+impl Display for Usn<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            Target::Uuid => write!(f, "uuid:{}", self.uuid),
+            _ => write!(
+                f,
+                "uuid:{}::{}",
+                self.uuid,
+                Nt {
+                    target: self.target,
+                    uuid: self.uuid
+                }
+            ),
+        }
+    }
+}
+
+/// Compose an `M-SEARCH` message for the provided search target (`ST`) in `buf`.
+///
+/// Parameters:
+/// - `st`: The search target, i.e. `ssdp:all`, `upnp:rootdevice`, `uuid:<uuid>` or a device or a service type
+/// - `mx`: The maximum number of seconds the devices should wait before replying (1 to 5)
+/// - `buf`: The buffer where the message will be composed
+///
+/// Returns the length of the message.
+pub fn compose_search(st: &str, mx: u8, buf: &mut [u8]) -> Result<usize, SsdpError> {
+    compose(buf, |w| {
+        write!(
+            w,
+            "M-SEARCH * HTTP/1.1\r\nHOST: {HOST}\r\nMAN: \"ssdp:discover\"\r\nMX: {mx}\r\nST: {st}\r\n\r\n"
+        )
+    })
+}
+
+fn compose<F>(buf: &mut [u8], f: F) -> Result<usize, SsdpError>
+where
+    F: FnOnce(&mut Buf) -> fmt::Result,
+{
+    let mut buf = Buf(buf, 0);
+
+    f(&mut buf)?;
+
+    Ok(buf.1)
+}
+
+/// What a peer advertisement is about.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PeerAdvertisementKind {
+    /// An `ssdp:alive` `NOTIFY` message - the peer is available
+    Alive,
+    /// An `ssdp:byebye` `NOTIFY` message - the peer is no longer available
+    ByeBye,
+    /// A response to a search
+    Found,
+}
+
+/// An advertisement of a network peer, parsed from an incoming SSDP message.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PeerAdvertisement<'a> {
+    /// What the advertisement is about
+    pub kind: PeerAdvertisementKind,
+    /// The notification type (`NT`), or the search target (`ST`) of a search response
+    pub target: &'a str,
+    /// The unique service name (`USN`)
+    pub usn: &'a str,
+    /// The URL of the UPnP description of the peer (`None` for `ssdp:byebye` messages)
+    pub location: Option<&'a str>,
+    /// The `SERVER` header
+    pub server: Option<&'a str>,
+    /// The number of seconds the advertisement is valid for (`None` for `ssdp:byebye` messages)
+    pub max_age: Option<u32>,
+}
+
+impl<'a> PeerAdvertisement<'a> {
+    /// Return the UUID of the peer device (without the `uuid:` prefix), as contained in the `USN`.
+    pub fn uuid(&self) -> Option<&'a str> {
+        let uuid = self.usn.strip_prefix("uuid:")?;
+
+        Some(uuid.split_once("::").map(|(uuid, _)| uuid).unwrap_or(uuid))
+    }
+}
+
+impl Display for PeerAdvertisement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {}", self.kind, self.usn)?;
+
+        if let Some(location) = self.location {
+            write!(f, " ({location})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An incoming SSDP message.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum SsdpMessage<'a> {
+    /// An `M-SEARCH` message
+    Search {
+        /// The search target
+        st: &'a str,
+        /// The maximum number of seconds to wait before replying; `None` for unicast searches
+        mx: Option<u8>,
+    },
+    /// A `NOTIFY` message, or a response to a search
+    Advertisement(PeerAdvertisement<'a>),
+}
+
+impl<'a> SsdpMessage<'a> {
+    /// Parse an incoming SSDP message.
+    ///
+    /// Returns `None` for well-formed messages which are not of interest,
+    /// i.e. `ssdp:update` notifications, or search responses with an error status.
+    pub fn parse(data: &'a [u8]) -> Result<Option<Self>, SsdpError> {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+
+        if data.starts_with(b"HTTP/") {
+            let mut response = httparse::Response::new(&mut headers);
+
+            if !response.parse(data)?.is_complete() {
+                return Err(SsdpError::InvalidMessage);
+            }
+
+            if response.code != Some(200) {
+                return Ok(None);
+            }
+
+            let headers = Headers::parse(response.headers)?;
+
+            Ok(Some(Self::Advertisement(PeerAdvertisement {
+                kind: PeerAdvertisementKind::Found,
+                target: Headers::required(headers.st)?,
+                usn: Headers::required(headers.usn)?,
+                location: headers.location,
+                server: headers.server,
+                max_age: headers.cache_control.and_then(max_age),
+            })))
+        } else {
+            let mut request = httparse::Request::new(&mut headers);
+
+            if !request.parse(data)?.is_complete() {
+                return Err(SsdpError::InvalidMessage);
+            }
+
+            let method = request.method;
+            let headers = Headers::parse(request.headers)?;
+
+            match method {
+                Some("M-SEARCH") => {
+                    if headers.man != Some("\"ssdp:discover\"") {
+                        return Ok(None);
+                    }
+
+                    let mx = headers
+                        .mx
+                        .map(|mx| mx.parse::<u8>().map_err(|_| SsdpError::InvalidMessage))
+                        .transpose()?;
+
+                    Ok(Some(Self::Search {
+                        st: Headers::required(headers.st)?,
+                        mx,
+                    }))
+                }
+                Some("NOTIFY") => {
+                    let kind = match Headers::required(headers.nts)? {
+                        "ssdp:alive" => PeerAdvertisementKind::Alive,
+                        "ssdp:byebye" => PeerAdvertisementKind::ByeBye,
+                        _ => return Ok(None),
+                    };
+
+                    Ok(Some(Self::Advertisement(PeerAdvertisement {
+                        kind,
+                        target: Headers::required(headers.nt)?,
+                        usn: Headers::required(headers.usn)?,
+                        location: headers.location,
+                        server: headers.server,
+                        max_age: headers.cache_control.and_then(max_age),
+                    })))
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+}
+
+/// The headers of an incoming SSDP message which are of interest
+#[derive(Default)]
+struct Headers<'a> {
+    st: Option<&'a str>,
+    nt: Option<&'a str>,
+    nts: Option<&'a str>,
+    usn: Option<&'a str>,
+    man: Option<&'a str>,
+    mx: Option<&'a str>,
+    location: Option<&'a str>,
+    server: Option<&'a str>,
+    cache_control: Option<&'a str>,
+}
+
+impl<'a> Headers<'a> {
+    fn parse(headers: &[httparse::Header<'a>]) -> Result<Self, SsdpError> {
+        let mut this = Self::default();
+
+        for header in headers {
+            let field = match header.name {
+                name if name.eq_ignore_ascii_case("ST") => &mut this.st,
+                name if name.eq_ignore_ascii_case("NT") => &mut this.nt,
+                name if name.eq_ignore_ascii_case("NTS") => &mut this.nts,
+                name if name.eq_ignore_ascii_case("USN") => &mut this.usn,
+                name if name.eq_ignore_ascii_case("MAN") => &mut this.man,
+                name if name.eq_ignore_ascii_case("MX") => &mut this.mx,
+                name if name.eq_ignore_ascii_case("LOCATION") => &mut this.location,
+                name if name.eq_ignore_ascii_case("SERVER") => &mut this.server,
+                name if name.eq_ignore_ascii_case("CACHE-CONTROL") => &mut this.cache_control,
+                _ => continue,
+            };
+
+            let value =
+                core::str::from_utf8(header.value).map_err(|_| SsdpError::InvalidMessage)?;
+
+            *field = Some(value.trim());
+        }
+
+        Ok(this)
+    }
+
+    fn required(value: Option<&'a str>) -> Result<&'a str, SsdpError> {
+        value
+            .filter(|value| !value.is_empty())
+            .ok_or(SsdpError::InvalidMessage)
+    }
+}
+
+/// Extract the `max-age` directive from a `CACHE-CONTROL` header
+fn max_age(cache_control: &str) -> Option<u32> {
+    cache_control.split(',').find_map(|directive| {
+        let (name, value) = directive.split_once('=')?;
+
+        if name.trim().eq_ignore_ascii_case("max-age") {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Type of request for `SsdpHandler::handle`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SsdpRequest<'a> {
+    /// No incoming SSDP message. Send the `ssdp:alive` advertisements
+    None,
+    /// No incoming SSDP message. Send the `ssdp:byebye` advertisements,
+    /// because the device is about to leave the network
+    ByeBye,
+    /// Incoming SSDP message
+    Request {
+        /// The data of the message
+        data: &'a [u8],
+    },
+}
+
+/// Instructs the caller of `SsdpHandler::handle` how to send the prepared reply.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SsdpReplyTarget {
+    /// Reply privately (unicast) to the address and port the message came from,
+    /// after a random delay of up to `max_delay_secs` seconds (see `reply_delay_ms`), as per spec.
+    Unicast { max_delay_secs: u8 },
+    /// Send the reply to the SSDP multicast address(es).
+    Multicast,
+}
+
+/// Return type for `SsdpHandler::handle`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SsdpResponse<'a> {
+    None,
+    Reply {
+        data: &'a [u8],
+        target: SsdpReplyTarget,
+        /// Whether the response continues in the next page (see `SsdpHandler::handle_page`)
+        more: bool,
+    },
+}
+
+/// Compute the random delay (in milliseconds) to wait before replying privately to a search.
+///
+/// Parameters:
+/// - `rand`: A function which fills the provided buffer with random bytes
+/// - `max_delay_secs`: The maximum delay, as instructed by `SsdpReplyTarget::Unicast`
+pub fn reply_delay_ms<F>(rand: F, max_delay_secs: u8) -> u32
+where
+    F: FnOnce(&mut [u8]),
+{
+    let mut b = [0; 2];
+    rand(&mut b);
+
+    u16::from_le_bytes(b) as u32 * max_delay_secs.min(MAX_MX) as u32 * 1000 / 65536
+}
+
+/// A trait that abstracts the processing logic for an incoming SSDP message.
+///
+/// Handles an incoming SSDP message by parsing it and potentially preparing a response.
+///
+/// If request is `None` or `ByeBye`, the handler should prepare the `ssdp:alive` or
+/// the `ssdp:byebye` messages with all its advertisements.
+///
+/// Returns an `SsdpResponse` instance that instructs the caller
+/// what data to send as a response (if any) and where to send it.
+pub trait SsdpHandler {
+    fn handle<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError>;
+
+    /// Same as `handle`, but prepares the `page`-th message of a response which consists of
+    /// multiple messages (i.e. one `NOTIFY` message for each advertisement of a device).
+    ///
+    /// Callers start with page 0 and keep asking for the next page for as long as the returned reply
+    /// has its `more` flag set.
+    ///
+    /// The default implementation returns the response of `handle` as the one and only page.
+    fn handle_page<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        if page == 0 {
+            self.handle(request, response_buf)
+        } else {
+            Ok(SsdpResponse::None)
+        }
+    }
+}
+
+impl<T> SsdpHandler for &mut T
+where
+    T: SsdpHandler,
+{
+    fn handle<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        (**self).handle(request, response_buf)
+    }
+
+    fn handle_page<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        (**self).handle_page(request, page, response_buf)
+    }
+}
+
+/// A structure representing a handler that does not do any processing.
+///
+/// Useful only when chaining multiple `SsdpHandler` instances.
+pub struct NoHandler;
+
+impl NoHandler {
+    /// Chains a `NoHandler` with another handler.
+    pub fn chain<T>(self, handler: T) -> ChainedHandler<T, Self> {
+        ChainedHandler::new(handler, self)
+    }
+}
+
+impl SsdpHandler for NoHandler {
+    fn handle<'a>(
+        &mut self,
+        _request: SsdpRequest<'_>,
+        _response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        Ok(SsdpResponse::None)
+    }
+}
+
+/// A composite handler that chains two handlers together.
+pub struct ChainedHandler<T, U> {
+    first: T,
+    second: U,
+}
+
+impl<T, U> ChainedHandler<T, U> {
+    /// Create a new `ChainedHandler` instance from two handlers.
+    pub const fn new(first: T, second: U) -> Self {
+        Self { first, second }
+    }
+
+    /// Chains a `ChainedHandler` with another handler,
+    /// where our instance would be the first one to be called.
+    ///
+    /// Chaining works by calling each chained handler from the first to the last,
+    /// until a handler in the chain returns a reply.
+    ///
+    /// Once that happens, traversing the handlers down the chain stops.
+    pub fn chain<V>(self, handler: V) -> ChainedHandler<V, Self> {
+        ChainedHandler::new(handler, self)
+    }
+}
+
+impl<T, U> SsdpHandler for ChainedHandler<T, U>
+where
+    T: SsdpHandler,
+    U: SsdpHandler,
+{
+    fn handle<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        self.handle_page(request, 0, response_buf)
+    }
+
+    fn handle_page<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        match self
+            .first
+            .handle_page(request.clone(), page, response_buf)?
+        {
+            SsdpResponse::None => self.second.handle_page(request, page, response_buf),
+            SsdpResponse::Reply { data, target, more } => {
+                let len = data.len();
+
+                Ok(SsdpResponse::Reply {
+                    data: &response_buf[..len],
+                    target,
+                    more,
+                })
+            }
+        }
+    }
+}
+
+/// A trait that abstracts the logic for providing the advertisements of a device.
+///
+/// The visitor-pattern-with-a-callback is chosen for symmetry with the `edge-mdns` `HostAnswers` trait,
+/// and because it allows the advertisements to be constructed on-the-fly.
+///
+/// Look at the implementation of `Advertisements` for `host::Device` for an example.
+pub trait Advertisements {
+    /// Visits an entity that does have advertisements.
+    ///
+    /// The advertisements will be provided to the supplied `f` callback.
+    ///
+    /// Note that the entity should provide ALL of its advertisements, regardless of the
+    /// concrete search target. The matching is done by the caller.
+    fn visit<F, E>(&self, f: F) -> Result<(), E>
+    where
+        F: FnMut(Advertisement) -> Result<(), E>,
+        E: From<SsdpError>;
+}
+
+impl<T> Advertisements for &T
+where
+    T: Advertisements,
+{
+    fn visit<F, E>(&self, f: F) -> Result<(), E>
+    where
+        F: FnMut(Advertisement) -> Result<(), E>,
+        E: From<SsdpError>,
+    {
+        (*self).visit(f)
+    }
+}
+
+impl<T> Advertisements for &mut T
+where
+    T: Advertisements,
+{
+    fn visit<F, E>(&self, f: F) -> Result<(), E>
+    where
+        F: FnMut(Advertisement) -> Result<(), E>,
+        E: From<SsdpError>,
+    {
+        (**self).visit(f)
+    }
+}
+
+/// A structure modeling an entity that does not have any advertisements.
+///
+/// Useful only when chaining multiple `Advertisements` instances.
+pub struct NoAdvertisements;
+
+impl NoAdvertisements {
+    /// Chains a `NoAdvertisements` with another `Advertisements` instance.
+    pub fn chain<T>(self, advertisements: T) -> ChainedAdvertisements<T, Self> {
+        ChainedAdvertisements::new(advertisements, self)
+    }
+}
+
+impl Advertisements for NoAdvertisements {
+    fn visit<F, E>(&self, _f: F) -> Result<(), E>
+    where
+        F: FnMut(Advertisement) -> Result<(), E>,
+    {
+        Ok(())
+    }
+}
+
+/// A composite `Advertisements` that chains two `Advertisements` instances together.
+pub struct ChainedAdvertisements<T, U> {
+    first: T,
+    second: U,
+}
+
+impl<T, U> ChainedAdvertisements<T, U> {
+    /// Create a new `ChainedAdvertisements` instance from two `Advertisements` instances.
+    pub const fn new(first: T, second: U) -> Self {
+        Self { first, second }
+    }
+
+    /// Chains this instance with another `Advertisements` instance.
+    pub fn chain<V>(self, advertisements: V) -> ChainedAdvertisements<V, Self> {
+        ChainedAdvertisements::new(advertisements, self)
+    }
+}
+
+impl<T, U> Advertisements for ChainedAdvertisements<T, U>
+where
+    T: Advertisements,
+    U: Advertisements,
+{
+    fn visit<F, E>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(Advertisement) -> Result<(), E>,
+        E: From<SsdpError>,
+    {
+        self.first.visit(&mut f)?;
+        self.second.visit(f)
+    }
+}
+
+/// An `SsdpHandler` implementation that advertises the devices provided by an entity
+/// implementing the `Advertisements` trait, and answers the searches matching them.
+///
+/// Typically, this structure will be used to make a device discoverable by UPnP control points
+/// (i.e. the "Network" folder of Windows) - i.e. this is the "responder" aspect of the SSDP protocol.
+pub struct AdvertisementsSsdpHandler<T> {
+    advertisements: T,
+}
+
+impl<T> AdvertisementsSsdpHandler<T> {
+    /// Create a new `AdvertisementsSsdpHandler` instance from an entity that provides advertisements.
+    pub const fn new(advertisements: T) -> Self {
+        Self { advertisements }
+    }
+}
+
+impl<T> AdvertisementsSsdpHandler<T>
+where
+    T: Advertisements,
+{
+    /// Compose the `page`-th message with the advertisements matching `filter`
+    ///
+    /// Returns the length of the message (if there is such a page) and whether there are more pages.
+    fn compose<M, C>(
+        &self,
+        page: usize,
+        filter: M,
+        mut compose: C,
+    ) -> Result<(Option<usize>, bool), SsdpError>
+    where
+        M: Fn(&Advertisement) -> bool,
+        C: FnMut(&Advertisement) -> Result<usize, SsdpError>,
+    {
+        let mut index = 0;
+        let mut len = None;
+        let mut more = false;
+
+        self.advertisements.visit(|advertisement| {
+            if filter(&advertisement) {
+                if index == page {
+                    len = Some(compose(&advertisement)?);
+                } else if index > page {
+                    more = true;
+                }
+
+                index += 1;
+            }
+
+            Ok::<_, SsdpError>(())
+        })?;
+
+        Ok((len, more))
+    }
+}
+
+impl<T> SsdpHandler for AdvertisementsSsdpHandler<T>
+where
+    T: Advertisements,
+{
+    fn handle<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        self.handle_page(request, 0, response_buf)
+    }
+
+    fn handle_page<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        page: usize,
+        response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        let (len, target, more) = match request {
+            SsdpRequest::None => {
+                let (len, more) = self.compose(
+                    page,
+                    |_| true,
+                    |advertisement| advertisement.compose_alive(response_buf),
+                )?;
+
+                (len, SsdpReplyTarget::Multicast, more)
+            }
+            SsdpRequest::ByeBye => {
+                let (len, more) = self.compose(
+                    page,
+                    |_| true,
+                    |advertisement| advertisement.compose_byebye(response_buf),
+                )?;
+
+                (len, SsdpReplyTarget::Multicast, more)
+            }
+            SsdpRequest::Request { data } => {
+                let Some(SsdpMessage::Search { st, mx }) = SsdpMessage::parse(data)? else {
+                    return Ok(SsdpResponse::None);
+                };
+
+                let (len, more) = self.compose(
+                    page,
+                    |advertisement| advertisement.matches(st),
+                    |advertisement| {
+                        debug!("Answering search for [{st}] with: [{advertisement}]");
+
+                        advertisement.compose_search_response(st, response_buf)
+                    },
+                )?;
+
+                (
+                    len,
+                    SsdpReplyTarget::Unicast {
+                        max_delay_secs: mx.unwrap_or(0).min(MAX_MX),
+                    },
+                    more,
+                )
+            }
+        };
+
+        Ok(match len {
+            Some(len) => SsdpResponse::Reply {
+                data: &response_buf[..len],
+                target,
+                more,
+            },
+            None => SsdpResponse::None,
+        })
+    }
+}
+
+/// A trait that abstracts the logic for processing the advertisements of network peers,
+/// as multicast by them or received in response to our searches.
+pub trait PeerAdvertisements {
+    /// Processes an advertisement from an incoming SSDP message.
+    fn advertisement(&self, advertisement: &PeerAdvertisement<'_>) -> Result<(), SsdpError>;
+}
+
+impl<T> PeerAdvertisements for &mut T
+where
+    T: PeerAdvertisements,
+{
+    fn advertisement(&self, advertisement: &PeerAdvertisement<'_>) -> Result<(), SsdpError> {
+        (**self).advertisement(advertisement)
+    }
+}
+
+impl<T> PeerAdvertisements for &T
+where
+    T: PeerAdvertisements,
+{
+    fn advertisement(&self, advertisement: &PeerAdvertisement<'_>) -> Result<(), SsdpError> {
+        (*self).advertisement(advertisement)
+    }
+}
+
+/// A structure implementing the `SsdpHandler` trait by delegating the advertisements
+/// from the incoming SSDP messages to an entity implementing the `PeerAdvertisements` trait.
+///
+/// Typically, this structure will be used to process the responses to the searches
+/// sent with `compose_search`, as well as the `NOTIFY` messages multicast by the network peers,
+/// i.e., this is the "search client" part of the SSDP protocol.
+///
+/// This handler never replies anything.
+pub struct PeerAdvertisementsSsdpHandler<T> {
+    advertisements: T,
+}
+
+impl<T> PeerAdvertisementsSsdpHandler<T> {
+    /// Create a new `PeerAdvertisementsSsdpHandler` instance from an entity that processes advertisements.
+    pub const fn new(advertisements: T) -> Self {
+        Self { advertisements }
+    }
+}
+
+impl<T> SsdpHandler for PeerAdvertisementsSsdpHandler<T>
+where
+    T: PeerAdvertisements,
+{
+    fn handle<'a>(
+        &mut self,
+        request: SsdpRequest<'_>,
+        _response_buf: &'a mut [u8],
+    ) -> Result<SsdpResponse<'a>, SsdpError> {
+        let SsdpRequest::Request { data } = request else {
+            return Ok(SsdpResponse::None);
+        };
+
+        if let Some(SsdpMessage::Advertisement(advertisement)) = SsdpMessage::parse(data)? {
+            self.advertisements.advertisement(&advertisement)?;
+        }
+
+        Ok(SsdpResponse::None)
+    }
+}
+
+/// A `fmt::Write` implementation over a `&mut [u8]` slice
+struct Buf<'a>(&'a mut [u8], usize);
+
+impl fmt::Write for Buf<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.1 + s.len();
+
+        if end > self.0.len() {
+            return Err(fmt::Error);
+        }
+
+        self.0[self.1..end].copy_from_slice(s.as_bytes());
+        self.1 = end;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UUID: &str = "2fac1234-31f8-11b4-a222-08002b34c003";
+
+    fn advertisement(target: Target<'static>) -> Advertisement<'static> {
+        Advertisement {
+            target,
+            uuid: UUID,
+            location: "http://192.168.1.10:80/description.xml",
+            server: "edge-net/1.0 UPnP/1.0 test/1.0",
+            max_age: 1800,
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let device = advertisement(Target::Type("urn:schemas-upnp-org:device:Basic:2"));
+
+        assert!(device.matches(ALL));
+        assert!(device.matches("urn:schemas-upnp-org:device:Basic:2"));
+        assert!(device.matches("urn:schemas-upnp-org:device:Basic:1"));
+        assert!(!device.matches("urn:schemas-upnp-org:device:Basic:3"));
+        assert!(!device.matches("urn:schemas-upnp-org:device:Printer:1"));
+        assert!(!device.matches(ROOT_DEVICE));
+
+        assert!(advertisement(Target::RootDevice).matches(ROOT_DEVICE));
+        assert!(advertisement(Target::Uuid).matches("uuid:2FAC1234-31F8-11B4-A222-08002B34C003"));
+        assert!(!advertisement(Target::Uuid).matches(UUID));
+    }
+
+    #[test]
+    fn notify() {
+        let mut buf = [0; 512];
+
+        let len = advertisement(Target::RootDevice)
+            .compose_alive(&mut buf)
+            .unwrap();
+
+        let Some(SsdpMessage::Advertisement(peer)) = SsdpMessage::parse(&buf[..len]).unwrap()
+        else {
+            panic!("Not an advertisement");
+        };
+
+        assert_eq!(peer.kind, PeerAdvertisementKind::Alive);
+        assert_eq!(peer.target, ROOT_DEVICE);
+        assert_eq!(
+            peer.usn,
+            "uuid:2fac1234-31f8-11b4-a222-08002b34c003::upnp:rootdevice"
+        );
+        assert_eq!(peer.uuid(), Some(UUID));
+        assert_eq!(
+            peer.location,
+            Some("http://192.168.1.10:80/description.xml")
+        );
+        assert_eq!(peer.max_age, Some(1800));
+
+        let len = advertisement(Target::Uuid)
+            .compose_byebye(&mut buf)
+            .unwrap();
+
+        let Some(SsdpMessage::Advertisement(peer)) = SsdpMessage::parse(&buf[..len]).unwrap()
+        else {
+            panic!("Not an advertisement");
+        };
+
+        assert_eq!(peer.kind, PeerAdvertisementKind::ByeBye);
+        assert_eq!(peer.usn, "uuid:2fac1234-31f8-11b4-a222-08002b34c003");
+        assert_eq!(peer.location, None);
+
+        assert_eq!(
+            advertisement(Target::RootDevice).compose_alive(&mut [0; 64]),
+            Err(SsdpError::ShortBuf)
+        );
+    }
+
+    #[test]
+    fn search() {
+        let mut buf = [0; 512];
+
+        let len = compose_search("urn:schemas-upnp-org:device:Basic:1", 3, &mut buf).unwrap();
+
+        assert_eq!(
+            SsdpMessage::parse(&buf[..len]).unwrap(),
+            Some(SsdpMessage::Search {
+                st: "urn:schemas-upnp-org:device:Basic:1",
+                mx: Some(3)
+            })
+        );
+
+        let mut response = [0; 512];
+
+        let mut handler = AdvertisementsSsdpHandler::new(crate::host::Device {
+            uuid: UUID,
+            device_type: "urn:schemas-upnp-org:device:Basic:2",
+            services: &[],
+            location: "http://192.168.1.10:80/description.xml",
+            server: "edge-net/1.0 UPnP/1.0 test/1.0",
+            max_age: 1800,
+        });
+
+        let SsdpResponse::Reply { data, target, more } = handler
+            .handle(SsdpRequest::Request { data: &buf[..len] }, &mut response)
+            .unwrap()
+        else {
+            panic!("No reply");
+        };
+
+        assert_eq!(target, SsdpReplyTarget::Unicast { max_delay_secs: 3 });
+        assert!(!more);
+
+        let Some(SsdpMessage::Advertisement(peer)) = SsdpMessage::parse(data).unwrap() else {
+            panic!("Not an advertisement");
+        };
+
+        assert_eq!(peer.kind, PeerAdvertisementKind::Found);
+        assert_eq!(peer.target, "urn:schemas-upnp-org:device:Basic:1");
+        assert_eq!(
+            peer.usn,
+            "uuid:2fac1234-31f8-11b4-a222-08002b34c003::urn:schemas-upnp-org:device:Basic:2"
+        );
+
+        // Not a discovery request
+        assert_eq!(
+            SsdpMessage::parse(
+                b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nST: ssdp:all\r\n\r\n"
+            )
+            .unwrap(),
+            None
+        );
+
+        assert_eq!(
+            SsdpMessage::parse(b"M-SEARCH * HTTP/1.1\r\nMAN: \"ssdp:discover\"\r\n\r\n"),
+            Err(SsdpError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn max_age() {
+        assert_eq!(super::max_age("max-age=1800"), Some(1800));
+        assert_eq!(
+            super::max_age("no-cache=\"Ext\", max-age = 5000"),
+            Some(5000)
+        );
+        assert_eq!(super::max_age("no-cache"), None);
+    }
+}
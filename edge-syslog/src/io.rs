@@ -0,0 +1,247 @@
+use core::fmt::{self, Debug};
+use core::net::SocketAddr;
+
+use edge_nal::io::Write;
+use edge_nal::{TcpConnect, UdpSend};
+
+use crate as syslog;
+use crate::{Header, Message};
+
+#[cfg(feature = "logger")]
+pub mod logger;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error<E> {
+    Io(E),
+    Format(syslog::Error),
+}
+
+pub type ErrorKind = Error<edge_nal::io::ErrorKind>;
+
+impl<E> Error<E>
+where
+    E: edge_nal::io::Error,
+{
+    pub fn erase(&self) -> Error<edge_nal::io::ErrorKind> {
+        match self {
+            Self::Io(e) => Error::Io(e.kind()),
+            Self::Format(e) => Error::Format(*e),
+        }
+    }
+}
+
+impl<E> From<syslog::Error> for Error<E> {
+    fn from(value: syslog::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "IO error: {err}"),
+            Self::Format(err) => write!(f, "Format error: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for Error<E> where E: std::error::Error {}
+
+/// A trait for sending the composed syslog messages to a collector
+pub trait Sender {
+    type Error: Debug;
+
+    /// Send one composed message
+    async fn send(&mut self, message: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<S> Sender for &mut S
+where
+    S: Sender,
+{
+    type Error = S::Error;
+
+    async fn send(&mut self, message: &[u8]) -> Result<(), Self::Error> {
+        (**self).send(message).await
+    }
+}
+
+/// A sender of syslog messages over UDP (RFC 5426), one message per datagram
+///
+/// The delivery of the messages is not guaranteed. To avoid IP fragmentation, the messages should be
+/// composed with buffers of no more than 480 bytes (IPv4) or 1180 bytes (IPv6), as RFC 5426 recommends.
+pub struct UdpSender<S> {
+    socket: S,
+    remote: SocketAddr,
+}
+
+impl<S> UdpSender<S>
+where
+    S: UdpSend,
+{
+    /// Create a new UDP sender
+    ///
+    /// Parameters:
+    /// - `socket`: A UDP socket, bound to any local port
+    /// - `remote`: The address of the collector, usually on port `syslog::PORT`
+    pub const fn new(socket: S, remote: SocketAddr) -> Self {
+        Self { socket, remote }
+    }
+}
+
+impl<S> Sender for UdpSender<S>
+where
+    S: UdpSend,
+{
+    type Error = S::Error;
+
+    async fn send(&mut self, message: &[u8]) -> Result<(), Self::Error> {
+        self.socket.send(self.remote, message).await
+    }
+}
+
+/// The framing of the syslog messages sent over a stream connection (RFC 6587, section 3.4)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Framing {
+    /// Each message is preceded by its length and a space; understood by all modern collectors
+    OctetCounting,
+    /// Each message is followed by a line feed; the messages must not contain line feeds themselves
+    NonTransparent,
+}
+
+/// A sender of syslog messages over TCP (RFC 6587)
+///
+/// The connection to the collector is established with the first message, and re-established with the
+/// message following a failed one. The messages which fail to send are lost.
+pub struct TcpSender<'a, T>
+where
+    T: TcpConnect + 'a,
+{
+    stack: &'a T,
+    remote: SocketAddr,
+    framing: Framing,
+    socket: Option<T::Socket<'a>>,
+}
+
+impl<'a, T> TcpSender<'a, T>
+where
+    T: TcpConnect,
+{
+    /// Create a new TCP sender
+    ///
+    /// Parameters:
+    /// - `stack`: The TCP stack to connect to the collector with
+    /// - `remote`: The address of the collector, usually on port `syslog::PORT`
+    /// - `framing`: The framing of the messages
+    pub const fn new(stack: &'a T, remote: SocketAddr, framing: Framing) -> Self {
+        Self {
+            stack,
+            remote,
+            framing,
+            socket: None,
+        }
+    }
+
+    async fn send_framed(&mut self, message: &[u8]) -> Result<(), T::Error> {
+        let socket = match &mut self.socket {
+            Some(socket) => socket,
+            socket => socket.insert(self.stack.connect(self.remote).await?),
+        };
+
+        match self.framing {
+            Framing::OctetCounting => {
+                let mut prefix = [0; 6];
+                let len = write_len(&mut prefix, message.len());
+
+                socket.write_all(&prefix[..len]).await?;
+                socket.write_all(message).await?;
+            }
+            Framing::NonTransparent => {
+                socket.write_all(message).await?;
+                socket.write_all(b"\n").await?;
+            }
+        }
+
+        socket.flush().await
+    }
+}
+
+impl<'a, T> Sender for TcpSender<'a, T>
+where
+    T: TcpConnect,
+{
+    type Error = T::Error;
+
+    async fn send(&mut self, message: &[u8]) -> Result<(), Self::Error> {
+        let result = self.send_framed(message).await;
+
+        if result.is_err() {
+            // Reconnect with the next message
+            self.socket = None;
+        }
+
+        result
+    }
+}
+
+/// A syslog client, composing the messages of a program and sending them with a `Sender`
+pub struct Client<'a, S> {
+    sender: S,
+    header: Header<'a>,
+}
+
+impl<'a, S> Client<'a, S>
+where
+    S: Sender,
+{
+    /// Create a new client
+    ///
+    /// Parameters:
+    /// - `sender`: The sender of the messages, i.e. `UdpSender` or `TcpSender`
+    /// - `header`: The header fields which are the same for all messages of the program
+    pub const fn new(sender: S, header: Header<'a>) -> Self {
+        Self { sender, header }
+    }
+
+    /// Compose the provided message in `buf` and send it
+    ///
+    /// The text of the message is truncated to fit into `buf`.
+    pub async fn send(
+        &mut self,
+        message: &Message<'_>,
+        buf: &mut [u8],
+    ) -> Result<(), Error<S::Error>> {
+        let len = message.compose(&self.header, buf)?;
+
+        self.sender.send(&buf[..len]).await.map_err(Error::Io)
+    }
+}
+
+/// Write `len` as the octet counting prefix of a message, returning the length of the prefix
+fn write_len(buf: &mut [u8; 6], len: usize) -> usize {
+    let mut digits = [0; 5];
+    let mut count = 0;
+    let mut len = len.min(99_999);
+
+    loop {
+        digits[count] = b'0' + (len % 10) as u8;
+        count += 1;
+        len /= 10;
+
+        if len == 0 {
+            break;
+        }
+    }
+
+    for (index, digit) in digits[..count].iter().rev().enumerate() {
+        buf[index] = *digit;
+    }
+
+    buf[count] = b' ';
+
+    count + 1
+}
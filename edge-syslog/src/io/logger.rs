@@ -0,0 +1,168 @@
+//! A `log` backend shipping the log records to a syslog collector
+//!
+//! Since `log::Log` is synchronous, `SyslogLogger` only composes the records into an in-memory queue,
+//! and `SyslogLogger::run` sends them asynchronously.
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::pipe::Pipe;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::{Header, Message, Severity, MIN_MAX_MESSAGE_LEN};
+
+use super::Sender;
+
+/// The maximum length of the messages composed from the log records; longer texts are truncated
+pub const MAX_LOG_MESSAGE_LEN: usize = MIN_MAX_MESSAGE_LEN;
+
+/// The default size of the queue of a `SyslogLogger`
+pub const DEFAULT_QUEUE_SIZE: usize = 2048;
+
+const LEN_PREFIX: usize = 2;
+
+const OWN_TARGET: &str = "edge_syslog";
+
+/// A `log` backend, which composes the log records into syslog messages and queues them for sending
+///
+/// The records are dropped if the queue is full, i.e. when they are logged faster than sent; the number of
+/// the dropped records is reported with the next message sent.
+///
+/// The records of this crate itself are not shipped, so that sending a message never logs another one.
+///
+/// Usage:
+/// - Create a `static` logger, and install it with `log::set_logger` and `log::set_max_level`
+/// - Run `SyslogLogger::run` with a `Sender` in a task of its own
+pub struct SyslogLogger<'a, M, const N: usize = DEFAULT_QUEUE_SIZE>
+where
+    M: RawMutex,
+{
+    header: Header<'a>,
+    level: LevelFilter,
+    now: fn() -> Option<Duration>,
+    queue: Pipe<M, N>,
+    dropped: Mutex<M, Cell<usize>>,
+}
+
+impl<'a, M, const N: usize> SyslogLogger<'a, M, N>
+where
+    M: RawMutex,
+{
+    /// Create a new logger
+    ///
+    /// Parameters:
+    /// - `header`: The header fields which are the same for all messages
+    /// - `level`: The most verbose level of the records which are shipped
+    /// - `now`: A function returning the current time since the Unix epoch, or `None` if it is not known (yet),
+    ///   i.e. because the device did not synchronize with an SNTP server yet
+    pub const fn new(
+        header: Header<'a>,
+        level: LevelFilter,
+        now: fn() -> Option<Duration>,
+    ) -> Self {
+        Self {
+            header,
+            level,
+            now,
+            queue: Pipe::new(),
+            dropped: Mutex::new(Cell::new(0)),
+        }
+    }
+
+    /// Send the queued messages with the provided sender, forever
+    ///
+    /// The messages which fail to send are lost.
+    pub async fn run<S>(&self, mut sender: S) -> !
+    where
+        S: Sender,
+    {
+        let mut buf = [0; MAX_LOG_MESSAGE_LEN];
+
+        loop {
+            let mut len = [0; LEN_PREFIX];
+            self.read_exact(&mut len).await;
+
+            let message = &mut buf[..u16::from_be_bytes(len) as usize];
+            self.read_exact(message).await;
+
+            let _ = sender.send(message).await;
+
+            let dropped = self.dropped.lock(|dropped| dropped.replace(0));
+
+            if dropped > 0 {
+                let len = Message {
+                    timestamp: (self.now)(),
+                    ..Message::new(
+                        Severity::Warning,
+                        &format_args!("{dropped} log records dropped, as the queue was full"),
+                    )
+                }
+                .compose(&self.header, &mut buf);
+
+                if let Ok(len) = len {
+                    let _ = sender.send(&buf[..len]).await;
+                }
+            }
+        }
+    }
+
+    async fn read_exact(&self, mut buf: &mut [u8]) {
+        while !buf.is_empty() {
+            let len = self.queue.read(buf).await;
+            buf = &mut buf[len..];
+        }
+    }
+}
+
+impl<M, const N: usize> Log for SyslogLogger<'_, M, N>
+where
+    M: RawMutex + Send + Sync,
+{
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !metadata.target().starts_with(OWN_TARGET)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut buf = [0; LEN_PREFIX + MAX_LOG_MESSAGE_LEN];
+
+        let Ok(len) = Message {
+            timestamp: (self.now)(),
+            ..Message::new(
+                record.level().into(),
+                &format_args!("{}: {}", record.target(), record.args()),
+            )
+        }
+        .compose(&self.header, &mut buf[LEN_PREFIX..]) else {
+            return;
+        };
+
+        buf[..LEN_PREFIX].copy_from_slice(&(len as u16).to_be_bytes());
+
+        let mut message = &buf[..LEN_PREFIX + len];
+
+        // Queue the whole message or nothing, so that the queue does not end up with half a message
+        self.dropped.lock(|dropped| {
+            if self.queue.free_capacity() >= message.len() {
+                // Takes two writes when the message wraps around the end of the queue
+                while let Ok(len) = self.queue.try_write(message) {
+                    message = &message[len..];
+
+                    if message.is_empty() {
+                        break;
+                    }
+                }
+            } else {
+                dropped.set(dropped.get() + 1);
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,473 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(async_fn_in_trait)]
+#![warn(clippy::large_futures)]
+
+use core::fmt::{self, Write};
+use core::time::Duration;
+
+#[cfg(feature = "io")]
+pub mod io;
+
+/// The port of the syslog collectors, for both UDP (RFC 5426) and TCP (RFC 6587)
+pub const PORT: u16 = 514;
+
+/// The version of the syslog protocol, as per RFC 5424
+pub const VERSION: u8 = 1;
+
+/// The length of the messages which all collectors must accept (RFC 5424, section 6.1);
+/// the collectors should accept messages of up to 2048 bytes
+pub const MIN_MAX_MESSAGE_LEN: usize = 480;
+
+const NILVALUE: &str = "-";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The buffer is too small for the header and the structured data of the message
+    BufferOverflow,
+    /// A header field or a structured data name is too long, or contains characters other than printable ASCII
+    InvalidField,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferOverflow => write!(f, "Buffer overflow"),
+            Self::InvalidField => write!(f, "Invalid field"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The severity of a message (RFC 5424, section 6.2.1)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Informational = 6,
+    Debug = 7,
+}
+
+impl From<log::Level> for Severity {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => Self::Error,
+            log::Level::Warn => Self::Warning,
+            log::Level::Info => Self::Informational,
+            log::Level::Debug | log::Level::Trace => Self::Debug,
+        }
+    }
+}
+
+/// The facility of a message, i.e. the kind of the program which emits it (RFC 5424, section 6.2.1)
+///
+/// The programs running on devices usually use `User`, or one of the `Local` facilities.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Facility {
+    Kernel = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Printer = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    AuthPriv = 10,
+    Ftp = 11,
+    Ntp = 12,
+    Audit = 13,
+    Alert = 14,
+    Clock = 15,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// The fields of the header of the messages which are the same for all messages of a program
+///
+/// The empty fields are sent as the `-` NILVALUE. All fields may only contain printable ASCII characters.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Header<'a> {
+    pub facility: Facility,
+    /// The name of the device, preferably its FQDN or its IP address (at most 255 characters)
+    pub hostname: &'a str,
+    /// The name of the program (at most 48 characters)
+    pub app_name: &'a str,
+    /// The ID of the process of the program (at most 128 characters)
+    pub procid: &'a str,
+}
+
+impl<'a> Header<'a> {
+    /// Create a header with the `User` facility, and the provided host name and program name
+    pub const fn new(hostname: &'a str, app_name: &'a str) -> Self {
+        Self {
+            facility: Facility::User,
+            hostname,
+            app_name,
+            procid: "",
+        }
+    }
+}
+
+/// A structured data element of a message (RFC 5424, section 6.3), i.e. `[exampleSDID@32473 iut="3"]`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct StructuredData<'a> {
+    /// The ID of the element; the IDs which are not registered with IANA must have the form `name@<private enterprise number>`
+    pub id: &'a str,
+    /// The names and the values of the parameters; the values are escaped as necessary
+    pub params: &'a [(&'a str, &'a str)],
+}
+
+/// A syslog message (RFC 5424, section 6)
+#[derive(Copy, Clone)]
+pub struct Message<'a> {
+    pub severity: Severity,
+    /// The time of the message, since the Unix epoch; devices which do not know the time yet
+    /// should leave it out, rather than send a wrong one
+    pub timestamp: Option<Duration>,
+    /// The type of the message, i.e. `"TCPIN"` (at most 32 characters), or empty
+    pub msgid: &'a str,
+    pub structured_data: &'a [StructuredData<'a>],
+    /// The free-form text of the message
+    pub msg: &'a dyn fmt::Display,
+}
+
+impl<'a> Message<'a> {
+    /// Create a message without timestamp, type and structured data
+    pub const fn new(severity: Severity, msg: &'a dyn fmt::Display) -> Self {
+        Self {
+            severity,
+            timestamp: None,
+            msgid: "",
+            structured_data: &[],
+            msg,
+        }
+    }
+
+    /// Compose the message with the provided header into `buf`, returning its length.
+    ///
+    /// If the text of the message does not fit into `buf`, it is truncated (as RFC 5424, section 6.1 allows);
+    /// the header and the structured data must fit though.
+    pub fn compose(&self, header: &Header<'_>, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut w = BufWriter { buf, len: 0 };
+
+        let pri = (header.facility as u8) * 8 + self.severity as u8;
+
+        write!(w, "<{pri}>{VERSION} ").map_err(|_| Error::BufferOverflow)?;
+
+        match self.timestamp {
+            Some(timestamp) => write_timestamp(&mut w, timestamp),
+            None => w.write_str(NILVALUE),
+        }
+        .map_err(|_| Error::BufferOverflow)?;
+
+        for (field, max_len) in [
+            (header.hostname, 255),
+            (header.app_name, 48),
+            (header.procid, 128),
+            (self.msgid, 32),
+        ] {
+            w.write_char(' ').map_err(|_| Error::BufferOverflow)?;
+            write_field(&mut w, field, max_len)?;
+        }
+
+        w.write_char(' ').map_err(|_| Error::BufferOverflow)?;
+
+        if self.structured_data.is_empty() {
+            w.write_str(NILVALUE).map_err(|_| Error::BufferOverflow)?;
+        }
+
+        for element in self.structured_data {
+            w.write_char('[').map_err(|_| Error::BufferOverflow)?;
+            write_sd_name(&mut w, element.id)?;
+
+            for (name, value) in element.params {
+                w.write_char(' ').map_err(|_| Error::BufferOverflow)?;
+                write_sd_name(&mut w, name)?;
+                w.write_str("=\"").map_err(|_| Error::BufferOverflow)?;
+
+                for c in value.chars() {
+                    if matches!(c, '"' | '\\' | ']') {
+                        w.write_char('\\').map_err(|_| Error::BufferOverflow)?;
+                    }
+
+                    w.write_char(c).map_err(|_| Error::BufferOverflow)?;
+                }
+
+                w.write_char('"').map_err(|_| Error::BufferOverflow)?;
+            }
+
+            w.write_char(']').map_err(|_| Error::BufferOverflow)?;
+        }
+
+        let header_len = w.len;
+
+        let mut msg = Truncating {
+            w: &mut w,
+            truncated: false,
+        };
+
+        let _ = write!(msg, " {}", self.msg);
+
+        // No text at all, rather than a lone separator
+        if w.len == header_len + 1 {
+            w.len = header_len;
+        }
+
+        Ok(w.len)
+    }
+}
+
+/// Write the time elapsed since the Unix epoch as an RFC 3339 timestamp in UTC, with a microsecond precision
+fn write_timestamp<W: Write>(w: &mut W, since_epoch: Duration) -> fmt::Result {
+    let secs = since_epoch.as_secs();
+    let days = secs / 86_400;
+    let secs_of_day = secs % 86_400;
+
+    // Civil date from the days since the Unix epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+
+    write!(
+        w,
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+fn write_field<W: Write>(w: &mut W, field: &str, max_len: usize) -> Result<(), Error> {
+    if field.is_empty() {
+        return w.write_str(NILVALUE).map_err(|_| Error::BufferOverflow);
+    }
+
+    if field.len() > max_len || !field.bytes().all(|b| b.is_ascii_graphic()) {
+        Err(Error::InvalidField)?;
+    }
+
+    w.write_str(field).map_err(|_| Error::BufferOverflow)
+}
+
+fn write_sd_name<W: Write>(w: &mut W, name: &str) -> Result<(), Error> {
+    if name.is_empty()
+        || name.len() > 32
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !matches!(b, b'=' | b']' | b'"'))
+    {
+        Err(Error::InvalidField)?;
+    }
+
+    w.write_str(name).map_err(|_| Error::BufferOverflow)
+}
+
+struct BufWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for BufWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+
+        if end > self.buf.len() {
+            return Err(fmt::Error);
+        }
+
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+
+        Ok(())
+    }
+}
+
+/// A writer which writes as much as fits into the buffer, and silently drops the rest
+struct Truncating<'a, 'b> {
+    w: &'a mut BufWriter<'b>,
+    truncated: bool,
+}
+
+impl Write for Truncating<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+
+        let mut len = s.len().min(self.w.buf.len() - self.w.len);
+
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        self.truncated = len < s.len();
+
+        self.w.write_str(&s[..len])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::fmt;
+    use core::str;
+    use core::time::Duration;
+
+    use crate::{Error, Facility, Header, Message, Severity, StructuredData};
+
+    fn compose<'b>(
+        header: &Header<'_>,
+        message: &Message<'_>,
+        buf: &'b mut [u8],
+    ) -> Result<&'b str, Error> {
+        let len = message.compose(header, buf)?;
+
+        Ok(str::from_utf8(&buf[..len]).unwrap())
+    }
+
+    /// A text written in several pieces
+    struct Pieces;
+
+    impl fmt::Display for Pieces {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Grüße")?;
+            write!(f, " ")?;
+            write!(f, "42")
+        }
+    }
+
+    #[test]
+    fn rfc5424_example() {
+        let header = Header {
+            facility: Facility::Auth,
+            procid: "",
+            ..Header::new("mymachine.example.com", "su")
+        };
+
+        let message = Message {
+            timestamp: Some(Duration::from_millis(1_065_910_455_003)),
+            msgid: "ID47",
+            ..Message::new(
+                Severity::Critical,
+                &"'su root' failed for lonvick on /dev/pts/8",
+            )
+        };
+
+        assert_eq!(
+            compose(&header, &message, &mut [0; 256]),
+            Ok("<34>1 2003-10-11T22:14:15.003000Z mymachine.example.com su - ID47 - 'su root' failed for lonvick on /dev/pts/8")
+        );
+    }
+
+    #[test]
+    fn structured_data() {
+        let header = Header::new("", "evntslog");
+
+        let message = Message {
+            structured_data: &[
+                StructuredData {
+                    id: "exampleSDID@32473",
+                    params: &[("iut", "3"), ("eventSource", "Application \"x\" [y]")],
+                },
+                StructuredData {
+                    id: "examplePriority@32473",
+                    params: &[("class", "high")],
+                },
+            ],
+            ..Message::new(Severity::Notice, &"")
+        };
+
+        assert_eq!(
+            compose(&header, &message, &mut [0; 256]),
+            Ok(
+                r#"<13>1 - - evntslog - - [exampleSDID@32473 iut="3" eventSource="Application \"x\" [y\]"][examplePriority@32473 class="high"]"#
+            )
+        );
+
+        let message = Message {
+            structured_data: &[StructuredData {
+                id: "bad id",
+                params: &[],
+            }],
+            ..Message::new(Severity::Notice, &"")
+        };
+
+        assert_eq!(
+            compose(&header, &message, &mut [0; 256]),
+            Err(Error::InvalidField)
+        );
+    }
+
+    #[test]
+    fn truncation() {
+        let header = Header::new("host", "app");
+
+        let message = Message::new(Severity::Debug, &Pieces);
+
+        assert_eq!(
+            compose(&header, &message, &mut [0; 256]),
+            Ok("<15>1 - host app - - - Grüße 42")
+        );
+        // The `ü` does not fit and is dropped entirely
+        assert_eq!(
+            compose(&header, &message, &mut [0; 26]),
+            Ok("<15>1 - host app - - - Gr")
+        );
+        assert_eq!(
+            compose(&header, &message, &mut [0; 20]),
+            Err(Error::BufferOverflow)
+        );
+        assert_eq!(
+            compose(&Header::new("host name", "app"), &message, &mut [0; 256]),
+            Err(Error::InvalidField)
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        let header = Header::new("h", "a");
+
+        for (since_epoch, expected) in [
+            (Duration::ZERO, "1970-01-01T00:00:00.000000Z"),
+            (
+                Duration::new(951_825_600, 1_000),
+                "2000-02-29T12:00:00.000001Z",
+            ),
+            (
+                Duration::from_secs(4_107_542_399),
+                "2100-02-28T23:59:59.000000Z",
+            ),
+        ] {
+            let message = Message {
+                timestamp: Some(since_epoch),
+                ..Message::new(Severity::Alert, &"")
+            };
+
+            let mut buf = [0; 64];
+            let composed = compose(&header, &message, &mut buf).unwrap();
+
+            assert_eq!(&composed[..5], "<9>1 ");
+            assert_eq!(&composed[5..32], expected);
+            assert_eq!(&composed[32..], " h a - - -");
+        }
+    }
+}
@@ -0,0 +1,25 @@
+#![no_main]
+
+use edge_ws::FrameHeader;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((header, payload_offset)) = FrameHeader::deserialize(data) {
+        assert!(payload_offset <= data.len());
+        assert!(payload_offset <= FrameHeader::MAX_LEN);
+
+        // Whatever was accepted must serialize back to an equivalent header
+        let mut buf = [0; FrameHeader::MAX_LEN];
+        let len = header.serialize(&mut buf).unwrap();
+
+        assert_eq!(len, header.serialized_len());
+
+        let (reparsed, reparsed_offset) = FrameHeader::deserialize(&buf[..len]).unwrap();
+
+        assert_eq!(reparsed_offset, len);
+        assert_eq!(reparsed.frame_type, header.frame_type);
+        assert_eq!(reparsed.payload_len, header.payload_len);
+        assert_eq!(reparsed.mask_key, header.mask_key);
+    }
+});
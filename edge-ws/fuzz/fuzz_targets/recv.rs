@@ -0,0 +1,17 @@
+#![no_main]
+
+use edge_ws::io::recv;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    embassy_futures::block_on(async {
+        let mut read = data;
+        let mut buf = [0; 256];
+
+        // Receive frames until the input is exhausted or rejected
+        while let Ok((_, len)) = recv(&mut read, &mut buf).await {
+            assert!(len <= buf.len());
+        }
+    });
+});
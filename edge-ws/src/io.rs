@@ -1,6 +1,10 @@
 use core::cmp::min;
 
-use embedded_io_async::{self, Read, ReadExactError, Write};
+use edge_nal::WriteVectored;
+
+use embassy_time::{with_deadline, Duration, Instant};
+
+use embedded_io_async::{self, ErrorType, Read, ReadExactError, Write};
 
 use super::*;
 
@@ -19,11 +23,26 @@ where
             Self::Invalid => Error::Invalid,
             Self::BufferOverflow => Error::BufferOverflow,
             Self::InvalidLen => Error::InvalidLen,
+            Self::Timeout => Error::Timeout,
             Self::Io(e) => Error::Io(e.kind()),
         }
     }
 }
 
+impl<E> embedded_io_async::Error for Error<E>
+where
+    E: embedded_io_async::Error,
+{
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        match self {
+            Self::Io(e) => e.kind(),
+            Self::Timeout => embedded_io_async::ErrorKind::TimedOut,
+            Self::BufferOverflow => embedded_io_async::ErrorKind::OutOfMemory,
+            _ => embedded_io_async::ErrorKind::InvalidData,
+        }
+    }
+}
+
 impl<E> From<ReadExactError<E>> for Error<E> {
     fn from(e: ReadExactError<E>) -> Self {
         match e {
@@ -63,7 +82,7 @@ impl FrameHeader {
         W: Write,
     {
         let mut header_buf = [0; FrameHeader::MAX_LEN];
-        let header_len = self.serialize(&mut header_buf).unwrap();
+        let header_len = self.serialize(&mut header_buf).map_err(Error::recast)?;
 
         write
             .write_all(&header_buf[..header_len])
@@ -100,7 +119,7 @@ impl FrameHeader {
         payload: &'a [u8],
     ) -> Result<(), Error<W::Error>>
     where
-        W: Write,
+        W: WriteVectored,
     {
         let payload_buf_len = payload.len() as u64;
 
@@ -111,26 +130,74 @@ impl FrameHeader {
         } else if self.mask_key.is_none() {
             write.write_all(payload).await.map_err(Error::Io)
         } else {
-            let mut buf = [0_u8; 32];
+            self.send_masked_payload(write, payload, &[]).await
+        }
+    }
 
-            let mut offset = 0;
+    /// Send the header, immediately followed by the payload
+    ///
+    /// Unlike calling `send` and then `send_payload`, the header and (the beginning of) the payload
+    /// are sent with a single vectored write, so that short frames do not end up split in multiple TCP segments.
+    pub async fn send_with_payload<'a, W>(
+        &'a self,
+        mut write: W,
+        payload: &'a [u8],
+    ) -> Result<(), Error<W::Error>>
+    where
+        W: WriteVectored,
+    {
+        if payload.len() as u64 != self.payload_len {
+            Err(Error::InvalidLen)?;
+        }
 
-            while offset < payload.len() {
-                let len = min(buf.len(), payload.len() - offset);
+        let mut header_buf = [0; FrameHeader::MAX_LEN];
+        let header_len = self.serialize(&mut header_buf).map_err(Error::recast)?;
 
-                let buf = &mut buf[..len];
+        let header = &header_buf[..header_len];
 
-                buf.copy_from_slice(&payload[offset..offset + len]);
+        if self.mask_key.is_none() {
+            write
+                .write_all_vectored(&[header, payload])
+                .await
+                .map_err(Error::Io)
+        } else {
+            self.send_masked_payload(write, payload, header).await
+        }
+    }
 
-                self.mask(buf, offset);
+    /// Mask and send the payload in chunks, where the first chunk is preceded by `prefix`
+    async fn send_masked_payload<W>(
+        &self,
+        mut write: W,
+        payload: &[u8],
+        mut prefix: &[u8],
+    ) -> Result<(), Error<W::Error>>
+    where
+        W: WriteVectored,
+    {
+        let mut buf = [0_u8; 32];
 
-                write.write_all(buf).await.map_err(Error::Io)?;
+        let mut offset = 0;
 
-                offset += len;
-            }
+        while offset < payload.len() || !prefix.is_empty() {
+            let len = min(buf.len(), payload.len() - offset);
 
-            Ok(())
+            let buf = &mut buf[..len];
+
+            buf.copy_from_slice(&payload[offset..offset + len]);
+
+            self.mask(buf, offset);
+
+            write
+                .write_all_vectored(&[prefix, buf])
+                .await
+                .map_err(Error::Io)?;
+
+            prefix = &[];
+            offset += len;
         }
+
+        Ok(())
     }
 }
 
@@ -148,13 +215,13 @@ where
 }
 
 pub async fn send<W>(
-    mut write: W,
+    write: W,
     frame_type: FrameType,
     mask_key: Option<u32>,
     frame_data_buf: &[u8],
 ) -> Result<(), Error<W::Error>>
 where
-    W: Write,
+    W: WriteVectored,
 {
     let header = FrameHeader {
         frame_type,
@@ -162,15 +229,245 @@ where
         mask_key,
     };
 
-    header.send(&mut write).await?;
-    header.send_payload(write, frame_data_buf).await
+    header.send_with_payload(write, frame_data_buf).await
+}
+
+/// A reader for sockets which were upgraded to WebSocket by an external HTTP stack
+///
+/// When the HTTP stack reads the upgrade request (or response) it might also read past it,
+/// consuming the beginning of the first WebSocket frame. This wrapper first returns these
+/// already-consumed "residue" bytes, and only then continues reading from the socket.
+///
+/// Use it in place of the socket when calling `recv` or `FrameHeader::recv`.
+/// Writes are passed through to the socket as-is.
+pub struct ResidueRead<'a, R> {
+    residue: &'a [u8],
+    read: R,
+}
+
+impl<'a, R> ResidueRead<'a, R> {
+    /// Create a new reader
+    ///
+    /// Parameters:
+    /// - `residue`: The bytes read by the HTTP stack past the end of the upgrade request (or response)
+    /// - `read`: The upgraded socket
+    pub const fn new(residue: &'a [u8], read: R) -> Self {
+        Self { residue, read }
+    }
+
+    /// Return the residue bytes which are not consumed yet
+    pub fn residue(&self) -> &[u8] {
+        self.residue
+    }
+
+    /// Return the wrapped socket, dropping the residue which is not consumed yet
+    pub fn release(self) -> R {
+        self.read
+    }
+}
+
+impl<R> ErrorType for ResidueRead<'_, R>
+where
+    R: ErrorType,
+{
+    type Error = R::Error;
+}
+
+impl<R> Read for ResidueRead<'_, R>
+where
+    R: Read,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.residue.is_empty() {
+            self.read.read(buf).await
+        } else {
+            let len = min(buf.len(), self.residue.len());
+
+            buf[..len].copy_from_slice(&self.residue[..len]);
+            self.residue = &self.residue[len..];
+
+            Ok(len)
+        }
+    }
+}
+
+impl<R> Write for ResidueRead<'_, R>
+where
+    R: Write,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.read.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.read.flush().await
+    }
+}
+
+impl<R> WriteVectored for ResidueRead<'_, R>
+where
+    R: WriteVectored,
+{
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Self::Error> {
+        self.read.write_all_vectored(bufs).await
+    }
+}
+
+/// The close code sent when a `TimedConnection` expires ("going away")
+pub const CLOSE_GOING_AWAY: u16 = 1001;
+
+/// Lifetime and idle timeouts for a `TimedConnection`
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Timeouts {
+    /// An optional absolute lifetime of the connection in milliseconds
+    pub lifetime_ms: Option<u32>,
+    /// An optional timeout in milliseconds after which a connection which had no data
+    /// sent or received is considered idle
+    pub idle_timeout_ms: Option<u32>,
+}
+
+impl Timeouts {
+    /// Create a new policy with no timeouts
+    pub const fn new() -> Self {
+        Self {
+            lifetime_ms: None,
+            idle_timeout_ms: None,
+        }
+    }
+}
+
+/// A WebSocket connection which is closed once it outlives its lifetime, or once it stays idle for too long
+///
+/// When either timeout expires while waiting for a frame, a Close frame with code 1001 ("going away")
+/// is sent to the peer and `Error::Timeout` is returned, so that the socket can be released.
+/// This prevents forgotten browser tabs from holding on to the few sockets of a device indefinitely.
+pub struct TimedConnection<T, M> {
+    io: T,
+    mask_gen: M,
+    timeouts: Timeouts,
+    started: Instant,
+    last_activity: Instant,
+}
+
+impl<T, M> TimedConnection<T, M>
+where
+    T: Read + WriteVectored,
+    M: Fn() -> Option<u32>,
+{
+    /// Create a new connection
+    ///
+    /// Parameters:
+    /// - `io`: The upgraded socket
+    /// - `mask_gen`: A closure generating the mask keys of the sent frames (`None` on the server side)
+    /// - `timeouts`: The lifetime and idle timeouts of the connection
+    pub fn new(io: T, mask_gen: M, timeouts: Timeouts) -> Self {
+        let now = Instant::now();
+
+        Self {
+            io,
+            mask_gen,
+            timeouts,
+            started: now,
+            last_activity: now,
+        }
+    }
+
+    /// Receive a frame into the provided buffer
+    ///
+    /// Returns `Error::Timeout` (after sending a 1001 Close frame) if the connection expired
+    /// before a frame was received.
+    pub async fn recv(
+        &mut self,
+        frame_data_buf: &mut [u8],
+    ) -> Result<(FrameType, usize), Error<T::Error>> {
+        let result = if let Some(deadline) = self.deadline() {
+            with_deadline(deadline, recv(&mut self.io, frame_data_buf))
+                .await
+                .map_err(|_| Error::Timeout)
+                .and_then(|result| result)
+        } else {
+            recv(&mut self.io, frame_data_buf).await
+        };
+
+        match result {
+            Err(Error::Timeout) => {
+                self.close(CLOSE_GOING_AWAY).await?;
+
+                Err(Error::Timeout)
+            }
+            result => {
+                self.last_activity = Instant::now();
+
+                result
+            }
+        }
+    }
+
+    /// Send a frame with the provided payload
+    ///
+    /// Returns `Error::Timeout` (after sending a 1001 Close frame) if the connection had already expired.
+    pub async fn send(
+        &mut self,
+        frame_type: FrameType,
+        frame_data_buf: &[u8],
+    ) -> Result<(), Error<T::Error>> {
+        if self
+            .deadline()
+            .map(|deadline| deadline <= Instant::now())
+            .unwrap_or(false)
+        {
+            self.close(CLOSE_GOING_AWAY).await?;
+
+            return Err(Error::Timeout);
+        }
+
+        send(&mut self.io, frame_type, (self.mask_gen)(), frame_data_buf).await?;
+
+        self.last_activity = Instant::now();
+
+        Ok(())
+    }
+
+    /// Send a Close frame with the provided close code
+    pub async fn close(&mut self, code: u16) -> Result<(), Error<T::Error>> {
+        send(
+            &mut self.io,
+            FrameType::Close,
+            (self.mask_gen)(),
+            &code.to_be_bytes(),
+        )
+        .await
+    }
+
+    /// Return the wrapped socket
+    pub fn release(self) -> T {
+        self.io
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        let lifetime = self
+            .timeouts
+            .lifetime_ms
+            .map(|ms| self.started + Duration::from_millis(ms as _));
+        let idle = self
+            .timeouts
+            .idle_timeout_ms
+            .map(|ms| self.last_activity + Duration::from_millis(ms as _));
+
+        match (lifetime, idle) {
+            (Some(lifetime), Some(idle)) => Some(lifetime.min(idle)),
+            (lifetime, idle) => lifetime.or(idle),
+        }
+    }
 }
 
 #[cfg(feature = "embedded-svc")]
 mod embedded_svc_compat {
     use core::convert::TryInto;
 
-    use embedded_io_async::{Read, Write};
+    use edge_nal::WriteVectored;
+
+    use embedded_io_async::Read;
     use embedded_svc::io::ErrorType as IoErrorType;
     use embedded_svc::ws::asynch::Sender;
     use embedded_svc::ws::ErrorType;
@@ -209,7 +506,7 @@ mod embedded_svc_compat {
 
     impl<T, M> Sender for WsConnection<T, M>
     where
-        T: Write,
+        T: WriteVectored,
         M: Fn() -> Option<u32>,
     {
         async fn send(
@@ -227,3 +524,150 @@ mod embedded_svc_compat {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// An in-memory socket which reads the provided input and records the output;
+    /// once the input is exhausted, reading blocks forever
+    struct TestIo<'a> {
+        input: &'a [u8],
+        output: [u8; 64],
+        output_len: usize,
+    }
+
+    impl<'a> TestIo<'a> {
+        const fn new(input: &'a [u8]) -> Self {
+            Self {
+                input,
+                output: [0; 64],
+                output_len: 0,
+            }
+        }
+
+        fn output(&self) -> &[u8] {
+            &self.output[..self.output_len]
+        }
+    }
+
+    impl ErrorType for TestIo<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for TestIo<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.input.is_empty() {
+                core::future::pending().await
+            }
+
+            let len = min(buf.len(), self.input.len());
+
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input = &self.input[len..];
+
+            Ok(len)
+        }
+    }
+
+    impl Write for TestIo<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output[self.output_len..self.output_len + buf.len()].copy_from_slice(buf);
+            self.output_len += buf.len();
+
+            Ok(buf.len())
+        }
+    }
+
+    impl WriteVectored for TestIo<'_> {}
+
+    /// An unmasked Close frame with code 1001
+    const CLOSE_FRAME: &[u8] = &[0x88, 0x02, 0x03, 0xe9];
+
+    #[test]
+    fn test_residue() {
+        // The HTTP stack consumed a whole "Hello" text frame, and the first byte of the following binary frame
+        let residue = b"\x81\x05Hello\x82";
+        let mut socket = TestIo::new(b"\x02\x01\x02");
+
+        let mut read = ResidueRead::new(residue, &mut socket);
+        let mut buf = [0; 16];
+
+        assert_eq!(
+            block_on(recv(&mut read, &mut buf)),
+            Ok((FrameType::Text(false), 5))
+        );
+        assert_eq!(&buf[..5], b"Hello");
+        assert_eq!(read.residue(), b"\x82");
+
+        // The header of the second frame spans the residue and the socket data
+        assert_eq!(
+            block_on(recv(&mut read, &mut buf)),
+            Ok((FrameType::Binary(false), 2))
+        );
+        assert_eq!(&buf[..2], b"\x01\x02");
+        assert!(read.residue().is_empty());
+
+        // Writes go to the socket as-is
+        block_on(send(&mut read, FrameType::Pong, None, &[])).unwrap();
+        assert_eq!(socket.output(), b"\x8a\x00");
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let timeouts = Timeouts {
+            idle_timeout_ms: Some(100),
+            ..Timeouts::new()
+        };
+
+        let mut connection = TimedConnection::new(TestIo::new(b"\x81\x01a"), || None, timeouts);
+        let mut buf = [0; 16];
+
+        assert_eq!(
+            block_on(connection.recv(&mut buf)),
+            Ok((FrameType::Text(false), 1))
+        );
+
+        // No more frames arrive, so the connection goes idle
+        let started = Instant::now();
+
+        assert_eq!(block_on(connection.recv(&mut buf)), Err(Error::Timeout));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        assert_eq!(connection.release().output(), CLOSE_FRAME);
+    }
+
+    #[test]
+    fn test_lifetime() {
+        let timeouts = Timeouts {
+            lifetime_ms: Some(100),
+            ..Timeouts::new()
+        };
+
+        let mut connection = TimedConnection::new(TestIo::new(&[]), || None, timeouts);
+        let mut buf = [0; 16];
+
+        assert_eq!(block_on(connection.recv(&mut buf)), Err(Error::Timeout));
+        assert_eq!(connection.release().output(), CLOSE_FRAME);
+
+        // Sending on an expired connection closes it, instead of sending the frame
+        let mut connection = TimedConnection::new(TestIo::new(&[]), || None, timeouts);
+
+        block_on(connection.send(FrameType::Text(false), b"a")).unwrap();
+
+        embassy_time::block_for(Duration::from_millis(100));
+
+        assert_eq!(
+            block_on(connection.send(FrameType::Text(false), b"b")),
+            Err(Error::Timeout)
+        );
+
+        let output = connection.release();
+        assert_eq!(&output.output()[..3], b"\x81\x01a");
+        assert_eq!(&output.output()[3..], CLOSE_FRAME);
+    }
+}
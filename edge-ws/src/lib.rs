@@ -69,6 +69,7 @@ pub enum Error<E> {
     Invalid,
     BufferOverflow,
     InvalidLen,
+    Timeout,
     Io(E),
 }
 
@@ -79,6 +80,7 @@ impl Error<()> {
             Self::Invalid => Error::Invalid,
             Self::BufferOverflow => Error::BufferOverflow,
             Self::InvalidLen => Error::InvalidLen,
+            Self::Timeout => Error::Timeout,
             Self::Io(_) => panic!(),
         }
     }
@@ -94,6 +96,7 @@ where
             Self::Invalid => write!(f, "Invalid"),
             Self::BufferOverflow => write!(f, "Buffer overflow"),
             Self::InvalidLen => write!(f, "Invalid length"),
+            Self::Timeout => write!(f, "Timeout"),
             Self::Io(err) => write!(f, "IO error: {}", err),
         }
     }
@@ -157,6 +160,12 @@ impl FrameHeader {
                     payload_len = u64::from_be_bytes([
                         buf[2], buf[3], buf[4], buf[5], buf[6], buf[7], buf[8], buf[9],
                     ]);
+
+                    // The most significant bit of a 64-bit payload length must be 0
+                    if payload_len > i64::MAX as u64 {
+                        return Err(Error::Invalid);
+                    }
+
                     payload_offset += 8;
                 }
             }
@@ -191,14 +200,16 @@ impl FrameHeader {
                 _ => unreachable!(),
             };
 
+            // Control frames cannot be fragmented and carry at most 125 payload bytes.
+            // Their payload must still be accounted for, or the next frame header
+            // would be read from the middle of it
+            if opcode >= 8 && (!final_frame || payload_len > 125) {
+                return Err(Error::Invalid);
+            }
+
             let frame_header = FrameHeader {
                 frame_type,
-                payload_len: matches!(
-                    frame_type,
-                    FrameType::Binary(_) | FrameType::Text(_) | FrameType::Continue(_)
-                )
-                .then(|| payload_len)
-                .unwrap_or(0),
+                payload_len,
                 mask_key,
             };
 
@@ -219,7 +230,7 @@ impl FrameHeader {
     }
 
     pub fn serialize(&self, buf: &mut [u8]) -> Result<usize, Error<()>> {
-        if buf.len() < self.serialized_len() {
+        if buf.len() < self.serialized_len() || self.payload_len > i64::MAX as u64 {
             return Err(Error::InvalidLen);
         }
 
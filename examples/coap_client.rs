@@ -0,0 +1,79 @@
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use edge_coap::io::{Client, Config, Error, Request};
+use edge_coap::PORT;
+
+use edge_nal::UdpBind;
+
+use log::*;
+
+// Change this to the IP address of the CoAP server, i.e. the one of the `coap_server` example
+const SERVER_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let stack = edge_nal_std::Stack::new();
+
+    let mut tx_buf = [0; 1152];
+    let mut rx_buf = [0; 1152];
+
+    futures_lite::future::block_on(run(&stack, &mut tx_buf, &mut rx_buf)).unwrap();
+}
+
+async fn run<T>(stack: &T, tx_buf: &mut [u8], rx_buf: &mut [u8]) -> Result<(), Error<T::Error>>
+where
+    T: UdpBind,
+{
+    let server = SocketAddr::new(IpAddr::V4(SERVER_IP), PORT);
+
+    let socket = stack
+        .bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))
+        .await
+        .map_err(Error::Io)?;
+
+    let mut client = Client::new(socket, rand::thread_rng(), Config::new());
+
+    let code = client
+        .request(server, &Request::get("hello"), tx_buf, rx_buf, |response| {
+            info!("Got: {}", String::from_utf8_lossy(response.payload));
+        })
+        .await?;
+
+    info!("GET /hello: {code}");
+
+    let mut size = 0;
+
+    let code = client
+        .request(server, &Request::get("large"), tx_buf, rx_buf, |response| {
+            size += response.payload.len();
+        })
+        .await?;
+
+    info!("GET /large: {code}, {size} bytes in blocks");
+
+    let mut notifications = 0;
+
+    let code = client
+        .observe(
+            server,
+            &Request::get("counter"),
+            tx_buf,
+            rx_buf,
+            |notification| {
+                info!("Counter: {}", String::from_utf8_lossy(notification.payload));
+
+                notifications += 1;
+
+                // Stop observing after 3 notifications
+                notifications <= 3
+            },
+        )
+        .await?;
+
+    info!("Observed /counter: {code}");
+
+    Ok(())
+}
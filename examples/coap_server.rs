@@ -0,0 +1,103 @@
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use edge_coap::io::{Error, Handler, Server};
+use edge_coap::{self as coap, Code, ContentFormat, Message, MessageWriter, PORT};
+
+use edge_nal::UdpBind;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+
+use log::*;
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let stack = edge_nal_std::Stack::new();
+
+    let mut tx_buf = [0; 1152];
+    let mut rx_buf = [0; 1152];
+
+    futures_lite::future::block_on(run(&stack, &mut tx_buf, &mut rx_buf)).unwrap();
+}
+
+async fn run<T>(stack: &T, tx_buf: &mut [u8], rx_buf: &mut [u8]) -> Result<(), Error<T::Error>>
+where
+    T: UdpBind,
+{
+    info!("Running a CoAP server on port {PORT}. Try `coap get coap://localhost/hello`, or the `coap_client` example");
+
+    let socket = stack
+        .bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), PORT))
+        .await
+        .map_err(Error::Io)?;
+
+    let handler = ExampleHandler {
+        counter: AtomicU32::new(0),
+    };
+
+    // Signalled whenever the counter changes, so that the server notifies its observers
+    let notify = Signal::<NoopRawMutex, _>::new();
+
+    let mut server = Server::<4>::new(&mut rand::thread_rng());
+
+    let tick = async {
+        loop {
+            Timer::after(Duration::from_secs(5)).await;
+
+            handler.counter.fetch_add(1, Ordering::SeqCst);
+            notify.signal(());
+        }
+    };
+
+    match select(server.run(socket, tx_buf, rx_buf, &handler, &notify), tick).await {
+        Either::First(result) => result,
+        Either::Second(()) => unreachable!(),
+    }
+}
+
+struct ExampleHandler {
+    counter: AtomicU32,
+}
+
+impl Handler for ExampleHandler {
+    type Error = coap::Error;
+
+    async fn handle(
+        &self,
+        remote: SocketAddr,
+        request: &Message<'_>,
+        response: &mut MessageWriter<'_>,
+    ) -> Result<(), Self::Error> {
+        info!("{} request from {remote}", request.code);
+
+        if request.code != Code::GET {
+            response.set_code(Code::METHOD_NOT_ALLOWED);
+        } else if request.path_is("hello") {
+            response.set_code(Code::CONTENT);
+            response.content_format(ContentFormat::TEXT_PLAIN)?;
+            response.payload(b"Hello from edge-coap!")?;
+        } else if request.path_is("counter") {
+            // Observable: the observers get a notification every time the counter changes
+            let text = self.counter.load(Ordering::SeqCst).to_string();
+
+            response.set_code(Code::CONTENT);
+            response.content_format(ContentFormat::TEXT_PLAIN)?;
+            response.payload(text.as_bytes())?;
+        } else if request.path_is("large") {
+            // Too large for one message: returned block by block
+            let data = [b'x'; 3000];
+
+            response.set_code(Code::CONTENT);
+            response.content_format(ContentFormat::TEXT_PLAIN)?;
+            response.block2_payload(request.block2(), 1024, &data)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,11 +1,8 @@
 //! NOTE: Run this example with `sudo` to be able to bind to the interface, as it uses raw sockets which require root privileges.
 
-use core::net::{Ipv4Addr, SocketAddrV4};
-
 use edge_dhcp::client::Client;
-use edge_dhcp::io::{client::Lease, DEFAULT_CLIENT_PORT, DEFAULT_SERVER_PORT};
-use edge_nal::{MacAddr, RawBind};
-use edge_raw::io::RawSocket2Udp;
+use edge_dhcp::io::client::{Lease, LeaseEvent};
+use edge_nal::MacAddr;
 
 use log::info;
 
@@ -27,26 +24,13 @@ async fn run(if_index: u32, if_mac: MacAddr) -> Result<(), anyhow::Error> {
     let stack = edge_nal_std::Interface::new(if_index);
     let mut buf = [0; 1500];
 
-    loop {
-        let mut socket: RawSocket2Udp<_> = RawSocket2Udp::new(
-            stack.bind().await?,
-            Some(SocketAddrV4::new(
-                Ipv4Addr::UNSPECIFIED,
-                DEFAULT_CLIENT_PORT,
-            )),
-            Some(SocketAddrV4::new(
-                Ipv4Addr::UNSPECIFIED,
-                DEFAULT_SERVER_PORT,
-            )),
-            [255; 6], // Broadcast
-        );
-
-        let (mut lease, options) = Lease::new(&mut client, &mut socket, &mut buf).await?;
-
-        info!("Got lease {lease:?} with options {options:?}");
-
-        info!("Entering an endless loop to keep the lease...");
+    Lease::run_raw(&mut client, &stack, &mut buf, |event| match event {
+        LeaseEvent::Bound(lease, options) => {
+            info!("Got lease {lease:?} with options {options:?}")
+        }
+        event => info!("Lease event: {event:?}"),
+    })
+    .await?;
 
-        lease.keep(&mut client, &mut socket, &mut buf).await?;
-    }
+    Ok(())
 }
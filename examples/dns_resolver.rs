@@ -0,0 +1,76 @@
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use edge_dns::io::{Config, ErrorKind, Resolver, Upstream};
+use edge_dns::PORT;
+
+use edge_nal::{AddrType, Dns, DnsQuery, TcpConnect, UdpBind};
+
+use log::*;
+
+use rand::{thread_rng, RngCore};
+
+// Change this to the IP address of the DNS server you want to query
+const SERVER_IP: Ipv4Addr = Ipv4Addr::new(1, 1, 1, 1);
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let stack = edge_nal_std::Stack::new();
+
+    futures_lite::future::block_on(run(&stack, &stack)).unwrap();
+}
+
+async fn run<U, T>(udp: U, tcp: &T) -> Result<(), ErrorKind>
+where
+    U: UdpBind,
+    T: TcpConnect,
+{
+    // Use `Upstream::Tls` or `Upstream::Https` (with the `http` feature) instead, along with `Resolver::with_tls`,
+    // for querying the server over an encrypted connection
+    let servers = [Upstream::Plain(SocketAddr::new(
+        IpAddr::V4(SERVER_IP),
+        PORT,
+    ))];
+
+    let mut cache = [0; 2048];
+
+    let resolver = Resolver::new(
+        udp,
+        tcp,
+        &servers,
+        &mut cache,
+        |buf| thread_rng().fill_bytes(buf),
+        Config::new(),
+    );
+
+    for _ in 0..2 {
+        // The second lookup is answered from the cache
+        let addr = resolver
+            .get_host_by_name("github.com", AddrType::Either)
+            .await?;
+
+        info!("github.com: {addr}");
+    }
+
+    let mut result = [0; 1024];
+
+    for srv in resolver
+        .query_srv("_xmpp-client._tcp.jabber.org", &mut result)
+        .await?
+    {
+        info!(
+            "_xmpp-client._tcp.jabber.org: {}:{} (priority {}, weight {})",
+            srv.target, srv.port, srv.priority, srv.weight
+        );
+    }
+
+    for txt in resolver.query_txt("example.com", &mut result).await? {
+        for string in txt.txt() {
+            info!("example.com: {}", String::from_utf8_lossy(string));
+        }
+    }
+
+    Ok(())
+}
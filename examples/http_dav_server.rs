@@ -0,0 +1,144 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use edge_http::dav::Metadata;
+use edge_http::io::server::dav::{DavFilesystem, DavHandler};
+use edge_http::io::server::DefaultServer;
+use edge_nal::TcpBind;
+
+use log::info;
+
+use rand::{thread_rng, RngCore};
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let mut server = DefaultServer::new();
+
+    futures_lite::future::block_on(run(&mut server)).unwrap();
+}
+
+pub async fn run(server: &mut DefaultServer) -> Result<(), anyhow::Error> {
+    let addr = "0.0.0.0:8881";
+    let root = std::env::temp_dir().join("edge-dav");
+
+    fs::create_dir_all(&root)?;
+
+    info!(
+        "Serving {} over WebDAV at http://{addr}/dav",
+        root.display()
+    );
+
+    let acceptor = edge_nal_std::Stack::new()
+        .bind(addr.parse().unwrap())
+        .await?;
+
+    server
+        .run(
+            None,
+            acceptor,
+            DavHandler::new("/dav", StdFilesystem(root), |buf| {
+                thread_rng().fill_bytes(buf)
+            }),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// A `DavFilesystem` backed by a directory of the host
+struct StdFilesystem(PathBuf);
+
+impl StdFilesystem {
+    fn path(&self, path: &str) -> PathBuf {
+        self.0.join(path.trim_start_matches('/'))
+    }
+
+    fn metadata(metadata: fs::Metadata) -> Metadata {
+        Metadata {
+            dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs()),
+        }
+    }
+}
+
+impl DavFilesystem for StdFilesystem {
+    type Error = io::Error;
+
+    async fn metadata(&self, path: &str) -> Result<Option<Metadata>, Self::Error> {
+        match fs::metadata(self.path(path)) {
+            Ok(metadata) => Ok(Some(Self::metadata(metadata))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read_dir(
+        &self,
+        path: &str,
+        index: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<(usize, Metadata)>, Self::Error> {
+        let mut entries = fs::read_dir(self.path(path))?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let Some(entry) = entries.get(index) else {
+            return Ok(None);
+        };
+
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+
+        let buf = buf
+            .get_mut(..name.len())
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        buf.copy_from_slice(name.as_bytes());
+
+        Ok(Some((name.len(), Self::metadata(entry.metadata()?))))
+    }
+
+    async fn read(&self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut file = File::open(self.path(path))?;
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.read(buf)
+    }
+
+    async fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<(), Self::Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(offset == 0)
+            .open(self.path(path))?;
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(data)
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<(), Self::Error> {
+        fs::create_dir(self.path(path))
+    }
+
+    async fn remove(&self, path: &str) -> Result<(), Self::Error> {
+        let path = self.path(path);
+
+        if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), Self::Error> {
+        fs::rename(self.path(from), self.path(to))
+    }
+}
@@ -3,7 +3,7 @@ use core::fmt::{Debug, Display};
 use edge_http::io::server::{Connection, DefaultServer, Handler};
 use edge_http::io::Error;
 use edge_http::Method;
-use edge_nal::TcpBind;
+use edge_nal::{TcpBind, WriteVectored};
 
 use embedded_io_async::{Read, Write};
 
@@ -47,7 +47,7 @@ impl Handler for HttpHandler {
         conn: &mut Connection<'_, T, N>,
     ) -> Result<(), Self::Error<T::Error>>
     where
-        T: Read + Write,
+        T: Read + WriteVectored,
     {
         let headers = conn.headers()?;
 
@@ -0,0 +1,99 @@
+use core::net::SocketAddr;
+
+use edge_mqtt::client::{Client, Config, EmbassyClock, Error, Event, MemoryStore};
+use edge_mqtt::{Connect, QoS, PORT};
+
+use edge_nal::{AddrType, Dns, TcpConnect};
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+
+use log::*;
+
+const MQTT_HOST: &str = "broker.emqx.io";
+const MQTT_CLIENT_ID: &str = "edge-mqtt-native-demo";
+const MQTT_TOPIC: &str = "edge-mqtt-demo";
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let stack: edge_nal_std::Stack = Default::default();
+
+    let mut rx_buf = [0; 1024];
+    let mut tx_buf = [0; 1024];
+
+    futures_lite::future::block_on(run(&stack, &mut rx_buf, &mut tx_buf)).unwrap();
+}
+
+async fn run<T>(
+    stack: &T,
+    rx_buf: &mut [u8],
+    tx_buf: &mut [u8],
+) -> Result<(), Error<<T as TcpConnect>::Error>>
+where
+    T: TcpConnect + Dns,
+    <T as Dns>::Error: Into<<T as TcpConnect>::Error>,
+{
+    let ip = stack
+        .get_host_by_name(MQTT_HOST, AddrType::IPv4)
+        .await
+        .map_err(|e| Error::Io(e.into()))?;
+
+    let socket = stack
+        .connect(SocketAddr::new(ip, PORT))
+        .await
+        .map_err(Error::Io)?;
+
+    let mut client = Client::new(
+        EmbassyClock,
+        MemoryStore::<8, 256>::new(),
+        Config::new(),
+        rx_buf,
+        tx_buf,
+    );
+
+    let connect = Connect {
+        keep_alive_secs: 10,
+        ..Connect::new(MQTT_CLIENT_ID)
+    };
+
+    let session_present = client.connect(socket, &connect).await?;
+
+    info!("Connected to {MQTT_HOST}, session present: {session_present}");
+
+    client.subscribe(&[(MQTT_TOPIC, QoS::AtLeastOnce)]).await?;
+
+    let mut next_publish = Instant::now() + Duration::from_secs(1);
+
+    loop {
+        let publish = match select(client.next(), Timer::at(next_publish)).await {
+            Either::First(event) => {
+                match event? {
+                    Event::Received(message) => info!(
+                        "Received on \"{}\": {}",
+                        message.topic,
+                        String::from_utf8_lossy(message.payload)
+                    ),
+                    event => info!("Event: {event:?}"),
+                }
+
+                false
+            }
+            Either::Second(_) => true,
+        };
+
+        if publish {
+            let payload = "Hello from edge-mqtt-native-demo!";
+
+            let id = client
+                .publish(MQTT_TOPIC, QoS::AtLeastOnce, false, payload.as_bytes())
+                .await?;
+
+            info!("Published \"{payload}\" to topic \"{MQTT_TOPIC}\" as message {id}");
+
+            next_publish += Duration::from_secs(2);
+        }
+    }
+}
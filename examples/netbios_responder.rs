@@ -0,0 +1,47 @@
+use core::net::Ipv4Addr;
+use core::time::Duration;
+
+use edge_nal::UdpBind;
+
+use edge_netbios::io::{self, Error, DEFAULT_SOCKET};
+use edge_netbios::Host;
+
+use log::*;
+
+use rand::{thread_rng, RngCore};
+
+// Change this to the IP address of the machine where you'll run this example
+const OUR_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 100);
+
+const OUR_NAME: &str = "mypc";
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let stack = edge_nal_std::Stack::new();
+
+    let mut tx_buf = [0; 576];
+    let mut rx_buf = [0; 576];
+
+    futures_lite::future::block_on(run(&stack, &mut tx_buf, &mut rx_buf)).unwrap();
+}
+
+async fn run<T>(stack: &T, tx_buf: &mut [u8], rx_buf: &mut [u8]) -> Result<(), Error<T::Error>>
+where
+    T: UdpBind,
+{
+    info!("About to run a NetBIOS name service responder for our PC. On Windows, it will be addressable using {OUR_NAME}, so try to `ping {OUR_NAME}`. NOTE: Binding to port 137 usually requires elevated privileges.");
+
+    let host = Host {
+        name: OUR_NAME,
+        ip: OUR_IP,
+        ttl: Duration::from_secs(300),
+    };
+
+    io::run(stack, DEFAULT_SOCKET, tx_buf, rx_buf, &host, |buf| {
+        thread_rng().fill_bytes(buf)
+    })
+    .await
+}
@@ -0,0 +1,55 @@
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use std::time::{Duration, SystemTime};
+
+use edge_nal::UdpBind;
+
+use edge_sntp::io::{Client, Config, Error};
+use edge_sntp::PORT;
+
+use log::*;
+
+// Change this to the IP address of the (S)NTP server you want to query
+// (i.e. one of the servers of the `pool.ntp.org` project, as resolved by the DNS)
+const SERVER_IP: Ipv4Addr = Ipv4Addr::new(162, 159, 200, 1);
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let stack = edge_nal_std::Stack::new();
+
+    futures_lite::future::block_on(run(&stack)).unwrap();
+}
+
+async fn run<T>(stack: &T) -> Result<(), Error<T::Error>>
+where
+    T: UdpBind,
+{
+    let servers = [SocketAddr::new(IpAddr::V4(SERVER_IP), PORT)];
+
+    let config = Config {
+        poll_interval: embassy_time::Duration::from_secs(64),
+        ..Config::new()
+    };
+
+    let mut client = Client::new(stack, &servers, rand::thread_rng(), config);
+
+    info!("Synchronizing with {SERVER_IP} every ~64 seconds");
+
+    client
+        .run(|sample| {
+            let local = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or(Duration::ZERO);
+
+            info!(
+                "Time: {}s since the Unix epoch (stratum {}); the local clock is off by {}us",
+                sample.time.as_secs(),
+                sample.stratum,
+                sample.offset_micros(local)
+            );
+        })
+        .await
+}
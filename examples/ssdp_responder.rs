@@ -0,0 +1,158 @@
+use core::fmt::{Debug, Display};
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use edge_http::io::server::{Connection, DefaultServer, Handler};
+use edge_http::io::Error;
+use edge_http::Method;
+use edge_nal::{TcpBind, UdpSplit, WriteVectored};
+use edge_ssdp::host::{Description, Device, DEFAULT_MAX_AGE};
+use edge_ssdp::io::{self, DEFAULT_SOCKET};
+use edge_ssdp::AdvertisementsSsdpHandler;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::signal::Signal;
+
+use embedded_io_async::{Read, Write};
+
+use log::*;
+
+use rand::{thread_rng, RngCore};
+
+// Change this to the IP address of the machine where you'll run this example
+const OUR_IP: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
+
+// The port of the HTTP server serving the UPnP description of the device
+const HTTP_PORT: u16 = 8882;
+
+// Generate your own, and keep it the same across reboots
+const OUR_UUID: &str = "5d5f3b0a-6c5e-4c1a-9a7e-3c1b2f0e8d41";
+
+fn main() {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+    );
+
+    let stack = edge_nal_std::Stack::new();
+
+    let mut server = DefaultServer::new();
+
+    futures_lite::future::block_on(run(&stack, &mut server)).unwrap();
+}
+
+async fn run(stack: &edge_nal_std::Stack, server: &mut DefaultServer) -> Result<(), anyhow::Error> {
+    let location = format!("http://{OUR_IP}:{HTTP_PORT}/description.xml");
+    let presentation_url = format!("http://{OUR_IP}:{HTTP_PORT}/");
+
+    let device = Device {
+        uuid: OUR_UUID,
+        device_type: "urn:schemas-upnp-org:device:Basic:1",
+        services: &[],
+        location: &location,
+        server: "Linux/6.0 UPnP/1.0 edge-ssdp/0.4",
+        max_age: DEFAULT_MAX_AGE,
+    };
+
+    let description = Description {
+        friendly_name: "edge-net SSDP example",
+        manufacturer: "edge-net",
+        manufacturer_url: Some("https://github.com/ivmarkov/edge-net"),
+        model_description: None,
+        model_name: "SSDP responder",
+        model_number: None,
+        serial_number: None,
+        presentation_url: Some(&presentation_url),
+    };
+
+    info!("About to advertise our device via SSDP. It should show up in the \"Network\" folder of Windows, and its description is served at {location}");
+
+    let mut socket = io::bind(stack, DEFAULT_SOCKET, Some(Ipv4Addr::UNSPECIFIED), Some(0)).await?;
+
+    let (recv, send) = socket.split();
+
+    // A way to notify the SSDP responder that the data in `Device` had changed
+    // We don't use it in this example, because the data is hard-coded
+    let signal = Signal::new();
+
+    let ssdp = io::Ssdp::<NoopRawMutex, _, _, _, _>::new(
+        Some(Ipv4Addr::UNSPECIFIED),
+        Some(0),
+        recv,
+        send,
+        [0; 1500],
+        [0; 1500],
+        |buf| thread_rng().fill_bytes(buf),
+        &signal,
+    );
+
+    let acceptor = TcpBind::bind(
+        stack,
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), HTTP_PORT),
+    )
+    .await?;
+
+    let handler = DescriptionHandler {
+        device: &device,
+        description: &description,
+    };
+
+    match select(
+        ssdp.run(AdvertisementsSsdpHandler::new(&device)),
+        server.run(None, acceptor, handler),
+    )
+    .await
+    {
+        Either::First(result) => result?,
+        Either::Second(result) => result?,
+    }
+
+    Ok(())
+}
+
+struct DescriptionHandler<'a> {
+    device: &'a Device<'a>,
+    description: &'a Description<'a>,
+}
+
+impl Handler for DescriptionHandler<'_> {
+    type Error<E>
+        = Error<E>
+    where
+        E: Debug;
+
+    async fn handle<T, const N: usize>(
+        &self,
+        _task_id: impl Display + Copy,
+        conn: &mut Connection<'_, T, N>,
+    ) -> Result<(), Self::Error<T::Error>>
+    where
+        T: Read + WriteVectored,
+    {
+        let headers = conn.headers()?;
+
+        if headers.method != Method::Get {
+            conn.initiate_response(405, Some("Method Not Allowed"), &[])
+                .await?;
+        } else if headers.path == "/description.xml" {
+            let mut xml = String::new();
+            self.device
+                .write_description(self.description, &mut xml)
+                .unwrap();
+
+            conn.initiate_response(200, Some("OK"), &[("Content-Type", "text/xml")])
+                .await?;
+
+            conn.write_all(xml.as_bytes()).await?;
+        } else if headers.path == "/" {
+            conn.initiate_response(200, Some("OK"), &[("Content-Type", "text/plain")])
+                .await?;
+
+            conn.write_all(b"Hello from the SSDP example device!")
+                .await?;
+        } else {
+            conn.initiate_response(404, Some("Not Found"), &[]).await?;
+        }
+
+        Ok(())
+    }
+}
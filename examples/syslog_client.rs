@@ -0,0 +1,90 @@
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::time::Duration;
+
+use std::time::SystemTime;
+
+use edge_nal::UdpBind;
+
+use edge_syslog::io::logger::SyslogLogger;
+use edge_syslog::io::{Client, Error, UdpSender};
+use edge_syslog::{Header, Message, Severity, StructuredData, PORT};
+
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::Timer;
+
+use log::*;
+
+// Change this to the IP address of your syslog collector, i.e. `rsyslog` with UDP reception enabled
+const COLLECTOR_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
+
+const HEADER: Header = Header::new("mydevice", "syslog_client");
+
+// Ships the records of the `log` crate to the collector
+static LOGGER: SyslogLogger<CriticalSectionRawMutex> =
+    SyslogLogger::new(HEADER, LevelFilter::Info, now);
+
+fn main() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    let stack = edge_nal_std::Stack::new();
+
+    futures_lite::future::block_on(run(&stack)).unwrap();
+}
+
+async fn run<T>(stack: &T) -> Result<(), Error<T::Error>>
+where
+    T: UdpBind,
+{
+    let collector = SocketAddr::new(IpAddr::V4(COLLECTOR_IP), PORT);
+    let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+    println!("Sending syslog messages to {collector}");
+
+    // Messages can be sent with a client directly, i.e. when they carry structured data
+    let socket = stack.bind(local).await.map_err(Error::Io)?;
+
+    let mut client = Client::new(UdpSender::new(socket, collector), HEADER);
+
+    let mut buf = [0; 480];
+
+    client
+        .send(
+            &Message {
+                timestamp: now(),
+                structured_data: &[StructuredData {
+                    id: "meta",
+                    params: &[("sequenceId", "1"), ("language", "en")],
+                }],
+                ..Message::new(Severity::Notice, &"Device started")
+            },
+            &mut buf,
+        )
+        .await?;
+
+    // ... or as log records, queued by the logger and sent by its `run` future
+    let socket = stack.bind(local).await.map_err(Error::Io)?;
+
+    let log = async {
+        for tick in 0.. {
+            info!("Tick {tick}");
+
+            if tick % 5 == 0 {
+                warn!("Tick {tick} is a multiple of 5");
+            }
+
+            Timer::after_secs(1).await;
+        }
+    };
+
+    select(LOGGER.run(UdpSender::new(socket, collector)), log).await;
+
+    Ok(())
+}
+
+fn now() -> Option<Duration> {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+}
@@ -4,7 +4,7 @@ use edge_http::io::server::{Connection, DefaultServer, Handler};
 use edge_http::io::Error;
 use edge_http::ws::MAX_BASE64_KEY_RESPONSE_LEN;
 use edge_http::Method;
-use edge_nal::TcpBind;
+use edge_nal::{TcpBind, WriteVectored};
 use edge_ws::{FrameHeader, FrameType};
 
 use embedded_io_async::{Read, Write};
@@ -61,7 +61,7 @@ impl Handler for WsHandler {
         conn: &mut Connection<'_, T, N>,
     ) -> Result<(), Self::Error<T::Error>>
     where
-        T: Read + Write,
+        T: Read + WriteVectored,
     {
         let headers = conn.headers()?;
 
@@ -2,10 +2,11 @@
 #![allow(async_fn_in_trait)]
 
 pub use edge_captive as captive;
+pub use edge_coap as coap;
 pub use edge_dhcp as dhcp;
+pub use edge_dns as dns;
 pub use edge_http as http;
 pub use edge_mdns as mdns;
-#[cfg(feature = "std")]
 pub use edge_mqtt as mqtt;
 #[cfg(feature = "io")]
 pub use edge_nal as nal;
@@ -13,5 +14,9 @@ pub use edge_nal as nal;
 pub use edge_nal_embassy as embassy;
 #[cfg(feature = "std")]
 pub use edge_nal_std as std;
+pub use edge_netbios as netbios;
 pub use edge_raw as raw;
+pub use edge_sntp as sntp;
+pub use edge_ssdp as ssdp;
+pub use edge_syslog as syslog;
 pub use edge_ws as ws;